@@ -0,0 +1,54 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use PMXUtil::reader::{read_model_parallel, ModelInfoStage};
+use PMXUtil::types::{Face, Model, Vertex, VertexWeight};
+use PMXUtil::writer::Writer;
+
+const VERTICES: usize = 1_000_000;
+
+fn large_model() -> Vec<u8> {
+    let vertices: Vec<Vertex> = (0..VERTICES)
+        .map(|i| Vertex {
+            position: [i as f32, 0.0, 0.0],
+            norm: [0.0, 1.0, 0.0],
+            uv: [0.0, 0.0],
+            add_uv: [[0.0; 4]; 4],
+            weight_type: if i % 2 == 0 {
+                VertexWeight::BDEF1(0)
+            } else {
+                VertexWeight::BDEF2 {
+                    bone_index_1: 0,
+                    bone_index_2: 0,
+                    bone_weight_1: 0.5,
+                }
+            },
+            edge_mag: 1.0,
+        })
+        .collect();
+    let faces: Vec<Face> = (0..VERTICES as u32 - 2)
+        .map(|i| Face {
+            vertices: [i, i + 1, i + 2],
+        })
+        .collect();
+    let model = Model {
+        vertices,
+        faces,
+        ..Model::default()
+    };
+    Writer::from(model).write_to_vec().unwrap()
+}
+
+fn read(c: &mut Criterion) {
+    let buf = large_model();
+    let mut group = c.benchmark_group("read_1m_vertices");
+    group.sample_size(20);
+    group.bench_function("sequential", |b| {
+        b.iter(|| ModelInfoStage::from_reader(&buf[..]).unwrap().read_model())
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| read_model_parallel(&buf).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, read);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use PMXUtil::types::{Face, Model, Vertex, VertexWeight};
+use PMXUtil::writer::Writer;
+
+const VERTICES: usize = 1_000_000;
+
+fn large_model() -> Model {
+    let vertices: Vec<Vertex> = (0..VERTICES)
+        .map(|i| Vertex {
+            position: [i as f32, 0.0, 0.0],
+            norm: [0.0, 1.0, 0.0],
+            uv: [0.0, 0.0],
+            add_uv: [[0.0; 4]; 4],
+            weight_type: VertexWeight::BDEF4 {
+                bone_index_1: 0,
+                bone_index_2: 0,
+                bone_index_3: 0,
+                bone_index_4: 0,
+                bone_weight_1: 0.25,
+                bone_weight_2: 0.25,
+                bone_weight_3: 0.25,
+                bone_weight_4: 0.25,
+            },
+            edge_mag: 1.0,
+        })
+        .collect();
+    let faces: Vec<Face> = (0..VERTICES as u32 - 2)
+        .map(|i| Face {
+            vertices: [i, i + 1, i + 2],
+        })
+        .collect();
+    Model {
+        vertices,
+        faces,
+        ..Model::default()
+    }
+}
+
+fn write(c: &mut Criterion) {
+    let model = large_model();
+    let mut group = c.benchmark_group("write_1m_vertices");
+    group.sample_size(20);
+    for parallel in [false, true] {
+        let name = if parallel { "parallel" } else { "sequential" };
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || {
+                    let mut writer = Writer::from(model.clone());
+                    writer.set_parallel(parallel);
+                    writer
+                },
+                |writer| writer.write_to_vec().unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, write);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::ops::ControlFlow;
+use PMXUtil::reader::{ModelInfoStage, ParseOptions};
+use PMXUtil::types::{Face, ModelInfo, Vertex, VertexWeight};
+use PMXUtil::writer::Writer;
+
+const VERTICES: usize = 200_000;
+
+fn large_model() -> Vec<u8> {
+    let vertices: Vec<Vertex> = (0..VERTICES)
+        .map(|i| Vertex {
+            position: [i as f32, 0.0, 0.0],
+            norm: [0.0, 1.0, 0.0],
+            uv: [0.0, 0.0],
+            add_uv: [[0.0; 4]; 4],
+            weight_type: VertexWeight::BDEF1(0),
+            edge_mag: 1.0,
+        })
+        .collect();
+    let faces: Vec<Face> = (0..VERTICES as u32 - 2)
+        .map(|i| Face {
+            vertices: [i, i + 1, i + 2],
+        })
+        .collect();
+    let mut writer = Writer::begin_writer(false);
+    writer.set_mmd_compat(false);
+    writer.set_model_info(&ModelInfo::default());
+    writer.add_vertices(&vertices);
+    writer.add_faces(&faces);
+    let mut buf = vec![];
+    writer.write(&mut buf).unwrap();
+    buf
+}
+
+fn read(c: &mut Criterion) {
+    let buf = large_model();
+    c.bench_function("read_model", |b| {
+        b.iter(|| ModelInfoStage::from_reader(&buf[..]).unwrap().read_model())
+    });
+    c.bench_function("read_model_with_progress", |b| {
+        b.iter(|| {
+            let options = ParseOptions::default().progress(|_| ControlFlow::Continue(()));
+            ModelInfoStage::from_reader(&buf[..])
+                .unwrap()
+                .read_model_with(options)
+                .unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, read);
+criterion_main!(benches);
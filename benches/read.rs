@@ -0,0 +1,60 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use PMXUtil::reader::ModelInfoStage;
+use PMXUtil::types::{Face, Material, ModelInfo, Vertex, VertexWeight};
+use PMXUtil::writer::Writer;
+
+const VERTICES: usize = 200_000;
+const MATERIALS: usize = 10_000;
+
+/// vertices with 2 additional uvs and BDEF4 weights, and many materials.
+fn large_model() -> Vec<u8> {
+    let vertices: Vec<Vertex> = (0..VERTICES)
+        .map(|i| Vertex {
+            position: [i as f32, 0.0, 0.0],
+            norm: [0.0, 1.0, 0.0],
+            uv: [0.0, 0.0],
+            add_uv: [[1.0; 4], [2.0; 4], [0.0; 4], [0.0; 4]],
+            weight_type: VertexWeight::BDEF4 {
+                bone_index_1: 0,
+                bone_index_2: 0,
+                bone_index_3: 0,
+                bone_index_4: 0,
+                bone_weight_1: 0.25,
+                bone_weight_2: 0.25,
+                bone_weight_3: 0.25,
+                bone_weight_4: 0.25,
+            },
+            edge_mag: 1.0,
+        })
+        .collect();
+    let faces: Vec<Face> = (0..MATERIALS as u32)
+        .map(|i| Face {
+            vertices: [i, i + 1, i + 2],
+        })
+        .collect();
+    let materials: Vec<Material> = (0..MATERIALS)
+        .map(|i| {
+            Material::builder(&format!("材質{}", i))
+                .num_face_vertices(3)
+                .build()
+        })
+        .collect();
+    let mut writer = Writer::begin_writer(false);
+    writer.set_mmd_compat(false);
+    writer.set_model_info(&ModelInfo::default());
+    writer.set_additional_uv(2).unwrap();
+    writer.add_vertices(&vertices);
+    writer.add_faces(&faces);
+    writer.add_materials(&materials);
+    writer.write_to_vec().unwrap()
+}
+
+fn read(c: &mut Criterion) {
+    let buf = large_model();
+    c.bench_function("read_vertices_and_materials", |b| {
+        b.iter(|| ModelInfoStage::from_reader(&buf[..]).unwrap().read_model())
+    });
+}
+
+criterion_group!(benches, read);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use PMXUtil::reader::ModelInfoStage;
+use PMXUtil::types::{Encode, Material, Model, ModelInfo};
+use PMXUtil::writer::Writer;
+
+const MATERIALS: usize = 10_000;
+
+/// long japanese comment and memos like models with detailed terms of use.
+fn comment_heavy_model(encode: Encode) -> Model {
+    let comment = "利用規約：改変可、再配布不可。クレジット表記をお願いします。\n".repeat(2_000);
+    let materials = (0..MATERIALS)
+        .map(|i| {
+            let mut material = Material::builder(&format!("材質{}", i)).build();
+            material.memo = "スフィアは加算、トゥーンは共有のものを使用しています。".repeat(8);
+            material
+        })
+        .collect();
+    Model {
+        encode,
+        model_info: ModelInfo {
+            name: "モデル".to_owned(),
+            name_en: "model".to_owned(),
+            comment_en: "terms of use\n".repeat(2_000),
+            comment,
+        },
+        materials,
+        ..Model::default()
+    }
+}
+
+fn text(c: &mut Criterion) {
+    for (encode, name) in [(Encode::Utf16Le, "utf16"), (Encode::UTF8, "utf8")] {
+        let model = comment_heavy_model(encode);
+        let writer = || {
+            let mut writer = Writer::from(model.clone());
+            writer.set_mmd_compat(false);
+            writer
+        };
+        let buf = writer().write_to_vec().unwrap();
+        c.bench_function(&format!("read_texts_{}", name), |b| {
+            b.iter(|| ModelInfoStage::from_reader(&buf[..]).unwrap().read_model())
+        });
+        c.bench_function(&format!("write_texts_{}", name), |b| {
+            b.iter_batched(
+                writer,
+                |writer| writer.write_to_vec().unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+}
+
+criterion_group!(benches, text);
+criterion_main!(benches);
@@ -0,0 +1,56 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use std::fs::File;
+use PMXUtil::types::{Face, Model, Vertex, VertexWeight};
+use PMXUtil::writer::Writer;
+
+const VERTICES: usize = 200_000;
+
+fn large_model() -> Model {
+    let vertices: Vec<Vertex> = (0..VERTICES)
+        .map(|i| Vertex {
+            position: [i as f32, 0.0, 0.0],
+            norm: [0.0, 1.0, 0.0],
+            uv: [0.0, 0.0],
+            add_uv: [[0.0; 4]; 4],
+            weight_type: VertexWeight::BDEF2 {
+                bone_index_1: 0,
+                bone_index_2: 0,
+                bone_weight_1: 0.5,
+            },
+            edge_mag: 1.0,
+        })
+        .collect();
+    let faces: Vec<Face> = (0..VERTICES as u32 - 2)
+        .map(|i| Face {
+            vertices: [i, i + 1, i + 2],
+        })
+        .collect();
+    Model {
+        vertices,
+        faces,
+        ..Model::default()
+    }
+}
+
+fn write(c: &mut Criterion) {
+    let model = large_model();
+    let path = std::env::temp_dir().join("pmxutil_write_bench.pmx");
+    c.bench_function("write_to_vec", |b| {
+        b.iter_batched(
+            || Writer::from(model.clone()),
+            |writer| writer.write_to_vec().unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    c.bench_function("write_to_file", |b| {
+        b.iter_batched(
+            || Writer::from(model.clone()),
+            |writer| writer.write(File::create(&path).unwrap()).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    let _ = std::fs::remove_file(&path);
+}
+
+criterion_group!(benches, write);
+criterion_main!(benches);
@@ -0,0 +1,126 @@
+//! read a PMX file and write it again, optionally converting it.
+//!
+//! ```sh
+//! cargo run --example pmx_copy -- [--utf8 | --utf16] [--force-2.0] input.pmx output.pmx
+//! ```
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::process::exit;
+use PMXUtil::convert::{transcode, TranscodeOptions};
+use PMXUtil::types::{Encode, PMXVersion};
+
+const USAGE: &str = "usage: pmx_copy [--utf8 | --utf16] [--force-2.0] <input.pmx> <output.pmx>";
+
+/// parsed command line.
+#[derive(Debug)]
+struct Args {
+    input: OsString,
+    output: OsString,
+    options: TranscodeOptions,
+}
+
+fn parse_args(args: impl IntoIterator<Item = OsString>) -> Result<Args, String> {
+    let mut options = TranscodeOptions::default();
+    let mut paths = vec![];
+    for arg in args {
+        match arg.to_str() {
+            Some("--utf8") => options.encode = Some(Encode::UTF8),
+            Some("--utf16") => options.encode = Some(Encode::Utf16Le),
+            Some("--force-2.0") => options.force_version = Some(PMXVersion::V20),
+            Some(flag) if flag.starts_with("--") => {
+                return Err(format!("unknown option {}", flag));
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        return Err("expected input and output paths".to_owned());
+    }
+    let output = paths.pop().unwrap();
+    let input = paths.pop().unwrap();
+    Ok(Args {
+        input,
+        output,
+        options,
+    })
+}
+
+fn main() {
+    let args = match parse_args(std::env::args_os().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            exit(2);
+        }
+    };
+    let input = match File::open(&args.input) {
+        Ok(file) => BufReader::new(file),
+        Err(err) => {
+            eprintln!("{}: {}", args.input.to_string_lossy(), err);
+            exit(1);
+        }
+    };
+    let output = match File::create(&args.output) {
+        Ok(file) => BufWriter::new(file),
+        Err(err) => {
+            eprintln!("{}: {}", args.output.to_string_lossy(), err);
+            exit(1);
+        }
+    };
+    match transcode(input, output, args.options) {
+        Ok(report) => println!("{}", report),
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_args;
+    use std::ffi::OsString;
+    use PMXUtil::convert::transcode;
+    use PMXUtil::reader::ModelInfoStage;
+    use PMXUtil::types::{Encode, Model, ModelInfo, PMXVersion};
+    use PMXUtil::writer::Writer;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn flags() {
+        let parsed = parse_args(args(&["--utf8", "a.pmx", "--force-2.0", "b.pmx"])).unwrap();
+        assert_eq!(parsed.input, "a.pmx");
+        assert_eq!(parsed.output, "b.pmx");
+        assert_eq!(parsed.options.encode, Some(Encode::UTF8));
+        assert_eq!(parsed.options.force_version, Some(PMXVersion::V20));
+        assert!(parse_args(args(&["a.pmx"])).is_err());
+        assert!(parse_args(args(&["--utf32", "a.pmx", "b.pmx"])).is_err());
+    }
+
+    #[test]
+    fn copy_converted() {
+        let model = Model {
+            model_info: ModelInfo {
+                name: "コピー".to_owned(),
+                ..ModelInfo::default()
+            },
+            bones: PMXUtil::builder::standard_humanoid_skeleton(),
+            ..Model::default()
+        };
+        let input = Writer::from_model(&model).write_to_vec().unwrap();
+        let parsed = parse_args(args(&["--utf8", "--force-2.0", "in", "out"])).unwrap();
+        let mut output = vec![];
+        let report = transcode(input.as_slice(), &mut output, parsed.options).unwrap();
+        assert_eq!(report.new_encode, Encode::UTF8);
+        let stage = ModelInfoStage::from_reader(output.as_slice()).unwrap();
+        assert_eq!(stage.encode(), Encode::UTF8);
+        assert_eq!(stage.version(), PMXVersion::V20);
+        let copy = stage.read_model();
+        assert_eq!(copy.model_info, model.model_info);
+        assert_eq!(copy.bones, model.bones);
+    }
+}
@@ -0,0 +1,123 @@
+//! print summary of a PMX file.
+//!
+//! ```sh
+//! cargo run --example pmx_info -- model.pmx
+//! ```
+use std::fmt::Write;
+use std::fs::File;
+use std::io::BufReader;
+use std::process::exit;
+use PMXUtil::reader::ModelInfoStage;
+use PMXUtil::stats::analyze;
+use PMXUtil::types::{Header, Model};
+
+fn main() {
+    let path = match std::env::args_os().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: pmx_info <model.pmx>");
+            exit(2);
+        }
+    };
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("{}: {}", path.to_string_lossy(), err);
+            exit(1);
+        }
+    };
+    let stage = match ModelInfoStage::from_reader(BufReader::new(file)) {
+        Some(stage) => stage,
+        None => {
+            eprintln!("{}: not a PMX file", path.to_string_lossy());
+            exit(1);
+        }
+    };
+    let header = stage.get_header();
+    print!("{}", summary(&header, &stage.read_model()));
+}
+
+/// name, comment, header and counts followed by faces of each material.
+fn summary(header: &Header, model: &Model) -> String {
+    let info = &model.model_info;
+    let mut out = String::new();
+    // writing to String never fails.
+    writeln!(out, "name: {}", info.name).unwrap();
+    writeln!(out, "name (en): {}", info.name_en).unwrap();
+    writeln!(out, "comment:").unwrap();
+    for line in info.comment.lines() {
+        writeln!(out, "  {}", line).unwrap();
+    }
+    writeln!(
+        out,
+        "encoding: {} additional uv: {}",
+        header.encode, header.additional_uv
+    )
+    .unwrap();
+    write!(out, "{}", analyze(model)).unwrap();
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    writeln!(out, "materials:").unwrap();
+    for (index, material) in model.materials.iter().enumerate() {
+        writeln!(
+            out,
+            "  {:>3} {} faces: {}",
+            index,
+            material.name,
+            material.num_face_vertices / 3
+        )
+        .unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::summary;
+    use PMXUtil::reader::ModelInfoStage;
+    use PMXUtil::types::{Face, Material, Model, ModelInfo};
+    use PMXUtil::writer::Writer;
+
+    #[test]
+    fn summary_of_written_model() {
+        let model = Model {
+            model_info: ModelInfo {
+                name: "テスト".to_owned(),
+                name_en: "test".to_owned(),
+                comment: "line 1\r\nline 2".to_owned(),
+                comment_en: String::new(),
+            },
+            faces: vec![
+                Face {
+                    vertices: [0, 0, 0]
+                };
+                3
+            ],
+            materials: vec![
+                Material::builder("body").num_face_vertices(6).build(),
+                Material::builder("hair").num_face_vertices(3).build(),
+            ],
+            ..Model::default()
+        };
+        let mut writer = Writer::from_model(&model);
+        writer.set_version(PMXUtil::types::PMXVersion::V20);
+        let bytes = writer.write_to_vec().unwrap();
+        let stage = ModelInfoStage::from_reader(bytes.as_slice()).unwrap();
+        let header = stage.get_header();
+        let text = summary(&header, &stage.read_model());
+        assert!(
+            text.starts_with("name: テスト\nname (en): test\n"),
+            "{}",
+            text
+        );
+        assert!(text.contains("  line 2\n"), "{}", text);
+        assert!(text.contains("encoding: UTF-16LE"), "{}", text);
+        assert!(text.contains("PMX 2.0"), "{}", text);
+        assert!(
+            text.ends_with("    0 body faces: 2\n    1 hair faces: 1\n"),
+            "{}",
+            text
+        );
+    }
+}
@@ -0,0 +1,41 @@
+//! print time spent on each section.
+//!
+//! ```sh
+//! cargo run --example trace_sections --features tracing -- model.pmx
+//! ```
+//!
+//! without argument a small model built in memory is written and read.
+use tracing_subscriber::fmt::format::FmtSpan;
+use PMXUtil::builder::standard_humanoid_skeleton;
+use PMXUtil::reader::ModelInfoStage;
+use PMXUtil::types::Model;
+use PMXUtil::writer::Writer;
+
+fn main() {
+    // `close` events of spans carry `time.busy` and `time.idle`.
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_span_events(FmtSpan::CLOSE)
+        .init();
+
+    let bytes = match std::env::args_os().nth(1) {
+        Some(path) => std::fs::read(path).expect("failed to read file"),
+        None => {
+            let model = Model {
+                bones: standard_humanoid_skeleton(),
+                ..Model::default()
+            };
+            Writer::from_model(&model)
+                .write_to_vec()
+                .expect("failed to write model")
+        }
+    };
+    let model = ModelInfoStage::from_reader(bytes.as_slice())
+        .expect("not a PMX file")
+        .read_model();
+    println!(
+        "read {} vertices and {} bones",
+        model.vertices.len(),
+        model.bones.len()
+    );
+}
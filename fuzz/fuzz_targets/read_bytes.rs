@@ -0,0 +1,13 @@
+//! feed random bytes to the reader.
+//!
+//! reader still panics on invalid input so every crash found here is
+//! a place which should return an error instead.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use PMXUtil::reader::ModelInfoStage;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(reader) = ModelInfoStage::from_reader(data) {
+        let _ = reader.read_model();
+    }
+});
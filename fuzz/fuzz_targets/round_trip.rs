@@ -0,0 +1,21 @@
+//! write -> read -> write must produce the same bytes.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use PMXUtil::reader::ModelInfoStage;
+use PMXUtil::types::Model;
+use PMXUtil::writer::Writer;
+
+fn write(model: &Model) -> Vec<u8> {
+    let mut buf = vec![];
+    let mut writer = Writer::from_model(model);
+    writer.set_mmd_compat(false);
+    writer.write(&mut buf).unwrap();
+    buf
+}
+
+fuzz_target!(|model: Model| {
+    let buf = write(&model);
+    let model_cpy = ModelInfoStage::from_reader(&buf[..]).unwrap().read_model();
+    assert_eq!(model, model_cpy);
+    assert_eq!(buf, write(&model_cpy));
+});
@@ -0,0 +1,320 @@
+#ifndef PMXUTIL_H
+#define PMXUTIL_H
+
+/* generated by cbindgen. do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * result of fallible functions.
+ */
+typedef enum PmxError {
+  PMX_ERROR_OK = 0,
+  /**
+   * required pointer argument was null.
+   */
+  PMX_ERROR_NULL_POINTER = 1,
+  /**
+   * magic number, version or encoding is invalid.
+   */
+  PMX_ERROR_INVALID_HEADER = 2,
+  /**
+   * the file is truncated or broken.
+   */
+  PMX_ERROR_INVALID_DATA = 3,
+  /**
+   * index is out of range.
+   */
+  PMX_ERROR_OUT_OF_RANGE = 4,
+} PmxError;
+
+/**
+ * opaque handle of a parsed model.
+ */
+typedef struct PmxModel PmxModel;
+
+/**
+ * material parameters except strings.
+ *
+ * use [`pmxutil_material_name`] for the name.
+ */
+typedef struct PmxMaterial {
+  float diffuse[4];
+  float specular[3];
+  float specular_factor;
+  float ambient[3];
+  /**
+   * bits of [`MaterialFlags`](crate::types::MaterialFlags)
+   */
+  uint8_t draw_mode;
+  float edge_color[4];
+  float edge_size;
+  /**
+   * `-1` if none.
+   */
+  int32_t texture_index;
+  /**
+   * `-1` if none.
+   */
+  int32_t sphere_texture_index;
+  /**
+   * 0: none 1: multiply 2: add 3: sub texture
+   */
+  uint8_t sphere_mode;
+  /**
+   * 0: `toon_index` refers texture list 1: `toon_index` is common toon 0 ~ 9
+   */
+  uint8_t toon_is_common;
+  int32_t toon_index;
+  /**
+   * count of face vertices (3 per triangle) using this material.
+   */
+  int32_t num_face_vertices;
+} PmxMaterial;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * parse PMX in `ptr[0..len]`.
+ *
+ * on success writes a new handle to `out_handle`.
+ * release it with [`pmxutil_free`].
+ *
+ * # Safety
+ * * `ptr` must be valid for reads of `len` bytes.
+ * * `out_handle` must be valid for a write of a pointer.
+ */
+enum PmxError pmxutil_load_from_memory(const uint8_t *ptr,
+                                       size_t len,
+                                       struct PmxModel **out_handle);
+
+/**
+ * release `handle` returned by [`pmxutil_load_from_memory`].
+ *
+ * strings got from `handle` are invalid after this. null is ignored.
+ *
+ * # Safety
+ * `handle` must be null or a handle not freed yet.
+ */
+void pmxutil_free(struct PmxModel *handle);
+
+/**
+ * vertex count. `0` if `handle` is null.
+ *
+ * # Safety
+ * `handle` must be null or a live handle.
+ */
+size_t pmxutil_vertex_count(const struct PmxModel *handle);
+
+/**
+ * face count. `0` if `handle` is null.
+ *
+ * # Safety
+ * `handle` must be null or a live handle.
+ */
+size_t pmxutil_face_count(const struct PmxModel *handle);
+
+/**
+ * texture count. `0` if `handle` is null.
+ *
+ * # Safety
+ * `handle` must be null or a live handle.
+ */
+size_t pmxutil_texture_count(const struct PmxModel *handle);
+
+/**
+ * material count. `0` if `handle` is null.
+ *
+ * # Safety
+ * `handle` must be null or a live handle.
+ */
+size_t pmxutil_material_count(const struct PmxModel *handle);
+
+/**
+ * bone count. `0` if `handle` is null.
+ *
+ * # Safety
+ * `handle` must be null or a live handle.
+ */
+size_t pmxutil_bone_count(const struct PmxModel *handle);
+
+/**
+ * morph count. `0` if `handle` is null.
+ *
+ * # Safety
+ * `handle` must be null or a live handle.
+ */
+size_t pmxutil_morph_count(const struct PmxModel *handle);
+
+/**
+ * rigid body count. `0` if `handle` is null.
+ *
+ * # Safety
+ * `handle` must be null or a live handle.
+ */
+size_t pmxutil_rigid_body_count(const struct PmxModel *handle);
+
+/**
+ * joint count. `0` if `handle` is null.
+ *
+ * # Safety
+ * `handle` must be null or a live handle.
+ */
+size_t pmxutil_joint_count(const struct PmxModel *handle);
+
+/**
+ * copy vertex positions as `x y z` to `out_ptr`.
+ *
+ * returns copied vertex count which is at most `cap`.
+ *
+ * # Safety
+ * * `handle` must be null or a live handle.
+ * * `out_ptr` must be null or valid for writes of `cap * 3` floats.
+ */
+size_t pmxutil_copy_positions(const struct PmxModel *handle, float *out_ptr, size_t cap);
+
+/**
+ * copy vertex normals as `x y z` to `out_ptr`.
+ *
+ * returns copied vertex count which is at most `cap`.
+ *
+ * # Safety
+ * * `handle` must be null or a live handle.
+ * * `out_ptr` must be null or valid for writes of `cap * 3` floats.
+ */
+size_t pmxutil_copy_normals(const struct PmxModel *handle, float *out_ptr, size_t cap);
+
+/**
+ * copy vertex uvs as `u v` to `out_ptr`.
+ *
+ * returns copied vertex count which is at most `cap`.
+ *
+ * # Safety
+ * * `handle` must be null or a live handle.
+ * * `out_ptr` must be null or valid for writes of `cap * 2` floats.
+ */
+size_t pmxutil_copy_uvs(const struct PmxModel *handle, float *out_ptr, size_t cap);
+
+/**
+ * copy vertex indices of faces to `out_ptr`. 3 indices per face.
+ *
+ * returns copied face count which is at most `cap`.
+ *
+ * # Safety
+ * * `handle` must be null or a live handle.
+ * * `out_ptr` must be null or valid for writes of `cap * 3` integers.
+ */
+size_t pmxutil_copy_indices(const struct PmxModel *handle, uint32_t *out_ptr, size_t cap);
+
+/**
+ * write material at `index` to `out_struct`.
+ *
+ * # Safety
+ * * `handle` must be null or a live handle.
+ * * `out_struct` must be null or valid for a write of [`PmxMaterial`].
+ */
+enum PmxError pmxutil_material_get(const struct PmxModel *handle,
+                                   size_t index,
+                                   struct PmxMaterial *out_struct);
+
+/**
+ * model name in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+ *
+ * writes byte length to `out_len`. not NUL terminated.
+ *
+ * # Safety
+ * * `handle` must be null or a live handle.
+ * * `out_len` must be null or valid for a write of `size_t`.
+ */
+const uint8_t *pmxutil_model_name(const struct PmxModel *handle, size_t *out_len);
+
+/**
+ * model name in english in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+ *
+ * writes byte length to `out_len`. not NUL terminated.
+ *
+ * # Safety
+ * * `handle` must be null or a live handle.
+ * * `out_len` must be null or valid for a write of `size_t`.
+ */
+const uint8_t *pmxutil_model_name_en(const struct PmxModel *handle, size_t *out_len);
+
+/**
+ * model comment in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+ *
+ * writes byte length to `out_len`. not NUL terminated.
+ *
+ * # Safety
+ * * `handle` must be null or a live handle.
+ * * `out_len` must be null or valid for a write of `size_t`.
+ */
+const uint8_t *pmxutil_model_comment(const struct PmxModel *handle, size_t *out_len);
+
+/**
+ * model comment in english in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+ *
+ * writes byte length to `out_len`. not NUL terminated.
+ *
+ * # Safety
+ * * `handle` must be null or a live handle.
+ * * `out_len` must be null or valid for a write of `size_t`.
+ */
+const uint8_t *pmxutil_model_comment_en(const struct PmxModel *handle, size_t *out_len);
+
+/**
+ * texture path relative to the model at `index` in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+ *
+ * writes byte length to `out_len`. not NUL terminated. null if `index` is out of range.
+ *
+ * # Safety
+ * * `handle` must be null or a live handle.
+ * * `out_len` must be null or valid for a write of `size_t`.
+ */
+const uint8_t *pmxutil_texture_path(const struct PmxModel *handle,
+                                    size_t index,
+                                    size_t *out_len);
+
+/**
+ * material name at `index` in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+ *
+ * writes byte length to `out_len`. not NUL terminated. null if `index` is out of range.
+ *
+ * # Safety
+ * * `handle` must be null or a live handle.
+ * * `out_len` must be null or valid for a write of `size_t`.
+ */
+const uint8_t *pmxutil_material_name(const struct PmxModel *handle, size_t index, size_t *out_len);
+
+/**
+ * bone name at `index` in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+ *
+ * writes byte length to `out_len`. not NUL terminated. null if `index` is out of range.
+ *
+ * # Safety
+ * * `handle` must be null or a live handle.
+ * * `out_len` must be null or valid for a write of `size_t`.
+ */
+const uint8_t *pmxutil_bone_name(const struct PmxModel *handle, size_t index, size_t *out_len);
+
+/**
+ * morph name at `index` in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+ *
+ * writes byte length to `out_len`. not NUL terminated. null if `index` is out of range.
+ *
+ * # Safety
+ * * `handle` must be null or a live handle.
+ * * `out_len` must be null or valid for a write of `size_t`.
+ */
+const uint8_t *pmxutil_morph_name(const struct PmxModel *handle, size_t index, size_t *out_len);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* PMXUTIL_H */
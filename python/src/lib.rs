@@ -0,0 +1,279 @@
+//! Python bindings.
+//!
+//! ```python
+//! import numpy as np
+//! import pmxutil
+//!
+//! model = pmxutil.load("model.pmx")
+//! positions = np.asarray(model.vertices)  # float32 (vertex count, 3)
+//! print(model.materials[0].name)
+//! model.save("copy.pmx")
+//! ```
+#![allow(non_snake_case)]
+// false positive on `PyResult` expanded by pyo3 macros.
+#![allow(clippy::useless_conversion)]
+
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyMemoryView};
+use PMXUtil::reader::{ModelInfoStage, ReadError};
+use PMXUtil::types::{Bone, Material, Model, PMXVersion, ToonMode};
+use PMXUtil::writer::{WritePMXErrors, Writer};
+
+fn parse(bytes: &[u8]) -> PyResult<Model> {
+    ModelInfoStage::from_reader(bytes)
+        .ok_or_else(|| PyValueError::new_err("invalid PMX header"))?
+        .try_read_model()
+        .map_err(read_error)
+}
+
+fn read_error(err: ReadError) -> PyErr {
+    match err {
+        ReadError::Io(err) => PyIOError::new_err(err.to_string()),
+        err => PyValueError::new_err(err.to_string()),
+    }
+}
+
+fn write_error(err: WritePMXErrors) -> PyErr {
+    match err {
+        WritePMXErrors::IoError(err) => PyIOError::new_err(err.to_string()),
+        err => PyValueError::new_err(err.to_string()),
+    }
+}
+
+/// writer keeping the encoding of `model` as loaded, even UTF-8 PMX 2.0.
+fn writer(model: &Model) -> Writer {
+    let mut writer = Writer::from_model(model);
+    writer.set_mmd_compat(false);
+    writer
+}
+
+/// `memoryview` of `rows` x `N` items in `format` ("f" or "I").
+fn matrix<'py, T: Copy, const N: usize>(
+    py: Python<'py>,
+    rows: impl ExactSizeIterator<Item = [T; N]>,
+    format: &str,
+    to_le_bytes: impl Fn(T) -> [u8; 4],
+) -> PyResult<Bound<'py, PyAny>> {
+    let len = rows.len();
+    let mut buf = Vec::with_capacity(len * N * 4);
+    for row in rows {
+        for x in row.iter() {
+            buf.extend_from_slice(&to_le_bytes(*x));
+        }
+    }
+    // memoryview uses native byte order. PMX is little endian as well as all supported hosts.
+    let view = PyMemoryView::from_bound(&PyBytes::new_bound(py, &buf))?;
+    view.call_method1("cast", (format, (len, N)))
+}
+
+/// a parsed PMX model.
+#[pyclass(name = "PmxModel")]
+struct PyModel {
+    model: Model,
+}
+
+#[pymethods]
+impl PyModel {
+    /// parse PMX in `data`.
+    #[staticmethod]
+    fn from_bytes(data: &[u8]) -> PyResult<Self> {
+        parse(data).map(|model| Self { model })
+    }
+
+    /// encode to PMX.
+    fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let buf = writer(&self.model).write_to_vec().map_err(write_error)?;
+        Ok(PyBytes::new_bound(py, &buf))
+    }
+
+    /// write to `path`.
+    fn save(&self, path: &str) -> PyResult<()> {
+        writer(&self.model).write_to_path(path).map_err(write_error)
+    }
+
+    /// "2.0" or "2.1"
+    #[getter]
+    fn version(&self) -> &'static str {
+        match self.model.version {
+            PMXVersion::V20 => "2.0",
+            PMXVersion::V21 => "2.1",
+        }
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.model.model_info.name
+    }
+
+    #[getter]
+    fn name_en(&self) -> &str {
+        &self.model.model_info.name_en
+    }
+
+    #[getter]
+    fn comment(&self) -> &str {
+        &self.model.model_info.comment
+    }
+
+    #[getter]
+    fn comment_en(&self) -> &str {
+        &self.model.model_info.comment_en
+    }
+
+    /// vertex positions. float32 `memoryview` shaped (vertex count, 3).
+    #[getter]
+    fn vertices<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let rows = self.model.vertices.iter().map(|vertex| vertex.position);
+        matrix(py, rows, "f", f32::to_le_bytes)
+    }
+
+    /// vertex normals. float32 `memoryview` shaped (vertex count, 3).
+    #[getter]
+    fn normals<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let rows = self.model.vertices.iter().map(|vertex| vertex.norm);
+        matrix(py, rows, "f", f32::to_le_bytes)
+    }
+
+    /// vertex uvs. float32 `memoryview` shaped (vertex count, 2).
+    #[getter]
+    fn uvs<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let rows = self.model.vertices.iter().map(|vertex| vertex.uv);
+        matrix(py, rows, "f", f32::to_le_bytes)
+    }
+
+    /// vertex indices of faces. uint32 `memoryview` shaped (face count, 3).
+    #[getter]
+    fn faces<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let rows = self.model.faces.iter().map(|face| face.vertices);
+        matrix(py, rows, "I", u32::to_le_bytes)
+    }
+
+    /// texture paths relative to the model.
+    #[getter]
+    fn textures(&self) -> Vec<String> {
+        self.model.textures.textures.clone()
+    }
+
+    #[getter]
+    fn materials(&self) -> Vec<PyMaterial> {
+        self.model.materials.iter().map(PyMaterial::from).collect()
+    }
+
+    #[getter]
+    fn bones(&self) -> Vec<PyBone> {
+        self.model.bones.iter().map(PyBone::from).collect()
+    }
+
+    /// morph names.
+    #[getter]
+    fn morphs(&self) -> Vec<String> {
+        self.model
+            .morphs
+            .iter()
+            .map(|morph| morph.name.clone())
+            .collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("PmxModel({})", self.model.summary())
+    }
+}
+
+/// material parameters. read only copy.
+#[pyclass(name = "Material", get_all, frozen)]
+struct PyMaterial {
+    name: String,
+    name_en: String,
+    diffuse: (f32, f32, f32, f32),
+    specular: (f32, f32, f32),
+    specular_factor: f32,
+    ambient: (f32, f32, f32),
+    /// `-1` if none.
+    texture_index: i32,
+    /// `None` for common toon.
+    toon_texture_index: Option<i32>,
+    /// 0 ~ 9 or `None` if toon texture is in texture list.
+    common_toon: Option<u8>,
+    num_face_vertices: i32,
+}
+
+impl From<&Material> for PyMaterial {
+    fn from(material: &Material) -> Self {
+        let [r, g, b, a] = material.diffuse;
+        let [sr, sg, sb] = material.specular;
+        let [ar, ag, ab] = material.ambient;
+        let (toon_texture_index, common_toon) = match material.toon_mode {
+            ToonMode::Separate(index) => (Some(index), None),
+            ToonMode::Common(index) => (None, Some(index)),
+        };
+        Self {
+            name: material.name.clone(),
+            name_en: material.english_name.clone(),
+            diffuse: (r, g, b, a),
+            specular: (sr, sg, sb),
+            specular_factor: material.specular_factor,
+            ambient: (ar, ag, ab),
+            texture_index: material.texture_index,
+            toon_texture_index,
+            common_toon,
+            num_face_vertices: material.num_face_vertices,
+        }
+    }
+}
+
+#[pymethods]
+impl PyMaterial {
+    fn __repr__(&self) -> String {
+        format!("Material({:?})", self.name)
+    }
+}
+
+/// bone parameters. read only copy.
+#[pyclass(name = "Bone", get_all, frozen)]
+struct PyBone {
+    name: String,
+    name_en: String,
+    position: (f32, f32, f32),
+    /// `-1` if root.
+    parent: i32,
+    deform_depth: i32,
+    is_ik: bool,
+}
+
+impl From<&Bone> for PyBone {
+    fn from(bone: &Bone) -> Self {
+        let [x, y, z] = bone.position;
+        Self {
+            name: bone.name.clone(),
+            name_en: bone.english_name.clone(),
+            position: (x, y, z),
+            parent: bone.parent,
+            deform_depth: bone.deform_depth,
+            is_ik: bone.ik_info.is_some(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyBone {
+    fn __repr__(&self) -> String {
+        format!("Bone({:?})", self.name)
+    }
+}
+
+/// read PMX file at `path`.
+#[pyfunction]
+fn load(path: &str) -> PyResult<PyModel> {
+    let bytes = std::fs::read(path).map_err(|err| PyIOError::new_err(err.to_string()))?;
+    PyModel::from_bytes(&bytes)
+}
+
+#[pymodule]
+fn pmxutil(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(load, m)?)?;
+    m.add_class::<PyModel>()?;
+    m.add_class::<PyMaterial>()?;
+    m.add_class::<PyBone>()?;
+    Ok(())
+}
@@ -0,0 +1,87 @@
+"""run after `maturin develop` with `python -m unittest discover tests`"""
+import os
+import struct
+import tempfile
+import unittest
+
+import pmxutil
+
+
+def text(s):
+    data = s.encode("utf-8")
+    return struct.pack("<i", len(data)) + data
+
+
+def triangle():
+    """PMX 2.0 in UTF-8 with 3 vertices, 1 face, 1 texture, 1 material and 1 bone."""
+    buf = b"PMX " + struct.pack("<fB", 2.0, 8) + bytes([1, 0, 1, 1, 1, 1, 1, 1])
+    buf += text("三角形") + text("triangle") + text("") + text("")
+    positions = [(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0)]
+    buf += struct.pack("<i", len(positions))
+    for position in positions:
+        buf += struct.pack("<3f3f2f", *position, 0.0, 0.0, -1.0, *position[:2])
+        # BDEF1 bone 0 and edge scale
+        buf += struct.pack("<Bbf", 0, 0, 1.0)
+    buf += struct.pack("<i3B", 3, 0, 1, 2)
+    buf += struct.pack("<i", 1) + text("tex.png")
+    buf += struct.pack("<i", 1) + text("材質") + text("material")
+    buf += struct.pack("<4f3ff3f", 1.0, 0.5, 0.25, 1.0, 0.0, 0.0, 0.0, 5.0, 0.5, 0.25, 0.125)
+    buf += struct.pack("<B4ff", 0, 0.0, 0.0, 0.0, 1.0, 1.0)
+    # texture 0, no sphere, common toon 3
+    buf += struct.pack("<bbBBB", 0, -1, 0, 1, 3) + text("") + struct.pack("<i", 3)
+    buf += struct.pack("<i", 1) + text("センター") + text("center")
+    buf += struct.pack("<3fbiH3f", 0.0, 8.0, 0.0, -1, 0, 0, 0.0, 1.0, 0.0)
+    # morphs, frames, rigid bodies and joints
+    buf += struct.pack("<4i", 0, 0, 0, 0)
+    return buf
+
+
+class TestPmxUtil(unittest.TestCase):
+    def test_accessors(self):
+        model = pmxutil.PmxModel.from_bytes(triangle())
+        self.assertEqual(model.version, "2.0")
+        self.assertEqual(model.name, "三角形")
+        self.assertEqual(model.name_en, "triangle")
+        vertices = model.vertices
+        self.assertEqual(vertices.shape, (3, 3))
+        self.assertEqual(vertices.format, "f")
+        self.assertEqual(vertices.tolist()[1], [1.0, 0.0, 0.0])
+        self.assertEqual(model.uvs.shape, (3, 2))
+        self.assertEqual(model.faces.tolist(), [[0, 1, 2]])
+        self.assertEqual(model.textures, ["tex.png"])
+        material = model.materials[0]
+        self.assertEqual(material.name, "材質")
+        self.assertEqual(material.diffuse, (1.0, 0.5, 0.25, 1.0))
+        self.assertEqual(material.texture_index, 0)
+        self.assertEqual(material.common_toon, 3)
+        self.assertIsNone(material.toon_texture_index)
+        self.assertEqual(material.num_face_vertices, 3)
+        bone = model.bones[0]
+        self.assertEqual((bone.name, bone.position, bone.parent), ("センター", (0.0, 8.0, 0.0), -1))
+        self.assertFalse(bone.is_ik)
+        self.assertEqual(model.morphs, [])
+
+    def test_load_and_save(self):
+        with tempfile.TemporaryDirectory() as directory:
+            source = os.path.join(directory, "triangle.pmx")
+            with open(source, "wb") as f:
+                f.write(triangle())
+            model = pmxutil.load(source)
+            copy = os.path.join(directory, "copy.pmx")
+            model.save(copy)
+            saved = pmxutil.load(copy)
+            self.assertEqual(saved.to_bytes(), model.to_bytes())
+            self.assertEqual(saved.vertices.tolist(), model.vertices.tolist())
+
+    def test_errors(self):
+        with self.assertRaises(ValueError):
+            pmxutil.PmxModel.from_bytes(b"Pmd " + triangle()[4:])
+        # broken data names where it broke.
+        with self.assertRaisesRegex(ValueError, "at offset"):
+            pmxutil.PmxModel.from_bytes(triangle()[:60])
+        with self.assertRaises(OSError):
+            pmxutil.load("/nonexistent/model.pmx")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,820 @@
+//! [`Arbitrary`] implementations for fuzzing and property tests.
+//!
+//! generated values are always writable and read back to the same value.
+//! * indices are in range of the referred section or `-1`
+//! * weights of `BDEF4` and `QDEF` sum to 1
+//! * floats are finite
+//! * [`Model::version`] matches the content
+//! * sum of `num_face_vertices` equals face count * 3
+//!
+//! elements generated alone refer sections of small random length.
+use crate::types::{
+    Bone, BoneFlags, BoneIKInfo, BoneInherits, BoneMorph, ConnectionDisplayMode, ControlPanel,
+    Encode, Face, FlipMorph, Frame, FrameInner, GroupMorph, IKLink, ImpulseMorph, Joint, JointType,
+    Material, MaterialFlags, MaterialMorph, MaterialMorphFormula, Model, ModelInfo, Morph,
+    MorphKinds, PMXVersion, Rigid, RigidCalcMethod, RigidForm, RigidGroups,
+    RotateAndTranslateInherits, SoftBody, SoftBodyAeroModel, SoftBodyAnchorRigid, SoftBodyFlags,
+    SoftBodyForm, SphereMode, SphereModeKind, TextureList, ToonMode, UVMorph, UnknownData, Vec2,
+    Vec3, Vec4, Vertex, VertexMorph, VertexWeight,
+};
+use arbitrary::{Arbitrary, Result, Unstructured};
+use std::convert::TryFrom;
+
+/// length of sections which elements refer.
+#[derive(Debug, Copy, Clone)]
+struct Counts {
+    vertices: usize,
+    textures: usize,
+    materials: usize,
+    bones: usize,
+    morphs: usize,
+    rigid_bodies: usize,
+    /// additional uv channels. uv morphs target only these.
+    additional_uv: usize,
+}
+
+struct Gen<'u, 'a> {
+    u: &'u mut Unstructured<'a>,
+    counts: Counts,
+}
+
+impl<'u, 'a> Gen<'u, 'a> {
+    /// generator for an element which is not a part of [`Model`]
+    fn standalone(u: &'u mut Unstructured<'a>) -> Result<Self> {
+        let counts = Counts {
+            vertices: u.int_in_range(1..=8)?,
+            textures: u.int_in_range(1..=8)?,
+            materials: u.int_in_range(1..=8)?,
+            bones: u.int_in_range(1..=8)?,
+            morphs: u.int_in_range(1..=8)?,
+            rigid_bodies: u.int_in_range(1..=8)?,
+            additional_uv: 4,
+        };
+        Ok(Self { u, counts })
+    }
+
+    fn len(&mut self, max: usize) -> Result<usize> {
+        self.u.int_in_range(0..=max)
+    }
+
+    fn collect<T>(
+        &mut self,
+        max: usize,
+        mut f: impl FnMut(&mut Self) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let len = self.len(max)?;
+        (0..len).map(|_| f(self)).collect()
+    }
+
+    fn bool(&mut self) -> Result<bool> {
+        bool::arbitrary(self.u)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        u8::arbitrary(self.u)
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        i32::arbitrary(self.u)
+    }
+
+    fn text(&mut self) -> Result<String> {
+        String::arbitrary(self.u)
+    }
+
+    /// finite and exactly representable
+    fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from(self.u.int_in_range(-4096i16..=4096)?) / 64.0)
+    }
+
+    /// 0.0 ..= 1.0
+    fn weight(&mut self) -> Result<f32> {
+        Ok(f32::from(self.u8()?) / 255.0)
+    }
+
+    /// 4 weights which sum is 1.0
+    fn weights(&mut self) -> Result<[f32; 4]> {
+        let raw = [self.u8()?, self.u8()?, self.u8()?, self.u8()?];
+        let sum: u32 = raw.iter().map(|x| u32::from(*x)).sum();
+        if sum == 0 {
+            return Ok([1.0, 0.0, 0.0, 0.0]);
+        }
+        let mut weights = [0.0; 4];
+        for (weight, raw) in weights.iter_mut().zip(raw.iter()) {
+            *weight = f32::from(*raw) / sum as f32;
+        }
+        Ok(weights)
+    }
+
+    fn vec2(&mut self) -> Result<Vec2> {
+        Ok([self.f32()?, self.f32()?])
+    }
+
+    fn vec3(&mut self) -> Result<Vec3> {
+        Ok([self.f32()?, self.f32()?, self.f32()?])
+    }
+
+    fn vec4(&mut self) -> Result<Vec4> {
+        Ok([self.f32()?, self.f32()?, self.f32()?, self.f32()?])
+    }
+
+    /// `-1 ..len`
+    fn index(&mut self, len: usize) -> Result<i32> {
+        let index = self.u.int_in_range(0..=len)?;
+        Ok(i32::try_from(index).unwrap() - 1)
+    }
+
+    /// `0..len` vertex index can't be `-1`
+    fn vertex_index(&mut self) -> Result<u32> {
+        let index = self.u.int_in_range(0..=self.counts.vertices - 1)?;
+        Ok(u32::try_from(index).unwrap())
+    }
+
+    /// elements which refer vertices. empty if there are no vertices.
+    fn vertex_refs<T>(
+        &mut self,
+        max: usize,
+        f: impl FnMut(&mut Self) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        if self.counts.vertices == 0 {
+            Ok(vec![])
+        } else {
+            self.collect(max, f)
+        }
+    }
+
+    fn bone_index(&mut self) -> Result<i32> {
+        self.index(self.counts.bones)
+    }
+
+    fn texture_index(&mut self) -> Result<i32> {
+        self.index(self.counts.textures)
+    }
+
+    fn material_index(&mut self) -> Result<i32> {
+        self.index(self.counts.materials)
+    }
+
+    fn morph_index(&mut self) -> Result<i32> {
+        self.index(self.counts.morphs)
+    }
+
+    fn rigid_index(&mut self) -> Result<i32> {
+        self.index(self.counts.rigid_bodies)
+    }
+
+    fn model_info(&mut self) -> Result<ModelInfo> {
+        Ok(ModelInfo {
+            name: self.text()?,
+            name_en: self.text()?,
+            comment: self.text()?,
+            comment_en: self.text()?,
+        })
+    }
+
+    fn vertex_weight(&mut self) -> Result<VertexWeight> {
+        Ok(match self.u.int_in_range(0..=4)? {
+            0 => VertexWeight::BDEF1(self.bone_index()?),
+            1 => VertexWeight::BDEF2 {
+                bone_index_1: self.bone_index()?,
+                bone_index_2: self.bone_index()?,
+                bone_weight_1: self.weight()?,
+            },
+            2 => {
+                let [bone_weight_1, bone_weight_2, bone_weight_3, bone_weight_4] =
+                    self.weights()?;
+                VertexWeight::BDEF4 {
+                    bone_index_1: self.bone_index()?,
+                    bone_index_2: self.bone_index()?,
+                    bone_index_3: self.bone_index()?,
+                    bone_index_4: self.bone_index()?,
+                    bone_weight_1,
+                    bone_weight_2,
+                    bone_weight_3,
+                    bone_weight_4,
+                }
+            }
+            3 => VertexWeight::SDEF {
+                bone_index_1: self.bone_index()?,
+                bone_index_2: self.bone_index()?,
+                bone_weight_1: self.weight()?,
+                sdef_c: self.vec3()?,
+                sdef_r0: self.vec3()?,
+                sdef_r1: self.vec3()?,
+            },
+            _ => {
+                let [bone_weight_1, bone_weight_2, bone_weight_3, bone_weight_4] =
+                    self.weights()?;
+                VertexWeight::QDEF {
+                    bone_index_1: self.bone_index()?,
+                    bone_index_2: self.bone_index()?,
+                    bone_index_3: self.bone_index()?,
+                    bone_index_4: self.bone_index()?,
+                    bone_weight_1,
+                    bone_weight_2,
+                    bone_weight_3,
+                    bone_weight_4,
+                }
+            }
+        })
+    }
+
+    /// additional uvs after `additional_uv` are not written so they are zero.
+    fn vertex(&mut self, additional_uv: usize) -> Result<Vertex> {
+        let mut add_uv = [[0.0; 4]; 4];
+        for slot in add_uv.iter_mut().take(additional_uv) {
+            *slot = self.vec4()?;
+        }
+        Ok(Vertex {
+            position: self.vec3()?,
+            norm: self.vec3()?,
+            uv: self.vec2()?,
+            add_uv,
+            weight_type: self.vertex_weight()?,
+            edge_mag: self.f32()?,
+        })
+    }
+
+    fn face(&mut self) -> Result<Face> {
+        Ok(Face {
+            vertices: [
+                self.vertex_index()?,
+                self.vertex_index()?,
+                self.vertex_index()?,
+            ],
+        })
+    }
+
+    fn material(&mut self, num_face_vertices: i32) -> Result<Material> {
+        Ok(Material {
+            name: self.text()?,
+            english_name: self.text()?,
+            diffuse: self.vec4()?,
+            specular: self.vec3()?,
+            specular_factor: self.f32()?,
+            ambient: self.vec3()?,
+            draw_mode: MaterialFlags::from_bits_truncate(self.u8()?),
+            edge_color: self.vec4()?,
+            edge_size: self.f32()?,
+            texture_index: self.texture_index()?,
+            sphere_mode: if self.bool()? {
+                Some(SphereMode {
+                    index: self.texture_index()?,
+                    kind: *self.u.choose(&[
+                        SphereModeKind::Mul,
+                        SphereModeKind::Add,
+                        SphereModeKind::SubTexture,
+                    ])?,
+                })
+            } else {
+                None
+            },
+            toon_mode: if self.bool()? {
+                ToonMode::Separate(self.texture_index()?)
+            } else {
+                ToonMode::Common(self.u.int_in_range(0..=9)?)
+            },
+            memo: self.text()?,
+            num_face_vertices,
+        })
+    }
+
+    fn ik_link(&mut self) -> Result<IKLink> {
+        Ok(IKLink {
+            ik_bone_index: self.bone_index()?,
+            angle_limit: if self.bool()? {
+                Some((self.vec3()?, self.vec3()?))
+            } else {
+                None
+            },
+        })
+    }
+
+    fn bone(&mut self) -> Result<Bone> {
+        Ok(Bone {
+            name: self.text()?,
+            english_name: self.text()?,
+            position: self.vec3()?,
+            parent: self.bone_index()?,
+            deform_depth: self.i32()?,
+            connection_display_mode: if self.bool()? {
+                ConnectionDisplayMode::OtherBone(self.bone_index()?)
+            } else {
+                ConnectionDisplayMode::Offset(self.vec3()?)
+            },
+            rotatable_in_viewer: self.bool()?,
+            translatable_in_viewer: self.bool()?,
+            display_bone_in_viewer: self.bool()?,
+            controllable_in_viewer: self.bool()?,
+            inherits: BoneInherits {
+                inherit_local: self.bool()?,
+                rotate_and_translate: match self.u.int_in_range(0..=3)? {
+                    0 => RotateAndTranslateInherits::None,
+                    1 => RotateAndTranslateInherits::Both(self.bone_index()?, self.f32()?),
+                    2 => RotateAndTranslateInherits::Rotate(self.bone_index()?, self.f32()?),
+                    _ => RotateAndTranslateInherits::Translate(self.bone_index()?, self.f32()?),
+                },
+            },
+            fixed_axis: if self.bool()? {
+                Some(self.vec3()?)
+            } else {
+                None
+            },
+            local_axis: if self.bool()? {
+                Some((self.vec3()?, self.vec3()?))
+            } else {
+                None
+            },
+            physics_after_deform: self.bool()?,
+            external_parent: if self.bool()? {
+                Some(self.i32()?)
+            } else {
+                None
+            },
+            ik_info: if self.bool()? {
+                Some(BoneIKInfo {
+                    ik_target_bone_index: self.bone_index()?,
+                    ik_iter_count: self.i32()?,
+                    ik_limit_angle: self.f32()?,
+                    ik_links: self.collect(4, Self::ik_link)?,
+                })
+            } else {
+                None
+            },
+            reserved_flags: {
+                let bits = self.u8()?;
+                u16::from_le_bytes([bits, bits]) & BoneFlags::RESERVED_BITS
+            },
+        })
+    }
+
+    fn uv_morph(&mut self) -> Result<UVMorph> {
+        Ok(UVMorph {
+            index: self.vertex_index()?,
+            offset: self.vec4()?,
+        })
+    }
+
+    fn morph_kinds(&mut self) -> Result<MorphKinds> {
+        const MAX: usize = 8;
+        let kind = self.u.int_in_range(0..=10)?;
+        // writer raises additional uv count for uv morphs of undeclared channels.
+        let kind = if (2..=5).contains(&kind) && kind - 2 >= self.counts.additional_uv {
+            1
+        } else {
+            kind
+        };
+        Ok(match kind {
+            0 => MorphKinds::Vertex(self.vertex_refs(MAX, |gen| {
+                Ok(VertexMorph {
+                    index: gen.vertex_index()?,
+                    offset: gen.vec3()?,
+                })
+            })?),
+            1 => MorphKinds::UV(self.vertex_refs(MAX, Self::uv_morph)?),
+            2 => MorphKinds::UV1(self.vertex_refs(MAX, Self::uv_morph)?),
+            3 => MorphKinds::UV2(self.vertex_refs(MAX, Self::uv_morph)?),
+            4 => MorphKinds::UV3(self.vertex_refs(MAX, Self::uv_morph)?),
+            5 => MorphKinds::UV4(self.vertex_refs(MAX, Self::uv_morph)?),
+            6 => MorphKinds::Bone(self.collect(MAX, |gen| {
+                Ok(BoneMorph {
+                    index: gen.bone_index()?,
+                    translates: gen.vec3()?,
+                    rotates: gen.vec4()?,
+                })
+            })?),
+            7 => MorphKinds::Material(self.collect(MAX, |gen| {
+                Ok(MaterialMorph {
+                    index: gen.material_index()?,
+                    formula: *gen.u.choose(&[
+                        MaterialMorphFormula::Multiply,
+                        MaterialMorphFormula::Additive,
+                    ])?,
+                    diffuse: gen.vec4()?,
+                    specular: gen.vec3()?,
+                    specular_factor: gen.f32()?,
+                    ambient: gen.vec3()?,
+                    edge_color: gen.vec4()?,
+                    edge_size: gen.f32()?,
+                    texture_factor: gen.vec4()?,
+                    sphere_texture_factor: gen.vec4()?,
+                    toon_texture_factor: gen.vec4()?,
+                })
+            })?),
+            8 => MorphKinds::Group(self.collect(MAX, |gen| {
+                Ok(GroupMorph {
+                    index: gen.morph_index()?,
+                    morph_factor: gen.f32()?,
+                })
+            })?),
+            9 => MorphKinds::Flip(self.collect(MAX, |gen| {
+                Ok(FlipMorph {
+                    index: gen.morph_index()?,
+                    morph_factor: gen.f32()?,
+                })
+            })?),
+            _ => MorphKinds::Impulse(self.collect(MAX, |gen| {
+                Ok(ImpulseMorph {
+                    rigid_index: gen.rigid_index()?,
+                    is_local: gen.bool()?,
+                    velocity: gen.vec3()?,
+                    torque: gen.vec3()?,
+                })
+            })?),
+        })
+    }
+
+    fn morph(&mut self) -> Result<Morph> {
+        Ok(Morph {
+            name: self.text()?,
+            english_name: self.text()?,
+            control_panel: *self.u.choose(&[
+                ControlPanel::System,
+                ControlPanel::BottomLeft,
+                ControlPanel::TopLeft,
+                ControlPanel::TopRight,
+                ControlPanel::BottomRight,
+            ])?,
+            morph_data: self.morph_kinds()?,
+        })
+    }
+
+    fn frame(&mut self) -> Result<Frame> {
+        Ok(Frame {
+            name: self.text()?,
+            name_en: self.text()?,
+            is_special: self.bool()?,
+            inners: self.collect(8, |gen| {
+                Ok(if gen.bool()? {
+                    FrameInner::Bone(gen.bone_index()?)
+                } else {
+                    FrameInner::Morph(gen.morph_index()?)
+                })
+            })?,
+        })
+    }
+
+    fn rigid(&mut self) -> Result<Rigid> {
+        Ok(Rigid {
+            name: self.text()?,
+            name_en: self.text()?,
+            bone_index: self.bone_index()?,
+            group: self.u8()?,
+            un_collision_group_flag: RigidGroups::from(u16::arbitrary(self.u)?),
+            form: self
+                .u
+                .choose(&[RigidForm::Sphere, RigidForm::Box, RigidForm::Capsule])?
+                .clone(),
+            size: self.vec3()?,
+            position: self.vec3()?,
+            rotation: self.vec3()?,
+            mass: self.f32()?,
+            move_resist: self.f32()?,
+            rotation_resist: self.f32()?,
+            repulsion: self.f32()?,
+            friction: self.f32()?,
+            calc_method: self
+                .u
+                .choose(&[
+                    RigidCalcMethod::Static,
+                    RigidCalcMethod::Dynamic,
+                    RigidCalcMethod::DynamicWithBonePosition,
+                ])?
+                .clone(),
+        })
+    }
+
+    fn joint_type(&mut self) -> Result<JointType> {
+        Ok(match self.u.int_in_range(0..=5)? {
+            0 => JointType::Spring6DOF {
+                a_rigid_index: self.rigid_index()?,
+                b_rigid_index: self.rigid_index()?,
+                position: self.vec3()?,
+                rotation: self.vec3()?,
+                move_limit_down: self.vec3()?,
+                move_limit_up: self.vec3()?,
+                rotation_limit_down: self.vec3()?,
+                rotation_limit_up: self.vec3()?,
+                spring_const_move: self.vec3()?,
+                spring_const_rotation: self.vec3()?,
+            },
+            1 => JointType::SixDof {
+                a_rigid_index: self.rigid_index()?,
+                b_rigid_index: self.rigid_index()?,
+                position: self.vec3()?,
+                rotation: self.vec3()?,
+                move_limit_down: self.vec3()?,
+                move_limit_up: self.vec3()?,
+                rotation_limit_down: self.vec3()?,
+                rotation_limit_up: self.vec3()?,
+            },
+            2 => JointType::P2P {
+                a_rigid_index: self.rigid_index()?,
+                b_rigid_index: self.rigid_index()?,
+                position: self.vec3()?,
+                rotation: self.vec3()?,
+            },
+            3 => JointType::ConeTwist {
+                a_rigid_index: self.rigid_index()?,
+                b_rigid_index: self.rigid_index()?,
+                position: self.vec3()?,
+                rotation: self.vec3()?,
+                swing_span1: self.f32()?,
+                swing_span2: self.f32()?,
+                twist_span: self.f32()?,
+                softness: self.f32()?,
+                bias_factor: self.f32()?,
+                relaxation_factor: self.f32()?,
+                damping: self.f32()?,
+                fix_thresh: self.f32()?,
+                enable_motor: self.bool()?,
+                max_motor_impulse: self.f32()?,
+                motor_target_in_constraint_space: self.vec3()?,
+            },
+            4 => JointType::Slider {
+                a_rigid_index: self.rigid_index()?,
+                b_rigid_index: self.rigid_index()?,
+                position: self.vec3()?,
+                rotation: self.vec3()?,
+                lower_linear_limit: self.f32()?,
+                upper_linear_limit: self.f32()?,
+                lower_angle_limit: self.f32()?,
+                upper_angle_limit: self.f32()?,
+                power_linear_motor: self.bool()?,
+                target_linear_motor_velocity: self.f32()?,
+                max_linear_motor_force: self.f32()?,
+                power_angler_motor: self.bool()?,
+                target_angler_motor_velocity: self.f32()?,
+                max_angler_motor_force: self.f32()?,
+            },
+            _ => JointType::Hinge {
+                a_rigid_index: self.rigid_index()?,
+                b_rigid_index: self.rigid_index()?,
+                position: self.vec3()?,
+                rotation: self.vec3()?,
+                low: self.f32()?,
+                high: self.f32()?,
+                softness: self.f32()?,
+                bias_factor: self.f32()?,
+                relaxation_factor: self.f32()?,
+                enable_motor: self.bool()?,
+                target_velocity: self.f32()?,
+                max_motor_impulse: self.f32()?,
+            },
+        })
+    }
+
+    fn joint(&mut self) -> Result<Joint> {
+        Ok(Joint {
+            name: self.text()?,
+            name_en: self.text()?,
+            joint_type: self.joint_type()?,
+        })
+    }
+
+    fn soft_body(&mut self) -> Result<SoftBody> {
+        Ok(SoftBody {
+            name: self.text()?,
+            name_en: self.text()?,
+            form: *self
+                .u
+                .choose(&[SoftBodyForm::TriMesh, SoftBodyForm::Rope])?,
+            material_index: self.material_index()?,
+            group: self.u8()?,
+            un_collision_group_flag: RigidGroups::from(u16::arbitrary(self.u)?),
+            bit_flag: SoftBodyFlags::from_bits_truncate(self.u8()?),
+            b_link_create_distance: self.i32()?,
+            clusters: self.i32()?,
+            mass: self.f32()?,
+            collision_margin: self.f32()?,
+            aero_model: *self.u.choose(&[
+                SoftBodyAeroModel::VPoint,
+                SoftBodyAeroModel::VTwoSide,
+                SoftBodyAeroModel::VOneSided,
+                SoftBodyAeroModel::FTwoSided,
+                SoftBodyAeroModel::FOneSided,
+            ])?,
+            vcf: self.f32()?,
+            dp: self.f32()?,
+            dg: self.f32()?,
+            lf: self.f32()?,
+            pr: self.f32()?,
+            vc: self.f32()?,
+            df: self.f32()?,
+            mt: self.f32()?,
+            chr: self.f32()?,
+            khr: self.f32()?,
+            shr: self.f32()?,
+            ahr: self.f32()?,
+            srhr_cl: self.f32()?,
+            skhr_cl: self.f32()?,
+            sshr_cl: self.f32()?,
+            sr_splt_cl: self.f32()?,
+            sk_splt_cl: self.f32()?,
+            ss_splt_cl: self.f32()?,
+            v_it: self.i32()?,
+            p_it: self.i32()?,
+            d_it: self.i32()?,
+            c_it: self.i32()?,
+            lst: self.f32()?,
+            ast: self.f32()?,
+            vst: self.f32()?,
+            anchor_rigid: self.vertex_refs(4, |gen| {
+                Ok(SoftBodyAnchorRigid {
+                    rigid_index: gen.rigid_index()?,
+                    vertex_index: gen.vertex_index()? as i32,
+                    near_mode: gen.bool()?,
+                })
+            })?,
+            pin_vertex: self.vertex_refs(4, |gen| Ok(gen.vertex_index()? as i32))?,
+        })
+    }
+}
+
+/// same rule as `Writer`
+fn version_of(model: &Model) -> PMXVersion {
+    let qdef = model
+        .vertices
+        .iter()
+        .any(|vertex| matches!(vertex.weight_type, VertexWeight::QDEF { .. }));
+    let morph = model.morphs.iter().any(|morph| {
+        matches!(
+            morph.morph_data,
+            MorphKinds::Flip(_) | MorphKinds::Impulse(_)
+        )
+    });
+    let joint = model
+        .joints
+        .iter()
+        .any(|joint| !matches!(joint.joint_type, JointType::Spring6DOF { .. }));
+    let material = model
+        .materials
+        .iter()
+        .any(crate::writer::material_requires_v21);
+    if qdef || morph || joint || material || !model.soft_bodies.is_empty() {
+        PMXVersion::V21
+    } else {
+        PMXVersion::V20
+    }
+}
+
+impl<'a> Arbitrary<'a> for Model {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let encode = *u.choose(&[Encode::Utf16Le, Encode::UTF8])?;
+        let additional_uv = u.int_in_range(0..=4)?;
+        let counts = Counts {
+            // crosses u8 and i8 index size boundaries
+            vertices: u.int_in_range(0..=300)?,
+            textures: u.int_in_range(0..=8)?,
+            materials: u.int_in_range(0..=8)?,
+            bones: u.int_in_range(0..=150)?,
+            morphs: u.int_in_range(0..=16)?,
+            rigid_bodies: u.int_in_range(0..=8)?,
+            additional_uv: usize::from(additional_uv),
+        };
+        let mut gen = Gen { u, counts };
+        let model_info = gen.model_info()?;
+        let vertices = (0..counts.vertices)
+            .map(|_| gen.vertex(counts.additional_uv))
+            .collect::<Result<Vec<_>>>()?;
+        let faces = if counts.materials == 0 {
+            vec![]
+        } else {
+            gen.vertex_refs(200, Gen::face)?
+        };
+        let textures = (0..counts.textures)
+            .map(|_| gen.text())
+            .collect::<Result<Vec<_>>>()?;
+        let mut remaining = faces.len();
+        let mut materials = Vec::with_capacity(counts.materials);
+        for i in 0..counts.materials {
+            let faces = if i + 1 == counts.materials {
+                remaining
+            } else {
+                gen.u.int_in_range(0..=remaining)?
+            };
+            remaining -= faces;
+            materials.push(gen.material(i32::try_from(faces * 3).unwrap())?);
+        }
+        let bones = (0..counts.bones)
+            .map(|_| gen.bone())
+            .collect::<Result<Vec<_>>>()?;
+        let morphs = (0..counts.morphs)
+            .map(|_| gen.morph())
+            .collect::<Result<Vec<_>>>()?;
+        let frames = gen.collect(8, Gen::frame)?;
+        let rigid_bodies = (0..counts.rigid_bodies)
+            .map(|_| gen.rigid())
+            .collect::<Result<Vec<_>>>()?;
+        let joints = gen.collect(8, Gen::joint)?;
+        let soft_bodies = gen.collect(4, Gen::soft_body)?;
+        let mut model = Model {
+            version: PMXVersion::V20,
+            encode,
+            additional_uv,
+            model_info,
+            vertices,
+            faces,
+            textures: TextureList { textures },
+            materials,
+            bones,
+            morphs,
+            frames,
+            rigid_bodies,
+            joints,
+            soft_bodies,
+            unknown: UnknownData::default(),
+        };
+        model.version = version_of(&model);
+        Ok(model)
+    }
+}
+
+macro_rules! impl_arbitrary {
+    ($($t:ty => $f:expr),* $(,)?) => {
+        $(impl<'a> Arbitrary<'a> for $t {
+            fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+                let mut gen = Gen::standalone(u)?;
+                $f(&mut gen)
+            }
+        })*
+    };
+}
+
+impl_arbitrary!(
+    ModelInfo => Gen::model_info,
+    VertexWeight => Gen::vertex_weight,
+    Vertex => |gen: &mut Gen| gen.vertex(4),
+    Face => Gen::face,
+    Material => |gen: &mut Gen| {
+        let num_face_vertices = gen.u.int_in_range(0..=64)? * 3;
+        gen.material(num_face_vertices)
+    },
+    IKLink => Gen::ik_link,
+    Bone => Gen::bone,
+    MorphKinds => Gen::morph_kinds,
+    Morph => Gen::morph,
+    Frame => Gen::frame,
+    Rigid => Gen::rigid,
+    JointType => Gen::joint_type,
+    Joint => Gen::joint,
+    SoftBody => Gen::soft_body,
+);
+
+#[cfg(test)]
+mod test {
+    use super::version_of;
+    use crate::test_support::{read, write};
+    use crate::types::{MaterialFlags, Model, PMXVersion};
+    use arbitrary::{Arbitrary, Unstructured};
+
+    /// deterministic pseudo random bytes
+    fn bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 33) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn write_read_write_is_idempotent() {
+        for seed in 0..64 {
+            let data = bytes(seed, 64 * 1024);
+            let model = Model::arbitrary(&mut Unstructured::new(&data)).unwrap();
+            let buf = write(&model);
+            let model_cpy = read(&buf);
+            assert_eq!(model, model_cpy, "seed {}", seed);
+            assert_eq!(buf, write(&model_cpy), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn material_flags_decide_version() {
+        let mut model = (0..)
+            .map(|seed| {
+                let data = bytes(seed, 64 * 1024);
+                Model::arbitrary(&mut Unstructured::new(&data)).unwrap()
+            })
+            .find(|model| !model.materials.is_empty())
+            .unwrap();
+        model.vertices.clear();
+        model.faces.clear();
+        model.morphs.clear();
+        model.joints.clear();
+        model.soft_bodies.clear();
+        for material in &mut model.materials {
+            material.num_face_vertices = 0;
+            material.draw_mode = MaterialFlags::empty();
+        }
+        assert_eq!(version_of(&model), PMXVersion::V20);
+        model.materials[0].draw_mode = MaterialFlags::LINE_DRAW;
+        model.version = version_of(&model);
+        assert_eq!(model.version, PMXVersion::V21);
+        assert_eq!(read(&write(&model)), model);
+    }
+}
@@ -1,22 +1,55 @@
-use crate::types::{Encode, HeaderRaw, IndexKinds, Vec2, Vec3, Vec4, VertexIndexKinds};
+use crate::types::{Encode, HeaderRaw, IndexKinds, Vec3, Vec4, VertexIndexKinds};
 use std::convert::TryFrom;
+#[cfg(feature = "std-fs")]
 use std::fs::File;
-use std::io::{BufReader, Error, Read};
+#[cfg(feature = "std-fs")]
+use std::io::Error;
+use std::io::{BufRead, BufReader, Read};
+#[cfg(feature = "std-fs")]
 use std::path::Path;
 
 macro_rules! read_bin {
     ($F:ident,$T:ty) => {
         pub(crate) fn $F(&mut self) -> $T {
             let mut buf = [0_u8; std::mem::size_of::<$T>()];
-            self.inner.read_exact(&mut buf).unwrap();
+            self.fill(&mut buf);
             <$T>::from_le_bytes(buf)
         }
     };
 }
 
+/// texts longer than this are not allocated at once.
+const MAX_RESERVE: usize = 1 << 16;
+
+/// bytes kept by [`BinaryReader::recent`].
+pub(crate) const RECENT_BYTES: usize = 16;
+
+/// first problem met by [`BinaryReader`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Failure {
+    /// offset from the start of file where the problem was found.
+    pub(crate) position: u64,
+    pub(crate) reason: String,
+}
+
+/// reader of little endian values.
+///
+/// reading never panics. after the first failure, e.g. the end of file or invalid text,
+/// nothing is read any more and all values are zero. callers check [`failure`](Self::failure).
 pub(crate) struct BinaryReader<R: Read> {
     inner: BufReader<R>,
+    /// bytes consumed from the start of file.
+    position: u64,
+    /// reused for undecoded UTF-16 text.
+    scratch: Vec<u8>,
+    /// reused for decoded UTF-16 text before it is copied to a string of exact size.
+    /// only grows and keeps its contents so it isn't zeroed on each text.
+    decoded: String,
+    failure: Option<Failure>,
+    /// the last bytes read. valid ones are at the end.
+    recent: [u8; RECENT_BYTES],
 }
+#[cfg(feature = "std-fs")]
 impl BinaryReader<File> {
     pub(crate) fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
         let file = File::open(&path);
@@ -24,7 +57,14 @@ impl BinaryReader<File> {
         match file {
             Ok(file) => {
                 let inner = BufReader::new(file);
-                Ok(BinaryReader { inner })
+                Ok(BinaryReader {
+                    inner,
+                    position: 0,
+                    scratch: Vec::new(),
+                    decoded: String::new(),
+                    failure: None,
+                    recent: [0; RECENT_BYTES],
+                })
             }
             Err(err) => Err(err),
         }
@@ -34,19 +74,133 @@ impl<R: Read> BinaryReader<R> {
     pub(crate) fn from_reader(r: R) -> Self {
         Self {
             inner: BufReader::new(r),
+            position: 0,
+            scratch: Vec::new(),
+            decoded: String::new(),
+            failure: None,
+            recent: [0; RECENT_BYTES],
+        }
+    }
+    pub(crate) fn position(&self) -> u64 {
+        self.position
+    }
+    /// the first problem met so far.
+    pub(crate) fn failure(&self) -> Option<&Failure> {
+        self.failure.as_ref()
+    }
+    /// stop reading because of `reason` found at the current position.
+    /// only the first failure is kept.
+    pub(crate) fn fail(&mut self, reason: String) {
+        if self.failure.is_none() {
+            self.failure = Some(Failure {
+                position: self.position,
+                reason,
+            });
+        }
+    }
+    /// up to [`RECENT_BYTES`] bytes read last.
+    pub(crate) fn recent(&self) -> &[u8] {
+        let len = usize::try_from(self.position).map_or(RECENT_BYTES, |p| p.min(RECENT_BYTES));
+        &self.recent[RECENT_BYTES - len..]
+    }
+    fn remember(&mut self, bytes: &[u8]) {
+        let len = bytes.len().min(RECENT_BYTES);
+        self.recent.copy_within(len.., 0);
+        self.recent[RECENT_BYTES - len..].copy_from_slice(&bytes[bytes.len() - len..]);
+    }
+    /// read exactly `buf.len()` bytes. `buf` is zeroed on failure.
+    fn fill(&mut self, buf: &mut [u8]) {
+        if self.failure.is_some() {
+            buf.fill(0);
+            return;
+        }
+        match self.inner.read_exact(buf) {
+            Ok(()) => {
+                self.position += buf.len() as u64;
+                self.remember(buf);
+            }
+            Err(err) => {
+                buf.fill(0);
+                self.fail(err.to_string());
+            }
         }
     }
     pub(crate) fn read_vec(&mut self, n: usize) -> Vec<u8> {
-        let mut v = vec![0; n];
-        self.inner.read_exact(&mut v).unwrap();
+        let mut v = vec![];
+        self.read_into(n, &mut v);
         v
     }
+    /// replace `buf` with `n` bytes read. `buf` is empty on failure.
+    ///
+    /// `buf` grows with read bytes so broken length doesn't allocate at once.
+    fn read_into(&mut self, n: usize, buf: &mut Vec<u8>) {
+        buf.clear();
+        self.read_append(n, buf);
+    }
+    /// append `n` bytes read to `buf`. `buf` is unchanged on failure.
+    pub(crate) fn read_append(&mut self, n: usize, buf: &mut Vec<u8>) {
+        if self.failure.is_some() {
+            return;
+        }
+        let start = buf.len();
+        buf.reserve(n.min(MAX_RESERVE));
+        match (&mut self.inner).take(n as u64).read_to_end(buf) {
+            Ok(len) if len == n => {
+                self.position += n as u64;
+                self.remember(&buf[start..]);
+            }
+            Ok(_) => {
+                buf.truncate(start);
+                self.fail("failed to fill whole buffer".to_owned())
+            }
+            Err(err) => {
+                buf.truncate(start);
+                self.fail(err.to_string())
+            }
+        }
+    }
+    /// length then text. one allocation per non-empty string and none for empty one.
     pub(crate) fn read_text_buf(&mut self, encode: Encode) -> String {
         let length = self.read_i32();
-        let v = self.read_vec(usize::try_from(length).unwrap());
+        let length = match usize::try_from(length) {
+            Ok(length) => length,
+            Err(_) => {
+                self.fail(format!("negative text length {}", length));
+                return String::new();
+            }
+        };
         match encode {
-            Encode::UTF8 => String::from_utf8(v).unwrap(),
-            Encode::Utf16Le => encoding_rs::UTF_16LE.decode(&v).0.to_string(),
+            // bytes are validated in place and become the string without copy.
+            Encode::UTF8 => String::from_utf8(self.read_vec(length)).unwrap_or_else(|err| {
+                self.fail("invalid UTF-8 text".to_owned());
+                String::from_utf8_lossy(err.as_bytes()).into_owned()
+            }),
+            Encode::Utf16Le => {
+                let mut scratch = std::mem::take(&mut self.scratch);
+                self.read_into(length, &mut scratch);
+                self.scratch = scratch;
+                if self.failure.is_some() {
+                    return String::new();
+                }
+                // decode into a buffer of worst case capacity then copy, so the text is
+                // allocated once in exact size. `Encoding::decode` guesses smaller and grows
+                // for CJK text.
+                let mut decoder = encoding_rs::UTF_16LE.new_decoder();
+                let capacity = decoder.max_utf8_buffer_length(length).unwrap();
+                if self.decoded.len() < capacity {
+                    let grow = capacity - self.decoded.len();
+                    self.decoded.extend(std::iter::repeat_n('\0', grow));
+                }
+                // previous text may end in the middle of a character.
+                let mut end = capacity;
+                while !self.decoded.is_char_boundary(end) {
+                    end += 1;
+                }
+                let (result, _, written, _) =
+                    decoder.decode_to_str(&self.scratch, &mut self.decoded[..end], true);
+                debug_assert_eq!(result, encoding_rs::CoderResult::InputEmpty);
+                self.decoded[..written].to_owned()
+            }
         }
     }
 
@@ -66,32 +220,49 @@ impl<R: Read> BinaryReader<R> {
         }
     }
 
+    /// `true` if the source has no bytes left. `false` after failure or on error of the source,
+    /// which the next read meets.
+    pub(crate) fn at_end(&mut self) -> bool {
+        self.failure.is_none() && matches!(self.inner.fill_buf(), Ok(buf) if buf.is_empty())
+    }
+
+    /// all bytes left in the source. nothing after failure.
+    pub(crate) fn read_to_end(&mut self) -> std::io::Result<Vec<u8>> {
+        let mut rest = vec![];
+        if self.failure.is_some() {
+            return Ok(rest);
+        }
+        self.inner.read_to_end(&mut rest)?;
+        self.position += rest.len() as u64;
+        Ok(rest)
+    }
+
+    /// read `N` bytes at once.
+    pub(crate) fn read_array<const N: usize>(&mut self) -> [u8; N] {
+        let mut buf = [0_u8; N];
+        self.fill(&mut buf);
+        buf
+    }
+    /// read `N` floats at once.
+    pub(crate) fn read_f32_array<const N: usize>(&mut self) -> [f32; N] {
+        let mut buf = [[0_u8; 4]; N];
+        self.fill(buf.as_flattened_mut());
+        buf.map(f32::from_le_bytes)
+    }
+
     pub(crate) fn read_vec4(&mut self) -> Vec4 {
-        [
-            self.read_f32(),
-            self.read_f32(),
-            self.read_f32(),
-            self.read_f32(),
-        ]
+        self.read_f32_array()
     }
     pub(crate) fn read_vec3(&mut self) -> Vec3 {
-        [self.read_f32(), self.read_f32(), self.read_f32()]
-    }
-    pub(crate) fn read_vec2(&mut self) -> Vec2 {
-        [self.read_f32(), self.read_f32()]
+        self.read_f32_array()
     }
     pub(crate) fn read_raw_header(&mut self) -> HeaderRaw {
-        let mut header = HeaderRaw {
-            magic: [0; 4],
-            version: 0.0,
-            length: 0,
-            config: [0; 8],
-        };
-        header.magic.iter_mut().for_each(|c| *c = self.read_u8());
-        header.version = self.read_f32();
-        header.length = self.read_u8();
-        header.config.iter_mut().for_each(|c| *c = self.read_u8());
-        header
+        HeaderRaw {
+            magic: self.read_array(),
+            version: self.read_f32(),
+            length: self.read_u8(),
+            config: self.read_array(),
+        }
     }
 
     read_bin!(read_f32, f32);
@@ -101,11 +272,13 @@ impl<R: Read> BinaryReader<R> {
     read_bin!(read_i8, i8);
     read_bin!(read_u8, u8);
     /// read `0_u8` as `false`, `1_u8` as `true`
-    pub(crate) fn read_bool(&mut self) -> Option<bool> {
+    ///
+    /// other values are returned as `Err`.
+    pub(crate) fn read_bool(&mut self) -> Result<bool, u8> {
         match self.read_u8() {
-            0 => Some(false),
-            1 => Some(true),
-            _ => None,
+            0 => Ok(false),
+            1 => Ok(true),
+            x => Err(x),
         }
     }
 }
@@ -1,24 +1,38 @@
+#[cfg(feature = "std-fs")]
 use std::fs::File;
 use std::io::BufWriter;
+#[cfg(feature = "std-fs")]
 use std::io::Error;
 use std::io::Write;
+#[cfg(feature = "std-fs")]
 use std::path::Path;
 
+use crate::progress::SectionKind;
 use crate::types::{
-    Bone, BoneMorph, ConnectionDisplayMode, ControlPanel, Encode, Face, FlipMorph, Frame,
-    FrameInner, GroupMorph, Header, IKLink, ImpulseMorph, IndexKinds, Joint, JointType, Material,
-    MaterialMorph, Morph, MorphKinds, PMXVersion, Rigid, RigidCalcMethod, RigidForm,
+    Bone, BoneMorph, ConnectionDisplayMode, Encode, Face, FlipMorph, Frame, FrameInner, GroupMorph,
+    Header, IKLink, ImpulseMorph, IndexKinds, Joint, JointType, Material, MaterialMorph, ModelInfo,
+    Morph, MorphKinds, PMXVersion, RawSection, Rigid, RigidCalcMethod, RigidForm,
     RotateAndTranslateInherits, SoftBody, SoftBodyAeroModel, SoftBodyForm, SphereModeKind,
     ToonMode, UVMorph, Vertex, VertexIndexKinds, VertexMorph, VertexWeight,
 };
 use crate::types::{Vec2, Vec3, Vec4};
+use crate::writer::{section_count, WritePMXErrors};
 use std::convert::TryFrom;
 
+/// buffer size unless configured by [`Writer::set_buffer_capacity`](crate::writer::Writer::set_buffer_capacity).
+pub(crate) const DEFAULT_BUFFER_CAPACITY: usize = 256 * 1024;
+
 /// This is internal use only struct
 /// Do not use this struct
 pub(crate) struct BinaryWriter<W: Write> {
     pub(crate) inner: BufWriter<W>,
     pub(crate) header: Header,
+    /// first index which didn't fit its index size. see [`check_indices`](Self::check_indices).
+    invalid_index: Option<i64>,
+    /// reused for UTF-16 code units of texts. only grows.
+    utf16: Vec<u16>,
+    /// texts are cut to this many characters.
+    pub(crate) text_limit: Option<usize>,
 }
 
 macro_rules! write_bin {
@@ -41,30 +55,38 @@ macro_rules! write_bin_array {
         }
     };
 }
+#[cfg(feature = "std-fs")]
 impl BinaryWriter<File> {
-    pub(crate) fn create<P: AsRef<Path>>(path: P, header: Header) -> Result<Self, Error> {
+    pub(crate) fn create<P: AsRef<Path>>(
+        path: P,
+        header: Header,
+        capacity: usize,
+    ) -> Result<Self, Error> {
         let file = File::create(&path);
 
         match file {
-            Ok(file) => {
-                let inner = BufWriter::with_capacity(1024, file);
-                Ok(BinaryWriter { inner, header })
-            }
+            Ok(file) => Ok(Self::with_capacity(file, header, capacity)),
             Err(err) => Err(err),
         }
     }
 }
 impl<W: Write> BinaryWriter<W> {
     pub(crate) fn from_writer(writer: W, header: Header) -> Self {
+        Self::with_capacity(writer, header, DEFAULT_BUFFER_CAPACITY)
+    }
+    /// `capacity` 0 passes every write to `writer` as is.
+    pub(crate) fn with_capacity(writer: W, header: Header, capacity: usize) -> Self {
         Self {
-            inner: BufWriter::new(writer),
+            inner: BufWriter::with_capacity(capacity, writer),
             header,
+            invalid_index: None,
+            utf16: Vec::new(),
+            text_limit: None,
         }
     }
     pub(crate) fn write_header(&mut self) {
-        let magic = self.header.magic.clone();
-        let bytes = magic.as_bytes();
-        self.write_vec(bytes);
+        let magic = self.header.magic;
+        self.write_vec(&magic);
         self.write_f32(match self.header.version {
             PMXVersion::V20 => 2.0,
             PMXVersion::V21 => 2.1,
@@ -86,35 +108,107 @@ impl<W: Write> BinaryWriter<W> {
         self.inner.write_all(v).unwrap();
     }
 
+    /// `text` cut to [`text_limit`](Self::text_limit) characters if set.
+    ///
+    /// # Panics
+    /// * if `text` is longer than `i32::MAX` bytes. writers check texts before writing.
     pub(crate) fn write_text_buf(&mut self, text: &str) {
-        let len = text.len();
+        let text = match self
+            .text_limit
+            .and_then(|limit| text.char_indices().nth(limit))
+        {
+            Some((end, _)) => &text[..end],
+            None => text,
+        };
         if self.header.encode == Encode::Utf16Le {
-            let utf_16_sequence: Vec<u16> = text.encode_utf16().map(u16::to_le).collect();
-            self.write_i32(i32::try_from(utf_16_sequence.len() * 2).unwrap());
-            for ch in utf_16_sequence {
-                self.write_u16(ch);
+            // code units are never more than UTF-8 bytes.
+            if self.utf16.len() < text.len() {
+                self.utf16.resize(text.len(), 0);
+            }
+            let len = encoding_rs::mem::convert_str_to_utf16(text, &mut self.utf16[..text.len()]);
+            self.write_i32(i32::try_from(len * 2).unwrap());
+            // bytes go through a stack buffer instead of a write per code unit.
+            let mut buf = [0; 256];
+            for units in self.utf16[..len].chunks(buf.len() / 2) {
+                for (bytes, unit) in buf.chunks_exact_mut(2).zip(units) {
+                    bytes.copy_from_slice(&unit.to_le_bytes());
+                }
+                self.inner.write_all(&buf[..units.len() * 2]).unwrap();
             }
         } else {
-            self.write_i32(i32::try_from(len).unwrap());
+            self.write_i32(i32::try_from(text.len()).unwrap());
             self.write_vec(text.as_bytes());
         };
     }
 
-    pub(crate) fn write_vertex_index(&mut self, value: i32) {
+    /// fail with `element` of `section` if an index written since the last check didn't fit
+    /// its index size.
+    pub(crate) fn check_indices(
+        &mut self,
+        section: SectionKind,
+        element: usize,
+    ) -> Result<(), WritePMXErrors> {
+        match self.invalid_index.take() {
+            Some(index) => Err(WritePMXErrors::IndexOutOfRange {
+                section,
+                element,
+                index,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// record `value` unless it fits. the bytes written for it are garbage.
+    fn fit<T: TryFrom<V> + Default, V: Copy + Into<i64>>(&mut self, value: V) -> T {
+        T::try_from(value).unwrap_or_else(|_| {
+            self.invalid_index.get_or_insert(value.into());
+            T::default()
+        })
+    }
+
+    /// vertex index of faces and morphs. 32 bit ones are signed in file.
+    pub(crate) fn write_vertex_index(&mut self, value: u32) {
+        match self.header.s_vertex_index {
+            VertexIndexKinds::U8 => {
+                let value = self.fit(value);
+                self.write_u8(value)
+            }
+            VertexIndexKinds::U16 => {
+                let value = self.fit(value);
+                self.write_u16(value)
+            }
+            VertexIndexKinds::I32 => {
+                let value = self.fit(value);
+                self.write_i32(value)
+            }
+        }
+    }
+
+    /// vertex indices are unsigned in 8 and 16 bit so `-1` is only written in 32 bit.
+    fn write_signed_vertex_index(&mut self, value: i32) {
         match self.header.s_vertex_index {
-            VertexIndexKinds::U8 => self.write_u8(u8::try_from(value).unwrap()),
-            VertexIndexKinds::U16 => self.write_u16(u16::try_from(value).unwrap()),
+            VertexIndexKinds::U8 => {
+                let value = self.fit(value);
+                self.write_u8(value)
+            }
+            VertexIndexKinds::U16 => {
+                let value = self.fit(value);
+                self.write_u16(value)
+            }
             VertexIndexKinds::I32 => self.write_i32(value),
         }
     }
 
+    /// other indices are signed and `-1` means none at any size.
     fn write_sized(&mut self, size: IndexKinds, value: i32) {
         match size {
             IndexKinds::I8 => {
-                self.write_i8(i8::try_from(value).unwrap());
+                let value = self.fit(value);
+                self.write_i8(value);
             }
             IndexKinds::I16 => {
-                self.write_i16(i16::try_from(value).unwrap());
+                let value = self.fit(value);
+                self.write_i16(value);
             }
             IndexKinds::I32 => {
                 self.write_i32(value);
@@ -136,6 +230,12 @@ impl<W: Write> BinaryWriter<W> {
     fn write_rigid_index(&mut self, value: i32) {
         self.write_sized(self.header.s_rigid_body_index, value);
     }
+    pub(crate) fn write_model_info(&mut self, model_info: &ModelInfo) {
+        self.write_text_buf(&model_info.name);
+        self.write_text_buf(&model_info.name_en);
+        self.write_text_buf(&model_info.comment);
+        self.write_text_buf(&model_info.comment_en);
+    }
     pub(crate) fn write_face(&mut self, face: &Face) {
         self.write_vertex_index(face.vertices[0]);
         self.write_vertex_index(face.vertices[1]);
@@ -179,6 +279,28 @@ impl<W: Write> BinaryWriter<W> {
         self.write_i32(material.num_face_vertices);
     }
 
+    /// count then records of `raw` vertices as they were read.
+    ///
+    /// # Errors
+    /// * `WritePMXErrors::RawSectionMismatch` if additional uv count or bone index size of
+    ///   the header differs from the one `raw` was read with.
+    /// * `WritePMXErrors::RequiresV21` if `raw` has QDEF weights in PMX 2.0.
+    pub(crate) fn write_raw_section(&mut self, raw: &RawSection) -> Result<(), WritePMXErrors> {
+        if raw.additional_uv != self.header.additional_uv
+            || raw.bone_index != self.header.s_bone_index
+        {
+            return Err(WritePMXErrors::RawSectionMismatch {
+                section: SectionKind::Vertices,
+            });
+        }
+        if self.header.version == PMXVersion::V20 && raw.requires_v21() {
+            return Err(WritePMXErrors::RequiresV21);
+        }
+        self.write_i32(section_count(SectionKind::Vertices, raw.count as usize)?);
+        self.write_vec(&raw.bytes);
+        Ok(())
+    }
+
     pub(crate) fn write_vertex(&mut self, vertex: &Vertex) {
         self.write_vec3(vertex.position);
         self.write_vec3(vertex.norm);
@@ -273,8 +395,7 @@ impl<W: Write> BinaryWriter<W> {
         self.write_vec3(bone.position);
         self.write_bone_index(bone.parent);
         self.write_i32(bone.deform_depth);
-        let bone_flags = bone.calculate_bone_flag();
-        self.write_u16(bone_flags.bits());
+        self.write_u16(bone.flag_bits());
         match bone.connection_display_mode {
             ConnectionDisplayMode::OtherBone(x) => {
                 self.write_bone_index(x);
@@ -317,13 +438,7 @@ impl<W: Write> BinaryWriter<W> {
     pub(crate) fn write_morph(&mut self, morph: &Morph) {
         self.write_text_buf(&morph.name);
         self.write_text_buf(&morph.english_name);
-        self.write_u8(match morph.control_panel {
-            ControlPanel::BottomLeft => 1,
-            ControlPanel::TopLeft => 2,
-            ControlPanel::TopRight => 3,
-            ControlPanel::BottomRight => 4,
-            ControlPanel::System => 0,
-        });
+        self.write_u8(morph.control_panel.into());
         match &morph.morph_data {
             MorphKinds::Group(x) => {
                 self.write_u8(0);
@@ -401,7 +516,7 @@ impl<W: Write> BinaryWriter<W> {
 
     fn write_material_morph(&mut self, morph: &MaterialMorph) {
         self.write_material_index(morph.index);
-        self.write_u8(morph.formula);
+        self.write_u8(morph.formula.into());
         self.write_vec4(morph.diffuse);
         self.write_vec3(morph.specular);
         self.write_f32(morph.specular_factor);
@@ -452,7 +567,7 @@ impl<W: Write> BinaryWriter<W> {
         self.write_text_buf(&rigid.name_en);
         self.write_bone_index(rigid.bone_index);
         self.write_u8(rigid.group);
-        self.write_u16(rigid.un_collision_group_flag);
+        self.write_u16(rigid.un_collision_group_flag.into());
         let form = match rigid.form {
             RigidForm::Sphere => 0,
             RigidForm::Box => 1,
@@ -554,6 +669,8 @@ impl<W: Write> BinaryWriter<W> {
             JointType::ConeTwist {
                 a_rigid_index,
                 b_rigid_index,
+                position,
+                rotation,
                 swing_span1,
                 swing_span2,
                 twist_span,
@@ -567,8 +684,6 @@ impl<W: Write> BinaryWriter<W> {
                 motor_target_in_constraint_space,
             } => {
                 let dummy = [0.0, 0.0, 0.0];
-                let position = dummy;
-                let rotation = dummy;
                 let move_limit_down = [damping, 0.0, if enable_motor { 1.0 } else { 0.0 }];
                 let move_limit_up = [fix_thresh, 0.0, max_motor_impulse];
                 let rotation_limit_down = [twist_span, swing_span2, swing_span1];
@@ -589,6 +704,8 @@ impl<W: Write> BinaryWriter<W> {
             JointType::Slider {
                 a_rigid_index,
                 b_rigid_index,
+                position,
+                rotation,
                 lower_linear_limit,
                 upper_linear_limit,
                 lower_angle_limit,
@@ -616,8 +733,8 @@ impl<W: Write> BinaryWriter<W> {
                 ];
                 self.write_rigid_index(a_rigid_index);
                 self.write_rigid_index(b_rigid_index);
-                self.write_vec3([0.0, 0.0, 0.0]);
-                self.write_vec3([0.0, 0.0, 0.0]);
+                self.write_vec3(position);
+                self.write_vec3(rotation);
                 self.write_vec3(move_limit_down);
                 self.write_vec3(move_limit_up);
                 self.write_vec3(rotation_limit_down);
@@ -628,6 +745,8 @@ impl<W: Write> BinaryWriter<W> {
             JointType::Hinge {
                 a_rigid_index,
                 b_rigid_index,
+                position,
+                rotation,
                 low,
                 high,
                 softness,
@@ -647,8 +766,8 @@ impl<W: Write> BinaryWriter<W> {
                 ];
                 self.write_rigid_index(a_rigid_index);
                 self.write_rigid_index(b_rigid_index);
-                self.write_vec3([0.0, 0.0, 0.0]);
-                self.write_vec3([0.0, 0.0, 0.0]);
+                self.write_vec3(position);
+                self.write_vec3(rotation);
                 self.write_vec3([0.0, 0.0, 0.0]);
                 self.write_vec3([0.0, 0.0, 0.0]);
                 self.write_vec3(rotation_limit_down);
@@ -668,8 +787,8 @@ impl<W: Write> BinaryWriter<W> {
         }
         self.write_material_index(soft_body.material_index);
         self.write_u8(soft_body.group);
-        self.write_u16(soft_body.un_collision_group_flag);
-        self.write_u8(soft_body.bit_flag);
+        self.write_u16(soft_body.un_collision_group_flag.into());
+        self.write_u8(soft_body.bit_flag.bits());
         self.write_i32(soft_body.b_link_create_distance);
         self.write_i32(soft_body.clusters);
         self.write_f32(soft_body.mass);
@@ -715,7 +834,7 @@ impl<W: Write> BinaryWriter<W> {
         self.write_i32(i32::try_from(soft_body.anchor_rigid.len()).unwrap());
         soft_body.anchor_rigid.iter().for_each(|anchor_rigid| {
             self.write_rigid_index(anchor_rigid.rigid_index);
-            self.write_vertex_index(anchor_rigid.vertex_index);
+            self.write_signed_vertex_index(anchor_rigid.vertex_index);
             self.write_bool(anchor_rigid.near_mode);
         });
 
@@ -724,7 +843,7 @@ impl<W: Write> BinaryWriter<W> {
         soft_body
             .pin_vertex
             .iter()
-            .for_each(|vertex_index| self.write_vertex_index(*vertex_index));
+            .for_each(|vertex_index| self.write_signed_vertex_index(*vertex_index));
     }
     write_bin_array!(write_vec4, Vec4);
     write_bin_array!(write_vec3, Vec3);
@@ -736,6 +855,9 @@ impl<W: Write> BinaryWriter<W> {
     write_bin!(write_i8, i8);
     write_bin!(write_u8, u8);
     /// write `true` as `1_u8` , `false` as `0_u8`
+    ///
+    /// reader takes other non zero bytes as `true` with
+    /// [`ReadWarning::InvalidBool`](crate::reader::ReadWarning::InvalidBool) in lenient mode.
     fn write_bool(&mut self, v: bool) {
         if v {
             self.write_u8(1);
@@ -744,3 +866,67 @@ impl<W: Write> BinaryWriter<W> {
         }
     }
 }
+
+/// element of a section, written by the method of [`BinaryWriter`] for it.
+pub(crate) trait WriteElement {
+    fn write_to<W: Write>(&self, writer: &mut BinaryWriter<W>);
+}
+
+macro_rules! impl_write_element {
+    ($($T:ty => $method:ident),*) => {
+        $(
+            impl WriteElement for $T {
+                fn write_to<W: Write>(&self, writer: &mut BinaryWriter<W>) {
+                    writer.$method(self)
+                }
+            }
+        )*
+    };
+}
+impl_write_element!(
+    ModelInfo => write_model_info,
+    Vertex => write_vertex,
+    Face => write_face,
+    String => write_text_buf,
+    Material => write_material,
+    Bone => write_bone,
+    Morph => write_morph,
+    Frame => write_frame,
+    Rigid => write_rigid,
+    Joint => write_joint,
+    SoftBody => write_soft_body
+);
+
+/// text fields of an element named as in `WritePMXErrors::TextTooLong`.
+pub(crate) trait TextFields {
+    fn text_fields(&self) -> Vec<(&'static str, &str)>;
+}
+
+macro_rules! impl_text_fields {
+    ($($T:ty => [$($field:ident),*]),*) => {
+        $(
+            impl TextFields for $T {
+                fn text_fields(&self) -> Vec<(&'static str, &str)> {
+                    vec![$((stringify!($field), &self.$field)),*]
+                }
+            }
+        )*
+    };
+}
+impl_text_fields!(
+    ModelInfo => [name, name_en, comment, comment_en],
+    Material => [name, english_name, memo],
+    Bone => [name, english_name],
+    Morph => [name, english_name],
+    Frame => [name, name_en],
+    Rigid => [name, name_en],
+    Joint => [name, name_en],
+    SoftBody => [name, name_en]
+);
+
+/// texture path is the only field.
+impl TextFields for String {
+    fn text_fields(&self) -> Vec<(&'static str, &str)> {
+        vec![("", self)]
+    }
+}
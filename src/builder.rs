@@ -0,0 +1,665 @@
+//! Builders filling the rest of fields with `MMD` conventional values.
+//!
+//! ```rust
+//! use PMXUtil::types::Material;
+//!
+//! let material = Material::builder("肌")
+//!     .diffuse([1.0, 0.9, 0.8, 1.0])
+//!     .texture(0)
+//!     .double_sided(true)
+//!     .edge([0.0, 0.0, 0.0, 1.0], 0.5)
+//!     .num_face_vertices(300)
+//!     .build();
+//! assert!(material.casts_ground_shadow());
+//! ```
+use crate::types::{
+    Bone, BoneIKInfo, ConnectionDisplayMode, IKLink, Material, MaterialFlags, Rigid,
+    RigidCalcMethod, RigidForm, RigidGroups, RotateAndTranslateInherits, SphereMode,
+    SphereModeKind, ToonMode, Vec3, Vec4,
+};
+use std::f32::consts::PI;
+
+/// builder of [`Material`]. created by [`Material::builder`].
+///
+/// # Defaults
+/// * diffuse: opaque white
+/// * ambient: half of diffuse color unless set by [`ambient`](Self::ambient)
+/// * specular: black with factor 5
+/// * toon: `Common(0)` (`toon01.bmp`)
+/// * flags: ground shadow, self shadow map, self shadow and edge
+/// * edge: black, size 1
+/// * no texture and no sphere
+/// * `num_face_vertices`: 0
+#[derive(Debug, Clone)]
+pub struct MaterialBuilder {
+    material: Material,
+    ambient: Option<Vec3>,
+}
+
+impl Material {
+    pub fn builder(name: &str) -> MaterialBuilder {
+        MaterialBuilder {
+            material: Material {
+                name: name.to_owned(),
+                english_name: String::new(),
+                diffuse: [1.0, 1.0, 1.0, 1.0],
+                specular: [0.0; 3],
+                specular_factor: 5.0,
+                ambient: [0.5; 3],
+                draw_mode: MaterialFlags::GROUND_SHADOW
+                    | MaterialFlags::DRAW_SHADOW
+                    | MaterialFlags::RECEIVE_SHADOW
+                    | MaterialFlags::HAS_EDGE,
+                edge_color: [0.0, 0.0, 0.0, 1.0],
+                edge_size: 1.0,
+                texture_index: -1,
+                sphere_mode: None,
+                toon_mode: ToonMode::Common(0),
+                memo: String::new(),
+                num_face_vertices: 0,
+            },
+            ambient: None,
+        }
+    }
+}
+
+impl MaterialBuilder {
+    pub fn english_name(mut self, english_name: &str) -> Self {
+        self.material.english_name = english_name.to_owned();
+        self
+    }
+    /// RGBA
+    pub fn diffuse(mut self, diffuse: Vec4) -> Self {
+        self.material.diffuse = diffuse;
+        self
+    }
+    pub fn specular(mut self, specular: Vec3, specular_factor: f32) -> Self {
+        self.material.specular = specular;
+        self.material.specular_factor = specular_factor;
+        self
+    }
+    pub fn ambient(mut self, ambient: Vec3) -> Self {
+        self.ambient = Some(ambient);
+        self
+    }
+    /// index in texture list.
+    pub fn texture(mut self, texture_index: i32) -> Self {
+        self.material.texture_index = texture_index;
+        self
+    }
+    /// index in texture list.
+    pub fn sphere(mut self, texture_index: i32, kind: SphereModeKind) -> Self {
+        self.material.sphere_mode = Some(SphereMode {
+            index: texture_index,
+            kind,
+        });
+        self
+    }
+    pub fn toon(mut self, toon_mode: ToonMode) -> Self {
+        self.material.toon_mode = toon_mode;
+        self
+    }
+    /// disable back face culling.
+    pub fn double_sided(self, double_sided: bool) -> Self {
+        self.flag(MaterialFlags::DISABLE_CULLING, double_sided)
+    }
+    /// ground shadow, self shadow map and self shadow at once.
+    pub fn shadow(self, shadow: bool) -> Self {
+        self.flag(
+            MaterialFlags::GROUND_SHADOW
+                | MaterialFlags::DRAW_SHADOW
+                | MaterialFlags::RECEIVE_SHADOW,
+            shadow,
+        )
+    }
+    /// draw edge with `color` (RGBA) and `size`.
+    pub fn edge(mut self, color: Vec4, size: f32) -> Self {
+        self.material.edge_color = color;
+        self.material.edge_size = size;
+        self.flag(MaterialFlags::HAS_EDGE, true)
+    }
+    pub fn no_edge(self) -> Self {
+        self.flag(MaterialFlags::HAS_EDGE, false)
+    }
+    /// set or clear any flags.
+    pub fn flag(mut self, flags: MaterialFlags, value: bool) -> Self {
+        self.material.draw_mode.set(flags, value);
+        self
+    }
+    pub fn memo(mut self, memo: &str) -> Self {
+        self.material.memo = memo.to_owned();
+        self
+    }
+    /// 3 times of face count using this material.
+    ///
+    /// faces of materials are stored in order so this must match faces written.
+    /// [`Writer::add_mesh`](crate::writer::Writer::add_mesh) sets it from faces instead.
+    pub fn num_face_vertices(mut self, num_face_vertices: i32) -> Self {
+        self.material.num_face_vertices = num_face_vertices;
+        self
+    }
+    pub fn build(self) -> Material {
+        let mut material = self.material;
+        material.ambient = self.ambient.unwrap_or_else(|| {
+            let [r, g, b, _] = material.diffuse;
+            [r * 0.5, g * 0.5, b * 0.5]
+        });
+        material
+    }
+}
+
+/// builder of [`Rigid`]. created by [`Rigid::builder`].
+///
+/// # Defaults
+/// * sphere of radius 1 at origin
+/// * follows bone (static)
+/// * mass 1, damping 0.5, repulsion 0, friction 0.5
+/// * group 0 and collides with all groups
+#[derive(Debug, Clone)]
+pub struct RigidBuilder {
+    rigid: Rigid,
+}
+
+impl Rigid {
+    /// `bone_index` is `-1` if rigid is not related to any bone.
+    pub fn builder(name: &str, bone_index: i32) -> RigidBuilder {
+        RigidBuilder {
+            rigid: Rigid {
+                name: name.to_owned(),
+                name_en: String::new(),
+                bone_index,
+                group: 0,
+                un_collision_group_flag: RigidGroups::empty(),
+                form: RigidForm::Sphere,
+                size: [1.0, 0.0, 0.0],
+                position: [0.0; 3],
+                rotation: [0.0; 3],
+                mass: 1.0,
+                move_resist: 0.5,
+                rotation_resist: 0.5,
+                repulsion: 0.0,
+                friction: 0.5,
+                calc_method: RigidCalcMethod::Static,
+            },
+        }
+    }
+}
+
+impl RigidBuilder {
+    pub fn english_name(mut self, name_en: &str) -> Self {
+        self.rigid.name_en = name_en.to_owned();
+        self
+    }
+    /// `size` is `[radius, _, _]` for sphere, `[width, height, depth]` (half) for box
+    /// and `[radius, height, _]` for capsule.
+    pub fn shape(mut self, form: RigidForm, size: Vec3) -> Self {
+        self.rigid.form = form;
+        self.rigid.size = size;
+        self
+    }
+    pub fn position(mut self, position: Vec3) -> Self {
+        self.rigid.position = position;
+        self
+    }
+    /// euler angles in radian.
+    pub fn rotation(mut self, rotation: Vec3) -> Self {
+        self.rigid.rotation = rotation;
+        self
+    }
+    pub fn mass(mut self, mass: f32) -> Self {
+        self.rigid.mass = mass;
+        self
+    }
+    pub fn damping(mut self, move_resist: f32, rotation_resist: f32) -> Self {
+        self.rigid.move_resist = move_resist;
+        self.rigid.rotation_resist = rotation_resist;
+        self
+    }
+    pub fn repulsion(mut self, repulsion: f32) -> Self {
+        self.rigid.repulsion = repulsion;
+        self
+    }
+    pub fn friction(mut self, friction: f32) -> Self {
+        self.rigid.friction = friction;
+        self
+    }
+    pub fn calc_method(mut self, calc_method: RigidCalcMethod) -> Self {
+        self.rigid.calc_method = calc_method;
+        self
+    }
+    /// 0 origin. see [`RigidGroups`].
+    pub fn group(mut self, group: u8) -> Self {
+        self.rigid.group = group;
+        self
+    }
+    /// do not collide with rigid bodies in `groups`.
+    pub fn no_collision_with(mut self, groups: RigidGroups) -> Self {
+        self.rigid.un_collision_group_flag = groups;
+        self
+    }
+    pub fn build(self) -> Rigid {
+        self.rigid
+    }
+}
+
+/// builder of [`Bone`]. created by [`Bone::builder`].
+///
+/// flags are derived from fields by [`Bone::calculate_bone_flag`] so builder only sets fields.
+///
+/// # Defaults
+/// * root bone (parent `-1`) at origin with deform depth 0
+/// * connected to no bone
+/// * visible, rotatable and controllable but not translatable
+#[derive(Debug, Clone)]
+pub struct BoneBuilder {
+    bone: Bone,
+}
+
+impl Bone {
+    pub fn builder(name: &str) -> BoneBuilder {
+        BoneBuilder {
+            bone: Bone {
+                name: name.to_owned(),
+                parent: -1,
+                rotatable_in_viewer: true,
+                display_bone_in_viewer: true,
+                controllable_in_viewer: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl BoneBuilder {
+    pub fn english_name(mut self, english_name: &str) -> Self {
+        self.bone.english_name = english_name.to_owned();
+        self
+    }
+    pub fn parent(mut self, parent: i32) -> Self {
+        self.bone.parent = parent;
+        self
+    }
+    pub fn position(mut self, position: Vec3) -> Self {
+        self.bone.position = position;
+        self
+    }
+    pub fn deform_depth(mut self, deform_depth: i32) -> Self {
+        self.bone.deform_depth = deform_depth;
+        self
+    }
+    /// display bone as pointing to `bone_index`.
+    pub fn connect_to(mut self, bone_index: i32) -> Self {
+        self.bone.connection_display_mode = ConnectionDisplayMode::OtherBone(bone_index);
+        self
+    }
+    /// display bone as pointing to `offset` from its position.
+    pub fn offset(mut self, offset: Vec3) -> Self {
+        self.bone.connection_display_mode = ConnectionDisplayMode::Offset(offset);
+        self
+    }
+    pub fn rotatable(mut self, rotatable: bool) -> Self {
+        self.bone.rotatable_in_viewer = rotatable;
+        self
+    }
+    pub fn translatable(mut self, translatable: bool) -> Self {
+        self.bone.translatable_in_viewer = translatable;
+        self
+    }
+    pub fn visible(mut self, visible: bool) -> Self {
+        self.bone.display_bone_in_viewer = visible;
+        self
+    }
+    pub fn controllable(mut self, controllable: bool) -> Self {
+        self.bone.controllable_in_viewer = controllable;
+        self
+    }
+    /// inherit rotation of `parent` multiplied by `weight`.
+    ///
+    /// combined with [`inherit_translation`](Self::inherit_translation) of the same
+    /// parent and weight. otherwise replaces it because PMX has only one inherit parent.
+    pub fn inherit_rotation(mut self, parent: i32, weight: f32) -> Self {
+        self.bone.inherits.rotate_and_translate = match self.bone.inherits.rotate_and_translate {
+            RotateAndTranslateInherits::Translate(p, w) if (p, w) == (parent, weight) => {
+                RotateAndTranslateInherits::Both(parent, weight)
+            }
+            _ => RotateAndTranslateInherits::Rotate(parent, weight),
+        };
+        self
+    }
+    /// inherit translation of `parent` multiplied by `weight`.
+    ///
+    /// see [`inherit_rotation`](Self::inherit_rotation).
+    pub fn inherit_translation(mut self, parent: i32, weight: f32) -> Self {
+        self.bone.inherits.rotate_and_translate = match self.bone.inherits.rotate_and_translate {
+            RotateAndTranslateInherits::Rotate(p, w) if (p, w) == (parent, weight) => {
+                RotateAndTranslateInherits::Both(parent, weight)
+            }
+            _ => RotateAndTranslateInherits::Translate(parent, weight),
+        };
+        self
+    }
+    /// inherit local transform instead of the one applied in the parent's parent space.
+    pub fn inherit_local(mut self, inherit_local: bool) -> Self {
+        self.bone.inherits.inherit_local = inherit_local;
+        self
+    }
+    /// restrict rotation to `axis`. typical for twist bones.
+    pub fn fixed_axis(mut self, axis: Vec3) -> Self {
+        self.bone.fixed_axis = Some(axis);
+        self
+    }
+    /// local X and Z axes used by `MMD` local rotation.
+    pub fn local_axes(mut self, x: Vec3, z: Vec3) -> Self {
+        self.bone.local_axis = Some((x, z));
+        self
+    }
+    pub fn physics_after_deform(mut self, physics_after_deform: bool) -> Self {
+        self.bone.physics_after_deform = physics_after_deform;
+        self
+    }
+    pub fn external_parent(mut self, key: i32) -> Self {
+        self.bone.external_parent = Some(key);
+        self
+    }
+    /// make this bone IK moving `target` by rotating `links`.
+    ///
+    /// `limit_angle` is the limit per iteration in radian.
+    /// each link is a bone index and optional `(min, max)` euler angles in radian.
+    pub fn ik<I>(mut self, target: i32, iterations: i32, limit_angle: f32, links: I) -> Self
+    where
+        I: IntoIterator<Item = (i32, Option<(Vec3, Vec3)>)>,
+    {
+        self.bone.ik_info = Some(BoneIKInfo {
+            ik_target_bone_index: target,
+            ik_iter_count: iterations,
+            ik_limit_angle: limit_angle,
+            ik_links: links
+                .into_iter()
+                .map(|(ik_bone_index, angle_limit)| IKLink {
+                    ik_bone_index,
+                    angle_limit,
+                })
+                .collect(),
+        });
+        self
+    }
+    pub fn build(self) -> Bone {
+        self.bone
+    }
+}
+
+/// the standard bone set of `MMD` models.
+///
+/// bone names follow the ones motions refer to. sizes are of a typical model about 20 units tall.
+/// leg IKs use the common parameters (40 iterations, knee bends only backward).
+pub fn standard_humanoid_skeleton() -> Vec<Bone> {
+    let mut bones: Vec<Bone> = vec![];
+    let index = |bones: &[Bone], name: &str| -> i32 {
+        let index = bones.iter().position(|bone| bone.name == name);
+        index
+            .map(|index| index as i32)
+            .expect("bone defined before use")
+    };
+    macro_rules! bone {
+        ($name:expr, $name_en:expr, $parent:expr, $position:expr) => {
+            Bone::builder($name)
+                .english_name($name_en)
+                .parent(if $parent.is_empty() {
+                    -1
+                } else {
+                    index(&bones, $parent)
+                })
+                .position($position)
+        };
+    }
+    bones.push(
+        bone!("全ての親", "master", "", [0.0; 3])
+            .translatable(true)
+            .offset([0.0, 1.0, 0.0])
+            .build(),
+    );
+    bones.push(
+        bone!("センター", "center", "全ての親", [0.0, 8.0, 0.0])
+            .translatable(true)
+            .offset([0.0, -1.0, 0.0])
+            .build(),
+    );
+    bones.push(
+        bone!("グルーブ", "groove", "センター", [0.0, 8.2, 0.0])
+            .translatable(true)
+            .offset([0.0, -1.0, 0.0])
+            .build(),
+    );
+    bones.push(bone!("腰", "waist", "グルーブ", [0.0, 12.0, 0.0]).build());
+    for (name, name_en, parent, position) in [
+        ("上半身", "upper body", "腰", [0.0, 12.5, 0.0]),
+        ("上半身2", "upper body2", "上半身", [0.0, 14.0, 0.0]),
+        ("首", "neck", "上半身2", [0.0, 16.5, 0.0]),
+        ("頭", "head", "首", [0.0, 17.5, 0.0]),
+    ] {
+        bones.push(bone!(name, name_en, parent, position).build());
+    }
+    // connect to the next bone in the chain
+    for (from, to) in [("上半身", "上半身2"), ("上半身2", "首"), ("首", "頭")] {
+        let to = index(&bones, to);
+        let from = index(&bones, from) as usize;
+        bones[from].connection_display_mode = ConnectionDisplayMode::OtherBone(to);
+    }
+    let head = index(&bones, "頭") as usize;
+    bones[head].connection_display_mode = ConnectionDisplayMode::Offset([0.0, 2.0, 0.0]);
+    bones.push(
+        bone!("両目", "eyes", "頭", [0.0, 19.0, -1.0])
+            .offset([0.0, 0.0, -1.0])
+            .deform_depth(2)
+            .build(),
+    );
+    bones.push(
+        bone!("下半身", "lower body", "腰", [0.0, 12.5, 0.0])
+            .offset([0.0, -2.0, 0.0])
+            .build(),
+    );
+    for (side, side_en, x) in [("左", "left", 1.0_f32), ("右", "right", -1.0)] {
+        let name = |name: &str| format!("{}{}", side, name);
+        let name_en = |name: &str| format!("{} {}", side_en, name);
+        let eyes = index(&bones, "両目");
+        bones.push(
+            bone!(&name("目"), &name_en("eye"), "頭", [0.3 * x, 19.0, -0.8])
+                .offset([0.0, 0.0, -1.0])
+                .inherit_rotation(eyes, 1.0)
+                .deform_depth(2)
+                .build(),
+        );
+        let chains: [(&str, &str, &str, Vec3); 8] = [
+            ("肩", "shoulder", "上半身2", [0.5 * x, 15.8, 0.0]),
+            ("腕", "arm", "肩", [1.5 * x, 15.5, 0.0]),
+            ("ひじ", "elbow", "腕", [3.5 * x, 13.8, 0.0]),
+            ("手首", "wrist", "ひじ", [5.3 * x, 12.2, -0.2]),
+            ("足", "leg", "下半身", [1.0 * x, 11.5, 0.0]),
+            ("ひざ", "knee", "足", [1.0 * x, 6.0, -0.2]),
+            ("足首", "ankle", "ひざ", [1.0 * x, 1.2, 0.5]),
+            ("つま先", "toe", "足首", [1.0 * x, 0.0, -1.6]),
+        ];
+        for (bone_name, bone_name_en, parent, position) in chains.iter() {
+            let parent = if *parent == "上半身2" || *parent == "下半身" {
+                parent.to_string()
+            } else {
+                name(parent)
+            };
+            bones.push(bone!(&name(bone_name), &name_en(bone_name_en), &parent, *position).build());
+        }
+        for (from, to) in [
+            ("肩", "腕"),
+            ("腕", "ひじ"),
+            ("ひじ", "手首"),
+            ("足", "ひざ"),
+            ("ひざ", "足首"),
+            ("足首", "つま先"),
+        ] {
+            let to = index(&bones, &name(to));
+            let from = index(&bones, &name(from)) as usize;
+            bones[from].connection_display_mode = ConnectionDisplayMode::OtherBone(to);
+        }
+        for (tip, offset) in [("手首", [1.0 * x, -0.8, 0.0]), ("つま先", [0.0, 0.0, -1.0])] {
+            let tip = index(&bones, &name(tip)) as usize;
+            bones[tip].connection_display_mode = ConnectionDisplayMode::Offset(offset);
+        }
+        let knee = index(&bones, &name("ひざ"));
+        let leg = index(&bones, &name("足"));
+        let ankle = index(&bones, &name("足首"));
+        let toe = index(&bones, &name("つま先"));
+        bones.push(
+            bone!(
+                &name("足ＩＫ"),
+                &name_en("leg IK"),
+                "全ての親",
+                [1.0 * x, 1.2, 0.5]
+            )
+            .translatable(true)
+            .offset([0.0, 0.0, 1.0])
+            .ik(
+                ankle,
+                40,
+                2.0,
+                vec![
+                    (
+                        knee,
+                        Some(([-PI, 0.0, 0.0], [-0.5_f32.to_radians(), 0.0, 0.0])),
+                    ),
+                    (leg, None),
+                ],
+            )
+            .deform_depth(1)
+            .build(),
+        );
+        let leg_ik = index(&bones, &name("足ＩＫ"));
+        bones.push(
+            bone!(
+                &name("つま先ＩＫ"),
+                &name_en("toe IK"),
+                "",
+                [1.0 * x, 0.0, -1.6]
+            )
+            .parent(leg_ik)
+            .translatable(true)
+            .offset([0.0, -1.0, 0.0])
+            .ik(toe, 3, 4.0, vec![(ankle, None)])
+            .deform_depth(1)
+            .build(),
+        );
+    }
+    bones
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builder::standard_humanoid_skeleton;
+    use crate::types::{
+        Bone, BoneFlags, ConnectionDisplayMode, Material, MaterialFlags, Rigid, RigidCalcMethod,
+        RigidForm, RigidGroups, RotateAndTranslateInherits, ToonMode,
+    };
+
+    #[test]
+    fn material_defaults() {
+        let material = Material::builder("材質")
+            .diffuse([0.8, 0.6, 0.4, 1.0])
+            .build();
+        assert_eq!(material.ambient, [0.4, 0.3, 0.2]);
+        assert_eq!(material.specular, [0.0; 3]);
+        assert_eq!(material.toon_mode, ToonMode::Common(0));
+        assert_eq!(
+            material.draw_mode,
+            MaterialFlags::GROUND_SHADOW
+                | MaterialFlags::DRAW_SHADOW
+                | MaterialFlags::RECEIVE_SHADOW
+                | MaterialFlags::HAS_EDGE
+        );
+        assert_eq!(material.texture_index, -1);
+        assert_eq!(material.sphere_mode, None);
+
+        let material = Material::builder("材質")
+            .ambient([0.1; 3])
+            .diffuse([1.0; 4])
+            .double_sided(true)
+            .no_edge()
+            .shadow(false)
+            .num_face_vertices(6)
+            .build();
+        assert_eq!(material.ambient, [0.1; 3]);
+        assert_eq!(material.draw_mode, MaterialFlags::DISABLE_CULLING);
+        assert_eq!(material.num_face_vertices, 6);
+    }
+
+    #[test]
+    fn rigid_builder() {
+        let rigid = Rigid::builder("髪", 3)
+            .shape(RigidForm::Capsule, [0.5, 2.0, 0.0])
+            .calc_method(RigidCalcMethod::Dynamic)
+            .group(2)
+            .no_collision_with(RigidGroups::single(2))
+            .build();
+        assert_eq!(rigid.bone_index, 3);
+        assert_eq!(rigid.form, RigidForm::Capsule);
+        assert_eq!(rigid.mass, 1.0);
+        let other = Rigid::builder("体", 0).group(2).build();
+        assert!(!rigid.collides_with(&other));
+    }
+
+    #[test]
+    fn bone_builder_flags() {
+        let bone = Bone::builder("腕捩")
+            .parent(1)
+            .connect_to(2)
+            .fixed_axis([1.0, 0.0, 0.0])
+            .inherit_rotation(3, 0.5)
+            .inherit_translation(3, 0.5)
+            .build();
+        assert_eq!(
+            bone.inherits.rotate_and_translate,
+            RotateAndTranslateInherits::Both(3, 0.5)
+        );
+        assert_eq!(
+            bone.calculate_bone_flag(),
+            BoneFlags::CONNECT_TO_OTHER_BONE
+                | BoneFlags::ROTATABLE
+                | BoneFlags::IS_VISIBLE
+                | BoneFlags::ENABLED
+                | BoneFlags::INHERIT_ROTATION
+                | BoneFlags::INHERIT_TRANSLATION
+                | BoneFlags::FIXED_AXIS
+        );
+        let bone = Bone::builder("IK")
+            .inherit_rotation(1, 1.0)
+            .inherit_translation(2, 1.0)
+            .ik(3, 40, 2.0, vec![(4, None)])
+            .build();
+        assert_eq!(
+            bone.inherits.rotate_and_translate,
+            RotateAndTranslateInherits::Translate(2, 1.0)
+        );
+        assert!(bone.calculate_bone_flag().contains(BoneFlags::IK));
+    }
+
+    #[test]
+    fn humanoid_skeleton() {
+        let bones = standard_humanoid_skeleton();
+        let index = |name: &str| bones.iter().position(|bone| bone.name == name).unwrap() as i32;
+        assert_eq!(bones.len(), 32);
+        for (i, bone) in bones.iter().enumerate() {
+            assert!(bone.parent < i as i32, "{} is before its parent", bone.name);
+            if let ConnectionDisplayMode::OtherBone(to) = bone.connection_display_mode {
+                assert!(-1 <= to && to < bones.len() as i32);
+            }
+        }
+        let leg_ik = &bones[index("左足ＩＫ") as usize];
+        let ik = leg_ik.ik_info.as_ref().unwrap();
+        assert_eq!(ik.ik_target_bone_index, index("左足首"));
+        assert_eq!(ik.ik_links[0].ik_bone_index, index("左ひざ"));
+        assert_eq!(bones[index("右ひじ") as usize].parent, index("右腕"));
+
+        let mut writer = crate::writer::Writer::begin_writer(true);
+        writer.set_model_info(&Default::default());
+        writer.add_bones(&bones);
+        let model = crate::test_support::read(&writer.write_to_vec().unwrap());
+        assert_eq!(model.bones, bones);
+    }
+}
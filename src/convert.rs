@@ -0,0 +1,2695 @@
+//! Re-encode existing PMX and rewrite models for redistribution or other pipelines.
+//!
+//! ```rust
+//! use PMXUtil::convert::{transcode, TranscodeOptions};
+//! use PMXUtil::types::Encode;
+//!
+//! # let mut writer = PMXUtil::writer::Writer::begin_writer(true);
+//! # writer.set_model_info(&Default::default());
+//! # let input = writer.write_to_vec().unwrap();
+//! let mut output = vec![];
+//! let options = TranscodeOptions {
+//!     encode: Some(Encode::UTF8),
+//!     ..Default::default()
+//! };
+//! let report = transcode(&input[..], &mut output, options).unwrap();
+//! assert_eq!(report.new_encode, Encode::UTF8);
+//! println!("{}", report);
+//! ```
+use crate::binary_writer::BinaryWriter;
+use crate::edit::shift_bones;
+use crate::frames::{
+    add_bone_to, expression_frame, is_special_frame, root_frame, DEFAULT_BONE_FRAME_NAME,
+    EXPRESSION_FRAME_NAME, ROOT_FRAME_NAME,
+};
+use crate::morph::{remap_indices, IndexMap};
+use crate::progress::SectionKind;
+use crate::reader::{ModelInfoStage, PmxVisitor, ReadError};
+use crate::types::{
+    Bone, ConnectionDisplayMode, ControlPanel, Encode, Face, Frame, FrameInner, Header, Joint,
+    Material, MaterialMorph, MaterialMorphFormula, Model, ModelInfo, Morph, MorphKinds, PMXVersion,
+    Rigid, RotateAndTranslateInherits, SoftBody, Vec3, Vertex, VertexWeight,
+};
+use crate::usage::material_faces;
+use crate::validation::{SemiStandardBone, Side};
+use crate::writer::{
+    joint_requires_v21, material_requires_v21, morph_requires_v21, section_count,
+    vertex_requires_v21, WritePMXErrors, Writer,
+};
+use std::collections::HashSet;
+use std::convert::{TryFrom, TryInto};
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use std::mem::replace;
+
+/// options for [`transcode`]. default keeps everything.
+#[derive(Debug, Clone, Default)]
+pub struct TranscodeOptions {
+    /// text encoding of output. `None` keeps the input one.
+    pub encode: Option<Encode>,
+    /// shrink index sizes to the smallest ones fitting element counts.
+    ///
+    /// whole model is loaded into memory because the sizes are in header.
+    /// otherwise sections are converted element by element.
+    pub reoptimize_indices: bool,
+    /// version of output. `None` keeps the input one.
+    pub force_version: Option<PMXVersion>,
+}
+
+/// bytes used by each index kind.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct IndexSizes {
+    pub vertex: u8,
+    pub texture: u8,
+    pub material: u8,
+    pub bone: u8,
+    pub morph: u8,
+    pub rigid_body: u8,
+}
+
+impl From<&Header> for IndexSizes {
+    fn from(header: &Header) -> Self {
+        Self {
+            vertex: u8::from(header.s_vertex_index),
+            texture: u8::from(header.s_texture_index),
+            material: u8::from(header.s_material_index),
+            bone: u8::from(header.s_bone_index),
+            morph: u8::from(header.s_morph_index),
+            rigid_body: u8::from(header.s_rigid_body_index),
+        }
+    }
+}
+
+/// what [`transcode`] changed.
+#[derive(Debug, Clone)]
+pub struct TranscodeReport {
+    pub old_encode: Encode,
+    pub new_encode: Encode,
+    pub old_version: PMXVersion,
+    pub new_version: PMXVersion,
+    pub old_index_sizes: IndexSizes,
+    pub new_index_sizes: IndexSizes,
+    /// bytes read from input.
+    pub old_size: u64,
+    /// bytes written to output.
+    pub new_size: u64,
+}
+
+impl TranscodeReport {
+    /// `new_size - old_size`
+    pub fn size_delta(&self) -> i64 {
+        self.new_size as i64 - self.old_size as i64
+    }
+}
+
+impl Display for TranscodeReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "encode: {:?} -> {:?}", self.old_encode, self.new_encode)?;
+        writeln!(
+            f,
+            "version: {:?} -> {:?}",
+            self.old_version, self.new_version
+        )?;
+        writeln!(
+            f,
+            "index sizes: {:?} -> {:?}",
+            self.old_index_sizes, self.new_index_sizes
+        )?;
+        write!(
+            f,
+            "size: {} -> {} ({:+} bytes)",
+            self.old_size,
+            self.new_size,
+            self.size_delta()
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum TranscodeError {
+    /// input is not PMX.
+    InvalidHeader,
+    /// input is broken. output is left incomplete.
+    Read(ReadError),
+    Write(WritePMXErrors),
+}
+
+impl Display for TranscodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TranscodeError::InvalidHeader => write!(f, "input is not PMX"),
+            TranscodeError::Read(err) => err.fmt(f),
+            TranscodeError::Write(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for TranscodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TranscodeError::Read(err) => Some(err),
+            TranscodeError::Write(err) => Some(err),
+            TranscodeError::InvalidHeader => None,
+        }
+    }
+}
+
+impl From<ReadError> for TranscodeError {
+    fn from(err: ReadError) -> Self {
+        Self::Read(err)
+    }
+}
+
+impl From<WritePMXErrors> for TranscodeError {
+    fn from(err: WritePMXErrors) -> Self {
+        Self::Write(err)
+    }
+}
+
+/// read PMX from `input` and write it to `output` converted by `options`.
+///
+/// # Errors
+/// * `TranscodeError::InvalidHeader` if input is not PMX.
+/// * `TranscodeError::Read` if input is broken, e.g. truncated.
+/// * `TranscodeError::Write(WritePMXErrors::RequiresV21)` if PMX 2.0 was forced for a model
+///   using 2.1 only elements. output is left incomplete.
+/// * `TranscodeError::Write` if failed to write.
+pub fn transcode<R: Read, W: Write>(
+    input: R,
+    output: W,
+    options: TranscodeOptions,
+) -> Result<TranscodeReport, TranscodeError> {
+    let mut input = Counting::new(input);
+    let mut output = Counting::new(output);
+    let stage = ModelInfoStage::from_reader(&mut input).ok_or(TranscodeError::InvalidHeader)?;
+    let old_header = stage.get_header();
+    let encode = options.encode.unwrap_or(old_header.encode);
+    let version = options.force_version.unwrap_or(old_header.version);
+    let new_header = if options.reoptimize_indices {
+        let mut writer = Writer::from(stage.try_read_model()?);
+        writer.set_encode(encode);
+        writer.set_version(version);
+        // the encoding is asked for as in the streamed path, e.g. kept from the input.
+        writer.set_mmd_compat(false);
+        let (header, _) = writer.calculate_header(&Default::default())?;
+        writer.write(&mut output)?;
+        header
+    } else {
+        let header = Header {
+            encode,
+            version,
+            raw_version: None,
+            ..old_header
+        };
+        stream(stage, header, &mut output)?;
+        header
+    };
+    Ok(TranscodeReport {
+        old_encode: old_header.encode,
+        new_encode: new_header.encode,
+        old_version: old_header.version,
+        new_version: new_header.version,
+        old_index_sizes: IndexSizes::from(&old_header),
+        new_index_sizes: IndexSizes::from(&new_header),
+        old_size: input.count,
+        new_size: output.count,
+    })
+}
+
+/// copy all sections of `stage` to `output` element by element using `header`.
+fn stream<R: Read, W: Write>(
+    stage: ModelInfoStage<R>,
+    header: Header,
+    output: W,
+) -> Result<(), TranscodeError> {
+    let input_version = stage.get_header().version;
+    let mut visitor = StreamVisitor {
+        downgrade: input_version == PMXVersion::V21 && header.version == PMXVersion::V20,
+        writer: BinaryWriter::from_writer(output, header),
+        section: SectionKind::ModelInfo,
+        element: 0,
+        error: None,
+    };
+    visitor.writer.write_header();
+    let read = stage.try_visit(&mut visitor);
+    // a write error comes first if any because nothing is visited after broken input.
+    if let Some(err) = visitor.error {
+        return Err(err.into());
+    }
+    read?;
+    if input_version == PMXVersion::V20 && visitor.writer.header.version == PMXVersion::V21 {
+        // empty soft body section
+        visitor.writer.write_i32(0);
+    }
+    visitor.writer.inner.flush().map_err(WritePMXErrors::from)?;
+    Ok(())
+}
+
+struct StreamVisitor<W: Write> {
+    writer: BinaryWriter<W>,
+    /// input is 2.1 and output is 2.0
+    downgrade: bool,
+    /// section being written and index of the next element in it.
+    section: SectionKind,
+    element: usize,
+    /// first error. nothing is written after it.
+    error: Option<WritePMXErrors>,
+}
+
+impl<W: Write> StreamVisitor<W> {
+    /// write `element` unless failed already or it needs 2.1 in 2.0 output.
+    fn write<T>(
+        &mut self,
+        element: &T,
+        requires_v21: impl Fn(&T) -> bool,
+        f: impl FnOnce(&mut BinaryWriter<W>, &T),
+    ) {
+        if self.error.is_some() {
+            return;
+        }
+        if self.downgrade && requires_v21(element) {
+            self.error = Some(WritePMXErrors::RequiresV21);
+            return;
+        }
+        f(&mut self.writer, element);
+        if let Err(err) = self.writer.check_indices(self.section, self.element) {
+            self.error = Some(err);
+        }
+        self.element += 1;
+    }
+}
+
+impl<W: Write> PmxVisitor for StreamVisitor<W> {
+    fn section_start(&mut self, section: SectionKind, count: usize) {
+        if self.error.is_some() {
+            return;
+        }
+        self.section = section;
+        self.element = 0;
+        let count = match section {
+            SectionKind::ModelInfo => return,
+            SectionKind::Faces => count * 3,
+            SectionKind::SoftBodies if self.downgrade => {
+                if count > 0 {
+                    self.error = Some(WritePMXErrors::RequiresV21);
+                }
+                return;
+            }
+            _ => count,
+        };
+        match section_count(section, count) {
+            Ok(count) => self.writer.write_i32(count),
+            Err(err) => self.error = Some(err),
+        }
+    }
+    fn model_info(&mut self, model_info: ModelInfo) {
+        self.write(&model_info, |_| false, BinaryWriter::write_model_info);
+    }
+    fn vertex(&mut self, vertex: Vertex) {
+        self.write(&vertex, vertex_requires_v21, BinaryWriter::write_vertex);
+    }
+    fn face(&mut self, face: Face) {
+        self.write(&face, |_| false, BinaryWriter::write_face);
+    }
+    fn texture(&mut self, texture: String) {
+        self.write(
+            &texture,
+            |_| false,
+            |writer, texture| writer.write_text_buf(texture),
+        );
+    }
+    fn material(&mut self, material: Material) {
+        self.write(
+            &material,
+            material_requires_v21,
+            BinaryWriter::write_material,
+        );
+    }
+    fn bone(&mut self, bone: Bone) {
+        self.write(&bone, |_| false, BinaryWriter::write_bone);
+    }
+    fn morph(&mut self, morph: Morph) {
+        self.write(&morph, morph_requires_v21, BinaryWriter::write_morph);
+    }
+    fn frame(&mut self, frame: Frame) {
+        self.write(&frame, |_| false, BinaryWriter::write_frame);
+    }
+    fn rigid(&mut self, rigid: Rigid) {
+        self.write(&rigid, |_| false, BinaryWriter::write_rigid);
+    }
+    fn joint(&mut self, joint: Joint) {
+        self.write(&joint, joint_requires_v21, BinaryWriter::write_joint);
+    }
+    fn soft_body(&mut self, soft_body: SoftBody) {
+        // count check in `section_start` rejects soft bodies in downgrade
+        self.write(&soft_body, |_| false, BinaryWriter::write_soft_body);
+    }
+}
+
+/// counts bytes passing through.
+struct Counting<T> {
+    inner: T,
+    count: u64,
+}
+
+impl<T> Counting<T> {
+    fn new(inner: T) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.count += len as u64;
+        Ok(len)
+    }
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.count += len as u64;
+        Ok(len)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// options for [`strip_metadata`]. default only clears comments.
+#[derive(Debug, Clone, Default)]
+pub struct StripOptions {
+    /// text replacing model comments and material memos. empty clears them.
+    pub comment: String,
+    /// replace names of model and elements with identifiers like `bone_0`.
+    ///
+    /// special display frames keep their names because `MMD` finds them by name.
+    pub rename: bool,
+    /// replace texture paths with empty ones.
+    pub clear_texture_paths: bool,
+}
+
+/// japanese and english name of an element.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Names {
+    pub name: String,
+    pub name_en: String,
+}
+
+/// original names replaced by [`strip_metadata`]. save it to map names back.
+///
+/// each list is indexed same as the model. lists not replaced are empty.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NameMapping {
+    pub model: Option<Names>,
+    pub materials: Vec<Names>,
+    pub bones: Vec<Names>,
+    pub morphs: Vec<Names>,
+    pub frames: Vec<Names>,
+    pub rigid_bodies: Vec<Names>,
+    pub joints: Vec<Names>,
+    pub soft_bodies: Vec<Names>,
+    pub textures: Vec<String>,
+}
+
+impl NameMapping {
+    /// put original names and texture paths back. comments are not restored.
+    pub fn restore(&self, model: &mut Model) {
+        if let Some(names) = &self.model {
+            model.model_info.name = names.name.clone();
+            model.model_info.name_en = names.name_en.clone();
+        }
+        macro_rules! restore {
+            ($list:ident, $name_en:ident) => {
+                for (element, names) in model.$list.iter_mut().zip(&self.$list) {
+                    element.name = names.name.clone();
+                    element.$name_en = names.name_en.clone();
+                }
+            };
+        }
+        restore!(materials, english_name);
+        restore!(bones, english_name);
+        restore!(morphs, english_name);
+        restore!(frames, name_en);
+        restore!(rigid_bodies, name_en);
+        restore!(joints, name_en);
+        restore!(soft_bodies, name_en);
+        for (path, original) in model.textures.textures.iter_mut().zip(&self.textures) {
+            *path = original.clone();
+        }
+    }
+}
+
+/// remove personal information from `model` before sharing it.
+///
+/// nothing in PMX refers elements by name so renaming keeps the model valid.
+/// motions do refer bones and morphs by name. use returned mapping to retarget them.
+///
+/// # Examples
+///
+/// ```
+/// use PMXUtil::convert::{strip_metadata, StripOptions};
+/// use PMXUtil::types::{Bone, Model};
+///
+/// let mut model = Model::default();
+/// model.model_info.comment = "配布禁止".to_owned();
+/// model.bones.push(Bone::builder("センター").build());
+/// let options = StripOptions {
+///     rename: true,
+///     ..Default::default()
+/// };
+/// let mapping = strip_metadata(&mut model, &options);
+/// assert_eq!(model.model_info.comment, "");
+/// assert_eq!(model.bones[0].name, "bone_0");
+/// assert_eq!(mapping.bones[0].name, "センター");
+/// ```
+pub fn strip_metadata(model: &mut Model, options: &StripOptions) -> NameMapping {
+    let info = &mut model.model_info;
+    info.comment = options.comment.clone();
+    info.comment_en = options.comment.clone();
+    for material in &mut model.materials {
+        material.memo = options.comment.clone();
+    }
+    let mut mapping = NameMapping::default();
+    if options.clear_texture_paths {
+        mapping.textures = model
+            .textures
+            .textures
+            .iter_mut()
+            .map(std::mem::take)
+            .collect();
+    }
+    if !options.rename {
+        return mapping;
+    }
+    let rename = |prefix: &str, index: usize, name: &mut String, name_en: &mut String| {
+        let id = format!("{}_{}", prefix, index);
+        Names {
+            name: replace(name, id.clone()),
+            name_en: replace(name_en, id),
+        }
+    };
+    mapping.model = Some(rename("model", 0, &mut info.name, &mut info.name_en));
+    macro_rules! rename {
+        ($list:ident, $prefix:expr, $name_en:ident) => {
+            mapping.$list = model
+                .$list
+                .iter_mut()
+                .enumerate()
+                .map(|(i, element)| rename($prefix, i, &mut element.name, &mut element.$name_en))
+                .collect();
+        };
+    }
+    rename!(materials, "material", english_name);
+    rename!(bones, "bone", english_name);
+    rename!(morphs, "morph", english_name);
+    rename!(rigid_bodies, "rigid", name_en);
+    rename!(joints, "joint", name_en);
+    rename!(soft_bodies, "soft_body", name_en);
+    mapping.frames = model
+        .frames
+        .iter_mut()
+        .enumerate()
+        .map(|(i, frame)| {
+            if frame.is_special {
+                Names {
+                    name: frame.name.clone(),
+                    name_en: frame.name_en.clone(),
+                }
+            } else {
+                rename("frame", i, &mut frame.name, &mut frame.name_en)
+            }
+        })
+        .collect();
+    mapping
+}
+
+/// how [`dedupe_names`] makes new names.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DedupeStrategy {
+    /// `name+1`, `name+2`, ... like `PMXEditor` does.
+    PlusSuffix,
+    /// `{name}` and `{n}` in the pattern are replaced by the original name and a number from 1.
+    Pattern(String),
+}
+
+impl DedupeStrategy {
+    fn candidate(&self, name: &str, n: usize) -> String {
+        match self {
+            DedupeStrategy::PlusSuffix => format!("{}+{}", name, n),
+            DedupeStrategy::Pattern(pattern) => pattern
+                .replace("{name}", name)
+                .replace("{n}", &n.to_string()),
+        }
+    }
+}
+
+/// name changed by [`dedupe_names`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rename {
+    /// index of the element.
+    pub index: usize,
+    pub old: String,
+    pub new: String,
+}
+
+/// names changed by [`dedupe_names`]. use it to fix names in motions.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DedupeMapping {
+    pub bones: Vec<Rename>,
+    pub morphs: Vec<Rename>,
+    pub materials: Vec<Rename>,
+    pub rigid_bodies: Vec<Rename>,
+    pub frames: Vec<Rename>,
+}
+
+/// rename elements so that japanese names are unique in each list.
+///
+/// the first element keeps its name and later ones get the first candidate of `strategy`
+/// colliding with no name. see also
+/// [`validation::find_duplicate_names`](crate::validation::find_duplicate_names).
+///
+/// # Examples
+///
+/// ```
+/// use PMXUtil::convert::{dedupe_names, DedupeStrategy};
+/// use PMXUtil::types::{Bone, Model};
+///
+/// let mut model = Model::default();
+/// model.bones = vec![Bone::builder("右腕").build(); 2];
+/// let mapping = dedupe_names(&mut model, &DedupeStrategy::PlusSuffix);
+/// assert_eq!(model.bones[1].name, "右腕+1");
+/// assert_eq!(mapping.bones[0].index, 1);
+/// ```
+pub fn dedupe_names(model: &mut Model, strategy: &DedupeStrategy) -> DedupeMapping {
+    DedupeMapping {
+        bones: dedupe(model.bones.iter_mut().map(|bone| &mut bone.name), strategy),
+        morphs: dedupe(
+            model.morphs.iter_mut().map(|morph| &mut morph.name),
+            strategy,
+        ),
+        materials: dedupe(
+            model
+                .materials
+                .iter_mut()
+                .map(|material| &mut material.name),
+            strategy,
+        ),
+        rigid_bodies: dedupe(
+            model.rigid_bodies.iter_mut().map(|rigid| &mut rigid.name),
+            strategy,
+        ),
+        frames: dedupe(
+            model.frames.iter_mut().map(|frame| &mut frame.name),
+            strategy,
+        ),
+    }
+}
+
+fn dedupe<'a>(
+    names: impl Iterator<Item = &'a mut String>,
+    strategy: &DedupeStrategy,
+) -> Vec<Rename> {
+    let names = names.collect::<Vec<_>>();
+    let mut taken = names
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<HashSet<_>>();
+    let mut seen = HashSet::new();
+    let mut renames = vec![];
+    for (index, name) in names.into_iter().enumerate() {
+        if seen.insert(name.clone()) {
+            continue;
+        }
+        let new = (1..)
+            .map(|n| strategy.candidate(name, n))
+            .find(|candidate| !taken.contains(candidate))
+            .expect("some candidate is free");
+        taken.insert(new.clone());
+        seen.insert(new.clone());
+        renames.push(Rename {
+            index,
+            old: replace(name, new.clone()),
+            new,
+        });
+    }
+    renames
+}
+
+/// morph specified by index or name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MorphIndexOrName {
+    Index(i32),
+    Name(String),
+}
+
+impl From<i32> for MorphIndexOrName {
+    fn from(index: i32) -> Self {
+        Self::Index(index)
+    }
+}
+
+impl From<&str> for MorphIndexOrName {
+    fn from(name: &str) -> Self {
+        Self::Name(name.to_owned())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BakeError {
+    /// no morph has the index or name.
+    UnknownMorph(MorphIndexOrName),
+    /// flip and impulse morphs depend on runtime state.
+    Unsupported { index: i32, kind: &'static str },
+}
+
+impl Display for BakeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BakeError::UnknownMorph(morph) => write!(f, "unknown morph {:?}", morph),
+            BakeError::Unsupported { index, kind } => {
+                write!(f, "morph {} is {} morph which can't be baked", index, kind)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BakeError {}
+
+/// apply morphs at given weights to base data making the result new neutral shape.
+///
+/// * vertex and uv morphs move vertices.
+/// * material morphs change materials. texture factors are ignored because base has none.
+///   offsets of [`MaterialMorphFormula::Unknown`] are skipped.
+/// * bone morphs translate rest positions of the bone and its descendants together with
+///   vertices weighted to them and rigid bodies on them. joints are not moved.
+///   rotations are ignored because bones have no rest rotation.
+/// * group morphs apply their children multiplied by the factor.
+///
+/// morphs are additive so other morphs stay relative to the new base as is.
+/// `remove_baked` removes morphs in `weights` and entries referring them in
+/// group morphs, flip morphs and display frames.
+///
+/// # Errors
+/// * `BakeError::UnknownMorph` if a morph is not found.
+/// * `BakeError::Unsupported` if flip or impulse morph is given directly or by group.
+///
+/// model is unchanged on error.
+pub fn bake_morphs(
+    model: &mut Model,
+    weights: &[(MorphIndexOrName, f32)],
+    remove_baked: bool,
+) -> Result<(), BakeError> {
+    let mut baked = vec![];
+    let mut flat = vec![0.0; model.morphs.len()];
+    for (morph, weight) in weights {
+        let index = match morph {
+            MorphIndexOrName::Index(index) => usize::try_from(*index)
+                .ok()
+                .filter(|index| *index < model.morphs.len()),
+            MorphIndexOrName::Name(name) => model.morphs.iter().position(|m| &m.name == name),
+        }
+        .ok_or_else(|| BakeError::UnknownMorph(morph.clone()))?;
+        flatten_morph(&model.morphs, index, *weight, &mut flat, 0)?;
+        baked.push(index);
+    }
+    let mut bone_translations = vec![[0.0; 3]; model.bones.len()];
+    for (morph, weight) in model.morphs.iter().zip(&flat) {
+        if *weight != 0.0 {
+            apply_morph(
+                &mut model.vertices,
+                &mut model.materials,
+                &mut bone_translations,
+                morph,
+                *weight,
+            );
+        }
+    }
+    translate_bones(model, &bone_translations);
+    if remove_baked {
+        remove_morphs(model, &baked);
+    }
+    Ok(())
+}
+
+/// add weight of `index` to `flat` expanding group morphs.
+fn flatten_morph(
+    morphs: &[Morph],
+    index: usize,
+    weight: f32,
+    flat: &mut [f32],
+    depth: usize,
+) -> Result<(), BakeError> {
+    let unsupported = || BakeError::Unsupported {
+        index: index as i32,
+        kind: morphs[index].morph_data.kind_name(),
+    };
+    match &morphs[index].morph_data {
+        // PMX doesn't allow nested group. depth limit only guards cycles.
+        MorphKinds::Group(children) if depth < 8 => {
+            for child in children {
+                let child_index = usize::try_from(child.index)
+                    .ok()
+                    .filter(|index| *index < morphs.len())
+                    .ok_or(BakeError::UnknownMorph(MorphIndexOrName::Index(
+                        child.index,
+                    )))?;
+                flatten_morph(
+                    morphs,
+                    child_index,
+                    weight * child.morph_factor,
+                    flat,
+                    depth + 1,
+                )?;
+            }
+        }
+        MorphKinds::Flip(_) | MorphKinds::Impulse(_) => return Err(unsupported()),
+        _ => flat[index] += weight,
+    }
+    Ok(())
+}
+fn get<T>(list: &mut [T], index: impl TryInto<usize>) -> Option<&mut T> {
+    index
+        .try_into()
+        .ok()
+        .and_then(move |index| list.get_mut(index))
+}
+
+fn add_scaled<const N: usize>(base: &mut [f32; N], offset: &[f32], weight: f32) {
+    for (x, offset) in base.iter_mut().zip(offset) {
+        *x += offset * weight;
+    }
+}
+
+/// apply `morph` at `weight`. bone translations are accumulated to `bone_translations`.
+fn apply_morph(
+    vertices: &mut [Vertex],
+    materials: &mut [Material],
+    bone_translations: &mut [Vec3],
+    morph: &Morph,
+    weight: f32,
+) {
+    let uv_channel = match morph.morph_data {
+        MorphKinds::UV1(_) => 0,
+        MorphKinds::UV2(_) => 1,
+        MorphKinds::UV3(_) => 2,
+        MorphKinds::UV4(_) => 3,
+        _ => 0,
+    };
+    match &morph.morph_data {
+        MorphKinds::Vertex(offsets) => {
+            for offset in offsets {
+                if let Some(vertex) = get(vertices, offset.index) {
+                    add_scaled(&mut vertex.position, &offset.offset, weight);
+                }
+            }
+        }
+        MorphKinds::UV(offsets) => {
+            for offset in offsets {
+                if let Some(vertex) = get(vertices, offset.index) {
+                    add_scaled(&mut vertex.uv, &offset.offset, weight);
+                }
+            }
+        }
+        MorphKinds::UV1(offsets)
+        | MorphKinds::UV2(offsets)
+        | MorphKinds::UV3(offsets)
+        | MorphKinds::UV4(offsets) => {
+            for offset in offsets {
+                if let Some(vertex) = get(vertices, offset.index) {
+                    add_scaled(&mut vertex.add_uv[uv_channel], &offset.offset, weight);
+                }
+            }
+        }
+        MorphKinds::Bone(offsets) => {
+            for offset in offsets {
+                if let Some(translation) = get(bone_translations, offset.index) {
+                    add_scaled(translation, &offset.translates, weight);
+                }
+            }
+        }
+        MorphKinds::Material(offsets) => {
+            for offset in offsets {
+                if offset.index == -1 {
+                    for material in materials.iter_mut() {
+                        apply_material_morph(material, offset, weight);
+                    }
+                } else if let Some(material) = get(materials, offset.index) {
+                    apply_material_morph(material, offset, weight);
+                }
+            }
+        }
+        MorphKinds::Group(_) | MorphKinds::Flip(_) | MorphKinds::Impulse(_) => {}
+    }
+}
+
+fn apply_material_morph(material: &mut Material, morph: &MaterialMorph, weight: f32) {
+    fn apply<const N: usize>(
+        formula: MaterialMorphFormula,
+        base: &mut [f32; N],
+        value: [f32; N],
+        weight: f32,
+    ) {
+        for (x, value) in base.iter_mut().zip(value.iter()) {
+            match formula {
+                MaterialMorphFormula::Multiply => *x *= 1.0 + (value - 1.0) * weight,
+                MaterialMorphFormula::Additive => *x += value * weight,
+                // meaning is unknown so material is left as is.
+                MaterialMorphFormula::Unknown(_) => {}
+            }
+        }
+    }
+    let formula = morph.formula;
+    apply(formula, &mut material.diffuse, morph.diffuse, weight);
+    apply(formula, &mut material.specular, morph.specular, weight);
+    let mut specular_factor = [material.specular_factor];
+    apply(
+        formula,
+        &mut specular_factor,
+        [morph.specular_factor],
+        weight,
+    );
+    material.specular_factor = specular_factor[0];
+    apply(formula, &mut material.ambient, morph.ambient, weight);
+    apply(formula, &mut material.edge_color, morph.edge_color, weight);
+    let mut edge_size = [material.edge_size];
+    apply(formula, &mut edge_size, [morph.edge_size], weight);
+    material.edge_size = edge_size[0];
+}
+
+/// move bones, vertices and rigid bodies by bone morph translations.
+///
+/// a bone moves by sum of translations of itself and its ancestors.
+fn translate_bones(model: &mut Model, translations: &[Vec3]) {
+    if translations
+        .iter()
+        .all(|translation| *translation == [0.0; 3])
+    {
+        return;
+    }
+    let bones = &model.bones;
+    let accumulated = (0..bones.len())
+        .map(|mut index| {
+            let mut sum = [0.0; 3];
+            // step limit guards broken parent loops
+            for _ in 0..bones.len() {
+                add_scaled(&mut sum, &translations[index], 1.0);
+                match usize::try_from(bones[index].parent) {
+                    Ok(parent) if parent < bones.len() => index = parent,
+                    _ => break,
+                }
+            }
+            sum
+        })
+        .collect::<Vec<Vec3>>();
+    let moved = |bone: i32| {
+        usize::try_from(bone)
+            .ok()
+            .and_then(|bone| accumulated.get(bone))
+            .copied()
+            .unwrap_or_default()
+    };
+    for (bone, translation) in model.bones.iter_mut().zip(&accumulated) {
+        add_scaled(&mut bone.position, translation, 1.0);
+    }
+    for vertex in &mut model.vertices {
+        let mut displacement = [0.0; 3];
+        for (bone, weight) in vertex.weight_type.bone_weights() {
+            add_scaled(&mut displacement, &moved(bone), weight);
+        }
+        add_scaled(&mut vertex.position, &displacement, 1.0);
+        if let VertexWeight::SDEF {
+            sdef_c,
+            sdef_r0,
+            sdef_r1,
+            ..
+        } = &mut vertex.weight_type
+        {
+            for point in [sdef_c, sdef_r0, sdef_r1] {
+                add_scaled(point, &displacement, 1.0);
+            }
+        }
+    }
+    for rigid in &mut model.rigid_bodies {
+        add_scaled(&mut rigid.position, &moved(rigid.bone_index), 1.0);
+    }
+}
+
+/// remove `removed` morphs and references to them.
+fn remove_morphs(model: &mut Model, removed: &[usize]) {
+    let map = IndexMap::removing(SectionKind::Morphs, model.morphs.len(), removed);
+    let mut index = 0;
+    model.morphs.retain(|_| {
+        index += 1;
+        !removed.contains(&(index - 1))
+    });
+    for morph in &mut model.morphs {
+        remap_indices(morph, &map);
+    }
+    for frame in &mut model.frames {
+        frame.inners.retain_mut(|inner| match inner {
+            FrameInner::Morph(index) => map.get(*index).map(|new| *index = new).is_some(),
+            FrameInner::Bone(_) => true,
+        });
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ReorderError {
+    /// `new_order` has `len` entries but the model has `expected` elements of `section`.
+    LengthMismatch {
+        section: SectionKind,
+        len: usize,
+        expected: usize,
+    },
+    /// index in `new_order` is out of range or appears twice.
+    InvalidIndex(usize),
+    /// sum of `num_face_vertices` of materials differs from 3 times of face count.
+    FaceCountMismatch,
+}
+
+impl Display for ReorderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReorderError::LengthMismatch {
+                section,
+                len,
+                expected,
+            } => {
+                write!(
+                    f,
+                    "{} {:?} are ordered but model has {}",
+                    len, section, expected
+                )
+            }
+            ReorderError::InvalidIndex(index) => {
+                write!(f, "index {} is out of range or duplicated", index)
+            }
+            ReorderError::FaceCountMismatch => {
+                write!(f, "faces of materials don't match face count")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReorderError {}
+
+/// move materials to draw in `new_order`, a permutation of old material indices.
+///
+/// `new_order[i]` is the old index of the material placed at `i`. `MMD` draws materials in order
+/// so e.g. transparent ones are usually moved to the end.
+/// faces are moved together with their materials, material morphs and soft bodies are updated.
+///
+/// # Errors
+/// * `ReorderError::LengthMismatch` and `ReorderError::InvalidIndex` if `new_order` is not
+///   a permutation.
+/// * `ReorderError::FaceCountMismatch` if faces of materials don't cover all faces.
+///
+/// model is unchanged on error.
+pub fn reorder_materials(model: &mut Model, new_order: &[usize]) -> Result<(), ReorderError> {
+    let map = permutation(SectionKind::Materials, new_order, model.materials.len())?;
+    let material_vertices = model
+        .materials
+        .iter()
+        .map(|material| i64::from(material.num_face_vertices))
+        .sum::<i64>();
+    if material_vertices != model.faces.len() as i64 * 3 {
+        return Err(ReorderError::FaceCountMismatch);
+    }
+    let ranges = material_faces(model);
+    model.faces = new_order
+        .iter()
+        .flat_map(|&old| model.faces[ranges[old].clone()].iter().copied())
+        .collect();
+    permute(&mut model.materials, new_order);
+    for morph in &mut model.morphs {
+        remap_indices(morph, &map);
+    }
+    for soft_body in &mut model.soft_bodies {
+        // every material has a new index.
+        soft_body.material_index = map.get(soft_body.material_index).unwrap();
+    }
+    Ok(())
+}
+
+/// stable sort materials by `key` with [`reorder_materials`].
+///
+/// # Errors
+/// * `ReorderError::FaceCountMismatch` if faces of materials don't cover all faces.
+pub fn sort_materials_by<K: Ord>(
+    model: &mut Model,
+    mut key: impl FnMut(&Material) -> K,
+) -> Result<(), ReorderError> {
+    let mut order = (0..model.materials.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&index| key(&model.materials[index]));
+    reorder_materials(model, &order)
+}
+
+/// map of `new_order`, a permutation of `len` elements.
+fn permutation(
+    section: SectionKind,
+    new_order: &[usize],
+    len: usize,
+) -> Result<IndexMap, ReorderError> {
+    if new_order.len() != len {
+        return Err(ReorderError::LengthMismatch {
+            section,
+            len: new_order.len(),
+            expected: len,
+        });
+    }
+    let mut new_indices = vec![None; len];
+    for (new, &old) in new_order.iter().enumerate() {
+        match new_indices.get_mut(old) {
+            Some(slot @ None) => *slot = Some(new as i32),
+            _ => return Err(ReorderError::InvalidIndex(old)),
+        }
+    }
+    Ok(IndexMap::new(section, new_indices))
+}
+
+/// move elements to `new_order` checked by [`permutation`].
+fn permute<T>(elements: &mut Vec<T>, new_order: &[usize]) {
+    let mut old = std::mem::take(elements)
+        .into_iter()
+        .map(Some)
+        .collect::<Vec<_>>();
+    *elements = new_order
+        .iter()
+        .map(|&index| old[index].take().unwrap())
+        .collect();
+}
+
+/// move morphs to `new_order`, a permutation of old morph indices.
+///
+/// `new_order[i]` is the old index of the morph placed at `i`.
+/// group and flip morphs and display frames are updated.
+///
+/// # Errors
+/// * `ReorderError::LengthMismatch` and `ReorderError::InvalidIndex` if `new_order` is not
+///   a permutation. model is unchanged on error.
+pub fn remap_morph_indices(model: &mut Model, new_order: &[usize]) -> Result<(), ReorderError> {
+    let map = permutation(SectionKind::Morphs, new_order, model.morphs.len())?;
+    permute(&mut model.morphs, new_order);
+    for morph in &mut model.morphs {
+        remap_indices(morph, &map);
+    }
+    for inner in model.frames.iter_mut().flat_map(|frame| &mut frame.inners) {
+        if let FrameInner::Morph(index) = inner {
+            *index = map.get(*index).unwrap_or(*index);
+        }
+    }
+    Ok(())
+}
+
+/// move rigid bodies to `new_order`, a permutation of old rigid body indices.
+///
+/// `new_order[i]` is the old index of the rigid body placed at `i`.
+/// joints, impulse morphs and anchors of soft bodies are updated.
+///
+/// # Errors
+/// * `ReorderError::LengthMismatch` and `ReorderError::InvalidIndex` if `new_order` is not
+///   a permutation. model is unchanged on error.
+pub fn remap_rigid_indices(model: &mut Model, new_order: &[usize]) -> Result<(), ReorderError> {
+    let map = permutation(
+        SectionKind::RigidBodies,
+        new_order,
+        model.rigid_bodies.len(),
+    )?;
+    permute(&mut model.rigid_bodies, new_order);
+    let shift = |index: &mut i32| *index = map.get(*index).unwrap_or(*index);
+    for joint in &mut model.joints {
+        let (a, b) = joint.joint_type.rigid_indices_mut();
+        shift(a);
+        shift(b);
+    }
+    for morph in &mut model.morphs {
+        remap_indices(morph, &map);
+    }
+    for anchor in model
+        .soft_bodies
+        .iter_mut()
+        .flat_map(|soft_body| &mut soft_body.anchor_rigid)
+    {
+        shift(&mut anchor.rigid_index);
+    }
+    Ok(())
+}
+
+/// stable sort morphs by japanese name with [`remap_morph_indices`],
+/// e.g. for deterministic diffs.
+pub fn sort_morphs_by_name(model: &mut Model) {
+    let mut order = (0..model.morphs.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| model.morphs[a].name.cmp(&model.morphs[b].name));
+    remap_morph_indices(model, &order).expect("sorted indices are a permutation");
+}
+
+/// stable sort rigid bodies by japanese name with [`remap_rigid_indices`].
+pub fn sort_rigids_by_name(model: &mut Model) {
+    let mut order = (0..model.rigid_bodies.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| model.rigid_bodies[a].name.cmp(&model.rigid_bodies[b].name));
+    remap_rigid_indices(model, &order).expect("sorted indices are a permutation");
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum RemoveBonesError {
+    /// bone index is out of range.
+    InvalidIndex(usize),
+    /// `bone` is the target of IK bone `ik`. see `force` of [`remove_bones`].
+    IkTarget { bone: usize, ik: usize },
+    /// vertices are weighted to `bone` which has no remaining ancestor to take the weights.
+    Skinned { bone: usize },
+}
+
+impl Display for RemoveBonesError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemoveBonesError::InvalidIndex(bone) => write!(f, "bone {} is out of range", bone),
+            RemoveBonesError::IkTarget { bone, ik } => {
+                write!(f, "bone {} is the target of IK bone {}", bone, ik)
+            }
+            RemoveBonesError::Skinned { bone } => {
+                write!(f, "bone {} has weights but no remaining ancestor", bone)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RemoveBonesError {}
+
+/// remove `bones` and update references to bones.
+///
+/// * children, vertex weights and rigid bodies move to the nearest remaining ancestor.
+///   rigid bodies without one follow no bone.
+/// * inheritance from removed bones is cleared.
+/// * IK links of removed bones are removed. IK of a removed target is cleared if `force`.
+/// * display connection to a removed bone is `OtherBone(-1)`.
+/// * bone morph offsets and frame entries of removed bones are removed.
+///
+/// other fields such as deform depth are unchanged.
+/// see [`usage::bone_usage`](crate::usage::bone_usage) to find removable bones.
+///
+/// # Errors
+/// * `RemoveBonesError::InvalidIndex` if a bone is out of range.
+/// * `RemoveBonesError::IkTarget` if a bone is IK target of remaining bone and not `force`.
+/// * `RemoveBonesError::Skinned` if vertices can't be moved to an ancestor.
+///
+/// model is unchanged on error.
+pub fn remove_bones(
+    model: &mut Model,
+    bones: &[usize],
+    force: bool,
+) -> Result<(), RemoveBonesError> {
+    if let Some(&bone) = bones.iter().find(|&&bone| bone >= model.bones.len()) {
+        return Err(RemoveBonesError::InvalidIndex(bone));
+    }
+    let removed = |bone: i32| usize::try_from(bone).is_ok_and(|bone| bones.contains(&bone));
+    if !force {
+        for (ik, bone) in model.bones.iter().enumerate() {
+            match &bone.ik_info {
+                Some(info) if !bones.contains(&ik) && removed(info.ik_target_bone_index) => {
+                    return Err(RemoveBonesError::IkTarget {
+                        bone: info.ik_target_bone_index as usize,
+                        ik,
+                    })
+                }
+                _ => {}
+            }
+        }
+    }
+    // nearest remaining ancestor. steps are limited against parent cycles.
+    let ancestor = |mut bone: i32| {
+        for _ in 0..=model.bones.len() {
+            if !removed(bone) {
+                return bone;
+            }
+            bone = model.bones[bone as usize].parent;
+        }
+        -1
+    };
+    let ancestors = (0..model.bones.len() as i32)
+        .map(ancestor)
+        .collect::<Vec<_>>();
+    let ancestor = |bone: i32| {
+        usize::try_from(bone)
+            .ok()
+            .and_then(|bone| ancestors.get(bone).copied())
+            .unwrap_or(bone)
+    };
+    for vertex in &model.vertices {
+        for (bone, weight) in vertex.weight_type.bone_weights() {
+            if weight != 0.0 && removed(bone) && ancestor(bone) == -1 {
+                return Err(RemoveBonesError::Skinned {
+                    bone: bone as usize,
+                });
+            }
+        }
+    }
+
+    let map = IndexMap::removing(SectionKind::Bones, model.bones.len(), bones);
+    let remap = |bone: i32| map.get(ancestor(bone)).unwrap_or(-1);
+    for vertex in &mut model.vertices {
+        for bone in vertex.weight_type.bone_indices_mut() {
+            *bone = remap(*bone);
+        }
+    }
+    for bone in &mut model.bones {
+        bone.parent = remap(bone.parent);
+        if let ConnectionDisplayMode::OtherBone(other) = &mut bone.connection_display_mode {
+            *other = map.get(*other).unwrap_or(-1);
+        }
+        let inherits = &mut bone.inherits.rotate_and_translate;
+        match inherits {
+            RotateAndTranslateInherits::Both(parent, _)
+            | RotateAndTranslateInherits::Rotate(parent, _)
+            | RotateAndTranslateInherits::Translate(parent, _) => match map.get(*parent) {
+                Some(new) => *parent = new,
+                None => *inherits = RotateAndTranslateInherits::None,
+            },
+            RotateAndTranslateInherits::None => {}
+        }
+        if let Some(info) = &mut bone.ik_info {
+            match map.get(info.ik_target_bone_index) {
+                Some(target) => {
+                    info.ik_target_bone_index = target;
+                    info.ik_links.retain_mut(|link| {
+                        map.get(link.ik_bone_index)
+                            .map(|new| link.ik_bone_index = new)
+                            .is_some()
+                    });
+                }
+                None => bone.ik_info = None,
+            }
+        }
+    }
+    let mut index = 0;
+    model.bones.retain(|_| {
+        index += 1;
+        !bones.contains(&(index - 1))
+    });
+    for morph in &mut model.morphs {
+        remap_indices(morph, &map);
+    }
+    for frame in &mut model.frames {
+        frame.inners.retain_mut(|inner| match inner {
+            FrameInner::Bone(index) => map.get(*index).map(|new| *index = new).is_some(),
+            FrameInner::Morph(_) => true,
+        });
+    }
+    for rigid in &mut model.rigid_bodies {
+        rigid.bone_index = remap(rigid.bone_index);
+    }
+    Ok(())
+}
+
+/// name heuristic of [`assign_panels`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PanelRule<'a> {
+    /// matched against the name, and also the lowercased english name if it is ascii.
+    pub keyword: &'a str,
+    /// whole name needs to equal `keyword` instead of containing it.
+    pub exact: bool,
+    pub panel: ControlPanel,
+}
+
+const fn rule(keyword: &str, exact: bool, panel: ControlPanel) -> PanelRule<'_> {
+    PanelRule {
+        keyword,
+        exact,
+        panel,
+    }
+}
+
+/// rules for common morph names of `MMD` models. the first matching rule wins.
+///
+/// extend them by `[DEFAULT_PANEL_RULES, &my_rules].concat()`.
+pub const DEFAULT_PANEL_RULES: &[PanelRule<'static>] = &[
+    rule("あ", true, ControlPanel::TopRight),
+    rule("い", true, ControlPanel::TopRight),
+    rule("う", true, ControlPanel::TopRight),
+    rule("え", true, ControlPanel::TopRight),
+    rule("お", true, ControlPanel::TopRight),
+    rule("ワ", true, ControlPanel::TopRight),
+    rule("ω", true, ControlPanel::TopRight),
+    rule("∧", true, ControlPanel::TopRight),
+    rule("▲", true, ControlPanel::TopRight),
+    rule("ん", true, ControlPanel::TopRight),
+    rule("眉", false, ControlPanel::BottomLeft),
+    rule("真面目", false, ControlPanel::BottomLeft),
+    rule("困る", false, ControlPanel::BottomLeft),
+    rule("にこり", false, ControlPanel::BottomLeft),
+    rule("怒り", false, ControlPanel::BottomLeft),
+    rule("brow", false, ControlPanel::BottomLeft),
+    rule("まばたき", false, ControlPanel::TopLeft),
+    rule("笑い", false, ControlPanel::TopLeft),
+    rule("ウィンク", false, ControlPanel::TopLeft),
+    rule("ｳｨﾝｸ", false, ControlPanel::TopLeft),
+    rule("はぅ", false, ControlPanel::TopLeft),
+    rule("なごみ", false, ControlPanel::TopLeft),
+    rule("びっくり", false, ControlPanel::TopLeft),
+    rule("じと目", false, ControlPanel::TopLeft),
+    rule("ｷﾘｯ", false, ControlPanel::TopLeft),
+    rule("瞳", false, ControlPanel::TopLeft),
+    rule("目", false, ControlPanel::TopLeft),
+    rule("blink", false, ControlPanel::TopLeft),
+    rule("wink", false, ControlPanel::TopLeft),
+    rule("eye", false, ControlPanel::TopLeft),
+    rule("口", false, ControlPanel::TopRight),
+    rule("舌", false, ControlPanel::TopRight),
+    rule("歯", false, ControlPanel::TopRight),
+    rule("ぺろっ", false, ControlPanel::TopRight),
+    rule("にやり", false, ControlPanel::TopRight),
+    rule("えー", false, ControlPanel::TopRight),
+    rule("はんっ", false, ControlPanel::TopRight),
+    rule("mouth", false, ControlPanel::TopRight),
+    rule("lip", false, ControlPanel::TopRight),
+    rule("tongue", false, ControlPanel::TopRight),
+    rule("照れ", false, ControlPanel::BottomRight),
+    rule("涙", false, ControlPanel::BottomRight),
+    rule("汗", false, ControlPanel::BottomRight),
+    rule("青ざめ", false, ControlPanel::BottomRight),
+    rule("頬", false, ControlPanel::BottomRight),
+    rule("cheek", false, ControlPanel::BottomRight),
+    rule("blush", false, ControlPanel::BottomRight),
+    rule("tear", false, ControlPanel::BottomRight),
+];
+
+impl PanelRule<'_> {
+    fn matches(&self, name: &str, name_en: &str) -> bool {
+        let matches = |name: &str| {
+            if self.exact {
+                name == self.keyword
+            } else {
+                name.contains(self.keyword)
+            }
+        };
+        matches(name) || (self.keyword.is_ascii() && matches(name_en))
+    }
+}
+
+/// how [`assign_panels`] chooses panels. default keeps panels already set.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PanelStrategy<'a> {
+    pub rules: &'a [PanelRule<'a>],
+    /// reassign morphs which already have a panel other than `ControlPanel::System`.
+    pub overwrite: bool,
+    /// panel of morphs matching no rule. `None` leaves them unchanged.
+    pub fallback: Option<ControlPanel>,
+}
+
+impl Default for PanelStrategy<'_> {
+    fn default() -> Self {
+        Self {
+            rules: DEFAULT_PANEL_RULES,
+            overwrite: false,
+            fallback: Some(ControlPanel::BottomRight),
+        }
+    }
+}
+
+/// set control panels of `morphs` from their names and return indices of changed morphs.
+///
+/// see [`validation::check_morph_panels`](crate::validation::check_morph_panels).
+///
+/// # Examples
+///
+/// ```
+/// use PMXUtil::convert::{assign_panels, PanelRule, PanelStrategy, DEFAULT_PANEL_RULES};
+/// use PMXUtil::types::ControlPanel;
+///
+/// # let mut morphs = vec![PMXUtil::types::Morph {
+/// #     name: "まばたき".to_owned(),
+/// #     english_name: String::new(),
+/// #     control_panel: ControlPanel::System,
+/// #     morph_data: PMXUtil::types::MorphKinds::Vertex(vec![]),
+/// # }];
+/// let rules = [
+///     DEFAULT_PANEL_RULES,
+///     &[PanelRule {
+///         keyword: "ハイライト",
+///         exact: false,
+///         panel: ControlPanel::TopLeft,
+///     }],
+/// ]
+/// .concat();
+/// let strategy = PanelStrategy {
+///     rules: &rules,
+///     ..Default::default()
+/// };
+/// let changed = assign_panels(&mut morphs, &strategy);
+/// assert_eq!(morphs[0].control_panel, ControlPanel::TopLeft);
+/// assert_eq!(changed, [0]);
+/// ```
+pub fn assign_panels(morphs: &mut [Morph], strategy: &PanelStrategy) -> Vec<usize> {
+    let mut changed = vec![];
+    for (index, morph) in morphs.iter_mut().enumerate() {
+        if !strategy.overwrite && morph.control_panel != ControlPanel::System {
+            continue;
+        }
+        let name_en = morph.english_name.to_ascii_lowercase();
+        let panel = strategy
+            .rules
+            .iter()
+            .find(|rule| rule.matches(&morph.name, &name_en))
+            .map(|rule| rule.panel)
+            .or(strategy.fallback);
+        if let Some(panel) = panel {
+            if morph.control_panel != panel {
+                morph.control_panel = panel;
+                changed.push(index);
+            }
+        }
+    }
+    changed
+}
+
+/// changes made by [`fix_frames`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct FrameFix {
+    /// special frames created because they were missing.
+    pub created_specials: usize,
+    /// frames no longer special because they were extra special frames.
+    pub demoted: Vec<String>,
+    /// entries moved out of the special frames.
+    pub moved_entries: usize,
+    /// duplicate entries removed.
+    pub removed_entries: usize,
+}
+
+/// repair issues reported by [`validation::check_frames`](crate::validation::check_frames).
+///
+/// * special Root and 表情 frames are found by name, created if missing and moved to the front.
+///   other special frames become normal frames.
+/// * Root frame keeps its first bone, or gets bone 0. other bones in special frames move to the
+///   frame named [`DEFAULT_BONE_FRAME_NAME`] and morphs in Root frame move to 表情 frame.
+/// * duplicate entries in a frame are removed keeping the first one.
+pub fn fix_frames(model: &mut Model) -> FrameFix {
+    let mut fix = FrameFix::default();
+    let mut frames = std::mem::take(&mut model.frames);
+    let mut take_special = |name: &str, create: fn(&[Bone]) -> Frame| {
+        let position = frames
+            .iter()
+            .position(|frame| is_special_frame(frame, name))
+            .or_else(|| frames.iter().position(|frame| frame.name == name));
+        match position {
+            Some(index) => frames.remove(index),
+            None => {
+                fix.created_specials += 1;
+                create(&[])
+            }
+        }
+    };
+    let mut root = take_special(ROOT_FRAME_NAME, root_frame);
+    let mut expression = take_special(EXPRESSION_FRAME_NAME, |_| expression_frame(&[]));
+    root.is_special = true;
+    expression.is_special = true;
+    for frame in frames.iter_mut().filter(|frame| frame.is_special) {
+        frame.is_special = false;
+        fix.demoted.push(frame.name.clone());
+    }
+
+    let mut root_bone = None;
+    let mut moved = vec![];
+    for inner in root.inners.drain(..) {
+        match inner {
+            FrameInner::Bone(_) if root_bone.is_none() => root_bone = Some(inner),
+            _ => moved.push(inner),
+        }
+    }
+    if root_bone.is_none() && !model.bones.is_empty() {
+        root_bone = Some(FrameInner::Bone(0));
+    }
+    root.inners.extend(root_bone);
+    expression.inners.retain(|inner| match inner {
+        FrameInner::Bone(_) => {
+            moved.push(*inner);
+            false
+        }
+        FrameInner::Morph(_) => true,
+    });
+    fix.moved_entries = moved.len();
+    for inner in moved {
+        match inner {
+            FrameInner::Bone(bone) => {
+                // frames other than the special ones accept bones.
+                let _ = add_bone_to(&mut frames, DEFAULT_BONE_FRAME_NAME, bone);
+            }
+            FrameInner::Morph(_) => expression.inners.push(inner),
+        }
+    }
+
+    model.frames = vec![root, expression];
+    model.frames.append(&mut frames);
+    for frame in &mut model.frames {
+        let mut found = HashSet::new();
+        let len = frame.inners.len();
+        frame.inners.retain(|inner| found.insert(*inner));
+        fix.removed_entries += len - frame.inners.len();
+    }
+    fix
+}
+
+/// move everything placed in model space by `offset`.
+///
+/// vertices including `SDEF` parameters, bones, rigid bodies and joints are moved.
+/// morph offsets, connection offsets and axes of bones are relative so they are unchanged.
+pub fn translate(model: &mut Model, offset: Vec3) {
+    for vertex in &mut model.vertices {
+        add_scaled(&mut vertex.position, &offset, 1.0);
+        if let VertexWeight::SDEF {
+            sdef_c,
+            sdef_r0,
+            sdef_r1,
+            ..
+        } = &mut vertex.weight_type
+        {
+            for point in [sdef_c, sdef_r0, sdef_r1] {
+                add_scaled(point, &offset, 1.0);
+            }
+        }
+    }
+    for bone in &mut model.bones {
+        add_scaled(&mut bone.position, &offset, 1.0);
+    }
+    for rigid in &mut model.rigid_bodies {
+        add_scaled(&mut rigid.position, &offset, 1.0);
+    }
+    for joint in &mut model.joints {
+        add_scaled(joint.joint_type.position_mut(), &offset, 1.0);
+    }
+}
+
+/// point of the model moved to the origin by [`recenter_to_origin`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecenterAnchor {
+    /// center of [`Model::bounds`].
+    BoundsCenter,
+    /// center of bounds in x and z on the lowest y, so the model stands on the floor.
+    /// usual for stages.
+    FloorCenter,
+    /// position of the bone of the name.
+    Bone(String),
+}
+
+/// [`translate`] `model` so `anchor` comes to the origin.
+///
+/// returns the offset applied. `None` and nothing changes if the model has no vertices
+/// or the bone is not found.
+pub fn recenter_to_origin(model: &mut Model, anchor: &RecenterAnchor) -> Option<Vec3> {
+    let center = |(min, max): (Vec3, Vec3)| {
+        let mut center = [0.0; 3];
+        for axis in 0..3 {
+            center[axis] = (min[axis] + max[axis]) / 2.0;
+        }
+        center
+    };
+    let origin = match anchor {
+        RecenterAnchor::BoundsCenter => center(model.bounds()?),
+        RecenterAnchor::FloorCenter => {
+            let bounds = model.bounds()?;
+            let [x, _, z] = center(bounds);
+            [x, bounds.0[1], z]
+        }
+        RecenterAnchor::Bone(name) => model.bones.iter().find(|bone| bone.name == *name)?.position,
+    };
+    let offset = [-origin[0], -origin[1], -origin[2]];
+    translate(model, offset);
+    Some(offset)
+}
+
+/// add semi-standard bones in `which` which can be placed from existing bones,
+/// and return those added in order.
+///
+/// * [`ArmTwist`](SemiStandardBone::ArmTwist) and [`HandTwist`](SemiStandardBone::HandTwist)
+///   are added at the middle of 腕 and ひじ, or ひじ and 手首, turning around the arm.
+///   the next bone is moved under it. hidden bones 腕捩1 to 腕捩3 (手捩1 to 手捩3)
+///   inheriting 25%, 50% and 75% of the twist are added for weights.
+/// * [`LegIkParent`](SemiStandardBone::LegIkParent) is added on the floor below 足ＩＫ
+///   which is moved under it.
+///
+/// bones already present, whose base bones are missing, or of other kinds are skipped.
+/// vertex weights are unchanged so arms twist below ひじ (手首) until weights are moved
+/// to the hidden bones. visible bones are added next to their base bones in display frames.
+pub fn add_missing_semistandard(
+    model: &mut Model,
+    which: &[SemiStandardBone],
+) -> Vec<SemiStandardBone> {
+    let mut added = vec![];
+    for &semistandard in which {
+        let name = semistandard.name();
+        if model.bones.iter().any(|bone| bone.name == name) {
+            continue;
+        }
+        let done = match semistandard {
+            SemiStandardBone::ArmTwist(side) => add_twist(model, side, "腕", "ひじ", name, "arm"),
+            SemiStandardBone::HandTwist(side) => {
+                add_twist(model, side, "ひじ", "手首", name, "wrist")
+            }
+            SemiStandardBone::LegIkParent(side) => add_leg_ik_parent(model, side, name),
+            _ => false,
+        };
+        if done {
+            added.push(semistandard);
+        }
+    }
+    added
+}
+
+fn side_names(side: Side) -> (&'static str, &'static str) {
+    match side {
+        Side::Left => ("左", "left"),
+        Side::Right => ("右", "right"),
+    }
+}
+
+fn find_bone(model: &Model, name: &str) -> Option<usize> {
+    model.bones.iter().position(|bone| bone.name == name)
+}
+
+/// insert `bone` whose indices refer bones after the insertion.
+fn insert_bone(model: &mut Model, at: usize, bone: Bone) {
+    let map = IndexMap::new(
+        SectionKind::Bones,
+        (0..model.bones.len() as i32)
+            .map(|index| Some(if index < at as i32 { index } else { index + 1 }))
+            .collect(),
+    );
+    shift_bones(model, &map);
+    model.bones.insert(at, bone);
+}
+
+/// put bone `new` before or after `base` in frames containing `base`.
+fn frame_next_to(model: &mut Model, base: usize, new: usize, after: bool) {
+    for frame in &mut model.frames {
+        if let Some(position) = frame
+            .inners
+            .iter()
+            .position(|inner| *inner == FrameInner::Bone(base as i32))
+        {
+            frame
+                .inners
+                .insert(position + after as usize, FrameInner::Bone(new as i32));
+        }
+    }
+}
+
+fn add_twist(
+    model: &mut Model,
+    side: Side,
+    upper: &str,
+    lower: &str,
+    name: &str,
+    name_en: &str,
+) -> bool {
+    let (prefix, prefix_en) = side_names(side);
+    let upper = find_bone(model, &format!("{}{}", prefix, upper));
+    let lower = find_bone(model, &format!("{}{}", prefix, lower));
+    let (upper, lower) = match (upper, lower) {
+        (Some(upper), Some(lower)) => (upper, lower),
+        _ => return false,
+    };
+    let start = model.bones[upper].position;
+    let end = model.bones[lower].position;
+    let direction = [end[0] - start[0], end[1] - start[1], end[2] - start[2]];
+    let length = direction.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if length == 0.0 {
+        return false;
+    }
+    let point = |t: f32| {
+        let mut point = start;
+        add_scaled(&mut point, &direction, t);
+        point
+    };
+    let depth = model.bones[upper].deform_depth;
+    let twist = upper + 1;
+    let bone = Bone::builder(name)
+        .english_name(&format!("{} {} twist", prefix_en, name_en))
+        .parent(upper as i32)
+        .position(point(0.5))
+        .fixed_axis(direction.map(|x| x / length))
+        .deform_depth(depth)
+        .build();
+    insert_bone(model, twist, bone);
+    for step in 1..=3 {
+        let weight = step as f32 / 4.0;
+        let bone = Bone::builder(&format!("{}{}", name, step))
+            .parent(upper as i32)
+            .position(point(weight))
+            .inherit_rotation(twist as i32, weight)
+            .visible(false)
+            .controllable(false)
+            .deform_depth(depth)
+            .build();
+        insert_bone(model, twist + step, bone);
+    }
+    let lower = if lower > upper { lower + 4 } else { lower };
+    if model.bones[lower].parent == upper as i32 {
+        model.bones[lower].parent = twist as i32;
+    }
+    frame_next_to(model, upper, twist, true);
+    true
+}
+
+fn add_leg_ik_parent(model: &mut Model, side: Side, name: &str) -> bool {
+    let (prefix, prefix_en) = side_names(side);
+    let ik = match find_bone(model, &format!("{}足ＩＫ", prefix)) {
+        Some(ik) => ik,
+        None => return false,
+    };
+    let [x, _, z] = model.bones[ik].position;
+    let bone = Bone::builder(name)
+        .english_name(&format!("{} leg IK parent", prefix_en))
+        .position([x, 0.0, z])
+        .translatable(true)
+        .offset([0.0, 0.0, 1.0])
+        .deform_depth(model.bones[ik].deform_depth)
+        .build();
+    insert_bone(model, ik, bone);
+    model.bones[ik].parent = model.bones[ik + 1].parent;
+    model.bones[ik + 1].parent = ik as i32;
+    frame_next_to(model, ik + 1, ik, false);
+    true
+}
+
+/// where v of texture coordinates is 0.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum TexCoordConvention {
+    /// top of texture like Direct3D. PMX and `MMD` use this.
+    #[default]
+    TopLeft,
+    /// bottom of texture like OpenGL.
+    BottomLeft,
+}
+
+/// convert v of texture coordinates of `model` from `from` to `to` convention.
+///
+/// `v` becomes `1 - v` in [`Vertex::uv`] and y of [`Vertex::add_uv`] channels in
+/// `add_uv_channels`, and v offsets of uv morphs targeting them are negated
+/// so morphed coordinates are flipped too. nothing changes if `from` equals `to`,
+/// so converting to a convention twice is the same as once.
+/// channels not used by the model are ignored.
+pub fn flip_uv_v(
+    model: &mut Model,
+    from: TexCoordConvention,
+    to: TexCoordConvention,
+    add_uv_channels: &[usize],
+) {
+    if from == to {
+        return;
+    }
+    let channels = add_uv_channels
+        .iter()
+        .copied()
+        .filter(|channel| *channel < usize::from(model.additional_uv.min(4)))
+        .collect::<Vec<_>>();
+    for vertex in &mut model.vertices {
+        vertex.uv[1] = 1.0 - vertex.uv[1];
+        for channel in &channels {
+            vertex.add_uv[*channel][1] = 1.0 - vertex.add_uv[*channel][1];
+        }
+    }
+    for morph in &mut model.morphs {
+        let flipped = match morph.morph_data.uv_channel() {
+            Some(channel) => channels.contains(&channel),
+            None => matches!(morph.morph_data, MorphKinds::UV(_)),
+        };
+        match &mut morph.morph_data {
+            MorphKinds::UV(offsets)
+            | MorphKinds::UV1(offsets)
+            | MorphKinds::UV2(offsets)
+            | MorphKinds::UV3(offsets)
+            | MorphKinds::UV4(offsets)
+                if flipped =>
+            {
+                for offset in offsets {
+                    offset.offset[1] = -offset.offset[1];
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::convert::{
+        add_missing_semistandard, apply_morph, assign_panels, bake_morphs, dedupe_names,
+        fix_frames, flip_uv_v, recenter_to_origin, remap_morph_indices, remap_rigid_indices,
+        remove_bones, reorder_materials, sort_materials_by, sort_morphs_by_name,
+        sort_rigids_by_name, stream, strip_metadata, transcode, translate, BakeError,
+        DedupeStrategy, FrameFix, MorphIndexOrName, PanelRule, PanelStrategy, RecenterAnchor,
+        RemoveBonesError, Rename, ReorderError, StripOptions, TexCoordConvention, TranscodeError,
+        TranscodeOptions, DEFAULT_PANEL_RULES,
+    };
+    use crate::progress::SectionKind;
+    use crate::reader::{ModelInfoStage, ReadError};
+    use crate::types::{
+        Bone, ConnectionDisplayMode, ControlPanel, Encode, FrameInner, GroupMorph, IndexKinds,
+        Material, MaterialMorph, Model, Morph, MorphKinds, PMXVersion, Rigid,
+        RotateAndTranslateInherits, VertexIndexKinds, VertexMorph, VertexWeight,
+    };
+    use crate::validation::{check_semistandard_bones, SemiStandardBone, Side};
+    use crate::writer::WritePMXErrors;
+
+    #[test]
+    fn transcode_encoding() {
+        let model = crate::test_support::cube();
+        let input = crate::test_support::write(&model);
+        let mut output = vec![];
+        let options = TranscodeOptions {
+            encode: Some(Encode::UTF8),
+            ..Default::default()
+        };
+        let report = transcode(&input[..], &mut output, options).unwrap();
+        assert_eq!(report.old_encode, Encode::Utf16Le);
+        assert_eq!(report.new_encode, Encode::UTF8);
+        assert_eq!(report.old_index_sizes, report.new_index_sizes);
+        assert_eq!(report.old_size, input.len() as u64);
+        assert_eq!(report.new_size, output.len() as u64);
+
+        let mut converted = crate::test_support::read(&output);
+        assert_eq!(converted.encode, Encode::UTF8);
+        converted.encode = Encode::Utf16Le;
+        assert_eq!(converted, model);
+    }
+
+    /// both paths keep the encoding of UTF-8 PMX 2.0.
+    #[test]
+    fn transcode_utf8_v20() {
+        let mut writer = crate::writer::Writer::begin_writer(false);
+        writer.set_model_info(&Default::default());
+        writer.set_mmd_compat(false);
+        let input = writer.write_to_vec().unwrap();
+        for reoptimize_indices in [false, true] {
+            let mut output = vec![];
+            let options = TranscodeOptions {
+                reoptimize_indices,
+                ..Default::default()
+            };
+            transcode(&input[..], &mut output, options).unwrap();
+            assert_eq!(output, input);
+        }
+    }
+
+    #[test]
+    fn transcode_indices() {
+        let model = crate::test_support::cube();
+        let input = crate::test_support::write(&model);
+        // same model with widest indices
+        let stage = ModelInfoStage::from_reader(&input[..]).unwrap();
+        let mut header = stage.get_header();
+        header.s_vertex_index = VertexIndexKinds::I32;
+        header.s_bone_index = IndexKinds::I32;
+        header.s_material_index = IndexKinds::I32;
+        let mut wide = vec![];
+        stream(stage, header, &mut wide).unwrap();
+        assert_eq!(crate::test_support::read(&wide), model);
+
+        let mut output = vec![];
+        let options = TranscodeOptions {
+            reoptimize_indices: true,
+            ..Default::default()
+        };
+        let report = transcode(&wide[..], &mut output, options).unwrap();
+        assert_eq!(report.old_index_sizes.vertex, 4);
+        assert_eq!(report.new_index_sizes.vertex, 1);
+        assert_eq!(report.new_index_sizes.bone, 1);
+        assert!(report.size_delta() < 0);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn transcode_version() {
+        let input = crate::test_support::write(&crate::test_support::cube());
+        for reoptimize_indices in [false, true] {
+            let options = TranscodeOptions {
+                force_version: Some(PMXVersion::V20),
+                reoptimize_indices,
+                ..Default::default()
+            };
+            assert!(matches!(
+                transcode(&input[..], vec![], options),
+                Err(TranscodeError::Write(WritePMXErrors::RequiresV21))
+            ));
+        }
+
+        let mut writer = crate::writer::Writer::begin_writer(true);
+        writer.set_model_info(&Default::default());
+        let input = writer.write_to_vec().unwrap();
+        let mut output = vec![];
+        let options = TranscodeOptions {
+            force_version: Some(PMXVersion::V21),
+            ..Default::default()
+        };
+        let report = transcode(&input[..], &mut output, options).unwrap();
+        assert_eq!(report.new_version, PMXVersion::V21);
+        assert_eq!(report.size_delta(), 4);
+        assert_eq!(crate::test_support::read(&output).version, PMXVersion::V21);
+    }
+
+    #[test]
+    fn transcode_invalid_header() {
+        assert!(matches!(
+            transcode(
+                &[b'P', b'M', b'D', b' ', 0, 0, 0, 0, 8, 0, 0, 1, 1, 1, 1, 1, 1][..],
+                vec![],
+                Default::default()
+            ),
+            Err(TranscodeError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn transcode_truncated() {
+        let bytes = crate::test_support::write(&crate::test_support::cube());
+        for reoptimize_indices in [false, true] {
+            let options = TranscodeOptions {
+                reoptimize_indices,
+                ..Default::default()
+            };
+            let err = transcode(&bytes[..bytes.len() - 1], vec![], options).unwrap_err();
+            assert!(
+                matches!(err, TranscodeError::Read(ReadError::InvalidData { .. })),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn strip_all_metadata() {
+        let original = crate::test_support::cube();
+        let mut model = original.clone();
+        model.model_info.comment = "作者: 誰か".to_owned();
+        model.textures.textures = vec!["C:/Users/someone/tex.png".to_owned()];
+        let options = StripOptions {
+            comment: "stripped".to_owned(),
+            rename: true,
+            clear_texture_paths: true,
+        };
+        let mapping = strip_metadata(&mut model, &options);
+        assert_eq!(model.model_info.comment, "stripped");
+        assert_eq!(model.textures.textures, [""]);
+        assert_eq!(model.bones[1].name, "bone_1");
+        assert_eq!(mapping.bones[1].name, original.bones[1].name);
+        assert_eq!(model.frames[0].name, "Root");
+
+        let writer = crate::writer::Writer::from_model(&model);
+        writer.validate().unwrap();
+        let buf = writer.write_to_vec().unwrap();
+        let utf16 = |text: &str| {
+            text.encode_utf16()
+                .flat_map(u16::to_le_bytes)
+                .collect::<Vec<_>>()
+        };
+        let mut originals = vec![
+            original.model_info.name.clone(),
+            "作者: 誰か".to_owned(),
+            "C:/Users/someone/tex.png".to_owned(),
+        ];
+        originals.extend(original.bones.iter().map(|bone| bone.name.clone()));
+        originals.extend(original.morphs.iter().map(|morph| morph.name.clone()));
+        originals.extend(
+            original
+                .materials
+                .iter()
+                .map(|material| material.name.clone()),
+        );
+        originals.extend(original.rigid_bodies.iter().map(|rigid| rigid.name.clone()));
+        originals.extend(original.joints.iter().map(|joint| joint.name.clone()));
+        // special frames keep names
+        let kept = |text: &String| text.is_empty() || "表情Root".contains(text.as_str());
+        for text in originals.iter().filter(|text| !kept(text)) {
+            let bytes = utf16(text);
+            assert!(
+                !buf.windows(bytes.len()).any(|window| window == bytes),
+                "{} remains",
+                text
+            );
+        }
+
+        mapping.restore(&mut model);
+        assert_eq!(model.bones, original.bones);
+        assert_eq!(model.frames, original.frames);
+        assert_eq!(model.textures.textures, ["C:/Users/someone/tex.png"]);
+    }
+
+    /// vertex positions with morphs applied.
+    fn evaluate(model: &Model, morphs: &[(usize, f32)]) -> Vec<[f32; 3]> {
+        let mut vertices = model.vertices.clone();
+        let mut materials = model.materials.clone();
+        let mut bones = vec![[0.0; 3]; model.bones.len()];
+        for (index, weight) in morphs {
+            let morph = &model.morphs[*index];
+            apply_morph(&mut vertices, &mut materials, &mut bones, morph, *weight);
+        }
+        vertices.iter().map(|vertex| vertex.position).collect()
+    }
+
+    #[test]
+    fn bake_vertex_morph() {
+        let mut model = crate::test_support::cube();
+        model.morphs.push(Morph {
+            name: "追加".to_owned(),
+            morph_data: MorphKinds::Vertex(vec![VertexMorph {
+                index: 7,
+                offset: [1.0, 0.0, 0.0],
+            }]),
+            ..model.morphs[0].clone()
+        });
+        let original = model.clone();
+        bake_morphs(&mut model, &[("頂点".into(), 1.0)], true).unwrap();
+
+        assert_eq!(
+            model.vertices[7].position[1],
+            original.vertices[7].position[1] + 0.5
+        );
+        assert_eq!(model.morphs.len(), original.morphs.len() - 1);
+        assert_eq!(model.morphs[10].name, "追加");
+        // remaining morph gives the same shape as before on the new base
+        assert_eq!(
+            evaluate(&model, &[(10, 1.0)]),
+            evaluate(&original, &[(0, 1.0), (11, 1.0)])
+        );
+        // references to baked morph are removed and others are shifted
+        assert_eq!(
+            model.morphs[7].morph_data,
+            MorphKinds::Group(vec![GroupMorph {
+                index: 5,
+                morph_factor: 0.5,
+            }])
+        );
+        assert_eq!(model.morphs[8].morph_data, MorphKinds::Flip(vec![]));
+        assert_eq!(model.frames[1].inners, [FrameInner::Morph(7)]);
+    }
+
+    #[test]
+    fn bake_group_and_bone_morph() {
+        let mut model = crate::test_support::cube();
+        let original = model.clone();
+        bake_morphs(&mut model, &[(MorphIndexOrName::Index(8), 1.0)], false).unwrap();
+        assert_eq!(model.morphs, original.morphs);
+        // vertex morph at 1.0 and bone morph at 0.5 weighted by bone 1
+        let bone_weight = original.vertices[7]
+            .weight_type
+            .bone_weights()
+            .iter()
+            .filter(|(bone, _)| *bone == 1)
+            .map(|(_, weight)| weight)
+            .sum::<f32>();
+        let moved = model.vertices[7].position[1] - original.vertices[7].position[1];
+        assert!((moved - (0.5 + 0.5 * bone_weight)).abs() < 1e-6);
+        // bone morph at 0.5 moves bone 1 but not its parent
+        assert_eq!(model.bones[0].position, original.bones[0].position);
+        assert_eq!(
+            model.bones[1].position[1],
+            original.bones[1].position[1] + 0.5
+        );
+    }
+
+    #[test]
+    fn bake_errors() {
+        let mut model = crate::test_support::cube();
+        let original = model.clone();
+        assert_eq!(
+            bake_morphs(&mut model, &[(0.into(), 1.0), ("なし".into(), 1.0)], true),
+            Err(BakeError::UnknownMorph("なし".into()))
+        );
+        assert_eq!(
+            bake_morphs(&mut model, &[(9.into(), 1.0)], true),
+            Err(BakeError::Unsupported {
+                index: 9,
+                kind: "Flip"
+            })
+        );
+        assert_eq!(model, original);
+    }
+
+    #[test]
+    fn dedupe_suffix_collision() {
+        let mut model = crate::test_support::cube();
+        let bone = model.bones[0].clone();
+        model.bones = ["右腕", "右腕", "右腕+1", "右腕", "左腕"]
+            .iter()
+            .map(|name| crate::types::Bone {
+                name: name.to_string(),
+                ..bone.clone()
+            })
+            .collect();
+        let mapping = dedupe_names(&mut model, &DedupeStrategy::PlusSuffix);
+        let names = model.bones.iter().map(|bone| bone.name.as_str());
+        assert_eq!(
+            names.collect::<Vec<_>>(),
+            ["右腕", "右腕+2", "右腕+1", "右腕+3", "左腕"]
+        );
+        assert_eq!(
+            mapping.bones,
+            [
+                Rename {
+                    index: 1,
+                    old: "右腕".to_owned(),
+                    new: "右腕+2".to_owned()
+                },
+                Rename {
+                    index: 3,
+                    old: "右腕".to_owned(),
+                    new: "右腕+3".to_owned()
+                }
+            ]
+        );
+        assert!(crate::validation::find_duplicate_names(&model).is_empty());
+
+        model.morphs[1].name = model.morphs[0].name.clone();
+        let strategy = DedupeStrategy::Pattern("{name}_{n}".to_owned());
+        let mapping = dedupe_names(&mut model, &strategy);
+        assert!(mapping.bones.is_empty());
+        assert_eq!(model.morphs[1].name, "頂点_1");
+    }
+
+    #[test]
+    fn sort_by_name() {
+        let mut model = crate::test_support::cube();
+        model.rigid_bodies.insert(
+            0,
+            Rigid {
+                name: String::new(),
+                ..model.rigid_bodies[0].clone()
+            },
+        );
+        remap_rigid_indices(&mut model, &[1, 0]).unwrap();
+        assert_eq!(model.rigid_bodies[1].name, "");
+        // pairs of names of the referring and referred elements.
+        let targets = |model: &Model| {
+            let mut targets = vec![];
+            for morph in &model.morphs {
+                match &morph.morph_data {
+                    MorphKinds::Group(entries) => targets.extend(entries.iter().map(|e| {
+                        (
+                            morph.name.clone(),
+                            model.morphs[e.index as usize].name.clone(),
+                        )
+                    })),
+                    MorphKinds::Flip(entries) => targets.extend(entries.iter().map(|e| {
+                        (
+                            morph.name.clone(),
+                            model.morphs[e.index as usize].name.clone(),
+                        )
+                    })),
+                    MorphKinds::Impulse(entries) => targets.extend(entries.iter().map(|e| {
+                        (
+                            morph.name.clone(),
+                            model.rigid_bodies[e.rigid_index as usize].name.clone(),
+                        )
+                    })),
+                    _ => {}
+                }
+            }
+            for frame in &model.frames {
+                for inner in &frame.inners {
+                    if let FrameInner::Morph(index) = inner {
+                        targets.push((
+                            frame.name.clone(),
+                            model.morphs[*index as usize].name.clone(),
+                        ));
+                    }
+                }
+            }
+            for joint in &model.joints {
+                let mut joint_type = joint.joint_type.clone();
+                let (a, _) = joint_type.rigid_indices_mut();
+                targets.push((
+                    joint.name.clone(),
+                    model.rigid_bodies[*a as usize].name.clone(),
+                ));
+            }
+            let anchor = model.soft_bodies[0].anchor_rigid[0].rigid_index;
+            targets.push((
+                model.soft_bodies[0].name.clone(),
+                model.rigid_bodies[anchor as usize].name.clone(),
+            ));
+            targets.sort();
+            targets
+        };
+        let before = targets(&model);
+        let mut sorted = model.clone();
+        sort_morphs_by_name(&mut sorted);
+        sort_rigids_by_name(&mut sorted);
+        assert_ne!(sorted.morphs, model.morphs);
+        assert_eq!(sorted.rigid_bodies[0].name, model.rigid_bodies[1].name);
+        assert!(sorted.morphs.windows(2).all(|m| m[0].name <= m[1].name));
+        assert_eq!(targets(&sorted), before);
+
+        assert_eq!(
+            remap_morph_indices(&mut sorted, &[0]),
+            Err(ReorderError::LengthMismatch {
+                section: SectionKind::Morphs,
+                len: 1,
+                expected: model.morphs.len()
+            })
+        );
+        assert_eq!(
+            remap_rigid_indices(&mut sorted, &[0, 0]),
+            Err(ReorderError::InvalidIndex(0))
+        );
+    }
+
+    #[test]
+    fn reorder() {
+        let mut model = crate::test_support::cube();
+        let mut materials = model.materials.clone();
+        materials.push(Material {
+            name: "透明".to_owned(),
+            num_face_vertices: 0,
+            ..materials[0].clone()
+        });
+        model.materials = materials;
+        model.materials[0].num_face_vertices = 6;
+        model.materials[2].num_face_vertices = 12;
+        for morph in &mut model.morphs {
+            if let MorphKinds::Material(offsets) = &mut morph.morph_data {
+                let offset = MaterialMorph {
+                    index: 1,
+                    ..offsets[0].clone()
+                };
+                offsets.push(offset);
+            }
+        }
+        let faces = |model: &Model, material: &str| {
+            let index = model
+                .materials
+                .iter()
+                .position(|m| m.name == material)
+                .unwrap();
+            let ranges = crate::usage::material_faces(model);
+            model.faces[ranges[index].clone()].to_vec()
+        };
+        let names = model
+            .materials
+            .iter()
+            .map(|m| m.name.clone())
+            .collect::<Vec<_>>();
+        let before = names
+            .iter()
+            .map(|name| faces(&model, name))
+            .collect::<Vec<_>>();
+        let original = model.clone();
+
+        reorder_materials(&mut model, &[2, 0, 1]).unwrap();
+        assert_eq!(model.materials[0].name, names[2]);
+        for (name, before) in names.iter().zip(&before) {
+            assert_eq!(faces(&model, name), *before);
+        }
+        assert_eq!(model.faces.len(), original.faces.len());
+        for (morph, original) in model.morphs.iter().zip(&original.morphs) {
+            if let (MorphKinds::Material(new), MorphKinds::Material(old)) =
+                (&morph.morph_data, &original.morph_data)
+            {
+                let indices = new.iter().map(|offset| offset.index).collect::<Vec<_>>();
+                assert_eq!(
+                    old.iter().map(|offset| offset.index).collect::<Vec<_>>(),
+                    [-1, 1]
+                );
+                assert_eq!(indices, [-1, 2]);
+            }
+        }
+        assert_eq!(model.soft_bodies[0].material_index, 2);
+        assert_eq!(model.bones, original.bones);
+
+        // swap back by sorting on names.
+        sort_materials_by(&mut model, |m| {
+            names.iter().position(|name| *name == m.name)
+        })
+        .unwrap();
+        assert_eq!(model, original);
+
+        assert_eq!(
+            reorder_materials(&mut model, &[0, 0, 1]),
+            Err(ReorderError::InvalidIndex(0))
+        );
+        assert_eq!(
+            reorder_materials(&mut model, &[0, 1, 3]),
+            Err(ReorderError::InvalidIndex(3))
+        );
+        assert_eq!(
+            reorder_materials(&mut model, &[0, 1]),
+            Err(ReorderError::LengthMismatch {
+                section: SectionKind::Materials,
+                len: 2,
+                expected: 3
+            })
+        );
+        model.faces.pop();
+        assert_eq!(
+            reorder_materials(&mut model, &[0, 1, 2]),
+            Err(ReorderError::FaceCountMismatch)
+        );
+    }
+
+    #[test]
+    fn remove_bones_reparents() {
+        let mut model = crate::test_support::cube();
+        model.bones.push(Bone::builder("arm").parent(0).build());
+        model
+            .bones
+            .push(Bone::builder("elbow").parent(2).connect_to(4).build());
+        model.bones.push(
+            Bone::builder("wrist")
+                .parent(3)
+                .connect_to(3)
+                .inherit_rotation(2, 0.5)
+                .build(),
+        );
+        model.vertices[0].weight_type = VertexWeight::BDEF1(3);
+        model.frames[2].inners = vec![FrameInner::Bone(2), FrameInner::Bone(4)];
+        if let Some(ik) = &mut model.bones[1].ik_info {
+            ik.ik_target_bone_index = 4;
+            ik.ik_links[1].ik_bone_index = 3;
+        }
+
+        let original = model.clone();
+        assert_eq!(
+            remove_bones(&mut model, &[2, 5], false),
+            Err(RemoveBonesError::InvalidIndex(5))
+        );
+        assert_eq!(
+            remove_bones(&mut model, &[0], true),
+            Err(RemoveBonesError::Skinned { bone: 0 })
+        );
+        assert_eq!(
+            remove_bones(&mut model, &[4], false),
+            Err(RemoveBonesError::IkTarget { bone: 4, ik: 1 })
+        );
+        assert_eq!(model, original);
+
+        remove_bones(&mut model, &[2, 3], false).unwrap();
+        assert_eq!(model.bones.len(), 3);
+        let wrist = &model.bones[2];
+        assert_eq!(wrist.name, "wrist");
+        assert_eq!(wrist.parent, 0);
+        assert_eq!(
+            wrist.connection_display_mode,
+            ConnectionDisplayMode::OtherBone(-1)
+        );
+        assert_eq!(
+            wrist.inherits.rotate_and_translate,
+            RotateAndTranslateInherits::None
+        );
+        assert_eq!(model.vertices[0].weight_type, VertexWeight::BDEF1(0));
+        assert_eq!(model.frames[2].inners, [FrameInner::Bone(2)]);
+        let ik = model.bones[1].ik_info.as_ref().unwrap();
+        assert_eq!(ik.ik_target_bone_index, 2);
+        assert_eq!(ik.ik_links.len(), 1);
+
+        remove_bones(&mut model, &[2], true).unwrap();
+        assert_eq!(model.bones[1].ik_info, None);
+        let bytes = crate::test_support::write(&model);
+        assert_eq!(crate::test_support::read(&bytes).bones, model.bones);
+    }
+
+    #[test]
+    fn assign_panels_by_name() {
+        let base = crate::test_support::cube().morphs.remove(0);
+        let morph = |name: &str, name_en: &str, control_panel| Morph {
+            name: name.to_owned(),
+            english_name: name_en.to_owned(),
+            control_panel,
+            ..base.clone()
+        };
+        let mut morphs = vec![
+            morph("あ", "", ControlPanel::System),
+            morph("あ２", "", ControlPanel::System),
+            morph("まばたき", "", ControlPanel::System),
+            morph("上げ", "Brow Up", ControlPanel::System),
+            morph("照れ", "", ControlPanel::System),
+            morph("ハイライト消", "", ControlPanel::System),
+            morph("眉上", "", ControlPanel::TopRight),
+        ];
+        let changed = assign_panels(&mut morphs, &PanelStrategy::default());
+        let panels = morphs.iter().map(|m| m.control_panel).collect::<Vec<_>>();
+        assert_eq!(changed, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(
+            panels,
+            [
+                ControlPanel::TopRight,
+                ControlPanel::BottomRight,
+                ControlPanel::TopLeft,
+                ControlPanel::BottomLeft,
+                ControlPanel::BottomRight,
+                ControlPanel::BottomRight,
+                ControlPanel::TopRight,
+            ]
+        );
+
+        let rules = [
+            DEFAULT_PANEL_RULES,
+            &[PanelRule {
+                keyword: "ハイライト",
+                exact: false,
+                panel: ControlPanel::TopLeft,
+            }],
+        ]
+        .concat();
+        let strategy = PanelStrategy {
+            rules: &rules,
+            overwrite: true,
+            fallback: None,
+        };
+        let changed = assign_panels(&mut morphs, &strategy);
+        assert_eq!(changed, [5, 6]);
+        assert_eq!(morphs[1].control_panel, ControlPanel::BottomRight);
+        assert_eq!(morphs[5].control_panel, ControlPanel::TopLeft);
+        assert_eq!(morphs[6].control_panel, ControlPanel::BottomLeft);
+    }
+
+    #[test]
+    fn fix_scrambled_frames() {
+        use crate::validation::check_frames;
+        let cube = crate::test_support::cube();
+        let [mut root, mut expression, mut ik] = [0, 1, 2].map(|i| cube.frames[i].clone());
+        ik.inners.push(FrameInner::Bone(1));
+        expression.inners.insert(0, FrameInner::Bone(1));
+        expression
+            .inners
+            .retain(|inner| *inner != FrameInner::Morph(8));
+        expression.inners.push(FrameInner::Morph(0));
+        root.inners
+            .extend([FrameInner::Bone(1), FrameInner::Morph(8)]);
+        let mut extra = ik.clone();
+        extra.name = "その他".to_owned();
+        extra.is_special = true;
+        extra.inners.clear();
+        let mut model = cube.clone();
+        model.frames = vec![ik, expression, root, extra];
+        assert!(!check_frames(&model.frames).is_empty());
+
+        let fix = fix_frames(&mut model);
+        assert_eq!(
+            fix,
+            FrameFix {
+                created_specials: 0,
+                demoted: vec!["その他".to_owned()],
+                moved_entries: 3,
+                removed_entries: 2,
+            }
+        );
+        assert!(check_frames(&model.frames).is_empty());
+        assert_eq!(model.frames[..3], cube.frames[..]);
+        assert_eq!(model.frames[3].name, "その他");
+        assert_eq!(model.frames[4].name, "ボーン");
+        assert_eq!(model.frames[4].inners, [FrameInner::Bone(1)]);
+
+        // missing specials are created.
+        model.frames.clear();
+        let fix = fix_frames(&mut model);
+        assert_eq!(fix.created_specials, 2);
+        assert!(check_frames(&model.frames).is_empty());
+        assert_eq!(model.frames[0].inners, [FrameInner::Bone(0)]);
+    }
+
+    #[test]
+    fn flip_uv_with_morphs() {
+        // base uv, then additional uv 0 and 1 of vertex 3 at half weight of uv morphs.
+        let morphed = |model: &Model| {
+            let mut vertices = model.vertices.clone();
+            let mut materials = model.materials.clone();
+            let mut bones = vec![[0.0; 3]; model.bones.len()];
+            for morph in &model.morphs[1..4] {
+                apply_morph(&mut vertices, &mut materials, &mut bones, morph, 0.5);
+            }
+            let vertex = &vertices[3];
+            [
+                vertex.uv,
+                [vertex.add_uv[0][0], vertex.add_uv[0][1]],
+                [vertex.add_uv[1][0], vertex.add_uv[1][1]],
+            ]
+        };
+        let original = crate::test_support::cube();
+        let mut model = original.clone();
+        flip_uv_v(
+            &mut model,
+            TexCoordConvention::TopLeft,
+            TexCoordConvention::BottomLeft,
+            &[0],
+        );
+        let before = morphed(&original);
+        let after = morphed(&model);
+        for channel in 0..2 {
+            assert_eq!(after[channel][0], before[channel][0]);
+            assert!((after[channel][1] - (1.0 - before[channel][1])).abs() < 1e-6);
+        }
+        // channel 1 is not flipped.
+        assert_eq!(after[2], before[2]);
+
+        let flipped = model.clone();
+        flip_uv_v(
+            &mut model,
+            TexCoordConvention::BottomLeft,
+            TexCoordConvention::BottomLeft,
+            &[0],
+        );
+        assert_eq!(model, flipped);
+        flip_uv_v(
+            &mut model,
+            TexCoordConvention::BottomLeft,
+            TexCoordConvention::TopLeft,
+            &[0],
+        );
+        assert_eq!(model, original);
+        // channels not used by the model are ignored.
+        model.additional_uv = 2;
+        flip_uv_v(
+            &mut model,
+            TexCoordConvention::TopLeft,
+            TexCoordConvention::BottomLeft,
+            &[0, 1, 2, 3],
+        );
+        let v_offsets = model.morphs[1..6]
+            .iter()
+            .map(|morph| match &morph.morph_data {
+                MorphKinds::UV(offsets)
+                | MorphKinds::UV1(offsets)
+                | MorphKinds::UV2(offsets)
+                | MorphKinds::UV3(offsets)
+                | MorphKinds::UV4(offsets) => offsets[0].offset[1],
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(v_offsets, [-0.2, -0.2, -0.2, 0.2, 0.2]);
+    }
+
+    #[test]
+    fn semistandard_bones() {
+        let mut model = Model {
+            bones: crate::builder::standard_humanoid_skeleton(),
+            ..Model::default()
+        };
+        model.frames = crate::frames::generate_default(&model.bones, &model.morphs);
+        let missing = check_semistandard_bones(&model.bones).missing;
+        let added = add_missing_semistandard(&mut model, &missing);
+        assert_eq!(
+            added,
+            [
+                SemiStandardBone::ArmTwist(Side::Left),
+                SemiStandardBone::ArmTwist(Side::Right),
+                SemiStandardBone::HandTwist(Side::Left),
+                SemiStandardBone::HandTwist(Side::Right),
+                SemiStandardBone::LegIkParent(Side::Left),
+                SemiStandardBone::LegIkParent(Side::Right),
+            ]
+        );
+        assert!(crate::validation::check_ik(&model.bones).is_empty());
+        let report = check_semistandard_bones(&model.bones);
+        assert!(added.iter().all(|bone| !report.missing.contains(bone)));
+        let index = |name: &str| model.bones.iter().position(|b| b.name == name).unwrap();
+
+        let twist = &model.bones[index("左腕捩")];
+        assert_eq!(twist.parent, index("左腕") as i32);
+        assert_eq!(model.bones[index("左ひじ")].parent, index("左腕捩") as i32);
+        assert_eq!(
+            model.bones[index("左腕捩2")].inherits.rotate_and_translate,
+            RotateAndTranslateInherits::Rotate(index("左腕捩") as i32, 0.5)
+        );
+        assert_eq!(model.bones[index("左手首")].parent, index("左手捩") as i32);
+
+        let ik = index("右足ＩＫ");
+        let ik_parent = index("右足ＩＫ親");
+        assert_eq!(model.bones[ik].parent, ik_parent as i32);
+        assert_eq!(model.bones[ik_parent].parent, index("全ての親") as i32);
+        assert_eq!(model.bones[ik_parent].position, [-1.0, 0.0, 0.5]);
+        let ik_target = model.bones[ik]
+            .ik_info
+            .as_ref()
+            .unwrap()
+            .ik_target_bone_index;
+        assert_eq!(ik_target, index("右足首") as i32);
+        // visible bones are framed next to their base bones.
+        let framed = |bone: usize| {
+            model
+                .frames
+                .iter()
+                .any(|frame| frame.inners.contains(&FrameInner::Bone(bone as i32)))
+        };
+        assert!(framed(index("左腕捩")) && framed(ik_parent));
+        assert!(!framed(index("左腕捩1")));
+
+        // present bones are skipped.
+        assert!(add_missing_semistandard(&mut model, &added).is_empty());
+    }
+
+    #[test]
+    fn recenter() {
+        let mut original = crate::test_support::cube();
+        translate(&mut original, [10.0, 5.0, -3.0]);
+        let (min, max) = original.bounds().unwrap();
+        let rigid = original.rigid_bodies[0].position;
+        let center = original.bones[0].position;
+
+        let mut model = original.clone();
+        let offset = recenter_to_origin(&mut model, &RecenterAnchor::FloorCenter).unwrap();
+        let (new_min, new_max) = model.bounds().unwrap();
+        assert_eq!(new_min[1], 0.0);
+        assert_eq!(new_max[0], -new_min[0]);
+        assert_eq!(new_max[2], -new_min[2]);
+        for axis in 0..3 {
+            assert_eq!(new_max[axis] - new_min[axis], max[axis] - min[axis]);
+            // rigid bodies move with the mesh.
+            assert_eq!(
+                model.rigid_bodies[0].position[axis],
+                rigid[axis] + offset[axis]
+            );
+            assert_eq!(
+                model.joints[0].joint_type.position_mut()[axis],
+                original.joints[0].clone().joint_type.position_mut()[axis] + offset[axis]
+            );
+        }
+        // relative offsets are unchanged.
+        assert_eq!(model.morphs, original.morphs);
+
+        let mut model = original.clone();
+        recenter_to_origin(&mut model, &RecenterAnchor::BoundsCenter).unwrap();
+        let (min, max) = model.bounds().unwrap();
+        assert_eq!(min, [-max[0], -max[1], -max[2]]);
+
+        let mut model = original.clone();
+        let name = RecenterAnchor::Bone(original.bones[0].name.clone());
+        assert_eq!(
+            recenter_to_origin(&mut model, &name),
+            Some([-center[0], -center[1], -center[2]])
+        );
+        assert_eq!(model.bones[0].position, [0.0; 3]);
+        let missing = RecenterAnchor::Bone("missing".to_owned());
+        assert_eq!(recenter_to_origin(&mut model, &missing), None);
+        let mut empty = Model::default();
+        assert_eq!(
+            recenter_to_origin(&mut empty, &RecenterAnchor::FloorCenter),
+            None
+        );
+    }
+}
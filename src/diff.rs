@@ -0,0 +1,812 @@
+//! Compare two models section by section.
+//!
+//! Floats are compared with a tolerance and text is compared exactly.
+//! ```rust
+//! use PMXUtil::diff::compare;
+//! use PMXUtil::types::Model;
+//!
+//! let old = Model::default();
+//! let new = Model::default();
+//! let diff = compare(&old, &new, 1e-5);
+//! assert!(diff.is_empty());
+//! for difference in &diff {
+//!     println!("{}", difference);
+//! }
+//! ```
+use crate::types::{
+    Bone, BoneIKInfo, BoneInherits, BoneMorph, ConnectionDisplayMode, ControlPanel, Encode, Face,
+    FlipMorph, Frame, FrameInner, GroupMorph, IKLink, ImpulseMorph, Joint, JointType, Material,
+    MaterialFlags, MaterialMorph, MaterialMorphFormula, Model, ModelInfo, Morph, MorphKinds,
+    PMXVersion, Rigid, RigidCalcMethod, RigidForm, RigidGroups, RotateAndTranslateInherits,
+    SoftBody, SoftBodyAeroModel, SoftBodyAnchorRigid, SoftBodyFlags, SoftBodyForm, SphereMode,
+    ToonMode, UVMorph, Vertex, VertexMorph, VertexWeight,
+};
+use std::fmt::{Display, Formatter};
+
+/// panic unless [`compare`]`($left, $right, $tolerance)` is empty.
+///
+/// message lists all differences starting with the first differing field.
+/// ```rust
+/// let model = PMXUtil::types::Model::default();
+/// let copy = model.clone();
+/// PMXUtil::assert_model_approx_eq!(model, copy, 1e-5);
+/// ```
+#[macro_export]
+macro_rules! assert_model_approx_eq {
+    ($left:expr, $right:expr, $tolerance:expr $(,)?) => {{
+        let diff = $crate::diff::compare(&$left, &$right, $tolerance);
+        if let Some(first) = diff.iter().next() {
+            panic!(
+                "models differ more than {}: first {}\n{}",
+                $tolerance, first, diff
+            );
+        }
+    }};
+}
+
+/// part of a PMX file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Section {
+    /// version, encoding and additional uv count
+    Header,
+    ModelInfo,
+    Vertices,
+    Faces,
+    Textures,
+    Materials,
+    Bones,
+    Morphs,
+    Frames,
+    RigidBodies,
+    Joints,
+    SoftBodies,
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Section::Header => "header",
+            Section::ModelInfo => "model info",
+            Section::Vertices => "vertices",
+            Section::Faces => "faces",
+            Section::Textures => "textures",
+            Section::Materials => "materials",
+            Section::Bones => "bones",
+            Section::Morphs => "morphs",
+            Section::Frames => "frames",
+            Section::RigidBodies => "rigid bodies",
+            Section::Joints => "joints",
+            Section::SoftBodies => "soft bodies",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ChangeKind {
+    /// exists only in new model
+    Added,
+    /// exists only in old model
+    Removed,
+    Changed,
+}
+
+/// one element which differs between models.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Difference {
+    pub section: Section,
+    /// index in the section. always `0` for [`Section::Header`] and [`Section::ModelInfo`]
+    pub index: usize,
+    pub kind: ChangeKind,
+    /// name of the element if it has one.
+    pub name: Option<String>,
+    /// first field which differs for [`ChangeKind::Changed`] elements with fields.
+    pub field: Option<&'static str>,
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Changed => "changed",
+        };
+        write!(f, "{}[{}]", self.section, self.index)?;
+        if let Some(field) = self.field {
+            write!(f, ".{}", field)?;
+        }
+        if let Some(name) = &self.name {
+            write!(f, " \"{}\"", name)?;
+        }
+        write!(f, " {}", kind)
+    }
+}
+
+/// distance between old and new position of vertices which exist in both models.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PositionDelta {
+    pub max: f32,
+    pub mean: f32,
+}
+
+/// result of [`compare`]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ModelDiff {
+    differences: Vec<Difference>,
+    /// `None` if either model has no vertices.
+    pub vertex_position_delta: Option<PositionDelta>,
+}
+
+impl ModelDiff {
+    /// `true` if no element differs.
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+    pub fn len(&self) -> usize {
+        self.differences.len()
+    }
+    pub fn iter(&self) -> std::slice::Iter<'_, Difference> {
+        self.differences.iter()
+    }
+    pub fn in_section(&self, section: Section) -> impl Iterator<Item = &Difference> {
+        self.differences
+            .iter()
+            .filter(move |difference| difference.section == section)
+    }
+}
+
+impl<'a> IntoIterator for &'a ModelDiff {
+    type Item = &'a Difference;
+    type IntoIter = std::slice::Iter<'a, Difference>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.differences.iter()
+    }
+}
+
+impl IntoIterator for ModelDiff {
+    type Item = Difference;
+    type IntoIter = std::vec::IntoIter<Difference>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.differences.into_iter()
+    }
+}
+
+impl Display for ModelDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            write!(f, "no differences")?;
+        } else {
+            write!(f, "{} differences", self.len())?;
+            for difference in &self.differences {
+                write!(f, "\n{}", difference)?;
+            }
+        }
+        if let Some(delta) = self.vertex_position_delta {
+            write!(
+                f,
+                "\nvertex position delta: max {} mean {}",
+                delta.max, delta.mean
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// compare `old` and `new`.
+///
+/// elements are matched by index. floats are equal if they differ at most `tolerance`.
+pub fn compare(old: &Model, new: &Model, tolerance: f32) -> ModelDiff {
+    let mut diff = ModelDiff::default();
+    if old.version != new.version
+        || old.encode != new.encode
+        || old.additional_uv != new.additional_uv
+    {
+        diff.differences.push(Difference {
+            section: Section::Header,
+            index: 0,
+            kind: ChangeKind::Changed,
+            name: None,
+            field: None,
+        });
+    }
+    if old.model_info != new.model_info {
+        diff.differences.push(Difference {
+            section: Section::ModelInfo,
+            index: 0,
+            kind: ChangeKind::Changed,
+            name: None,
+            field: None,
+        });
+    }
+    let mut sections = Sections {
+        differences: &mut diff.differences,
+        tolerance,
+    };
+    sections.compare(Section::Vertices, &old.vertices, &new.vertices, |_| None);
+    sections.compare(Section::Faces, &old.faces, &new.faces, |_| None);
+    sections.compare(
+        Section::Textures,
+        &old.textures.textures,
+        &new.textures.textures,
+        |texture| Some(texture),
+    );
+    sections.compare(Section::Materials, &old.materials, &new.materials, |x| {
+        Some(&x.name)
+    });
+    sections.compare(Section::Bones, &old.bones, &new.bones, |x| Some(&x.name));
+    sections.compare(Section::Morphs, &old.morphs, &new.morphs, |x| Some(&x.name));
+    sections.compare(Section::Frames, &old.frames, &new.frames, |x| Some(&x.name));
+    sections.compare(
+        Section::RigidBodies,
+        &old.rigid_bodies,
+        &new.rigid_bodies,
+        |x| Some(&x.name),
+    );
+    sections.compare(Section::Joints, &old.joints, &new.joints, |x| Some(&x.name));
+    sections.compare(
+        Section::SoftBodies,
+        &old.soft_bodies,
+        &new.soft_bodies,
+        |x| Some(&x.name),
+    );
+    diff.vertex_position_delta = position_delta(&old.vertices, &new.vertices);
+    diff
+}
+
+fn position_delta(old: &[Vertex], new: &[Vertex]) -> Option<PositionDelta> {
+    let count = old.len().min(new.len());
+    if count == 0 {
+        return None;
+    }
+    let mut max = 0.0f32;
+    let mut sum = 0.0f64;
+    for (old, new) in old.iter().zip(new.iter()) {
+        let distance = old
+            .position
+            .iter()
+            .zip(new.position.iter())
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum::<f32>()
+            .sqrt();
+        max = max.max(distance);
+        sum += f64::from(distance);
+    }
+    Some(PositionDelta {
+        max,
+        mean: (sum / count as f64) as f32,
+    })
+}
+
+struct Sections<'a> {
+    differences: &'a mut Vec<Difference>,
+    tolerance: f32,
+}
+
+impl Sections<'_> {
+    fn compare<T: Approx>(
+        &mut self,
+        section: Section,
+        old: &[T],
+        new: &[T],
+        name: fn(&T) -> Option<&String>,
+    ) {
+        let tolerance = self.tolerance;
+        let mut push = |index: usize, kind: ChangeKind, element: &T, field| {
+            self.differences.push(Difference {
+                section,
+                index,
+                kind,
+                name: name(element).cloned(),
+                field,
+            })
+        };
+        for (index, (old, new)) in old.iter().zip(new.iter()).enumerate() {
+            if !old.approx_eq(new, tolerance) {
+                let field = old.differing_field(new, tolerance);
+                push(index, ChangeKind::Changed, new, field);
+            }
+        }
+        for (index, old) in old.iter().enumerate().skip(new.len()) {
+            push(index, ChangeKind::Removed, old, None);
+        }
+        for (index, new) in new.iter().enumerate().skip(old.len()) {
+            push(index, ChangeKind::Added, new, None);
+        }
+    }
+}
+
+/// equality with float tolerance
+trait Approx {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool;
+    /// name of the first field not [`approx_eq`](Self::approx_eq) if `Self` has fields.
+    fn differing_field(&self, _other: &Self, _tolerance: f32) -> Option<&'static str> {
+        None
+    }
+}
+
+impl Approx for f32 {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self == other || (self - other).abs() <= tolerance || (self.is_nan() && other.is_nan())
+    }
+}
+
+impl<T: Approx + ?Sized> Approx for &T {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        (**self).approx_eq(*other, tolerance)
+    }
+}
+
+impl<T: Approx, const N: usize> Approx for [T; N] {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.iter()
+            .zip(other.iter())
+            .all(|(a, b)| a.approx_eq(b, tolerance))
+    }
+}
+
+impl<T: Approx> Approx for Vec<T> {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .zip(other.iter())
+                .all(|(a, b)| a.approx_eq(b, tolerance))
+    }
+}
+
+impl<T: Approx> Approx for Option<T> {
+    fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+        match (self, other) {
+            (Some(a), Some(b)) => a.approx_eq(b, tolerance),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+macro_rules! approx_tuple {
+    ($($name:ident : $index:tt),*) => {
+        impl<$($name: Approx),*> Approx for ($($name,)*) {
+            fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+                true $(&& self.$index.approx_eq(&other.$index, tolerance))*
+            }
+        }
+    };
+}
+
+approx_tuple!(A: 0, B: 1);
+approx_tuple!(A: 0, B: 1, C: 2);
+approx_tuple!(A: 0, B: 1, C: 2, D: 3);
+approx_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4);
+approx_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+approx_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6);
+approx_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7);
+approx_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7, I: 8);
+approx_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7, I: 8, J: 9);
+approx_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7, I: 8, J: 9, K: 10);
+approx_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7, I: 8, J: 9, K: 10, L: 11);
+approx_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7, I: 8, J: 9, K: 10, L: 11, M: 12);
+approx_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7, I: 8, J: 9, K: 10, L: 11, M: 12, N: 13);
+approx_tuple!(
+    A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7, I: 8, J: 9, K: 10, L: 11, M: 12, N: 13, O: 14
+);
+
+/// types without floats. compared by `PartialEq`
+macro_rules! approx_exact {
+    ($($t:ty),* $(,)?) => {
+        $(impl Approx for $t {
+            fn approx_eq(&self, other: &Self, _tolerance: f32) -> bool {
+                self == other
+            }
+        })*
+    };
+}
+
+approx_exact!(
+    i32,
+    u32,
+    u16,
+    u8,
+    bool,
+    String,
+    Face,
+    FrameInner,
+    SphereMode,
+    ToonMode,
+    MaterialFlags,
+    ControlPanel,
+    MaterialMorphFormula,
+    RigidForm,
+    RigidCalcMethod,
+    RigidGroups,
+    SoftBodyFlags,
+    SoftBodyForm,
+    SoftBodyAeroModel,
+    PMXVersion,
+    Encode,
+    ModelInfo,
+);
+
+macro_rules! approx_struct {
+    ($t:ty { $($field:ident),* $(,)? }) => {
+        impl Approx for $t {
+            fn approx_eq(&self, other: &Self, tolerance: f32) -> bool {
+                true $(&& self.$field.approx_eq(&other.$field, tolerance))*
+            }
+            fn differing_field(&self, other: &Self, tolerance: f32) -> Option<&'static str> {
+                $(if !self.$field.approx_eq(&other.$field, tolerance) {
+                    return Some(stringify!($field));
+                })*
+                None
+            }
+        }
+    };
+}
+
+approx_struct!(Vertex {
+    position,
+    norm,
+    uv,
+    add_uv,
+    weight_type,
+    edge_mag
+});
+approx_struct!(Material {
+    name,
+    english_name,
+    diffuse,
+    specular,
+    specular_factor,
+    ambient,
+    draw_mode,
+    edge_color,
+    edge_size,
+    texture_index,
+    sphere_mode,
+    toon_mode,
+    memo,
+    num_face_vertices
+});
+approx_struct!(Bone {
+    name,
+    english_name,
+    position,
+    parent,
+    deform_depth,
+    connection_display_mode,
+    rotatable_in_viewer,
+    translatable_in_viewer,
+    display_bone_in_viewer,
+    controllable_in_viewer,
+    inherits,
+    fixed_axis,
+    local_axis,
+    physics_after_deform,
+    external_parent,
+    ik_info,
+    reserved_flags
+});
+approx_struct!(BoneInherits {
+    inherit_local,
+    rotate_and_translate
+});
+approx_struct!(BoneIKInfo {
+    ik_target_bone_index,
+    ik_iter_count,
+    ik_limit_angle,
+    ik_links
+});
+approx_struct!(IKLink {
+    ik_bone_index,
+    angle_limit
+});
+approx_struct!(Morph {
+    name,
+    english_name,
+    control_panel,
+    morph_data
+});
+approx_struct!(VertexMorph { index, offset });
+approx_struct!(UVMorph { index, offset });
+approx_struct!(GroupMorph {
+    index,
+    morph_factor
+});
+approx_struct!(FlipMorph {
+    index,
+    morph_factor
+});
+approx_struct!(BoneMorph {
+    index,
+    translates,
+    rotates
+});
+approx_struct!(MaterialMorph {
+    index,
+    formula,
+    diffuse,
+    specular,
+    specular_factor,
+    ambient,
+    edge_color,
+    edge_size,
+    texture_factor,
+    sphere_texture_factor,
+    toon_texture_factor
+});
+approx_struct!(ImpulseMorph {
+    rigid_index,
+    is_local,
+    velocity,
+    torque
+});
+approx_struct!(Frame {
+    name,
+    name_en,
+    is_special,
+    inners
+});
+approx_struct!(Rigid {
+    name,
+    name_en,
+    bone_index,
+    group,
+    un_collision_group_flag,
+    form,
+    size,
+    position,
+    rotation,
+    mass,
+    move_resist,
+    rotation_resist,
+    repulsion,
+    friction,
+    calc_method
+});
+approx_struct!(Joint {
+    name,
+    name_en,
+    joint_type
+});
+approx_struct!(SoftBody {
+    name,
+    name_en,
+    form,
+    material_index,
+    group,
+    un_collision_group_flag,
+    bit_flag,
+    b_link_create_distance,
+    clusters,
+    mass,
+    collision_margin,
+    aero_model,
+    vcf,
+    dp,
+    dg,
+    lf,
+    pr,
+    vc,
+    df,
+    mt,
+    chr,
+    khr,
+    shr,
+    ahr,
+    srhr_cl,
+    skhr_cl,
+    sshr_cl,
+    sr_splt_cl,
+    sk_splt_cl,
+    ss_splt_cl,
+    v_it,
+    p_it,
+    d_it,
+    c_it,
+    lst,
+    ast,
+    vst,
+    anchor_rigid,
+    pin_vertex
+});
+approx_struct!(SoftBodyAnchorRigid {
+    rigid_index,
+    vertex_index,
+    near_mode
+});
+
+/// enums whose variants have named fields.
+/// tuple variants are given as additional match arms which can use `$tolerance`.
+macro_rules! approx_enum {
+    ($t:ident, $tolerance:ident { $($variant:ident { $($field:ident),* }),* $(,)? } $($arms:tt)*) => {
+        impl Approx for $t {
+            fn approx_eq(&self, other: &Self, $tolerance: f32) -> bool {
+                match (self, other) {
+                    $(($t::$variant { .. }, $t::$variant { .. }) => {
+                        let lhs = match self {
+                            $t::$variant { $($field),* } => ($($field,)*),
+                            _ => unreachable!(),
+                        };
+                        let rhs = match other {
+                            $t::$variant { $($field),* } => ($($field,)*),
+                            _ => unreachable!(),
+                        };
+                        lhs.approx_eq(&rhs, $tolerance)
+                    })*
+                    $($arms)*
+                    _ => false,
+                }
+            }
+        }
+    };
+}
+
+approx_enum!(VertexWeight, tolerance {
+    BDEF2 { bone_index_1, bone_index_2, bone_weight_1 },
+    BDEF4 {
+        bone_index_1, bone_index_2, bone_index_3, bone_index_4,
+        bone_weight_1, bone_weight_2, bone_weight_3, bone_weight_4
+    },
+    SDEF { bone_index_1, bone_index_2, bone_weight_1, sdef_c, sdef_r0, sdef_r1 },
+    QDEF {
+        bone_index_1, bone_index_2, bone_index_3, bone_index_4,
+        bone_weight_1, bone_weight_2, bone_weight_3, bone_weight_4
+    },
+}
+    (VertexWeight::BDEF1(a), VertexWeight::BDEF1(b)) => a == b,
+);
+
+approx_enum!(JointType, tolerance {
+    Spring6DOF {
+        a_rigid_index, b_rigid_index, position, rotation, move_limit_down, move_limit_up,
+        rotation_limit_down, rotation_limit_up, spring_const_move, spring_const_rotation
+    },
+    SixDof {
+        a_rigid_index, b_rigid_index, position, rotation, move_limit_down, move_limit_up,
+        rotation_limit_down, rotation_limit_up
+    },
+    P2P { a_rigid_index, b_rigid_index, position, rotation },
+    ConeTwist {
+        a_rigid_index, b_rigid_index, position, rotation, swing_span1, swing_span2, twist_span, softness,
+        bias_factor, relaxation_factor, damping, fix_thresh, enable_motor, max_motor_impulse,
+        motor_target_in_constraint_space
+    },
+    Slider {
+        a_rigid_index, b_rigid_index, position, rotation, lower_linear_limit, upper_linear_limit,
+        lower_angle_limit, upper_angle_limit, power_linear_motor, target_linear_motor_velocity,
+        max_linear_motor_force, power_angler_motor, target_angler_motor_velocity,
+        max_angler_motor_force
+    },
+    Hinge {
+        a_rigid_index, b_rigid_index, position, rotation, low, high, softness, bias_factor, relaxation_factor,
+        enable_motor, target_velocity, max_motor_impulse
+    },
+});
+
+approx_enum!(ConnectionDisplayMode, tolerance {}
+    (ConnectionDisplayMode::OtherBone(a), ConnectionDisplayMode::OtherBone(b)) => a == b,
+    (ConnectionDisplayMode::Offset(a), ConnectionDisplayMode::Offset(b)) => a.approx_eq(b, tolerance),
+);
+
+approx_enum!(RotateAndTranslateInherits, tolerance {}
+    (RotateAndTranslateInherits::None, RotateAndTranslateInherits::None) => true,
+    (RotateAndTranslateInherits::Both(a, x), RotateAndTranslateInherits::Both(b, y))
+    | (RotateAndTranslateInherits::Rotate(a, x), RotateAndTranslateInherits::Rotate(b, y))
+    | (RotateAndTranslateInherits::Translate(a, x), RotateAndTranslateInherits::Translate(b, y)) => {
+        a == b && x.approx_eq(y, tolerance)
+    }
+);
+
+approx_enum!(MorphKinds, tolerance {}
+    (MorphKinds::Vertex(a), MorphKinds::Vertex(b)) => a.approx_eq(b, tolerance),
+    (MorphKinds::UV(a), MorphKinds::UV(b))
+    | (MorphKinds::UV1(a), MorphKinds::UV1(b))
+    | (MorphKinds::UV2(a), MorphKinds::UV2(b))
+    | (MorphKinds::UV3(a), MorphKinds::UV3(b))
+    | (MorphKinds::UV4(a), MorphKinds::UV4(b)) => a.approx_eq(b, tolerance),
+    (MorphKinds::Bone(a), MorphKinds::Bone(b)) => a.approx_eq(b, tolerance),
+    (MorphKinds::Material(a), MorphKinds::Material(b)) => a.approx_eq(b, tolerance),
+    (MorphKinds::Group(a), MorphKinds::Group(b)) => a.approx_eq(b, tolerance),
+    (MorphKinds::Flip(a), MorphKinds::Flip(b)) => a.approx_eq(b, tolerance),
+    (MorphKinds::Impulse(a), MorphKinds::Impulse(b)) => a.approx_eq(b, tolerance),
+);
+
+#[cfg(test)]
+mod test {
+    use crate::diff::{compare, ChangeKind, Section};
+    use crate::types::{Bone, Model, Vertex, VertexWeight};
+
+    fn vertex(position: [f32; 3]) -> Vertex {
+        Vertex {
+            position,
+            norm: [0.0, 1.0, 0.0],
+            uv: [0.0; 2],
+            add_uv: [[0.0; 4]; 4],
+            weight_type: VertexWeight::BDEF1(0),
+            edge_mag: 1.0,
+        }
+    }
+
+    fn model() -> Model {
+        Model {
+            vertices: vec![vertex([0.0; 3]), vertex([1.0, 0.0, 0.0])],
+            bones: vec![
+                Bone {
+                    name: "センター".to_owned(),
+                    ..Bone::default()
+                },
+                Bone {
+                    name: "上半身".to_owned(),
+                    ..Bone::default()
+                },
+            ],
+            ..Model::default()
+        }
+    }
+
+    #[test]
+    fn identical_models() {
+        let diff = compare(&model(), &model(), 0.0);
+        assert!(diff.is_empty());
+        assert_eq!(diff.vertex_position_delta.unwrap().max, 0.0);
+        assert!(diff.to_string().starts_with("no differences"));
+    }
+
+    #[test]
+    fn tolerance_and_sections() {
+        let old = model();
+        let mut new = model();
+        new.vertices[1].position[0] += 0.001;
+        new.vertices.push(vertex([0.0; 3]));
+        new.bones[1].name = "下半身".to_owned();
+        new.bones.remove(0);
+
+        let loose = compare(&old, &new, 0.01);
+        assert_eq!(loose.in_section(Section::Vertices).count(), 1);
+        let strict = compare(&old, &new, 0.0);
+        assert_eq!(strict.in_section(Section::Vertices).count(), 2);
+
+        let delta = strict.vertex_position_delta.unwrap();
+        assert!((delta.max - 0.001).abs() < 1e-6);
+        assert!((delta.mean - 0.0005).abs() < 1e-6);
+
+        let kinds: Vec<_> = strict
+            .iter()
+            .map(|difference| (difference.section, difference.index, difference.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (Section::Vertices, 1, ChangeKind::Changed),
+                (Section::Vertices, 2, ChangeKind::Added),
+                (Section::Bones, 0, ChangeKind::Changed),
+                (Section::Bones, 1, ChangeKind::Removed),
+            ]
+        );
+        let text = strict.to_string();
+        assert!(text.contains("bones[0].name \"下半身\" changed"));
+        assert!(text.contains("vertices[1].position changed"));
+        assert!(text.contains("bones[1] \"上半身\" removed"));
+    }
+
+    #[test]
+    fn assert_approx_eq() {
+        let old = model();
+        let mut new = model();
+        new.vertices[0].norm[2] += 1e-6;
+        crate::assert_model_approx_eq!(old, new, 1e-5);
+
+        new.bones[1].position[1] = 1.0;
+        let message = std::panic::catch_unwind(|| crate::assert_model_approx_eq!(old, new, 1e-5))
+            .unwrap_err()
+            .downcast::<String>()
+            .unwrap();
+        assert!(
+            message.starts_with("models differ more than 0.00001: first bones[1].position"),
+            "{}",
+            message
+        );
+    }
+}
@@ -0,0 +1,636 @@
+//! Edit models by handles which stay valid while elements are inserted, moved or removed.
+//!
+//! [`ModelEditor`] fixes every reference to bones and materials on each edit
+//! so indices never need to be fixed by hand.
+//!
+//! ```rust
+//! use PMXUtil::edit::ModelEditor;
+//! use PMXUtil::types::{Bone, Model};
+//!
+//! let mut model = Model::default();
+//! model.bones = vec![Bone::builder("センター").build(), Bone::builder("上半身").parent(0).build()];
+//! let mut editor = ModelEditor::new(model);
+//! let upper = editor.bone_handle(1).unwrap();
+//! let groove = editor.insert_bone(1, Bone::builder("グルーブ").parent(0).build());
+//! editor.move_bone(upper, Some(groove)).unwrap();
+//! let model = editor.commit().unwrap();
+//! assert_eq!(model.bones[2].parent, 1);
+//! ```
+use crate::convert::{remove_bones, reorder_materials, RemoveBonesError, ReorderError};
+use crate::morph::{remap_indices, IndexMap};
+use crate::progress::SectionKind;
+use crate::types::{
+    Bone, ConnectionDisplayMode, Face, FrameInner, Material, Model, RotateAndTranslateInherits,
+};
+use crate::usage::material_faces;
+use crate::validation::{
+    check_geometry, check_ik, GeometryIssue, GeometryOptions, IkIssue, Severity,
+};
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+
+/// bone in [`ModelEditor`]. invalid after the bone is removed.
+///
+/// only meaningful for the editor which made it. handles of another editor are not detected.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct BoneHandle(u32);
+
+/// material in [`ModelEditor`]. invalid after the material is removed.
+///
+/// only meaningful for the editor which made it. handles of another editor are not detected.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct MaterialHandle(u32);
+
+/// error of [`ModelEditor`] edits. the model is unchanged on error.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum EditError {
+    /// handle was removed.
+    InvalidHandle,
+    /// new parent is the bone itself or its descendant.
+    ParentCycle,
+    RemoveBones(RemoveBonesError),
+    Reorder(ReorderError),
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditError::InvalidHandle => write!(f, "handle is not in the model"),
+            EditError::ParentCycle => write!(f, "bone would be its own ancestor"),
+            EditError::RemoveBones(err) => write!(f, "failed to remove bone: {}", err),
+            EditError::Reorder(err) => write!(f, "failed to move material: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for EditError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EditError::RemoveBones(err) => Some(err),
+            EditError::Reorder(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<RemoveBonesError> for EditError {
+    fn from(err: RemoveBonesError) -> Self {
+        EditError::RemoveBones(err)
+    }
+}
+
+impl From<ReorderError> for EditError {
+    fn from(err: ReorderError) -> Self {
+        EditError::Reorder(err)
+    }
+}
+
+/// problem found by [`ModelEditor::commit`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommitIssue {
+    Ik(IkIssue),
+    /// issues of [`Severity::Error`] with default [`GeometryOptions`].
+    Geometry(GeometryIssue),
+}
+
+impl Display for CommitIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommitIssue::Ik(issue) => issue.fmt(f),
+            CommitIssue::Geometry(issue) => issue.fmt(f),
+        }
+    }
+}
+
+/// edited model has problems. the editor is returned to fix them.
+#[derive(Debug)]
+pub struct CommitError {
+    pub issues: Vec<CommitIssue>,
+    pub editor: Box<ModelEditor>,
+}
+
+impl Display for CommitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "edited model has {} problems", self.issues.len())?;
+        if let Some(issue) = self.issues.first() {
+            write!(f, ", first: {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CommitError {}
+
+/// wraps a model and updates references to bones and materials on each edit.
+///
+/// references in the model are indices as usual. handles returned by the editor follow
+/// elements while their indices change. read the model by [`model`](Self::model)
+/// and edit elements in place by [`bone_mut`](Self::bone_mut) and
+/// [`material_mut`](Self::material_mut).
+#[derive(Debug, Clone)]
+pub struct ModelEditor {
+    model: Model,
+    /// handle of the bone at each index.
+    bones: Vec<BoneHandle>,
+    materials: Vec<MaterialHandle>,
+    /// handles of elements in the original model are their original indices.
+    next_bone: u32,
+    next_material: u32,
+    original_bones: usize,
+    original_materials: usize,
+}
+
+impl ModelEditor {
+    pub fn new(model: Model) -> Self {
+        let bones = (0..model.bones.len() as u32).map(BoneHandle).collect();
+        let materials = (0..model.materials.len() as u32)
+            .map(MaterialHandle)
+            .collect();
+        Self {
+            next_bone: model.bones.len() as u32,
+            next_material: model.materials.len() as u32,
+            original_bones: model.bones.len(),
+            original_materials: model.materials.len(),
+            bones,
+            materials,
+            model,
+        }
+    }
+
+    /// the model edited so far.
+    pub fn model(&self) -> &Model {
+        &self.model
+    }
+
+    /// handle of the bone at `index`.
+    pub fn bone_handle(&self, index: usize) -> Option<BoneHandle> {
+        self.bones.get(index).copied()
+    }
+    /// current index of `bone`. `None` if removed.
+    pub fn bone_index(&self, bone: BoneHandle) -> Option<usize> {
+        self.bones.iter().position(|&handle| handle == bone)
+    }
+    /// the first bone named `name`.
+    pub fn find_bone(&self, name: &str) -> Option<BoneHandle> {
+        let index = self.model.bones.iter().position(|bone| bone.name == name)?;
+        self.bone_handle(index)
+    }
+    pub fn bone(&self, bone: BoneHandle) -> Option<&Bone> {
+        self.bone_index(bone).map(|index| &self.model.bones[index])
+    }
+    /// edit fields of `bone` in place. index fields are not updated by the editor.
+    pub fn bone_mut(&mut self, bone: BoneHandle) -> Option<&mut Bone> {
+        let index = self.bone_index(bone)?;
+        Some(&mut self.model.bones[index])
+    }
+
+    /// insert `bone` at `at` and shift later bones.
+    ///
+    /// indices in `bone` such as `parent` refer bones before the insertion
+    /// and are shifted as well as other references.
+    ///
+    /// # Panics
+    /// * if `at` is greater than bone count.
+    pub fn insert_bone(&mut self, at: usize, mut bone: Bone) -> BoneHandle {
+        assert!(at <= self.bones.len(), "bone index {} is out of range", at);
+        let map = IndexMap::new(
+            SectionKind::Bones,
+            (0..self.bones.len() as i32)
+                .map(|index| Some(if index < at as i32 { index } else { index + 1 }))
+                .collect(),
+        );
+        shift_bones(&mut self.model, &map);
+        shift_bone(&mut bone, &map);
+        self.model.bones.insert(at, bone);
+        let handle = BoneHandle(self.next_bone);
+        self.next_bone += 1;
+        self.bones.insert(at, handle);
+        handle
+    }
+
+    /// insert `bone` after all bones.
+    pub fn push_bone(&mut self, bone: Bone) -> BoneHandle {
+        self.insert_bone(self.bones.len(), bone)
+    }
+
+    /// remove `bone` moving its children, weights and rigid bodies to its parent.
+    ///
+    /// see [`convert::remove_bones`](crate::convert::remove_bones) for details and `force`.
+    ///
+    /// # Errors
+    /// * `EditError::InvalidHandle` if `bone` is not in the model.
+    /// * `EditError::RemoveBones` if [`remove_bones`] fails.
+    pub fn remove_bone(&mut self, bone: BoneHandle, force: bool) -> Result<Bone, EditError> {
+        let index = self.bone_index(bone).ok_or(EditError::InvalidHandle)?;
+        let removed = self.model.bones[index].clone();
+        remove_bones(&mut self.model, &[index], force)?;
+        self.bones.remove(index);
+        Ok(removed)
+    }
+
+    /// make `new_parent` the parent of `bone`. `None` makes it a root bone.
+    ///
+    /// bone order is unchanged. `MMD` deforms bones in order so a parent placed after
+    /// its child lags a frame unless `deform_depth` is adjusted.
+    ///
+    /// # Errors
+    /// * `EditError::InvalidHandle` if a bone is not in the model.
+    /// * `EditError::ParentCycle` if `new_parent` is `bone` or its descendant.
+    pub fn move_bone(
+        &mut self,
+        bone: BoneHandle,
+        new_parent: Option<BoneHandle>,
+    ) -> Result<(), EditError> {
+        let index = self.bone_index(bone).ok_or(EditError::InvalidHandle)?;
+        let parent = match new_parent {
+            None => -1,
+            Some(parent) => {
+                let parent = self.bone_index(parent).ok_or(EditError::InvalidHandle)?;
+                // walk up from the new parent. steps are limited against existing cycles.
+                let mut current = parent as i32;
+                for _ in 0..=self.model.bones.len() {
+                    if current == index as i32 {
+                        return Err(EditError::ParentCycle);
+                    }
+                    match usize::try_from(current)
+                        .ok()
+                        .and_then(|i| self.model.bones.get(i))
+                    {
+                        Some(bone) => current = bone.parent,
+                        None => break,
+                    }
+                }
+                parent as i32
+            }
+        };
+        self.model.bones[index].parent = parent;
+        Ok(())
+    }
+
+    /// new index of each bone of the model given to [`new`](Self::new).
+    /// use it to fix motions or other data referring bones.
+    pub fn bone_map(&self) -> IndexMap {
+        IndexMap::new(
+            SectionKind::Bones,
+            (0..self.original_bones as u32)
+                .map(|handle| {
+                    self.bone_index(BoneHandle(handle))
+                        .map(|index| index as i32)
+                })
+                .collect(),
+        )
+    }
+
+    /// handle of the material at `index`.
+    pub fn material_handle(&self, index: usize) -> Option<MaterialHandle> {
+        self.materials.get(index).copied()
+    }
+    /// current index of `material`. `None` if removed.
+    pub fn material_index(&self, material: MaterialHandle) -> Option<usize> {
+        self.materials.iter().position(|&handle| handle == material)
+    }
+    pub fn material(&self, material: MaterialHandle) -> Option<&Material> {
+        self.material_index(material)
+            .map(|index| &self.model.materials[index])
+    }
+    /// edit fields of `material` in place. `num_face_vertices` needs to stay the same.
+    pub fn material_mut(&mut self, material: MaterialHandle) -> Option<&mut Material> {
+        let index = self.material_index(material)?;
+        Some(&mut self.model.materials[index])
+    }
+
+    /// insert `material` drawing `faces` at `at` and shift later materials.
+    ///
+    /// faces are inserted before faces of later materials and `num_face_vertices` is set.
+    ///
+    /// # Panics
+    /// * if `at` is greater than material count.
+    pub fn insert_material(
+        &mut self,
+        at: usize,
+        mut material: Material,
+        faces: &[Face],
+    ) -> MaterialHandle {
+        assert!(
+            at <= self.materials.len(),
+            "material index {} is out of range",
+            at
+        );
+        let start = material_faces(&self.model)
+            .get(at)
+            .map_or(self.model.faces.len(), |range| range.start);
+        self.model.faces.splice(start..start, faces.iter().copied());
+        material.num_face_vertices = faces.len() as i32 * 3;
+        let map = IndexMap::new(
+            SectionKind::Materials,
+            (0..self.materials.len() as i32)
+                .map(|index| Some(if index < at as i32 { index } else { index + 1 }))
+                .collect(),
+        );
+        remap_materials(&mut self.model, &map);
+        self.model.materials.insert(at, material);
+        let handle = MaterialHandle(self.next_material);
+        self.next_material += 1;
+        self.materials.insert(at, handle);
+        handle
+    }
+
+    /// insert `material` drawing `faces` after all materials.
+    pub fn push_material(&mut self, material: Material, faces: &[Face]) -> MaterialHandle {
+        self.insert_material(self.materials.len(), material, faces)
+    }
+
+    /// remove `material` and its faces.
+    ///
+    /// its material morph offsets are removed and soft bodies on it refer no material (`-1`).
+    /// vertices used only by the material are kept.
+    ///
+    /// # Errors
+    /// * `EditError::InvalidHandle` if `material` is not in the model.
+    pub fn remove_material(&mut self, material: MaterialHandle) -> Result<Material, EditError> {
+        let index = self
+            .material_index(material)
+            .ok_or(EditError::InvalidHandle)?;
+        let faces = material_faces(&self.model).swap_remove(index);
+        self.model.faces.drain(faces);
+        let map = IndexMap::removing(SectionKind::Materials, self.materials.len(), &[index]);
+        remap_materials(&mut self.model, &map);
+        self.materials.remove(index);
+        Ok(self.model.materials.remove(index))
+    }
+
+    /// move `material` to `to` together with its faces. `MMD` draws materials in order.
+    ///
+    /// # Errors
+    /// * `EditError::InvalidHandle` if `material` is not in the model.
+    /// * `EditError::Reorder` if `to` is out of range or faces of materials don't cover
+    ///   all faces. see [`reorder_materials`].
+    pub fn move_material(&mut self, material: MaterialHandle, to: usize) -> Result<(), EditError> {
+        let index = self
+            .material_index(material)
+            .ok_or(EditError::InvalidHandle)?;
+        if to >= self.materials.len() {
+            return Err(ReorderError::InvalidIndex(to).into());
+        }
+        let mut order = (0..self.materials.len()).collect::<Vec<_>>();
+        let moved = order.remove(index);
+        order.insert(to, moved);
+        reorder_materials(&mut self.model, &order)?;
+        let handle = self.materials.remove(index);
+        self.materials.insert(to, handle);
+        Ok(())
+    }
+
+    /// new index of each material of the model given to [`new`](Self::new).
+    pub fn material_map(&self) -> IndexMap {
+        IndexMap::new(
+            SectionKind::Materials,
+            (0..self.original_materials as u32)
+                .map(|handle| {
+                    self.material_index(MaterialHandle(handle))
+                        .map(|index| index as i32)
+                })
+                .collect(),
+        )
+    }
+
+    /// problems of the edited model: IK setup and geometry errors such as face counts.
+    pub fn validate(&self) -> Vec<CommitIssue> {
+        let ik = check_ik(&self.model.bones).into_iter().map(CommitIssue::Ik);
+        let geometry = check_geometry(&self.model, &GeometryOptions::default())
+            .into_iter()
+            .filter(|(severity, _)| *severity == Severity::Error)
+            .map(|(_, issue)| CommitIssue::Geometry(issue));
+        ik.chain(geometry).collect()
+    }
+
+    /// finish editing if [`validate`](Self::validate) finds nothing.
+    ///
+    /// # Errors
+    /// * `CommitError` with the issues and this editor otherwise.
+    pub fn commit(self) -> Result<Model, CommitError> {
+        let issues = self.validate();
+        if issues.is_empty() {
+            Ok(self.model)
+        } else {
+            Err(CommitError {
+                issues,
+                editor: Box::new(self),
+            })
+        }
+    }
+}
+
+/// move references to bones by `map` keeping every bone.
+pub(crate) fn shift_bones(model: &mut Model, map: &IndexMap) {
+    let shift = |index: &mut i32| *index = map.get(*index).unwrap_or(*index);
+    for vertex in &mut model.vertices {
+        vertex
+            .weight_type
+            .bone_indices_mut()
+            .into_iter()
+            .for_each(shift);
+    }
+    for bone in &mut model.bones {
+        shift_bone(bone, map);
+    }
+    for morph in &mut model.morphs {
+        remap_indices(morph, map);
+    }
+    for inner in model.frames.iter_mut().flat_map(|frame| &mut frame.inners) {
+        if let FrameInner::Bone(index) = inner {
+            shift(index);
+        }
+    }
+    for rigid in &mut model.rigid_bodies {
+        shift(&mut rigid.bone_index);
+    }
+}
+
+fn shift_bone(bone: &mut Bone, map: &IndexMap) {
+    let shift = |index: &mut i32| *index = map.get(*index).unwrap_or(*index);
+    shift(&mut bone.parent);
+    if let ConnectionDisplayMode::OtherBone(other) = &mut bone.connection_display_mode {
+        shift(other);
+    }
+    match &mut bone.inherits.rotate_and_translate {
+        RotateAndTranslateInherits::Both(parent, _)
+        | RotateAndTranslateInherits::Rotate(parent, _)
+        | RotateAndTranslateInherits::Translate(parent, _) => shift(parent),
+        RotateAndTranslateInherits::None => {}
+    }
+    if let Some(info) = &mut bone.ik_info {
+        shift(&mut info.ik_target_bone_index);
+        for link in &mut info.ik_links {
+            shift(&mut link.ik_bone_index);
+        }
+    }
+}
+
+/// move references to materials by `map`. soft bodies on removed materials refer none.
+fn remap_materials(model: &mut Model, map: &IndexMap) {
+    for morph in &mut model.morphs {
+        remap_indices(morph, map);
+    }
+    for soft_body in &mut model.soft_bodies {
+        soft_body.material_index = map.get(soft_body.material_index).unwrap_or(-1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::edit::{CommitIssue, EditError, ModelEditor};
+    use crate::types::{
+        Bone, ConnectionDisplayMode, Face, FrameInner, Material, Model, MorphKinds,
+        RotateAndTranslateInherits,
+    };
+    use crate::validation::{GeometryIssue, IkIssue};
+
+    /// cube without IK links. links of cube are not ancestors of the target.
+    fn model() -> Model {
+        let mut cube = crate::test_support::cube();
+        let info = cube.bones[1].ik_info.as_mut().unwrap();
+        info.ik_links.truncate(1);
+        info.ik_target_bone_index = 1;
+        cube
+    }
+
+    #[test]
+    fn bone_edits() {
+        let cube = model();
+        let mut editor = ModelEditor::new(cube.clone());
+        let center = editor.find_bone("センター").unwrap();
+        let ik = editor.find_bone("足ＩＫ").unwrap();
+        let root = editor.insert_bone(0, Bone::builder("全ての親").build());
+        editor.move_bone(center, Some(root)).unwrap();
+        assert_eq!(
+            editor.move_bone(root, Some(ik)),
+            Err(EditError::ParentCycle)
+        );
+        assert_eq!(editor.bone_index(ik), Some(2));
+
+        let model = editor.model();
+        assert_eq!(model.bones[1].parent, 0);
+        let ik_bone = &model.bones[2];
+        assert_eq!(ik_bone.parent, 1);
+        assert_eq!(
+            ik_bone.inherits.rotate_and_translate,
+            match cube.bones[1].inherits.rotate_and_translate {
+                RotateAndTranslateInherits::Rotate(_, weight) => {
+                    RotateAndTranslateInherits::Rotate(1, weight)
+                }
+                other => panic!("{:?}", other),
+            }
+        );
+        let info = ik_bone.ik_info.as_ref().unwrap();
+        assert_eq!(info.ik_target_bone_index, 2);
+        assert_eq!(info.ik_links[0].ik_bone_index, 1);
+        assert_eq!(model.frames[0].inners, [FrameInner::Bone(1)]);
+        assert!(model.morphs.iter().any(|morph| matches!(
+            &morph.morph_data,
+            MorphKinds::Bone(offsets) if offsets[0].index == 2
+        )));
+        for (old, new) in cube.vertices.iter().zip(&model.vertices) {
+            let old = old.weight_type.bone_weights();
+            let new = new.weight_type.bone_weights();
+            for ((old, _), (new, _)) in old.iter().zip(&new) {
+                assert_eq!(*new, old + 1);
+            }
+        }
+        assert_eq!(
+            model.rigid_bodies[0].bone_index,
+            cube.rigid_bodies[0].bone_index + 1
+        );
+        let map = editor.bone_map();
+        assert_eq!(map.new_indices, [Some(1), Some(2)]);
+
+        let removed = editor.remove_bone(root, false).unwrap();
+        assert_eq!(removed.name, "全ての親");
+        assert_eq!(editor.bone_index(root), None);
+        assert_eq!(
+            editor.remove_bone(root, false),
+            Err(EditError::InvalidHandle)
+        );
+        assert_eq!(editor.bone_mut(ik).unwrap().name, "足ＩＫ");
+        let model = editor.commit().unwrap();
+        assert_eq!(model, cube);
+    }
+
+    #[test]
+    fn material_edits() {
+        let cube = model();
+        let mut editor = ModelEditor::new(cube.clone());
+        let first = editor.material_handle(0).unwrap();
+        let faces = [Face {
+            vertices: [0, 1, 2],
+        }];
+        let added = editor.insert_material(1, Material::builder("追加").build(), &faces);
+        assert_eq!(editor.material(added).unwrap().num_face_vertices, 3);
+        let model = editor.model();
+        assert_eq!(model.faces.len(), cube.faces.len() + 1);
+        assert_eq!(model.faces[6], faces[0]);
+        assert_eq!(editor.material_map().new_indices, [Some(0), Some(2)]);
+
+        editor.move_material(first, 2).unwrap();
+        assert_eq!(editor.material_index(first), Some(2));
+        assert_eq!(editor.model().faces[0], faces[0]);
+        assert_eq!(editor.model().faces[7..], cube.faces[..6]);
+        assert!(matches!(
+            editor.move_material(first, 3),
+            Err(EditError::Reorder(_))
+        ));
+
+        editor.remove_material(added).unwrap();
+        editor.remove_material(first).unwrap();
+        assert_eq!(editor.model().faces, cube.faces[6..]);
+        assert_eq!(editor.material_map().new_indices, [None, Some(0)]);
+
+        // faces of the removed material are gone so counts stay consistent.
+        let model = editor.commit().unwrap();
+        assert_eq!(model.materials.len(), 1);
+        assert_eq!(model.materials[0].name, cube.materials[1].name);
+    }
+
+    #[test]
+    fn commit_validates() {
+        let mut editor = ModelEditor::new(model());
+        let center = editor.bone_handle(0).unwrap();
+        let ik = editor.bone_handle(1).unwrap();
+        editor
+            .bone_mut(ik)
+            .unwrap()
+            .ik_info
+            .as_mut()
+            .unwrap()
+            .ik_iter_count = 0;
+        editor
+            .material_mut(editor.material_handle(0).unwrap())
+            .unwrap()
+            .num_face_vertices = 3;
+        let err = editor.commit().unwrap_err();
+        assert_eq!(
+            err.issues,
+            [
+                CommitIssue::Ik(IkIssue::NoIteration {
+                    bone: 1,
+                    iterations: 0
+                }),
+                CommitIssue::Geometry(GeometryIssue::MaterialFaceCount {
+                    material_vertices: 21,
+                    face_vertices: 36
+                }),
+            ]
+        );
+        // the editor is given back to fix issues.
+        let mut editor = err.editor;
+        assert_eq!(editor.bone(center).unwrap().name, "センター");
+        let connection = ConnectionDisplayMode::OtherBone(1);
+        editor.bone_mut(center).unwrap().connection_display_mode = connection;
+        editor.insert_bone(1, Bone::builder("追加").build());
+        assert_eq!(
+            editor.bone(center).unwrap().connection_display_mode,
+            ConnectionDisplayMode::OtherBone(2)
+        );
+    }
+}
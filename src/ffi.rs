@@ -0,0 +1,497 @@
+//! C ABI for loading and inspecting models.
+//!
+//! build a C library with
+//! `cargo rustc --release --features ffi --crate-type cdylib` (or `staticlib`)
+//! and include `include/pmxutil.h`.
+//!
+//! # Ownership
+//! * [`pmxutil_load_from_memory`] copies nothing from the input after it returns.
+//!   the caller keeps owning the input buffer.
+//! * the returned handle is owned by the caller and must be released by [`pmxutil_free`] exactly once.
+//! * strings returned by getters are borrowed from the handle.
+//!   they are UTF-8 without NUL terminator and valid until the handle is freed.
+//! * `copy_*` and `*_get` functions write into memory owned by the caller.
+//!
+//! Every function accepts null handle and reports it as [`PmxError::NullPointer`]
+//! or returns `0` / null.
+use crate::reader::ModelInfoStage;
+use crate::types::{Header, Material, Model, SphereModeKind, ToonMode};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+/// opaque handle of a parsed model.
+pub struct PmxModel(Model);
+
+/// result of fallible functions.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PmxError {
+    Ok = 0,
+    /// required pointer argument was null.
+    NullPointer = 1,
+    /// magic number, version or encoding is invalid.
+    InvalidHeader = 2,
+    /// the file is truncated or broken.
+    InvalidData = 3,
+    /// index is out of range.
+    OutOfRange = 4,
+}
+
+/// material parameters except strings.
+///
+/// use [`pmxutil_material_name`] for the name.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct PmxMaterial {
+    pub diffuse: [f32; 4],
+    pub specular: [f32; 3],
+    pub specular_factor: f32,
+    pub ambient: [f32; 3],
+    /// bits of [`MaterialFlags`](crate::types::MaterialFlags)
+    pub draw_mode: u8,
+    pub edge_color: [f32; 4],
+    pub edge_size: f32,
+    /// `-1` if none.
+    pub texture_index: i32,
+    /// `-1` if none.
+    pub sphere_texture_index: i32,
+    /// 0: none 1: multiply 2: add 3: sub texture
+    pub sphere_mode: u8,
+    /// 0: `toon_index` refers texture list 1: `toon_index` is common toon 0 ~ 9
+    pub toon_is_common: u8,
+    pub toon_index: i32,
+    /// count of face vertices (3 per triangle) using this material.
+    pub num_face_vertices: i32,
+}
+
+impl From<&Material> for PmxMaterial {
+    fn from(material: &Material) -> Self {
+        let (sphere_texture_index, sphere_mode) = match material.sphere_mode {
+            None => (-1, 0),
+            Some(sphere_mode) => (
+                sphere_mode.index,
+                match sphere_mode.kind {
+                    SphereModeKind::Mul => 1,
+                    SphereModeKind::Add => 2,
+                    SphereModeKind::SubTexture => 3,
+                },
+            ),
+        };
+        let (toon_is_common, toon_index) = match material.toon_mode {
+            ToonMode::Separate(index) => (0, index),
+            ToonMode::Common(index) => (1, i32::from(index)),
+        };
+        Self {
+            diffuse: material.diffuse,
+            specular: material.specular,
+            specular_factor: material.specular_factor,
+            ambient: material.ambient,
+            draw_mode: material.draw_mode.bits(),
+            edge_color: material.edge_color,
+            edge_size: material.edge_size,
+            texture_index: material.texture_index,
+            sphere_texture_index,
+            sphere_mode,
+            toon_is_common,
+            toon_index,
+            num_face_vertices: material.num_face_vertices,
+        }
+    }
+}
+
+unsafe fn model<'a>(handle: *const PmxModel) -> Option<&'a Model> {
+    handle.as_ref().map(|handle| &handle.0)
+}
+
+/// expose `text` and write its length to `out_len`.
+unsafe fn text(text: Option<&str>, out_len: *mut usize) -> *const u8 {
+    let (ptr, len) = match text {
+        Some(text) => (text.as_ptr(), text.len()),
+        None => (ptr::null(), 0),
+    };
+    if !out_len.is_null() {
+        *out_len = len;
+    }
+    ptr
+}
+
+/// copy `N` floats per element of `list` to `out_ptr`.
+unsafe fn copy<T, const N: usize>(
+    list: &[T],
+    f: impl Fn(&T) -> [f32; N],
+    out_ptr: *mut f32,
+    cap: usize,
+) -> usize {
+    if out_ptr.is_null() {
+        return 0;
+    }
+    let count = list.len().min(cap);
+    let out = std::slice::from_raw_parts_mut(out_ptr, count * N);
+    for (chunk, element) in out.chunks_exact_mut(N).zip(list) {
+        chunk.copy_from_slice(&f(element));
+    }
+    count
+}
+
+/// parse PMX in `ptr[0..len]`.
+///
+/// on success writes a new handle to `out_handle`.
+/// release it with [`pmxutil_free`].
+///
+/// # Safety
+/// * `ptr` must be valid for reads of `len` bytes.
+/// * `out_handle` must be valid for a write of a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_load_from_memory(
+    ptr: *const u8,
+    len: usize,
+    out_handle: *mut *mut PmxModel,
+) -> PmxError {
+    if ptr.is_null() || out_handle.is_null() {
+        return PmxError::NullPointer;
+    }
+    let bytes = std::slice::from_raw_parts(ptr, len);
+    if bytes.len() < Header::SIZE {
+        return PmxError::InvalidData;
+    }
+    // broken data is an error but nothing may unwind into C anyway.
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        ModelInfoStage::from_reader(bytes).map(ModelInfoStage::try_read_model)
+    }));
+    match result {
+        Ok(Some(Ok(model))) => {
+            *out_handle = Box::into_raw(Box::new(PmxModel(model)));
+            PmxError::Ok
+        }
+        Ok(None) => PmxError::InvalidHeader,
+        Ok(Some(Err(_))) | Err(_) => PmxError::InvalidData,
+    }
+}
+
+/// release `handle` returned by [`pmxutil_load_from_memory`].
+///
+/// strings got from `handle` are invalid after this. null is ignored.
+///
+/// # Safety
+/// `handle` must be null or a handle not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_free(handle: *mut PmxModel) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// vertex count. `0` if `handle` is null.
+///
+/// # Safety
+/// `handle` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_vertex_count(handle: *const PmxModel) -> usize {
+    model(handle).map_or(0, |model| model.vertices.len())
+}
+
+/// face count. `0` if `handle` is null.
+///
+/// # Safety
+/// `handle` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_face_count(handle: *const PmxModel) -> usize {
+    model(handle).map_or(0, |model| model.faces.len())
+}
+
+/// texture count. `0` if `handle` is null.
+///
+/// # Safety
+/// `handle` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_texture_count(handle: *const PmxModel) -> usize {
+    model(handle).map_or(0, |model| model.textures.textures.len())
+}
+
+/// material count. `0` if `handle` is null.
+///
+/// # Safety
+/// `handle` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_material_count(handle: *const PmxModel) -> usize {
+    model(handle).map_or(0, |model| model.materials.len())
+}
+
+/// bone count. `0` if `handle` is null.
+///
+/// # Safety
+/// `handle` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_bone_count(handle: *const PmxModel) -> usize {
+    model(handle).map_or(0, |model| model.bones.len())
+}
+
+/// morph count. `0` if `handle` is null.
+///
+/// # Safety
+/// `handle` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_morph_count(handle: *const PmxModel) -> usize {
+    model(handle).map_or(0, |model| model.morphs.len())
+}
+
+/// rigid body count. `0` if `handle` is null.
+///
+/// # Safety
+/// `handle` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_rigid_body_count(handle: *const PmxModel) -> usize {
+    model(handle).map_or(0, |model| model.rigid_bodies.len())
+}
+
+/// joint count. `0` if `handle` is null.
+///
+/// # Safety
+/// `handle` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_joint_count(handle: *const PmxModel) -> usize {
+    model(handle).map_or(0, |model| model.joints.len())
+}
+
+/// copy vertex positions as `x y z` to `out_ptr`.
+///
+/// returns copied vertex count which is at most `cap`.
+///
+/// # Safety
+/// * `handle` must be null or a live handle.
+/// * `out_ptr` must be null or valid for writes of `cap * 3` floats.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_copy_positions(
+    handle: *const PmxModel,
+    out_ptr: *mut f32,
+    cap: usize,
+) -> usize {
+    model(handle).map_or(0, |model| {
+        copy(&model.vertices, |vertex| vertex.position, out_ptr, cap)
+    })
+}
+
+/// copy vertex normals as `x y z` to `out_ptr`.
+///
+/// returns copied vertex count which is at most `cap`.
+///
+/// # Safety
+/// * `handle` must be null or a live handle.
+/// * `out_ptr` must be null or valid for writes of `cap * 3` floats.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_copy_normals(
+    handle: *const PmxModel,
+    out_ptr: *mut f32,
+    cap: usize,
+) -> usize {
+    model(handle).map_or(0, |model| {
+        copy(&model.vertices, |vertex| vertex.norm, out_ptr, cap)
+    })
+}
+
+/// copy vertex uvs as `u v` to `out_ptr`.
+///
+/// returns copied vertex count which is at most `cap`.
+///
+/// # Safety
+/// * `handle` must be null or a live handle.
+/// * `out_ptr` must be null or valid for writes of `cap * 2` floats.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_copy_uvs(
+    handle: *const PmxModel,
+    out_ptr: *mut f32,
+    cap: usize,
+) -> usize {
+    model(handle).map_or(0, |model| {
+        copy(&model.vertices, |vertex| vertex.uv, out_ptr, cap)
+    })
+}
+
+/// copy vertex indices of faces to `out_ptr`. 3 indices per face.
+///
+/// returns copied face count which is at most `cap`.
+///
+/// # Safety
+/// * `handle` must be null or a live handle.
+/// * `out_ptr` must be null or valid for writes of `cap * 3` integers.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_copy_indices(
+    handle: *const PmxModel,
+    out_ptr: *mut u32,
+    cap: usize,
+) -> usize {
+    let model = match model(handle) {
+        Some(model) if !out_ptr.is_null() => model,
+        _ => return 0,
+    };
+    let count = model.faces.len().min(cap);
+    let out = std::slice::from_raw_parts_mut(out_ptr, count * 3);
+    for (chunk, face) in out.chunks_exact_mut(3).zip(&model.faces) {
+        chunk.copy_from_slice(&face.vertices);
+    }
+    count
+}
+
+/// write material at `index` to `out_struct`.
+///
+/// # Safety
+/// * `handle` must be null or a live handle.
+/// * `out_struct` must be null or valid for a write of [`PmxMaterial`].
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_material_get(
+    handle: *const PmxModel,
+    index: usize,
+    out_struct: *mut PmxMaterial,
+) -> PmxError {
+    let model = match model(handle) {
+        Some(model) if !out_struct.is_null() => model,
+        _ => return PmxError::NullPointer,
+    };
+    match model.materials.get(index) {
+        Some(material) => {
+            *out_struct = PmxMaterial::from(material);
+            PmxError::Ok
+        }
+        None => PmxError::OutOfRange,
+    }
+}
+
+/// model name in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+///
+/// writes byte length to `out_len`. not NUL terminated.
+///
+/// # Safety
+/// * `handle` must be null or a live handle.
+/// * `out_len` must be null or valid for a write of `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_model_name(
+    handle: *const PmxModel,
+    out_len: *mut usize,
+) -> *const u8 {
+    text(
+        model(handle).map(|model| model.model_info.name.as_str()),
+        out_len,
+    )
+}
+
+/// model name in english in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+///
+/// writes byte length to `out_len`. not NUL terminated.
+///
+/// # Safety
+/// * `handle` must be null or a live handle.
+/// * `out_len` must be null or valid for a write of `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_model_name_en(
+    handle: *const PmxModel,
+    out_len: *mut usize,
+) -> *const u8 {
+    text(
+        model(handle).map(|model| model.model_info.name_en.as_str()),
+        out_len,
+    )
+}
+
+/// model comment in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+///
+/// writes byte length to `out_len`. not NUL terminated.
+///
+/// # Safety
+/// * `handle` must be null or a live handle.
+/// * `out_len` must be null or valid for a write of `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_model_comment(
+    handle: *const PmxModel,
+    out_len: *mut usize,
+) -> *const u8 {
+    text(
+        model(handle).map(|model| model.model_info.comment.as_str()),
+        out_len,
+    )
+}
+
+/// model comment in english in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+///
+/// writes byte length to `out_len`. not NUL terminated.
+///
+/// # Safety
+/// * `handle` must be null or a live handle.
+/// * `out_len` must be null or valid for a write of `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_model_comment_en(
+    handle: *const PmxModel,
+    out_len: *mut usize,
+) -> *const u8 {
+    text(
+        model(handle).map(|model| model.model_info.comment_en.as_str()),
+        out_len,
+    )
+}
+
+/// texture path relative to the model at `index` in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+///
+/// writes byte length to `out_len`. not NUL terminated. null if `index` is out of range.
+///
+/// # Safety
+/// * `handle` must be null or a live handle.
+/// * `out_len` must be null or valid for a write of `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_texture_path(
+    handle: *const PmxModel,
+    index: usize,
+    out_len: *mut usize,
+) -> *const u8 {
+    let texture = model(handle).and_then(|model| model.textures.textures.get(index));
+    text(texture.map(|texture| texture.as_str()), out_len)
+}
+
+/// material name at `index` in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+///
+/// writes byte length to `out_len`. not NUL terminated. null if `index` is out of range.
+///
+/// # Safety
+/// * `handle` must be null or a live handle.
+/// * `out_len` must be null or valid for a write of `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_material_name(
+    handle: *const PmxModel,
+    index: usize,
+    out_len: *mut usize,
+) -> *const u8 {
+    let material = model(handle).and_then(|model| model.materials.get(index));
+    text(material.map(|material| material.name.as_str()), out_len)
+}
+
+/// bone name at `index` in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+///
+/// writes byte length to `out_len`. not NUL terminated. null if `index` is out of range.
+///
+/// # Safety
+/// * `handle` must be null or a live handle.
+/// * `out_len` must be null or valid for a write of `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_bone_name(
+    handle: *const PmxModel,
+    index: usize,
+    out_len: *mut usize,
+) -> *const u8 {
+    let bone = model(handle).and_then(|model| model.bones.get(index));
+    text(bone.map(|bone| bone.name.as_str()), out_len)
+}
+
+/// morph name at `index` in UTF-8 borrowed from `handle`. valid until `handle` is freed.
+///
+/// writes byte length to `out_len`. not NUL terminated. null if `index` is out of range.
+///
+/// # Safety
+/// * `handle` must be null or a live handle.
+/// * `out_len` must be null or valid for a write of `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn pmxutil_morph_name(
+    handle: *const PmxModel,
+    index: usize,
+    out_len: *mut usize,
+) -> *const u8 {
+    let morph = model(handle).and_then(|model| model.morphs.get(index));
+    text(morph.map(|morph| morph.name.as_str()), out_len)
+}
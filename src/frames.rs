@@ -0,0 +1,262 @@
+//! Display frames `MMD` expects.
+//!
+//! `MMD` requires the first frame to be special "Root" frame holding the root bone
+//! and the second one to be special "表情" frame holding morphs.
+//!
+//! ```rust
+//! use PMXUtil::frames::generate_default;
+//! use PMXUtil::types::Bone;
+//!
+//! let bones = vec![Bone::builder("センター").build(), Bone::builder("上半身").build()];
+//! let frames = generate_default(&bones, &[]);
+//! assert_eq!(frames[0].name, "Root");
+//! assert_eq!(frames[1].name, "表情");
+//! assert_eq!(frames[2].name, "ボーン");
+//! ```
+use crate::types::{Bone, Frame, FrameInner, Morph};
+use std::fmt::{Display, Formatter};
+
+/// name of the special frame holding the root bone.
+pub const ROOT_FRAME_NAME: &str = "Root";
+/// name of the special frame holding morphs.
+pub const EXPRESSION_FRAME_NAME: &str = "表情";
+/// name of the frame [`generate_default`] puts other bones.
+pub const DEFAULT_BONE_FRAME_NAME: &str = "ボーン";
+
+/// special "Root" frame holding bone 0 if any.
+pub fn root_frame(bones: &[Bone]) -> Frame {
+    Frame {
+        name: ROOT_FRAME_NAME.to_owned(),
+        name_en: "Root".to_owned(),
+        is_special: true,
+        inners: if bones.is_empty() {
+            vec![]
+        } else {
+            vec![FrameInner::Bone(0)]
+        },
+    }
+}
+
+/// special "表情" frame holding all morphs.
+pub fn expression_frame(morphs: &[Morph]) -> Frame {
+    Frame {
+        name: EXPRESSION_FRAME_NAME.to_owned(),
+        name_en: "Exp".to_owned(),
+        is_special: true,
+        inners: (0..morphs.len() as i32).map(FrameInner::Morph).collect(),
+    }
+}
+
+/// true if `frame` is special frame named `name`.
+pub fn is_special_frame(frame: &Frame, name: &str) -> bool {
+    frame.is_special && frame.name == name
+}
+
+/// Root and 表情 frames followed by a frame named [`DEFAULT_BONE_FRAME_NAME`]
+/// holding bones other than root.
+pub fn generate_default(bones: &[Bone], morphs: &[Morph]) -> Vec<Frame> {
+    generate(bones, morphs, DEFAULT_BONE_FRAME_NAME)
+}
+
+/// [`generate_default`] putting bones other than root in frame named `bone_frame_name`.
+///
+/// the bone frame is omitted if there is no such bone.
+pub fn generate(bones: &[Bone], morphs: &[Morph], bone_frame_name: &str) -> Vec<Frame> {
+    let mut frames = vec![root_frame(bones), expression_frame(morphs)];
+    if bones.len() > 1 {
+        frames.push(Frame {
+            name: bone_frame_name.to_owned(),
+            name_en: bone_frame_name.to_owned(),
+            is_special: false,
+            inners: (1..bones.len() as i32).map(FrameInner::Bone).collect(),
+        });
+    }
+    frames
+}
+
+/// element rejected by a special frame in [`add_bone_to`] or [`add_morph_to`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum FrameError {
+    /// special "Root" frame holds only bones.
+    MorphInRootFrame,
+    /// special "表情" frame holds only morphs.
+    BoneInExpressionFrame,
+}
+
+impl Display for FrameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::MorphInRootFrame => write!(f, "Root frame can't hold morphs"),
+            FrameError::BoneInExpressionFrame => write!(f, "表情 frame can't hold bones"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+/// index of the first frame named `name`.
+pub fn find_by_name(frames: &[Frame], name: &str) -> Option<usize> {
+    frames.iter().position(|frame| frame.name == name)
+}
+
+/// add bone `bone` to frame named `frame_name` and return index of the frame.
+///
+/// a frame is appended if there is no such frame. nothing is added if the bone is already in it.
+/// # Errors
+/// * `FrameError::BoneInExpressionFrame` if the frame is special "表情" frame.
+pub fn add_bone_to(
+    frames: &mut Vec<Frame>,
+    frame_name: &str,
+    bone: i32,
+) -> Result<usize, FrameError> {
+    add_to(frames, frame_name, FrameInner::Bone(bone))
+}
+
+/// add morph `morph` to frame named `frame_name` and return index of the frame.
+///
+/// a frame is appended if there is no such frame. nothing is added if the morph is already in it.
+/// # Errors
+/// * `FrameError::MorphInRootFrame` if the frame is special "Root" frame.
+pub fn add_morph_to(
+    frames: &mut Vec<Frame>,
+    frame_name: &str,
+    morph: i32,
+) -> Result<usize, FrameError> {
+    add_to(frames, frame_name, FrameInner::Morph(morph))
+}
+
+fn add_to(
+    frames: &mut Vec<Frame>,
+    frame_name: &str,
+    inner: FrameInner,
+) -> Result<usize, FrameError> {
+    let index = match find_by_name(frames, frame_name) {
+        Some(index) => index,
+        None => {
+            frames.push(Frame {
+                name: frame_name.to_owned(),
+                name_en: frame_name.to_owned(),
+                is_special: false,
+                inners: vec![],
+            });
+            frames.len() - 1
+        }
+    };
+    let frame = &mut frames[index];
+    match inner {
+        FrameInner::Morph(_) if is_special_frame(frame, ROOT_FRAME_NAME) => {
+            return Err(FrameError::MorphInRootFrame)
+        }
+        FrameInner::Bone(_) if is_special_frame(frame, EXPRESSION_FRAME_NAME) => {
+            return Err(FrameError::BoneInExpressionFrame)
+        }
+        _ => {}
+    }
+    if !frame.inners.contains(&inner) {
+        frame.inners.push(inner);
+    }
+    Ok(index)
+}
+
+/// update frames for removal of bone `bone`.
+///
+/// references to `bone` are removed and larger bone indices are decreased by one,
+/// as `bones.remove(bone)` shifts them.
+pub fn remove_references_to_bone(frames: &mut [Frame], bone: i32) {
+    for frame in frames {
+        frame.inners.retain_mut(|inner| match inner {
+            FrameInner::Bone(index) if *index == bone => false,
+            FrameInner::Bone(index) => {
+                if *index > bone {
+                    *index -= 1;
+                }
+                true
+            }
+            FrameInner::Morph(_) => true,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builder::standard_humanoid_skeleton;
+    use crate::frames::{
+        add_bone_to, add_morph_to, find_by_name, generate, remove_references_to_bone, FrameError,
+        EXPRESSION_FRAME_NAME, ROOT_FRAME_NAME,
+    };
+    use crate::types::FrameInner;
+
+    #[test]
+    fn generate_frames() {
+        let bones = standard_humanoid_skeleton();
+        let morphs = &crate::test_support::cube().morphs[..3];
+        let frames = generate(&bones, morphs, "体");
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].name, ROOT_FRAME_NAME);
+        assert_eq!(frames[0].inners, [FrameInner::Bone(0)]);
+        assert_eq!(frames[1].name, EXPRESSION_FRAME_NAME);
+        assert_eq!(frames[1].inners, [0, 1, 2].map(FrameInner::Morph).to_vec());
+        assert_eq!(frames[2].name, "体");
+        assert!(!frames[2].is_special);
+        assert_eq!(frames[2].inners.len(), bones.len() - 1);
+
+        let frames = generate(&bones[..1], &[], "体");
+        assert_eq!(frames.len(), 2);
+        assert!(frames[1].inners.is_empty());
+    }
+
+    #[test]
+    fn add_to_frames() {
+        let bones = standard_humanoid_skeleton();
+        let mut frames = generate(&bones[..1], &[], "体");
+        assert_eq!(find_by_name(&frames, EXPRESSION_FRAME_NAME), Some(1));
+        assert_eq!(find_by_name(&frames, "体"), None);
+
+        // existing frames
+        assert_eq!(add_bone_to(&mut frames, ROOT_FRAME_NAME, 3), Ok(0));
+        assert_eq!(add_morph_to(&mut frames, EXPRESSION_FRAME_NAME, 2), Ok(1));
+        assert_eq!(add_morph_to(&mut frames, EXPRESSION_FRAME_NAME, 2), Ok(1));
+        assert_eq!(frames[0].inners, [FrameInner::Bone(0), FrameInner::Bone(3)]);
+        assert_eq!(frames[1].inners, [FrameInner::Morph(2)]);
+
+        // special frame rules
+        assert_eq!(
+            add_morph_to(&mut frames, ROOT_FRAME_NAME, 0),
+            Err(FrameError::MorphInRootFrame)
+        );
+        assert_eq!(
+            add_bone_to(&mut frames, EXPRESSION_FRAME_NAME, 1),
+            Err(FrameError::BoneInExpressionFrame)
+        );
+        assert_eq!(frames[0].inners.len(), 2);
+        assert_eq!(frames[1].inners.len(), 1);
+
+        // new frame holding both kinds
+        assert_eq!(add_bone_to(&mut frames, "髪", 5), Ok(2));
+        assert_eq!(add_morph_to(&mut frames, "髪", 0), Ok(2));
+        assert!(!frames[2].is_special);
+        assert_eq!(frames[2].name, "髪");
+        assert_eq!(
+            frames[2].inners,
+            [FrameInner::Bone(5), FrameInner::Morph(0)]
+        );
+    }
+
+    #[test]
+    fn remove_bone() {
+        let bones = standard_humanoid_skeleton();
+        let mut frames = generate(&bones[..4], &[], "体");
+        frames[2].inners.push(FrameInner::Morph(1));
+        remove_references_to_bone(&mut frames, 2);
+        assert_eq!(frames[0].inners, [FrameInner::Bone(0)]);
+        assert_eq!(
+            frames[2].inners,
+            [
+                FrameInner::Bone(1),
+                FrameInner::Bone(2),
+                FrameInner::Morph(1)
+            ]
+        );
+    }
+}
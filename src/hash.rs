@@ -0,0 +1,233 @@
+//! Content hash of models for caches.
+//!
+//! unlike hashing file bytes, [`content_hash`] doesn't depend on text encoding and index sizes,
+//! and can ignore sections not used by the cache.
+//!
+//! ```rust
+//! use PMXUtil::hash::{content_hash, HashOptions};
+//!
+//! let mut model = PMXUtil::types::Model::default();
+//! let geometry = content_hash(&model, HashOptions::geometry_only());
+//! model.model_info.comment = "new comment".to_owned();
+//! assert_eq!(content_hash(&model, HashOptions::geometry_only()), geometry);
+//! println!("{}", geometry);
+//! ```
+use crate::binary_writer::{BinaryWriter, WriteElement};
+use crate::types::{Encode, Header, IndexKinds, Model, VertexIndexKinds};
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+
+/// version of hashed data layout.
+///
+/// hashes of the same model are equal across crate versions with the same `HASH_VERSION`.
+/// it is increased when the layout changes, e.g. fields are added to types.
+pub const HASH_VERSION: u32 = 1;
+
+/// sections hashed by [`content_hash`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum HashScope {
+    /// vertices, faces and additional uv count.
+    Geometry,
+    /// [`Geometry`](Self::Geometry), textures and materials.
+    GeometryAndMaterials,
+    /// all sections, model info, version and [`Model::unknown`].
+    #[default]
+    Everything,
+}
+
+/// options of [`content_hash`]. default hashes everything.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub struct HashOptions {
+    pub scope: HashScope,
+}
+
+impl HashOptions {
+    pub fn geometry_only() -> Self {
+        Self {
+            scope: HashScope::Geometry,
+        }
+    }
+    pub fn geometry_and_materials() -> Self {
+        Self {
+            scope: HashScope::GeometryAndMaterials,
+        }
+    }
+    pub fn everything() -> Self {
+        Self {
+            scope: HashScope::Everything,
+        }
+    }
+}
+
+/// 128 bit digest returned by [`content_hash`]. shown as 32 hex digits.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct ContentHash(pub u128);
+
+impl ContentHash {
+    /// upper and lower 64 bits folded by xor.
+    pub fn to_u64(self) -> u64 {
+        (self.0 >> 64) as u64 ^ self.0 as u64
+    }
+}
+
+impl Display for ContentHash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+/// hash sections of `model` selected by `options`.
+///
+/// elements are hashed as written to PMX with 4 byte indices and UTF-8 text,
+/// so floats are hashed by their bits and text encoding and index sizes don't matter.
+/// not a cryptographic hash.
+pub fn content_hash(model: &Model, options: HashOptions) -> ContentHash {
+    let mut hasher = Fnv128::new();
+    let header = Header {
+        magic: *b"PMX ",
+        version: model.version,
+        length: 8,
+        encode: Encode::UTF8,
+        additional_uv: model.additional_uv,
+        s_vertex_index: VertexIndexKinds::I32,
+        s_texture_index: IndexKinds::I32,
+        s_material_index: IndexKinds::I32,
+        s_bone_index: IndexKinds::I32,
+        s_morph_index: IndexKinds::I32,
+        s_rigid_body_index: IndexKinds::I32,
+        raw_version: None,
+    };
+    let scope = options.scope;
+    hasher.update(&HASH_VERSION.to_le_bytes());
+    hasher.update(&[scope as u8, model.additional_uv]);
+    {
+        let mut writer = BinaryWriter::from_writer(&mut hasher, header);
+        write_section(&mut writer, &model.vertices);
+        write_section(&mut writer, &model.faces);
+        if scope != HashScope::Geometry {
+            write_section(&mut writer, &model.textures.textures);
+            write_section(&mut writer, &model.materials);
+        }
+        if scope == HashScope::Everything {
+            writer.write_u8(model.version as u8);
+            write_section(&mut writer, std::slice::from_ref(&model.model_info));
+            write_section(&mut writer, &model.bones);
+            write_section(&mut writer, &model.morphs);
+            write_section(&mut writer, &model.frames);
+            write_section(&mut writer, &model.rigid_bodies);
+            write_section(&mut writer, &model.joints);
+            write_section(&mut writer, &model.soft_bodies);
+            write_section(&mut writer, &[model.unknown.trailing.as_slice()]);
+        }
+        // hasher never fails.
+        writer.inner.flush().unwrap();
+    }
+    ContentHash(hasher.finish())
+}
+
+/// element count then elements.
+fn write_section<W: Write, T: WriteElement>(writer: &mut BinaryWriter<W>, list: &[T]) {
+    writer.write_vec(&(list.len() as u64).to_le_bytes());
+    list.iter().for_each(|element| element.write_to(writer));
+}
+
+impl WriteElement for &[u8] {
+    fn write_to<W: Write>(&self, writer: &mut BinaryWriter<W>) {
+        writer.write_vec(&(self.len() as u64).to_le_bytes());
+        writer.write_vec(self);
+    }
+}
+
+/// 128 bit FNV-1a. fixed by its specification unlike `std::hash::DefaultHasher`.
+struct Fnv128(u128);
+
+impl Fnv128 {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+    fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u128::from(byte);
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+    fn finish(&self) -> u128 {
+        self.0
+    }
+}
+
+impl Write for Fnv128 {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hash::{content_hash, ContentHash, Fnv128, HashOptions};
+    use crate::types::Encode;
+
+    #[test]
+    fn fnv_vectors() {
+        let mut hasher = Fnv128::new();
+        assert_eq!(hasher.finish(), Fnv128::OFFSET_BASIS);
+        hasher.update(b"a");
+        assert_eq!(hasher.finish(), 0xd228cb696f1a8caf78912b704e4a8964);
+    }
+
+    #[test]
+    fn scopes() {
+        let model = crate::test_support::cube();
+        let scopes = [
+            HashOptions::geometry_only(),
+            HashOptions::geometry_and_materials(),
+            HashOptions::everything(),
+        ];
+        let hashes = scopes.map(|options| content_hash(&model, options));
+        assert_ne!(hashes[0], hashes[1]);
+        assert_ne!(hashes[1], hashes[2]);
+        assert_eq!(content_hash(&model, HashOptions::default()), hashes[2]);
+
+        // model info is only in everything.
+        let mut renamed = model.clone();
+        renamed.model_info.name = "renamed".to_owned();
+        renamed.model_info.comment.push_str(" changed");
+        let renamed_hashes = scopes.map(|options| content_hash(&renamed, options));
+        assert_eq!(renamed_hashes[..2], hashes[..2]);
+        assert_ne!(renamed_hashes[2], hashes[2]);
+
+        // geometry is in all scopes.
+        let mut moved = model.clone();
+        moved.vertices[0].position[0] += 0.001;
+        for (options, hash) in scopes.iter().zip(&hashes) {
+            assert_ne!(content_hash(&moved, *options), *hash);
+        }
+
+        let mut material = model.clone();
+        material.materials[0].diffuse[0] = 0.25;
+        assert_eq!(content_hash(&material, scopes[0]), hashes[0]);
+        assert_ne!(content_hash(&material, scopes[1]), hashes[1]);
+    }
+
+    #[test]
+    fn independent_of_encoding() {
+        let model = crate::test_support::cube();
+        let mut utf8 = model.clone();
+        utf8.encode = Encode::UTF8;
+        let read = crate::test_support::read(&crate::test_support::write(&utf8));
+        assert_eq!(
+            content_hash(&read, HashOptions::everything()),
+            content_hash(&model, HashOptions::everything())
+        );
+        let hash = ContentHash(0x0123456789abcdef_fedcba9876543210);
+        assert_eq!(hash.to_string(), "0123456789abcdeffedcba9876543210");
+        assert_eq!(hash.to_u64(), 0x0123456789abcdef ^ 0xfedcba9876543210);
+    }
+}
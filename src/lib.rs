@@ -16,16 +16,48 @@
 //! * compare both content
 //! * compare hash.
 //! * load it by `PMXEditor` and `MMD`.
+#![allow(non_snake_case)]
 
 pub(crate) mod binary_writer;
 pub mod writer;
 
 pub(crate) mod binary_reader;
 
+pub mod builder;
+
+pub mod convert;
+
+pub mod diff;
+pub mod edit;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod frames;
+pub mod hash;
+pub mod metadata;
+pub mod morph;
+pub mod optimize;
+pub mod patch;
+pub mod presets;
+pub mod progress;
 pub mod reader;
+pub mod repair;
+pub mod skin;
+pub mod stats;
+pub mod stream_writer;
+#[cfg(feature = "tracing")]
+mod trace;
 pub mod types;
+pub mod usage;
+pub mod validation;
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_model;
 
 #[cfg(test)]
+pub(crate) mod test_support;
+
+// copy test uses files.
+#[cfg(all(test, feature = "std-fs"))]
 mod test {
 
     use crate::reader::ModelInfoStage;
@@ -33,9 +65,16 @@ mod test {
     use crate::writer::Writer;
 
     //Perform Copy test
+    //needs real model given by PMX_FILE. see test_support for synthetic one.
     #[test]
     fn copy_test() {
-        let path = std::env::var("PMX_FILE").unwrap();
+        let path = match std::env::var("PMX_FILE") {
+            Ok(path) => path,
+            Err(_) => {
+                eprintln!("PMX_FILE is not set. skip copy test");
+                return;
+            }
+        };
         let to = "./to.pmx";
         let mut writer = Writer::begin_writer(true);
         let copy_from = crate::reader::ModelInfoStage::open(path).unwrap();
@@ -48,7 +87,8 @@ mod test {
         let (morphs, ns) = ns.read();
         let (frames, ns) = ns.read();
         let (rigid_bodies, ns) = ns.read();
-        let (joints, _ns) = ns.read();
+        let (joints, ns) = ns.read();
+        let soft_bodies = ns.map(|ns| ns.read()).unwrap_or_default();
 
         writer.set_model_info(&model_info);
         writer.add_vertices(&vertices);
@@ -60,6 +100,7 @@ mod test {
         writer.add_frames(&frames);
         writer.add_rigid_bodies(&rigid_bodies);
         writer.add_joints(&joints);
+        writer.add_soft_bodies(&soft_bodies);
         writer.write_to_path(to).unwrap();
 
         let reader = ModelInfoStage::open(to).unwrap();
@@ -81,7 +122,9 @@ mod test {
         assert_eq!(frames, frames_cpy);
         let (rigid_bodies_cpy, ns) = ns.read();
         assert_eq!(rigid_bodies, rigid_bodies_cpy);
-        let (joints_cpy, _ns) = ns.read();
+        let (joints_cpy, ns) = ns.read();
         assert_eq!(joints, joints_cpy);
+        let soft_bodies_cpy = ns.map(|ns| ns.read()).unwrap_or_default();
+        assert_eq!(soft_bodies, soft_bodies_cpy);
     }
 }
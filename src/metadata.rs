@@ -0,0 +1,223 @@
+//! Directives written in material memos and model comments.
+//!
+//! ```rust
+//! use PMXUtil::metadata::{parse_directives, DirectiveSyntax};
+//! let directives = parse_directives("outline:off;[blend=add]", DirectiveSyntax::default());
+//! assert_eq!(directives["outline"], "off");
+//! assert_eq!(directives["blend"], "add");
+//! ```
+use crate::types::{Material, ModelInfo};
+use std::collections::BTreeMap;
+
+/// how directives are written in text.
+///
+/// segments without a separator are kept as keys with empty value.
+/// if a key appears more than once the last value wins.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum DirectiveSyntax {
+    /// `key:value;` pairs. a pair ends at `delimiter` or line break.
+    Delimited { assign: char, delimiter: char },
+    /// `[key=value]` pairs. text outside brackets is ignored.
+    Bracketed {
+        open: char,
+        assign: char,
+        close: char,
+    },
+    /// `[key=value]` pairs and `key:value;` pairs in the rest of text.
+    ///
+    /// `:` and `=` are both accepted as separator, and so are full width `：` `＝` `；`.
+    #[default]
+    Mixed,
+}
+
+const MIXED_ASSIGNS: [char; 4] = [':', '=', '：', '＝'];
+const MIXED_DELIMITERS: [char; 2] = [';', '；'];
+
+/// parse directives in `text`.
+///
+/// keys and values are trimmed. empty segments and unclosed brackets are not errors,
+/// an unclosed bracket is read as plain text.
+pub fn parse_directives(text: &str, syntax: DirectiveSyntax) -> BTreeMap<String, String> {
+    let mut directives = BTreeMap::new();
+    match syntax {
+        DirectiveSyntax::Delimited { assign, delimiter } => {
+            parse_delimited(text, &[assign], &[delimiter], &mut directives)
+        }
+        DirectiveSyntax::Bracketed {
+            open,
+            assign,
+            close,
+        } => split_brackets(text, open, close, |inner, bracketed| {
+            if bracketed {
+                insert(inner, &[assign], &mut directives)
+            }
+        }),
+        DirectiveSyntax::Mixed => split_brackets(text, '[', ']', |segment, bracketed| {
+            if bracketed {
+                insert(segment, &MIXED_ASSIGNS, &mut directives)
+            } else {
+                parse_delimited(segment, &MIXED_ASSIGNS, &MIXED_DELIMITERS, &mut directives)
+            }
+        }),
+    }
+    directives
+}
+
+/// call `f` with each bracketed content and each run of text between them.
+fn split_brackets(text: &str, open: char, close: char, mut f: impl FnMut(&str, bool)) {
+    let mut rest = text;
+    while let Some(start) = rest.find(open) {
+        let inner = &rest[start + open.len_utf8()..];
+        let end = match inner.find(close) {
+            Some(end) => end,
+            None => break,
+        };
+        f(&rest[..start], false);
+        f(&inner[..end], true);
+        rest = &inner[end + close.len_utf8()..];
+    }
+    f(rest, false);
+}
+
+fn parse_delimited(
+    text: &str,
+    assigns: &[char],
+    delimiters: &[char],
+    directives: &mut BTreeMap<String, String>,
+) {
+    for segment in text.split(|c| delimiters.contains(&c) || c == '\n' || c == '\r') {
+        insert(segment, assigns, directives);
+    }
+}
+
+fn insert(segment: &str, assigns: &[char], directives: &mut BTreeMap<String, String>) {
+    let segment = segment.trim();
+    if segment.is_empty() {
+        return;
+    }
+    let (key, value) = match segment.find(|c| assigns.contains(&c)) {
+        Some(at) => {
+            let assign = segment[at..].chars().next().unwrap();
+            (&segment[..at], &segment[at + assign.len_utf8()..])
+        }
+        None => (segment, ""),
+    };
+    let (key, value) = match key.trim() {
+        // keep the whole segment rather than an empty key.
+        "" => (segment, ""),
+        key => (key, value.trim()),
+    };
+    directives.insert(key.to_owned(), value.to_owned());
+}
+
+impl Material {
+    /// directives in [`memo`](Material::memo) parsed by [`DirectiveSyntax::Mixed`].
+    pub fn directives(&self) -> BTreeMap<String, String> {
+        parse_directives(&self.memo, DirectiveSyntax::default())
+    }
+}
+
+impl ModelInfo {
+    /// directives in [`comment`](ModelInfo::comment) and [`comment_en`](ModelInfo::comment_en)
+    /// parsed by [`DirectiveSyntax::Mixed`].
+    ///
+    /// keys in `comment` win over the same keys in `comment_en`.
+    pub fn directives(&self) -> BTreeMap<String, String> {
+        let mut directives = parse_directives(&self.comment_en, DirectiveSyntax::default());
+        directives.extend(parse_directives(&self.comment, DirectiveSyntax::default()));
+        directives
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::metadata::{parse_directives, DirectiveSyntax};
+    use crate::types::{Material, ModelInfo};
+    use std::collections::BTreeMap;
+
+    fn map(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|&(key, value)| (key.to_owned(), value.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn mixed_separators() {
+        let text = "outline:off; blend = add\n[影=なし]エッジ：太い；メモ書き;;[toon=3]";
+        assert_eq!(
+            parse_directives(text, DirectiveSyntax::Mixed),
+            map(&[
+                ("outline", "off"),
+                ("blend", "add"),
+                ("影", "なし"),
+                ("エッジ", "太い"),
+                ("メモ書き", ""),
+                ("toon", "3"),
+            ])
+        );
+        // values may contain separators after the first one.
+        assert_eq!(
+            parse_directives("url:https://example.com", DirectiveSyntax::Mixed),
+            map(&[("url", "https://example.com")])
+        );
+        // unclosed bracket and empty key are kept as text.
+        assert_eq!(
+            parse_directives("[open=1;:orphan", DirectiveSyntax::Mixed),
+            map(&[("[open", "1"), (":orphan", "")])
+        );
+        assert!(parse_directives(" ;\n ", DirectiveSyntax::Mixed).is_empty());
+    }
+
+    #[test]
+    fn configured_syntax() {
+        let text = "a=1|b=2|[c:3]";
+        assert_eq!(
+            parse_directives(
+                text,
+                DirectiveSyntax::Delimited {
+                    assign: '=',
+                    delimiter: '|'
+                }
+            ),
+            map(&[("a", "1"), ("b", "2"), ("[c:3]", "")])
+        );
+        assert_eq!(
+            parse_directives(
+                "物理:on 《揺れ＝弱》 《色＝赤》",
+                DirectiveSyntax::Bracketed {
+                    open: '《',
+                    assign: '＝',
+                    close: '》'
+                }
+            ),
+            map(&[("揺れ", "弱"), ("色", "赤")])
+        );
+    }
+
+    #[test]
+    fn material_and_model_info() {
+        let material = Material {
+            memo: "outline:off;blend:add".to_owned(),
+            ..crate::test_support::cube().materials[0].clone()
+        };
+        assert_eq!(
+            material.directives(),
+            map(&[("outline", "off"), ("blend", "add")])
+        );
+        let model_info = ModelInfo {
+            comment: "作者：誰か\n利用規約：自由".to_owned(),
+            comment_en: "author: someone\n[license=free]".to_owned(),
+            ..ModelInfo::default()
+        };
+        assert_eq!(
+            model_info.directives(),
+            map(&[
+                ("author", "someone"),
+                ("license", "free"),
+                ("作者", "誰か"),
+                ("利用規約", "自由"),
+            ])
+        );
+    }
+}
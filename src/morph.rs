@@ -0,0 +1,254 @@
+//! Keep morphs consistent with edits of the model.
+//!
+//! morph offsets are relative to the base so [`transform_offsets`] needs to be applied
+//! when vertices and bones are scaled, rotated or mirrored,
+//! and [`remap_indices`] when elements are removed, reordered or merged from another model.
+//!
+//! ```rust
+//! use PMXUtil::morph::{remap_indices, transform_offsets, IndexMap, Mat4OrScale};
+//! use PMXUtil::progress::SectionKind;
+//!
+//! let mut model = PMXUtil::types::Model::default();
+//! // mirror along x axis.
+//! for morph in &mut model.morphs {
+//!     transform_offsets(morph, &Mat4OrScale::Scale([-1.0, 1.0, 1.0]));
+//! }
+//! // vertex 1 is removed.
+//! let map = IndexMap::removing(SectionKind::Vertices, model.vertices.len(), &[1]);
+//! for morph in &mut model.morphs {
+//!     remap_indices(morph, &map);
+//! }
+//! ```
+use crate::progress::SectionKind;
+use crate::types::{Morph, MorphKinds, Vec3};
+use std::convert::{TryFrom, TryInto};
+
+/// transform applied by [`transform_offsets`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mat4OrScale {
+    /// column major affine matrix, `matrix[column][row]`. only upper left 3x3 is used
+    /// because offsets are directions.
+    Matrix([[f32; 4]; 4]),
+    /// scale per axis. negative values mirror.
+    Scale(Vec3),
+}
+
+impl Mat4OrScale {
+    /// apply linear part to `v`.
+    pub fn apply(&self, v: Vec3) -> Vec3 {
+        match self {
+            Mat4OrScale::Matrix(m) => {
+                let mut out = [0.0; 3];
+                for (row, out) in out.iter_mut().enumerate() {
+                    *out = (0..3).map(|column| m[column][row] * v[column]).sum();
+                }
+                out
+            }
+            Mat4OrScale::Scale(s) => [v[0] * s[0], v[1] * s[1], v[2] * s[2]],
+        }
+    }
+}
+
+/// transform vertex morph offsets and bone morph translates by linear part of `transform`.
+///
+/// other morphs have no positional offset. bone morph rotations are unchanged.
+pub fn transform_offsets(morph: &mut Morph, transform: &Mat4OrScale) {
+    match &mut morph.morph_data {
+        MorphKinds::Vertex(offsets) => {
+            for offset in offsets {
+                offset.offset = transform.apply(offset.offset);
+            }
+        }
+        MorphKinds::Bone(offsets) => {
+            for offset in offsets {
+                offset.translates = transform.apply(offset.translates);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// new indices of elements of a section used by [`remap_indices`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IndexMap {
+    /// section the indices refer to.
+    pub section: SectionKind,
+    /// new index of each old index. `None` if removed.
+    pub new_indices: Vec<Option<i32>>,
+}
+
+impl IndexMap {
+    pub fn new(section: SectionKind, new_indices: Vec<Option<i32>>) -> Self {
+        Self {
+            section,
+            new_indices,
+        }
+    }
+    /// map of `len` elements after elements in `removed` are removed keeping the order.
+    pub fn removing(section: SectionKind, len: usize, removed: &[usize]) -> Self {
+        let mut next = 0;
+        let new_indices = (0..len)
+            .map(|index| {
+                if removed.contains(&index) {
+                    None
+                } else {
+                    next += 1;
+                    Some(next - 1)
+                }
+            })
+            .collect();
+        Self::new(section, new_indices)
+    }
+    /// map of `len` elements appended after `offset` elements, e.g. merged from another model.
+    pub fn offset(section: SectionKind, len: usize, offset: i32) -> Self {
+        Self::new(section, (0..len as i32).map(|i| Some(i + offset)).collect())
+    }
+    /// new index of `index`. indices out of the map, e.g. `-1` for none, are unchanged.
+    pub fn get(&self, index: i32) -> Option<i32> {
+        usize::try_from(index)
+            .ok()
+            .and_then(|i| self.new_indices.get(i).copied())
+            .unwrap_or(Some(index))
+    }
+}
+
+/// rewrite indices of entries in `morph` referring to `map.section`.
+///
+/// entries pointing at removed elements are dropped.
+/// vertex and uv morphs refer to vertices, bone morphs to bones, material morphs to materials,
+/// group and flip morphs to morphs and impulse morphs to rigid bodies.
+pub fn remap_indices(morph: &mut Morph, map: &IndexMap) {
+    // vertex indices are unsigned. ones not fitting in `i32` are out of the map.
+    fn retain<T, I: Copy + TryInto<i32> + TryFrom<i32>>(
+        entries: &mut Vec<T>,
+        map: &IndexMap,
+        index: impl Fn(&mut T) -> &mut I,
+    ) {
+        entries.retain_mut(|entry| {
+            let index = index(entry);
+            match (*index).try_into() {
+                Ok(old) => map
+                    .get(old)
+                    .and_then(|new| I::try_from(new).ok())
+                    .map(|new| *index = new)
+                    .is_some(),
+                Err(_) => true,
+            }
+        })
+    }
+    match (&mut morph.morph_data, map.section) {
+        (MorphKinds::Vertex(entries), SectionKind::Vertices) => {
+            retain(entries, map, |e| &mut e.index)
+        }
+        (
+            MorphKinds::UV(entries)
+            | MorphKinds::UV1(entries)
+            | MorphKinds::UV2(entries)
+            | MorphKinds::UV3(entries)
+            | MorphKinds::UV4(entries),
+            SectionKind::Vertices,
+        ) => retain(entries, map, |e| &mut e.index),
+        (MorphKinds::Bone(entries), SectionKind::Bones) => retain(entries, map, |e| &mut e.index),
+        (MorphKinds::Material(entries), SectionKind::Materials) => {
+            retain(entries, map, |e| &mut e.index)
+        }
+        (MorphKinds::Group(entries), SectionKind::Morphs) => retain(entries, map, |e| &mut e.index),
+        (MorphKinds::Flip(entries), SectionKind::Morphs) => retain(entries, map, |e| &mut e.index),
+        (MorphKinds::Impulse(entries), SectionKind::RigidBodies) => {
+            retain(entries, map, |e| &mut e.rigid_index)
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::morph::{remap_indices, transform_offsets, IndexMap, Mat4OrScale};
+    use crate::progress::SectionKind;
+    use crate::types::{BoneMorph, MaterialMorph, Morph, MorphKinds, VertexMorph};
+
+    fn vertex_morph(entries: &[(u32, [f32; 3])]) -> Morph {
+        Morph {
+            morph_data: MorphKinds::Vertex(
+                entries
+                    .iter()
+                    .map(|&(index, offset)| VertexMorph { index, offset })
+                    .collect(),
+            ),
+            ..crate::test_support::cube().morphs[0].clone()
+        }
+    }
+
+    #[test]
+    fn rotation_and_scale() {
+        // rotate 90 degrees around z then scale by 2, translation is ignored.
+        let matrix = Mat4OrScale::Matrix([
+            [0.0, 2.0, 0.0, 0.0],
+            [-2.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 2.0, 0.0],
+            [5.0, 6.0, 7.0, 1.0],
+        ]);
+        let mut morph = vertex_morph(&[(0, [1.0, 0.0, 0.0]), (1, [0.0, 1.0, 3.0])]);
+        transform_offsets(&mut morph, &matrix);
+        assert_eq!(
+            morph.morph_data,
+            vertex_morph(&[(0, [0.0, 2.0, 0.0]), (1, [-2.0, 0.0, 6.0])]).morph_data
+        );
+
+        let mut bone = Morph {
+            morph_data: MorphKinds::Bone(vec![BoneMorph {
+                index: 0,
+                translates: [1.0, 2.0, 3.0],
+                rotates: [0.0, 0.0, 0.0, 1.0],
+            }]),
+            ..morph
+        };
+        transform_offsets(&mut bone, &Mat4OrScale::Scale([-1.0, 1.0, 0.5]));
+        assert_eq!(
+            bone.morph_data,
+            MorphKinds::Bone(vec![BoneMorph {
+                index: 0,
+                translates: [-1.0, 2.0, 1.5],
+                rotates: [0.0, 0.0, 0.0, 1.0],
+            }])
+        );
+    }
+
+    #[test]
+    fn sparse_remap() {
+        let mut morph = vertex_morph(&[(0, [1.0; 3]), (2, [2.0; 3]), (3, [3.0; 3]), (5, [5.0; 3])]);
+        let map = IndexMap::removing(SectionKind::Vertices, 6, &[1, 2, 5]);
+        assert_eq!(map.get(-1), Some(-1));
+        // other sections are not affected.
+        remap_indices(&mut morph, &IndexMap::offset(SectionKind::Bones, 6, 10));
+        remap_indices(&mut morph, &map);
+        assert_eq!(
+            morph.morph_data,
+            vertex_morph(&[(0, [1.0; 3]), (1, [3.0; 3])]).morph_data
+        );
+
+        let material = |index| MaterialMorph {
+            index,
+            ..crate::test_support::cube()
+                .morphs
+                .iter()
+                .find_map(|m| match &m.morph_data {
+                    MorphKinds::Material(entries) => entries.first().cloned(),
+                    _ => None,
+                })
+                .unwrap()
+        };
+        let mut morph = Morph {
+            morph_data: MorphKinds::Material(vec![material(-1), material(0), material(1)]),
+            ..morph
+        };
+        remap_indices(
+            &mut morph,
+            &IndexMap::new(SectionKind::Materials, vec![Some(1), None]),
+        );
+        assert_eq!(
+            morph.morph_data,
+            MorphKinds::Material(vec![material(-1), material(1)])
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Passes making models smaller or more compressible without changing how they look.
+//!
+//! ```rust
+//! let mut model = PMXUtil::types::Model::default();
+//! let changed = PMXUtil::optimize::normalize_edge_data(&mut model);
+//! println!("{} vertices changed", changed);
+//! ```
+use crate::types::Model;
+use crate::usage::vertex_materials;
+
+/// zero `edge_mag` of vertices not drawn by any material with `HAS_EDGE`.
+///
+/// edge scale of such vertices is never used. vertices drawn by no material are zeroed too.
+/// returns the number of changed vertices.
+pub fn normalize_edge_data(model: &mut Model) -> usize {
+    let materials = vertex_materials(model);
+    let has_edge = model
+        .materials
+        .iter()
+        .map(|material| material.has_edge())
+        .collect::<Vec<_>>();
+    let mut changed = 0;
+    for (vertex, used_by) in model.vertices.iter_mut().zip(materials) {
+        let has_edge = used_by.iter().any(|&material| has_edge[material as usize]);
+        if !has_edge && vertex.edge_mag != 0.0 {
+            vertex.edge_mag = 0.0;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod test {
+    use crate::optimize::normalize_edge_data;
+
+    #[test]
+    fn edge_data() {
+        let cube = crate::test_support::cube();
+        let mut model = cube.clone();
+        // faces 0 and 1 use vertices 0..4 and faces 2 and 3 use 4..8.
+        model.materials[0].num_face_vertices = 6;
+        model.materials[0].set_edge(None);
+        model.materials[1].num_face_vertices = 6;
+        assert!(model.materials[1].has_edge());
+        for vertex in &mut model.vertices {
+            vertex.edge_mag = 0.5;
+        }
+        assert_eq!(normalize_edge_data(&mut model), 4);
+        let edges = model
+            .vertices
+            .iter()
+            .map(|v| v.edge_mag)
+            .collect::<Vec<_>>();
+        assert_eq!(edges, [0.0, 0.0, 0.0, 0.0, 0.5, 0.5, 0.5, 0.5]);
+        assert_eq!(normalize_edge_data(&mut model), 0);
+
+        // vertices not drawn are zeroed too.
+        model.materials[1].num_face_vertices = 0;
+        assert_eq!(normalize_edge_data(&mut model), 4);
+
+        let mut model = cube.clone();
+        assert_eq!(normalize_edge_data(&mut model), 0);
+        assert_eq!(model, cube);
+    }
+}
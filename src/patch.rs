@@ -0,0 +1,471 @@
+//! Replace some sections of a PMX file keeping the others byte for byte.
+//!
+//! sections not replaced are parsed only to find where they end and copied as they are,
+//! so swapping materials of a model with a huge vertex section doesn't build `Vec<Vertex>`.
+//!
+//! ```rust
+//! use PMXUtil::patch::{replace_sections, SectionReplacements};
+//!
+//! # let input = PMXUtil::writer::Writer::from_model(&Default::default()).write_to_vec().unwrap();
+//! let replacements = SectionReplacements {
+//!     textures: Some(vec!["tex\\body.png".to_owned()]),
+//!     ..SectionReplacements::default()
+//! };
+//! let mut output = vec![];
+//! replace_sections(&input, &mut output, &replacements).unwrap();
+//! ```
+use crate::binary_writer::{BinaryWriter, WriteElement};
+use crate::progress::SectionKind;
+use crate::reader::{ModelInfoStage, PmxVisitor, SectionOffsets};
+use crate::types::{
+    Bone, Frame, Header, IndexKinds, Joint, Material, ModelInfo, Morph, PMXVersion, Rigid,
+};
+use crate::writer::{
+    check_entries, joint_requires_v21, material_requires_v21, morph_requires_v21,
+    optimal_data_type, section_count, WritePMXErrors,
+};
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+#[cfg(feature = "std-fs")]
+use std::path::Path;
+
+/// new contents of sections. `None` keeps the section of input.
+///
+/// vertices, faces and soft bodies can't be replaced.
+/// consistency with kept sections, e.g. face counts of materials, is not checked.
+#[derive(Debug, Clone, Default)]
+pub struct SectionReplacements {
+    pub model_info: Option<ModelInfo>,
+    pub textures: Option<Vec<String>>,
+    pub materials: Option<Vec<Material>>,
+    pub bones: Option<Vec<Bone>>,
+    pub morphs: Option<Vec<Morph>>,
+    pub frames: Option<Vec<Frame>>,
+    pub rigid_bodies: Option<Vec<Rigid>>,
+    pub joints: Option<Vec<Joint>>,
+}
+
+impl SectionReplacements {
+    /// `true` if `section` is replaced.
+    pub fn replaces(&self, section: SectionKind) -> bool {
+        match section {
+            SectionKind::ModelInfo => self.model_info.is_some(),
+            SectionKind::Textures => self.textures.is_some(),
+            SectionKind::Materials => self.materials.is_some(),
+            SectionKind::Bones => self.bones.is_some(),
+            SectionKind::Morphs => self.morphs.is_some(),
+            SectionKind::Frames => self.frames.is_some(),
+            SectionKind::RigidBodies => self.rigid_bodies.is_some(),
+            SectionKind::Joints => self.joints.is_some(),
+            SectionKind::Vertices | SectionKind::Faces | SectionKind::SoftBodies => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PatchError {
+    /// input is not PMX.
+    InvalidHeader,
+    /// replaced `section` has too many elements for the index size used by kept `required_by`.
+    IndexSizeChanged {
+        section: SectionKind,
+        required_by: SectionKind,
+    },
+    Write(WritePMXErrors),
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchError::InvalidHeader => write!(f, "input is not PMX"),
+            PatchError::IndexSizeChanged {
+                section,
+                required_by,
+            } => write!(
+                f,
+                "{:?} needs larger index size than kept {:?} uses. replace {:?} too",
+                section, required_by, required_by
+            ),
+            PatchError::Write(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PatchError::Write(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<WritePMXErrors> for PatchError {
+    fn from(err: WritePMXErrors) -> Self {
+        Self::Write(err)
+    }
+}
+
+/// sections in file order.
+const SECTIONS: [SectionKind; 11] = [
+    SectionKind::ModelInfo,
+    SectionKind::Vertices,
+    SectionKind::Faces,
+    SectionKind::Textures,
+    SectionKind::Materials,
+    SectionKind::Bones,
+    SectionKind::Morphs,
+    SectionKind::Frames,
+    SectionKind::RigidBodies,
+    SectionKind::Joints,
+    SectionKind::SoftBodies,
+];
+
+/// parse elements without keeping them.
+struct Skip;
+
+impl PmxVisitor for Skip {}
+
+/// copy `input` to `output` writing replaced sections instead of the ones in input.
+///
+/// text encoding, version and additional uv count are kept. index size of textures,
+/// materials, bones, morphs or rigid bodies is recalculated when all sections storing the index
+/// are replaced, otherwise it is kept and replaced section must fit in it.
+/// data after the last section is copied too. returns header of output.
+///
+/// # Errors
+/// * `PatchError::InvalidHeader` if input is not PMX.
+/// * `PatchError::IndexSizeChanged` if replaced section doesn't fit in index size of kept one.
+/// * `PatchError::Write(WritePMXErrors::RequiresV21)` if 2.1 only elements replace ones of PMX 2.0.
+/// * `PatchError::Write(WritePMXErrors::TooBig)` if a replaced section or a list in its element
+///   doesn't fit in `i32`.
+/// * `PatchError::Write(WritePMXErrors::IndexOutOfRange)` if an index in a replaced section
+///   doesn't fit the index size.
+/// * `PatchError::Write` if failed to write.
+///
+/// # Panics
+/// same as [`ModelInfoStage`] on broken input.
+pub fn replace_sections<W: Write>(
+    input: &[u8],
+    output: W,
+    replacements: &SectionReplacements,
+) -> Result<Header, PatchError> {
+    let stage = ModelInfoStage::from_reader(input).ok_or(PatchError::InvalidHeader)?;
+    let old = stage.get_header();
+    if old.version == PMXVersion::V20 {
+        let materials = replacements
+            .materials
+            .iter()
+            .flatten()
+            .any(material_requires_v21);
+        let morphs = replacements.morphs.iter().flatten().any(morph_requires_v21);
+        let joints = replacements.joints.iter().flatten().any(joint_requires_v21);
+        if materials || morphs || joints {
+            return Err(WritePMXErrors::RequiresV21.into());
+        }
+    }
+    check_entries(
+        SectionKind::Bones,
+        replacements.bones.as_deref().unwrap_or_default(),
+    )?;
+    check_entries(
+        SectionKind::Morphs,
+        replacements.morphs.as_deref().unwrap_or_default(),
+    )?;
+    check_entries(
+        SectionKind::Frames,
+        replacements.frames.as_deref().unwrap_or_default(),
+    )?;
+    let offsets = stage.visit_with_offsets(&mut Skip);
+    let header = new_header(&old, &offsets, replacements)?;
+
+    let mut writer = BinaryWriter::from_writer(output, header);
+    writer.write_header();
+    let mut end = 0;
+    for section in SECTIONS.iter().copied() {
+        let range = match offsets.get(section) {
+            Some(range) => range,
+            None => continue,
+        };
+        let start = range.start as usize;
+        end = start + range.len as usize;
+        if !replacements.replaces(section) {
+            writer.write_vec(&input[start..end]);
+            continue;
+        }
+        match section {
+            SectionKind::ModelInfo => {
+                if let Some(model_info) = &replacements.model_info {
+                    writer.write_model_info(model_info);
+                }
+            }
+            SectionKind::Textures => write_section(&mut writer, section, &replacements.textures)?,
+            SectionKind::Materials => write_section(&mut writer, section, &replacements.materials)?,
+            SectionKind::Bones => write_section(&mut writer, section, &replacements.bones)?,
+            SectionKind::Morphs => write_section(&mut writer, section, &replacements.morphs)?,
+            SectionKind::Frames => write_section(&mut writer, section, &replacements.frames)?,
+            SectionKind::RigidBodies => {
+                write_section(&mut writer, section, &replacements.rigid_bodies)?
+            }
+            SectionKind::Joints => write_section(&mut writer, section, &replacements.joints)?,
+            SectionKind::Vertices | SectionKind::Faces | SectionKind::SoftBodies => {
+                unreachable!("{:?} can't be replaced", section)
+            }
+        }
+    }
+    writer.write_vec(&input[end..]);
+    writer.inner.flush().map_err(WritePMXErrors::IoError)?;
+    Ok(header)
+}
+
+/// [`replace_sections`] of file at `input` written to `output`. both may be the same path.
+///
+/// input is loaded into memory but only replaced sections are kept as elements.
+#[cfg(feature = "std-fs")]
+pub fn replace_sections_in_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    input: P,
+    output: Q,
+    replacements: &SectionReplacements,
+) -> Result<Header, PatchError> {
+    let bytes = std::fs::read(input).map_err(WritePMXErrors::IoError)?;
+    let file = std::fs::File::create(output).map_err(WritePMXErrors::IoError)?;
+    replace_sections(&bytes, file, replacements)
+}
+
+/// count followed by elements.
+fn write_section<W: Write, T: WriteElement>(
+    writer: &mut BinaryWriter<W>,
+    section: SectionKind,
+    list: &Option<Vec<T>>,
+) -> Result<(), PatchError> {
+    let list = list.as_deref().unwrap_or_default();
+    writer.write_i32(section_count(section, list.len())?);
+    for (index, element) in list.iter().enumerate() {
+        element.write_to(writer);
+        writer.check_indices(section, index)?;
+    }
+    Ok(())
+}
+
+fn new_header(
+    old: &Header,
+    offsets: &SectionOffsets,
+    replacements: &SectionReplacements,
+) -> Result<Header, PatchError> {
+    // sections missing in file don't need the index.
+    let kept =
+        |section: SectionKind| !replacements.replaces(section) && offsets.get(section).is_some();
+    let resize = |old: IndexKinds,
+                  section: SectionKind,
+                  count: Option<usize>,
+                  stored_in: &[SectionKind]|
+     -> Result<IndexKinds, PatchError> {
+        let count = match count {
+            Some(count) => count,
+            None => return Ok(old),
+        };
+        let optimal = optimal_data_type(count);
+        match stored_in.iter().copied().find(|section| kept(*section)) {
+            None => Ok(optimal),
+            Some(_) if u8::from(optimal) <= u8::from(old) => Ok(old),
+            Some(required_by) => Err(PatchError::IndexSizeChanged {
+                section,
+                required_by,
+            }),
+        }
+    };
+    Ok(Header {
+        s_texture_index: resize(
+            old.s_texture_index,
+            SectionKind::Textures,
+            replacements.textures.as_ref().map(Vec::len),
+            &[SectionKind::Materials],
+        )?,
+        s_material_index: resize(
+            old.s_material_index,
+            SectionKind::Materials,
+            replacements.materials.as_ref().map(Vec::len),
+            &[SectionKind::Morphs, SectionKind::SoftBodies],
+        )?,
+        s_bone_index: resize(
+            old.s_bone_index,
+            SectionKind::Bones,
+            replacements.bones.as_ref().map(Vec::len),
+            &[
+                SectionKind::Vertices,
+                SectionKind::Bones,
+                SectionKind::Morphs,
+                SectionKind::Frames,
+                SectionKind::RigidBodies,
+            ],
+        )?,
+        s_morph_index: resize(
+            old.s_morph_index,
+            SectionKind::Morphs,
+            replacements.morphs.as_ref().map(Vec::len),
+            &[SectionKind::Morphs, SectionKind::Frames],
+        )?,
+        s_rigid_body_index: resize(
+            old.s_rigid_body_index,
+            SectionKind::RigidBodies,
+            replacements.rigid_bodies.as_ref().map(Vec::len),
+            &[
+                SectionKind::Morphs,
+                SectionKind::Joints,
+                SectionKind::SoftBodies,
+            ],
+        )?,
+        raw_version: None,
+        ..*old
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::patch::{replace_sections, PatchError, SectionReplacements};
+    use crate::progress::SectionKind;
+    use crate::reader::ModelInfoStage;
+    use crate::types::{Material, Model, ModelInfo};
+
+    fn patch(input: &[u8], replacements: &SectionReplacements) -> Result<Vec<u8>, PatchError> {
+        let mut output = vec![];
+        replace_sections(input, &mut output, replacements)?;
+        Ok(output)
+    }
+
+    fn offsets(bytes: &[u8]) -> crate::reader::SectionOffsets {
+        ModelInfoStage::from_reader(bytes)
+            .unwrap()
+            .read_model_with_offsets()
+            .1
+    }
+
+    #[test]
+    fn nothing_replaced() {
+        let mut model = crate::test_support::cube();
+        model.unknown.trailing = vec![1, 2, 3];
+        let input = crate::test_support::write(&model);
+        assert_eq!(
+            patch(&input, &SectionReplacements::default()).unwrap(),
+            input
+        );
+    }
+
+    #[test]
+    fn replace_textures_and_materials() {
+        let model = crate::test_support::cube();
+        let input = crate::test_support::write(&model);
+        let mut materials = model.materials.clone();
+        materials.reverse();
+        materials[0].name = "swapped".to_owned();
+        let replacements = SectionReplacements {
+            model_info: Some(ModelInfo {
+                name: "patched".to_owned(),
+                ..model.model_info.clone()
+            }),
+            textures: Some(vec!["a.png".to_owned()]),
+            materials: Some(materials.clone()),
+            ..SectionReplacements::default()
+        };
+        let output = patch(&input, &replacements).unwrap();
+        let read = crate::test_support::read(&output);
+        assert_eq!(read.model_info.name, "patched");
+        assert_eq!(read.textures.textures, ["a.png"]);
+        assert_eq!(read.materials, materials);
+        assert_eq!(read.vertices, model.vertices);
+        assert_eq!(read.bones, model.bones);
+        assert_eq!(read.soft_bodies, model.soft_bodies);
+
+        // kept sections are the same bytes.
+        let (old, new) = (offsets(&input), offsets(&output));
+        for section in [
+            SectionKind::Vertices,
+            SectionKind::Joints,
+            SectionKind::SoftBodies,
+        ] {
+            let old = old.get(section).unwrap();
+            let new = new.get(section).unwrap();
+            assert_eq!(
+                input[old.start as usize..(old.start + old.len) as usize],
+                output[new.start as usize..(new.start + new.len) as usize]
+            );
+        }
+    }
+
+    #[test]
+    fn index_sizes() {
+        let model = crate::test_support::cube();
+        let input = crate::test_support::write(&model);
+        let textures = (0..200).map(|i| format!("{}.png", i)).collect::<Vec<_>>();
+        // materials keep 1 byte texture indices.
+        let only_textures = SectionReplacements {
+            textures: Some(textures.clone()),
+            ..SectionReplacements::default()
+        };
+        match patch(&input, &only_textures) {
+            Err(PatchError::IndexSizeChanged {
+                section: SectionKind::Textures,
+                required_by: SectionKind::Materials,
+            }) => {}
+            other => panic!("{:?}", other),
+        }
+        let mut materials = model.materials.clone();
+        materials[0].texture_index = 150;
+        let with_materials = SectionReplacements {
+            textures: Some(textures.clone()),
+            materials: Some(materials.clone()),
+            ..SectionReplacements::default()
+        };
+        let mut output = vec![];
+        let header = replace_sections(&input, &mut output, &with_materials).unwrap();
+        assert_eq!(u8::from(header.s_texture_index), 2);
+        let read = crate::test_support::read(&output);
+        assert_eq!(read.textures.textures, textures);
+        assert_eq!(read.materials[0].texture_index, 150);
+        assert_eq!(read.faces, model.faces);
+
+        // fewer elements fit in the current size.
+        let fewer = SectionReplacements {
+            materials: Some(vec![Material::builder("one")
+                .num_face_vertices(3 * model.faces.len() as i32)
+                .build()]),
+            ..SectionReplacements::default()
+        };
+        let read = crate::test_support::read(&patch(&input, &fewer).unwrap());
+        assert_eq!(read.materials.len(), 1);
+    }
+
+    #[test]
+    fn index_out_of_range() {
+        let model = crate::test_support::cube();
+        let input = crate::test_support::write(&model);
+        let mut materials = model.materials.clone();
+        materials[1].texture_index = 1000;
+        let replacements = SectionReplacements {
+            materials: Some(materials),
+            ..SectionReplacements::default()
+        };
+        match patch(&input, &replacements) {
+            Err(PatchError::Write(crate::writer::WritePMXErrors::IndexOutOfRange {
+                section: SectionKind::Materials,
+                element: 1,
+                index: 1000,
+            })) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn requires_v21() {
+        let input = crate::test_support::write(&Model::default());
+        let replacements = SectionReplacements {
+            joints: Some(crate::test_support::cube().joints),
+            ..SectionReplacements::default()
+        };
+        match patch(&input, &replacements) {
+            Err(PatchError::Write(crate::writer::WritePMXErrors::RequiresV21)) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Conventional values of `MMD` models for models made from scratch.
+//!
+//! presets return builders or plain types so they can be adjusted further.
+//!
+//! ```rust
+//! use PMXUtil::presets;
+//! use PMXUtil::types::RigidForm;
+//!
+//! let skin = presets::standard_material("肌", 0)
+//!     .diffuse([1.0, 0.9, 0.8, 1.0])
+//!     .build();
+//! assert_eq!(skin.ambient, [0.5, 0.45, 0.4]);
+//! let hair = presets::hair_rigid("髪", 3)
+//!     .shape(RigidForm::Capsule, [0.2, 1.0, 0.0])
+//!     .build();
+//! let frames = presets::standard_frames();
+//! assert_eq!(frames[0].name, "Root");
+//! ```
+use crate::builder::{MaterialBuilder, RigidBuilder};
+use crate::frames::{expression_frame, root_frame};
+use crate::types::{Frame, Material, Rigid, RigidCalcMethod, RigidGroups, ToonMode, Vec4};
+
+pub use crate::types::BUILTIN_TOON_TEXTURES;
+
+/// rigid body group of [`body_rigid`]. 0 origin, shown as 1 in `PMXEditor`.
+pub const BODY_GROUP: u8 = 0;
+/// rigid body group of [`hair_rigid`].
+pub const HAIR_GROUP: u8 = 1;
+/// rigid body group of [`skirt_rigid`].
+pub const SKIRT_GROUP: u8 = 2;
+
+/// opaque white material showing `texture_index` (`-1` for none).
+///
+/// see [`Material::builder`] for the rest. ambient stays half of diffuse when diffuse is changed.
+pub fn standard_material(name: &str, texture_index: i32) -> MaterialBuilder {
+    Material::builder(name).texture(texture_index)
+}
+
+/// [`standard_material`] with outline of `edge_color` and `edge_size`.
+pub fn edge_material(
+    name: &str,
+    texture_index: i32,
+    edge_color: Vec4,
+    edge_size: f32,
+) -> MaterialBuilder {
+    standard_material(name, texture_index).edge(edge_color, edge_size)
+}
+
+/// `ToonMode::Common` of builtin toon `file_name` like `toon03.bmp` ignoring case.
+pub fn common_toon(file_name: &str) -> Option<ToonMode> {
+    BUILTIN_TOON_TEXTURES
+        .iter()
+        .position(|toon| toon.eq_ignore_ascii_case(file_name))
+        .map(|index| ToonMode::Common(index as u8))
+}
+
+/// rigid body following bone to push hair and skirt. collides with all groups.
+pub fn body_rigid(name: &str, bone_index: i32) -> RigidBuilder {
+    Rigid::builder(name, bone_index)
+        .group(BODY_GROUP)
+        .calc_method(RigidCalcMethod::Static)
+}
+
+/// light and strongly damped rigid body moved by physics.
+/// doesn't collide with other hair.
+pub fn hair_rigid(name: &str, bone_index: i32) -> RigidBuilder {
+    Rigid::builder(name, bone_index)
+        .group(HAIR_GROUP)
+        .no_collision_with(RigidGroups::single(HAIR_GROUP))
+        .calc_method(RigidCalcMethod::Dynamic)
+        .mass(0.5)
+        .damping(0.9, 0.99)
+}
+
+/// rigid body moved by physics keeping bone position so skirt doesn't stretch.
+/// doesn't collide with other skirt.
+pub fn skirt_rigid(name: &str, bone_index: i32) -> RigidBuilder {
+    Rigid::builder(name, bone_index)
+        .group(SKIRT_GROUP)
+        .no_collision_with(RigidGroups::single(SKIRT_GROUP))
+        .calc_method(RigidCalcMethod::DynamicWithBonePosition)
+        .damping(0.5, 0.99)
+}
+
+/// empty frames of the standard `MMD` model: Root and 表情 followed by bone frames.
+///
+/// add bones with [`frames::add_bone_to`](crate::frames::add_bone_to).
+pub fn standard_frames() -> Vec<Frame> {
+    let bone_frame = |name: &str, name_en: &str| Frame {
+        name: name.to_owned(),
+        name_en: name_en.to_owned(),
+        is_special: false,
+        inners: vec![],
+    };
+    vec![
+        root_frame(&[]),
+        expression_frame(&[]),
+        bone_frame("ＩＫ", "IK"),
+        bone_frame("体(上)", "Upper Body"),
+        bone_frame("髪", "Hair"),
+        bone_frame("腕", "Arms"),
+        bone_frame("指", "Fingers"),
+        bone_frame("体(下)", "Lower Body"),
+        bone_frame("足", "Legs"),
+        bone_frame("その他", "Others"),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::presets::{
+        body_rigid, common_toon, edge_material, hair_rigid, skirt_rigid, standard_frames,
+        standard_material, BUILTIN_TOON_TEXTURES,
+    };
+    use crate::types::{Bone, FrameInner, RigidCalcMethod, ToonMode};
+    use crate::validation::{check_geometry, find_unframed, GeometryOptions};
+
+    #[test]
+    fn presets_are_valid() {
+        let mut model = crate::test_support::cube();
+        model.materials = vec![
+            standard_material("肌", 0)
+                .diffuse([1.0, 0.8, 0.6, 1.0])
+                .num_face_vertices(18)
+                .build(),
+            edge_material("髪", -1, [0.2, 0.1, 0.1, 1.0], 0.5)
+                .num_face_vertices(18)
+                .build(),
+        ];
+        for material in &model.materials {
+            for channel in 0..3 {
+                assert_eq!(material.ambient[channel], material.diffuse[channel] * 0.5);
+            }
+            assert!(material.has_edge());
+            assert!(material.casts_ground_shadow());
+        }
+        assert_eq!(model.materials[1].edge_size, 0.5);
+        model.frames = standard_frames();
+        model.bones = vec![Bone::builder("センター").build()];
+        model.frames[0].inners = vec![FrameInner::Bone(0)];
+        model.morphs.clear();
+        model.rigid_bodies = vec![
+            body_rigid("上半身", 0).build(),
+            hair_rigid("髪", 0).build(),
+            skirt_rigid("スカート", 0).build(),
+        ];
+        model.joints.clear();
+        model.soft_bodies.clear();
+        assert!(check_geometry(&model, &GeometryOptions::default()).is_empty());
+        assert!(find_unframed(&model).is_empty());
+        let writer = crate::writer::Writer::from_model(&model);
+        assert!(writer.validate().unwrap().is_empty());
+
+        let [body, hair, skirt] = [0, 1, 2].map(|i| &model.rigid_bodies[i]);
+        assert_eq!(body.calc_method, RigidCalcMethod::Static);
+        assert_eq!(body.un_collision_group_flag.bits(), 0);
+        assert!(hair.collides_with(body));
+        assert!(!hair.collides_with(hair));
+        assert!(!skirt.collides_with(skirt));
+        assert!(skirt.collides_with(hair));
+        assert!(hair.mass > 0.0 && hair.move_resist < 1.0 && hair.rotation_resist < 1.0);
+    }
+
+    #[test]
+    fn toon_names() {
+        assert_eq!(common_toon("TOON03.bmp"), Some(ToonMode::Common(2)));
+        assert_eq!(common_toon("toon11.bmp"), None);
+        for (index, name) in BUILTIN_TOON_TEXTURES.iter().enumerate() {
+            assert_eq!(common_toon(name), Some(ToonMode::Common(index as u8)));
+        }
+        let frames = standard_frames();
+        assert!(frames[..2].iter().all(|frame| frame.is_special));
+        assert!(frames[2..].iter().all(|frame| !frame.is_special));
+    }
+}
@@ -0,0 +1,246 @@
+//! Progress reporting for long reads and writes.
+//!
+//! ```rust
+//! use std::ops::ControlFlow;
+//! use PMXUtil::progress::Progress;
+//! use PMXUtil::reader::ParseOptions;
+//!
+//! let options = ParseOptions::default().progress(|progress: Progress| {
+//!     println!("{:?} {}/{}", progress.section, progress.done, progress.total);
+//!     ControlFlow::Continue(())
+//! });
+//! ```
+use std::fmt::{Display, Formatter};
+use std::ops::ControlFlow;
+
+/// callback receives progress. return `ControlFlow::Break(())` to cancel.
+pub type ProgressCallback = Box<dyn FnMut(Progress) -> ControlFlow<()> + Send>;
+
+/// callback is invoked every `PROGRESS_INTERVAL` elements in a section.
+pub const PROGRESS_INTERVAL: usize = 1024;
+
+/// sections of PMX file in the order of appearance.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SectionKind {
+    ModelInfo,
+    Vertices,
+    Faces,
+    Textures,
+    Materials,
+    Bones,
+    Morphs,
+    Frames,
+    RigidBodies,
+    Joints,
+    /// only in PMX 2.1
+    SoftBodies,
+}
+
+impl SectionKind {
+    /// snake case name like `rigid_bodies`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SectionKind::ModelInfo => "model_info",
+            SectionKind::Vertices => "vertices",
+            SectionKind::Faces => "faces",
+            SectionKind::Textures => "textures",
+            SectionKind::Materials => "materials",
+            SectionKind::Bones => "bones",
+            SectionKind::Morphs => "morphs",
+            SectionKind::Frames => "frames",
+            SectionKind::RigidBodies => "rigid_bodies",
+            SectionKind::Joints => "joints",
+            SectionKind::SoftBodies => "soft_bodies",
+        }
+    }
+}
+
+/// `done` elements of `total` in `section` are processed.
+///
+/// reported with `done == 0` when a section starts and `done == total` when it ends.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Progress {
+    pub section: SectionKind,
+    pub done: usize,
+    pub total: usize,
+}
+
+/// callback returned `ControlFlow::Break`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cancelled by progress callback")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+#[derive(Default)]
+pub(crate) struct Reporter {
+    callback: Option<ProgressCallback>,
+    cancelled: bool,
+}
+
+impl Reporter {
+    pub(crate) fn new(callback: Option<ProgressCallback>) -> Self {
+        Self {
+            callback,
+            cancelled: false,
+        }
+    }
+
+    /// give back the callback to use it again.
+    pub(crate) fn into_callback(self) -> Option<ProgressCallback> {
+        self.callback
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    /// stop without asking the callback.
+    pub(crate) fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    /// returns `false` if cancelled.
+    #[inline]
+    pub(crate) fn report(&mut self, section: SectionKind, done: usize, total: usize) -> bool {
+        let callback = match &mut self.callback {
+            None => return !self.cancelled,
+            Some(callback) => callback,
+        };
+        if !self.cancelled && (done.is_multiple_of(PROGRESS_INTERVAL) || done == total) {
+            let progress = Progress {
+                section,
+                done,
+                total,
+            };
+            self.cancelled = callback(progress).is_break();
+        }
+        !self.cancelled
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::progress::{Cancelled, Progress, SectionKind, PROGRESS_INTERVAL};
+    use crate::reader::{ModelInfoStage, ParseOptions};
+    use crate::test_support::{cube, write};
+    use crate::types::{Face, Model};
+    use crate::writer::{WritePMXErrors, Writer};
+    use std::ops::ControlFlow;
+    use std::sync::{Arc, Mutex};
+
+    fn recorder(
+        cancel_at: Option<SectionKind>,
+    ) -> (
+        Arc<Mutex<Vec<Progress>>>,
+        impl FnMut(Progress) -> ControlFlow<()> + Send + 'static,
+    ) {
+        let log = Arc::new(Mutex::new(vec![]));
+        let sink = log.clone();
+        let callback = move |progress: Progress| {
+            sink.lock().unwrap().push(progress);
+            if Some(progress.section) == cancel_at {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        };
+        (log, callback)
+    }
+
+    fn big_model() -> Model {
+        let mut model = cube();
+        let faces = model.faces.clone();
+        model.faces = faces
+            .iter()
+            .cycle()
+            .take(PROGRESS_INTERVAL * 2 + 12)
+            .copied()
+            .collect::<Vec<Face>>();
+        model
+    }
+
+    #[test]
+    fn read_reports_every_section() {
+        let model = big_model();
+        let buf = write(&model);
+        let (log, callback) = recorder(None);
+        let options = ParseOptions {
+            progress: Some(Box::new(callback)),
+            ..ParseOptions::default()
+        };
+        let model_cpy = ModelInfoStage::from_reader(&buf[..])
+            .unwrap()
+            .read_model_with(options)
+            .unwrap();
+        assert_eq!(model, model_cpy);
+
+        let log = log.lock().unwrap();
+        let faces: Vec<usize> = log
+            .iter()
+            .filter(|progress| progress.section == SectionKind::Faces)
+            .map(|progress| progress.done)
+            .collect();
+        let total = model.faces.len();
+        assert_eq!(
+            faces,
+            vec![0, PROGRESS_INTERVAL, PROGRESS_INTERVAL * 2, total]
+        );
+        assert_eq!(log.first().unwrap().section, SectionKind::ModelInfo);
+        assert_eq!(
+            *log.last().unwrap(),
+            Progress {
+                section: SectionKind::SoftBodies,
+                done: 1,
+                total: 1
+            }
+        );
+    }
+
+    #[test]
+    fn read_cancel() {
+        let buf = write(&big_model());
+        let (log, callback) = recorder(Some(SectionKind::Faces));
+        let options = ParseOptions {
+            progress: Some(Box::new(callback)),
+            ..ParseOptions::default()
+        };
+        let result = ModelInfoStage::from_reader(&buf[..])
+            .unwrap()
+            .read_model_with(options);
+        assert_eq!(result, Err(Cancelled));
+        let log = log.lock().unwrap();
+        assert_eq!(log.last().unwrap().section, SectionKind::Faces);
+        assert_eq!(log.last().unwrap().done, 0);
+    }
+
+    #[test]
+    fn write_reports_and_cancel() {
+        let model = big_model();
+        let (log, callback) = recorder(None);
+        let mut writer = Writer::from_model(&model);
+        writer.set_progress(callback);
+        let mut buf = vec![];
+        writer.write(&mut buf).unwrap();
+        assert_eq!(buf, write(&model));
+        assert!(log
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|progress| progress.section == SectionKind::Joints
+                && progress.done == model.joints.len()));
+
+        let (_, callback) = recorder(Some(SectionKind::Bones));
+        let mut writer = Writer::from_model(&model);
+        writer.set_progress(callback);
+        assert!(matches!(
+            writer.write(&mut vec![]),
+            Err(WritePMXErrors::Cancelled)
+        ));
+    }
+}
@@ -14,32 +14,64 @@
 //! |[`RigidStage`]|[`Vec<Rigid>`]|[`JointStage`]|
 //! |[`JointStage`]|[`Vec<Joint>`]|[`Option<SoftBodyStage>`]|
 //! |[`SoftBodyStage`]|[`Vec<SoftBody>`]|There are no reader|
-//! ```rust
-//! // i want to get pmx path from env vars.
-//! let path = std::env::var("PMX_FILE").unwrap();
-//! let model_info_loader=PMXUtil::reader::ModelInfoStage::open(path);
-//! let (model_info,vertices_loader)=model_info_loader.unwrap().read();
+//!
+//! all of them implement [`Stage`] for code generic over stages.
+//! [`JointStage::read_with_end`] and [`SoftBodyStage::read_with_end`] continue to [`EndStage`]
+//! which checks the rest of file.
+//! ```
+//! # let mut writer = PMXUtil::writer::Writer::begin_writer(true);
+//! # writer.set_model_info(&Default::default());
+//! # let bytes = writer.write_to_vec().unwrap();
+//! // any `impl Read` works. `&[u8]` for in memory file.
+//! let model_info_loader = PMXUtil::reader::ModelInfoStage::from_reader(&bytes[..]);
+//! let (model_info, vertices_loader) = model_info_loader.unwrap().read();
 //! ```
 //!
+//! [`ModelInfoStage::open`] reads file at path. it needs `std-fs` feature (enabled by default).
+//!
+//! every stage is `Send` if its source is, so a partially read model can move to other
+//! threads, e.g. stages of [`ModelInfoStage::open`] between tasks of a thread pool.
+//!
+//! [`read_metadata`] reads only header and model info, e.g. for listing models.
+//!
+//! [`visit`] reads whole file passing elements one by one to [`PmxVisitor`] without keeping them.
+//!
 
 use crate::binary_reader::BinaryReader;
+use crate::progress::{Cancelled, Progress, ProgressCallback, Reporter, SectionKind};
 use crate::types::{
     Bone, BoneFlags, BoneIKInfo, BoneMorph, ConnectionDisplayMode, ControlPanel, Encode, Face,
     FlipMorph, Frame, FrameInner, GroupMorph, Header, HeaderConversionError, HeaderRaw, IKLink,
     ImpulseMorph, Joint, JointParameterRaw, JointType, Material, MaterialFlags, MaterialMorph,
-    ModelInfo, Morph, MorphKinds, PMXVersion, Rigid, RigidCalcMethod, RigidForm,
-    RotateAndTranslateInherits, SoftBody, SoftBodyAeroModel, SoftBodyAnchorRigid, SoftBodyForm,
-    SphereMode, SphereModeKind, ToonMode, UVMorph, Vertex, VertexMorph, VertexWeight,
+    MaterialMorphFormula, Model, ModelInfo, Morph, MorphKinds, PMXVersion, RawSection, Rigid,
+    RigidCalcMethod, RigidForm, RigidGroups, RotateAndTranslateInherits, SoftBody,
+    SoftBodyAeroModel, SoftBodyAnchorRigid, SoftBodyFlags, SoftBodyForm, SphereMode,
+    SphereModeKind, TextureList, ToonMode, UVMorph, UnknownData, Vertex, VertexBuffers,
+    VertexMorph, VertexWeight,
 };
-use std::convert::TryInto;
+use std::borrow::Cow;
+use std::convert::{TryFrom, TryInto};
+use std::fmt::{Display, Formatter};
+#[cfg(feature = "std-fs")]
 use std::fs::File;
 use std::io::Read;
+use std::ops::ControlFlow;
+#[cfg(feature = "std-fs")]
 use std::path::Path;
 
+#[cfg(feature = "zip")]
+mod archive;
+#[cfg(feature = "zip")]
+pub use archive::{open_from_zip, TextureResolver, ZipReadError};
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "rayon")]
+pub use parallel::{read_model_parallel, ParallelReadError};
+
 fn transform_header_c2r(header: &HeaderRaw) -> Result<Header, HeaderConversionError> {
     if header.magic == [0x50, 0x4d, 0x58, 0x20] {
         Ok(Header {
-            magic: "PMX ".to_owned(),
+            magic: *b"PMX ",
             version: if header.version >= 2.0 {
                 if header.version < 2.2 {
                     if header.version > 2.05 {
@@ -62,20 +94,356 @@ fn transform_header_c2r(header: &HeaderRaw) -> Result<Header, HeaderConversionEr
                 }
             },
             additional_uv: header.config[1],
-            s_vertex_index: header.config[2].try_into().unwrap(),
-            s_texture_index: header.config[3].try_into().unwrap(),
-            s_material_index: header.config[4].try_into().unwrap(),
-            s_bone_index: header.config[5].try_into().unwrap(),
-            s_morph_index: header.config[6].try_into().unwrap(),
-            s_rigid_body_index: header.config[7].try_into().unwrap(),
+            s_vertex_index: index_kind(header.config[2])?,
+            s_texture_index: index_kind(header.config[3])?,
+            s_material_index: index_kind(header.config[4])?,
+            s_bone_index: index_kind(header.config[5])?,
+            s_morph_index: index_kind(header.config[6])?,
+            s_rigid_body_index: index_kind(header.config[7])?,
+            raw_version: Some(header.version.to_bits()),
         })
     } else {
         Err(HeaderConversionError::InvalidMagic)
     }
 }
 
+fn index_kind<T: TryFrom<u8>>(size: u8) -> Result<T, HeaderConversionError> {
+    T::try_from(size).map_err(|_| HeaderConversionError::InvalidIndex)
+}
+
+impl Header {
+    /// size of the fixed part of PMX header in bytes.
+    pub const SIZE: usize = 17;
+
+    /// parse the first [`Header::SIZE`] bytes of `bytes`. the rest is not read.
+    ///
+    /// # Errors
+    /// * `HeaderConversionError::Truncated` if `bytes` is shorter than [`Header::SIZE`]
+    /// * other variants if the header is not PMX 2.0 or 2.1
+    ///
+    /// ```rust
+    /// # let bytes = PMXUtil::writer::Writer::from_model(&Default::default()).write_to_vec().unwrap();
+    /// let header = PMXUtil::types::Header::peek(&bytes).unwrap();
+    /// assert_eq!(header.version, PMXUtil::types::PMXVersion::V20);
+    /// ```
+    pub fn peek(bytes: &[u8]) -> Result<Header, HeaderConversionError> {
+        let bytes = bytes
+            .get(..Self::SIZE)
+            .ok_or(HeaderConversionError::Truncated)?;
+        transform_header_c2r(&HeaderRaw {
+            magic: bytes[..4].try_into().unwrap(),
+            version: f32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+            length: bytes[8],
+            config: bytes[9..].try_into().unwrap(),
+        })
+    }
+}
+
+/// at most this many bytes are read by [`read_metadata`].
+///
+/// enough for [`Header::SIZE`] and model info texts of ordinary models.
+pub const METADATA_MAX_BYTES: usize = 16 * 1024;
+
+/// error of [`read_metadata`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MetadataError {
+    /// not a PMX 2.0 or 2.1 header.
+    Header(HeaderConversionError),
+    /// texts of model info don't fit in [`METADATA_MAX_BYTES`].
+    TooLarge,
+    /// a text of model info is not valid UTF-8.
+    InvalidText,
+    /// failed to read or the file ended in model info.
+    Io(std::io::Error),
+}
+
+impl Display for MetadataError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetadataError::Header(err) => write!(f, "invalid header: {}", err),
+            MetadataError::TooLarge => {
+                write!(f, "model info is larger than {} bytes", METADATA_MAX_BYTES)
+            }
+            MetadataError::InvalidText => write!(f, "model info has invalid UTF-8 text"),
+            MetadataError::Io(err) => write!(f, "failed to read: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MetadataError::Header(err) => Some(err),
+            MetadataError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for MetadataError {
+    fn from(err: std::io::Error) -> Self {
+        MetadataError::Io(err)
+    }
+}
+
+/// read only header and model info from `reader`.
+///
+/// at most [`METADATA_MAX_BYTES`] bytes are read even if texts claim to be longer,
+/// and nothing after model info is read. `reader` is not buffered so wrap it in
+/// `BufReader` if small reads are slow.
+///
+/// # Errors
+/// see [`MetadataError`]. the file doesn't need to have sections after model info.
+///
+/// ```rust
+/// # let mut model = PMXUtil::types::Model::default();
+/// # model.model_info.name = "ミク".to_owned();
+/// # let bytes = PMXUtil::writer::Writer::from_model(&model).write_to_vec().unwrap();
+/// let (header, model_info) = PMXUtil::reader::read_metadata(&bytes[..]).unwrap();
+/// assert_eq!(model_info.name, "ミク");
+/// ```
+pub fn read_metadata<R: Read>(reader: R) -> Result<(Header, ModelInfo), MetadataError> {
+    let mut reader = reader.take(METADATA_MAX_BYTES as u64);
+    let mut bytes = [0; Header::SIZE];
+    reader.read_exact(&mut bytes)?;
+    let header = Header::peek(&bytes).map_err(MetadataError::Header)?;
+    let mut read_text = || {
+        let mut len = [0; 4];
+        reader.read_exact(&mut len)?;
+        let len = usize::try_from(i32::from_le_bytes(len)).unwrap_or(0);
+        if len as u64 > reader.limit() {
+            return Err(MetadataError::TooLarge);
+        }
+        let mut text = vec![0; len];
+        reader.read_exact(&mut text)?;
+        match header.encode {
+            Encode::UTF8 => String::from_utf8(text).map_err(|_| MetadataError::InvalidText),
+            Encode::Utf16Le => Ok(encoding_rs::UTF_16LE
+                .decode_without_bom_handling(&text)
+                .0
+                .into_owned()),
+        }
+    };
+    let model_info = ModelInfo {
+        name: read_text()?,
+        name_en: read_text()?,
+        comment: read_text()?,
+        comment_en: read_text()?,
+    };
+    Ok((header, model_info))
+}
+
+/// [`read_metadata`] of file at `path`.
+#[cfg(feature = "std-fs")]
+pub fn read_metadata_file<P: AsRef<Path>>(path: P) -> Result<(Header, ModelInfo), MetadataError> {
+    let file = File::open(path)?.take(METADATA_MAX_BYTES as u64);
+    read_metadata(std::io::BufReader::new(file))
+}
+
+/// options for [`ModelInfoStage::read_model_with`] and [`ModelInfoStage::open_with`].
+///
+/// the default reads as [`read_model`](ModelInfoStage::read_model) does.
+/// set fields by builder methods as more may be added.
+///
+/// ```rust
+/// use PMXUtil::reader::ParseOptions;
+/// let options = ParseOptions::default().lenient(true).dedup_textures(true);
+/// ```
+#[derive(Default)]
+#[non_exhaustive]
+pub struct ParseOptions {
+    /// invoked at section boundaries and every [`PROGRESS_INTERVAL`](crate::progress::PROGRESS_INTERVAL) elements.
+    pub progress: Option<ProgressCallback>,
+    /// merge duplicated texture paths by [`Model::dedup_textures`].
+    pub dedup_textures: bool,
+    /// keep data after the last section in [`Model::unknown`] to be written back by
+    /// [`Writer::from_model`](crate::writer::Writer::from_model). see [`UnknownData`].
+    pub preserve_unknown: bool,
+    /// tolerate invalid values as [`ModelInfoStage::set_lenient`].
+    pub lenient: bool,
+}
+
+impl ParseOptions {
+    pub fn progress<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(Progress) -> ControlFlow<()> + Send + 'static,
+    {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+    pub fn dedup_textures(mut self, dedup_textures: bool) -> Self {
+        self.dedup_textures = dedup_textures;
+        self
+    }
+    pub fn preserve_unknown(mut self, preserve_unknown: bool) -> Self {
+        self.preserve_unknown = preserve_unknown;
+        self
+    }
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+}
+
+/// receives elements one by one from [`visit`].
+///
+/// each element is passed by value and nothing is kept by reader.
+/// all methods do nothing by default so implement only what you need.
+#[allow(unused_variables)]
+pub trait PmxVisitor {
+    fn header(&mut self, header: Header) {}
+    /// called before the first element of each section with its element count.
+    fn section_start(&mut self, section: SectionKind, count: usize) {}
+    fn model_info(&mut self, model_info: ModelInfo) {}
+    fn vertex(&mut self, vertex: Vertex) {}
+    fn face(&mut self, face: Face) {}
+    fn texture(&mut self, texture: String) {}
+    fn material(&mut self, material: Material) {}
+    fn bone(&mut self, bone: Bone) {}
+    fn morph(&mut self, morph: Morph) {}
+    fn frame(&mut self, frame: Frame) {}
+    fn rigid(&mut self, rigid: Rigid) {}
+    fn joint(&mut self, joint: Joint) {}
+    /// only called for PMX 2.1
+    fn soft_body(&mut self, soft_body: SoftBody) {}
+}
+
+/// read PMX from `reader` passing elements to `visitor`.
+///
+/// use `&[u8]` as `reader` for in memory file.
+/// # None
+/// * read magic number is not `PMX `
+/// # Panics
+/// same as each stage
+///
+/// # Examples
+///
+/// ```
+/// use PMXUtil::reader::PmxVisitor;
+/// use PMXUtil::types::Vertex;
+///
+/// #[derive(Default)]
+/// struct VertexCounter(usize);
+/// impl PmxVisitor for VertexCounter {
+///     fn vertex(&mut self, _vertex: Vertex) {
+///         self.0 += 1;
+///     }
+/// }
+/// # let mut buf = vec![];
+/// # let mut writer = PMXUtil::writer::Writer::begin_writer(true);
+/// # writer.set_model_info(&Default::default());
+/// # writer.write(&mut buf).unwrap();
+/// let mut counter = VertexCounter::default();
+/// PMXUtil::reader::visit(&buf[..], &mut counter).unwrap();
+/// assert_eq!(counter.0, 0);
+/// ```
+pub fn visit<R: Read, V: PmxVisitor>(reader: R, visitor: &mut V) -> Option<()> {
+    ModelInfoStage::from_reader(reader)?.visit(visitor);
+    Some(())
+}
+
+/// [`visit`] file at `path`.
+/// # None
+/// * invalid path given
+/// * read magic number is not `PMX `
+#[cfg(feature = "std-fs")]
+pub fn visit_file<P: AsRef<Path>, V: PmxVisitor>(path: P, visitor: &mut V) -> Option<()> {
+    ModelInfoStage::open(path)?.visit(visitor);
+    Some(())
+}
+
+/// texture paths of PMX in `bytes` without copying UTF-8 text.
+///
+/// UTF-8 paths borrow `bytes` and UTF-16 ones are decoded into owned strings.
+/// textures section holds the most texts which are often kept as cache keys.
+/// vertices and faces before it are parsed but not kept.
+/// # None
+/// * read magic number is not `PMX `
+/// * vertices or faces are broken, e.g. truncated
+/// * textures section is truncated or has invalid UTF-8
+///
+/// ```rust
+/// # let model = PMXUtil::types::Model::default();
+/// # let bytes = PMXUtil::writer::Writer::from_model(&model).write_to_vec().unwrap();
+/// let textures = PMXUtil::reader::read_textures_borrowed(&bytes).unwrap();
+/// assert!(textures.is_empty());
+/// ```
+pub fn read_textures_borrowed(bytes: &[u8]) -> Option<Vec<Cow<'_, str>>> {
+    let mut stage = ModelInfoStage::from_reader(bytes)?;
+    // broken data stops reading instead of panic.
+    stage.0.fallible = true;
+    let (_, mut vertices) = stage.read();
+    let encode = vertices.encode();
+    let count = vertices.0.read_i32();
+    vertices.for_each_counted(
+        SectionKind::Vertices,
+        count,
+        VerticesStage::read_pmx_vertex,
+        drop,
+    );
+    if vertices.0.error.is_some() {
+        return None;
+    }
+    let mut faces = FacesStage(vertices.0);
+    let count = faces.0.read_i32() / 3;
+    faces.for_each_counted(SectionKind::Faces, count, FacesStage::read_face, drop);
+    if faces.0.error.is_some() {
+        return None;
+    }
+    let mut rest = bytes.get(usize::try_from(faces.0.position()).ok()?..)?;
+    let count = take_len(&mut rest)?;
+    let mut textures = Vec::with_capacity(count.min(bytes.len() / 4));
+    for _ in 0..count {
+        let len = take_len(&mut rest)?;
+        let text = take(&mut rest, len)?;
+        textures.push(match encode {
+            Encode::UTF8 => Cow::Borrowed(std::str::from_utf8(text).ok()?),
+            Encode::Utf16Le => Cow::Owned(
+                encoding_rs::UTF_16LE
+                    .decode_without_bom_handling(text)
+                    .0
+                    .into_owned(),
+            ),
+        });
+    }
+    Some(textures)
+}
+
+/// first `len` bytes of `rest` advancing it.
+fn take<'a>(rest: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    let taken = rest.get(..len)?;
+    *rest = &rest[len..];
+    Some(taken)
+}
+
+/// length or count field. negative one is 0 like the stages.
+fn take_len(rest: &mut &[u8]) -> Option<usize> {
+    let len = i32::from_le_bytes(take(rest, 4)?.try_into().unwrap());
+    Some(usize::try_from(len).unwrap_or(0))
+}
+
+/// return `Err(Stop)` if progress callback of the stage cancelled reading or data was broken.
+macro_rules! check_cancelled {
+    ($stage:expr) => {
+        if $stage.0.reporter.is_cancelled() {
+            return Err($stage.0.stop());
+        }
+    };
+}
+
+/// read a stage and return `Err(Stop)` if cancelled or failed.
+macro_rules! read_stage {
+    ($stage:expr) => {{
+        let (product, next) = $stage.read();
+        check_cancelled!(next);
+        (product, next)
+    }};
+}
+
 pub struct ModelInfoStage<R: Read>(ReaderInner<R>);
 
+#[cfg(feature = "std-fs")]
 impl ModelInfoStage<File> {
     /// the start of reader module.
     /// # None
@@ -88,7 +456,7 @@ impl ModelInfoStage<File> {
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```no_run
     /// let path = std::env::var("PMX_FILE").unwrap();
     /// let model_info_loader = PMXUtil::reader::ModelInfoStage::open(path).unwrap();
     /// ```
@@ -96,36 +464,297 @@ impl ModelInfoStage<File> {
         let mut inner = BinaryReader::open(path).ok()?;
         let header = inner.read_raw_header();
         let header_rs = transform_header_c2r(&header).ok()?;
-        Some(ModelInfoStage(ReaderInner {
-            inner,
-            header: header_rs,
-        }))
+        Some(ModelInfoStage(ReaderInner::new(inner, header_rs)))
+    }
+
+    /// [`open`](Self::open) reading with `options`.
+    ///
+    /// `lenient` applies to every stage. the rest is used by [`read_model`](Self::read_model),
+    /// [`try_read_model`](Self::try_read_model) and
+    /// [`read_model_with_offsets`](Self::read_model_with_offsets).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use PMXUtil::reader::{ModelInfoStage, ParseOptions};
+    /// let options = ParseOptions::default().lenient(true);
+    /// let model = ModelInfoStage::open_with("model.pmx", options)
+    ///     .unwrap()
+    ///     .try_read_model()
+    ///     .unwrap();
+    /// ```
+    pub fn open_with<P: AsRef<Path>>(path: P, options: ParseOptions) -> Option<Self> {
+        Some(Self::open(path)?.with_options(options))
     }
 }
 
 impl<R: Read> ModelInfoStage<R> {
+    /// the start of reader module for any byte source.
+    /// # None
+    /// * read magic number is not `PMX `
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # let mut writer = PMXUtil::writer::Writer::begin_writer(true);
+    /// # writer.set_model_info(&Default::default());
+    /// let bytes: Vec<u8> = writer.write_to_vec().unwrap();
+    /// let model_info_loader = PMXUtil::reader::ModelInfoStage::from_reader(&bytes[..]).unwrap();
+    /// ```
     pub fn from_reader(reader: R) -> Option<Self> {
         let mut inner = BinaryReader::from_reader(reader);
         let header_rs = transform_header_c2r(&inner.read_raw_header()).ok()?;
-        Some(ModelInfoStage(ReaderInner {
-            inner,
-            header: header_rs,
-        }))
+        Some(ModelInfoStage(ReaderInner::new(inner, header_rs)))
+    }
+
+    /// [`from_reader`](Self::from_reader) reading with `options`.
+    /// see [`open_with`](ModelInfoStage::open_with).
+    pub fn from_reader_with(reader: R, options: ParseOptions) -> Option<Self> {
+        Some(Self::from_reader(reader)?.with_options(options))
+    }
+
+    fn with_options(mut self, options: ParseOptions) -> Self {
+        self.0.lenient |= options.lenient;
+        self.0.options = options;
+        self
+    }
+    /// if `lenient` is true, reader tolerates some invalid values and reports them by `warnings()`
+    /// instead of panic.
+    ///
+    /// the default is strict.
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.0.lenient = lenient;
+    }
+
+    /// read all sections at once.
+    ///
+    /// # Panics
+    /// same as each stage
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # let mut buf = vec![];
+    /// # let mut writer = PMXUtil::writer::Writer::begin_writer(true);
+    /// # writer.set_model_info(&Default::default());
+    /// # writer.write(&mut buf).unwrap();
+    /// let model = PMXUtil::reader::ModelInfoStage::from_reader(&buf[..])
+    ///     .unwrap()
+    ///     .read_model();
+    /// println!("{}", model.summary());
+    /// ```
+    ///
+    /// # Panics
+    /// also if the progress callback given by [`open_with`](ModelInfoStage::open_with) cancels.
+    pub fn read_model(mut self) -> Model {
+        let options = std::mem::take(&mut self.0.options);
+        self.read_model_with(options)
+            .expect("callback of open_with cancelled")
+    }
+
+    /// [`read_model`](Self::read_model) with options instead of ones given to
+    /// [`open_with`](ModelInfoStage::open_with).
+    ///
+    /// # Errors
+    /// [`Cancelled`] if progress callback returned `ControlFlow::Break`
+    pub fn read_model_with(self, options: ParseOptions) -> Result<Model, Cancelled> {
+        // broken data panics before `Stop::Failed` because reader is not fallible.
+        let (model, _) = self.read_all(options).map_err(|_| Cancelled)?;
+        Ok(model)
+    }
+
+    /// [`read_model`](Self::read_model) returning broken data as error instead of panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # let mut writer = PMXUtil::writer::Writer::begin_writer(true);
+    /// # writer.set_model_info(&Default::default());
+    /// # let buf = writer.write_to_vec().unwrap();
+    /// let stage = PMXUtil::reader::ModelInfoStage::from_reader(&buf[..buf.len() - 1]).unwrap();
+    /// let err = stage.try_read_model().unwrap_err();
+    /// println!("{}", err);
+    /// ```
+    ///
+    /// # Errors
+    /// * `ReadError::InvalidData` with the section, element and offset of broken data,
+    ///   e.g. truncated file, unknown kinds and invalid values not tolerated by lenient mode.
+    /// * `ReadError::Cancelled` if the progress callback given by
+    ///   [`open_with`](ModelInfoStage::open_with) returned `ControlFlow::Break`.
+    /// * `ReadError::Io` if failed to read data kept by `ParseOptions::preserve_unknown`.
+    pub fn try_read_model(mut self) -> Result<Model, ReadError> {
+        self.0.fallible = true;
+        let options = std::mem::take(&mut self.0.options);
+        match self.read_all(options) {
+            Ok((model, _)) => Ok(model),
+            Err(Stop::Failed(err)) => Err(err),
+            Err(Stop::Cancelled) => Err(ReadError::Cancelled),
+        }
+    }
+
+    /// [`read_model`](Self::read_model) and where each section was in file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # let mut buf = vec![];
+    /// # let mut writer = PMXUtil::writer::Writer::begin_writer(true);
+    /// # writer.set_model_info(&Default::default());
+    /// # writer.write(&mut buf).unwrap();
+    /// let (_, offsets) = PMXUtil::reader::ModelInfoStage::from_reader(&buf[..])
+    ///     .unwrap()
+    ///     .read_model_with_offsets();
+    /// let vertices = offsets.vertices.unwrap();
+    /// println!("vertices at {} ({} bytes)", vertices.start, vertices.len);
+    /// ```
+    pub fn read_model_with_offsets(mut self) -> (Model, SectionOffsets) {
+        let options = std::mem::take(&mut self.0.options);
+        self.read_all(options)
+            .expect("callback of open_with cancelled")
+    }
+
+    fn read_all(mut self, options: ParseOptions) -> Result<(Model, SectionOffsets), Stop> {
+        self.0.reporter = Reporter::new(options.progress);
+        self.0.lenient |= options.lenient;
+        let (model_info, ns) = read_stage!(self);
+        let (vertices, ns) = read_stage!(ns);
+        let (faces, ns) = read_stage!(ns);
+        let (mut model, offsets) =
+            ns.read_rest(model_info, vertices, faces, options.preserve_unknown)?;
+        if options.dedup_textures {
+            model.dedup_textures();
+        }
+        Ok((model, offsets))
+    }
+
+    /// read all sections passing each element to `visitor`. see [`visit`].
+    pub fn visit<V: PmxVisitor>(self, visitor: &mut V) {
+        self.visit_with_offsets(visitor);
+    }
+
+    /// [`visit`](Self::visit) returning broken data as error instead of panic.
+    ///
+    /// elements before broken data are passed to `visitor`.
+    ///
+    /// # Errors
+    /// * `ReadError::InvalidData` with the section, element and offset of broken data.
+    /// * `ReadError::Cancelled` if the progress callback given by
+    ///   [`open_with`](ModelInfoStage::open_with) returned `ControlFlow::Break`.
+    pub fn try_visit<V: PmxVisitor>(mut self, visitor: &mut V) -> Result<(), ReadError> {
+        self.0.fallible = true;
+        let inner = self.visit_all(visitor);
+        if !inner.reporter.is_cancelled() {
+            return Ok(());
+        }
+        match inner.stop() {
+            Stop::Failed(err) => Err(err),
+            Stop::Cancelled => Err(ReadError::Cancelled),
+        }
+    }
+
+    /// [`visit`](Self::visit) returning where each section was in file.
+    pub(crate) fn visit_with_offsets<V: PmxVisitor>(self, visitor: &mut V) -> SectionOffsets {
+        self.visit_all(visitor).offsets
     }
-    pub fn get_header(&self) -> Header {
-        self.0.header.clone()
+
+    fn visit_all<V: PmxVisitor>(self, visitor: &mut V) -> ReaderInner<R> {
+        let header = self.get_header();
+        let version = header.version;
+        visitor.header(header);
+        visitor.section_start(SectionKind::ModelInfo, 1);
+        let (model_info, mut ns) = self.read();
+        visitor.model_info(model_info);
+        ns.visit_list(
+            SectionKind::Vertices,
+            VerticesStage::read_pmx_vertex,
+            visitor,
+            V::vertex,
+        );
+        let mut ns = FacesStage(ns.0);
+        ns.0.begin_section(SectionKind::Faces);
+        let count = ns.0.read_i32() / 3;
+        visitor.section_start(SectionKind::Faces, usize::try_from(count).unwrap_or(0));
+        ns.for_each_counted(SectionKind::Faces, count, FacesStage::read_face, |face| {
+            visitor.face(face)
+        });
+        let mut ns = TexturesStage(ns.0);
+        ns.visit_list(
+            SectionKind::Textures,
+            TexturesStage::read_texture,
+            visitor,
+            V::texture,
+        );
+        let mut ns = MaterialsStage(ns.0);
+        ns.visit_list(
+            SectionKind::Materials,
+            MaterialsStage::read_pmx_material,
+            visitor,
+            V::material,
+        );
+        let mut ns = BonesStage(ns.0);
+        ns.visit_list(
+            SectionKind::Bones,
+            BonesStage::read_pmx_bone,
+            visitor,
+            V::bone,
+        );
+        let mut ns = MorphsStage(ns.0);
+        ns.visit_list(
+            SectionKind::Morphs,
+            MorphsStage::read_pmx_morph,
+            visitor,
+            V::morph,
+        );
+        let mut ns = FrameStage(ns.0);
+        ns.visit_list(
+            SectionKind::Frames,
+            FrameStage::read_frame,
+            visitor,
+            V::frame,
+        );
+        let mut ns = RigidStage(ns.0);
+        ns.visit_list(
+            SectionKind::RigidBodies,
+            RigidStage::read_rigid,
+            visitor,
+            V::rigid,
+        );
+        let mut ns = JointStage(ns.0);
+        ns.visit_list(
+            SectionKind::Joints,
+            JointStage::read_joint,
+            visitor,
+            V::joint,
+        );
+        if version == PMXVersion::V21 && !ns.0.soft_bodies_missing() {
+            let mut ns = SoftBodyStage(ns.0);
+            ns.visit_list(
+                SectionKind::SoftBodies,
+                SoftBodyStage::read_soft_body,
+                visitor,
+                V::soft_body,
+            );
+            ns.0
+        } else {
+            ns.0
+        }
     }
 
     pub fn read(mut self) -> (ModelInfo, VerticesStage<R>) {
-        (
-            ModelInfo {
-                name: self.0.read_text_buf(),
-                name_en: self.0.read_text_buf(),
-                comment: self.0.read_text_buf(),
-                comment_en: self.0.read_text_buf(),
-            },
-            VerticesStage(self.0),
-        )
+        #[cfg(feature = "tracing")]
+        let _span = crate::trace::read_section(SectionKind::ModelInfo, 1);
+        self.0.reporter.report(SectionKind::ModelInfo, 0, 1);
+        self.0.begin_section(SectionKind::ModelInfo);
+        let model_info = ModelInfo {
+            name: self.0.read_text_buf(),
+            name_en: self.0.read_text_buf(),
+            comment: self.0.read_text_buf(),
+            comment_en: self.0.read_text_buf(),
+        };
+        self.0.check();
+        self.0.end_section(SectionKind::ModelInfo);
+        self.0.reporter.report(SectionKind::ModelInfo, 1, 1);
+        (model_info, VerticesStage(self.0))
     }
 }
 
@@ -134,13 +763,83 @@ pub struct VerticesStage<R: Read>(ReaderInner<R>);
 impl<R: Read> VerticesStage<R> {
     pub fn read(mut self) -> (Vec<Vertex>, FacesStage<R>) {
         (
-            (0..self.0.read_i32())
-                .map(|_| self.read_pmx_vertex())
-                .collect(),
+            self.read_list(SectionKind::Vertices, Self::read_pmx_vertex),
             FacesStage(self.0),
         )
     }
 
+    /// [`read`](Self::read) into separate arrays in one pass.
+    pub fn read_soa(mut self) -> (VertexBuffers, FacesStage<R>) {
+        let mut buffers = VertexBuffers::new(self.0.header.additional_uv);
+        self.0.begin_section(SectionKind::Vertices);
+        let count = self.0.read_i32();
+        buffers.reserve(usize::try_from(count).unwrap_or(0));
+        self.for_each_counted(
+            SectionKind::Vertices,
+            count,
+            Self::read_pmx_vertex,
+            |vertex| buffers.push(vertex),
+        );
+        (buffers, FacesStage(self.0))
+    }
+
+    /// [`read`](Self::read) keeping records as bytes, e.g. to copy them by
+    /// [`StreamingWriter::write_raw_vertices`](crate::stream_writer::StreamingWriter::write_raw_vertices).
+    ///
+    /// records are split by their weight types without parsing other fields.
+    /// broken data is reported as [`read`](Self::read) does.
+    pub fn read_raw(mut self) -> (RawSection, FacesStage<R>) {
+        let additional_uv = self.0.header.additional_uv;
+        let bone_index = self.0.header.s_bone_index;
+        let mut raw = RawSection {
+            count: 0,
+            bytes: vec![],
+            record_offsets: vec![],
+            additional_uv,
+            bone_index,
+        };
+        // position, normal, uv, additional uvs and weight type.
+        let head = 33 + 16 * usize::from(additional_uv);
+        let bone = usize::from(u8::from(bone_index));
+        self.0.begin_section(SectionKind::Vertices);
+        let count = self.0.read_i32();
+        self.for_each_counted(
+            SectionKind::Vertices,
+            count,
+            |stage| {
+                let start = raw.bytes.len();
+                stage.0.inner.read_append(head, &mut raw.bytes);
+                let weight_type = match raw.bytes.get(start + head - 1) {
+                    Some(&weight_type) => weight_type,
+                    None => return,
+                };
+                // weights then edge scale.
+                let rest = match weight_type {
+                    0 => bone,
+                    1 => 2 * bone + 4,
+                    2 | 4 => 4 * bone + 16,
+                    3 => 2 * bone + 40,
+                    _ => {
+                        raw.bytes.truncate(start);
+                        stage
+                            .0
+                            .corrupt(format!("unknown weight type {}", weight_type));
+                        return;
+                    }
+                } + 4;
+                stage.0.inner.read_append(rest, &mut raw.bytes);
+                if raw.bytes.len() == start + head + rest {
+                    raw.record_offsets.push(start as u32);
+                } else {
+                    raw.bytes.truncate(start);
+                }
+            },
+            |()| {},
+        );
+        raw.count = raw.record_offsets.len() as u32;
+        (raw, FacesStage(self.0))
+    }
+
     fn read_pmx_vertex(&mut self) -> Vertex {
         let mut ctx = Vertex {
             position: [0.0; 3],
@@ -150,9 +849,10 @@ impl<R: Read> VerticesStage<R> {
             weight_type: VertexWeight::BDEF1(-1),
             edge_mag: 0.0,
         };
-        ctx.position = self.0.read_vec3();
-        ctx.norm = self.0.read_vec3();
-        ctx.uv = self.0.read_vec2();
+        let [x, y, z, nx, ny, nz, u, v] = self.0.read_f32_array();
+        ctx.position = [x, y, z];
+        ctx.norm = [nx, ny, nz];
+        ctx.uv = [u, v];
 
         ctx.add_uv
             .iter_mut()
@@ -183,10 +883,8 @@ impl<R: Read> VerticesStage<R> {
                 let bone_index_2 = self.0.read_bone_index();
                 let bone_index_3 = self.0.read_bone_index();
                 let bone_index_4 = self.0.read_bone_index();
-                let bone_weight_1 = self.0.read_f32();
-                let bone_weight_2 = self.0.read_f32();
-                let bone_weight_3 = self.0.read_f32();
-                let bone_weight_4 = self.0.read_f32();
+                let [bone_weight_1, bone_weight_2, bone_weight_3, bone_weight_4] =
+                    self.0.read_f32_array();
                 if weight_type == 2 {
                     VertexWeight::BDEF4 {
                         bone_index_1,
@@ -214,21 +912,21 @@ impl<R: Read> VerticesStage<R> {
             3 => {
                 let bone_index_1 = self.0.read_bone_index();
                 let bone_index_2 = self.0.read_bone_index();
-                let bone_weight_1 = self.0.read_f32();
-                let sdef_c = self.0.read_vec3();
-                let sdef_r0 = self.0.read_vec3();
-                let sdef_r1 = self.0.read_vec3();
+                let [bone_weight_1, cx, cy, cz, r0x, r0y, r0z, r1x, r1y, r1z] =
+                    self.0.read_f32_array();
                 VertexWeight::SDEF {
                     bone_index_1,
                     bone_index_2,
                     bone_weight_1,
-                    sdef_c,
-                    sdef_r0,
-                    sdef_r1,
+                    sdef_c: [cx, cy, cz],
+                    sdef_r0: [r0x, r0y, r0z],
+                    sdef_r1: [r1x, r1y, r1z],
                 }
             }
             _ => {
-                panic!("Unknown Weight type:{}", weight_type);
+                self.0
+                    .corrupt(format!("unknown weight type {}", weight_type));
+                VertexWeight::BDEF1(-1)
             }
         };
 
@@ -243,34 +941,117 @@ impl<R: Read> FacesStage<R> {
     /// read [Face doc](crate::types::Face)
     pub fn read(mut self) -> (Vec<Face>, TexturesStage<R>) {
         (
-            (0..(self.0.read_i32() / 3))
-                .map(|_| Face {
-                    vertices: [
-                        self.0.read_vertex_index(),
-                        self.0.read_vertex_index(),
-                        self.0.read_vertex_index(),
-                    ],
-                })
-                .collect(),
+            {
+                self.0.begin_section(SectionKind::Faces);
+                let count = self.0.read_i32() / 3;
+                self.read_counted(SectionKind::Faces, count, Self::read_face)
+            },
             TexturesStage(self.0),
         )
     }
+
+    /// [`read`](Self::read) into flat `[v0, v1, v2, v0, v1, v2, ...]` index buffer.
+    /// # Panics
+    /// * if a face has negative vertex index.
+    pub fn read_flat(mut self) -> (Vec<u32>, TexturesStage<R>) {
+        self.0.begin_section(SectionKind::Faces);
+        let count = self.0.read_i32() / 3;
+        let mut indices = Vec::with_capacity(3 * usize::try_from(count).unwrap_or(0));
+        self.for_each_counted(SectionKind::Faces, count, Self::read_flat_face, |face| {
+            indices.extend_from_slice(&face)
+        });
+        (indices, TexturesStage(self.0))
+    }
+
+    fn read_flat_face(&mut self) -> [u32; 3] {
+        [
+            self.0.read_vertex_index(),
+            self.0.read_vertex_index(),
+            self.0.read_vertex_index(),
+        ]
+    }
+
+    fn read_face(&mut self) -> Face {
+        Face {
+            vertices: [
+                self.0.read_vertex_index(),
+                self.0.read_vertex_index(),
+                self.0.read_vertex_index(),
+            ],
+        }
+    }
 }
 
 pub struct TexturesStage<R: Read>(ReaderInner<R>);
 impl<R: Read> TexturesStage<R> {
+    /// read sections from textures to the end and make a model with preceding sections.
+    fn read_rest(
+        self,
+        model_info: ModelInfo,
+        vertices: Vec<Vertex>,
+        faces: Vec<Face>,
+        preserve_unknown: bool,
+    ) -> Result<(Model, SectionOffsets), Stop> {
+        let header = self.get_header();
+        let (textures, ns) = read_stage!(self);
+        let (materials, ns) = read_stage!(ns);
+        let (bones, ns) = read_stage!(ns);
+        let (morphs, ns) = read_stage!(ns);
+        let (frames, ns) = read_stage!(ns);
+        let (rigid_bodies, mut ns) = read_stage!(ns);
+        let joints = ns.read_list(SectionKind::Joints, JointStage::read_joint);
+        check_cancelled!(ns);
+        let (soft_bodies, mut inner) = if header.version == PMXVersion::V21 {
+            let mut ns = SoftBodyStage(ns.0);
+            let soft_bodies = ns.read_soft_bodies();
+            check_cancelled!(ns);
+            (soft_bodies, ns.0)
+        } else {
+            (vec![], ns.0)
+        };
+        let mut unknown = UnknownData::default();
+        if preserve_unknown {
+            unknown.trailing = match inner.inner.read_to_end() {
+                Ok(trailing) => trailing,
+                Err(err) if inner.fallible => return Err(Stop::Failed(ReadError::Io(err))),
+                Err(err) => panic!("failed to read unknown data: {}", err),
+            };
+            unknown.header = Some(header);
+        }
+        let model = Model {
+            version: header.version,
+            encode: header.encode,
+            additional_uv: header.additional_uv,
+            model_info,
+            vertices,
+            faces,
+            textures: TextureList { textures },
+            materials,
+            bones,
+            morphs,
+            frames,
+            rigid_bodies,
+            joints,
+            soft_bodies,
+            unknown,
+        };
+        Ok((model, inner.offsets))
+    }
+
     /// Read relative texture path from current reading file
     ///
     /// # Note
     /// for Unix like system user you need to convert \ to /
     pub fn read(mut self) -> (Vec<String>, MaterialsStage<R>) {
         (
-            (0..self.0.read_i32())
-                .map(|_| self.0.read_text_buf())
-                .collect(),
+            self.read_list(SectionKind::Textures, Self::read_texture),
             MaterialsStage(self.0),
         )
     }
+
+    fn read_texture(&mut self) -> String {
+        self.0.read_text_buf()
+    }
 }
 pub struct MaterialsStage<R: Read>(ReaderInner<R>);
 impl<R: Read> MaterialsStage<R> {
@@ -279,24 +1060,27 @@ impl<R: Read> MaterialsStage<R> {
     /// please read [Material](crate::types::Material) doc
     pub fn read(mut self) -> (Vec<Material>, BonesStage<R>) {
         (
-            (0..self.0.read_i32())
-                .map(|_| self.read_pmx_material())
-                .collect(),
+            self.read_list(SectionKind::Materials, Self::read_pmx_material),
             BonesStage(self.0),
         )
     }
 
     fn read_pmx_material(&mut self) -> Material {
+        let name = self.0.read_text_buf();
+        let english_name = self.0.read_text_buf();
+        let [dr, dg, db, da, sr, sg, sb, specular_factor, ar, ag, ab] = self.0.read_f32_array();
+        let draw_mode = MaterialFlags::from_bits_truncate(self.0.read_u8());
+        let [er, eg, eb, ea, edge_size] = self.0.read_f32_array();
         Material {
-            name: self.0.read_text_buf(),
-            english_name: self.0.read_text_buf(),
-            diffuse: self.0.read_vec4(),
-            specular: self.0.read_vec3(),
-            specular_factor: self.0.read_f32(),
-            ambient: self.0.read_vec3(),
-            draw_mode: MaterialFlags::from_bits_truncate(self.0.read_u8()),
-            edge_color: self.0.read_vec4(),
-            edge_size: self.0.read_f32(),
+            name,
+            english_name,
+            diffuse: [dr, dg, db, da],
+            specular: [sr, sg, sb],
+            specular_factor,
+            ambient: [ar, ag, ab],
+            draw_mode,
+            edge_color: [er, eg, eb, ea],
+            edge_size,
             texture_index: self.0.read_texture_index(),
             sphere_mode: {
                 let ti = self.0.read_texture_index();
@@ -314,16 +1098,24 @@ impl<R: Read> MaterialsStage<R> {
                         kind: SphereModeKind::SubTexture,
                         index: ti,
                     }),
-                    _ => {
-                        panic!("Invalid sphere mode detected in material")
+                    x => {
+                        self.0.corrupt(format!("invalid sphere mode {}", x));
+                        None
                     }
                 }
             },
             toon_mode: match self.0.read_u8() {
                 0 => ToonMode::Separate(self.0.read_texture_index()),
-                1 => ToonMode::Common(self.0.read_u8()),
-                _ => {
-                    panic!("Invalid toon mode detected in material")
+                1 => {
+                    let toon_mode = ToonMode::Common(self.0.read_u8());
+                    if !toon_mode.is_valid_common() {
+                        self.0.warn(ReadWarning::InvalidCommonToon(toon_mode));
+                    }
+                    toon_mode
+                }
+                x => {
+                    self.0.corrupt(format!("invalid toon mode {}", x));
+                    ToonMode::Separate(-1)
                 }
             },
             memo: self.0.read_text_buf(),
@@ -337,9 +1129,7 @@ impl<R: Read> BonesStage<R> {
     /// Exact model pose you should process this parameter
     pub fn read(mut self) -> (Vec<Bone>, MorphsStage<R>) {
         (
-            (0..self.0.read_i32())
-                .map(|_| self.read_pmx_bone())
-                .collect(),
+            self.read_list(SectionKind::Bones, Self::read_pmx_bone),
             MorphsStage(self.0),
         )
     }
@@ -352,11 +1142,14 @@ impl<R: Read> BonesStage<R> {
             deform_depth: self.0.read_i32(),
             ..crate::types::Bone::default()
         };
-        let bone_flags = BoneFlags::from_bits_truncate(self.0.read_u16());
+        let flag_bits = self.0.read_u16();
+        let bone_flags = BoneFlags::from_bits_truncate(flag_bits);
         ctx.controllable_in_viewer = bone_flags.intersects(BoneFlags::ENABLED);
         ctx.display_bone_in_viewer = bone_flags.intersects(BoneFlags::IS_VISIBLE);
         ctx.rotatable_in_viewer = bone_flags.intersects(BoneFlags::ROTATABLE);
         ctx.translatable_in_viewer = bone_flags.intersects(BoneFlags::TRANSLATABLE);
+        ctx.physics_after_deform = bone_flags.intersects(BoneFlags::PHYSICS_AFTER_DEFORM);
+        ctx.reserved_flags = flag_bits & BoneFlags::RESERVED_BITS;
         if bone_flags.intersects(BoneFlags::CONNECT_TO_OTHER_BONE) {
             ctx.connection_display_mode =
                 ConnectionDisplayMode::OtherBone(self.0.read_bone_index());
@@ -394,7 +1187,7 @@ impl<R: Read> BonesStage<R> {
                 ik_target_bone_index: self.0.read_bone_index(),
                 ik_iter_count: self.0.read_i32(),
                 ik_limit_angle: self.0.read_f32(),
-                ik_links: (0..self.0.read_i32()).map(|_| self.read_iklink()).collect(),
+                ik_links: self.read_entries(Self::read_iklink),
             });
         }
         ctx
@@ -402,12 +1195,10 @@ impl<R: Read> BonesStage<R> {
     fn read_iklink(&mut self) -> IKLink {
         IKLink {
             ik_bone_index: self.0.read_bone_index(),
-            angle_limit: match self.0.read_u8() {
-                0 => None,
-                1 => Some((self.0.read_vec3(), self.0.read_vec3())),
-                x => {
-                    panic!("we cant determine angle limit enabled because {}", x)
-                }
+            angle_limit: if self.0.read_bool("IKLink::angle_limit") {
+                Some((self.0.read_vec3(), self.0.read_vec3()))
+            } else {
+                None
             },
         }
     }
@@ -417,9 +1208,7 @@ pub struct MorphsStage<R: Read>(ReaderInner<R>);
 impl<R: Read> MorphsStage<R> {
     pub fn read(mut self) -> (Vec<Morph>, FrameStage<R>) {
         (
-            (0..self.0.read_i32())
-                .map(|_| self.read_pmx_morph())
-                .collect(),
+            self.read_list(SectionKind::Morphs, Self::read_pmx_morph),
             FrameStage(self.0),
         )
     }
@@ -428,73 +1217,32 @@ impl<R: Read> MorphsStage<R> {
         Morph {
             name: self.0.read_text_buf(),
             english_name: self.0.read_text_buf(),
-            control_panel: match self.0.read_u8() {
-                0 => ControlPanel::System,
-                1 => ControlPanel::BottomLeft,
-                2 => ControlPanel::TopLeft,
-                3 => ControlPanel::TopRight,
-                4 => ControlPanel::BottomRight,
-                x => panic!("Detected unknown morph control panel {} ", x),
+            control_panel: {
+                let control_panel = self.0.read_u8();
+                ControlPanel::try_from(control_panel).unwrap_or_else(|_| {
+                    self.0
+                        .corrupt(format!("unknown morph control panel {}", control_panel));
+                    ControlPanel::System
+                })
             },
             morph_data: {
                 let morph_kind = self.0.read_u8();
                 match morph_kind {
-                    0 => MorphKinds::Group(
-                        (0..self.0.read_i32())
-                            .map(|_| self.read_group_morph())
-                            .collect(),
-                    ),
-                    1 => MorphKinds::Vertex(
-                        (0..self.0.read_i32())
-                            .map(|_| self.read_vertex_morph())
-                            .collect(),
-                    ),
-                    2 => MorphKinds::Bone(
-                        (0..self.0.read_i32())
-                            .map(|_| self.read_bone_morph())
-                            .collect(),
-                    ),
-                    3 => MorphKinds::UV(
-                        (0..self.0.read_i32())
-                            .map(|_| self.read_uv_morph())
-                            .collect(),
-                    ),
-                    4 => MorphKinds::UV1(
-                        (0..self.0.read_i32())
-                            .map(|_| self.read_uv_morph())
-                            .collect(),
-                    ),
-                    5 => MorphKinds::UV2(
-                        (0..self.0.read_i32())
-                            .map(|_| self.read_uv_morph())
-                            .collect(),
-                    ),
-                    6 => MorphKinds::UV3(
-                        (0..self.0.read_i32())
-                            .map(|_| self.read_uv_morph())
-                            .collect(),
-                    ),
-                    7 => MorphKinds::UV4(
-                        (0..self.0.read_i32())
-                            .map(|_| self.read_uv_morph())
-                            .collect(),
-                    ),
-                    8 => MorphKinds::Material(
-                        (0..self.0.read_i32())
-                            .map(|_| self.read_material_morph())
-                            .collect(),
-                    ),
-                    9 => MorphKinds::Flip(
-                        (0..self.0.read_i32())
-                            .map(|_| self.read_flip_morph())
-                            .collect(),
-                    ),
-                    10 => MorphKinds::Impulse(
-                        (0..self.0.read_i32())
-                            .map(|_| self.read_impulse_morph())
-                            .collect(),
-                    ),
-                    x => panic!("Unknown morph kind {} detected.", x),
+                    0 => MorphKinds::Group(self.read_entries(Self::read_group_morph)),
+                    1 => MorphKinds::Vertex(self.read_entries(Self::read_vertex_morph)),
+                    2 => MorphKinds::Bone(self.read_entries(Self::read_bone_morph)),
+                    3 => MorphKinds::UV(self.read_entries(Self::read_uv_morph)),
+                    4 => MorphKinds::UV1(self.read_entries(Self::read_uv_morph)),
+                    5 => MorphKinds::UV2(self.read_entries(Self::read_uv_morph)),
+                    6 => MorphKinds::UV3(self.read_entries(Self::read_uv_morph)),
+                    7 => MorphKinds::UV4(self.read_entries(Self::read_uv_morph)),
+                    8 => MorphKinds::Material(self.read_entries(Self::read_material_morph)),
+                    9 => MorphKinds::Flip(self.read_entries(Self::read_flip_morph)),
+                    10 => MorphKinds::Impulse(self.read_entries(Self::read_impulse_morph)),
+                    x => {
+                        self.0.corrupt(format!("unknown morph kind {}", x));
+                        MorphKinds::Group(vec![])
+                    }
                 }
             },
         }
@@ -521,7 +1269,14 @@ impl<R: Read> MorphsStage<R> {
     fn read_material_morph(&mut self) -> MaterialMorph {
         MaterialMorph {
             index: self.0.read_material_index(),
-            formula: self.0.read_u8(),
+            formula: {
+                let formula = self.0.read_u8();
+                MaterialMorphFormula::try_from(formula).unwrap_or_else(|_| {
+                    self.0
+                        .warn(ReadWarning::InvalidMaterialMorphFormula(formula));
+                    MaterialMorphFormula::Unknown(formula)
+                })
+            },
             diffuse: self.0.read_vec4(),
             specular: self.0.read_vec3(),
             specular_factor: self.0.read_f32(),
@@ -548,7 +1303,7 @@ impl<R: Read> MorphsStage<R> {
     fn read_impulse_morph(&mut self) -> ImpulseMorph {
         ImpulseMorph {
             rigid_index: self.0.read_rigid_index(),
-            is_local: self.0.read_bool().unwrap(),
+            is_local: self.0.read_bool("ImpulseMorph::is_local"),
             velocity: self.0.read_vec3(),
             torque: self.0.read_vec3(),
         }
@@ -562,86 +1317,90 @@ impl<R: Read> FrameStage<R> {
     /// * if contains invalid target
     pub fn read(mut self) -> (Vec<Frame>, RigidStage<R>) {
         (
-            (0..self.0.read_i32())
-                .map(|_| Frame {
-                    name: self.0.read_text_buf(),
-                    name_en: self.0.read_text_buf(),
-                    is_special: self.0.read_bool().unwrap(),
-                    inners: (0..self.0.read_i32())
-                        .map(|_| {
-                            let target = self.0.read_u8();
-                            match target {
-                                0 => FrameInner::Bone(self.0.read_bone_index()),
-                                1 => FrameInner::Morph(self.0.read_morph_index()),
-                                x => {
-                                    panic!("Invalid frame target detected {}", x)
-                                }
-                            }
-                        })
-                        .collect(),
-                })
-                .collect(),
+            self.read_list(SectionKind::Frames, Self::read_frame),
             RigidStage(self.0),
         )
     }
+
+    fn read_frame(&mut self) -> Frame {
+        Frame {
+            name: self.0.read_text_buf(),
+            name_en: self.0.read_text_buf(),
+            is_special: self.0.read_bool("Frame::is_special"),
+            inners: self.read_entries(|ns| {
+                let target = ns.0.read_u8();
+                match target {
+                    0 => FrameInner::Bone(ns.0.read_bone_index()),
+                    1 => FrameInner::Morph(ns.0.read_morph_index()),
+                    x => {
+                        ns.0.corrupt(format!("invalid frame target {}", x));
+                        FrameInner::Bone(-1)
+                    }
+                }
+            }),
+        }
+    }
 }
 pub struct RigidStage<R: Read>(ReaderInner<R>);
 impl<R: Read> RigidStage<R> {
     pub fn read(mut self) -> (Vec<Rigid>, JointStage<R>) {
         (
-            (0..self.0.read_i32())
-                .map(|_| {
-                    let name = self.0.read_text_buf();
-                    let name_en = self.0.read_text_buf();
-                    let bone_index = self.0.read_bone_index();
-                    let group = self.0.read_u8();
-                    let un_collision_group_flag = self.0.read_u16();
-                    let form = match self.0.read_u8() {
-                        0 => RigidForm::Sphere,
-                        1 => RigidForm::Box,
-                        2 => RigidForm::Capsule,
-                        _ => {
-                            unreachable!("Invalid  file detected at rigid loader")
-                        }
-                    };
-                    let size = self.0.read_vec3();
-                    let position = self.0.read_vec3();
-                    let rotation = self.0.read_vec3();
-                    let mass = self.0.read_f32();
-                    let move_resist = self.0.read_f32();
-                    let rotation_resist = self.0.read_f32();
-                    let repulsion = self.0.read_f32();
-                    let friction = self.0.read_f32();
-                    let calc_method = match self.0.read_u8() {
-                        0 => RigidCalcMethod::Static,
-                        1 => RigidCalcMethod::Dynamic,
-                        2 => RigidCalcMethod::DynamicWithBonePosition,
-                        _ => {
-                            unreachable!("Invalid  file detected as rigid loader")
-                        }
-                    };
-                    Rigid {
-                        name,
-                        name_en,
-                        bone_index,
-                        group,
-                        un_collision_group_flag,
-                        form,
-                        size,
-                        position,
-                        rotation,
-                        mass,
-                        move_resist,
-                        rotation_resist,
-                        repulsion,
-                        friction,
-                        calc_method,
-                    }
-                })
-                .collect(),
+            self.read_list(SectionKind::RigidBodies, Self::read_rigid),
             JointStage(self.0),
         )
     }
+
+    fn read_rigid(&mut self) -> Rigid {
+        let name = self.0.read_text_buf();
+        let name_en = self.0.read_text_buf();
+        let bone_index = self.0.read_bone_index();
+        let group = self.0.read_u8();
+        let un_collision_group_flag = RigidGroups::from(self.0.read_u16());
+        let form = match self.0.read_u8() {
+            0 => RigidForm::Sphere,
+            1 => RigidForm::Box,
+            2 => RigidForm::Capsule,
+            x => {
+                self.0.corrupt(format!("invalid rigid body shape {}", x));
+                RigidForm::Sphere
+            }
+        };
+        let size = self.0.read_vec3();
+        let position = self.0.read_vec3();
+        let rotation = self.0.read_vec3();
+        let mass = self.0.read_f32();
+        let move_resist = self.0.read_f32();
+        let rotation_resist = self.0.read_f32();
+        let repulsion = self.0.read_f32();
+        let friction = self.0.read_f32();
+        let calc_method = match self.0.read_u8() {
+            0 => RigidCalcMethod::Static,
+            1 => RigidCalcMethod::Dynamic,
+            2 => RigidCalcMethod::DynamicWithBonePosition,
+            x => {
+                self.0
+                    .corrupt(format!("invalid rigid body calc method {}", x));
+                RigidCalcMethod::Static
+            }
+        };
+        Rigid {
+            name,
+            name_en,
+            bone_index,
+            group,
+            un_collision_group_flag,
+            form,
+            size,
+            position,
+            rotation,
+            mass,
+            move_resist,
+            rotation_resist,
+            repulsion,
+            friction,
+            calc_method,
+        }
+    }
 }
 
 pub struct JointStage<R: Read>(ReaderInner<R>);
@@ -649,7 +1408,7 @@ pub struct JointStage<R: Read>(ReaderInner<R>);
 impl<R: Read> JointStage<R> {
     pub fn read(mut self) -> (Vec<Joint>, Option<SoftBodyStage<R>>) {
         (
-            (0..self.0.read_i32()).map(|_| self.read_joint()).collect(),
+            self.read_list(SectionKind::Joints, Self::read_joint),
             if let crate::types::PMXVersion::V21 = self.0.header.version {
                 //this file contains softbody section
                 Some(SoftBodyStage(self.0))
@@ -658,6 +1417,17 @@ impl<R: Read> JointStage<R> {
             },
         )
     }
+    /// [`read`](Self::read) giving [`EndStage`] for PMX 2.0 files.
+    pub fn read_with_end(mut self) -> (Vec<Joint>, JointNextStage<R>) {
+        let joints = self.read_list(SectionKind::Joints, Self::read_joint);
+        let next = if let crate::types::PMXVersion::V21 = self.0.header.version {
+            JointNextStage::SoftBodies(SoftBodyStage(self.0))
+        } else {
+            JointNextStage::End(EndStage(self.0))
+        };
+        (joints, next)
+    }
+
     fn read_joint(&mut self) -> Joint {
         let name = self.0.read_text_buf();
         let name_en = self.0.read_text_buf();
@@ -677,7 +1447,22 @@ impl<R: Read> JointStage<R> {
             }
         };
 
-        let joint_parameter = translate_joint(&raw_parameter);
+        let joint_parameter = translate_joint(&raw_parameter).unwrap_or_else(|| {
+            self.0
+                .corrupt(format!("unknown joint type {}", raw_parameter.joint_type));
+            JointType::Spring6DOF {
+                a_rigid_index: -1,
+                b_rigid_index: -1,
+                position: [0.0; 3],
+                rotation: [0.0; 3],
+                move_limit_down: [0.0; 3],
+                move_limit_up: [0.0; 3],
+                rotation_limit_down: [0.0; 3],
+                rotation_limit_up: [0.0; 3],
+                spring_const_move: [0.0; 3],
+                spring_const_rotation: [0.0; 3],
+            }
+        });
         Joint {
             name,
             name_en,
@@ -685,12 +1470,12 @@ impl<R: Read> JointStage<R> {
         }
     }
 }
-fn translate_joint(raw_parameter: &JointParameterRaw) -> JointType {
+fn translate_joint(raw_parameter: &JointParameterRaw) -> Option<JointType> {
     fn is_eq_f32(lhs: f32, rhs: f32) -> bool {
         (lhs - rhs).abs() < 0.01
     }
     match raw_parameter.joint_type {
-        0 => JointType::Spring6DOF {
+        0 => Some(JointType::Spring6DOF {
             a_rigid_index: raw_parameter.a_rigid_index,
             b_rigid_index: raw_parameter.b_rigid_index,
             position: raw_parameter.position,
@@ -701,8 +1486,8 @@ fn translate_joint(raw_parameter: &JointParameterRaw) -> JointType {
             rotation_limit_up: raw_parameter.rotation_limit_up,
             spring_const_move: raw_parameter.spring_const_move,
             spring_const_rotation: raw_parameter.spring_const_rotation,
-        },
-        1 => JointType::SixDof {
+        }),
+        1 => Some(JointType::SixDof {
             a_rigid_index: raw_parameter.a_rigid_index,
             b_rigid_index: raw_parameter.b_rigid_index,
             position: raw_parameter.position,
@@ -711,16 +1496,18 @@ fn translate_joint(raw_parameter: &JointParameterRaw) -> JointType {
             move_limit_up: raw_parameter.move_limit_up,
             rotation_limit_down: raw_parameter.rotation_limit_down,
             rotation_limit_up: raw_parameter.rotation_limit_up,
-        },
-        2 => JointType::P2P {
+        }),
+        2 => Some(JointType::P2P {
             a_rigid_index: raw_parameter.a_rigid_index,
             b_rigid_index: raw_parameter.b_rigid_index,
             position: raw_parameter.position,
             rotation: raw_parameter.rotation,
-        },
-        3 => JointType::ConeTwist {
+        }),
+        3 => Some(JointType::ConeTwist {
             a_rigid_index: raw_parameter.a_rigid_index,
             b_rigid_index: raw_parameter.b_rigid_index,
+            position: raw_parameter.position,
+            rotation: raw_parameter.rotation,
             swing_span1: raw_parameter.rotation_limit_down[2],
             swing_span2: raw_parameter.rotation_limit_down[1],
             twist_span: raw_parameter.rotation_limit_down[0],
@@ -732,10 +1519,12 @@ fn translate_joint(raw_parameter: &JointParameterRaw) -> JointType {
             enable_motor: is_eq_f32(raw_parameter.move_limit_down[2], 1.0),
             max_motor_impulse: raw_parameter.move_limit_up[2],
             motor_target_in_constraint_space: raw_parameter.spring_const_rotation,
-        },
-        4 => JointType::Slider {
+        }),
+        4 => Some(JointType::Slider {
             a_rigid_index: raw_parameter.a_rigid_index,
             b_rigid_index: raw_parameter.b_rigid_index,
+            position: raw_parameter.position,
+            rotation: raw_parameter.rotation,
             lower_linear_limit: raw_parameter.move_limit_down[0],
             upper_linear_limit: raw_parameter.move_limit_up[0],
             lower_angle_limit: raw_parameter.rotation_limit_down[0],
@@ -746,31 +1535,42 @@ fn translate_joint(raw_parameter: &JointParameterRaw) -> JointType {
             power_angler_motor: is_eq_f32(raw_parameter.spring_const_rotation[0], 1.0),
             target_angler_motor_velocity: raw_parameter.spring_const_rotation[1],
             max_angler_motor_force: raw_parameter.spring_const_rotation[2],
-        },
-        5 => JointType::Hinge {
+        }),
+        5 => Some(JointType::Hinge {
             a_rigid_index: raw_parameter.a_rigid_index,
             b_rigid_index: raw_parameter.b_rigid_index,
-            low: raw_parameter.move_limit_down[0],
-            high: raw_parameter.move_limit_up[0],
+            position: raw_parameter.position,
+            rotation: raw_parameter.rotation,
+            low: raw_parameter.rotation_limit_down[0],
+            high: raw_parameter.rotation_limit_up[0],
             softness: raw_parameter.spring_const_move[0],
             bias_factor: raw_parameter.spring_const_move[1],
             relaxation_factor: raw_parameter.spring_const_move[2],
             enable_motor: is_eq_f32(raw_parameter.spring_const_rotation[0], 1.0),
             target_velocity: raw_parameter.spring_const_rotation[1],
             max_motor_impulse: raw_parameter.spring_const_rotation[2],
-        },
-        _ => {
-            unreachable!("Invalid joint type detected in joint loader")
-        }
+        }),
+        _ => None,
     }
 }
 pub struct SoftBodyStage<R: Read>(ReaderInner<R>);
 
 impl<R: Read> SoftBodyStage<R> {
+    /// empty if the file ends without the section. see [`ReadWarning::MissingSoftBodies`].
     pub fn read(mut self) -> Vec<SoftBody> {
-        (0..self.0.read_i32())
-            .map(|_| self.read_soft_body())
-            .collect()
+        self.read_soft_bodies()
+    }
+
+    /// [`read`](Self::read) giving [`EndStage`].
+    pub fn read_with_end(mut self) -> (Vec<SoftBody>, EndStage<R>) {
+        (self.read_soft_bodies(), EndStage(self.0))
+    }
+
+    fn read_soft_bodies(&mut self) -> Vec<SoftBody> {
+        if self.0.soft_bodies_missing() {
+            return vec![];
+        }
+        self.read_list(SectionKind::SoftBodies, Self::read_soft_body)
     }
     fn read_soft_body(&mut self) -> SoftBody {
         SoftBody {
@@ -779,14 +1579,15 @@ impl<R: Read> SoftBodyStage<R> {
             form: match self.0.read_u8() {
                 0 => SoftBodyForm::TriMesh,
                 1 => SoftBodyForm::Rope,
-                _ => {
-                    panic!("Error invalid SoftBodyForm ")
+                x => {
+                    self.0.corrupt(format!("invalid soft body form {}", x));
+                    SoftBodyForm::TriMesh
                 }
             },
             material_index: self.0.read_material_index(),
             group: self.0.read_u8(),
-            un_collision_group_flag: self.0.read_u16(),
-            bit_flag: self.0.read_u8(),
+            un_collision_group_flag: RigidGroups::from(self.0.read_u16()),
+            bit_flag: SoftBodyFlags::from_bits_retain(self.0.read_u8()),
             b_link_create_distance: self.0.read_i32(),
             clusters: self.0.read_i32(),
             mass: self.0.read_f32(),
@@ -797,8 +1598,10 @@ impl<R: Read> SoftBodyStage<R> {
                 2 => SoftBodyAeroModel::VOneSided,
                 3 => SoftBodyAeroModel::FTwoSided,
                 4 => SoftBodyAeroModel::FOneSided,
-                _ => {
-                    panic!("Error invalid SoftBodyAeroModel")
+                x => {
+                    self.0
+                        .corrupt(format!("invalid soft body aero model {}", x));
+                    SoftBodyAeroModel::VPoint
                 }
             },
             //config
@@ -830,42 +1633,562 @@ impl<R: Read> SoftBodyStage<R> {
             lst: self.0.read_f32(),
             ast: self.0.read_f32(),
             vst: self.0.read_f32(),
-            anchor_rigid: (0..self.0.read_i32())
-                .map(|_| SoftBodyAnchorRigid {
-                    rigid_index: self.0.read_rigid_index(),
-                    vertex_index: self.0.read_vertex_index(),
-                    near_mode: match self.0.read_i8() {
-                        0 => true,
-                        1 => false,
-                        x => {
-                            panic!("invalid near mode {}", x)
-                        }
-                    },
-                })
-                .collect(),
-            pin_vertex: (0..self.0.read_i32())
-                .map(|_| self.0.read_vertex_index())
-                .collect(),
+            anchor_rigid: self.read_entries(|ns| SoftBodyAnchorRigid {
+                rigid_index: ns.0.read_rigid_index(),
+                vertex_index: ns.0.read_signed_vertex_index(),
+                near_mode: match ns.0.read_u8() {
+                    0 => false,
+                    1 => true,
+                    value => {
+                        ns.0.warn(ReadWarning::InvalidNearMode(value));
+                        false
+                    }
+                },
+            }),
+            pin_vertex: self.read_entries(|ns| ns.0.read_signed_vertex_index()),
         }
     }
 }
-struct ReaderInner<R: Read> {
-    inner: BinaryReader<R>,
-    header: Header,
+
+/// stage after joints. soft bodies follow only in PMX 2.1.
+pub enum JointNextStage<R: Read> {
+    SoftBodies(SoftBodyStage<R>),
+    End(EndStage<R>),
 }
 
-impl<R: Read> ReaderInner<R> {
-    pub fn read_vertex_index(&mut self) -> i32 {
-        self.inner.read_vertex_index(self.header.s_vertex_index)
-    }
+/// after the last section.
+pub struct EndStage<R: Read>(ReaderInner<R>);
 
-    pub fn read_texture_index(&mut self) -> i32 {
-        self.inner.read_sized(self.header.s_texture_index)
+impl<R: Read> EndStage<R> {
+    /// check nothing is left after the last section.
+    ///
+    /// in lenient mode trailing bytes are returned in [`TailInfo::raw`] instead.
+    /// [`Writer::set_trailing_data`](crate::writer::Writer::set_trailing_data) writes them back.
+    ///
+    /// # Errors
+    /// * `ReadError::TrailingBytes` if bytes are left and reader is not lenient.
+    /// * `ReadError::Io` if failed to read the rest.
+    pub fn finish(mut self) -> Result<TailInfo, ReadError> {
+        let position = self.0.position();
+        let rest = self.0.inner.read_to_end().map_err(ReadError::Io)?;
+        if rest.is_empty() {
+            Ok(TailInfo::default())
+        } else if self.0.lenient {
+            Ok(TailInfo {
+                trailing_bytes: rest.len(),
+                raw: Some(rest),
+            })
+        } else {
+            Err(ReadError::TrailingBytes {
+                position,
+                len: rest.len(),
+            })
+        }
     }
+}
 
-    pub fn read_material_index(&mut self) -> i32 {
-        self.inner.read_sized(self.header.s_material_index)
-    }
+/// why reading all sections stopped early.
+#[derive(Debug)]
+enum Stop {
+    Cancelled,
+    Failed(ReadError),
+}
+
+/// what was after the last section. see [`EndStage::finish`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TailInfo {
+    pub trailing_bytes: usize,
+    /// trailing bytes captured in lenient mode.
+    pub raw: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ReadError {
+    /// `len` bytes are left at `position` after the last section.
+    TrailingBytes {
+        position: u64,
+        len: usize,
+    },
+    Io(std::io::Error),
+    /// progress callback returned `ControlFlow::Break`.
+    Cancelled,
+    /// element `element_index` of `section` is broken or truncated.
+    ///
+    /// `byte_offset` is where the problem was found, i.e. the start of a truncated value or
+    /// just after an invalid one. `recent_bytes` are up to 16 bytes read before it.
+    InvalidData {
+        section: SectionKind,
+        element_index: usize,
+        byte_offset: u64,
+        recent_bytes: Vec<u8>,
+        reason: String,
+    },
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::TrailingBytes { position, len } => {
+                write!(
+                    f,
+                    "{} bytes are left at {} after the last section",
+                    len, position
+                )
+            }
+            ReadError::Io(err) => write!(f, "failed to read: {}", err),
+            ReadError::Cancelled => Cancelled.fmt(f),
+            ReadError::InvalidData {
+                section,
+                element_index,
+                byte_offset,
+                recent_bytes,
+                reason,
+            } => {
+                write!(
+                    f,
+                    "{}[{}] at offset {:#X}: {}",
+                    section.name(),
+                    element_index,
+                    byte_offset,
+                    reason
+                )?;
+                if !recent_bytes.is_empty() {
+                    write!(f, " (last bytes:")?;
+                    for byte in recent_bytes {
+                        write!(f, " {:02X}", byte)?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+struct ReaderInner<R: Read> {
+    inner: BinaryReader<R>,
+    header: Header,
+    lenient: bool,
+    warnings: Vec<ReadWarning>,
+    reporter: Reporter,
+    offsets: SectionOffsets,
+    /// section and index of the element being read.
+    section: SectionKind,
+    element: usize,
+    /// return broken data as `error` instead of panic.
+    fallible: bool,
+    error: Option<ReadError>,
+    /// given by `open_with` for whole model reads.
+    options: ParseOptions,
+}
+
+/// byte range of a section in file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub struct SectionRange {
+    /// offset from the start of file.
+    pub start: u64,
+    /// length in bytes including element count.
+    pub len: u64,
+}
+
+/// where each section is placed in file.
+///
+/// `None` if the section is not read yet or not in file (soft bodies in PMX 2.0).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub struct SectionOffsets {
+    pub model_info: Option<SectionRange>,
+    pub vertices: Option<SectionRange>,
+    pub faces: Option<SectionRange>,
+    pub textures: Option<SectionRange>,
+    pub materials: Option<SectionRange>,
+    pub bones: Option<SectionRange>,
+    pub morphs: Option<SectionRange>,
+    pub frames: Option<SectionRange>,
+    pub rigid_bodies: Option<SectionRange>,
+    pub joints: Option<SectionRange>,
+    pub soft_bodies: Option<SectionRange>,
+}
+
+impl SectionOffsets {
+    pub fn get(&self, section: SectionKind) -> Option<SectionRange> {
+        match section {
+            SectionKind::ModelInfo => self.model_info,
+            SectionKind::Vertices => self.vertices,
+            SectionKind::Faces => self.faces,
+            SectionKind::Textures => self.textures,
+            SectionKind::Materials => self.materials,
+            SectionKind::Bones => self.bones,
+            SectionKind::Morphs => self.morphs,
+            SectionKind::Frames => self.frames,
+            SectionKind::RigidBodies => self.rigid_bodies,
+            SectionKind::Joints => self.joints,
+            SectionKind::SoftBodies => self.soft_bodies,
+        }
+    }
+
+    fn get_mut(&mut self, section: SectionKind) -> &mut Option<SectionRange> {
+        match section {
+            SectionKind::ModelInfo => &mut self.model_info,
+            SectionKind::Vertices => &mut self.vertices,
+            SectionKind::Faces => &mut self.faces,
+            SectionKind::Textures => &mut self.textures,
+            SectionKind::Materials => &mut self.materials,
+            SectionKind::Bones => &mut self.bones,
+            SectionKind::Morphs => &mut self.morphs,
+            SectionKind::Frames => &mut self.frames,
+            SectionKind::RigidBodies => &mut self.rigid_bodies,
+            SectionKind::Joints => &mut self.joints,
+            SectionKind::SoftBodies => &mut self.soft_bodies,
+        }
+    }
+}
+
+/// Recoverable problems found in lenient mode.
+///
+/// In strict mode (default) reader panics instead,
+/// or [`ModelInfoStage::try_read_model`] returns `ReadError::InvalidData`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ReadWarning {
+    /// boolean byte was neither 0 nor 1. it was read as `true` like `MMD` does.
+    ///
+    /// `field` is like `Frame::is_special`. strict reader panics with it and the position.
+    InvalidBool { field: &'static str, value: u8 },
+    /// material morph formula was neither 0 nor 1. it was kept as
+    /// [`MaterialMorphFormula::Unknown`].
+    InvalidMaterialMorphFormula(u8),
+    /// common toon index was not in 0 ~ 9. it was kept as is.
+    InvalidCommonToon(ToonMode),
+    /// near mode of soft body anchor was neither 0 (off) nor 1 (on). it was read as off.
+    InvalidNearMode(u8),
+    /// PMX 2.1 file ended right after joints. soft bodies were read as empty.
+    ///
+    /// some exporters write 2.1 or a version float like `2.0999999` without the section.
+    /// reported in strict mode too because nothing is broken.
+    /// see [`Header::raw_version`] for the version in file.
+    MissingSoftBodies,
+}
+
+/// common part of all stages.
+trait StageInner<R: Read>: Sized {
+    fn inner(&mut self) -> &mut ReaderInner<R>;
+
+    /// read count then elements of `section` reporting progress.
+    ///
+    /// stops reading if cancelled.
+    fn read_list<T>(&mut self, section: SectionKind, f: impl FnMut(&mut Self) -> T) -> Vec<T> {
+        self.inner().begin_section(section);
+        let count = self.inner().read_i32();
+        self.read_counted(section, count, f)
+    }
+
+    fn read_counted<T>(
+        &mut self,
+        section: SectionKind,
+        count: i32,
+        f: impl FnMut(&mut Self) -> T,
+    ) -> Vec<T> {
+        let mut list = vec![];
+        self.for_each_counted(section, count, f, |element| list.push(element));
+        list
+    }
+
+    /// [`read_list`](Self::read_list) passing each element to `visitor` instead of collecting.
+    fn visit_list<T, V: PmxVisitor>(
+        &mut self,
+        section: SectionKind,
+        f: impl FnMut(&mut Self) -> T,
+        visitor: &mut V,
+        mut callback: impl FnMut(&mut V, T),
+    ) {
+        self.inner().begin_section(section);
+        let count = self.inner().read_i32();
+        visitor.section_start(section, usize::try_from(count).unwrap_or(0));
+        self.for_each_counted(section, count, f, |element| callback(visitor, element));
+    }
+
+    /// count then entries of an element. stops at broken data.
+    fn read_entries<T>(&mut self, mut f: impl FnMut(&mut Self) -> T) -> Vec<T> {
+        let count = self.inner().read_i32();
+        let mut entries = vec![];
+        for _ in 0..count {
+            if self.inner().inner.failure().is_some() {
+                break;
+            }
+            entries.push(f(self));
+        }
+        entries
+    }
+
+    fn for_each_counted<T>(
+        &mut self,
+        section: SectionKind,
+        count: i32,
+        mut f: impl FnMut(&mut Self) -> T,
+        mut sink: impl FnMut(T),
+    ) {
+        let total = usize::try_from(count).unwrap_or(0);
+        #[cfg(feature = "tracing")]
+        let _span = crate::trace::read_section(section, total);
+        for done in 0..total {
+            if !self.inner().reporter.report(section, done, total) {
+                return;
+            }
+            self.inner().element = done;
+            let element = f(self);
+            if !self.inner().check() {
+                return;
+            }
+            sink(element);
+        }
+        // count itself may be broken.
+        if !self.inner().check() {
+            return;
+        }
+        self.inner().end_section(section);
+        self.inner().reporter.report(section, total, total);
+    }
+}
+
+/// a stage reading one section, to write helpers generic over stages.
+///
+/// each stage can only move forward, so [`Next`](Stage::Next) is the only way to the
+/// following sections. concrete methods like [`VerticesStage::read_soa`] stay available.
+/// [`EndStage`] reads no section and doesn't implement this.
+///
+/// ```
+/// use PMXUtil::reader::{ModelInfoStage, Stage};
+/// use PMXUtil::progress::SectionKind;
+/// # let mut writer = PMXUtil::writer::Writer::begin_writer(true);
+/// # writer.set_model_info(&Default::default());
+/// # let bytes = writer.write_to_vec().unwrap();
+/// /// skip a section telling which one was skipped.
+/// fn skip_logged<S: Stage>(stage: S, skipped: &mut Vec<SectionKind>) -> S::Next {
+///     skipped.push(S::KIND);
+///     stage.skip()
+/// }
+///
+/// let mut skipped = vec![];
+/// let stage = ModelInfoStage::from_reader(&bytes[..]).unwrap();
+/// let stage = skip_logged(stage, &mut skipped);
+/// let stage = skip_logged(stage, &mut skipped);
+/// let (faces, _) = stage.read();
+/// assert!(faces.is_empty());
+/// assert_eq!(skipped, [SectionKind::ModelInfo, SectionKind::Vertices]);
+/// ```
+pub trait Stage: Sized {
+    /// section read by this stage.
+    const KIND: SectionKind;
+    /// what [`read`](Stage::read) returns.
+    type Output;
+    /// stage of the next section.
+    type Next;
+
+    /// header read from the file.
+    fn header(&self) -> &Header;
+
+    /// read the section and move to the next stage.
+    fn read(self) -> (Self::Output, Self::Next);
+
+    /// read the section and drop its elements.
+    ///
+    /// elements are still parsed, so offsets, warnings and errors are the same as
+    /// [`read`](Stage::read).
+    fn skip(self) -> Self::Next {
+        self.read().1
+    }
+}
+
+macro_rules! impl_read_stage {
+    ($($stage:ident: $kind:ident, $output:ty, $next:ident, $read:ident;)*) => {
+        $(
+            impl<R: Read> Stage for $stage<R> {
+                const KIND: SectionKind = SectionKind::$kind;
+                type Output = $output;
+                type Next = $next<R>;
+                fn header(&self) -> &Header {
+                    &self.0.header
+                }
+                fn read(self) -> (Self::Output, Self::Next) {
+                    $stage::$read(self)
+                }
+            }
+        )*
+    };
+}
+impl_read_stage!(
+    ModelInfoStage: ModelInfo, ModelInfo, VerticesStage, read;
+    VerticesStage: Vertices, Vec<Vertex>, FacesStage, read;
+    FacesStage: Faces, Vec<Face>, TexturesStage, read;
+    TexturesStage: Textures, Vec<String>, MaterialsStage, read;
+    MaterialsStage: Materials, Vec<Material>, BonesStage, read;
+    BonesStage: Bones, Vec<Bone>, MorphsStage, read;
+    MorphsStage: Morphs, Vec<Morph>, FrameStage, read;
+    FrameStage: Frames, Vec<Frame>, RigidStage, read;
+    RigidStage: RigidBodies, Vec<Rigid>, JointStage, read;
+    JointStage: Joints, Vec<Joint>, JointNextStage, read_with_end;
+    SoftBodyStage: SoftBodies, Vec<SoftBody>, EndStage, read_with_end;
+);
+
+macro_rules! impl_stage {
+    ($($stage:ident),*) => {
+        $(
+            impl<R: Read> $stage<R> {
+                /// warnings collected in lenient mode so far,
+                /// and [`ReadWarning::MissingSoftBodies`] in any mode.
+                pub fn warnings(&self) -> &[ReadWarning] {
+                    &self.0.warnings
+                }
+                /// header read from the file.
+                pub fn get_header(&self) -> Header {
+                    self.0.header
+                }
+                /// text encoding of the file. cheaper than [`get_header`](Self::get_header).
+                pub fn encode(&self) -> Encode {
+                    self.0.header.encode
+                }
+                /// number of additional uvs per vertex.
+                pub fn additional_uv(&self) -> u8 {
+                    self.0.header.additional_uv
+                }
+                pub fn version(&self) -> PMXVersion {
+                    self.0.header.version
+                }
+                /// offsets of sections read so far.
+                pub fn get_section_offsets(&self) -> SectionOffsets {
+                    self.0.offsets
+                }
+            }
+            impl<R: Read> StageInner<R> for $stage<R> {
+                fn inner(&mut self) -> &mut ReaderInner<R> {
+                    &mut self.0
+                }
+            }
+        )*
+    };
+}
+impl_stage!(
+    ModelInfoStage,
+    VerticesStage,
+    FacesStage,
+    TexturesStage,
+    MaterialsStage,
+    BonesStage,
+    MorphsStage,
+    FrameStage,
+    RigidStage,
+    JointStage,
+    SoftBodyStage,
+    EndStage
+);
+
+impl<R: Read> ReaderInner<R> {
+    fn new(inner: BinaryReader<R>, header: Header) -> Self {
+        Self {
+            inner,
+            header,
+            lenient: false,
+            warnings: vec![],
+            reporter: Reporter::default(),
+            offsets: SectionOffsets::default(),
+            section: SectionKind::ModelInfo,
+            element: 0,
+            fallible: false,
+            error: None,
+            options: ParseOptions::default(),
+        }
+    }
+
+    /// bytes consumed from the start of file.
+    fn position(&self) -> u64 {
+        self.inner.position()
+    }
+
+    /// stop reading because data at the current position is broken.
+    ///
+    /// # Panics
+    /// * if reader is not fallible.
+    fn corrupt(&mut self, reason: String) {
+        self.inner.fail(reason);
+        self.check();
+    }
+
+    /// error recorded by [`check`](Self::check) or cancellation.
+    fn stop(self) -> Stop {
+        self.error.map_or(Stop::Cancelled, Stop::Failed)
+    }
+
+    /// `false` if reading failed and stages need to stop.
+    ///
+    /// # Panics
+    /// * if reading failed and reader is not fallible.
+    fn check(&mut self) -> bool {
+        let failure = match self.inner.failure() {
+            None => return true,
+            Some(failure) => failure,
+        };
+        if self.error.is_none() {
+            let error = ReadError::InvalidData {
+                section: self.section,
+                element_index: self.element,
+                byte_offset: failure.position,
+                recent_bytes: self.inner.recent().to_vec(),
+                reason: failure.reason.clone(),
+            };
+            if !self.fallible {
+                panic!("{}", error);
+            }
+            self.error = Some(error);
+            self.reporter.cancel();
+        }
+        false
+    }
+
+    fn begin_section(&mut self, section: SectionKind) {
+        self.section = section;
+        self.element = 0;
+        *self.offsets.get_mut(section) = Some(SectionRange {
+            start: self.position(),
+            len: 0,
+        });
+    }
+
+    fn end_section(&mut self, section: SectionKind) {
+        let position = self.position();
+        if let Some(range) = self.offsets.get_mut(section) {
+            range.len = position - range.start;
+        }
+    }
+
+    /// vertex index of faces and morphs. broken data if negative.
+    pub fn read_vertex_index(&mut self) -> u32 {
+        let index = self.read_signed_vertex_index();
+        u32::try_from(index).unwrap_or_else(|_| {
+            self.corrupt(format!("negative vertex index {}", index));
+            0
+        })
+    }
+
+    /// vertex index of soft bodies.
+    pub fn read_signed_vertex_index(&mut self) -> i32 {
+        self.inner.read_vertex_index(self.header.s_vertex_index)
+    }
+
+    pub fn read_texture_index(&mut self) -> i32 {
+        self.inner.read_sized(self.header.s_texture_index)
+    }
+
+    pub fn read_material_index(&mut self) -> i32 {
+        self.inner.read_sized(self.header.s_material_index)
+    }
 
     pub fn read_bone_index(&mut self) -> i32 {
         self.inner.read_sized(self.header.s_bone_index)
@@ -887,10 +2210,6 @@ impl<R: Read> ReaderInner<R> {
         self.inner.read_u16()
     }
 
-    pub fn read_i8(&mut self) -> i8 {
-        self.inner.read_i8()
-    }
-
     pub fn read_i32(&mut self) -> i32 {
         self.inner.read_i32()
     }
@@ -903,18 +2222,1473 @@ impl<R: Read> ReaderInner<R> {
         self.inner.read_vec3()
     }
 
-    pub fn read_vec2(&mut self) -> [f32; 2] {
-        self.inner.read_vec2()
-    }
-
     pub fn read_f32(&mut self) -> f32 {
         self.inner.read_f32()
     }
 
+    pub fn read_f32_array<const N: usize>(&mut self) -> [f32; N] {
+        self.inner.read_f32_array()
+    }
+
     pub fn read_text_buf(&mut self) -> String {
         self.inner.read_text_buf(self.header.encode)
     }
-    pub fn read_bool(&mut self) -> Option<bool> {
-        self.inner.read_bool()
+    /// read boolean byte. see [`ReadWarning::InvalidBool`].
+    pub fn read_bool(&mut self, field: &'static str) -> bool {
+        match self.inner.read_bool() {
+            Ok(value) => value,
+            Err(value) => {
+                self.warn(ReadWarning::InvalidBool { field, value });
+                true
+            }
+        }
+    }
+    /// `true` if a PMX 2.1 file ends before soft bodies. warned even if not lenient.
+    fn soft_bodies_missing(&mut self) -> bool {
+        if !self.inner.at_end() {
+            return false;
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            position = self.position(),
+            "PMX 2.1 file without soft bodies"
+        );
+        self.warnings.push(ReadWarning::MissingSoftBodies);
+        true
+    }
+
+    /// record warning in lenient mode. it is broken data otherwise.
+    /// # Panics
+    /// * if reader is neither lenient nor fallible.
+    fn warn(&mut self, warning: ReadWarning) {
+        if self.lenient {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(position = self.position(), ?warning, "lenient read warning");
+            self.warnings.push(warning);
+        } else {
+            self.corrupt(format!("{:?}", warning));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+    use std::convert::TryFrom;
+
+    use crate::progress::SectionKind;
+    use crate::reader::{
+        read_metadata, read_textures_borrowed, visit, JointNextStage, MetadataError,
+        ModelInfoStage, ParseOptions, PmxVisitor, ReadError, ReadWarning, SectionOffsets,
+        SectionRange, TailInfo, METADATA_MAX_BYTES,
+    };
+    use crate::types::{
+        Bone, ControlPanel, Encode, Face, Frame, FrameInner, Header, HeaderConversionError,
+        ImpulseMorph, IndexKinds, IndexKindsConfig, Joint, Material, MaterialFlags, MaterialMorph,
+        MaterialMorphFormula, ModelInfo, Morph, MorphKinds, PMXVersion, Rigid, RigidCalcMethod,
+        RigidForm, RigidGroups, SoftBody, SoftBodyAeroModel, SoftBodyAnchorRigid, SoftBodyFlags,
+        SoftBodyForm, ToonMode, Vertex, VertexBuffers, VertexIndexKinds, VertexWeight,
+    };
+    use crate::writer::{WritePMXErrors, Writer};
+
+    fn writer() -> Writer {
+        let mut writer = Writer::begin_writer(true);
+        writer.set_model_info(&ModelInfo::default());
+        writer
+    }
+
+    fn write(writer: Writer) -> Vec<u8> {
+        let mut buf = vec![];
+        writer.write(&mut buf).unwrap();
+        buf
+    }
+
+    fn rigid(group: u8, un_collision_group_flag: RigidGroups) -> Rigid {
+        Rigid {
+            name: "剛体".to_owned(),
+            name_en: "rigid".to_owned(),
+            bone_index: -1,
+            group,
+            un_collision_group_flag,
+            form: RigidForm::Box,
+            size: [1.0, 2.0, 3.0],
+            position: [0.0; 3],
+            rotation: [0.0; 3],
+            mass: 1.0,
+            move_resist: 0.5,
+            rotation_resist: 0.5,
+            repulsion: 0.0,
+            friction: 0.5,
+            calc_method: RigidCalcMethod::Dynamic,
+        }
+    }
+
+    fn soft_body(un_collision_group_flag: RigidGroups, bit_flag: SoftBodyFlags) -> SoftBody {
+        SoftBody {
+            name: "ソフトボディ".to_owned(),
+            name_en: "soft body".to_owned(),
+            form: SoftBodyForm::TriMesh,
+            material_index: -1,
+            group: 3,
+            un_collision_group_flag,
+            bit_flag,
+            b_link_create_distance: 2,
+            clusters: 0,
+            mass: 1.0,
+            collision_margin: 0.05,
+            aero_model: SoftBodyAeroModel::VPoint,
+            vcf: 1.0,
+            dp: 0.0,
+            dg: 0.0,
+            lf: 0.0,
+            pr: 0.0,
+            vc: 0.0,
+            df: 0.2,
+            mt: 0.0,
+            chr: 1.0,
+            khr: 0.1,
+            shr: 1.0,
+            ahr: 0.7,
+            srhr_cl: 0.1,
+            skhr_cl: 1.0,
+            sshr_cl: 0.5,
+            sr_splt_cl: 0.5,
+            sk_splt_cl: 0.5,
+            ss_splt_cl: 0.5,
+            v_it: 0,
+            p_it: 1,
+            d_it: 0,
+            c_it: 4,
+            lst: 1.0,
+            ast: 1.0,
+            vst: 1.0,
+            anchor_rigid: vec![],
+            pin_vertex: vec![],
+        }
+    }
+
+    #[test]
+    fn rigid_groups_round_trip() {
+        let groups = [
+            RigidGroups::empty(),
+            RigidGroups::all(),
+            RigidGroups::single(0),
+            RigidGroups::single(15),
+            RigidGroups::empty().with_group(0).with_group(9),
+        ];
+        let rigid_bodies: Vec<Rigid> = groups
+            .iter()
+            .enumerate()
+            .map(|(i, groups)| rigid(i as u8, *groups))
+            .collect();
+        let mut writer = writer();
+        writer.add_rigid_bodies(&rigid_bodies);
+        let buf = write(writer);
+
+        let (_, ns) = ModelInfoStage::from_reader(&buf[..]).unwrap().read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (rigid_bodies_cpy, _) = ns.read();
+        assert_eq!(rigid_bodies, rigid_bodies_cpy);
+        assert_eq!(
+            u16::from(rigid_bodies_cpy[4].un_collision_group_flag),
+            0x0201
+        );
+    }
+
+    #[test]
+    fn soft_body_flags_round_trip() {
+        let soft_bodies = vec![
+            soft_body(RigidGroups::empty(), SoftBodyFlags::empty()),
+            soft_body(
+                RigidGroups::single(1).with_group(14),
+                SoftBodyFlags::B_LINK_CREATION,
+            ),
+            soft_body(
+                RigidGroups::all(),
+                SoftBodyFlags::CLUSTER_CREATION | SoftBodyFlags::LINK_HYBRID,
+            ),
+            // undefined bits are kept.
+            soft_body(RigidGroups::empty(), SoftBodyFlags::from_bits_retain(0x85)),
+        ];
+        let mut writer = writer();
+        writer.add_soft_bodies(&soft_bodies);
+        let buf = write(writer);
+
+        let (_, ns) = ModelInfoStage::from_reader(&buf[..]).unwrap().read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let soft_bodies_cpy = ns.unwrap().read();
+        assert_eq!(soft_bodies, soft_bodies_cpy);
+        assert_eq!(
+            u16::from(soft_bodies_cpy[1].un_collision_group_flag),
+            0x4002
+        );
+        assert_eq!(soft_bodies_cpy[2].bit_flag.bits(), 0x06);
+        assert_eq!(soft_bodies_cpy[3].bit_flag.bits(), 0x85);
+    }
+
+    #[test]
+    fn soft_body_round_trip() {
+        let mut rope = soft_body(RigidGroups::single(0), SoftBodyFlags::B_LINK_CREATION);
+        rope.form = SoftBodyForm::Rope;
+        rope.aero_model = SoftBodyAeroModel::FOneSided;
+        rope.material_index = 0;
+        rope.anchor_rigid = vec![
+            SoftBodyAnchorRigid {
+                rigid_index: 0,
+                vertex_index: 1,
+                near_mode: true,
+            },
+            SoftBodyAnchorRigid {
+                rigid_index: 0,
+                vertex_index: 2,
+                near_mode: false,
+            },
+        ];
+        rope.pin_vertex = vec![0, 2];
+        let soft_bodies = vec![rope, soft_body(RigidGroups::empty(), SoftBodyFlags::all())];
+        let mut writer = writer();
+        writer.add_rigid_bodies(&[rigid(0, RigidGroups::empty())]);
+        writer.add_soft_bodies(&soft_bodies);
+        let buf = write(writer);
+
+        let reader = ModelInfoStage::from_reader(&buf[..]).unwrap();
+        assert_eq!(reader.get_header().version, PMXVersion::V21);
+        let (_, ns) = reader.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let ns = ns.unwrap();
+        assert_eq!(ns.version(), PMXVersion::V21);
+        assert_eq!(ns.encode(), ns.get_header().encode);
+        assert_eq!(ns.additional_uv(), 0);
+        assert_eq!(soft_bodies, ns.read());
+    }
+
+    #[test]
+    fn v21_without_soft_bodies() {
+        let mut model = crate::test_support::cube();
+        model.soft_bodies.clear();
+        let mut buf = crate::test_support::write(&model);
+        // drop soft body count and write version rounded by exporter.
+        assert_eq!(buf[buf.len() - 4..], [0; 4]);
+        buf.truncate(buf.len() - 4);
+        // 2.0999999
+        let version = f32::from_bits(2.1_f32.to_bits() - 1);
+        buf[4..8].copy_from_slice(&version.to_le_bytes());
+        let header = Header::peek(&buf).unwrap();
+        assert_eq!(header.version, PMXVersion::V21);
+        assert_eq!(header.raw_version(), Some(version));
+        assert_eq!(crate::test_support::read(&buf), model);
+
+        let (_, ns) = ModelInfoStage::from_reader(&buf[..]).unwrap().read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (joints, ns) = ns.read_with_end();
+        assert_eq!(joints, model.joints);
+        let ns = match ns {
+            JointNextStage::SoftBodies(ns) => ns,
+            JointNextStage::End(_) => unreachable!(),
+        };
+        let (soft_bodies, end) = ns.read_with_end();
+        assert!(soft_bodies.is_empty());
+        assert_eq!(end.warnings(), [ReadWarning::MissingSoftBodies]);
+        assert_eq!(end.finish().unwrap(), TailInfo::default());
+
+        let mut counter = Counter::default();
+        let offsets = ModelInfoStage::from_reader(&buf[..])
+            .unwrap()
+            .visit_with_offsets(&mut counter);
+        assert_eq!(offsets.soft_bodies, None);
+        assert_eq!(counter.sections.last(), Some(&(SectionKind::Joints, 6)));
+
+        // truncated inside the section is still broken.
+        buf.extend_from_slice(&[1, 0]);
+        assert!(matches!(
+            ModelInfoStage::from_reader(&buf[..])
+                .unwrap()
+                .try_read_model(),
+            Err(ReadError::InvalidData {
+                section: SectionKind::SoftBodies,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn generic_stages() {
+        use crate::reader::Stage;
+        /// skip sections until `kind` collecting skipped ones.
+        fn skip_until<S: Stage>(
+            stage: S,
+            kind: SectionKind,
+            skipped: &mut Vec<SectionKind>,
+        ) -> S::Next {
+            assert_eq!(stage.header().encode, Encode::Utf16Le);
+            if S::KIND != kind {
+                skipped.push(S::KIND);
+            }
+            stage.skip()
+        }
+        let model = crate::test_support::cube();
+        let bytes = crate::test_support::write(&model);
+        let mut skipped = vec![];
+        let stage = ModelInfoStage::from_reader(&bytes[..]).unwrap();
+        let stage = skip_until(stage, SectionKind::Bones, &mut skipped);
+        let stage = skip_until(stage, SectionKind::Bones, &mut skipped);
+        let stage = skip_until(stage, SectionKind::Bones, &mut skipped);
+        let stage = skip_until(stage, SectionKind::Bones, &mut skipped);
+        let stage = skip_until(stage, SectionKind::Bones, &mut skipped);
+        let (bones, stage) = Stage::read(stage);
+        assert_eq!(bones, model.bones);
+        assert_eq!(skipped.len(), 5);
+        assert_eq!(skipped.last(), Some(&SectionKind::Materials));
+
+        // joints continue to soft bodies then the end of file.
+        let stage = stage.skip().skip().skip();
+        let stage = match Stage::read(stage) {
+            (joints, JointNextStage::SoftBodies(stage)) => {
+                assert_eq!(joints, model.joints);
+                stage
+            }
+            (_, JointNextStage::End(_)) => unreachable!(),
+        };
+        assert!(stage.get_section_offsets().joints.is_some());
+        assert_eq!(stage.skip().finish().unwrap(), TailInfo::default());
+    }
+
+    #[test]
+    fn negative_face_index() {
+        let model = crate::test_support::cube();
+        let bytes = crate::test_support::write(&model);
+        let (_, offsets) = ModelInfoStage::from_reader(&bytes[..])
+            .unwrap()
+            .read_model_with_offsets();
+        // widen vertex indices to 32 bit and end the file after a broken face.
+        let mut buf = bytes[..offsets.faces.unwrap().start as usize].to_vec();
+        buf[11] = 4;
+        buf.extend_from_slice(&3_i32.to_le_bytes());
+        for index in [0_i32, 1, -1] {
+            buf.extend_from_slice(&index.to_le_bytes());
+        }
+        match ModelInfoStage::from_reader(&buf[..])
+            .unwrap()
+            .try_read_model()
+        {
+            Err(ReadError::InvalidData {
+                section, reason, ..
+            }) => {
+                assert_eq!(section, SectionKind::Faces);
+                assert_eq!(reason, "negative vertex index -1");
+            }
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        assert!(Face::try_from([0, 1, -1]).is_err());
+        assert_eq!(
+            Face::try_from([0, 1, 2]).unwrap(),
+            Face::from([0_u32, 1, 2])
+        );
+    }
+
+    #[test]
+    fn soft_body_near_mode() {
+        let mut rope = soft_body(RigidGroups::empty(), SoftBodyFlags::empty());
+        rope.anchor_rigid = vec![
+            SoftBodyAnchorRigid {
+                rigid_index: 0,
+                vertex_index: 1,
+                near_mode: true,
+            },
+            SoftBodyAnchorRigid {
+                rigid_index: 0,
+                vertex_index: 2,
+                near_mode: false,
+            },
+        ];
+        let mut writer = writer();
+        writer.add_rigid_bodies(&[rigid(0, RigidGroups::empty())]);
+        writer.add_soft_bodies(std::slice::from_ref(&rope));
+        let mut buf = write(writer);
+        // anchor count, rigid index, vertex index and near mode (1 on, 0 off) of each anchor,
+        // then pin vertex count. both indices are 1 byte in this file.
+        let tail = [2, 0, 0, 0, 0, 1, 1, 0, 2, 0, 0, 0, 0, 0];
+        let start = buf.len() - tail.len();
+        assert_eq!(buf[start..], tail);
+        assert_eq!(crate::test_support::read(&buf).soft_bodies, [rope]);
+
+        // seen in files of some editors.
+        buf[start + 6] = 0xFF;
+        let mut stage = ModelInfoStage::from_reader(&buf[..]).unwrap();
+        stage.set_lenient(true);
+        let (_, ns) = stage.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let ns = ns.unwrap();
+        let (soft_bodies, end) = ns.read_with_end();
+        assert!(!soft_bodies[0].anchor_rigid[0].near_mode);
+        assert_eq!(end.warnings(), [ReadWarning::InvalidNearMode(0xFF)]);
+    }
+
+    /// soft body section assembled field by field from PMX仕様.txt for a UTF-16 file with
+    /// 1 byte indices. the second anchor has near mode `0xFF`.
+    const SOFT_BODY_SECTION: &[u8] = &[
+        1, 0, 0, 0, // soft body count
+        4, 0, 0, 0, b'S', 0, b'B', 0, // name "SB"
+        0, 0, 0, 0,    // english name
+        1,    // shape: rope
+        0xFF, // material index -1
+        2,    // group
+        1, 0, // non collision groups: group 0
+        1, // flags: B-Link creation
+        2, 0, 0, 0, // B-Link creation distance
+        0, 0, 0, 0, // clusters
+        0, 0, 0x80, 0x3F, // mass 1.0
+        0, 0, 0, 0x3F, // collision margin 0.5
+        1, 0, 0, 0, // aero model: V_TwoSided
+        0, 0, 0x80, 0x3F, // VCF 1.0
+        0, 0, 0, 0, // DP
+        0, 0, 0, 0, // DG
+        0, 0, 0, 0, // LF
+        0, 0, 0, 0, // PR
+        0, 0, 0, 0, // VC
+        0, 0, 0, 0, // DF
+        0, 0, 0, 0, // MT
+        0, 0, 0x80, 0x3F, // CHR 1.0
+        0, 0, 0, 0, // KHR
+        0, 0, 0x80, 0x3F, // SHR 1.0
+        0, 0, 0, 0x3F, // AHR 0.5
+        0, 0, 0, 0, // SRHR_CL
+        0, 0, 0x80, 0x3F, // SKHR_CL 1.0
+        0, 0, 0, 0x3F, // SSHR_CL 0.5
+        0, 0, 0, 0x3F, // SR_SPLT_CL 0.5
+        0, 0, 0, 0x3F, // SK_SPLT_CL 0.5
+        0, 0, 0, 0x3F, // SS_SPLT_CL 0.5
+        0, 0, 0, 0, // V_IT
+        1, 0, 0, 0, // P_IT
+        0, 0, 0, 0, // D_IT
+        4, 0, 0, 0, // C_IT
+        0, 0, 0x80, 0x3F, // LST 1.0
+        0, 0, 0x80, 0x3F, // AST 1.0
+        0, 0, 0x80, 0x3F, // VST 1.0
+        2, 0, 0, 0, // anchor count
+        0, 1, 1, // rigid 0, vertex 1, near mode on
+        0, 2, 0xFF, // rigid 0, vertex 2, near mode 0xFF
+        1, 0, 0, 0, // pin vertex count
+        2, // vertex 2
+    ];
+
+    #[test]
+    fn soft_body_section_fixture() {
+        let mut writer = writer();
+        writer.set_version(PMXVersion::V21);
+        writer.add_rigid_bodies(&[rigid(0, RigidGroups::empty())]);
+        let mut buf = write(writer);
+        // replace empty soft body section
+        assert_eq!(buf.split_off(buf.len() - 4), [0; 4]);
+        buf.extend_from_slice(SOFT_BODY_SECTION);
+
+        let mut stage = ModelInfoStage::from_reader(&buf[..]).unwrap();
+        stage.set_lenient(true);
+        let (_, ns) = stage.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (soft_bodies, end) = ns.unwrap().read_with_end();
+        assert_eq!(end.warnings(), [ReadWarning::InvalidNearMode(0xFF)]);
+        let mut expected = soft_body(RigidGroups::single(0), SoftBodyFlags::B_LINK_CREATION);
+        expected.name = "SB".to_owned();
+        expected.name_en = String::new();
+        expected.form = SoftBodyForm::Rope;
+        expected.group = 2;
+        expected.collision_margin = 0.5;
+        expected.aero_model = SoftBodyAeroModel::VTwoSide;
+        expected.df = 0.0;
+        expected.khr = 0.0;
+        expected.ahr = 0.5;
+        expected.srhr_cl = 0.0;
+        expected.anchor_rigid = vec![
+            SoftBodyAnchorRigid {
+                rigid_index: 0,
+                vertex_index: 1,
+                near_mode: true,
+            },
+            SoftBodyAnchorRigid {
+                rigid_index: 0,
+                vertex_index: 2,
+                near_mode: false,
+            },
+        ];
+        expected.pin_vertex = vec![2];
+        assert_eq!(soft_bodies, [expected]);
+
+        // strict reader rejects the unknown near mode.
+        assert!(std::panic::catch_unwind(|| crate::test_support::read(&buf)).is_err());
+    }
+
+    #[test]
+    fn rigid_groups_collision() {
+        let mut a = rigid(0, RigidGroups::single(1));
+        let b = rigid(1, RigidGroups::empty());
+        let c = rigid(2, RigidGroups::empty());
+        assert!(!a.collides_with(&b));
+        assert!(!b.collides_with(&a));
+        assert!(a.collides_with(&c));
+        a.un_collision_group_flag = a.un_collision_group_flag.without_group(1);
+        assert!(a.collides_with(&b));
+        assert!(RigidGroups::single(3).contains_group(3));
+        assert!(!RigidGroups::all().contains_group(16));
+        assert_eq!(
+            RigidGroups::from(0x8001).groups().collect::<Vec<_>>(),
+            vec![0, 15]
+        );
+    }
+
+    #[test]
+    fn bool_fields_round_trip() {
+        let frames = vec![
+            Frame {
+                name: "Root".to_owned(),
+                name_en: "Root".to_owned(),
+                is_special: true,
+                inners: vec![FrameInner::Bone(0)],
+            },
+            Frame {
+                name: "その他".to_owned(),
+                name_en: "other".to_owned(),
+                is_special: false,
+                inners: vec![],
+            },
+        ];
+        let morphs = vec![Morph {
+            name: "衝撃".to_owned(),
+            english_name: "impulse".to_owned(),
+            control_panel: ControlPanel::System,
+            morph_data: MorphKinds::Impulse(vec![
+                ImpulseMorph {
+                    rigid_index: 0,
+                    is_local: true,
+                    velocity: [1.0, 0.0, 0.0],
+                    torque: [0.0; 3],
+                },
+                ImpulseMorph {
+                    rigid_index: 0,
+                    is_local: false,
+                    velocity: [0.0; 3],
+                    torque: [0.0, 1.0, 0.0],
+                },
+            ]),
+        }];
+        let mut writer = writer();
+        writer.add_frames(&frames);
+        writer.add_morphs(&morphs);
+        let buf = write(writer);
+
+        let (_, ns) = ModelInfoStage::from_reader(&buf[..]).unwrap().read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (morphs_cpy, ns) = ns.read();
+        let (frames_cpy, _) = ns.read();
+        assert_eq!(morphs, morphs_cpy);
+        assert_eq!(frames, frames_cpy);
+    }
+
+    /// a 2.0 file with one special frame whose `is_special` byte is 2.
+    fn corrupted_frame_file() -> Vec<u8> {
+        let mut writer = writer();
+        writer.add_frames(&[Frame {
+            name: String::new(),
+            name_en: String::new(),
+            is_special: true,
+            inners: vec![],
+        }]);
+        let mut buf = write(writer);
+        // is_special, inner count, rigid count, joint count
+        let offset = buf.len() - 13;
+        assert_eq!(buf[offset], 1);
+        buf[offset] = 2;
+        buf
+    }
+
+    /// read everything and finish.
+    fn finish(buf: &[u8], lenient: bool) -> Result<TailInfo, ReadError> {
+        let mut stage = ModelInfoStage::from_reader(buf).unwrap();
+        stage.set_lenient(lenient);
+        let (_, ns) = stage.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        match ns.read_with_end().1 {
+            JointNextStage::SoftBodies(ns) => ns.read_with_end().1.finish(),
+            JointNextStage::End(end) => end.finish(),
+        }
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        let junk = (0..16).collect::<Vec<u8>>();
+        for model in [crate::test_support::cube(), crate::types::Model::default()] {
+            let buf = crate::test_support::write(&model);
+            assert_eq!(finish(&buf, false).unwrap(), TailInfo::default());
+
+            let mut with_junk = buf.clone();
+            with_junk.extend_from_slice(&junk);
+            assert!(matches!(
+                finish(&with_junk, false),
+                Err(ReadError::TrailingBytes { position, len: 16 }) if position == buf.len() as u64
+            ));
+            let tail = finish(&with_junk, true).unwrap();
+            assert_eq!(tail.trailing_bytes, 16);
+            assert_eq!(tail.raw.as_deref(), Some(&junk[..]));
+
+            let mut writer = Writer::from_model(&model);
+            writer.set_trailing_data(tail.raw.unwrap());
+            assert_eq!(writer.write_to_vec().unwrap(), with_junk);
+        }
+    }
+
+    #[test]
+    fn preserve_unknown() {
+        let model = crate::test_support::cube();
+        let mut buf = crate::test_support::write(&model);
+        buf.extend_from_slice(b"future extension");
+        let read = |preserve_unknown| {
+            let options = ParseOptions {
+                preserve_unknown,
+                ..ParseOptions::default()
+            };
+            ModelInfoStage::from_reader(&buf[..])
+                .unwrap()
+                .read_model_with(options)
+                .unwrap()
+        };
+        assert!(read(false).unknown.is_empty());
+
+        let mut model_cpy = read(true);
+        assert_eq!(model_cpy.unknown.trailing, b"future extension");
+        model_cpy.materials[0].diffuse = [1.0, 0.0, 0.0, 1.0];
+        let mut edited = model.clone();
+        edited.materials[0].diffuse = [1.0, 0.0, 0.0, 1.0];
+        let mut expected = crate::test_support::write(&edited);
+        expected.extend_from_slice(b"future extension");
+        assert_eq!(
+            Writer::from_model(&model_cpy).write_to_vec().unwrap(),
+            expected
+        );
+
+        // texture indices grow to 2 bytes.
+        model_cpy
+            .textures
+            .textures
+            .extend((0..200).map(|i| format!("{}.png", i)));
+        assert!(matches!(
+            Writer::from_model(&model_cpy).write_to_vec(),
+            Err(WritePMXErrors::UnknownDataIndexSizes)
+        ));
+        model_cpy.unknown.trailing.clear();
+        assert!(Writer::from_model(&model_cpy).write_to_vec().is_ok());
+    }
+
+    #[test]
+    fn invalid_bool_lenient() {
+        let buf = corrupted_frame_file();
+        let mut stage = ModelInfoStage::from_reader(&buf[..]).unwrap();
+        stage.set_lenient(true);
+        let (_, ns) = stage.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (frames, ns) = ns.read();
+        assert!(frames[0].is_special);
+        assert_eq!(
+            ns.warnings(),
+            &[ReadWarning::InvalidBool {
+                field: "Frame::is_special",
+                value: 2
+            }]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "InvalidBool")]
+    fn invalid_bool_strict() {
+        let buf = corrupted_frame_file();
+        let (_, ns) = ModelInfoStage::from_reader(&buf[..]).unwrap().read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        ns.read();
+    }
+
+    #[test]
+    fn parse_options() {
+        let buf = crate::test_support::write(&crate::test_support::cube());
+        let default = ModelInfoStage::from_reader_with(&buf[..], ParseOptions::default())
+            .unwrap()
+            .read_model();
+        assert!(default == ModelInfoStage::from_reader(&buf[..]).unwrap().read_model());
+
+        let buf = corrupted_frame_file();
+        assert!(ModelInfoStage::from_reader(&buf[..])
+            .unwrap()
+            .try_read_model()
+            .is_err());
+        let options = ParseOptions::default().lenient(true);
+        let model = ModelInfoStage::from_reader_with(&buf[..], options)
+            .unwrap()
+            .try_read_model()
+            .unwrap();
+        assert!(model.frames[0].is_special);
+
+        let options = ParseOptions::default().progress(|_| std::ops::ControlFlow::Break(()));
+        assert!(matches!(
+            ModelInfoStage::from_reader_with(&buf[..], options)
+                .unwrap()
+                .try_read_model(),
+            Err(ReadError::Cancelled)
+        ));
+    }
+
+    /// file whose last IK link has angle limit flag `2`.
+    fn corrupted_ik_file() -> (Vec<u8>, Bone) {
+        let bone = Bone::builder("IK")
+            .ik(0, 40, 1.0, vec![(0, Some(([-1.0, 0.0, 0.0], [0.0; 3])))])
+            .build();
+        let mut writer = writer();
+        writer.add_bones(std::slice::from_ref(&bone));
+        let mut buf = write(writer);
+        let (_, offsets) = ModelInfoStage::from_reader(&buf[..])
+            .unwrap()
+            .read_model_with_offsets();
+        let bones = offsets.bones.unwrap();
+        // flag then 2 vectors at the end of the bone.
+        let offset = (bones.start + bones.len) as usize - 25;
+        assert_eq!(buf[offset], 1);
+        buf[offset] = 2;
+        (buf, bone)
+    }
+
+    #[test]
+    fn invalid_angle_limit_flag() {
+        let (buf, bone) = corrupted_ik_file();
+        let mut stage = ModelInfoStage::from_reader(&buf[..]).unwrap();
+        stage.set_lenient(true);
+        let (_, ns) = stage.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (bones, ns) = ns.read();
+        assert_eq!(bones, [bone]);
+        assert_eq!(
+            ns.warnings(),
+            &[ReadWarning::InvalidBool {
+                field: "IKLink::angle_limit",
+                value: 2
+            }]
+        );
+
+        let result = std::panic::catch_unwind(|| {
+            crate::test_support::read(&buf);
+        });
+        let message = result.unwrap_err();
+        let message = message.downcast_ref::<String>().unwrap();
+        assert!(message.contains("IKLink::angle_limit"), "{}", message);
+    }
+
+    fn material_morph(formula: MaterialMorphFormula) -> MaterialMorph {
+        MaterialMorph {
+            index: -1,
+            formula,
+            diffuse: [1.0; 4],
+            specular: [0.0; 3],
+            specular_factor: 0.0,
+            ambient: [0.5; 3],
+            edge_color: [0.0; 4],
+            edge_size: 1.0,
+            texture_factor: [1.0; 4],
+            sphere_texture_factor: [1.0; 4],
+            toon_texture_factor: [1.0; 4],
+        }
+    }
+
+    #[test]
+    fn material_morph_formula_and_control_panel_round_trip() {
+        let panels = [
+            ControlPanel::System,
+            ControlPanel::BottomLeft,
+            ControlPanel::TopLeft,
+            ControlPanel::TopRight,
+            ControlPanel::BottomRight,
+        ];
+        let morphs: Vec<Morph> = panels
+            .iter()
+            .map(|panel| Morph {
+                name: format!("{:?}", panel),
+                english_name: String::new(),
+                control_panel: *panel,
+                morph_data: MorphKinds::Material(vec![
+                    material_morph(MaterialMorphFormula::Multiply),
+                    material_morph(MaterialMorphFormula::Additive),
+                ]),
+            })
+            .collect();
+        let mut writer = writer();
+        writer.add_morphs(&morphs);
+        let buf = write(writer);
+
+        let (_, ns) = ModelInfoStage::from_reader(&buf[..]).unwrap().read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (morphs_cpy, _) = ns.read();
+        assert_eq!(morphs, morphs_cpy);
+    }
+
+    #[test]
+    fn unknown_material_morph_formula_lenient() {
+        let file = |formula| {
+            let mut writer = writer();
+            writer.add_morphs(&[Morph {
+                name: "材質".to_owned(),
+                english_name: String::new(),
+                control_panel: ControlPanel::System,
+                morph_data: MorphKinds::Material(vec![material_morph(formula)]),
+            }]);
+            write(writer)
+        };
+        let mut buf = file(MaterialMorphFormula::Multiply);
+        let additive = file(MaterialMorphFormula::Additive);
+        let position = (0..buf.len()).find(|i| buf[*i] != additive[*i]).unwrap();
+        buf[position] = 7;
+
+        let mut stage = ModelInfoStage::from_reader(&buf[..]).unwrap();
+        stage.set_lenient(true);
+        let (_, ns) = stage.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (morphs, ns) = ns.read();
+        assert_eq!(ns.warnings(), [ReadWarning::InvalidMaterialMorphFormula(7)]);
+        match &morphs[0].morph_data {
+            MorphKinds::Material(offsets) => {
+                assert_eq!(offsets[0].formula, MaterialMorphFormula::Unknown(7))
+            }
+            other => panic!("{:?}", other),
+        }
+        // unknown byte is written back.
+        let mut writer = writer();
+        writer.add_morphs(&morphs);
+        assert_eq!(write(writer), buf);
+    }
+
+    #[test]
+    fn control_panel_and_formula_mapping() {
+        for value in 0..=u8::MAX {
+            match ControlPanel::try_from(value) {
+                Ok(panel) => assert_eq!(u8::from(panel), value),
+                Err(err) => {
+                    assert!(value > 4);
+                    assert_eq!(err.value, value);
+                }
+            }
+            match MaterialMorphFormula::try_from(value) {
+                Ok(formula) => assert_eq!(u8::from(formula), value),
+                Err(err) => {
+                    assert!(value > 1);
+                    assert_eq!(err.value, value);
+                    assert_eq!(
+                        err.to_string(),
+                        format!("{} is not a valid MaterialMorphFormula", value)
+                    );
+                }
+            }
+        }
+    }
+
+    fn material(toon_mode: ToonMode) -> Material {
+        Material {
+            name: "材質".to_owned(),
+            english_name: "material".to_owned(),
+            diffuse: [1.0; 4],
+            specular: [0.0; 3],
+            specular_factor: 5.0,
+            ambient: [0.5; 3],
+            draw_mode: MaterialFlags::HAS_EDGE,
+            edge_color: [0.0, 0.0, 0.0, 1.0],
+            edge_size: 1.0,
+            texture_index: -1,
+            sphere_mode: None,
+            toon_mode,
+            memo: String::new(),
+            num_face_vertices: 0,
+        }
+    }
+
+    fn read_materials(buf: &[u8], lenient: bool) -> (Vec<Material>, Vec<ReadWarning>) {
+        let mut stage = ModelInfoStage::from_reader(buf).unwrap();
+        stage.set_lenient(lenient);
+        let (_, ns) = stage.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (_, ns) = ns.read();
+        let (materials, ns) = ns.read();
+        (materials, ns.warnings().to_vec())
+    }
+
+    #[test]
+    fn common_toon_range() {
+        let materials = vec![
+            material(ToonMode::Common(0)),
+            material(ToonMode::Common(9)),
+            material(ToonMode::Separate(-1)),
+        ];
+        let mut valid = writer();
+        valid.add_materials(&materials);
+        let (materials_cpy, warnings) = read_materials(&write(valid), false);
+        assert_eq!(materials, materials_cpy);
+        assert!(warnings.is_empty());
+
+        let mut invalid = writer();
+        invalid.add_materials(&[material(ToonMode::Common(10))]);
+        let (materials_cpy, warnings) = read_materials(&write(invalid), true);
+        assert_eq!(materials_cpy[0].toon_mode, ToonMode::Common(10));
+        assert_eq!(
+            warnings,
+            vec![ReadWarning::InvalidCommonToon(ToonMode::Common(10))]
+        );
+    }
+
+    #[test]
+    fn truncated_in_bones() {
+        let buf = crate::test_support::write(&crate::test_support::cube());
+        let (_, offsets) = ModelInfoStage::from_reader(&buf[..])
+            .unwrap()
+            .read_model_with_offsets();
+        let bones = offsets.bones.unwrap();
+        let end = (bones.start + bones.len) as usize - 1;
+        let err = ModelInfoStage::from_reader(&buf[..end])
+            .unwrap()
+            .try_read_model()
+            .unwrap_err();
+        match err {
+            ReadError::InvalidData {
+                section,
+                element_index,
+                byte_offset,
+                ref recent_bytes,
+                ref reason,
+            } => {
+                assert_eq!(section, SectionKind::Bones);
+                assert_eq!(element_index, 1);
+                assert!(bones.start < byte_offset && byte_offset <= end as u64);
+                let offset = byte_offset as usize;
+                assert_eq!(recent_bytes[..], buf[offset - 16..offset]);
+                assert_eq!(reason, "failed to fill whole buffer");
+            }
+            err => panic!("{:?}", err),
+        }
+        assert!(err.to_string().starts_with("bones[1] at offset 0x"));
+    }
+
+    #[test]
+    fn unknown_morph_kind() {
+        let mut buf = crate::test_support::write(&crate::test_support::cube());
+        let (_, offsets) = ModelInfoStage::from_reader(&buf[..])
+            .unwrap()
+            .read_model_with_offsets();
+        // count, "頂点" in UTF-16, empty english name and panel precede the kind.
+        let kind = offsets.morphs.unwrap().start as usize + 4 + 8 + 4 + 1;
+        assert_eq!(buf[kind], 1);
+        buf[kind] = 42;
+        let err = ModelInfoStage::from_reader(&buf[..])
+            .unwrap()
+            .try_read_model()
+            .unwrap_err();
+        let expected = format!(
+            "morphs[0] at offset {:#X}: unknown morph kind 42 (last bytes: {})",
+            kind + 1,
+            buf[kind - 15..=kind]
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        assert_eq!(err.to_string(), expected);
+
+        // strict reader panics with the same message.
+        let result = std::panic::catch_unwind(|| crate::test_support::read(&buf));
+        let message = result.unwrap_err();
+        assert_eq!(message.downcast_ref::<String>(), Some(&expected));
+
+        // invalid values tolerated by lenient mode are broken data otherwise.
+        let buf = corrupted_frame_file();
+        let err = ModelInfoStage::from_reader(&buf[..])
+            .unwrap()
+            .try_read_model()
+            .unwrap_err();
+        assert!(
+            err.to_string().starts_with("frames[0] at offset"),
+            "{}",
+            err
+        );
+        assert!(err.to_string().contains("InvalidBool"), "{}", err);
+        let model = crate::test_support::cube();
+        let buf = crate::test_support::write(&model);
+        let read = ModelInfoStage::from_reader(&buf[..])
+            .unwrap()
+            .try_read_model()
+            .unwrap();
+        assert_eq!(read, model);
+    }
+
+    #[test]
+    fn section_offsets() {
+        let mut writer = Writer::begin_writer(false);
+        writer.set_mmd_compat(false);
+        writer.set_model_info(&ModelInfo {
+            name: "a".to_owned(),
+            ..Default::default()
+        });
+        let vertex = Vertex {
+            position: [0.0; 3],
+            norm: [0.0; 3],
+            uv: [0.0; 2],
+            add_uv: [[0.0; 4]; 4],
+            weight_type: VertexWeight::BDEF1(-1),
+            edge_mag: 1.0,
+        };
+        writer.add_vertices(&[vertex.clone(), vertex]);
+        writer.add_faces(&[Face {
+            vertices: [0, 1, 0],
+        }]);
+        let buf = write(writer);
+
+        let stage = ModelInfoStage::from_reader(&buf[..]).unwrap();
+        assert_eq!(stage.get_section_offsets(), SectionOffsets::default());
+        let (model, offsets) = stage.read_model_with_offsets();
+        assert_eq!(model.vertices.len(), 2);
+
+        let range = |start, len| Some(SectionRange { start, len });
+        // header: magic 4 + version 4 + length 1 + config 8
+        // model info: 4 strings with length prefix. only name has 1 byte.
+        // vertex: position 12 + normal 12 + uv 8 + weight type 1 + bone index 1 + edge 4
+        // face: 3 vertex indices 1 byte each
+        let expected = SectionOffsets {
+            model_info: range(17, 17),
+            vertices: range(34, 4 + 2 * 38),
+            faces: range(114, 4 + 3),
+            textures: range(121, 4),
+            materials: range(125, 4),
+            bones: range(129, 4),
+            morphs: range(133, 4),
+            frames: range(137, 4),
+            rigid_bodies: range(141, 4),
+            joints: range(145, 4),
+            soft_bodies: None,
+        };
+        assert_eq!(offsets, expected);
+        assert_eq!(149, buf.len());
+        assert_eq!(offsets.get(SectionKind::Faces), expected.faces);
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        sections: Vec<(SectionKind, usize)>,
+        elements: usize,
+        vertices: usize,
+        position_sum: [f32; 3],
+        faces: usize,
+        soft_bodies: usize,
+    }
+
+    impl PmxVisitor for Counter {
+        fn section_start(&mut self, section: SectionKind, count: usize) {
+            self.sections.push((section, count));
+        }
+        fn model_info(&mut self, _: ModelInfo) {
+            self.elements += 1;
+        }
+        fn vertex(&mut self, vertex: Vertex) {
+            self.elements += 1;
+            self.vertices += 1;
+            for (sum, x) in self.position_sum.iter_mut().zip(vertex.position.iter()) {
+                *sum += x;
+            }
+        }
+        fn face(&mut self, _: Face) {
+            self.elements += 1;
+            self.faces += 1;
+        }
+        fn texture(&mut self, _: String) {
+            self.elements += 1;
+        }
+        fn material(&mut self, _: Material) {
+            self.elements += 1;
+        }
+        fn bone(&mut self, _: Bone) {
+            self.elements += 1;
+        }
+        fn morph(&mut self, _: Morph) {
+            self.elements += 1;
+        }
+        fn frame(&mut self, _: Frame) {
+            self.elements += 1;
+        }
+        fn rigid(&mut self, _: Rigid) {
+            self.elements += 1;
+        }
+        fn joint(&mut self, _: Joint) {
+            self.elements += 1;
+        }
+        fn soft_body(&mut self, _: SoftBody) {
+            self.elements += 1;
+            self.soft_bodies += 1;
+        }
+    }
+
+    #[test]
+    fn read_soa_matches_read() {
+        let mut model = crate::test_support::cube();
+        model.additional_uv = 2;
+        // uv morphs of later channels raise the count on write.
+        model.morphs.retain(|morph| {
+            morph
+                .morph_data
+                .uv_channel()
+                .is_none_or(|channel| channel < 2)
+        });
+        model.vertices[5].add_uv[1] = [1.0, 2.0, 3.0, 4.0];
+        let buf = crate::test_support::write(&model);
+        let vertices_stage = || ModelInfoStage::from_reader(&buf[..]).unwrap().read().1;
+        let (vertices, faces) = vertices_stage().read();
+        let (buffers, soa_faces) = vertices_stage().read_soa();
+        assert_eq!(buffers.add_uvs.len(), 2);
+        assert_eq!(buffers, VertexBuffers::from_vertices(&vertices, 2));
+        assert_eq!(buffers.to_vertices(), vertices);
+        assert_eq!(soa_faces.read().0, faces.read().0);
+    }
+
+    #[test]
+    fn read_flat_matches_read() {
+        let buf = crate::test_support::write(&crate::test_support::cube());
+        let faces_stage = || {
+            let (_, ns) = ModelInfoStage::from_reader(&buf[..]).unwrap().read();
+            let (_, ns) = ns.read();
+            ns
+        };
+        let (faces, textures) = faces_stage().read();
+        let (flat, flat_textures) = faces_stage().read_flat();
+        let expected = faces
+            .iter()
+            .flat_map(|face| face.vertices.iter().copied())
+            .collect::<Vec<_>>();
+        assert_eq!(flat, expected);
+        assert_eq!(flat_textures.read().0, textures.read().0);
+    }
+
+    #[test]
+    fn visitor_matches_read_model() {
+        let buf = crate::test_support::write(&crate::test_support::cube());
+        let model = ModelInfoStage::from_reader(&buf[..]).unwrap().read_model();
+        let mut counter = Counter::default();
+        visit(&buf[..], &mut counter).unwrap();
+
+        let mut position_sum = [0.0; 3];
+        for vertex in &model.vertices {
+            for (sum, x) in position_sum.iter_mut().zip(vertex.position.iter()) {
+                *sum += x;
+            }
+        }
+        assert_eq!(counter.position_sum, position_sum);
+        assert_eq!(counter.vertices, model.vertices.len());
+        assert_eq!(counter.faces, model.faces.len());
+        assert_eq!(counter.soft_bodies, model.soft_bodies.len());
+        let counts = vec![
+            (SectionKind::ModelInfo, 1),
+            (SectionKind::Vertices, model.vertices.len()),
+            (SectionKind::Faces, model.faces.len()),
+            (SectionKind::Textures, model.textures.textures.len()),
+            (SectionKind::Materials, model.materials.len()),
+            (SectionKind::Bones, model.bones.len()),
+            (SectionKind::Morphs, model.morphs.len()),
+            (SectionKind::Frames, model.frames.len()),
+            (SectionKind::RigidBodies, model.rigid_bodies.len()),
+            (SectionKind::Joints, model.joints.len()),
+            (SectionKind::SoftBodies, model.soft_bodies.len()),
+        ];
+        assert_eq!(
+            counter.elements,
+            counts.iter().map(|(_, count)| count).sum::<usize>()
+        );
+        assert_eq!(counter.sections, counts);
+    }
+
+    #[test]
+    fn texts_of_mixed_width() {
+        let mut model = crate::test_support::cube();
+        // decode buffer is reused so a long text ending in wide characters comes first.
+        model.model_info.comment = "規約".repeat(1000);
+        model.model_info.comment_en = "a".repeat(2999);
+        model.materials[0].memo = "🎀 リボン ribbon é".to_owned();
+        model.materials[1].memo = String::new();
+        for encode in [Encode::Utf16Le, Encode::UTF8] {
+            model.encode = encode;
+            let read = crate::test_support::read(&crate::test_support::write(&model));
+            assert_eq!(read, model);
+            for text in [&read.model_info.comment, &read.materials[0].memo] {
+                assert_eq!(text.capacity(), text.len());
+            }
+        }
+    }
+
+    #[test]
+    fn textures_borrowed() {
+        let mut model = crate::test_support::cube();
+        let expected = model.textures.textures.clone();
+        assert!(!expected.is_empty());
+        let utf16 = crate::test_support::write(&model);
+        let textures = read_textures_borrowed(&utf16).unwrap();
+        assert_eq!(textures, expected);
+        assert!(textures.iter().all(|text| matches!(text, Cow::Owned(_))));
+
+        model.encode = Encode::UTF8;
+        let utf8 = crate::test_support::write(&model);
+        let textures = read_textures_borrowed(&utf8).unwrap();
+        assert_eq!(textures, expected);
+        for text in &textures {
+            match text {
+                Cow::Borrowed(text) => assert!(utf8.as_ptr_range().contains(&text.as_ptr())),
+                Cow::Owned(_) => panic!("{} is copied", text),
+            }
+        }
+
+        let (_, offsets) = ModelInfoStage::from_reader(&utf8[..])
+            .unwrap()
+            .read_model_with_offsets();
+        let end = offsets.textures.unwrap().start as usize + 6;
+        assert_eq!(read_textures_borrowed(&utf8[..end]), None);
+        let mut pmd = utf8.clone();
+        pmd[..4].copy_from_slice(b"PMD ");
+        assert_eq!(read_textures_borrowed(&pmd), None);
+    }
+
+    #[test]
+    fn textures_borrowed_truncated() {
+        let bytes = crate::test_support::write(&crate::test_support::cube());
+        let (_, offsets) = ModelInfoStage::from_reader(&bytes[..])
+            .unwrap()
+            .read_model_with_offsets();
+        // model info, vertices and faces cut anywhere are not textures.
+        for end in Header::SIZE..offsets.textures.unwrap().start as usize {
+            assert_eq!(read_textures_borrowed(&bytes[..end]), None, "{}", end);
+        }
+    }
+
+    #[test]
+    fn manual_header() {
+        let kinds = IndexKindsConfig {
+            vertex: VertexIndexKinds::U16,
+            texture: IndexKinds::I8,
+            material: IndexKinds::I8,
+            bone: IndexKinds::I16,
+            morph: IndexKinds::I32,
+            rigid_body: IndexKinds::I8,
+        };
+        let header = Header::new(PMXVersion::V21, Encode::UTF8, 2, kinds).unwrap();
+        assert_eq!(header.index_kinds(), kinds);
+        let mut writer = crate::binary_writer::BinaryWriter::from_writer(Vec::new(), header);
+        writer.write_header();
+        let bytes = writer.inner.into_inner().unwrap();
+        assert_eq!(bytes.len(), Header::SIZE);
+        let raw = crate::binary_reader::BinaryReader::from_reader(&bytes[..]).read_raw_header();
+        let read = super::transform_header_c2r(&raw).unwrap();
+        assert_eq!(read.raw_version(), Some(2.1));
+        assert_eq!(header.raw_version(), None);
+        assert_eq!(
+            Header {
+                raw_version: None,
+                ..read
+            },
+            header
+        );
+        assert_eq!(
+            header.to_string(),
+            "PMX 2.1 UTF-8 additional uv: 2\n\
+             index sizes: vertex 2 texture 1 material 1 bone 2 morph 4 rigid body 1"
+        );
+        assert!(matches!(
+            Header::new(PMXVersion::V20, Encode::Utf16Le, 5, kinds),
+            Err(HeaderConversionError::InvalidAdditionalUv)
+        ));
+
+        // same decisions as writer.
+        let model = crate::test_support::cube();
+        let bytes = crate::test_support::write(&model);
+        let header = Header {
+            version: PMXVersion::V21,
+            additional_uv: model.additional_uv,
+            ..Header::for_counts(
+                model.vertices.len(),
+                model.textures.textures.len(),
+                model.materials.len(),
+                model.bones.len(),
+                model.morphs.len(),
+                model.rigid_bodies.len(),
+            )
+        };
+        assert_eq!(
+            Header {
+                raw_version: None,
+                ..Header::peek(&bytes).unwrap()
+            },
+            header
+        );
+        let counts = Header::for_counts(256, 128, 0, 32767, 32768, 0).index_kinds();
+        assert_eq!(counts.vertex, VertexIndexKinds::U16);
+        assert_eq!(counts.texture, IndexKinds::I16);
+        assert_eq!(counts.bone, IndexKinds::I16);
+        assert_eq!(counts.morph, IndexKinds::I32);
+    }
+
+    #[test]
+    fn metadata_only() {
+        let mut model = crate::test_support::cube();
+        let bytes = crate::test_support::write(&model);
+        let (_, offsets) = ModelInfoStage::from_reader(&bytes[..])
+            .unwrap()
+            .read_model_with_offsets();
+        let info = offsets.model_info.unwrap();
+        let info_end = (info.start + info.len) as usize;
+        let (header, model_info) = read_metadata(&bytes[..info_end]).unwrap();
+        assert_eq!(header.encode, Header::peek(&bytes).unwrap().encode);
+        assert_eq!(header.version, PMXVersion::V21);
+        assert_eq!(header.additional_uv, model.additional_uv);
+        assert_eq!(model_info, model.model_info);
+        assert!(matches!(
+            read_metadata(&bytes[..info_end - 1]),
+            Err(MetadataError::Io(_))
+        ));
+
+        assert!(matches!(
+            Header::peek(&bytes[..Header::SIZE - 1]),
+            Err(HeaderConversionError::Truncated)
+        ));
+        let mut invalid = bytes[..Header::SIZE].to_vec();
+        invalid[11] = 3;
+        assert!(matches!(
+            Header::peek(&invalid),
+            Err(HeaderConversionError::InvalidIndex)
+        ));
+
+        model.encode = Encode::UTF8;
+        model.model_info.comment = "a".repeat(METADATA_MAX_BYTES);
+        let bytes = crate::test_support::write(&model);
+        assert!(matches!(
+            read_metadata(&bytes[..]),
+            Err(MetadataError::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn unknown_data_io_error() {
+        struct Broken;
+        impl std::io::Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::BrokenPipe,
+                    "broken",
+                ))
+            }
+        }
+        let buf = crate::test_support::write(&crate::test_support::cube());
+        let options = ParseOptions::default().preserve_unknown(true);
+        let stage =
+            ModelInfoStage::from_reader_with(std::io::Read::chain(&buf[..], Broken), options);
+        assert!(matches!(
+            stage.unwrap().try_read_model(),
+            Err(ReadError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn stages_are_send() {
+        fn assert_send<T: Send>() {}
+        use crate::reader::{
+            BonesStage, EndStage, FacesStage, FrameStage, MaterialsStage, MorphsStage, RigidStage,
+            SoftBodyStage, TexturesStage, VerticesStage,
+        };
+        assert_send::<ModelInfoStage<&[u8]>>();
+        assert_send::<VerticesStage<&[u8]>>();
+        assert_send::<FacesStage<&[u8]>>();
+        assert_send::<TexturesStage<&[u8]>>();
+        assert_send::<MaterialsStage<&[u8]>>();
+        assert_send::<BonesStage<&[u8]>>();
+        assert_send::<MorphsStage<&[u8]>>();
+        assert_send::<FrameStage<&[u8]>>();
+        assert_send::<RigidStage<&[u8]>>();
+        assert_send::<crate::reader::JointStage<&[u8]>>();
+        assert_send::<JointNextStage<&[u8]>>();
+        assert_send::<SoftBodyStage<&[u8]>>();
+        assert_send::<EndStage<&[u8]>>();
+        #[cfg(feature = "std-fs")]
+        assert_send::<ModelInfoStage<std::fs::File>>();
+        #[cfg(feature = "std-fs")]
+        assert_send::<VerticesStage<std::fs::File>>();
     }
 }
@@ -0,0 +1,282 @@
+//! models distributed in zip archives with their textures.
+use super::{ModelInfoStage, ReadError};
+use crate::types::Model;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Seek};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+/// error of [`open_from_zip`] and [`TextureResolver::fetch`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ZipReadError {
+    /// the archive is broken or uses unsupported compression.
+    Zip(ZipError),
+    /// no `.pmx` entry, or no entry at the given path.
+    NoModel,
+    /// magic number, version or encoding of the model is invalid.
+    InvalidHeader,
+    /// the model is broken.
+    Read(ReadError),
+    /// no entry for the texture path.
+    TextureNotFound(String),
+    Io(std::io::Error),
+}
+
+impl Display for ZipReadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZipReadError::Zip(e) => write!(f, "invalid zip archive: {}", e),
+            ZipReadError::NoModel => write!(f, "no PMX model in zip archive"),
+            ZipReadError::InvalidHeader => write!(f, "invalid PMX header"),
+            ZipReadError::Read(e) => e.fmt(f),
+            ZipReadError::TextureNotFound(path) => {
+                write!(f, "texture {:?} is not in zip archive", path)
+            }
+            ZipReadError::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ZipReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ZipReadError::Zip(e) => Some(e),
+            ZipReadError::Read(e) => Some(e),
+            ZipReadError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ZipError> for ZipReadError {
+    fn from(e: ZipError) -> Self {
+        ZipReadError::Zip(e)
+    }
+}
+
+impl From<std::io::Error> for ZipReadError {
+    fn from(e: std::io::Error) -> Self {
+        ZipReadError::Io(e)
+    }
+}
+
+/// fetch textures of a model read by [`open_from_zip`] from the same archive.
+pub struct TextureResolver<R: Read + Seek> {
+    archive: ZipArchive<R>,
+    /// normalized path of the directory containing the model.
+    base: Vec<String>,
+    /// normalized paths of file entries to their index and name.
+    entries: HashMap<String, (usize, String)>,
+}
+
+impl<R: Read + Seek> TextureResolver<R> {
+    /// entry name of `texture`, a path relative to the model as stored in
+    /// [`TextureList`](crate::types::TextureList).
+    ///
+    /// `\` and `/` both separate directories, `.` and `..` are resolved and
+    /// case is ignored. `None` if the path leaves the archive or no entry matches.
+    pub fn resolve(&self, texture: &str) -> Option<String> {
+        let key = join(&self.base, texture)?;
+        self.entries.get(&key).map(|(_, name)| name.clone())
+    }
+
+    /// bytes of `texture`. see [`resolve`](Self::resolve) for how paths are matched.
+    ///
+    /// # Errors
+    /// * `ZipReadError::TextureNotFound` if no entry matches.
+    /// * `ZipReadError::Zip` and `ZipReadError::Io` if failed to extract the entry.
+    pub fn fetch(&mut self, texture: &str) -> Result<Vec<u8>, ZipReadError> {
+        let index = join(&self.base, texture)
+            .and_then(|key| self.entries.get(&key).map(|&(index, _)| index))
+            .ok_or_else(|| ZipReadError::TextureNotFound(texture.to_owned()))?;
+        let mut entry = self.archive.by_index(index)?;
+        let mut bytes = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// read a model in zip `archive` and keep the archive to fetch its textures.
+///
+/// `inner_path` is the entry of the model, matched like texture paths.
+/// the first `.pmx` entry is read if `None`.
+/// entry names not in UTF-8 are decoded as Shift_JIS as archives made on Japanese Windows are.
+///
+/// # Errors
+/// * `ZipReadError::NoModel` if the model entry is not found.
+/// * `ZipReadError::InvalidHeader` and `ZipReadError::Read` if the model is broken.
+/// * `ZipReadError::Zip` and `ZipReadError::Io` if failed to read the archive.
+///
+/// # Examples
+///
+/// ```no_run
+/// let file = std::fs::File::open("model.zip").unwrap();
+/// let (model, mut textures) = PMXUtil::reader::open_from_zip(file, None).unwrap();
+/// for texture in &model.textures.textures {
+///     let bytes = textures.fetch(texture).unwrap();
+///     println!("{}: {} bytes", texture, bytes.len());
+/// }
+/// ```
+pub fn open_from_zip<R: Read + Seek>(
+    archive: R,
+    inner_path: Option<&str>,
+) -> Result<(Model, TextureResolver<R>), ZipReadError> {
+    let mut archive = ZipArchive::new(archive)?;
+    let mut entries = HashMap::new();
+    let mut first_model = None;
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry_name(entry.name_raw());
+        let key = match join(&[], &name) {
+            Some(key) => key,
+            None => continue,
+        };
+        if first_model.is_none() && key.ends_with(".pmx") {
+            first_model = Some(key.clone());
+        }
+        entries.entry(key).or_insert((index, name));
+    }
+    let (index, name) = match inner_path {
+        Some(path) => join(&[], path),
+        None => first_model,
+    }
+    .and_then(|key| entries.get(&key).cloned())
+    .ok_or(ZipReadError::NoModel)?;
+
+    let mut entry = archive.by_index(index)?;
+    let mut bytes = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut bytes)?;
+    drop(entry);
+    let model = ModelInfoStage::from_reader(&bytes[..])
+        .ok_or(ZipReadError::InvalidHeader)?
+        .try_read_model()
+        .map_err(ZipReadError::Read)?;
+    let mut base = split(&name).map(str::to_lowercase).collect::<Vec<_>>();
+    base.pop();
+    Ok((
+        model,
+        TextureResolver {
+            archive,
+            base,
+            entries,
+        },
+    ))
+}
+
+/// entry name in UTF-8 or Shift_JIS.
+fn entry_name(raw: &[u8]) -> String {
+    match std::str::from_utf8(raw) {
+        Ok(name) => name.to_owned(),
+        Err(_) => encoding_rs::SHIFT_JIS.decode(raw).0.into_owned(),
+    }
+}
+
+fn split(path: &str) -> impl Iterator<Item = &str> {
+    path.split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+}
+
+/// lower cased `path` under `base` with `.` and `..` resolved. `None` if it goes above root.
+fn join(base: &[String], path: &str) -> Option<String> {
+    let mut parts = base.to_vec();
+    for part in split(path) {
+        if part == ".." {
+            parts.pop()?;
+        } else {
+            parts.push(part.to_lowercase());
+        }
+    }
+    Some(parts.join("/"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::{open_from_zip, ReadError, ZipReadError};
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    fn archive(files: &[(&str, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        zip.add_directory("モデル/", SimpleFileOptions::default())
+            .unwrap();
+        for (i, (name, bytes)) in files.iter().enumerate() {
+            let method = if i % 2 == 0 {
+                CompressionMethod::Deflated
+            } else {
+                CompressionMethod::Stored
+            };
+            zip.start_file(
+                *name,
+                SimpleFileOptions::default().compression_method(method),
+            )
+            .unwrap();
+            zip.write_all(bytes).unwrap();
+        }
+        let mut cursor = zip.finish().unwrap();
+        cursor.set_position(0);
+        cursor
+    }
+
+    #[test]
+    fn model_and_textures_in_zip() {
+        let mut model = crate::test_support::cube();
+        model.textures.textures = vec![
+            "tex\\cube.png".to_owned(),
+            "./Toon.BMP".to_owned(),
+            "..\\shared\\sphere.spa".to_owned(),
+            "missing.png".to_owned(),
+            "..\\..\\outside.png".to_owned(),
+        ];
+        let pmx = crate::test_support::write(&model);
+        let files: [(&str, &[u8]); 5] = [
+            ("readme.txt", b"read me"),
+            ("モデル/キューブ.PMX", &pmx),
+            ("モデル/Tex/Cube.png", b"png"),
+            ("モデル/toon.bmp", b"bmp"),
+            ("shared/sphere.spa", b"spa"),
+        ];
+
+        let (read, mut textures) = open_from_zip(archive(&files), None).unwrap();
+        assert_eq!(read, model);
+        assert_eq!(
+            textures.resolve("tex\\cube.png").as_deref(),
+            Some("モデル/Tex/Cube.png")
+        );
+        assert_eq!(textures.fetch("tex\\cube.png").unwrap(), b"png");
+        assert_eq!(textures.fetch("./Toon.BMP").unwrap(), b"bmp");
+        assert_eq!(textures.fetch("..\\shared\\sphere.spa").unwrap(), b"spa");
+        for path in &model.textures.textures[3..] {
+            assert!(textures.resolve(path).is_none());
+            assert!(matches!(
+                textures.fetch(path),
+                Err(ZipReadError::TextureNotFound(ref p)) if p == path
+            ));
+        }
+
+        // explicit path is matched like textures.
+        let (read, _) = open_from_zip(archive(&files), Some("モデル\\キューブ.pmx")).unwrap();
+        assert_eq!(read, model);
+        assert!(matches!(
+            open_from_zip(archive(&files), Some("other.pmx")),
+            Err(ZipReadError::NoModel)
+        ));
+        assert!(matches!(
+            open_from_zip(archive(&files[..1]), None),
+            Err(ZipReadError::NoModel)
+        ));
+        assert!(matches!(
+            open_from_zip(archive(&[("a.pmx", &pmx[..pmx.len() / 2])]), None),
+            Err(ZipReadError::Read(ReadError::InvalidData { .. }))
+        ));
+        assert!(matches!(
+            open_from_zip(Cursor::new(b"not a zip".to_vec()), None),
+            Err(ZipReadError::Zip(_))
+        ));
+    }
+}
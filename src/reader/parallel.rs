@@ -0,0 +1,235 @@
+//! parallel parsing of vertices and faces in a byte buffer.
+use super::{FacesStage, ModelInfoStage, ReaderInner, StageInner, TexturesStage, VerticesStage};
+use crate::binary_reader::BinaryReader;
+use crate::progress::SectionKind;
+use crate::types::{Header, Model};
+use rayon::prelude::*;
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// minimum records parsed by a task.
+const CHUNK: usize = 4096;
+
+/// error of [`read_model_parallel`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ParallelReadError {
+    /// magic number, version or encoding is invalid.
+    InvalidHeader,
+    /// `index`th element of `section` has unknown kind or is truncated.
+    BrokenRecord { section: SectionKind, index: usize },
+}
+
+impl Display for ParallelReadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParallelReadError::InvalidHeader => write!(f, "invalid PMX header"),
+            ParallelReadError::BrokenRecord { section, index } => {
+                write!(f, "{:?} {} is broken", section, index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParallelReadError {}
+
+fn inner(bytes: &[u8], header: Header) -> ReaderInner<&[u8]> {
+    ReaderInner::new(BinaryReader::from_reader(bytes), header)
+}
+
+/// element count at `position`. negative count is read as 0 like the staged reader.
+fn read_count(
+    bytes: &[u8],
+    position: &mut usize,
+    section: SectionKind,
+) -> Result<usize, ParallelReadError> {
+    let count = bytes
+        .get(*position..*position + 4)
+        .ok_or(ParallelReadError::BrokenRecord { section, index: 0 })?;
+    *position += 4;
+    let count = i32::from_le_bytes([count[0], count[1], count[2], count[3]]);
+    Ok(usize::try_from(count).unwrap_or(0))
+}
+
+/// byte length of a vertex record at the start of `bytes`.
+fn vertex_len(bytes: &[u8], header: &Header) -> Option<usize> {
+    let fixed = 4 * (8 + 4 * usize::from(header.additional_uv));
+    let bone = usize::from(u8::from(header.s_bone_index));
+    let weight = match *bytes.get(fixed)? {
+        0 => bone,
+        1 => 2 * bone + 4,
+        2 | 4 => 4 * bone + 16,
+        3 => 2 * bone + 40,
+        _ => return None,
+    };
+    let len = fixed + 1 + weight + 4;
+    if len <= bytes.len() {
+        Some(len)
+    } else {
+        None
+    }
+}
+
+/// parse records starting at `offsets` in parallel. the last offset is the end of section.
+fn parse<'a, S: StageInner<&'a [u8]>, T: Send>(
+    bytes: &'a [u8],
+    header: Header,
+    section: SectionKind,
+    offsets: &[usize],
+    stage: impl Fn(ReaderInner<&'a [u8]>) -> S + Sync,
+    read: impl Fn(&mut S) -> T + Sync,
+) -> Result<Vec<T>, ParallelReadError> {
+    let count = offsets.len() - 1;
+    let end = offsets[count];
+    // smallest index of records whose parsed length differs from scanned one
+    let failed = AtomicUsize::new(usize::MAX);
+    let mut records = Vec::with_capacity(count);
+    (0..count)
+        .into_par_iter()
+        .with_min_len(CHUNK)
+        // stage reading a run of records, index of its first record and the next one
+        .map_init(
+            || None,
+            |state: &mut Option<(S, usize, usize)>, index| {
+                match state {
+                    Some((_, _, next)) if *next == index => {}
+                    _ => {
+                        let reader = inner(&bytes[offsets[index]..end], header);
+                        *state = Some((stage(reader), index, index));
+                    }
+                }
+                let (stage, first, next) = state.as_mut().expect("set above");
+                let record = read(stage);
+                *next += 1;
+                let len = (offsets[index + 1] - offsets[*first]) as u64;
+                if stage.inner().position() != len {
+                    failed.fetch_min(index, Ordering::Relaxed);
+                }
+                record
+            },
+        )
+        .collect_into_vec(&mut records);
+    match failed.into_inner() {
+        usize::MAX => Ok(records),
+        index => Err(ParallelReadError::BrokenRecord { section, index }),
+    }
+}
+
+/// read whole PMX in `bytes` parsing vertices and faces, the largest sections, in parallel.
+///
+/// result is the same as [`ModelInfoStage::read_model`]. use it for streams.
+///
+/// # Errors
+/// * `ParallelReadError::InvalidHeader` if header is invalid.
+/// * `ParallelReadError::BrokenRecord` if a vertex or a face is broken.
+///
+/// # Panics
+/// same as [`ModelInfoStage::read_model`] for other sections.
+pub fn read_model_parallel(bytes: &[u8]) -> Result<Model, ParallelReadError> {
+    let stage = ModelInfoStage::from_reader(bytes).ok_or(ParallelReadError::InvalidHeader)?;
+    let header = stage.get_header();
+    let (model_info, stage) = stage.read();
+    let mut position = usize::try_from(stage.0.position()).expect("inside of slice");
+
+    let count = read_count(bytes, &mut position, SectionKind::Vertices)?;
+    let mut offsets = Vec::with_capacity(count + 1);
+    offsets.push(position);
+    for index in 0..count {
+        let len =
+            vertex_len(&bytes[position..], &header).ok_or(ParallelReadError::BrokenRecord {
+                section: SectionKind::Vertices,
+                index,
+            })?;
+        position += len;
+        offsets.push(position);
+    }
+    let vertices = parse(
+        bytes,
+        header,
+        SectionKind::Vertices,
+        &offsets,
+        VerticesStage,
+        VerticesStage::read_pmx_vertex,
+    )?;
+
+    let count = read_count(bytes, &mut position, SectionKind::Faces)? / 3;
+    let len = 3 * usize::from(u8::from(header.s_vertex_index));
+    if bytes.len() < position + count * len {
+        return Err(ParallelReadError::BrokenRecord {
+            section: SectionKind::Faces,
+            index: (bytes.len() - position) / len,
+        });
+    }
+    let offsets = (0..=count)
+        .map(|index| position + index * len)
+        .collect::<Vec<_>>();
+    let faces = parse(
+        bytes,
+        header,
+        SectionKind::Faces,
+        &offsets,
+        FacesStage,
+        FacesStage::read_face,
+    )?;
+    position += count * len;
+
+    let (model, _) = TexturesStage(inner(&bytes[position..], header))
+        .read_rest(model_info, vertices, faces, false)
+        .expect("no callback to cancel");
+    Ok(model)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::progress::SectionKind;
+    use crate::reader::{read_model_parallel, ModelInfoStage, ParallelReadError};
+    use crate::test_support::{cube, write};
+
+    #[test]
+    fn same_as_sequential() {
+        let mut model = cube();
+        // several chunks
+        let vertices = model.vertices.clone();
+        model.vertices = vertices.iter().cycle().take(10_000).cloned().collect();
+        let faces = model.faces.clone();
+        model.faces = faces.iter().cycle().take(9_000).cloned().collect();
+        model.materials[1].num_face_vertices = 3 * 9_000 - model.materials[0].num_face_vertices;
+        let buf = write(&model);
+        let expected = ModelInfoStage::from_reader(&buf[..]).unwrap().read_model();
+        assert_eq!(read_model_parallel(&buf).unwrap(), expected);
+    }
+
+    #[test]
+    fn broken_vertex() {
+        let mut model = cube();
+        let vertex = model.vertices[0].clone();
+        model.vertices = vec![vertex; 5000];
+        let mut buf = write(&model);
+        let (_, offsets) = ModelInfoStage::from_reader(&buf[..])
+            .unwrap()
+            .read_model_with_offsets();
+        let start = offsets.vertices.unwrap().start as usize + 4;
+        let len = offsets.vertices.unwrap().len as usize / 5000;
+        // weight kind of vertex 4321 follows position, normal, uv and 4 additional uvs
+        buf[start + 4321 * len + 96] = 9;
+        assert_eq!(
+            read_model_parallel(&buf),
+            Err(ParallelReadError::BrokenRecord {
+                section: SectionKind::Vertices,
+                index: 4321
+            })
+        );
+        assert_eq!(
+            read_model_parallel(&buf[..start + 10]),
+            Err(ParallelReadError::BrokenRecord {
+                section: SectionKind::Vertices,
+                index: 0
+            })
+        );
+        assert_eq!(
+            read_model_parallel(&[0; 17]),
+            Err(ParallelReadError::InvalidHeader)
+        );
+    }
+}
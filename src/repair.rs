@@ -0,0 +1,514 @@
+//! Make models with broken references loadable.
+//!
+//! readers in [lenient mode](crate::reader::ModelInfoStage::set_lenient) keep indices as read,
+//! [`fix_indices`] then rewrites the ones pointing outside their section like `PMXEditor` does.
+//!
+//! ```rust
+//! use PMXUtil::repair::{fix_indices, RepairPolicy};
+//! # let mut model = PMXUtil::types::Model::default();
+//! let log = fix_indices(&mut model, RepairPolicy::Clamp);
+//! for repair in &log.repairs {
+//!     println!("{}", repair);
+//! }
+//! ```
+use crate::progress::SectionKind;
+use crate::types::{
+    ConnectionDisplayMode, FrameInner, Model, MorphKinds, RotateAndTranslateInherits, ToonMode,
+};
+use crate::usage::material_faces;
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+
+/// what [`fix_indices`] does with a reference outside its section.
+///
+/// references held by an element itself, e.g. parent of a bone, may be `-1` for none.
+/// references which are entries of a list, e.g. face vertices, morph offsets, frame entries,
+/// IK links and soft body anchors and pins, must refer an element.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RepairPolicy {
+    /// set to `-1`. list entries are removed as they can't refer none.
+    Null,
+    /// set to the nearest element, the first for negative indices and the last otherwise.
+    /// set to `-1` or removed as [`Null`](Self::Null) if the section is empty.
+    Clamp,
+    /// remove list entries and faces. references held by an element are set to `-1`
+    /// as removing the element would shift indices of others.
+    RemoveEntry,
+}
+
+/// what happened to a reference.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RepairAction {
+    Nulled,
+    Clamped(i64),
+    /// the entry or face holding the reference is removed.
+    Removed,
+}
+
+/// a reference changed by [`fix_indices`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Repair {
+    /// section of the element holding the reference.
+    pub section: SectionKind,
+    /// index of the element before faces are removed.
+    pub index: usize,
+    pub field: &'static str,
+    /// section the reference points at.
+    pub target: SectionKind,
+    pub old: i64,
+    pub action: RepairAction,
+}
+
+impl Display for Repair {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} {}: {} refers {:?} {} which does not exist, ",
+            self.section, self.index, self.field, self.target, self.old
+        )?;
+        match self.action {
+            RepairAction::Nulled => write!(f, "set to -1"),
+            RepairAction::Clamped(new) => write!(f, "clamped to {}", new),
+            RepairAction::Removed => write!(f, "removed"),
+        }
+    }
+}
+
+/// changes made by [`fix_indices`] in order of sections.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct RepairLog {
+    pub repairs: Vec<Repair>,
+}
+
+impl RepairLog {
+    pub fn is_empty(&self) -> bool {
+        self.repairs.is_empty()
+    }
+}
+
+struct Fixer {
+    policy: RepairPolicy,
+    log: RepairLog,
+}
+
+impl Fixer {
+    fn log(
+        &mut self,
+        (section, index, field): (SectionKind, usize, &'static str),
+        target: SectionKind,
+        old: i64,
+        action: RepairAction,
+    ) {
+        self.log.repairs.push(Repair {
+            section,
+            index,
+            field,
+            target,
+            old,
+            action,
+        });
+    }
+
+    /// fix reference held by an element which may be `-1`.
+    fn field(
+        &mut self,
+        at: (SectionKind, usize, &'static str),
+        (target, len): (SectionKind, usize),
+        index: &mut i32,
+    ) {
+        let old = i64::from(*index);
+        if old == -1 || in_range(old, len) {
+            return;
+        }
+        match (self.policy, clamp(old, len)) {
+            (RepairPolicy::Clamp, Some(new)) => {
+                *index = new as i32;
+                self.log(at, target, old, RepairAction::Clamped(new));
+            }
+            _ => {
+                *index = -1;
+                self.log(at, target, old, RepairAction::Nulled);
+            }
+        }
+    }
+
+    /// fix reference which is an entry of a list. `false` if the entry should be removed.
+    fn entry<I: Copy + Into<i64> + TryFrom<i64>>(
+        &mut self,
+        at: (SectionKind, usize, &'static str),
+        (target, len): (SectionKind, usize),
+        index: &mut I,
+    ) -> bool {
+        let old = (*index).into();
+        if in_range(old, len) {
+            return true;
+        }
+        let clamped = clamp(old, len).and_then(|new| Some((new, I::try_from(new).ok()?)));
+        match (self.policy, clamped) {
+            (RepairPolicy::Clamp, Some((new, clamped))) => {
+                *index = clamped;
+                self.log(at, target, old, RepairAction::Clamped(new));
+                true
+            }
+            _ => {
+                self.log(at, target, old, RepairAction::Removed);
+                false
+            }
+        }
+    }
+
+    /// fix entries of `list` and remove the ones which can't be fixed.
+    fn entries<T, I: Copy + Into<i64> + TryFrom<i64>>(
+        &mut self,
+        at: (SectionKind, usize, &'static str),
+        target: (SectionKind, usize),
+        list: &mut Vec<T>,
+        index: impl Fn(&mut T) -> &mut I,
+    ) {
+        list.retain_mut(|entry| self.entry(at, target, index(entry)));
+    }
+}
+
+fn in_range(index: i64, len: usize) -> bool {
+    usize::try_from(index).is_ok_and(|index| index < len)
+}
+
+/// nearest index of `len` elements. `None` if empty.
+fn clamp(index: i64, len: usize) -> Option<i64> {
+    let last = i64::try_from(len)
+        .ok()?
+        .checked_sub(1)
+        .filter(|last| *last >= 0)?;
+    Some(index.clamp(0, last.min(i64::from(i32::MAX))))
+}
+
+/// rewrite every index pointing outside its section by `policy`.
+///
+/// covers vertex weights, faces, material textures, bone parents, connections, inherits and
+/// IK, morph offsets, frame entries, rigid body bones, joint rigid bodies and soft body
+/// materials, anchors and pins. elements are never removed so valid indices stay valid.
+/// `num_face_vertices` of materials are updated when faces are removed.
+///
+/// face vertices can't be `-1`, so faces with broken vertices are removed by `Null` too.
+/// `-1` of material morph offsets targets every material and is kept.
+pub fn fix_indices(model: &mut Model, policy: RepairPolicy) -> RepairLog {
+    let mut fixer = Fixer {
+        policy,
+        log: RepairLog::default(),
+    };
+    let vertices = (SectionKind::Vertices, model.vertices.len());
+    let textures = (SectionKind::Textures, model.textures.textures.len());
+    let materials = (SectionKind::Materials, model.materials.len());
+    let bones = (SectionKind::Bones, model.bones.len());
+    let morphs = (SectionKind::Morphs, model.morphs.len());
+    let rigid_bodies = (SectionKind::RigidBodies, model.rigid_bodies.len());
+
+    for (index, vertex) in model.vertices.iter_mut().enumerate() {
+        for bone in vertex.weight_type.bone_indices_mut() {
+            fixer.field((SectionKind::Vertices, index, "bone"), bones, bone);
+        }
+    }
+
+    let groups = material_faces(model);
+    let mut removed = vec![false; model.faces.len()];
+    for (index, face) in model.faces.iter_mut().enumerate() {
+        let at = (SectionKind::Faces, index, "vertex");
+        for vertex in face.vertices.iter_mut() {
+            // a face is removed as a whole so the other vertices are left as is.
+            if !fixer.entry(at, vertices, vertex) {
+                removed[index] = true;
+            }
+        }
+    }
+    for (material, group) in model.materials.iter_mut().zip(groups) {
+        let count = removed[group].iter().filter(|removed| **removed).count();
+        material.num_face_vertices -= i32::try_from(count * 3).unwrap_or(i32::MAX);
+    }
+    let mut removed = removed.into_iter();
+    model.faces.retain(|_| !removed.next().unwrap_or(false));
+
+    for (index, material) in model.materials.iter_mut().enumerate() {
+        let at = |field| (SectionKind::Materials, index, field);
+        fixer.field(at("texture"), textures, &mut material.texture_index);
+        if let Some(sphere) = &mut material.sphere_mode {
+            fixer.field(at("sphere"), textures, &mut sphere.index);
+        }
+        if let ToonMode::Separate(toon) = &mut material.toon_mode {
+            fixer.field(at("toon"), textures, toon);
+        }
+    }
+
+    for (index, bone) in model.bones.iter_mut().enumerate() {
+        let at = |field| (SectionKind::Bones, index, field);
+        fixer.field(at("parent"), bones, &mut bone.parent);
+        if let ConnectionDisplayMode::OtherBone(other) = &mut bone.connection_display_mode {
+            fixer.field(at("connection"), bones, other);
+        }
+        match &mut bone.inherits.rotate_and_translate {
+            RotateAndTranslateInherits::Both(parent, _)
+            | RotateAndTranslateInherits::Rotate(parent, _)
+            | RotateAndTranslateInherits::Translate(parent, _) => {
+                fixer.field(at("inherit"), bones, parent)
+            }
+            RotateAndTranslateInherits::None => {}
+        }
+        if let Some(ik) = &mut bone.ik_info {
+            fixer.field(at("ik_target"), bones, &mut ik.ik_target_bone_index);
+            fixer.entries(at("ik_link"), bones, &mut ik.ik_links, |link| {
+                &mut link.ik_bone_index
+            });
+        }
+    }
+
+    for (index, morph) in model.morphs.iter_mut().enumerate() {
+        let at = (SectionKind::Morphs, index, "offset");
+        match &mut morph.morph_data {
+            MorphKinds::Vertex(offsets) => fixer.entries(at, vertices, offsets, |o| &mut o.index),
+            MorphKinds::UV(offsets)
+            | MorphKinds::UV1(offsets)
+            | MorphKinds::UV2(offsets)
+            | MorphKinds::UV3(offsets)
+            | MorphKinds::UV4(offsets) => fixer.entries(at, vertices, offsets, |o| &mut o.index),
+            MorphKinds::Bone(offsets) => fixer.entries(at, bones, offsets, |o| &mut o.index),
+            // `-1` targets every material.
+            MorphKinds::Material(offsets) => {
+                offsets.retain_mut(|o| o.index == -1 || fixer.entry(at, materials, &mut o.index))
+            }
+            MorphKinds::Group(offsets) => fixer.entries(at, morphs, offsets, |o| &mut o.index),
+            MorphKinds::Flip(offsets) => fixer.entries(at, morphs, offsets, |o| &mut o.index),
+            MorphKinds::Impulse(offsets) => {
+                fixer.entries(at, rigid_bodies, offsets, |o| &mut o.rigid_index)
+            }
+        }
+    }
+
+    for (index, frame) in model.frames.iter_mut().enumerate() {
+        let at = (SectionKind::Frames, index, "entry");
+        frame.inners.retain_mut(|inner| match inner {
+            FrameInner::Bone(bone) => fixer.entry(at, bones, bone),
+            FrameInner::Morph(morph) => fixer.entry(at, morphs, morph),
+        });
+    }
+
+    for (index, rigid) in model.rigid_bodies.iter_mut().enumerate() {
+        fixer.field(
+            (SectionKind::RigidBodies, index, "bone"),
+            bones,
+            &mut rigid.bone_index,
+        );
+    }
+
+    for (index, joint) in model.joints.iter_mut().enumerate() {
+        let (a, b) = joint.joint_type.rigid_indices_mut();
+        fixer.field((SectionKind::Joints, index, "rigid_a"), rigid_bodies, a);
+        fixer.field((SectionKind::Joints, index, "rigid_b"), rigid_bodies, b);
+    }
+
+    for (index, soft_body) in model.soft_bodies.iter_mut().enumerate() {
+        let at = |field| (SectionKind::SoftBodies, index, field);
+        fixer.field(at("material"), materials, &mut soft_body.material_index);
+        soft_body.anchor_rigid.retain_mut(|anchor| {
+            fixer.entry(at("anchor_rigid"), rigid_bodies, &mut anchor.rigid_index)
+                && fixer.entry(at("anchor_vertex"), vertices, &mut anchor.vertex_index)
+        });
+        fixer.entries(at("pin"), vertices, &mut soft_body.pin_vertex, |pin| pin);
+    }
+    fixer.log
+}
+
+#[cfg(test)]
+mod test {
+    use crate::progress::SectionKind;
+    use crate::repair::{fix_indices, Repair, RepairAction, RepairPolicy};
+    use crate::types::{
+        ConnectionDisplayMode, Face, FrameInner, Model, MorphKinds, SoftBodyAnchorRigid,
+    };
+
+    /// cube with one broken reference in each section.
+    fn broken() -> Model {
+        let mut model = crate::test_support::cube();
+        *model.vertices[0].weight_type.bone_indices_mut()[0] = 7;
+        model.faces[0].vertices[2] = 100;
+        model.materials[1].texture_index = -3;
+        model.bones[0].connection_display_mode = ConnectionDisplayMode::OtherBone(999);
+        if let MorphKinds::Bone(offsets) = &mut model.morphs[6].morph_data {
+            offsets[0].index = 999;
+        }
+        model.frames[0].inners.push(FrameInner::Bone(-1));
+        model.rigid_bodies[0].bone_index = 5;
+        *model.joints[0].joint_type.rigid_indices_mut().1 = 9;
+        model.soft_bodies[0].anchor_rigid.push(SoftBodyAnchorRigid {
+            rigid_index: 0,
+            vertex_index: 8,
+            near_mode: false,
+        });
+        model
+    }
+
+    fn repair(
+        section: SectionKind,
+        index: usize,
+        field: &'static str,
+        target: SectionKind,
+        old: i64,
+        action: RepairAction,
+    ) -> Repair {
+        Repair {
+            section,
+            index,
+            field,
+            target,
+            old,
+            action,
+        }
+    }
+
+    #[test]
+    fn each_policy() {
+        let original = broken();
+        let expected = |policy: RepairPolicy| {
+            let field = match policy {
+                RepairPolicy::Clamp => RepairAction::Clamped,
+                _ => |_| RepairAction::Nulled,
+            };
+            let entry = match policy {
+                RepairPolicy::Clamp => RepairAction::Clamped,
+                _ => |_| RepairAction::Removed,
+            };
+            vec![
+                repair(
+                    SectionKind::Vertices,
+                    0,
+                    "bone",
+                    SectionKind::Bones,
+                    7,
+                    field(1),
+                ),
+                repair(
+                    SectionKind::Faces,
+                    0,
+                    "vertex",
+                    SectionKind::Vertices,
+                    100,
+                    entry(7),
+                ),
+                repair(
+                    SectionKind::Materials,
+                    1,
+                    "texture",
+                    SectionKind::Textures,
+                    -3,
+                    field(0),
+                ),
+                repair(
+                    SectionKind::Bones,
+                    0,
+                    "connection",
+                    SectionKind::Bones,
+                    999,
+                    field(1),
+                ),
+                repair(
+                    SectionKind::Morphs,
+                    6,
+                    "offset",
+                    SectionKind::Bones,
+                    999,
+                    entry(1),
+                ),
+                repair(
+                    SectionKind::Frames,
+                    0,
+                    "entry",
+                    SectionKind::Bones,
+                    -1,
+                    entry(0),
+                ),
+                repair(
+                    SectionKind::RigidBodies,
+                    0,
+                    "bone",
+                    SectionKind::Bones,
+                    5,
+                    field(1),
+                ),
+                repair(
+                    SectionKind::Joints,
+                    0,
+                    "rigid_b",
+                    SectionKind::RigidBodies,
+                    9,
+                    field(0),
+                ),
+                repair(
+                    SectionKind::SoftBodies,
+                    0,
+                    "anchor_vertex",
+                    SectionKind::Vertices,
+                    8,
+                    entry(7),
+                ),
+            ]
+        };
+
+        let mut model = original.clone();
+        let log = fix_indices(&mut model, RepairPolicy::Clamp);
+        assert_eq!(log.repairs, expected(RepairPolicy::Clamp));
+        assert_eq!(model.faces[0].vertices[2], 7);
+        assert_eq!(model.faces.len(), original.faces.len());
+        assert_eq!(
+            model.bones[0].connection_display_mode,
+            ConnectionDisplayMode::OtherBone(1)
+        );
+        assert_eq!(model.frames[0].inners.last(), Some(&FrameInner::Bone(0)));
+        assert_eq!(
+            model.soft_bodies[0]
+                .anchor_rigid
+                .last()
+                .unwrap()
+                .vertex_index,
+            7
+        );
+        // fixed model has nothing left to fix.
+        assert!(fix_indices(&mut model, RepairPolicy::Null).is_empty());
+
+        for policy in [RepairPolicy::Null, RepairPolicy::RemoveEntry] {
+            let mut model = original.clone();
+            let log = fix_indices(&mut model, policy);
+            assert_eq!(log.repairs, expected(policy), "{:?}", policy);
+            assert_eq!(*model.vertices[0].weight_type.bone_indices_mut()[0], -1);
+            assert_eq!(model.faces, original.faces[1..]);
+            assert_eq!(
+                model.materials[0].num_face_vertices,
+                original.materials[0].num_face_vertices - 3
+            );
+            assert_eq!(model.materials[1].texture_index, -1);
+            assert!(matches!(
+                &model.morphs[6].morph_data,
+                MorphKinds::Bone(offsets) if offsets.len() + 1 == match &original.morphs[6].morph_data {
+                    MorphKinds::Bone(offsets) => offsets.len(),
+                    _ => unreachable!(),
+                }
+            ));
+            assert_eq!(model.frames[0].inners, original.frames[0].inners[..1]);
+            assert_eq!(model.rigid_bodies[0].bone_index, -1);
+            assert_eq!(model.joints[0].joint_type.rigid_indices().1, -1);
+            assert_eq!(
+                model.soft_bodies[0].anchor_rigid.len() + 1,
+                original.soft_bodies[0].anchor_rigid.len()
+            );
+            assert!(fix_indices(&mut model, policy).is_empty());
+        }
+
+        // nothing to clamp to in an empty section.
+        let mut model = Model {
+            faces: vec![Face {
+                vertices: [0, 1, 2],
+            }],
+            ..Model::default()
+        };
+        let log = fix_indices(&mut model, RepairPolicy::Clamp);
+        assert_eq!(log.repairs.len(), 3);
+        assert!(model.faces.is_empty());
+    }
+}
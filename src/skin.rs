@@ -0,0 +1,511 @@
+//! Split meshes for skinning with limited bone palette and check `SDEF` parameters.
+//!
+//! GPU skinning usually uploads bone matrices per draw call and the palette size is limited.
+//! [`partition`] splits faces of each material into parts using at most `max_bones` bones
+//! and gives vertices local bone indices in the part.
+//!
+//! models converted by other tools often have broken `SDEF` parameters collapsing the mesh.
+//! [`validate_sdef`] finds them and [`approximate_sdef`] rebuilds them from bone positions.
+//!
+//! ```rust
+//! let model = PMXUtil::types::Model::default();
+//! for part in PMXUtil::skin::partition(&model, 256) {
+//!     println!("material {} uses {} bones", part.material, part.palette.len());
+//! }
+//! ```
+use crate::types::{Face, Model, Vec3, VertexWeight};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+
+/// faces drawn by one material sharing a bone palette.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SkinPartition {
+    /// index of material drawing the faces.
+    pub material: usize,
+    /// faces indexing [`vertices`](Self::vertices) of this partition.
+    pub faces: Vec<[u32; 3]>,
+    /// model vertex index of each local vertex.
+    pub vertices: Vec<u32>,
+    /// model bone index of each local bone index.
+    pub palette: Vec<i32>,
+    /// up to 4 local bone indices per local vertex. unused slots are 0 with weight 0.
+    pub joints: Vec<[u32; 4]>,
+    /// weights of `joints`.
+    pub weights: Vec<[f32; 4]>,
+}
+
+impl SkinPartition {
+    /// local index of model bone `bone`.
+    pub fn local_bone(&self, bone: i32) -> Option<u32> {
+        self.palette
+            .iter()
+            .position(|&b| b == bone)
+            .map(|local| local as u32)
+    }
+}
+
+/// split faces to parts using at most `max_bones` bones each.
+///
+/// parts never cross materials and a material is split only when it uses too many bones.
+/// faces are kept in order. a face using more than `max_bones` bones by itself gets its own part
+/// exceeding the limit. faces not drawn by any material are ignored.
+/// faces referring missing vertices are skipped.
+/// bones with zero weight don't count. SDEF and QDEF are treated as BDEF2 and BDEF4.
+pub fn partition(model: &Model, max_bones: usize) -> Vec<SkinPartition> {
+    let mut partitions = vec![];
+    let mut start = 0;
+    for (material, m) in model.materials.iter().enumerate() {
+        let count = usize::try_from(m.num_face_vertices).unwrap_or(0) / 3;
+        let end = (start + count).min(model.faces.len());
+        let mut part = PartitionBuilder::new(material);
+        for face in &model.faces[start..end] {
+            if !has_vertices(model, face) {
+                continue;
+            }
+            let bones = face_bones(model, face);
+            if !part.partition.faces.is_empty() && part.bones_with(&bones) > max_bones {
+                partitions.push(part.partition);
+                part = PartitionBuilder::new(material);
+            }
+            part.add_face(model, face);
+        }
+        if !part.partition.faces.is_empty() {
+            partitions.push(part.partition);
+        }
+        start = end;
+    }
+    partitions
+}
+
+fn has_vertices(model: &Model, face: &Face) -> bool {
+    face.vertices.iter().all(|&vertex| {
+        usize::try_from(vertex)
+            .ok()
+            .and_then(|v| model.vertices.get(v))
+            .is_some()
+    })
+}
+
+/// bones affecting vertex with non zero weight. at most 4.
+fn vertex_bones(model: &Model, vertex: u32) -> impl Iterator<Item = (i32, f32)> {
+    model.vertices[vertex as usize]
+        .weight_type
+        .bone_weights()
+        .into_iter()
+        .filter(|&(bone, weight)| bone >= 0 && weight != 0.0)
+        .take(4)
+}
+
+fn face_bones(model: &Model, face: &Face) -> Vec<i32> {
+    let mut bones = face
+        .vertices
+        .iter()
+        .flat_map(|&vertex| vertex_bones(model, vertex).map(|(bone, _)| bone))
+        .collect::<Vec<_>>();
+    bones.sort_unstable();
+    bones.dedup();
+    bones
+}
+
+struct PartitionBuilder {
+    partition: SkinPartition,
+    local_vertices: HashMap<u32, u32>,
+    local_bones: HashMap<i32, u32>,
+}
+
+impl PartitionBuilder {
+    fn new(material: usize) -> Self {
+        Self {
+            partition: SkinPartition {
+                material,
+                ..SkinPartition::default()
+            },
+            local_vertices: HashMap::new(),
+            local_bones: HashMap::new(),
+        }
+    }
+
+    /// palette size after adding `bones`.
+    fn bones_with(&self, bones: &[i32]) -> usize {
+        self.local_bones.len()
+            + bones
+                .iter()
+                .filter(|bone| !self.local_bones.contains_key(bone))
+                .count()
+    }
+
+    fn add_face(&mut self, model: &Model, face: &Face) {
+        let mut local_face = [0; 3];
+        for (local, &vertex) in local_face.iter_mut().zip(face.vertices.iter()) {
+            *local = self.add_vertex(model, vertex);
+        }
+        self.partition.faces.push(local_face);
+    }
+
+    fn add_vertex(&mut self, model: &Model, vertex: u32) -> u32 {
+        if let Some(&local) = self.local_vertices.get(&vertex) {
+            return local;
+        }
+        let mut joints = [0; 4];
+        let mut weights = [0.0; 4];
+        for (slot, (bone, weight)) in vertex_bones(model, vertex).enumerate() {
+            let palette = &mut self.partition.palette;
+            joints[slot] = *self.local_bones.entry(bone).or_insert_with(|| {
+                palette.push(bone);
+                palette.len() as u32 - 1
+            });
+            weights[slot] = weight;
+        }
+        let local = self.partition.vertices.len() as u32;
+        self.partition.vertices.push(vertex);
+        self.partition.joints.push(joints);
+        self.partition.weights.push(weights);
+        self.local_vertices.insert(vertex, local);
+        local
+    }
+}
+
+/// `SDEF` values with larger coordinates are reported as [`SdefIssue::InvalidValue`].
+pub const SDEF_MAX_COORDINATE: f32 = 1.0e5;
+
+/// problem of `SDEF` vertex found by [`validate_sdef`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SdefIssue {
+    /// C, R0 or R1 of `vertex` is NaN, infinite or beyond [`SDEF_MAX_COORDINATE`].
+    InvalidValue { vertex: usize },
+    /// both bones of `vertex` are at the same position so there is no segment to rotate around.
+    DegenerateBones { vertex: usize },
+    /// C of `vertex` is `distance` away from the segment between the bones,
+    /// more than a tenth of the segment length.
+    CenterOffSegment { vertex: usize, distance: f32 },
+}
+
+impl Display for SdefIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SdefIssue::InvalidValue { vertex } => {
+                write!(f, "vertex {}: invalid SDEF parameter", vertex)
+            }
+            SdefIssue::DegenerateBones { vertex } => {
+                write!(f, "vertex {}: SDEF bones are at the same position", vertex)
+            }
+            SdefIssue::CenterOffSegment { vertex, distance } => write!(
+                f,
+                "vertex {}: SDEF C is {} away from the bone segment",
+                vertex, distance
+            ),
+        }
+    }
+}
+
+/// check C, R0 and R1 of `SDEF` vertices.
+///
+/// vertices referring missing bones are skipped,
+/// see [`validation::check_geometry`](crate::validation::check_geometry) for them.
+pub fn validate_sdef(model: &Model) -> Vec<SdefIssue> {
+    let mut issues = vec![];
+    for (vertex, sdef) in sdef_vertices(model) {
+        let values = [sdef.c, sdef.r0, sdef.r1];
+        if values
+            .iter()
+            .flatten()
+            .any(|v| !v.is_finite() || v.abs() > SDEF_MAX_COORDINATE)
+        {
+            issues.push(SdefIssue::InvalidValue { vertex });
+            continue;
+        }
+        let length = distance(sdef.bones[0], sdef.bones[1]);
+        if length < f32::EPSILON {
+            issues.push(SdefIssue::DegenerateBones { vertex });
+            continue;
+        }
+        let distance = distance(sdef.c, closest_on_segment(sdef.c, sdef.bones));
+        if distance > length * 0.1 {
+            issues.push(SdefIssue::CenterOffSegment { vertex, distance });
+        }
+    }
+    issues
+}
+
+/// set C, R0 and R1 of `SDEF` vertices to values derived from bone positions and weights
+/// and return the number of changed vertices.
+///
+/// this is not `PMXEditor`'s "SDEF再計算", whose algorithm is not published, and results differ
+/// from it. it is a simple approximation giving parameters [`validate_sdef`] accepts.
+/// with bone positions `B0` and `B1` and weight `w` of the first bone,
+/// * C is the point on segment `B0`-`B1` closest to the vertex.
+/// * R0 and R1 start at `B0` and `B1` and are moved by `C - (w * B0 + (1 - w) * B1)`
+///   so that their weighted average is C as the PMX specification requires.
+///
+/// vertices referring missing bones or bones at the same position are unchanged.
+pub fn approximate_sdef(model: &mut Model) -> usize {
+    let recomputed = sdef_vertices(model)
+        .filter(|(_, sdef)| distance(sdef.bones[0], sdef.bones[1]) >= f32::EPSILON)
+        .map(|(vertex, sdef)| {
+            let [b0, b1] = sdef.bones;
+            let c = closest_on_segment(model.vertices[vertex].position, sdef.bones);
+            let w = sdef.weight;
+            let shift: Vec3 = std::array::from_fn(|i| c[i] - (w * b0[i] + (1.0 - w) * b1[i]));
+            let r0 = std::array::from_fn(|i| b0[i] + shift[i]);
+            let r1 = std::array::from_fn(|i| b1[i] + shift[i]);
+            (vertex, [c, r0, r1])
+        })
+        .collect::<Vec<_>>();
+    let mut changed = 0;
+    for (vertex, new) in recomputed {
+        if let VertexWeight::SDEF {
+            sdef_c,
+            sdef_r0,
+            sdef_r1,
+            ..
+        } = &mut model.vertices[vertex].weight_type
+        {
+            if [*sdef_c, *sdef_r0, *sdef_r1] != new {
+                [*sdef_c, *sdef_r0, *sdef_r1] = new;
+                changed += 1;
+            }
+        }
+    }
+    changed
+}
+
+struct Sdef {
+    bones: [Vec3; 2],
+    weight: f32,
+    c: Vec3,
+    r0: Vec3,
+    r1: Vec3,
+}
+
+/// `SDEF` vertices whose bones exist.
+fn sdef_vertices(model: &Model) -> impl Iterator<Item = (usize, Sdef)> + '_ {
+    let bones = &model.bones;
+    let bone = move |index: i32| {
+        usize::try_from(index)
+            .ok()
+            .and_then(|i| bones.get(i))
+            .map(|bone| bone.position)
+    };
+    model
+        .vertices
+        .iter()
+        .enumerate()
+        .filter_map(move |(index, vertex)| match vertex.weight_type {
+            VertexWeight::SDEF {
+                bone_index_1,
+                bone_index_2,
+                bone_weight_1,
+                sdef_c,
+                sdef_r0,
+                sdef_r1,
+            } => Some((
+                index,
+                Sdef {
+                    bones: [bone(bone_index_1)?, bone(bone_index_2)?],
+                    weight: bone_weight_1,
+                    c: sdef_c,
+                    r0: sdef_r0,
+                    r1: sdef_r1,
+                },
+            )),
+            _ => None,
+        })
+}
+
+fn distance(a: Vec3, b: Vec3) -> f32 {
+    (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f32>().sqrt()
+}
+
+/// point on segment `ends` closest to `point`.
+fn closest_on_segment(point: Vec3, ends: [Vec3; 2]) -> Vec3 {
+    let [a, b] = ends;
+    let ab: Vec3 = std::array::from_fn(|i| b[i] - a[i]);
+    let length = ab.iter().map(|v| v * v).sum::<f32>();
+    let t = if length > 0.0 {
+        ((0..3).map(|i| (point[i] - a[i]) * ab[i]).sum::<f32>() / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    std::array::from_fn(|i| a[i] + ab[i] * t)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::skin::{approximate_sdef, partition, validate_sdef, SdefIssue};
+    use crate::types::{Bone, Face, Material, Model, Vertex, VertexWeight};
+
+    /// strip of `bones - 1` quads. row `i` of vertices is skinned to bones `i` and `i + 1`.
+    fn rig(bones: i32, materials: &[i32]) -> Model {
+        let mut model = Model::default();
+        for i in 0..bones {
+            for side in 0..2 {
+                model.vertices.push(Vertex {
+                    position: [side as f32, i as f32, 0.0],
+                    norm: [0.0, 0.0, 1.0],
+                    uv: [0.0; 2],
+                    add_uv: [[0.0; 4]; 4],
+                    weight_type: VertexWeight::BDEF2 {
+                        bone_index_1: i,
+                        bone_index_2: (i + 1).min(bones - 1),
+                        bone_weight_1: 0.5,
+                    },
+                    edge_mag: 1.0,
+                });
+            }
+        }
+        for i in 0..bones - 1 {
+            let [a, b, c, d] = [2 * i, 2 * i + 1, 2 * i + 2, 2 * i + 3].map(|v| v as u32);
+            model.faces.push(Face {
+                vertices: [a, b, c],
+            });
+            model.faces.push(Face {
+                vertices: [b, d, c],
+            });
+        }
+        model.materials = materials
+            .iter()
+            .map(|&quads| {
+                Material::builder("skin")
+                    .num_face_vertices(6 * quads)
+                    .build()
+            })
+            .collect();
+        model
+    }
+
+    #[test]
+    fn palette_limit() {
+        let model = rig(20, &[12, 7]);
+        let parts = partition(&model, 4);
+        let mut faces = 0;
+        for part in &parts {
+            assert!(part.palette.len() <= 4, "{:?}", part.palette);
+            // local geometry and weights match the model
+            for (local, global) in part.faces.iter().zip(&model.faces[faces..]) {
+                let vertices = local.map(|v| part.vertices[v as usize]);
+                assert_eq!(vertices, global.vertices);
+            }
+            for (i, &vertex) in part.vertices.iter().enumerate() {
+                let mut weights = model.vertices[vertex as usize].weight_type.bone_weights();
+                weights.retain(|&(_, weight)| weight != 0.0);
+                let local = part.joints[i]
+                    .iter()
+                    .zip(part.weights[i].iter())
+                    .take(weights.len())
+                    .map(|(&joint, &weight)| (part.palette[joint as usize], weight))
+                    .collect::<Vec<_>>();
+                assert_eq!(local, weights);
+                assert_eq!(part.local_bone(local[0].0), Some(part.joints[i][0]));
+            }
+            faces += part.faces.len();
+        }
+        assert_eq!(faces, model.faces.len());
+        // quad `i` uses bones `i..=i + 2` so 2 quads fit in 4 bones.
+        let materials = parts.iter().map(|part| part.material).collect::<Vec<_>>();
+        assert_eq!(materials, [0, 0, 0, 0, 0, 0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn material_boundaries() {
+        let model = rig(20, &[12, 7]);
+        let parts = partition(&model, 256);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].palette, (0..=13).collect::<Vec<_>>());
+        assert_eq!(parts[1].palette, (12..20).collect::<Vec<_>>());
+        assert_eq!(parts[1].faces[0], [0, 1, 2]);
+    }
+
+    #[test]
+    fn missing_vertices_skipped() {
+        let mut model = rig(3, &[2]);
+        model.faces[1].vertices[2] = 100;
+        model.faces[2].vertices[0] = u32::MAX;
+        let parts = partition(&model, 256);
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].faces.len(), 2);
+    }
+
+    #[test]
+    fn face_over_limit() {
+        let model = rig(3, &[2]);
+        let parts = partition(&model, 1);
+        assert_eq!(parts.len(), 4);
+        assert!(parts.iter().all(|part| part.faces.len() == 1));
+    }
+
+    fn sdef(position: [f32; 3], weight: f32, bones: [i32; 2], c: [f32; 3]) -> Vertex {
+        Vertex {
+            weight_type: VertexWeight::SDEF {
+                bone_index_1: bones[0],
+                bone_index_2: bones[1],
+                bone_weight_1: weight,
+                sdef_c: c,
+                sdef_r0: c,
+                sdef_r1: c,
+            },
+            position,
+            ..rig(1, &[]).vertices[0].clone()
+        }
+    }
+
+    #[test]
+    fn sdef_parameters() {
+        let mut model = Model {
+            bones: vec![
+                Bone::builder("腕").build(),
+                Bone::builder("ひじ").position([0.0, 2.0, 0.0]).build(),
+            ],
+            ..Model::default()
+        };
+        model.vertices = vec![
+            sdef([1.0, 0.5, 0.0], 0.75, [0, 1], [0.0; 3]),
+            sdef([1.0, 0.5, 0.0], 0.5, [0, 1], [0.0, 0.5, 0.0]),
+            sdef([1.0, 3.0, 0.0], 0.5, [0, 1], [f32::NAN, 0.0, 0.0]),
+            sdef([1.0, 0.5, 0.0], 0.5, [0, 1], [1.0, 0.5, 0.0]),
+            sdef([1.0, 0.5, 0.0], 0.5, [0, 0], [0.0; 3]),
+            // missing bones are left to other checks.
+            sdef([1.0, 0.5, 0.0], 0.5, [0, 5], [f32::NAN; 3]),
+        ];
+        assert_eq!(
+            validate_sdef(&model),
+            [
+                SdefIssue::InvalidValue { vertex: 2 },
+                SdefIssue::CenterOffSegment {
+                    vertex: 3,
+                    distance: 1.0
+                },
+                SdefIssue::DegenerateBones { vertex: 4 },
+            ]
+        );
+
+        assert_eq!(approximate_sdef(&mut model), 4);
+        assert!(validate_sdef(&model)
+            .iter()
+            .all(|issue| *issue == SdefIssue::DegenerateBones { vertex: 4 }));
+        let params = |vertex: usize| match model.vertices[vertex].weight_type {
+            VertexWeight::SDEF {
+                sdef_c,
+                sdef_r0,
+                sdef_r1,
+                ..
+            } => [sdef_c, sdef_r0, sdef_r1],
+            _ => unreachable!(),
+        };
+        // weighted average of the bones is already C.
+        assert_eq!(
+            params(0),
+            [[0.0, 0.5, 0.0], [0.0, 0.0, 0.0], [0.0, 2.0, 0.0]]
+        );
+        assert_eq!(
+            params(1),
+            [[0.0, 0.5, 0.0], [0.0, -0.5, 0.0], [0.0, 1.5, 0.0]]
+        );
+        // C is clamped to the segment.
+        assert_eq!(
+            params(2),
+            [[0.0, 2.0, 0.0], [0.0, 1.0, 0.0], [0.0, 3.0, 0.0]]
+        );
+        assert_eq!(approximate_sdef(&mut model), 0);
+    }
+}
@@ -0,0 +1,517 @@
+//! Model statistics.
+//!
+//! ```rust
+//! let model = PMXUtil::types::Model::default();
+//! let stats = PMXUtil::stats::analyze(&model);
+//! assert_eq!(stats.vertices, 0);
+//! println!("{}", stats);
+//! ```
+use crate::types::{
+    Bone, Frame, Joint, Material, Model, Morph, MorphKinds, Rigid, SoftBody, Vertex, VertexWeight,
+};
+use crate::validation::{weights_are_normalized, GeometryOptions};
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+use std::mem::size_of;
+
+/// number of vertices per weight type.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WeightTypeCounts {
+    pub bdef1: usize,
+    pub bdef2: usize,
+    pub bdef4: usize,
+    pub sdef: usize,
+    pub qdef: usize,
+}
+
+/// number of morphs per kind.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MorphKindCounts {
+    pub vertex: usize,
+    pub uv: usize,
+    pub uv1: usize,
+    pub uv2: usize,
+    pub uv3: usize,
+    pub uv4: usize,
+    pub bone: usize,
+    pub material: usize,
+    pub group: usize,
+    pub flip: usize,
+    pub impulse: usize,
+}
+
+/// result of [`analyze`]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ModelStats {
+    /// "2.0" or "2.1"
+    pub version: String,
+    pub vertices: usize,
+    pub faces: usize,
+    pub textures: usize,
+    pub materials: usize,
+    pub bones: usize,
+    pub morphs: usize,
+    pub frames: usize,
+    pub rigid_bodies: usize,
+    pub joints: usize,
+    pub soft_bodies: usize,
+    pub weight_types: WeightTypeCounts,
+    /// additional uv count declared by the model.
+    pub additional_uv: u8,
+    /// number of vertices which have non zero value in each additional uv channel.
+    pub additional_uv_usage: [usize; 4],
+    pub ik_bones: usize,
+    pub ik_links: usize,
+    pub morph_kinds: MorphKindCounts,
+    /// rough size of parsed structures in bytes including heap allocated strings and vectors.
+    pub estimated_memory: usize,
+}
+
+/// collect statistics of `model`
+pub fn analyze(model: &Model) -> ModelStats {
+    let mut stats = ModelStats {
+        version: model.version.to_string(),
+        vertices: model.vertices.len(),
+        faces: model.faces.len(),
+        textures: model.textures.textures.len(),
+        materials: model.materials.len(),
+        bones: model.bones.len(),
+        morphs: model.morphs.len(),
+        frames: model.frames.len(),
+        rigid_bodies: model.rigid_bodies.len(),
+        joints: model.joints.len(),
+        soft_bodies: model.soft_bodies.len(),
+        additional_uv: model.additional_uv,
+        ..ModelStats::default()
+    };
+    for vertex in &model.vertices {
+        let counter = match vertex.weight_type {
+            VertexWeight::BDEF1(_) => &mut stats.weight_types.bdef1,
+            VertexWeight::BDEF2 { .. } => &mut stats.weight_types.bdef2,
+            VertexWeight::BDEF4 { .. } => &mut stats.weight_types.bdef4,
+            VertexWeight::SDEF { .. } => &mut stats.weight_types.sdef,
+            VertexWeight::QDEF { .. } => &mut stats.weight_types.qdef,
+        };
+        *counter += 1;
+        for (usage, add_uv) in stats
+            .additional_uv_usage
+            .iter_mut()
+            .zip(vertex.add_uv.iter())
+        {
+            if add_uv.iter().any(|x| *x != 0.0) {
+                *usage += 1;
+            }
+        }
+    }
+    for ik_info in model.bones.iter().filter_map(|bone| bone.ik_info.as_ref()) {
+        stats.ik_bones += 1;
+        stats.ik_links += ik_info.ik_links.len();
+    }
+    for morph in &model.morphs {
+        let kinds = &mut stats.morph_kinds;
+        let counter = match morph.morph_data {
+            MorphKinds::Vertex(_) => &mut kinds.vertex,
+            MorphKinds::UV(_) => &mut kinds.uv,
+            MorphKinds::UV1(_) => &mut kinds.uv1,
+            MorphKinds::UV2(_) => &mut kinds.uv2,
+            MorphKinds::UV3(_) => &mut kinds.uv3,
+            MorphKinds::UV4(_) => &mut kinds.uv4,
+            MorphKinds::Bone(_) => &mut kinds.bone,
+            MorphKinds::Material(_) => &mut kinds.material,
+            MorphKinds::Group(_) => &mut kinds.group,
+            MorphKinds::Flip(_) => &mut kinds.flip,
+            MorphKinds::Impulse(_) => &mut kinds.impulse,
+        };
+        *counter += 1;
+    }
+    stats.estimated_memory = estimate_memory(model);
+    stats
+}
+
+/// result of [`weight_stats`]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WeightStats {
+    /// number of vertices with non zero weight on each bone.
+    pub per_bone_vertex_counts: Vec<u32>,
+    /// sum of weights on each bone.
+    pub per_bone_weight_sum: Vec<f32>,
+    /// number of bones with non zero weight on each vertex.
+    pub per_vertex_influences: Vec<u8>,
+    pub max_influences_per_vertex: u8,
+    /// vertices whose weights are negative or don't sum to 1,
+    /// with the tolerance of [`GeometryOptions::weight_tolerance`].
+    pub vertices_with_unnormalized_weights: Vec<u32>,
+}
+
+impl WeightStats {
+    /// [`per_bone_weight_sum`](Self::per_bone_weight_sum) scaled so that the largest is 1.
+    ///
+    /// handy as heat of each bone. all 0 if no bone has weight.
+    pub fn bone_heat(&self) -> Vec<f32> {
+        let max = self
+            .per_bone_weight_sum
+            .iter()
+            .copied()
+            .fold(0.0_f32, f32::max);
+        self.per_bone_weight_sum
+            .iter()
+            .map(|sum| if max > 0.0 { sum / max } else { 0.0 })
+            .collect()
+    }
+}
+
+/// skinning influences of `bone_count` bones on `vertices`.
+///
+/// weights on the same bone of a vertex are counted as one influence.
+/// zero weights and bone indices out of `0..bone_count` are ignored.
+pub fn weight_stats(vertices: &[Vertex], bone_count: usize) -> WeightStats {
+    let tolerance = GeometryOptions::default().weight_tolerance;
+    let mut stats = WeightStats {
+        per_bone_vertex_counts: vec![0; bone_count],
+        per_bone_weight_sum: vec![0.0; bone_count],
+        per_vertex_influences: Vec::with_capacity(vertices.len()),
+        ..WeightStats::default()
+    };
+    for (index, vertex) in vertices.iter().enumerate() {
+        let mut bones = vertex
+            .weight_type
+            .bone_weights()
+            .into_iter()
+            .filter(|&(_, weight)| weight != 0.0)
+            .filter_map(|(bone, weight)| {
+                usize::try_from(bone)
+                    .ok()
+                    .filter(|&bone| bone < bone_count)
+                    .map(|bone| (bone, weight))
+            })
+            .collect::<Vec<_>>();
+        for &(bone, weight) in &bones {
+            stats.per_bone_weight_sum[bone] += weight;
+        }
+        bones.sort_unstable_by_key(|&(bone, _)| bone);
+        bones.dedup_by_key(|(bone, _)| *bone);
+        for &(bone, _) in &bones {
+            stats.per_bone_vertex_counts[bone] += 1;
+        }
+        let influences = bones.len() as u8;
+        stats.per_vertex_influences.push(influences);
+        stats.max_influences_per_vertex = stats.max_influences_per_vertex.max(influences);
+        if !weights_are_normalized(&vertex.weight_type, tolerance) {
+            stats.vertices_with_unnormalized_weights.push(index as u32);
+        }
+    }
+    stats
+}
+
+fn estimate_memory(model: &Model) -> usize {
+    let info = &model.model_info;
+    size_of::<Model>()
+        + info.name.len()
+        + info.name_en.len()
+        + info.comment.len()
+        + info.comment_en.len()
+        + model.vertices.len() * size_of::<Vertex>()
+        + model.faces.len() * size_of::<crate::types::Face>()
+        + model
+            .textures
+            .textures
+            .iter()
+            .map(|texture| size_of::<String>() + texture.len())
+            .sum::<usize>()
+        + model
+            .materials
+            .iter()
+            .map(|material| {
+                size_of::<Material>()
+                    + material.name.len()
+                    + material.english_name.len()
+                    + material.memo.len()
+            })
+            .sum::<usize>()
+        + model.bones.iter().map(bone_memory).sum::<usize>()
+        + model.morphs.iter().map(morph_memory).sum::<usize>()
+        + model
+            .frames
+            .iter()
+            .map(|frame| {
+                size_of::<Frame>()
+                    + frame.name.len()
+                    + frame.name_en.len()
+                    + frame.inners.len() * size_of::<crate::types::FrameInner>()
+            })
+            .sum::<usize>()
+        + model
+            .rigid_bodies
+            .iter()
+            .map(|rigid| size_of::<Rigid>() + rigid.name.len() + rigid.name_en.len())
+            .sum::<usize>()
+        + model
+            .joints
+            .iter()
+            .map(|joint| size_of::<Joint>() + joint.name.len() + joint.name_en.len())
+            .sum::<usize>()
+        + model
+            .soft_bodies
+            .iter()
+            .map(|soft_body| {
+                size_of::<SoftBody>()
+                    + soft_body.name.len()
+                    + soft_body.name_en.len()
+                    + soft_body.anchor_rigid.len() * size_of::<crate::types::SoftBodyAnchorRigid>()
+                    + soft_body.pin_vertex.len() * size_of::<i32>()
+            })
+            .sum::<usize>()
+}
+
+fn bone_memory(bone: &Bone) -> usize {
+    size_of::<Bone>()
+        + bone.name.len()
+        + bone.english_name.len()
+        + bone.ik_info.as_ref().map_or(0, |ik_info| {
+            ik_info.ik_links.len() * size_of::<crate::types::IKLink>()
+        })
+}
+
+fn morph_memory(morph: &Morph) -> usize {
+    let offsets = match &morph.morph_data {
+        MorphKinds::Vertex(x) => x.len() * size_of::<crate::types::VertexMorph>(),
+        MorphKinds::UV(x)
+        | MorphKinds::UV1(x)
+        | MorphKinds::UV2(x)
+        | MorphKinds::UV3(x)
+        | MorphKinds::UV4(x) => x.len() * size_of::<crate::types::UVMorph>(),
+        MorphKinds::Bone(x) => x.len() * size_of::<crate::types::BoneMorph>(),
+        MorphKinds::Material(x) => x.len() * size_of::<crate::types::MaterialMorph>(),
+        MorphKinds::Group(x) => x.len() * size_of::<crate::types::GroupMorph>(),
+        MorphKinds::Flip(x) => x.len() * size_of::<crate::types::FlipMorph>(),
+        MorphKinds::Impulse(x) => x.len() * size_of::<crate::types::ImpulseMorph>(),
+    };
+    size_of::<Morph>() + morph.name.len() + morph.english_name.len() + offsets
+}
+
+impl Display for ModelStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let weights = &self.weight_types;
+        let kinds = &self.morph_kinds;
+        writeln!(f, "PMX {}", self.version)?;
+        writeln!(
+            f,
+            "vertices: {} (BDEF1: {} BDEF2: {} BDEF4: {} SDEF: {} QDEF: {})",
+            self.vertices, weights.bdef1, weights.bdef2, weights.bdef4, weights.sdef, weights.qdef
+        )?;
+        writeln!(
+            f,
+            "additional uv: {} used: {:?}",
+            self.additional_uv, self.additional_uv_usage
+        )?;
+        writeln!(
+            f,
+            "faces: {} textures: {} materials: {}",
+            self.faces, self.textures, self.materials
+        )?;
+        writeln!(
+            f,
+            "bones: {} (IK: {} links: {})",
+            self.bones, self.ik_bones, self.ik_links
+        )?;
+        writeln!(
+            f,
+            "morphs: {} (vertex: {} uv: {} uv1: {} uv2: {} uv3: {} uv4: {} bone: {} material: {} group: {} flip: {} impulse: {})",
+            self.morphs,
+            kinds.vertex,
+            kinds.uv,
+            kinds.uv1,
+            kinds.uv2,
+            kinds.uv3,
+            kinds.uv4,
+            kinds.bone,
+            kinds.material,
+            kinds.group,
+            kinds.flip,
+            kinds.impulse
+        )?;
+        writeln!(
+            f,
+            "frames: {} rigid bodies: {} joints: {} soft bodies: {}",
+            self.frames, self.rigid_bodies, self.joints, self.soft_bodies
+        )?;
+        write!(f, "estimated memory: {} bytes", self.estimated_memory)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::{analyze, weight_stats};
+    use crate::types::{
+        Bone, BoneIKInfo, ControlPanel, GroupMorph, IKLink, Model, Morph, MorphKinds, Vertex,
+        VertexWeight,
+    };
+
+    fn vertex(weight_type: VertexWeight, add_uv_1: f32) -> Vertex {
+        Vertex {
+            position: [0.0; 3],
+            norm: [0.0, 1.0, 0.0],
+            uv: [0.0; 2],
+            add_uv: [[0.0; 4], [add_uv_1, 0.0, 0.0, 0.0], [0.0; 4], [0.0; 4]],
+            weight_type,
+            edge_mag: 1.0,
+        }
+    }
+
+    #[test]
+    fn analyze_synthetic_model() {
+        let mut model = Model {
+            additional_uv: 2,
+            ..Model::default()
+        };
+        model.vertices = vec![
+            vertex(VertexWeight::BDEF1(0), 0.0),
+            vertex(VertexWeight::BDEF1(0), 0.5),
+            vertex(
+                VertexWeight::BDEF2 {
+                    bone_index_1: 0,
+                    bone_index_2: 1,
+                    bone_weight_1: 0.5,
+                },
+                0.0,
+            ),
+            vertex(
+                VertexWeight::SDEF {
+                    bone_index_1: 0,
+                    bone_index_2: 1,
+                    bone_weight_1: 0.5,
+                    sdef_c: [0.0; 3],
+                    sdef_r0: [0.0; 3],
+                    sdef_r1: [0.0; 3],
+                },
+                1.0,
+            ),
+        ];
+        model.bones = vec![
+            Bone::default(),
+            Bone {
+                ik_info: Some(BoneIKInfo {
+                    ik_target_bone_index: 0,
+                    ik_iter_count: 40,
+                    ik_limit_angle: 1.0,
+                    ik_links: vec![
+                        IKLink {
+                            ik_bone_index: 0,
+                            angle_limit: None,
+                        };
+                        3
+                    ],
+                }),
+                ..Bone::default()
+            },
+        ];
+        model.morphs = vec![
+            Morph {
+                name: "グループ".to_owned(),
+                english_name: String::new(),
+                control_panel: ControlPanel::System,
+                morph_data: MorphKinds::Group(vec![GroupMorph {
+                    index: 1,
+                    morph_factor: 1.0,
+                }]),
+            },
+            Morph {
+                name: "あ".to_owned(),
+                english_name: String::new(),
+                control_panel: ControlPanel::TopRight,
+                morph_data: MorphKinds::Vertex(vec![]),
+            },
+        ];
+
+        let stats = analyze(&model);
+        assert_eq!(stats.version, "2.0");
+        assert_eq!(stats.vertices, 4);
+        assert_eq!(stats.weight_types.bdef1, 2);
+        assert_eq!(stats.weight_types.bdef2, 1);
+        assert_eq!(stats.weight_types.sdef, 1);
+        assert_eq!(stats.weight_types.qdef, 0);
+        assert_eq!(stats.additional_uv_usage, [0, 2, 0, 0]);
+        assert_eq!(stats.ik_bones, 1);
+        assert_eq!(stats.ik_links, 3);
+        assert_eq!(stats.morph_kinds.group, 1);
+        assert_eq!(stats.morph_kinds.vertex, 1);
+        assert!(stats.estimated_memory > 4 * std::mem::size_of::<Vertex>());
+        assert!(stats
+            .to_string()
+            .contains("vertices: 4 (BDEF1: 2 BDEF2: 1 BDEF4: 0 SDEF: 1 QDEF: 0)"));
+    }
+
+    #[test]
+    fn weights_of_synthetic_mesh() {
+        let mut vertices = vec![
+            vertex(VertexWeight::BDEF1(0), 0.0),
+            vertex(
+                VertexWeight::BDEF2 {
+                    bone_index_1: 0,
+                    bone_index_2: 1,
+                    bone_weight_1: 0.25,
+                },
+                0.0,
+            ),
+            // same bone twice and a zero weight.
+            vertex(
+                VertexWeight::BDEF4 {
+                    bone_index_1: 2,
+                    bone_index_2: 2,
+                    bone_index_3: 1,
+                    bone_index_4: 0,
+                    bone_weight_1: 0.5,
+                    bone_weight_2: 0.25,
+                    bone_weight_3: 0.25,
+                    bone_weight_4: 0.0,
+                },
+                0.0,
+            ),
+            // doesn't sum to 1 and refers a missing bone.
+            vertex(
+                VertexWeight::BDEF4 {
+                    bone_index_1: 0,
+                    bone_index_2: 1,
+                    bone_index_3: 2,
+                    bone_index_4: 9,
+                    bone_weight_1: 0.5,
+                    bone_weight_2: 0.5,
+                    bone_weight_3: 0.5,
+                    bone_weight_4: 0.5,
+                },
+                0.0,
+            ),
+        ];
+        let stats = weight_stats(&vertices, 3);
+        assert_eq!(stats.per_bone_vertex_counts, [3, 3, 2]);
+        assert_eq!(stats.per_bone_weight_sum, [1.75, 1.5, 1.25]);
+        assert_eq!(stats.per_vertex_influences, [1, 2, 2, 3]);
+        assert_eq!(stats.max_influences_per_vertex, 3);
+        assert_eq!(stats.vertices_with_unnormalized_weights, [3]);
+        assert_eq!(stats.bone_heat(), [1.0, 1.5 / 1.75, 1.25 / 1.75]);
+
+        // a strip skinned by a chain of bones.
+        vertices = (0..10)
+            .map(|i| {
+                vertex(
+                    VertexWeight::BDEF2 {
+                        bone_index_1: i / 2,
+                        bone_index_2: i / 2 + 1,
+                        bone_weight_1: if i % 2 == 0 { 1.0 } else { 0.5 },
+                    },
+                    0.0,
+                )
+            })
+            .collect();
+        let stats = weight_stats(&vertices, 6);
+        assert_eq!(stats.per_bone_vertex_counts, [2, 3, 3, 3, 3, 1]);
+        assert_eq!(stats.per_bone_weight_sum, [1.5, 2.0, 2.0, 2.0, 2.0, 0.5]);
+        assert_eq!(stats.max_influences_per_vertex, 2);
+        assert!(stats.vertices_with_unnormalized_weights.is_empty());
+        assert_eq!(weight_stats(&[], 2).bone_heat(), [0.0, 0.0]);
+    }
+}
@@ -0,0 +1,766 @@
+//! # PMX writing without buffering.
+//!
+//! [`Writer`](crate::writer::Writer) keeps every element until written.
+//! [`StreamingWriter`] writes each section as soon as it is given instead, so elements can be
+//! generated on the fly. header is decided up front by [`StreamingHeader`].
+//!
+//! sections must be given in PMX order. each stage consumes itself and returns the next one.
+//!
+//! |Current stage|writes|Next stage|
+//! |-------------|------|----------|
+//! |[`StreamingWriter`]|vertices|[`FacesWriteStage`]|
+//! |[`FacesWriteStage`]|faces|[`TexturesWriteStage`]|
+//! |[`TexturesWriteStage`]|texture paths|[`MaterialsWriteStage`]|
+//! |[`MaterialsWriteStage`]|materials|[`BonesWriteStage`]|
+//! |[`BonesWriteStage`]|bones|[`MorphsWriteStage`]|
+//! |[`MorphsWriteStage`]|morphs|[`FramesWriteStage`]|
+//! |[`FramesWriteStage`]|frames|[`RigidsWriteStage`]|
+//! |[`RigidsWriteStage`]|rigid bodies|[`JointsWriteStage`]|
+//! |[`JointsWriteStage`]|joints|[`SoftBodiesWriteStage`]|
+//! |[`SoftBodiesWriteStage`]|soft bodies|There are no writer|
+//!
+//! every stage is `Send` if its destination is.
+//!
+//! `write_*` takes [`ExactSizeIterator`] to write element count first.
+//! `write_*_uncounted` takes any iterator and fills the count afterwards by seeking back.
+//!
+//! ```rust
+//! use PMXUtil::stream_writer::{ElementCounts, StreamingHeader, StreamingWriter};
+//! use PMXUtil::types::{Face, ModelInfo, Vertex, VertexWeight};
+//!
+//! let vertex = |x: f32| Vertex {
+//!     position: [x, 0.0, 0.0],
+//!     norm: [0.0, 0.0, -1.0],
+//!     uv: [0.0; 2],
+//!     add_uv: [[0.0; 4]; 4],
+//!     weight_type: VertexWeight::BDEF1(-1),
+//!     edge_mag: 1.0,
+//! };
+//! let header = StreamingHeader::for_counts(&ElementCounts {
+//!     vertices: 3,
+//!     ..Default::default()
+//! });
+//! let mut buf = vec![];
+//! StreamingWriter::new(&mut buf, header, &ModelInfo::default())
+//!     .unwrap()
+//!     .write_vertices((0..3).map(|i| vertex(i as f32)))
+//!     .unwrap()
+//!     .write_faces((0..1).map(|_| Face { vertices: [0, 1, 2] }))
+//!     .unwrap()
+//!     .skip_to_end()
+//!     .unwrap();
+//! ```
+use crate::binary_writer::BinaryWriter;
+use crate::progress::SectionKind;
+use crate::types::{
+    Bone, Encode, Face, Frame, Header, IndexKinds, Joint, Material, ModelInfo, Morph, PMXVersion,
+    RawSection, Rigid, SoftBody, Vertex, VertexIndexKinds,
+};
+use crate::writer::{
+    check_entries, joint_requires_v21, material_requires_v21, morph_requires_v21,
+    optimal_data_type, optimal_data_type_vertex, section_count, vertex_additional_uv,
+    vertex_requires_v21, WritePMXErrors,
+};
+use std::io::{Seek, SeekFrom, Write};
+
+/// header decisions made before writing any element.
+#[derive(Debug, Clone, Copy)]
+pub struct StreamingHeader {
+    pub encode: Encode,
+    pub version: PMXVersion,
+    /// 0..4
+    pub additional_uv: u8,
+    pub vertex_index: VertexIndexKinds,
+    pub texture_index: IndexKinds,
+    pub material_index: IndexKinds,
+    pub bone_index: IndexKinds,
+    pub morph_index: IndexKinds,
+    pub rigid_body_index: IndexKinds,
+}
+
+/// upper bounds of element counts used to estimate index kinds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ElementCounts {
+    pub vertices: usize,
+    pub textures: usize,
+    pub materials: usize,
+    pub bones: usize,
+    pub morphs: usize,
+    pub rigid_bodies: usize,
+}
+
+impl StreamingHeader {
+    /// smallest index kinds holding `counts` elements.
+    ///
+    /// UTF-16 PMX 2.0 without additional uv. change fields as needed.
+    pub fn for_counts(counts: &ElementCounts) -> Self {
+        Self {
+            encode: Encode::Utf16Le,
+            version: PMXVersion::V20,
+            additional_uv: 0,
+            vertex_index: optimal_data_type_vertex(counts.vertices),
+            texture_index: optimal_data_type(counts.textures),
+            material_index: optimal_data_type(counts.materials),
+            bone_index: optimal_data_type(counts.bones),
+            morph_index: optimal_data_type(counts.morphs),
+            rigid_body_index: optimal_data_type(counts.rigid_bodies),
+        }
+    }
+}
+
+impl From<StreamingHeader> for Header {
+    fn from(header: StreamingHeader) -> Self {
+        Header {
+            magic: *b"PMX ",
+            version: header.version,
+            length: 8,
+            encode: header.encode,
+            additional_uv: header.additional_uv,
+            s_vertex_index: header.vertex_index,
+            s_texture_index: header.texture_index,
+            s_material_index: header.material_index,
+            s_bone_index: header.bone_index,
+            s_morph_index: header.morph_index,
+            s_rigid_body_index: header.rigid_body_index,
+            raw_version: None,
+        }
+    }
+}
+
+/// common part of all stages.
+struct Inner<W: Write> {
+    writer: BinaryWriter<W>,
+}
+
+impl<W: Write> Inner<W> {
+    /// write count and elements of `section`.
+    ///
+    /// count field is `scale` times element count.
+    fn write_counted<T>(
+        &mut self,
+        section: SectionKind,
+        scale: usize,
+        elements: impl ExactSizeIterator<Item = T>,
+        f: impl FnMut(&mut BinaryWriter<W>, &T) -> Result<(), WritePMXErrors>,
+    ) -> Result<(), WritePMXErrors> {
+        let declared = elements.len();
+        self.writer
+            .write_i32(section_count(section, declared * scale)?);
+        let written = self.write_elements(section, elements, f)?;
+        if written == declared {
+            Ok(())
+        } else {
+            Err(WritePMXErrors::SectionCountMismatch {
+                section,
+                declared,
+                written,
+            })
+        }
+    }
+
+    /// write elements and return how many were written.
+    fn write_elements<T>(
+        &mut self,
+        section: SectionKind,
+        elements: impl Iterator<Item = T>,
+        mut f: impl FnMut(&mut BinaryWriter<W>, &T) -> Result<(), WritePMXErrors>,
+    ) -> Result<usize, WritePMXErrors> {
+        let mut written = 0;
+        for element in elements {
+            f(&mut self.writer, &element)?;
+            self.writer.check_indices(section, written)?;
+            written += 1;
+        }
+        Ok(written)
+    }
+}
+
+fn check_vertex(header: &Header, vertex: &Vertex) -> Result<(), WritePMXErrors> {
+    let used = vertex_additional_uv(vertex);
+    if header.additional_uv < used {
+        Err(WritePMXErrors::AdditionalUvTruncated {
+            declared: header.additional_uv,
+            used,
+        })
+    } else {
+        check_version(header, vertex_requires_v21(vertex))
+    }
+}
+
+fn check_version(header: &Header, requires_v21: bool) -> Result<(), WritePMXErrors> {
+    if requires_v21 && header.version == PMXVersion::V20 {
+        Err(WritePMXErrors::RequiresV21)
+    } else {
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> Inner<W> {
+    /// write elements of unknown count then go back to fill count.
+    fn write_uncounted<T>(
+        &mut self,
+        section: SectionKind,
+        scale: usize,
+        elements: impl Iterator<Item = T>,
+        f: impl FnMut(&mut BinaryWriter<W>, &T) -> Result<(), WritePMXErrors>,
+    ) -> Result<(), WritePMXErrors> {
+        let count_position = self.writer.inner.stream_position()?;
+        self.writer.write_i32(0);
+        let written = self.write_elements(section, elements, f)?;
+        let count = section_count(section, written * scale)?;
+        let end = self.writer.inner.stream_position()?;
+        self.writer.inner.seek(SeekFrom::Start(count_position))?;
+        self.writer.write_i32(count);
+        self.writer.inner.seek(SeekFrom::Start(end))?;
+        Ok(())
+    }
+}
+
+/// first stage writing vertices. header and model info are written on creation.
+pub struct StreamingWriter<W: Write>(Inner<W>);
+
+impl<W: Write> StreamingWriter<W> {
+    /// write header and `model_info` to `writer`.
+    ///
+    /// # Errors
+    /// * `WritePMXErrors::TooManyAdditionalUv` if `additional_uv` exceeds 4.
+    pub fn new(
+        writer: W,
+        header: StreamingHeader,
+        model_info: &ModelInfo,
+    ) -> Result<Self, WritePMXErrors> {
+        if header.additional_uv > 4 {
+            return Err(WritePMXErrors::TooManyAdditionalUv(header.additional_uv));
+        }
+        let mut writer = BinaryWriter::from_writer(writer, Header::from(header));
+        writer.write_header();
+        writer.write_model_info(model_info);
+        Ok(Self(Inner { writer }))
+    }
+}
+
+impl<W: Write> StreamingWriter<W> {
+    /// write vertices read by [`VerticesStage::read_raw`](crate::reader::VerticesStage::read_raw)
+    /// byte for byte and move to the next section.
+    ///
+    /// # Errors
+    /// * `WritePMXErrors::RawSectionMismatch` if `additional_uv` or `bone_index` of the header
+    ///   differs from the file `raw` was read from.
+    /// * `WritePMXErrors::RequiresV21` if `raw` has QDEF weights in PMX 2.0.
+    pub fn write_raw_vertices(
+        mut self,
+        raw: &RawSection,
+    ) -> Result<FacesWriteStage<W>, WritePMXErrors> {
+        self.0.writer.write_raw_section(raw)?;
+        Ok(FacesWriteStage(self.0))
+    }
+}
+
+/// stage writing soft bodies. PMX 2.0 can only have none.
+pub struct SoftBodiesWriteStage<W: Write>(Inner<W>);
+
+impl<W: Write> SoftBodiesWriteStage<W> {
+    /// write soft bodies and flush.
+    ///
+    /// # Errors
+    /// * `WritePMXErrors::RequiresV21` if any soft body is given in PMX 2.0.
+    /// * `WritePMXErrors::SectionCountMismatch` if iterator yielded other than its length.
+    /// * `WritePMXErrors::TooBig` if too many soft bodies or entries in a soft body.
+    /// * `WritePMXErrors::IoError` if failed to flush.
+    pub fn write_soft_bodies<I>(mut self, soft_bodies: I) -> Result<(), WritePMXErrors>
+    where
+        I: IntoIterator<Item = SoftBody>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let soft_bodies = soft_bodies.into_iter();
+        if self.0.writer.header.version == PMXVersion::V20 {
+            if soft_bodies.len() > 0 {
+                return Err(WritePMXErrors::RequiresV21);
+            }
+        } else {
+            self.0.write_counted(
+                SectionKind::SoftBodies,
+                1,
+                soft_bodies,
+                |writer, soft_body| {
+                    check_entries(SectionKind::SoftBodies, std::slice::from_ref(soft_body))?;
+                    writer.write_soft_body(soft_body);
+                    Ok(())
+                },
+            )?;
+        }
+        self.0.writer.inner.flush()?;
+        Ok(())
+    }
+
+    /// finish without soft bodies.
+    pub fn skip_to_end(self) -> Result<(), WritePMXErrors> {
+        self.write_soft_bodies(vec![])
+    }
+}
+
+impl<W: Write + Seek> SoftBodiesWriteStage<W> {
+    /// [`write_soft_bodies`](Self::write_soft_bodies) for iterators of unknown length.
+    pub fn write_soft_bodies_uncounted<I>(mut self, soft_bodies: I) -> Result<(), WritePMXErrors>
+    where
+        I: IntoIterator<Item = SoftBody>,
+    {
+        if self.0.writer.header.version == PMXVersion::V20 {
+            if soft_bodies.into_iter().next().is_some() {
+                return Err(WritePMXErrors::RequiresV21);
+            }
+        } else {
+            self.0.write_uncounted(
+                SectionKind::SoftBodies,
+                1,
+                soft_bodies.into_iter(),
+                |writer, soft_body| {
+                    check_entries(SectionKind::SoftBodies, std::slice::from_ref(soft_body))?;
+                    writer.write_soft_body(soft_body);
+                    Ok(())
+                },
+            )?;
+        }
+        self.0.writer.inner.flush()?;
+        Ok(())
+    }
+}
+
+macro_rules! write_stage {
+    (
+        $stage:ident, $T:ty, $section:expr, $scale:expr,
+        $write:ident, $write_uncounted:ident, $next:ident,
+        |$header:ident, $writer:ident, $element:ident| $body:expr
+    ) => {
+        impl<W: Write> $stage<W> {
+            /// write count then elements and move to the next section.
+            ///
+            /// # Errors
+            /// * `WritePMXErrors::SectionCountMismatch` if iterator yielded other than its length.
+            /// * `WritePMXErrors::RequiresV21` if 2.1 only element is given in PMX 2.0.
+            /// * `WritePMXErrors::AdditionalUvTruncated` if vertex uses more additional uvs than
+            ///   header.
+            /// * `WritePMXErrors::TooBig` if too many elements or entries in an element.
+            /// * `WritePMXErrors::IndexOutOfRange` if an index doesn't fit the index size of header.
+            pub fn $write<I>(mut self, elements: I) -> Result<$next<W>, WritePMXErrors>
+            where
+                I: IntoIterator<Item = $T>,
+                I::IntoIter: ExactSizeIterator,
+            {
+                self.0.write_counted(
+                    $section,
+                    $scale,
+                    elements.into_iter(),
+                    |$writer, $element| {
+                        let $header = &$writer.header;
+                        $body
+                    },
+                )?;
+                Ok($next(self.0))
+            }
+
+            /// write this and all following sections empty and flush.
+            pub fn skip_to_end(self) -> Result<(), WritePMXErrors> {
+                self.$write(vec![])?.skip_to_end()
+            }
+        }
+
+        impl<W: Write + Seek> $stage<W> {
+            /// write elements of iterator of unknown length and move to the next section.
+            ///
+            /// count is written after elements by seeking back.
+            ///
+            /// # Errors
+            /// same as the counted one except `SectionCountMismatch`.
+            pub fn $write_uncounted<I>(mut self, elements: I) -> Result<$next<W>, WritePMXErrors>
+            where
+                I: IntoIterator<Item = $T>,
+            {
+                self.0.write_uncounted(
+                    $section,
+                    $scale,
+                    elements.into_iter(),
+                    |$writer, $element| {
+                        let $header = &$writer.header;
+                        $body
+                    },
+                )?;
+                Ok($next(self.0))
+            }
+        }
+    };
+}
+
+/// stage writing faces.
+pub struct FacesWriteStage<W: Write>(Inner<W>);
+/// stage writing texture paths.
+pub struct TexturesWriteStage<W: Write>(Inner<W>);
+/// stage writing materials.
+pub struct MaterialsWriteStage<W: Write>(Inner<W>);
+/// stage writing bones.
+pub struct BonesWriteStage<W: Write>(Inner<W>);
+/// stage writing morphs.
+pub struct MorphsWriteStage<W: Write>(Inner<W>);
+/// stage writing display frames.
+pub struct FramesWriteStage<W: Write>(Inner<W>);
+/// stage writing rigid bodies.
+pub struct RigidsWriteStage<W: Write>(Inner<W>);
+/// stage writing joints.
+pub struct JointsWriteStage<W: Write>(Inner<W>);
+
+write_stage!(
+    StreamingWriter,
+    Vertex,
+    SectionKind::Vertices,
+    1,
+    write_vertices,
+    write_vertices_uncounted,
+    FacesWriteStage,
+    |header, writer, vertex| {
+        check_vertex(header, vertex)?;
+        writer.write_vertex(vertex);
+        Ok(())
+    }
+);
+write_stage!(
+    FacesWriteStage,
+    Face,
+    SectionKind::Faces,
+    3,
+    write_faces,
+    write_faces_uncounted,
+    TexturesWriteStage,
+    |_header, writer, face| {
+        writer.write_face(face);
+        Ok(())
+    }
+);
+write_stage!(
+    TexturesWriteStage,
+    String,
+    SectionKind::Textures,
+    1,
+    write_textures,
+    write_textures_uncounted,
+    MaterialsWriteStage,
+    |_header, writer, texture| {
+        writer.write_text_buf(texture);
+        Ok(())
+    }
+);
+write_stage!(
+    MaterialsWriteStage,
+    Material,
+    SectionKind::Materials,
+    1,
+    write_materials,
+    write_materials_uncounted,
+    BonesWriteStage,
+    |header, writer, material| {
+        check_version(header, material_requires_v21(material))?;
+        writer.write_material(material);
+        Ok(())
+    }
+);
+write_stage!(
+    BonesWriteStage,
+    Bone,
+    SectionKind::Bones,
+    1,
+    write_bones,
+    write_bones_uncounted,
+    MorphsWriteStage,
+    |_header, writer, bone| {
+        check_entries(SectionKind::Bones, std::slice::from_ref(bone))?;
+        writer.write_bone(bone);
+        Ok(())
+    }
+);
+write_stage!(
+    MorphsWriteStage,
+    Morph,
+    SectionKind::Morphs,
+    1,
+    write_morphs,
+    write_morphs_uncounted,
+    FramesWriteStage,
+    |header, writer, morph| {
+        check_version(header, morph_requires_v21(morph))?;
+        check_entries(SectionKind::Morphs, std::slice::from_ref(morph))?;
+        writer.write_morph(morph);
+        Ok(())
+    }
+);
+write_stage!(
+    FramesWriteStage,
+    Frame,
+    SectionKind::Frames,
+    1,
+    write_frames,
+    write_frames_uncounted,
+    RigidsWriteStage,
+    |_header, writer, frame| {
+        check_entries(SectionKind::Frames, std::slice::from_ref(frame))?;
+        writer.write_frame(frame);
+        Ok(())
+    }
+);
+write_stage!(
+    RigidsWriteStage,
+    Rigid,
+    SectionKind::RigidBodies,
+    1,
+    write_rigid_bodies,
+    write_rigid_bodies_uncounted,
+    JointsWriteStage,
+    |_header, writer, rigid| {
+        writer.write_rigid(rigid);
+        Ok(())
+    }
+);
+write_stage!(
+    JointsWriteStage,
+    Joint,
+    SectionKind::Joints,
+    1,
+    write_joints,
+    write_joints_uncounted,
+    SoftBodiesWriteStage,
+    |header, writer, joint| {
+        check_version(header, joint_requires_v21(joint))?;
+        writer.write_joint(joint);
+        Ok(())
+    }
+);
+
+#[cfg(test)]
+mod test {
+    use crate::progress::SectionKind;
+    use crate::reader::{ModelInfoStage, PmxVisitor};
+    use crate::stream_writer::{ElementCounts, StreamingHeader, StreamingWriter};
+    use crate::types::{Model, PMXVersion, Vertex, VertexWeight};
+    use crate::writer::WritePMXErrors;
+    use std::io::{Cursor, Seek, Write};
+
+    fn header(model: &Model) -> StreamingHeader {
+        StreamingHeader {
+            version: PMXVersion::V21,
+            additional_uv: model.additional_uv,
+            ..StreamingHeader::for_counts(&ElementCounts {
+                vertices: model.vertices.len(),
+                textures: model.textures.textures.len(),
+                materials: model.materials.len(),
+                bones: model.bones.len(),
+                morphs: model.morphs.len(),
+                rigid_bodies: model.rigid_bodies.len(),
+            })
+        }
+    }
+
+    fn write_counted<W: Write>(model: &Model, writer: W) -> Result<(), WritePMXErrors> {
+        let model = model.clone();
+        StreamingWriter::new(writer, header(&model), &model.model_info)?
+            .write_vertices(model.vertices)?
+            .write_faces(model.faces)?
+            .write_textures(model.textures.textures)?
+            .write_materials(model.materials)?
+            .write_bones(model.bones)?
+            .write_morphs(model.morphs)?
+            .write_frames(model.frames)?
+            .write_rigid_bodies(model.rigid_bodies)?
+            .write_joints(model.joints)?
+            .write_soft_bodies(model.soft_bodies)
+    }
+
+    /// iterator without exact length.
+    fn unsized_iter<T>(elements: Vec<T>) -> impl Iterator<Item = T> {
+        elements.into_iter().filter(|_| true)
+    }
+
+    fn write_uncounted<W: Write + Seek>(model: &Model, writer: W) -> Result<(), WritePMXErrors> {
+        let model = model.clone();
+        StreamingWriter::new(writer, header(&model), &model.model_info)?
+            .write_vertices_uncounted(unsized_iter(model.vertices))?
+            .write_faces_uncounted(unsized_iter(model.faces))?
+            .write_textures_uncounted(unsized_iter(model.textures.textures))?
+            .write_materials_uncounted(unsized_iter(model.materials))?
+            .write_bones_uncounted(unsized_iter(model.bones))?
+            .write_morphs_uncounted(unsized_iter(model.morphs))?
+            .write_frames_uncounted(unsized_iter(model.frames))?
+            .write_rigid_bodies_uncounted(unsized_iter(model.rigid_bodies))?
+            .write_joints_uncounted(unsized_iter(model.joints))?
+            .write_soft_bodies_uncounted(unsized_iter(model.soft_bodies))
+    }
+
+    #[test]
+    fn same_as_writer() {
+        let model = crate::test_support::cube();
+        let expected = crate::test_support::write(&model);
+
+        let mut buf = vec![];
+        write_counted(&model, &mut buf).unwrap();
+        assert_eq!(buf, expected);
+
+        let mut cursor = Cursor::new(vec![]);
+        write_uncounted(&model, &mut cursor).unwrap();
+        assert_eq!(cursor.into_inner(), expected);
+    }
+
+    #[test]
+    fn raw_vertices() {
+        let model = crate::test_support::cube();
+        let expected = crate::test_support::write(&model);
+        let (_, stage) = ModelInfoStage::from_reader(&expected[..]).unwrap().read();
+        let (raw, _) = stage.read_raw();
+        assert_eq!(raw.count as usize, model.vertices.len());
+        assert_eq!(raw.record_offsets.len(), model.vertices.len());
+        let kinds = (0..model.vertices.len())
+            .map(|i| raw.weight_kind(i).unwrap())
+            .collect::<Vec<_>>();
+        let (_, stage) = ModelInfoStage::from_reader(&expected[..]).unwrap().read();
+        let (vertices, _) = stage.read();
+        for (kind, vertex) in kinds.iter().zip(&vertices) {
+            let expected = match vertex.weight_type {
+                VertexWeight::BDEF1(_) => 0,
+                VertexWeight::BDEF2 { .. } => 1,
+                VertexWeight::BDEF4 { .. } => 2,
+                VertexWeight::SDEF { .. } => 3,
+                VertexWeight::QDEF { .. } => 4,
+            };
+            assert_eq!(*kind, expected);
+        }
+        assert!(raw.requires_v21());
+
+        let mut buf = vec![];
+        StreamingWriter::new(&mut buf, header(&model), &model.model_info)
+            .unwrap()
+            .write_raw_vertices(&raw)
+            .unwrap()
+            .write_faces(model.faces.clone())
+            .unwrap()
+            .write_textures(model.textures.textures.clone())
+            .unwrap()
+            .write_materials(model.materials.clone())
+            .unwrap()
+            .write_bones(model.bones.clone())
+            .unwrap()
+            .write_morphs(model.morphs.clone())
+            .unwrap()
+            .write_frames(model.frames.clone())
+            .unwrap()
+            .write_rigid_bodies(model.rigid_bodies.clone())
+            .unwrap()
+            .write_joints(model.joints.clone())
+            .unwrap()
+            .write_soft_bodies(model.soft_bodies.clone())
+            .unwrap();
+        assert_eq!(buf, expected);
+
+        let mut other = header(&model);
+        other.additional_uv = 0;
+        let result = StreamingWriter::new(vec![], other, &model.model_info)
+            .unwrap()
+            .write_raw_vertices(&raw);
+        assert!(matches!(
+            result,
+            Err(WritePMXErrors::RawSectionMismatch {
+                section: SectionKind::Vertices
+            })
+        ));
+        let mut other = header(&model);
+        other.version = PMXVersion::V20;
+        let result = StreamingWriter::new(vec![], other, &model.model_info)
+            .unwrap()
+            .write_raw_vertices(&raw);
+        assert!(matches!(result, Err(WritePMXErrors::RequiresV21)));
+    }
+
+    #[test]
+    fn version_check() {
+        let mut model = crate::test_support::cube();
+        let mut header = header(&model);
+        header.version = PMXVersion::V20;
+        let result = StreamingWriter::new(vec![], header, &model.model_info)
+            .unwrap()
+            .write_vertices(model.vertices.clone());
+        assert!(matches!(result, Err(WritePMXErrors::RequiresV21)));
+
+        for vertex in &mut model.vertices {
+            vertex.weight_type = VertexWeight::BDEF1(0);
+        }
+        let result = StreamingWriter::new(vec![], header, &model.model_info)
+            .unwrap()
+            .write_vertices(model.vertices)
+            .unwrap()
+            .skip_to_end();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn too_many_additional_uv() {
+        let model = crate::test_support::cube();
+        let mut header = header(&model);
+        header.additional_uv = 5;
+        assert!(matches!(
+            StreamingWriter::new(vec![], header, &model.model_info),
+            Err(WritePMXErrors::TooManyAdditionalUv(5))
+        ));
+    }
+
+    #[test]
+    fn million_vertices() {
+        const COUNT: usize = 1_000_000;
+        let vertex = |i: usize| Vertex {
+            position: [i as f32, 0.0, 0.0],
+            norm: [0.0, 1.0, 0.0],
+            uv: [0.0; 2],
+            add_uv: [[0.0; 4]; 4],
+            weight_type: VertexWeight::BDEF1(0),
+            edge_mag: 1.0,
+        };
+        let header = StreamingHeader::for_counts(&ElementCounts {
+            vertices: COUNT,
+            bones: 1,
+            ..Default::default()
+        });
+        let mut buf = vec![];
+        StreamingWriter::new(&mut buf, header, &Default::default())
+            .unwrap()
+            .write_vertices((0..COUNT).map(vertex))
+            .unwrap()
+            .skip_to_end()
+            .unwrap();
+
+        struct Counter(usize, f32);
+        impl PmxVisitor for Counter {
+            fn vertex(&mut self, vertex: Vertex) {
+                self.0 += 1;
+                self.1 = vertex.position[0];
+            }
+        }
+        let mut counter = Counter(0, 0.0);
+        crate::reader::visit(&buf[..], &mut counter).unwrap();
+        assert_eq!(counter.0, COUNT);
+        assert_eq!(counter.1, (COUNT - 1) as f32);
+    }
+
+    #[test]
+    fn stages_are_send() {
+        use crate::stream_writer::{
+            BonesWriteStage, FacesWriteStage, FramesWriteStage, JointsWriteStage,
+            MaterialsWriteStage, MorphsWriteStage, RigidsWriteStage, SoftBodiesWriteStage,
+            TexturesWriteStage,
+        };
+        fn assert_send<T: Send>() {}
+        assert_send::<StreamingWriter<Vec<u8>>>();
+        assert_send::<FacesWriteStage<Vec<u8>>>();
+        assert_send::<TexturesWriteStage<Vec<u8>>>();
+        assert_send::<MaterialsWriteStage<Vec<u8>>>();
+        assert_send::<BonesWriteStage<Vec<u8>>>();
+        assert_send::<MorphsWriteStage<Vec<u8>>>();
+        assert_send::<FramesWriteStage<Vec<u8>>>();
+        assert_send::<RigidsWriteStage<Vec<u8>>>();
+        assert_send::<JointsWriteStage<Vec<u8>>>();
+        assert_send::<SoftBodiesWriteStage<Vec<u8>>>();
+    }
+}
@@ -0,0 +1,530 @@
+//! In memory models for tests.
+//!
+//! [`cube`] uses every kind of element so round trip through [`Writer`] and
+//! [`ModelInfoStage`] covers all code paths without real model file.
+use crate::reader::ModelInfoStage;
+use crate::types::{
+    Bone, BoneIKInfo, BoneInherits, BoneMorph, ConnectionDisplayMode, ControlPanel, Encode, Face,
+    FlipMorph, Frame, FrameInner, GroupMorph, IKLink, ImpulseMorph, Joint, JointType, Material,
+    MaterialFlags, MaterialMorph, MaterialMorphFormula, Model, ModelInfo, Morph, MorphKinds,
+    PMXVersion, Rigid, RigidCalcMethod, RigidForm, RigidGroups, RotateAndTranslateInherits,
+    SoftBody, SoftBodyAeroModel, SoftBodyAnchorRigid, SoftBodyFlags, SoftBodyForm, SphereMode,
+    SphereModeKind, TextureList, ToonMode, UVMorph, Vertex, VertexMorph, VertexWeight,
+};
+use crate::writer::Writer;
+
+/// write `model` to memory. UTF-8 PMX 2.0 is allowed to test every combination.
+pub(crate) fn write(model: &Model) -> Vec<u8> {
+    let mut writer = Writer::from_model(model);
+    writer.set_mmd_compat(false);
+    writer.write_to_vec().unwrap()
+}
+
+/// read model from memory.
+pub(crate) fn read(buf: &[u8]) -> Model {
+    ModelInfoStage::from_reader(buf).unwrap().read_model()
+}
+
+fn vertex(position: [f32; 3], weight_type: VertexWeight) -> Vertex {
+    let norm = [position[0], position[1], position[2]];
+    Vertex {
+        position,
+        norm,
+        uv: [(position[0] + 1.0) / 2.0, (position[1] + 1.0) / 2.0],
+        add_uv: [[position[2], 0.0, 0.0, 1.0], [0.0; 4], [0.0; 4], [0.0; 4]],
+        weight_type,
+        edge_mag: 1.0,
+    }
+}
+
+fn material(name: &str, sphere_mode: Option<SphereMode>, toon_mode: ToonMode) -> Material {
+    Material {
+        name: name.to_owned(),
+        english_name: name.to_owned(),
+        diffuse: [1.0, 1.0, 1.0, 1.0],
+        specular: [0.5, 0.5, 0.5],
+        specular_factor: 5.0,
+        ambient: [0.3, 0.3, 0.3],
+        draw_mode: MaterialFlags::GROUND_SHADOW | MaterialFlags::HAS_EDGE,
+        edge_color: [0.0, 0.0, 0.0, 1.0],
+        edge_size: 1.0,
+        texture_index: 0,
+        sphere_mode,
+        toon_mode,
+        memo: String::new(),
+        // 6 faces each
+        num_face_vertices: 18,
+    }
+}
+
+fn soft_body() -> SoftBody {
+    SoftBody {
+        name: "布".to_owned(),
+        name_en: "cloth".to_owned(),
+        form: SoftBodyForm::TriMesh,
+        material_index: 1,
+        group: 2,
+        un_collision_group_flag: RigidGroups::single(1),
+        bit_flag: SoftBodyFlags::B_LINK_CREATION | SoftBodyFlags::CLUSTER_CREATION,
+        b_link_create_distance: 2,
+        clusters: 3,
+        mass: 1.0,
+        collision_margin: 0.05,
+        aero_model: SoftBodyAeroModel::VTwoSide,
+        vcf: 1.0,
+        dp: 0.0,
+        dg: 0.0,
+        lf: 0.0,
+        pr: 0.0,
+        vc: 0.0,
+        df: 0.2,
+        mt: 0.0,
+        chr: 1.0,
+        khr: 0.1,
+        shr: 1.0,
+        ahr: 0.7,
+        srhr_cl: 0.1,
+        skhr_cl: 1.0,
+        sshr_cl: 0.5,
+        sr_splt_cl: 0.5,
+        sk_splt_cl: 0.5,
+        ss_splt_cl: 0.5,
+        v_it: 0,
+        p_it: 1,
+        d_it: 0,
+        c_it: 4,
+        lst: 1.0,
+        ast: 1.0,
+        vst: 1.0,
+        anchor_rigid: vec![SoftBodyAnchorRigid {
+            rigid_index: 0,
+            vertex_index: 4,
+            near_mode: true,
+        }],
+        pin_vertex: vec![4, 5],
+    }
+}
+
+/// cube with 2 materials, 2 bones with IK, every morph kind, a rigid body,
+/// every joint type and a soft body.
+///
+/// contains PMX 2.1 only elements so this is written as 2.1
+pub(crate) fn cube() -> Model {
+    let weights = [
+        VertexWeight::BDEF1(0),
+        VertexWeight::BDEF2 {
+            bone_index_1: 0,
+            bone_index_2: 1,
+            bone_weight_1: 0.25,
+        },
+        VertexWeight::BDEF4 {
+            bone_index_1: 0,
+            bone_index_2: 1,
+            bone_index_3: 0,
+            bone_index_4: 1,
+            bone_weight_1: 0.1,
+            bone_weight_2: 0.2,
+            bone_weight_3: 0.3,
+            bone_weight_4: 0.4,
+        },
+        VertexWeight::SDEF {
+            bone_index_1: 0,
+            bone_index_2: 1,
+            bone_weight_1: 0.5,
+            sdef_c: [0.0, 0.5, 0.0],
+            sdef_r0: [0.0, 0.0, 0.0],
+            sdef_r1: [0.0, 1.0, 0.0],
+        },
+        VertexWeight::QDEF {
+            bone_index_1: 0,
+            bone_index_2: 1,
+            bone_index_3: 0,
+            bone_index_4: 1,
+            bone_weight_1: 0.4,
+            bone_weight_2: 0.3,
+            bone_weight_3: 0.2,
+            bone_weight_4: 0.1,
+        },
+    ];
+    let vertices = (0..8)
+        .map(|i| {
+            let position = [
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+            ];
+            vertex(position, weights[i % weights.len()])
+        })
+        .collect();
+    let faces = [
+        [0, 2, 1],
+        [1, 2, 3],
+        [4, 5, 6],
+        [5, 7, 6],
+        [0, 1, 4],
+        [1, 5, 4],
+        [2, 6, 3],
+        [3, 6, 7],
+        [0, 4, 2],
+        [2, 4, 6],
+        [1, 3, 5],
+        [3, 7, 5],
+    ]
+    .iter()
+    .map(|vertices| Face {
+        vertices: *vertices,
+    })
+    .collect();
+    let bones = vec![
+        Bone {
+            name: "センター".to_owned(),
+            english_name: "center".to_owned(),
+            position: [0.0, 0.0, 0.0],
+            parent: -1,
+            deform_depth: 0,
+            connection_display_mode: ConnectionDisplayMode::OtherBone(1),
+            rotatable_in_viewer: true,
+            translatable_in_viewer: true,
+            display_bone_in_viewer: true,
+            controllable_in_viewer: true,
+            local_axis: Some(([1.0, 0.0, 0.0], [0.0, 0.0, 1.0])),
+            ..Bone::default()
+        },
+        Bone {
+            name: "足ＩＫ".to_owned(),
+            english_name: "leg IK".to_owned(),
+            position: [0.0, -1.0, 0.0],
+            parent: 0,
+            deform_depth: 1,
+            connection_display_mode: ConnectionDisplayMode::Offset([0.0, 0.0, 1.0]),
+            rotatable_in_viewer: true,
+            translatable_in_viewer: true,
+            display_bone_in_viewer: true,
+            controllable_in_viewer: true,
+            inherits: BoneInherits {
+                inherit_local: false,
+                rotate_and_translate: RotateAndTranslateInherits::Rotate(0, 0.5),
+            },
+            fixed_axis: Some([0.0, 1.0, 0.0]),
+            physics_after_deform: true,
+            external_parent: Some(3),
+            ik_info: Some(BoneIKInfo {
+                ik_target_bone_index: 0,
+                ik_iter_count: 40,
+                ik_limit_angle: 2.0,
+                ik_links: vec![
+                    IKLink {
+                        ik_bone_index: 0,
+                        angle_limit: Some(([-3.0, 0.0, 0.0], [-0.01, 0.0, 0.0])),
+                    },
+                    IKLink {
+                        ik_bone_index: 0,
+                        angle_limit: None,
+                    },
+                ],
+            }),
+            ..Bone::default()
+        },
+    ];
+    let morph = |name: &str, control_panel: ControlPanel, morph_data: MorphKinds| Morph {
+        name: name.to_owned(),
+        english_name: String::new(),
+        control_panel,
+        morph_data,
+    };
+    let uv_morph = vec![UVMorph {
+        index: 3,
+        offset: [0.1, 0.2, 0.0, 0.0],
+    }];
+    let morphs = vec![
+        morph(
+            "頂点",
+            ControlPanel::BottomLeft,
+            MorphKinds::Vertex(vec![VertexMorph {
+                index: 7,
+                offset: [0.0, 0.5, 0.0],
+            }]),
+        ),
+        morph(
+            "UV",
+            ControlPanel::TopLeft,
+            MorphKinds::UV(uv_morph.clone()),
+        ),
+        morph(
+            "UV1",
+            ControlPanel::TopLeft,
+            MorphKinds::UV1(uv_morph.clone()),
+        ),
+        morph(
+            "UV2",
+            ControlPanel::TopLeft,
+            MorphKinds::UV2(uv_morph.clone()),
+        ),
+        morph(
+            "UV3",
+            ControlPanel::TopLeft,
+            MorphKinds::UV3(uv_morph.clone()),
+        ),
+        morph("UV4", ControlPanel::TopLeft, MorphKinds::UV4(uv_morph)),
+        morph(
+            "ボーン",
+            ControlPanel::TopRight,
+            MorphKinds::Bone(vec![BoneMorph {
+                index: 1,
+                translates: [0.0, 1.0, 0.0],
+                rotates: [0.0, 0.0, 0.0, 1.0],
+            }]),
+        ),
+        morph(
+            "材質",
+            ControlPanel::BottomRight,
+            MorphKinds::Material(vec![MaterialMorph {
+                index: -1,
+                formula: MaterialMorphFormula::Multiply,
+                diffuse: [1.0, 0.0, 0.0, 1.0],
+                specular: [1.0; 3],
+                specular_factor: 1.0,
+                ambient: [1.0; 3],
+                edge_color: [1.0; 4],
+                edge_size: 1.0,
+                texture_factor: [1.0; 4],
+                sphere_texture_factor: [1.0; 4],
+                toon_texture_factor: [1.0; 4],
+            }]),
+        ),
+        morph(
+            "グループ",
+            ControlPanel::BottomRight,
+            MorphKinds::Group(vec![
+                GroupMorph {
+                    index: 0,
+                    morph_factor: 1.0,
+                },
+                GroupMorph {
+                    index: 6,
+                    morph_factor: 0.5,
+                },
+            ]),
+        ),
+        morph(
+            "フリップ",
+            ControlPanel::BottomRight,
+            MorphKinds::Flip(vec![FlipMorph {
+                index: 0,
+                morph_factor: 1.0,
+            }]),
+        ),
+        morph(
+            "インパルス",
+            ControlPanel::System,
+            MorphKinds::Impulse(vec![ImpulseMorph {
+                rigid_index: 0,
+                is_local: true,
+                velocity: [0.0, 1.0, 0.0],
+                torque: [0.0; 3],
+            }]),
+        ),
+    ];
+    let frames = vec![
+        Frame {
+            name: "Root".to_owned(),
+            name_en: "Root".to_owned(),
+            is_special: true,
+            inners: vec![FrameInner::Bone(0)],
+        },
+        Frame {
+            name: "表情".to_owned(),
+            name_en: "Exp".to_owned(),
+            is_special: true,
+            inners: vec![FrameInner::Morph(0), FrameInner::Morph(8)],
+        },
+        Frame {
+            name: "IK".to_owned(),
+            name_en: "IK".to_owned(),
+            is_special: false,
+            inners: vec![FrameInner::Bone(1)],
+        },
+    ];
+    let rigid_bodies = vec![Rigid {
+        name: "センター".to_owned(),
+        name_en: "center".to_owned(),
+        bone_index: 0,
+        group: 0,
+        un_collision_group_flag: RigidGroups::single(0),
+        form: RigidForm::Box,
+        size: [1.0, 1.0, 1.0],
+        position: [0.0; 3],
+        rotation: [0.0; 3],
+        mass: 1.0,
+        move_resist: 0.5,
+        rotation_resist: 0.5,
+        repulsion: 0.0,
+        friction: 0.5,
+        calc_method: RigidCalcMethod::DynamicWithBonePosition,
+    }];
+    let joint_types = vec![
+        JointType::Spring6DOF {
+            a_rigid_index: 0,
+            b_rigid_index: -1,
+            position: [0.0, 1.0, 0.0],
+            rotation: [0.0; 3],
+            move_limit_down: [-1.0; 3],
+            move_limit_up: [1.0; 3],
+            rotation_limit_down: [-0.5; 3],
+            rotation_limit_up: [0.5; 3],
+            spring_const_move: [10.0; 3],
+            spring_const_rotation: [20.0; 3],
+        },
+        JointType::SixDof {
+            a_rigid_index: 0,
+            b_rigid_index: -1,
+            position: [0.0, 1.0, 0.0],
+            rotation: [0.0; 3],
+            move_limit_down: [-1.0; 3],
+            move_limit_up: [1.0; 3],
+            rotation_limit_down: [-0.5; 3],
+            rotation_limit_up: [0.5; 3],
+        },
+        JointType::P2P {
+            a_rigid_index: 0,
+            b_rigid_index: -1,
+            position: [0.0, 1.0, 0.0],
+            rotation: [0.0; 3],
+        },
+        JointType::ConeTwist {
+            a_rigid_index: 0,
+            b_rigid_index: -1,
+            position: [0.0, 2.0, 0.5],
+            rotation: [0.0, 0.5, 0.0],
+            swing_span1: 0.1,
+            swing_span2: 0.2,
+            twist_span: 0.3,
+            softness: 0.4,
+            bias_factor: 0.5,
+            relaxation_factor: 0.6,
+            damping: 0.7,
+            fix_thresh: 0.8,
+            enable_motor: true,
+            max_motor_impulse: 0.9,
+            motor_target_in_constraint_space: [1.0, 2.0, 3.0],
+        },
+        JointType::Slider {
+            a_rigid_index: 0,
+            b_rigid_index: -1,
+            position: [1.0, 0.0, -1.0],
+            rotation: [0.0, 0.5, 0.0],
+            lower_linear_limit: -1.0,
+            upper_linear_limit: 1.0,
+            lower_angle_limit: -0.5,
+            upper_angle_limit: 0.5,
+            power_linear_motor: true,
+            target_linear_motor_velocity: 2.0,
+            max_linear_motor_force: 3.0,
+            power_angler_motor: false,
+            target_angler_motor_velocity: 4.0,
+            max_angler_motor_force: 5.0,
+        },
+        JointType::Hinge {
+            a_rigid_index: 0,
+            b_rigid_index: -1,
+            position: [-1.0, 1.5, 0.0],
+            rotation: [0.0, 0.5, 0.0],
+            low: -1.0,
+            high: 1.0,
+            softness: 0.9,
+            bias_factor: 0.3,
+            relaxation_factor: 1.0,
+            enable_motor: false,
+            target_velocity: 0.0,
+            max_motor_impulse: 0.0,
+        },
+    ];
+    let joints = joint_types
+        .into_iter()
+        .enumerate()
+        .map(|(i, joint_type)| Joint {
+            name: format!("ジョイント{}", i),
+            name_en: format!("joint{}", i),
+            joint_type,
+        })
+        .collect();
+    Model {
+        version: PMXVersion::V21,
+        encode: Encode::Utf16Le,
+        additional_uv: 4,
+        model_info: ModelInfo {
+            name: "キューブ".to_owned(),
+            name_en: "cube".to_owned(),
+            comment: "テスト用".to_owned(),
+            comment_en: "for tests".to_owned(),
+        },
+        vertices,
+        faces,
+        textures: TextureList {
+            textures: vec!["tex\\cube.png".to_owned(), "toon.bmp".to_owned()],
+        },
+        materials: vec![
+            material(
+                "表",
+                Some(SphereMode {
+                    index: 0,
+                    kind: SphereModeKind::Add,
+                }),
+                ToonMode::Separate(1),
+            ),
+            material("裏", None, ToonMode::Common(3)),
+        ],
+        bones,
+        morphs,
+        frames,
+        rigid_bodies,
+        joints,
+        soft_bodies: vec![soft_body()],
+        unknown: Default::default(),
+    }
+}
+
+#[test]
+fn cube_round_trip() {
+    let model = cube();
+    let model_cpy = read(&write(&model));
+    assert_eq!(model.version, model_cpy.version);
+    assert_eq!(model.encode, model_cpy.encode);
+    assert_eq!(model.additional_uv, model_cpy.additional_uv);
+    assert_eq!(model.model_info, model_cpy.model_info);
+    assert_eq!(model.vertices, model_cpy.vertices);
+    assert_eq!(model.faces, model_cpy.faces);
+    assert_eq!(model.textures, model_cpy.textures);
+    assert_eq!(model.materials, model_cpy.materials);
+    assert_eq!(model.bones, model_cpy.bones);
+    assert_eq!(model.morphs, model_cpy.morphs);
+    assert_eq!(model.frames, model_cpy.frames);
+    assert_eq!(model.rigid_bodies, model_cpy.rigid_bodies);
+    assert_eq!(model.joints, model_cpy.joints);
+    assert_eq!(model.soft_bodies, model_cpy.soft_bodies);
+    assert_eq!(model, model_cpy);
+}
+
+#[test]
+fn cube_round_trip_utf8() {
+    let model = Model {
+        encode: Encode::UTF8,
+        ..cube()
+    };
+    assert_eq!(model, read(&write(&model)));
+}
+
+#[test]
+fn pmx_2_0_round_trip() {
+    let mut model = cube();
+    model.version = PMXVersion::V20;
+    model.vertices.iter_mut().for_each(|vertex| {
+        if let VertexWeight::QDEF { .. } = vertex.weight_type {
+            vertex.weight_type = VertexWeight::BDEF1(1);
+        }
+    });
+    model.morphs.truncate(9);
+    model.joints.truncate(1);
+    model.soft_bodies.clear();
+    assert_eq!(model, read(&write(&model)));
+}
@@ -0,0 +1,108 @@
+//! spans of `tracing` feature.
+//!
+//! every section read by stages and written by [`Writer`](crate::writer::Writer) is wrapped in a
+//! `DEBUG` span named `pmx.read.<section>` or `pmx.write.<section>` with `count` field.
+//! warnings of lenient mode are emitted as `DEBUG` events in the span of their section.
+use crate::progress::SectionKind;
+use tracing::span::EnteredSpan;
+
+/// span names need to be static so every section has its own invocation.
+macro_rules! section_span {
+    ($op:literal, $section:expr, $count:expr) => {
+        match $section {
+            SectionKind::ModelInfo => {
+                tracing::debug_span!(concat!("pmx.", $op, ".model_info"), count = $count)
+            }
+            SectionKind::Vertices => {
+                tracing::debug_span!(concat!("pmx.", $op, ".vertices"), count = $count)
+            }
+            SectionKind::Faces => {
+                tracing::debug_span!(concat!("pmx.", $op, ".faces"), count = $count)
+            }
+            SectionKind::Textures => {
+                tracing::debug_span!(concat!("pmx.", $op, ".textures"), count = $count)
+            }
+            SectionKind::Materials => {
+                tracing::debug_span!(concat!("pmx.", $op, ".materials"), count = $count)
+            }
+            SectionKind::Bones => {
+                tracing::debug_span!(concat!("pmx.", $op, ".bones"), count = $count)
+            }
+            SectionKind::Morphs => {
+                tracing::debug_span!(concat!("pmx.", $op, ".morphs"), count = $count)
+            }
+            SectionKind::Frames => {
+                tracing::debug_span!(concat!("pmx.", $op, ".frames"), count = $count)
+            }
+            SectionKind::RigidBodies => {
+                tracing::debug_span!(concat!("pmx.", $op, ".rigid_bodies"), count = $count)
+            }
+            SectionKind::Joints => {
+                tracing::debug_span!(concat!("pmx.", $op, ".joints"), count = $count)
+            }
+            SectionKind::SoftBodies => {
+                tracing::debug_span!(concat!("pmx.", $op, ".soft_bodies"), count = $count)
+            }
+        }
+    };
+}
+
+/// enter span of reading `count` elements of `section`. exited when dropped.
+pub(crate) fn read_section(section: SectionKind, count: usize) -> EnteredSpan {
+    section_span!("read", section, count).entered()
+}
+
+/// enter span of writing `count` elements of `section`. exited when dropped.
+pub(crate) fn write_section(section: SectionKind, count: usize) -> EnteredSpan {
+    section_span!("write", section, count).entered()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::ModelInfoStage;
+    use std::sync::{Arc, Mutex};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// records names of created spans.
+    #[derive(Default)]
+    struct Names(Arc<Mutex<Vec<&'static str>>>);
+
+    impl Subscriber for Names {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut names = self.0.lock().unwrap();
+            names.push(span.metadata().name());
+            Id::from_u64(names.len() as u64)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn section_spans() {
+        let bytes = crate::test_support::write(&crate::test_support::cube());
+        let names = Names::default();
+        let recorded = names.0.clone();
+        tracing::subscriber::with_default(names, || {
+            let model = ModelInfoStage::from_reader(bytes.as_slice())
+                .unwrap()
+                .read_model();
+            crate::writer::Writer::from_model(&model)
+                .write_to_vec()
+                .unwrap();
+        });
+        let names = recorded.lock().unwrap();
+        assert_eq!(names.len(), 22);
+        assert_eq!(names[0], "pmx.read.model_info");
+        assert_eq!(names[1], "pmx.read.vertices");
+        assert_eq!(names[10], "pmx.read.soft_bodies");
+        assert_eq!(names[11], "pmx.write.model_info");
+        assert_eq!(names[21], "pmx.write.soft_bodies");
+    }
+}
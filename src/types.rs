@@ -1,22 +1,39 @@
 //! PMX type definitions.
 
+use crate::writer::{optimal_data_type, optimal_data_type_vertex};
 use bitflags::bitflags;
 use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
 
 pub type Vec2 = [f32; 2];
 pub type Vec3 = [f32; 3];
 pub type Vec4 = [f32; 4];
+/// index of material in the material list.
+pub type MaterialIndex = i32;
 
 /// represent text encoding but all texts in pmx file are converted to String so you don't need to care
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
 pub enum Encode {
     UTF8 = 0x01,
+    #[default]
     Utf16Le = 0x00,
 }
 
+impl Encode {
+    /// bytes `text` takes in this encoding, without the 4 byte length before it.
+    ///
+    /// PMX stores the length as `i32` so longer texts than `i32::MAX` bytes can't be written.
+    pub fn text_len(self, text: &str) -> usize {
+        match self {
+            Encode::UTF8 => text.len(),
+            Encode::Utf16Le => text.encode_utf16().count() * 2,
+        }
+    }
+}
+
 /// PMX仕様.txt 156~173
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct HeaderRaw {
     pub magic: [u8; 4],
     pub version: f32,
@@ -24,16 +41,17 @@ pub struct HeaderRaw {
     pub config: [u8; 8],
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
 pub enum PMXVersion {
+    #[default]
     V20,
     V21,
 }
 
 /// rustic wrapped header.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct Header {
-    pub(crate) magic: String,
+    pub(crate) magic: [u8; 4],
     pub version: PMXVersion,
     pub(crate) length: u8,
     pub encode: Encode,
@@ -44,6 +62,94 @@ pub struct Header {
     pub(crate) s_bone_index: IndexKinds,
     pub(crate) s_morph_index: IndexKinds,
     pub(crate) s_rigid_body_index: IndexKinds,
+    /// bits of version float in file. `None` if not read from file.
+    pub(crate) raw_version: Option<u32>,
+}
+
+/// index kinds of each section used by [`Header::new`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct IndexKindsConfig {
+    pub vertex: VertexIndexKinds,
+    pub texture: IndexKinds,
+    pub material: IndexKinds,
+    pub bone: IndexKinds,
+    pub morph: IndexKinds,
+    pub rigid_body: IndexKinds,
+}
+
+impl Header {
+    /// header with `PMX ` magic and 8 config bytes.
+    ///
+    /// # Errors
+    /// * `HeaderConversionError::InvalidAdditionalUv` if `additional_uv` is over 4
+    pub fn new(
+        version: PMXVersion,
+        encode: Encode,
+        additional_uv: u8,
+        index_kinds: IndexKindsConfig,
+    ) -> Result<Header, HeaderConversionError> {
+        if additional_uv > 4 {
+            return Err(HeaderConversionError::InvalidAdditionalUv);
+        }
+        Ok(Header {
+            magic: *b"PMX ",
+            version,
+            length: 8,
+            encode,
+            additional_uv,
+            s_vertex_index: index_kinds.vertex,
+            s_texture_index: index_kinds.texture,
+            s_material_index: index_kinds.material,
+            s_bone_index: index_kinds.bone,
+            s_morph_index: index_kinds.morph,
+            s_rigid_body_index: index_kinds.rigid_body,
+            raw_version: None,
+        })
+    }
+
+    /// smallest index kinds holding the element counts, chosen as [`Writer`](crate::writer::Writer) does.
+    ///
+    /// UTF-16 PMX 2.0 without additional uv. change fields as needed.
+    pub fn for_counts(
+        vertices: usize,
+        textures: usize,
+        materials: usize,
+        bones: usize,
+        morphs: usize,
+        rigid_bodies: usize,
+    ) -> Header {
+        Header {
+            magic: *b"PMX ",
+            version: PMXVersion::V20,
+            length: 8,
+            encode: Encode::Utf16Le,
+            additional_uv: 0,
+            s_vertex_index: optimal_data_type_vertex(vertices),
+            s_texture_index: optimal_data_type(textures),
+            s_material_index: optimal_data_type(materials),
+            s_bone_index: optimal_data_type(bones),
+            s_morph_index: optimal_data_type(morphs),
+            s_rigid_body_index: optimal_data_type(rigid_bodies),
+            raw_version: None,
+        }
+    }
+
+    /// version float as written in file, e.g. `2.0999999` classified as
+    /// [`PMXVersion::V21`]. `None` if the header was not read from file.
+    pub fn raw_version(&self) -> Option<f32> {
+        self.raw_version.map(f32::from_bits)
+    }
+
+    pub fn index_kinds(&self) -> IndexKindsConfig {
+        IndexKindsConfig {
+            vertex: self.s_vertex_index,
+            texture: self.s_texture_index,
+            material: self.s_material_index,
+            bone: self.s_bone_index,
+            morph: self.s_morph_index,
+            rigid_body: self.s_rigid_body_index,
+        }
+    }
 }
 
 /// Pmx embedded comments and names
@@ -191,28 +297,264 @@ pub struct Vertex {
     pub edge_mag: f32,
 }
 
+/// vertices as separate arrays, one element per vertex in each.
+///
+/// `add_uvs` has one array per additional uv channel.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VertexBuffers {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    pub add_uvs: Vec<Vec<Vec4>>,
+    pub weights: Vec<VertexWeight>,
+    pub edge_mags: Vec<f32>,
+}
+
+impl VertexBuffers {
+    /// empty buffers with `additional_uv` channels.
+    pub fn new(additional_uv: u8) -> Self {
+        Self {
+            add_uvs: vec![vec![]; usize::from(additional_uv.min(4))],
+            ..Self::default()
+        }
+    }
+
+    /// `vertices` keeping first `additional_uv` additional uv channels.
+    pub fn from_vertices(vertices: &[Vertex], additional_uv: u8) -> Self {
+        let mut buffers = Self::new(additional_uv);
+        buffers.reserve(vertices.len());
+        for vertex in vertices {
+            buffers.push(vertex.clone());
+        }
+        buffers
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.positions.reserve(additional);
+        self.normals.reserve(additional);
+        self.uvs.reserve(additional);
+        for channel in &mut self.add_uvs {
+            channel.reserve(additional);
+        }
+        self.weights.reserve(additional);
+        self.edge_mags.reserve(additional);
+    }
+
+    /// append `vertex`. additional uvs after channels of `self` are dropped.
+    pub fn push(&mut self, vertex: Vertex) {
+        self.positions.push(vertex.position);
+        self.normals.push(vertex.norm);
+        self.uvs.push(vertex.uv);
+        for (channel, add_uv) in self.add_uvs.iter_mut().zip(vertex.add_uv.iter()) {
+            channel.push(*add_uv);
+        }
+        self.weights.push(vertex.weight_type);
+        self.edge_mags.push(vertex.edge_mag);
+    }
+
+    /// back to array of [`Vertex`]. channels after 4th are dropped.
+    /// # Panics
+    /// * if arrays have different lengths.
+    pub fn to_vertices(&self) -> Vec<Vertex> {
+        let len = self.len();
+        assert!(
+            self.normals.len() == len
+                && self.uvs.len() == len
+                && self.add_uvs.iter().all(|channel| channel.len() == len)
+                && self.weights.len() == len
+                && self.edge_mags.len() == len,
+            "vertex buffers have different lengths"
+        );
+        (0..len)
+            .map(|i| {
+                let mut add_uv = [[0.0; 4]; 4];
+                for (slot, channel) in add_uv.iter_mut().zip(&self.add_uvs) {
+                    *slot = channel[i];
+                }
+                Vertex {
+                    position: self.positions[i],
+                    norm: self.normals[i],
+                    uv: self.uvs[i],
+                    add_uv,
+                    weight_type: self.weights[i],
+                    edge_mag: self.edge_mags[i],
+                }
+            })
+            .collect()
+    }
+}
+
+/// records of a section as read from a file, see
+/// [`VerticesStage::read_raw`](crate::reader::VerticesStage::read_raw).
+///
+/// records depend on `additional_uv` and `bone_index` of the header they were read with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawSection {
+    pub count: u32,
+    /// records without the count.
+    pub bytes: Vec<u8>,
+    /// start of each record in `bytes`.
+    pub record_offsets: Vec<u32>,
+    /// additional uv count of the header.
+    pub additional_uv: u8,
+    /// bone index size of the header.
+    pub bone_index: IndexKinds,
+}
+
+impl RawSection {
+    /// weight type byte of vertex record `index`, `0` for BDEF1 to `4` for QDEF.
+    pub fn weight_kind(&self, index: usize) -> Option<u8> {
+        let start = *self.record_offsets.get(index)? as usize;
+        // position, normal and uv then additional uvs.
+        let offset = start + 32 + 16 * usize::from(self.additional_uv);
+        self.bytes.get(offset).copied()
+    }
+
+    /// true if a vertex has a 2.1 only weight type.
+    pub fn requires_v21(&self) -> bool {
+        (0..self.record_offsets.len()).any(|index| self.weight_kind(index) == Some(4))
+    }
+}
+
+impl From<VertexBuffers> for Vec<Vertex> {
+    fn from(buffers: VertexBuffers) -> Self {
+        buffers.to_vertices()
+    }
+}
+
+impl VertexWeight {
+    /// bone indices and their weights. SDEF is treated as BDEF2.
+    pub fn bone_weights(&self) -> Vec<(i32, f32)> {
+        match *self {
+            VertexWeight::BDEF1(bone) => vec![(bone, 1.0)],
+            VertexWeight::BDEF2 {
+                bone_index_1,
+                bone_index_2,
+                bone_weight_1,
+            }
+            | VertexWeight::SDEF {
+                bone_index_1,
+                bone_index_2,
+                bone_weight_1,
+                ..
+            } => vec![
+                (bone_index_1, bone_weight_1),
+                (bone_index_2, 1.0 - bone_weight_1),
+            ],
+            VertexWeight::BDEF4 {
+                bone_index_1,
+                bone_index_2,
+                bone_index_3,
+                bone_index_4,
+                bone_weight_1,
+                bone_weight_2,
+                bone_weight_3,
+                bone_weight_4,
+            }
+            | VertexWeight::QDEF {
+                bone_index_1,
+                bone_index_2,
+                bone_index_3,
+                bone_index_4,
+                bone_weight_1,
+                bone_weight_2,
+                bone_weight_3,
+                bone_weight_4,
+            } => vec![
+                (bone_index_1, bone_weight_1),
+                (bone_index_2, bone_weight_2),
+                (bone_index_3, bone_weight_3),
+                (bone_index_4, bone_weight_4),
+            ],
+        }
+    }
+    /// bone indices in the order of [`bone_weights`](Self::bone_weights).
+    pub fn bone_indices_mut(&mut self) -> Vec<&mut i32> {
+        match self {
+            VertexWeight::BDEF1(bone) => vec![bone],
+            VertexWeight::BDEF2 {
+                bone_index_1,
+                bone_index_2,
+                ..
+            }
+            | VertexWeight::SDEF {
+                bone_index_1,
+                bone_index_2,
+                ..
+            } => vec![bone_index_1, bone_index_2],
+            VertexWeight::BDEF4 {
+                bone_index_1,
+                bone_index_2,
+                bone_index_3,
+                bone_index_4,
+                ..
+            }
+            | VertexWeight::QDEF {
+                bone_index_1,
+                bone_index_2,
+                bone_index_3,
+                bone_index_4,
+                ..
+            } => vec![bone_index_1, bone_index_2, bone_index_3, bone_index_4],
+        }
+    }
+}
+
 /// In PMX 2.0 represent one triangle but PMX 2.1 you need to determine drawing primitive.
 ///
 /// # How to determine primitives In  2.1
 /// * `TriangleList` if
-/// `!Material.draw_mode.intersects(MaterialFlags::POINT_DRAW|MaterialFlag::LINE_DRAW)`
+///   `!Material.draw_mode.intersects(MaterialFlags::POINT_DRAW|MaterialFlag::LINE_DRAW)`
 /// * `LineList` if
-/// `Material.draw_mode.intersects(MaterialFlags::LINE_DRAW) && !Material.draw_mode.intersects(MaterialFlag::POINT_DRAW) `
+///   `Material.draw_mode.intersects(MaterialFlags::LINE_DRAW) && !Material.draw_mode.intersects(MaterialFlag::POINT_DRAW) `
 /// * `PointList` if
-/// `Material.draw_mode.intersects(MaterialFlags::POINT_DRAW)`
+///   `Material.draw_mode.intersects(MaterialFlags::POINT_DRAW)`
 ///
 /// # Recording format of each primitive
 /// * `TriangleList`
-///     A-B-C
+///   A-B-C
 /// * `LineList`
-///     A-B-A
-///     so you can drop last point without any problems in this face
+///   A-B-A
+///   so you can drop last point without any problems in this face
 /// * `Point`
-///     A-A-A
-///     so you only need to pass first vertex index in this face
+///   A-A-A
+///   so you only need to pass first vertex index in this face
+///
+/// vertex indices are unsigned since 2.0 of this crate. faces never refer no vertex
+/// and negative indices are rejected by the reader.
+/// `Face::try_from([i32; 3])` converts indices of older code.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct Face {
-    pub vertices: [i32; 3],
+    pub vertices: [u32; 3],
+}
+
+impl From<[u32; 3]> for Face {
+    fn from(vertices: [u32; 3]) -> Self {
+        Self { vertices }
+    }
+}
+
+impl TryFrom<[i32; 3]> for Face {
+    type Error = std::num::TryFromIntError;
+
+    /// fails if an index is negative.
+    fn try_from(vertices: [i32; 3]) -> Result<Self, Self::Error> {
+        Ok(Self {
+            vertices: [
+                u32::try_from(vertices[0])?,
+                u32::try_from(vertices[1])?,
+                u32::try_from(vertices[2])?,
+            ],
+        })
+    }
 }
 /// texture file name list
 ///
@@ -221,10 +563,45 @@ pub struct Face {
 ///
 /// path separator may contains `/` or `\` so unix-like system will need  to convert it
 ///
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
 pub struct TextureList {
     pub textures: Vec<String>,
 }
+
+impl TextureList {
+    /// merge same paths keeping the first one. `/` and `\` are the same separator.
+    ///
+    /// returns new index of each old index.
+    pub fn dedup(&mut self) -> Vec<i32> {
+        self.dedup_with(false)
+    }
+
+    /// [`dedup`](Self::dedup) also ignoring case of paths as Windows does if `ignore_case`.
+    pub fn dedup_with(&mut self, ignore_case: bool) -> Vec<i32> {
+        let key = |path: &str| {
+            let path = path.replace('\\', "/");
+            if ignore_case {
+                path.to_lowercase()
+            } else {
+                path
+            }
+        };
+        let mut kept: Vec<String> = vec![];
+        let mut keys = std::collections::HashMap::new();
+        let mapping = self
+            .textures
+            .drain(..)
+            .map(|path| {
+                *keys.entry(key(&path)).or_insert_with(|| {
+                    kept.push(path);
+                    kept.len() as i32 - 1
+                })
+            })
+            .collect();
+        self.textures = kept;
+        mapping
+    }
+}
 /// how to apply sphere mode texture
 /// refer PMX仕様.txt 295
 ///
@@ -252,9 +629,59 @@ pub struct SphereMode {
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ToonMode {
     Separate(i32),
+    /// 0 ~ 9 refer [`BUILTIN_TOON_TEXTURES`]
     Common(u8),
 }
 
+/// file names of toon textures shipped with `MMD` and `PMXEditor`.
+///
+/// `ToonMode::Common(n)` refers `BUILTIN_TOON_TEXTURES[n]`
+pub const BUILTIN_TOON_TEXTURES: [&str; 10] = [
+    "toon01.bmp",
+    "toon02.bmp",
+    "toon03.bmp",
+    "toon04.bmp",
+    "toon05.bmp",
+    "toon06.bmp",
+    "toon07.bmp",
+    "toon08.bmp",
+    "toon09.bmp",
+    "toon10.bmp",
+];
+
+/// resolved toon texture. see [`ToonMode::resolve`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ToonTextureRef<'a> {
+    /// path in texture list.
+    Texture(&'a str),
+    /// one of [`BUILTIN_TOON_TEXTURES`]
+    BuiltIn(&'static str),
+    /// no toon texture or index is out of range.
+    None,
+}
+
+impl ToonMode {
+    /// `true` if `Common` index is in 0 ~ 9
+    pub fn is_valid_common(&self) -> bool {
+        match self {
+            ToonMode::Separate(_) => true,
+            ToonMode::Common(index) => usize::from(*index) < BUILTIN_TOON_TEXTURES.len(),
+        }
+    }
+    /// get texture path or built-in toon file name.
+    pub fn resolve<'a>(&self, textures: &'a TextureList) -> ToonTextureRef<'a> {
+        match *self {
+            ToonMode::Separate(index) => usize::try_from(index)
+                .ok()
+                .and_then(|index| textures.textures.get(index))
+                .map_or(ToonTextureRef::None, |path| ToonTextureRef::Texture(path)),
+            ToonMode::Common(index) => BUILTIN_TOON_TEXTURES
+                .get(usize::from(index))
+                .map_or(ToonTextureRef::None, |name| ToonTextureRef::BuiltIn(name)),
+        }
+    }
+}
+
 ///
 ///
 ///
@@ -293,16 +720,155 @@ pub struct Material {
     pub num_face_vertices: i32,
 }
 
+/// how to draw faces of the material.
+///
+/// let see [`Face`](crate::types::Face) to get how primitives are recorded.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PrimitiveTopology {
+    TriangleList,
+    /// PMX 2.1 only
+    LineList,
+    /// PMX 2.1 only
+    PointList,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CullMode {
+    /// cull back faces
+    Back,
+    /// draw both faces
+    None,
+}
+
+/// edge (outline) parameters.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EdgeState {
+    pub color: Vec4,
+    pub size: f32,
+}
+
+/// renderer friendly summary of [`MaterialFlags`] and edge parameters.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RenderState {
+    pub cull_mode: CullMode,
+    /// `None` if the material does not have `HAS_EDGE`
+    pub edge: Option<EdgeState>,
+    pub topology: PrimitiveTopology,
+    pub ground_shadow: bool,
+    pub cast_self_shadow: bool,
+    pub receive_self_shadow: bool,
+    pub vertex_color: bool,
+}
+
+impl Material {
+    /// flag 0x01
+    pub fn is_double_sided(&self) -> bool {
+        self.draw_mode.contains(MaterialFlags::DISABLE_CULLING)
+    }
+    /// flag 0x02
+    pub fn casts_ground_shadow(&self) -> bool {
+        self.draw_mode.contains(MaterialFlags::GROUND_SHADOW)
+    }
+    /// flag 0x04
+    pub fn casts_self_shadow(&self) -> bool {
+        self.draw_mode.contains(MaterialFlags::DRAW_SHADOW)
+    }
+    /// flag 0x08
+    pub fn receives_self_shadow(&self) -> bool {
+        self.draw_mode.contains(MaterialFlags::RECEIVE_SHADOW)
+    }
+    /// flag 0x10
+    pub fn has_edge(&self) -> bool {
+        self.draw_mode.contains(MaterialFlags::HAS_EDGE)
+    }
+    /// flag 0x20 PMX 2.1 only
+    pub fn uses_vertex_color(&self) -> bool {
+        self.draw_mode.contains(MaterialFlags::VERTEX_COLOR)
+    }
+    /// determine primitive by flag 0x40 and 0x80.
+    ///
+    /// `POINT_DRAW` takes priority over `LINE_DRAW`.
+    pub fn topology(&self) -> PrimitiveTopology {
+        if self.draw_mode.intersects(MaterialFlags::POINT_DRAW) {
+            PrimitiveTopology::PointList
+        } else if self.draw_mode.intersects(MaterialFlags::LINE_DRAW) {
+            PrimitiveTopology::LineList
+        } else {
+            PrimitiveTopology::TriangleList
+        }
+    }
+    pub fn render_state(&self) -> RenderState {
+        RenderState {
+            cull_mode: if self.is_double_sided() {
+                CullMode::None
+            } else {
+                CullMode::Back
+            },
+            edge: if self.has_edge() {
+                Some(EdgeState {
+                    color: self.edge_color,
+                    size: self.edge_size,
+                })
+            } else {
+                None
+            },
+            topology: self.topology(),
+            ground_shadow: self.casts_ground_shadow(),
+            cast_self_shadow: self.casts_self_shadow(),
+            receive_self_shadow: self.receives_self_shadow(),
+            vertex_color: self.uses_vertex_color(),
+        }
+    }
+
+    pub fn set_double_sided(&mut self, double_sided: bool) {
+        self.draw_mode
+            .set(MaterialFlags::DISABLE_CULLING, double_sided);
+    }
+    pub fn set_ground_shadow(&mut self, ground_shadow: bool) {
+        self.draw_mode
+            .set(MaterialFlags::GROUND_SHADOW, ground_shadow);
+    }
+    pub fn set_self_shadow(&mut self, cast: bool, receive: bool) {
+        self.draw_mode.set(MaterialFlags::DRAW_SHADOW, cast);
+        self.draw_mode.set(MaterialFlags::RECEIVE_SHADOW, receive);
+    }
+    /// set `HAS_EDGE` and edge parameters together.
+    ///
+    /// `None` clears `HAS_EDGE` and keeps edge color and size as is.
+    pub fn set_edge(&mut self, edge: Option<EdgeState>) {
+        self.draw_mode.set(MaterialFlags::HAS_EDGE, edge.is_some());
+        if let Some(edge) = edge {
+            self.edge_color = edge.color;
+            self.edge_size = edge.size;
+        }
+    }
+    pub fn set_vertex_color(&mut self, vertex_color: bool) {
+        self.draw_mode
+            .set(MaterialFlags::VERTEX_COLOR, vertex_color);
+    }
+    /// set `POINT_DRAW` and `LINE_DRAW` so that only one of them is enabled.
+    pub fn set_topology(&mut self, topology: PrimitiveTopology) {
+        self.draw_mode
+            .remove(MaterialFlags::POINT_DRAW | MaterialFlags::LINE_DRAW);
+        match topology {
+            PrimitiveTopology::TriangleList => {}
+            PrimitiveTopology::LineList => self.draw_mode.insert(MaterialFlags::LINE_DRAW),
+            PrimitiveTopology::PointList => self.draw_mode.insert(MaterialFlags::POINT_DRAW),
+        }
+    }
+}
+
 ///from PMX仕様.txt 476 ~ 497
 #[derive(Debug, Clone, PartialEq)]
 pub struct Frame {
     pub name: String,
     pub name_en: String,
+    /// written as 1 byte. only `0` and `1` are valid.
     pub is_special: bool,
     pub inners: Vec<FrameInner>,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum FrameInner {
     Bone(i32),
     Morph(i32),
@@ -351,6 +917,8 @@ pub struct Bone {
     pub external_parent: Option<i32>,
     /// 0x0020 refer PMX仕様.txt 378 ~ 396
     pub ik_info: Option<BoneIKInfo>,
+    /// bits of [`BoneFlags::RESERVED_BITS`] as read. written back as they are for tools using them.
+    pub reserved_flags: u16,
 }
 impl Bone {
     pub fn calculate_bone_flag(&self) -> BoneFlags {
@@ -429,6 +997,12 @@ impl Bone {
         };
         flags
     }
+
+    /// flags as written, [`calculate_bone_flag`](Self::calculate_bone_flag) with
+    /// [`reserved_flags`](Self::reserved_flags).
+    pub fn flag_bits(&self) -> u16 {
+        self.calculate_bone_flag().bits() | (self.reserved_flags & BoneFlags::RESERVED_BITS)
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Default)]
@@ -438,18 +1012,14 @@ pub struct BoneInherits {
 }
 
 /// represents how inherits rotate and translate
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
 pub enum RotateAndTranslateInherits {
+    #[default]
     None,
     Both(i32, f32),
     Rotate(i32, f32),
     Translate(i32, f32),
 }
-impl Default for RotateAndTranslateInherits {
-    fn default() -> Self {
-        Self::None
-    }
-}
 
 /// refer PMX仕様.txt 378 ~ 396
 #[derive(Debug, Clone, PartialEq)]
@@ -479,6 +1049,8 @@ pub struct Morph {
 }
 
 /// where to place morph.
+///
+/// panels hold morphs of a part of face by convention. see [`semantic`](Self::semantic).
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Ord, Eq)]
 pub enum ControlPanel {
     BottomLeft,
@@ -488,6 +1060,92 @@ pub enum ControlPanel {
     System,
 }
 
+/// byte which has no meaning as `kind`, returned by `TryFrom<u8>` of enums like [`ControlPanel`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct UnknownValue {
+    /// name of the enum.
+    pub kind: &'static str,
+    pub value: u8,
+}
+
+impl Display for UnknownValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is not a valid {}", self.value, self.kind)
+    }
+}
+
+impl std::error::Error for UnknownValue {}
+
+/// part of face controlled by morphs of a [`ControlPanel`], as labeled in `PMXEditor`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FacialCategory {
+    /// 眉, bottom left.
+    Eyebrow,
+    /// 目, top left.
+    Eye,
+    /// リップ, top right.
+    Mouth,
+    /// その他, bottom right.
+    Other,
+    /// not shown in panels.
+    System,
+}
+
+impl ControlPanel {
+    /// category of morphs placed in the panel.
+    pub fn semantic(&self) -> FacialCategory {
+        match self {
+            ControlPanel::BottomLeft => FacialCategory::Eyebrow,
+            ControlPanel::TopLeft => FacialCategory::Eye,
+            ControlPanel::TopRight => FacialCategory::Mouth,
+            ControlPanel::BottomRight => FacialCategory::Other,
+            ControlPanel::System => FacialCategory::System,
+        }
+    }
+}
+
+impl From<FacialCategory> for ControlPanel {
+    fn from(category: FacialCategory) -> Self {
+        match category {
+            FacialCategory::Eyebrow => ControlPanel::BottomLeft,
+            FacialCategory::Eye => ControlPanel::TopLeft,
+            FacialCategory::Mouth => ControlPanel::TopRight,
+            FacialCategory::Other => ControlPanel::BottomRight,
+            FacialCategory::System => ControlPanel::System,
+        }
+    }
+}
+
+impl TryFrom<u8> for ControlPanel {
+    type Error = UnknownValue;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::System),
+            1 => Ok(Self::BottomLeft),
+            2 => Ok(Self::TopLeft),
+            3 => Ok(Self::TopRight),
+            4 => Ok(Self::BottomRight),
+            value => Err(UnknownValue {
+                kind: "ControlPanel",
+                value,
+            }),
+        }
+    }
+}
+
+impl From<ControlPanel> for u8 {
+    fn from(panel: ControlPanel) -> Self {
+        match panel {
+            ControlPanel::System => 0,
+            ControlPanel::BottomLeft => 1,
+            ControlPanel::TopLeft => 2,
+            ControlPanel::TopRight => 3,
+            ControlPanel::BottomRight => 4,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum MorphKinds {
     Vertex(Vec<VertexMorph>),
@@ -505,13 +1163,15 @@ pub enum MorphKinds {
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct VertexMorph {
-    pub index: i32,
+    /// vertex index. unsigned like [`Face::vertices`].
+    pub index: u32,
     pub offset: Vec3,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct UVMorph {
-    pub index: i32,
+    /// vertex index. unsigned like [`Face::vertices`].
+    pub index: u32,
     pub offset: Vec4,
 }
 
@@ -531,7 +1191,7 @@ pub struct BoneMorph {
 #[derive(Debug, Clone, PartialEq)]
 pub struct MaterialMorph {
     pub index: i32,
-    pub formula: u8,
+    pub formula: MaterialMorphFormula,
     pub diffuse: Vec4,
     pub specular: Vec3,
     pub specular_factor: f32,
@@ -543,6 +1203,44 @@ pub struct MaterialMorph {
     pub toon_texture_factor: Vec4,
 }
 
+/// how to apply [`MaterialMorph`] to material.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MaterialMorphFormula {
+    /// 0
+    Multiply,
+    /// 1
+    Additive,
+    /// byte other than 0 and 1 read in lenient mode. written back as it is.
+    ///
+    /// `TryFrom<u8>` never makes it.
+    Unknown(u8),
+}
+
+impl TryFrom<u8> for MaterialMorphFormula {
+    type Error = UnknownValue;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Multiply),
+            1 => Ok(Self::Additive),
+            value => Err(UnknownValue {
+                kind: "MaterialMorphFormula",
+                value,
+            }),
+        }
+    }
+}
+
+impl From<MaterialMorphFormula> for u8 {
+    fn from(formula: MaterialMorphFormula) -> Self {
+        match formula {
+            MaterialMorphFormula::Multiply => 0,
+            MaterialMorphFormula::Additive => 1,
+            MaterialMorphFormula::Unknown(value) => value,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct FlipMorph {
     pub index: i32,
@@ -552,6 +1250,7 @@ pub struct FlipMorph {
 #[derive(Debug, Clone, PartialEq)]
 pub struct ImpulseMorph {
     pub rigid_index: i32,
+    /// written as 1 byte. only `0` and `1` are valid.
     pub is_local: bool,
     pub velocity: Vec3,
     pub torque: Vec3,
@@ -563,7 +1262,7 @@ pub struct Rigid {
     pub name_en: String,
     pub bone_index: i32,
     pub group: u8,
-    pub un_collision_group_flag: u16,
+    pub un_collision_group_flag: RigidGroups,
     pub form: RigidForm,
     pub size: Vec3,
     pub position: Vec3,
@@ -576,6 +1275,128 @@ pub struct Rigid {
     pub calc_method: RigidCalcMethod,
 }
 
+impl Rigid {
+    /// `true` if this rigid and `other` are not excluded by each other's non collision group.
+    pub fn collides_with(&self, other: &Rigid) -> bool {
+        self.un_collision_group_flag
+            .collides_with(&RigidGroups::single(other.group))
+            && other
+                .un_collision_group_flag
+                .collides_with(&RigidGroups::single(self.group))
+    }
+    /// `size` interpreted by `form`. unused components are ignored.
+    pub fn shape(&self) -> RigidShape {
+        let [x, y, z] = self.size;
+        match self.form {
+            RigidForm::Sphere => RigidShape::Sphere { radius: x },
+            RigidForm::Box => RigidShape::Box {
+                half_extents: [x, y, z],
+            },
+            RigidForm::Capsule => RigidShape::Capsule {
+                radius: x,
+                height: y,
+            },
+        }
+    }
+    /// set `form` and `size` from `shape`. unused components of `size` are set to zero.
+    pub fn set_shape(&mut self, shape: RigidShape) {
+        let (form, size) = match shape {
+            RigidShape::Sphere { radius } => (RigidForm::Sphere, [radius, 0.0, 0.0]),
+            RigidShape::Box { half_extents } => (RigidForm::Box, half_extents),
+            RigidShape::Capsule { radius, height } => (RigidForm::Capsule, [radius, height, 0.0]),
+        };
+        self.form = form;
+        self.size = size;
+    }
+}
+
+/// typed [`Rigid::size`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RigidShape {
+    /// size `[radius, 0, 0]`
+    Sphere { radius: f32 },
+    /// size is half of width, height and depth.
+    Box { half_extents: Vec3 },
+    /// size `[radius, height, 0]`. `height` is the length between centers of hemispheres.
+    Capsule { radius: f32, height: f32 },
+}
+
+/// set of rigid body collision groups.
+///
+/// used as non collision group flag of [`Rigid`] and [`SoftBody`].
+/// bit `n` represents group `n` (0 origin, PMXEditor shows it as `n + 1`).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct RigidGroups(u16);
+
+impl RigidGroups {
+    /// number of groups available in PMX.
+    pub const GROUP_COUNT: u8 = 16;
+
+    /// contains no group.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+    /// contains all 16 groups.
+    pub const fn all() -> Self {
+        Self(u16::MAX)
+    }
+    /// contains only `group`.
+    pub fn single(group: u8) -> Self {
+        Self::empty().with_group(group)
+    }
+    pub const fn bits(&self) -> u16 {
+        self.0
+    }
+    /// `false` if `group` is out of range (16 or more).
+    pub fn contains_group(&self, group: u8) -> bool {
+        group < Self::GROUP_COUNT && self.0 & (1 << group) != 0
+    }
+    /// `true` if no group in `groups` is contained in this set.
+    ///
+    /// when this set is a non collision group flag it means the rigid collides with `groups`.
+    pub fn collides_with(&self, groups: &RigidGroups) -> bool {
+        self.0 & groups.0 == 0
+    }
+    /// add `group` to this set.
+    /// # Panics
+    /// * if `group` is 16 or more.
+    pub fn with_group(self, group: u8) -> Self {
+        assert!(
+            group < Self::GROUP_COUNT,
+            "rigid group {} out of range",
+            group
+        );
+        Self(self.0 | (1 << group))
+    }
+    /// remove `group` from this set.
+    /// # Panics
+    /// * if `group` is 16 or more.
+    pub fn without_group(self, group: u8) -> Self {
+        assert!(
+            group < Self::GROUP_COUNT,
+            "rigid group {} out of range",
+            group
+        );
+        Self(self.0 & !(1 << group))
+    }
+    /// iterate contained groups in ascending order.
+    pub fn groups(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..Self::GROUP_COUNT).filter(move |group| self.contains_group(*group))
+    }
+}
+
+impl From<u16> for RigidGroups {
+    fn from(bits: u16) -> Self {
+        Self(bits)
+    }
+}
+
+impl From<RigidGroups> for u16 {
+    fn from(groups: RigidGroups) -> Self {
+        groups.0
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum RigidForm {
     Sphere,
@@ -595,7 +1416,7 @@ pub enum RigidCalcMethod {
 }
 
 /// C bridge
-#[repr(packed)]
+#[repr(C, packed)]
 pub(crate) struct JointParameterRaw {
     pub(crate) joint_type: u8,
     pub(crate) a_rigid_index: i32,
@@ -617,6 +1438,9 @@ pub struct Joint {
     pub joint_type: JointType,
 }
 
+/// joint kinds. 2.1 only kinds pack their parameters into the slots of `Spring6DOF`.
+///
+/// slots not used by a kind are read as is and written as zero.
 #[derive(Clone, Debug, PartialEq)]
 pub enum JointType {
     Spring6DOF {
@@ -650,6 +1474,8 @@ pub enum JointType {
     ConeTwist {
         a_rigid_index: i32,
         b_rigid_index: i32,
+        position: Vec3,
+        rotation: Vec3,
         swing_span1: f32,
         swing_span2: f32,
         twist_span: f32,
@@ -665,6 +1491,8 @@ pub enum JointType {
     Slider {
         a_rigid_index: i32,
         b_rigid_index: i32,
+        position: Vec3,
+        rotation: Vec3,
         lower_linear_limit: f32,
         upper_linear_limit: f32,
         lower_angle_limit: f32,
@@ -679,6 +1507,8 @@ pub enum JointType {
     Hinge {
         a_rigid_index: i32,
         b_rigid_index: i32,
+        position: Vec3,
+        rotation: Vec3,
         low: f32,
         high: f32,
         softness: f32,
@@ -691,15 +1521,15 @@ pub enum JointType {
     },
 }
 /// from Util 0.5.0
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SoftBody {
     pub name: String,
     pub name_en: String,
     pub form: SoftBodyForm, //i8
     pub material_index: i32,
     pub group: u8,
-    pub un_collision_group_flag: u16,
-    pub bit_flag: u8,
+    pub un_collision_group_flag: RigidGroups,
+    pub bit_flag: SoftBodyFlags,
     pub b_link_create_distance: i32,
     pub clusters: i32,
     pub mass: f32,
@@ -737,18 +1567,19 @@ pub struct SoftBody {
     pub anchor_rigid: Vec<SoftBodyAnchorRigid>,
     pub pin_vertex: Vec<i32>,
 }
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct SoftBodyAnchorRigid {
     pub rigid_index: i32,
     pub vertex_index: i32,
+    /// written as 1 byte, `0` for off and `1` for on. other values are read as off in lenient mode.
     pub near_mode: bool,
 }
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum SoftBodyForm {
     TriMesh,
     Rope,
 }
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum SoftBodyAeroModel {
     VPoint,
     VTwoSide,
@@ -758,6 +1589,7 @@ pub enum SoftBodyAeroModel {
 }
 
 bitflags! {
+    /// every bit has a name, so flags are kept as read.
     pub struct MaterialFlags :u8 {
         const DISABLE_CULLING =0x01;
         const GROUND_SHADOW =0x02;
@@ -771,6 +1603,29 @@ bitflags! {
 }
 
 bitflags! {
+    /// PMX 2.1 soft body flags
+    pub struct SoftBodyFlags : u8 {
+        const B_LINK_CREATION = 0x01;
+        const CLUSTER_CREATION = 0x02;
+        const LINK_HYBRID = 0x04;
+    }
+}
+
+impl SoftBodyFlags {
+    /// flags keeping bits the spec doesn't define, so they are written back as read.
+    pub fn from_bits_retain(bits: u8) -> Self {
+        // SAFETY: any bits are a valid value. unknown bits are only kept, not interpreted.
+        unsafe { Self::from_bits_unchecked(bits) }
+    }
+}
+
+bitflags! {
+    /// flags of [`Bone`] derived by [`Bone::calculate_bone_flag`].
+    ///
+    /// payloads follow the flags in order of connection, inherit, fixed axis, local axis,
+    /// external parent then IK. `INHERIT_LOCAL` has no payload of its own.
+    /// undefined bits `0x0040`, `0x4000` and `0x8000` have no payload and are kept in
+    /// [`Bone::reserved_flags`].
     pub struct BoneFlags : u16{
         const CONNECT_TO_OTHER_BONE=0x01;
         const ROTATABLE =0x02;
@@ -788,14 +1643,19 @@ bitflags! {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+impl BoneFlags {
+    /// bits not defined by the spec. not a flag so `BoneFlags` never holds them.
+    pub const RESERVED_BITS: u16 = 0xC040;
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum IndexKinds {
     I8,
     I16,
     I32,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum VertexIndexKinds {
     U8,
     U16,
@@ -847,9 +1707,1286 @@ impl From<VertexIndexKinds> for u8 {
     }
 }
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum HeaderConversionError {
     InvalidMagic,
     InvalidEncoding,
     InvalidIndex,
     InvalidVersion,
+    /// fewer bytes than [`Header::SIZE`].
+    Truncated,
+    /// more than 4 additional uv.
+    InvalidAdditionalUv,
+}
+
+impl Display for HeaderConversionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeaderConversionError::InvalidMagic => write!(f, "not a PMX file"),
+            HeaderConversionError::InvalidEncoding => write!(f, "unknown text encoding"),
+            HeaderConversionError::InvalidIndex => write!(f, "invalid index size"),
+            HeaderConversionError::InvalidVersion => write!(f, "unsupported PMX version"),
+            HeaderConversionError::Truncated => write!(f, "header is truncated"),
+            HeaderConversionError::InvalidAdditionalUv => {
+                write!(f, "additional uv count is over 4")
+            }
+        }
+    }
+}
+
+impl std::error::Error for HeaderConversionError {}
+
+/// error of [`Model::recalculate_num_face_vertices`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum FaceCountError {
+    LengthMismatch {
+        counts: usize,
+        materials: usize,
+    },
+    /// counts sum to `counted` faces but the model has `faces`.
+    SumMismatch {
+        counted: usize,
+        faces: usize,
+    },
+    /// 3 times of a count is over `i32::MAX`.
+    TooBig,
+}
+
+impl Display for FaceCountError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FaceCountError::LengthMismatch { counts, materials } => write!(
+                f,
+                "{} face counts are given for {} materials",
+                counts, materials
+            ),
+            FaceCountError::SumMismatch { counted, faces } => {
+                write!(
+                    f,
+                    "face counts sum to {} but there are {} faces",
+                    counted, faces
+                )
+            }
+            FaceCountError::TooBig => write!(f, "face count is too big"),
+        }
+    }
+}
+
+impl std::error::Error for FaceCountError {}
+
+/// whole content of a PMX file.
+///
+/// `Model` is `Send` and `Sync` so it can be shared between threads, e.g. by `Arc`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Model {
+    pub version: PMXVersion,
+    pub encode: Encode,
+    pub additional_uv: u8,
+    pub model_info: ModelInfo,
+    pub vertices: Vec<Vertex>,
+    pub faces: Vec<Face>,
+    pub textures: TextureList,
+    pub materials: Vec<Material>,
+    pub bones: Vec<Bone>,
+    pub morphs: Vec<Morph>,
+    pub frames: Vec<Frame>,
+    pub rigid_bodies: Vec<Rigid>,
+    pub joints: Vec<Joint>,
+    /// always empty in PMX 2.0
+    pub soft_bodies: Vec<SoftBody>,
+    /// data this crate doesn't parse. see [`ParseOptions::preserve_unknown`](crate::reader::ParseOptions::preserve_unknown).
+    pub unknown: UnknownData,
+}
+
+/// raw bytes kept to write back as they are.
+///
+/// indices inside them are not updated when elements are added, removed or reordered,
+/// so keep them only when editing values in place.
+/// writing fails if index sizes would differ from [`header`](Self::header).
+/// unknown morph kinds can't be kept because their size is unknown and reading fails at them.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct UnknownData {
+    /// bytes after the last section.
+    pub trailing: Vec<u8>,
+    /// header of the file the bytes come from. index sizes in it apply to the bytes.
+    pub header: Option<Header>,
+}
+
+impl UnknownData {
+    pub fn is_empty(&self) -> bool {
+        self.trailing.is_empty()
+    }
+}
+
+impl Model {
+    /// [`TextureList::dedup`] and update texture indices of materials.
+    ///
+    /// returns new index of each old index.
+    pub fn dedup_textures(&mut self) -> Vec<i32> {
+        self.dedup_textures_with(false)
+    }
+
+    /// [`dedup_textures`](Self::dedup_textures) also ignoring case if `ignore_case`.
+    pub fn dedup_textures_with(&mut self, ignore_case: bool) -> Vec<i32> {
+        let mapping = self.textures.dedup_with(ignore_case);
+        let remap = |index: &mut i32| {
+            if let Some(&new) = usize::try_from(*index).ok().and_then(|i| mapping.get(i)) {
+                *index = new;
+            }
+        };
+        for material in &mut self.materials {
+            remap(&mut material.texture_index);
+            if let Some(sphere) = &mut material.sphere_mode {
+                remap(&mut sphere.index);
+            }
+            if let ToonMode::Separate(index) = &mut material.toon_mode {
+                remap(index);
+            }
+        }
+        mapping
+    }
+
+    /// set `num_face_vertices` of each material from its face count in `per_material_counts`.
+    ///
+    /// # Errors
+    /// * `FaceCountError::LengthMismatch` if counts are not given for each material.
+    /// * `FaceCountError::SumMismatch` if counts don't sum to the face count.
+    /// * `FaceCountError::TooBig` if a count doesn't fit in `num_face_vertices`.
+    ///
+    /// materials are unchanged on error.
+    pub fn recalculate_num_face_vertices(
+        &mut self,
+        per_material_counts: &[usize],
+    ) -> Result<(), FaceCountError> {
+        if per_material_counts.len() != self.materials.len() {
+            return Err(FaceCountError::LengthMismatch {
+                counts: per_material_counts.len(),
+                materials: self.materials.len(),
+            });
+        }
+        let counted = per_material_counts.iter().sum::<usize>();
+        if counted != self.faces.len() {
+            return Err(FaceCountError::SumMismatch {
+                counted,
+                faces: self.faces.len(),
+            });
+        }
+        let counts = per_material_counts
+            .iter()
+            .map(|count| {
+                count
+                    .checked_mul(3)
+                    .and_then(|count| i32::try_from(count).ok())
+                    .ok_or(FaceCountError::TooBig)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for (material, count) in self.materials.iter_mut().zip(counts) {
+            material.num_face_vertices = count;
+        }
+        Ok(())
+    }
+
+    /// make `num_face_vertices` of materials cover faces exactly keeping them in order.
+    ///
+    /// materials keep their faces while faces are left and the last material takes
+    /// all the rest, so faces without a material, e.g. counts left 0 after adding faces,
+    /// are drawn with the last material. counts are rounded down to whole faces.
+    ///
+    /// returns `true` if a count changed. nothing changes without materials.
+    pub fn assign_remaining_faces(&mut self) -> bool {
+        let mut remaining = self.faces.len();
+        let last = self.materials.len().saturating_sub(1);
+        let mut changed = false;
+        for (index, material) in self.materials.iter_mut().enumerate() {
+            let count = if index == last {
+                remaining
+            } else {
+                (usize::try_from(material.num_face_vertices).unwrap_or(0) / 3).min(remaining)
+            };
+            remaining -= count;
+            let count = i32::try_from(count * 3).unwrap_or(i32::MAX);
+            changed |= material.num_face_vertices != count;
+            material.num_face_vertices = count;
+        }
+        changed
+    }
+
+    /// minimum and maximum of vertex positions. `None` if there are no vertices.
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        let first = self.vertices.first()?.position;
+        Some(
+            self.vertices
+                .iter()
+                .fold((first, first), |(mut min, mut max), vertex| {
+                    for axis in 0..3 {
+                        min[axis] = min[axis].min(vertex.position[axis]);
+                        max[axis] = max[axis].max(vertex.position[axis]);
+                    }
+                    (min, max)
+                }),
+        )
+    }
+
+    /// version, encoding and element counts in a few lines.
+    pub fn summary(&self) -> String {
+        format!(
+            "PMX {} {} additional uv: {}\n\
+             {}\n\
+             vertices: {} faces: {} textures: {} materials: {}\n\
+             bones: {} morphs: {} frames: {}\n\
+             rigid bodies: {} joints: {} soft bodies: {}",
+            self.version,
+            self.encode,
+            self.additional_uv,
+            self.model_info,
+            self.vertices.len(),
+            self.faces.len(),
+            self.textures.textures.len(),
+            self.materials.len(),
+            self.bones.len(),
+            self.morphs.len(),
+            self.frames.len(),
+            self.rigid_bodies.len(),
+            self.joints.len(),
+            self.soft_bodies.len()
+        )
+    }
+}
+
+impl Display for PMXVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PMXVersion::V20 => write!(f, "2.0"),
+            PMXVersion::V21 => write!(f, "2.1"),
+        }
+    }
+}
+
+impl Display for Header {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kinds = self.index_kinds();
+        write!(
+            f,
+            "PMX {} {} additional uv: {}\n\
+             index sizes: vertex {} texture {} material {} bone {} morph {} rigid body {}",
+            self.version,
+            self.encode,
+            self.additional_uv,
+            u8::from(kinds.vertex),
+            u8::from(kinds.texture),
+            u8::from(kinds.material),
+            u8::from(kinds.bone),
+            u8::from(kinds.morph),
+            u8::from(kinds.rigid_body)
+        )
+    }
+}
+
+impl Display for Encode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Encode::UTF8 => write!(f, "UTF-8"),
+            Encode::Utf16Le => write!(f, "UTF-16LE"),
+        }
+    }
+}
+
+impl Display for ModelInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "\"{}\" (\"{}\") comment: {} chars",
+            self.name,
+            self.name_en,
+            self.comment.chars().count()
+        )
+    }
+}
+
+impl Display for Material {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Material \"{}\" faces: {} diffuse: {:?} texture: {} flags: {:#04x}",
+            self.name,
+            self.num_face_vertices / 3,
+            self.diffuse,
+            self.texture_index,
+            self.draw_mode.bits()
+        )?;
+        if let Some(sphere_mode) = self.sphere_mode {
+            write!(f, " sphere: {:?}({})", sphere_mode.kind, sphere_mode.index)?;
+        }
+        write!(f, " toon: {:?}", self.toon_mode)
+    }
+}
+
+impl Display for Bone {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Bone \"{}\" parent: {} position: {:?} flags: {:#06x}",
+            self.name,
+            self.parent,
+            self.position,
+            self.flag_bits()
+        )?;
+        if let Some(ik_info) = &self.ik_info {
+            write!(
+                f,
+                " IK target: {} links: {}",
+                ik_info.ik_target_bone_index,
+                ik_info.ik_links.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl MorphKinds {
+    /// name of variant.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            MorphKinds::Vertex(_) => "Vertex",
+            MorphKinds::UV(_) => "UV",
+            MorphKinds::UV1(_) => "UV1",
+            MorphKinds::UV2(_) => "UV2",
+            MorphKinds::UV3(_) => "UV3",
+            MorphKinds::UV4(_) => "UV4",
+            MorphKinds::Bone(_) => "Bone",
+            MorphKinds::Material(_) => "Material",
+            MorphKinds::Group(_) => "Group",
+            MorphKinds::Flip(_) => "Flip",
+            MorphKinds::Impulse(_) => "Impulse",
+        }
+    }
+    /// index of additional uv channel in [`Vertex::add_uv`] targeted by `UV1`..`UV4`.
+    ///
+    /// `None` for other kinds including `UV` which targets the base uv.
+    pub fn uv_channel(&self) -> Option<usize> {
+        match self {
+            MorphKinds::UV1(_) => Some(0),
+            MorphKinds::UV2(_) => Some(1),
+            MorphKinds::UV3(_) => Some(2),
+            MorphKinds::UV4(_) => Some(3),
+            _ => None,
+        }
+    }
+    /// number of offsets.
+    pub fn offset_count(&self) -> usize {
+        match self {
+            MorphKinds::Vertex(x) => x.len(),
+            MorphKinds::UV(x)
+            | MorphKinds::UV1(x)
+            | MorphKinds::UV2(x)
+            | MorphKinds::UV3(x)
+            | MorphKinds::UV4(x) => x.len(),
+            MorphKinds::Bone(x) => x.len(),
+            MorphKinds::Material(x) => x.len(),
+            MorphKinds::Group(x) => x.len(),
+            MorphKinds::Flip(x) => x.len(),
+            MorphKinds::Impulse(x) => x.len(),
+        }
+    }
+}
+
+impl Display for Morph {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Morph \"{}\" {} offsets: {} panel: {:?}",
+            self.name,
+            self.morph_data.kind_name(),
+            self.morph_data.offset_count(),
+            self.control_panel
+        )
+    }
+}
+
+impl Display for Rigid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Rigid \"{}\" {:?} {:?} bone: {} group: {} size: {:?}",
+            self.name, self.form, self.calc_method, self.bone_index, self.group, self.size
+        )
+    }
+}
+
+impl JointType {
+    /// name of variant.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            JointType::Spring6DOF { .. } => "Spring6DOF",
+            JointType::SixDof { .. } => "SixDof",
+            JointType::P2P { .. } => "P2P",
+            JointType::ConeTwist { .. } => "ConeTwist",
+            JointType::Slider { .. } => "Slider",
+            JointType::Hinge { .. } => "Hinge",
+        }
+    }
+    /// rigid body indices connected by the joint.
+    pub fn rigid_indices(&self) -> (i32, i32) {
+        match *self {
+            JointType::Spring6DOF {
+                a_rigid_index,
+                b_rigid_index,
+                ..
+            }
+            | JointType::SixDof {
+                a_rigid_index,
+                b_rigid_index,
+                ..
+            }
+            | JointType::P2P {
+                a_rigid_index,
+                b_rigid_index,
+                ..
+            }
+            | JointType::ConeTwist {
+                a_rigid_index,
+                b_rigid_index,
+                ..
+            }
+            | JointType::Slider {
+                a_rigid_index,
+                b_rigid_index,
+                ..
+            }
+            | JointType::Hinge {
+                a_rigid_index,
+                b_rigid_index,
+                ..
+            } => (a_rigid_index, b_rigid_index),
+        }
+    }
+    /// position of the joint in model space.
+    pub fn position_mut(&mut self) -> &mut Vec3 {
+        match self {
+            JointType::Spring6DOF { position, .. }
+            | JointType::SixDof { position, .. }
+            | JointType::P2P { position, .. }
+            | JointType::ConeTwist { position, .. }
+            | JointType::Slider { position, .. }
+            | JointType::Hinge { position, .. } => position,
+        }
+    }
+    /// mutable [`rigid_indices`](Self::rigid_indices).
+    pub fn rigid_indices_mut(&mut self) -> (&mut i32, &mut i32) {
+        match self {
+            JointType::Spring6DOF {
+                a_rigid_index,
+                b_rigid_index,
+                ..
+            }
+            | JointType::SixDof {
+                a_rigid_index,
+                b_rigid_index,
+                ..
+            }
+            | JointType::P2P {
+                a_rigid_index,
+                b_rigid_index,
+                ..
+            }
+            | JointType::ConeTwist {
+                a_rigid_index,
+                b_rigid_index,
+                ..
+            }
+            | JointType::Slider {
+                a_rigid_index,
+                b_rigid_index,
+                ..
+            }
+            | JointType::Hinge {
+                a_rigid_index,
+                b_rigid_index,
+                ..
+            } => (a_rigid_index, b_rigid_index),
+        }
+    }
+}
+
+impl Display for Joint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (a, b) = self.joint_type.rigid_indices();
+        write!(
+            f,
+            "Joint \"{}\" {} rigid: {} - {}",
+            self.name,
+            self.joint_type.kind_name(),
+            a,
+            b
+        )
+    }
+}
+
+impl Display for SoftBody {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "SoftBody \"{}\" {:?} material: {} anchors: {} pins: {}",
+            self.name,
+            self.form,
+            self.material_index,
+            self.anchor_rigid.len(),
+            self.pin_vertex.len()
+        )
+    }
+}
+
+/// skeleton without PMX index encoding. see [`SkeletonDesc::from_bones`].
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SkeletonDesc {
+    pub bones: Vec<SkeletonBone>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SkeletonBone {
+    pub name: String,
+    pub name_en: String,
+    /// `None` for root bones.
+    pub parent: Option<usize>,
+    pub rest_position: Vec3,
+    pub deform_depth: i32,
+    pub physics_after_deform: bool,
+    pub inherit: Option<InheritDesc>,
+    pub ik: Option<IkChainDesc>,
+}
+
+/// rotation and/or translation copied from another bone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InheritDesc {
+    pub source: usize,
+    pub weight: f32,
+    pub rotation: bool,
+    pub translation: bool,
+    /// inherits local transform of `source`.
+    pub local: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IkChainDesc {
+    /// bone moved to reach the IK bone.
+    pub target: usize,
+    pub iterations: i32,
+    /// radian per iteration.
+    pub limit_angle: f32,
+    /// from the target side to the root side.
+    pub links: Vec<IkLinkDesc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IkLinkDesc {
+    pub bone: usize,
+    /// lower and upper euler angle limits in radian.
+    pub angle_limit: Option<(Vec3, Vec3)>,
+}
+
+impl SkeletonDesc {
+    /// describe `bones`. references to missing bones are dropped.
+    pub fn from_bones(bones: &[Bone]) -> Self {
+        let index = |index: i32| usize::try_from(index).ok().filter(|&i| i < bones.len());
+        let bones = bones
+            .iter()
+            .map(|bone| {
+                let inherit = match bone.inherits.rotate_and_translate {
+                    RotateAndTranslateInherits::None => None,
+                    RotateAndTranslateInherits::Both(source, weight) => {
+                        Some((source, weight, true, true))
+                    }
+                    RotateAndTranslateInherits::Rotate(source, weight) => {
+                        Some((source, weight, true, false))
+                    }
+                    RotateAndTranslateInherits::Translate(source, weight) => {
+                        Some((source, weight, false, true))
+                    }
+                }
+                .and_then(|(source, weight, rotation, translation)| {
+                    Some(InheritDesc {
+                        source: index(source)?,
+                        weight,
+                        rotation,
+                        translation,
+                        local: bone.inherits.inherit_local,
+                    })
+                });
+                let ik = bone.ik_info.as_ref().and_then(|ik| {
+                    Some(IkChainDesc {
+                        target: index(ik.ik_target_bone_index)?,
+                        iterations: ik.ik_iter_count,
+                        limit_angle: ik.ik_limit_angle,
+                        links: ik
+                            .ik_links
+                            .iter()
+                            .filter_map(|link| {
+                                Some(IkLinkDesc {
+                                    bone: index(link.ik_bone_index)?,
+                                    angle_limit: link.angle_limit,
+                                })
+                            })
+                            .collect(),
+                    })
+                });
+                SkeletonBone {
+                    name: bone.name.clone(),
+                    name_en: bone.english_name.clone(),
+                    parent: index(bone.parent),
+                    rest_position: bone.position,
+                    deform_depth: bone.deform_depth,
+                    physics_after_deform: bone.physics_after_deform,
+                    inherit,
+                    ik,
+                }
+            })
+            .collect();
+        Self { bones }
+    }
+
+    /// bone indices in the order `MMD` transforms them.
+    ///
+    /// bones before physics come first, then sorted by deform depth keeping index order.
+    pub fn deform_order(&self) -> Vec<usize> {
+        let mut order = (0..self.bones.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| {
+            let bone = &self.bones[i];
+            (bone.physics_after_deform, bone.deform_depth)
+        });
+        order
+    }
+
+    /// semi standard bones found by Japanese name. first one wins if names are duplicated.
+    pub fn classify_standard_bones(&self) -> Vec<(usize, StandardBone)> {
+        let mut found: Vec<(usize, StandardBone)> = vec![];
+        for (index, bone) in self.bones.iter().enumerate() {
+            if let Some(standard) = StandardBone::from_name(&bone.name) {
+                if found.iter().all(|(_, s)| *s != standard) {
+                    found.push((index, standard));
+                }
+            }
+        }
+        found
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BoneSide {
+    Left,
+    Right,
+}
+
+/// semi standard bones of `MMD` humanoid models.
+///
+/// finger joints are numbered as in names. thumb has 0 ~ 2 and others 1 ~ 3.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StandardBone {
+    /// 全ての親
+    Master,
+    /// センター
+    Center,
+    /// グルーブ
+    Groove,
+    /// 腰
+    Waist,
+    /// 上半身
+    UpperBody,
+    /// 上半身2
+    UpperBody2,
+    /// 下半身
+    LowerBody,
+    /// 首
+    Neck,
+    /// 頭
+    Head,
+    /// 両目
+    Eyes,
+    Eye(BoneSide),
+    Shoulder(BoneSide),
+    Arm(BoneSide),
+    /// 腕捩
+    ArmTwist(BoneSide),
+    Elbow(BoneSide),
+    /// 手捩
+    WristTwist(BoneSide),
+    Wrist(BoneSide),
+    Thumb(BoneSide, u8),
+    Index(BoneSide, u8),
+    Middle(BoneSide, u8),
+    Ring(BoneSide, u8),
+    Little(BoneSide, u8),
+    Leg(BoneSide),
+    Knee(BoneSide),
+    Ankle(BoneSide),
+    Toe(BoneSide),
+    LegIk(BoneSide),
+    ToeIk(BoneSide),
+    /// 足D
+    LegD(BoneSide),
+    /// ひざD
+    KneeD(BoneSide),
+    /// 足首D
+    AnkleD(BoneSide),
+    /// 足先EX
+    ToeEx(BoneSide),
+}
+
+impl StandardBone {
+    /// classify bone `name`. full width and half width digits and "IK" are both accepted.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name
+            .chars()
+            .map(|c| match c {
+                '０'..='９' | 'Ａ'..='Ｚ' => {
+                    char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap_or(c)
+                }
+                c => c,
+            })
+            .collect::<String>();
+        let center = match name.as_str() {
+            "全ての親" => Some(StandardBone::Master),
+            "センター" => Some(StandardBone::Center),
+            "グルーブ" => Some(StandardBone::Groove),
+            "腰" => Some(StandardBone::Waist),
+            "上半身" => Some(StandardBone::UpperBody),
+            "上半身2" => Some(StandardBone::UpperBody2),
+            "下半身" => Some(StandardBone::LowerBody),
+            "首" => Some(StandardBone::Neck),
+            "頭" => Some(StandardBone::Head),
+            "両目" => Some(StandardBone::Eyes),
+            _ => None,
+        };
+        if center.is_some() {
+            return center;
+        }
+        let (side, rest) = if let Some(rest) = name.strip_prefix('左') {
+            (BoneSide::Left, rest)
+        } else if let Some(rest) = name.strip_prefix('右') {
+            (BoneSide::Right, rest)
+        } else {
+            return None;
+        };
+        let finger = |rest: &str, prefix: &str, joints: std::ops::RangeInclusive<u8>| {
+            let joint = rest.strip_prefix(prefix)?.parse::<u8>().ok()?;
+            Some(joint).filter(|joint| joints.contains(joint))
+        };
+        let bone = match rest {
+            "目" => StandardBone::Eye(side),
+            "肩" => StandardBone::Shoulder(side),
+            "腕" => StandardBone::Arm(side),
+            "腕捩" => StandardBone::ArmTwist(side),
+            "ひじ" => StandardBone::Elbow(side),
+            "手捩" => StandardBone::WristTwist(side),
+            "手首" => StandardBone::Wrist(side),
+            "足" => StandardBone::Leg(side),
+            "ひざ" => StandardBone::Knee(side),
+            "足首" => StandardBone::Ankle(side),
+            "つま先" => StandardBone::Toe(side),
+            "足IK" => StandardBone::LegIk(side),
+            "つま先IK" => StandardBone::ToeIk(side),
+            "足D" => StandardBone::LegD(side),
+            "ひざD" => StandardBone::KneeD(side),
+            "足首D" => StandardBone::AnkleD(side),
+            "足先EX" => StandardBone::ToeEx(side),
+            rest => {
+                if let Some(joint) = finger(rest, "親指", 0..=2) {
+                    StandardBone::Thumb(side, joint)
+                } else if let Some(joint) = finger(rest, "人指", 1..=3) {
+                    StandardBone::Index(side, joint)
+                } else if let Some(joint) = finger(rest, "中指", 1..=3) {
+                    StandardBone::Middle(side, joint)
+                } else if let Some(joint) = finger(rest, "薬指", 1..=3) {
+                    StandardBone::Ring(side, joint)
+                } else if let Some(joint) = finger(rest, "小指", 1..=3) {
+                    StandardBone::Little(side, joint)
+                } else {
+                    return None;
+                }
+            }
+        };
+        Some(bone)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::{
+        Bone, BoneIKInfo, ControlPanel, CullMode, EdgeState, Face, FaceCountError,
+        HeaderConversionError, Joint, JointType, Material, MaterialFlags, Model, ModelInfo, Morph,
+        MorphKinds, PrimitiveTopology, Rigid, RigidCalcMethod, RigidForm, RigidGroups, TextureList,
+        ToonMode, ToonTextureRef, VertexBuffers, VertexMorph, BUILTIN_TOON_TEXTURES,
+    };
+    use crate::types::{
+        BoneSide, InheritDesc, RigidShape, SkeletonDesc, SphereMode, SphereModeKind, StandardBone,
+    };
+
+    fn material(draw_mode: MaterialFlags) -> Material {
+        Material {
+            name: String::new(),
+            english_name: String::new(),
+            diffuse: [1.0; 4],
+            specular: [0.0; 3],
+            specular_factor: 0.0,
+            ambient: [0.5; 3],
+            draw_mode,
+            edge_color: [0.1, 0.2, 0.3, 1.0],
+            edge_size: 2.0,
+            texture_index: -1,
+            sphere_mode: None,
+            toon_mode: ToonMode::Common(0),
+            memo: String::new(),
+            num_face_vertices: 0,
+        }
+    }
+
+    #[test]
+    fn material_flag_predicates() {
+        for bits in 0..=u8::MAX {
+            let source = material(MaterialFlags::from_bits_truncate(bits));
+            let state = source.render_state();
+            assert_eq!(source.is_double_sided(), bits & 0x01 != 0);
+            assert_eq!(source.casts_ground_shadow(), bits & 0x02 != 0);
+            assert_eq!(source.casts_self_shadow(), bits & 0x04 != 0);
+            assert_eq!(source.receives_self_shadow(), bits & 0x08 != 0);
+            assert_eq!(source.has_edge(), bits & 0x10 != 0);
+            assert_eq!(source.uses_vertex_color(), bits & 0x20 != 0);
+            assert_eq!(
+                state.cull_mode,
+                if bits & 0x01 != 0 {
+                    CullMode::None
+                } else {
+                    CullMode::Back
+                }
+            );
+            assert_eq!(
+                state.edge,
+                if bits & 0x10 != 0 {
+                    Some(EdgeState {
+                        color: [0.1, 0.2, 0.3, 1.0],
+                        size: 2.0,
+                    })
+                } else {
+                    None
+                }
+            );
+            let topology = match (bits & 0x40 != 0, bits & 0x80 != 0) {
+                (true, _) => PrimitiveTopology::PointList,
+                (false, true) => PrimitiveTopology::LineList,
+                (false, false) => PrimitiveTopology::TriangleList,
+            };
+            assert_eq!(state.topology, topology);
+
+            // setters must reproduce the same flags.
+            let mut rebuilt = material(MaterialFlags::empty());
+            rebuilt.set_double_sided(source.is_double_sided());
+            rebuilt.set_ground_shadow(source.casts_ground_shadow());
+            rebuilt.set_self_shadow(source.casts_self_shadow(), source.receives_self_shadow());
+            rebuilt.set_edge(state.edge);
+            rebuilt.set_vertex_color(source.uses_vertex_color());
+            rebuilt.set_topology(state.topology);
+            assert_eq!(rebuilt.render_state(), state);
+        }
+    }
+
+    #[test]
+    fn set_topology_keeps_single_primitive_bit() {
+        let mut material = material(MaterialFlags::POINT_DRAW | MaterialFlags::LINE_DRAW);
+        material.set_topology(PrimitiveTopology::LineList);
+        assert_eq!(material.draw_mode, MaterialFlags::LINE_DRAW);
+        material.set_topology(PrimitiveTopology::TriangleList);
+        assert!(material.draw_mode.is_empty());
+        material.set_edge(Some(EdgeState {
+            color: [1.0; 4],
+            size: 0.5,
+        }));
+        assert!(material.has_edge());
+        assert_eq!(material.edge_size, 0.5);
+    }
+
+    #[test]
+    fn resolve_toon() {
+        let textures = TextureList {
+            textures: vec!["tex.png".to_owned(), "toon\\skin.bmp".to_owned()],
+        };
+        assert_eq!(
+            ToonMode::Separate(1).resolve(&textures),
+            ToonTextureRef::Texture("toon\\skin.bmp")
+        );
+        assert_eq!(
+            ToonMode::Separate(-1).resolve(&textures),
+            ToonTextureRef::None
+        );
+        assert_eq!(
+            ToonMode::Separate(2).resolve(&textures),
+            ToonTextureRef::None
+        );
+        assert_eq!(
+            ToonMode::Common(0).resolve(&textures),
+            ToonTextureRef::BuiltIn("toon01.bmp")
+        );
+        assert_eq!(
+            ToonMode::Common(9).resolve(&textures),
+            ToonTextureRef::BuiltIn("toon10.bmp")
+        );
+        assert_eq!(
+            ToonMode::Common(10).resolve(&textures),
+            ToonTextureRef::None
+        );
+        assert!(!ToonMode::Common(10).is_valid_common());
+        assert_eq!(BUILTIN_TOON_TEXTURES.len(), 10);
+    }
+
+    #[test]
+    fn display() {
+        let mut model = Model {
+            model_info: ModelInfo {
+                name: "モデル".to_owned(),
+                name_en: "model".to_owned(),
+                comment: "コメント".to_owned(),
+                comment_en: String::new(),
+            },
+            faces: vec![
+                Face {
+                    vertices: [0, 1, 2]
+                };
+                4
+            ],
+            ..Model::default()
+        };
+        let mut material = material(MaterialFlags::HAS_EDGE | MaterialFlags::GROUND_SHADOW);
+        material.name = "体".to_owned();
+        material.num_face_vertices = 12;
+        model.materials.push(material);
+        model.bones.push(Bone {
+            name: "右足ＩＫ".to_owned(),
+            parent: 0,
+            position: [1.0, 2.0, 0.5],
+            rotatable_in_viewer: true,
+            ik_info: Some(BoneIKInfo {
+                ik_target_bone_index: 3,
+                ik_iter_count: 40,
+                ik_limit_angle: 2.0,
+                ik_links: vec![],
+            }),
+            ..Bone::default()
+        });
+        model.morphs.push(Morph {
+            name: "あ".to_owned(),
+            english_name: "a".to_owned(),
+            control_panel: ControlPanel::TopRight,
+            morph_data: MorphKinds::Vertex(vec![
+                VertexMorph {
+                    index: 0,
+                    offset: [0.0; 3]
+                };
+                120
+            ]),
+        });
+        model.rigid_bodies.push(Rigid {
+            name: "頭".to_owned(),
+            name_en: "head".to_owned(),
+            bone_index: 5,
+            group: 0,
+            un_collision_group_flag: RigidGroups::empty(),
+            form: RigidForm::Sphere,
+            size: [1.0, 0.0, 0.0],
+            position: [0.0; 3],
+            rotation: [0.0; 3],
+            mass: 1.0,
+            move_resist: 0.5,
+            rotation_resist: 0.5,
+            repulsion: 0.0,
+            friction: 0.5,
+            calc_method: RigidCalcMethod::Static,
+        });
+        model.joints.push(Joint {
+            name: "首".to_owned(),
+            name_en: "neck".to_owned(),
+            joint_type: JointType::P2P {
+                a_rigid_index: 0,
+                b_rigid_index: 1,
+                position: [0.0; 3],
+                rotation: [0.0; 3],
+            },
+        });
+
+        assert_eq!(
+            model.materials[0].to_string(),
+            "Material \"体\" faces: 4 diffuse: [1.0, 1.0, 1.0, 1.0] texture: -1 flags: 0x12 toon: Common(0)"
+        );
+        assert_eq!(
+            model.bones[0].to_string(),
+            "Bone \"右足ＩＫ\" parent: 0 position: [1.0, 2.0, 0.5] flags: 0x0023 IK target: 3 links: 0"
+        );
+        assert_eq!(
+            model.morphs[0].to_string(),
+            "Morph \"あ\" Vertex offsets: 120 panel: TopRight"
+        );
+        assert_eq!(
+            model.rigid_bodies[0].to_string(),
+            "Rigid \"頭\" Sphere Static bone: 5 group: 0 size: [1.0, 0.0, 0.0]"
+        );
+        assert_eq!(model.joints[0].to_string(), "Joint \"首\" P2P rigid: 0 - 1");
+        assert_eq!(
+            model.summary(),
+            "PMX 2.0 UTF-16LE additional uv: 0\n\
+             \"モデル\" (\"model\") comment: 4 chars\n\
+             vertices: 0 faces: 4 textures: 0 materials: 1\n\
+             bones: 1 morphs: 1 frames: 0\n\
+             rigid bodies: 1 joints: 1 soft bodies: 0"
+        );
+    }
+
+    #[test]
+    fn header_error_display() {
+        assert_eq!(
+            HeaderConversionError::InvalidMagic.to_string(),
+            "not a PMX file"
+        );
+        assert_eq!(
+            HeaderConversionError::InvalidVersion.to_string(),
+            "unsupported PMX version"
+        );
+    }
+
+    #[test]
+    fn vertex_buffers_round_trip() {
+        let mut vertices = crate::test_support::cube().vertices;
+        vertices[0].add_uv[0] = [1.0, 0.0, 0.0, 1.0];
+        let buffers = VertexBuffers::from_vertices(&vertices, 1);
+        assert_eq!(buffers.len(), vertices.len());
+        assert_eq!(buffers.add_uvs.len(), 1);
+        assert_eq!(buffers.add_uvs[0][0], [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(Vec::from(buffers), vertices);
+
+        // channels not kept are zero
+        vertices[1].add_uv[1] = [2.0; 4];
+        let restored = VertexBuffers::from_vertices(&vertices, 1).to_vertices();
+        assert_eq!(restored[1].add_uv[1], [0.0; 4]);
+    }
+
+    #[test]
+    fn skeleton_desc() {
+        let bones = crate::builder::standard_humanoid_skeleton();
+        let skeleton = SkeletonDesc::from_bones(&bones);
+        let index = |name: &str| bones.iter().position(|bone| bone.name == name).unwrap();
+        assert_eq!(skeleton.bones.len(), bones.len());
+        assert_eq!(skeleton.bones[0].parent, None);
+        assert_eq!(skeleton.bones[index("首")].parent, Some(index("上半身2")));
+        assert_eq!(
+            skeleton.bones[index("左目")].inherit,
+            Some(InheritDesc {
+                source: index("両目"),
+                weight: 1.0,
+                rotation: true,
+                translation: false,
+                local: false,
+            })
+        );
+        let ik = skeleton.bones[index("左足ＩＫ")].ik.as_ref().unwrap();
+        assert_eq!(ik.target, index("左足首"));
+        let links = ik.links.iter().map(|link| link.bone).collect::<Vec<_>>();
+        assert_eq!(links, [index("左ひざ"), index("左足")]);
+        assert!(ik.links[0].angle_limit.is_some());
+
+        // IKs and eyes have deform depth 1 and 2
+        let order = skeleton.deform_order();
+        let position = |name: &str| order.iter().position(|&i| i == index(name)).unwrap();
+        assert!(position("左足ＩＫ") > position("左つま先"));
+        assert!(position("左目") > position("右つま先ＩＫ"));
+    }
+
+    #[test]
+    fn standard_bones() {
+        let bones = crate::builder::standard_humanoid_skeleton();
+        let skeleton = SkeletonDesc::from_bones(&bones);
+        let standard = skeleton.classify_standard_bones();
+        assert_eq!(standard.len(), bones.len());
+        assert!(standard.contains(&(0, StandardBone::Master)));
+        assert_eq!(
+            StandardBone::from_name("右足IK"),
+            Some(StandardBone::LegIk(BoneSide::Right))
+        );
+        assert_eq!(
+            StandardBone::from_name("左親指０"),
+            Some(StandardBone::Thumb(BoneSide::Left, 0))
+        );
+        assert_eq!(
+            StandardBone::from_name("左人指3"),
+            Some(StandardBone::Index(BoneSide::Left, 3))
+        );
+        assert_eq!(StandardBone::from_name("左人指0"), None);
+        assert_eq!(StandardBone::from_name("髪"), None);
+    }
+
+    #[test]
+    fn dedup_textures() {
+        let mut model = Model::default();
+        model.textures.textures = [
+            "tex/body.png",
+            "tex\\face.png",
+            "tex\\body.png",
+            "sph/hair.spa",
+            "TEX/Body.png",
+            "tex/body.png",
+        ]
+        .iter()
+        .map(|path| path.to_string())
+        .collect();
+        for texture in 0..6 {
+            let mut material = Material::builder("m").build();
+            material.texture_index = texture;
+            material.sphere_mode = Some(SphereMode {
+                index: 3,
+                kind: SphereModeKind::Add,
+            });
+            material.toon_mode = ToonMode::Separate(5);
+            model.materials.push(material);
+        }
+        let before = model
+            .materials
+            .iter()
+            .map(|material| {
+                model.textures.textures[material.texture_index as usize].replace('\\', "/")
+            })
+            .collect::<Vec<_>>();
+
+        let mut case_sensitive = model.textures.clone();
+        assert_eq!(case_sensitive.dedup(), [0, 1, 0, 2, 3, 0]);
+        assert_eq!(case_sensitive.textures.len(), 4);
+
+        assert_eq!(model.dedup_textures_with(true), [0, 1, 0, 2, 0, 0]);
+        assert_eq!(
+            model.textures.textures,
+            ["tex/body.png", "tex\\face.png", "sph/hair.spa"]
+        );
+        for (material, path) in model.materials.iter().zip(&before) {
+            let texture = &model.textures.textures[material.texture_index as usize];
+            assert!(texture.replace('\\', "/").eq_ignore_ascii_case(path));
+            assert_eq!(material.sphere_mode.unwrap().index, 2);
+            assert_eq!(material.toon_mode, ToonMode::Separate(0));
+        }
+    }
+
+    #[test]
+    fn rigid_shapes() {
+        let mut rigid = crate::test_support::cube().rigid_bodies[0].clone();
+        assert_eq!(
+            rigid.shape(),
+            RigidShape::Box {
+                half_extents: [1.0, 1.0, 1.0]
+            }
+        );
+        // garbage in unused components is ignored by `shape` and cleared by `set_shape`.
+        rigid.form = RigidForm::Sphere;
+        rigid.size = [0.5, 7.0, 8.0];
+        let sphere = RigidShape::Sphere { radius: 0.5 };
+        assert_eq!(rigid.shape(), sphere);
+        rigid.set_shape(sphere);
+        assert_eq!(rigid.size, [0.5, 0.0, 0.0]);
+
+        rigid.form = RigidForm::Capsule;
+        rigid.size = [0.25, 2.0, 9.0];
+        let capsule = RigidShape::Capsule {
+            radius: 0.25,
+            height: 2.0,
+        };
+        assert_eq!(rigid.shape(), capsule);
+        rigid.set_shape(capsule);
+        assert_eq!(rigid.size, [0.25, 2.0, 0.0]);
+
+        let cuboid = RigidShape::Box {
+            half_extents: [1.0, 2.0, 3.0],
+        };
+        rigid.set_shape(cuboid);
+        assert_eq!(rigid.form, RigidForm::Box);
+        assert_eq!(rigid.size, [1.0, 2.0, 3.0]);
+        assert_eq!(rigid.shape(), cuboid);
+    }
+
+    #[test]
+    fn face_counts() {
+        let mut model = Model {
+            faces: vec![
+                Face {
+                    vertices: [0, 1, 2]
+                };
+                5
+            ],
+            materials: vec![material(MaterialFlags::empty()); 3],
+            ..Model::default()
+        };
+        assert_eq!(
+            model.recalculate_num_face_vertices(&[1, 2]),
+            Err(FaceCountError::LengthMismatch {
+                counts: 2,
+                materials: 3
+            })
+        );
+        assert_eq!(
+            model.recalculate_num_face_vertices(&[1, 2, 3]),
+            Err(FaceCountError::SumMismatch {
+                counted: 6,
+                faces: 5
+            })
+        );
+        model.recalculate_num_face_vertices(&[1, 0, 4]).unwrap();
+        let counts = |model: &Model| {
+            model
+                .materials
+                .iter()
+                .map(|material| material.num_face_vertices)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(counts(&model), [3, 0, 12]);
+        assert!(!model.assign_remaining_faces());
+
+        // faces added after materials go to the last one.
+        model.faces.extend(model.faces.clone());
+        assert!(model.assign_remaining_faces());
+        assert_eq!(counts(&model), [3, 0, 27]);
+        // faces removed from the end are taken from the last materials.
+        model.materials[1].num_face_vertices = 4;
+        model.faces.truncate(1);
+        assert!(model.assign_remaining_faces());
+        assert_eq!(counts(&model), [3, 0, 0]);
+    }
+
+    #[test]
+    fn control_panel_categories() {
+        use crate::types::FacialCategory;
+        use std::convert::TryFrom;
+        let expected = [
+            (0, FacialCategory::System),
+            (1, FacialCategory::Eyebrow),
+            (2, FacialCategory::Eye),
+            (3, FacialCategory::Mouth),
+            (4, FacialCategory::Other),
+        ];
+        let mut model = crate::test_support::cube();
+        for (byte, category) in expected {
+            let panel = ControlPanel::from(category);
+            assert_eq!(ControlPanel::try_from(byte), Ok(panel));
+            assert_eq!(u8::from(panel), byte);
+            assert_eq!(panel.semantic(), category);
+            model.morphs[0].control_panel = panel;
+            let read = crate::test_support::read(&crate::test_support::write(&model));
+            assert_eq!(read.morphs[0].control_panel.semantic(), category);
+        }
+        assert!(ControlPanel::try_from(5).is_err());
+    }
+
+    #[test]
+    fn model_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Model>();
+    }
 }
@@ -0,0 +1,197 @@
+//! Which elements use which.
+//!
+//! ```rust
+//! let model = PMXUtil::types::Model::default();
+//! for (vertex, materials) in PMXUtil::usage::vertex_materials(&model).iter().enumerate() {
+//!     println!("vertex {} is drawn by materials {:?}", vertex, materials);
+//! }
+//! ```
+use crate::types::{FrameInner, MaterialIndex, Model, MorphKinds, RotateAndTranslateInherits};
+use bitflags::bitflags;
+use std::convert::TryFrom;
+use std::ops::Range;
+
+bitflags! {
+    /// how a bone is used. see [`bone_usage`].
+    pub struct BoneUses: u8 {
+        /// a vertex has non zero weight on it.
+        const SKINNING = 0x01;
+        /// a rigid body follows it.
+        const RIGID = 0x02;
+        /// IK bone, its target or a link.
+        const IK = 0x04;
+        /// another bone inherits rotation or translation from it.
+        const APPEND_PARENT = 0x08;
+        /// a bone morph moves it.
+        const MORPH = 0x10;
+        /// a display frame shows it.
+        const FRAME = 0x20;
+    }
+}
+
+/// uses of each bone returned by [`bone_usage`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct BoneUsage {
+    pub uses: Vec<BoneUses>,
+}
+
+impl BoneUsage {
+    /// uses of `bone`. empty if out of range.
+    pub fn get(&self, bone: usize) -> BoneUses {
+        self.uses.get(bone).copied().unwrap_or_else(BoneUses::empty)
+    }
+    /// `true` if `bone` exists and is at most shown in display frames.
+    ///
+    /// [`remove_bones`](crate::convert::remove_bones) updates frames and reparents children
+    /// so they don't keep the bone.
+    pub fn is_removable(&self, bone: usize) -> bool {
+        bone < self.uses.len() && (self.get(bone) - BoneUses::FRAME).is_empty()
+    }
+}
+
+/// find how each bone is used by vertices, rigid bodies, IK, inheritance, morphs and frames.
+///
+/// out of range bone indices are ignored.
+pub fn bone_usage(model: &Model) -> BoneUsage {
+    let mut uses = vec![BoneUses::empty(); model.bones.len()];
+    let mut set = |bone: i32, usage: BoneUses| {
+        if let Some(uses) = usize::try_from(bone)
+            .ok()
+            .and_then(|bone| uses.get_mut(bone))
+        {
+            uses.insert(usage);
+        }
+    };
+    for vertex in &model.vertices {
+        for (bone, weight) in vertex.weight_type.bone_weights() {
+            if weight != 0.0 {
+                set(bone, BoneUses::SKINNING);
+            }
+        }
+    }
+    for rigid in &model.rigid_bodies {
+        set(rigid.bone_index, BoneUses::RIGID);
+    }
+    for (index, bone) in model.bones.iter().enumerate() {
+        if let Some(ik) = &bone.ik_info {
+            set(index as i32, BoneUses::IK);
+            set(ik.ik_target_bone_index, BoneUses::IK);
+            for link in &ik.ik_links {
+                set(link.ik_bone_index, BoneUses::IK);
+            }
+        }
+        match bone.inherits.rotate_and_translate {
+            RotateAndTranslateInherits::Both(parent, _)
+            | RotateAndTranslateInherits::Rotate(parent, _)
+            | RotateAndTranslateInherits::Translate(parent, _) => {
+                set(parent, BoneUses::APPEND_PARENT)
+            }
+            RotateAndTranslateInherits::None => {}
+        }
+    }
+    for morph in &model.morphs {
+        if let MorphKinds::Bone(offsets) = &morph.morph_data {
+            for offset in offsets {
+                set(offset.index, BoneUses::MORPH);
+            }
+        }
+    }
+    for inner in model.frames.iter().flat_map(|frame| &frame.inners) {
+        if let FrameInner::Bone(bone) = *inner {
+            set(bone, BoneUses::FRAME);
+        }
+    }
+    BoneUsage { uses }
+}
+
+/// faces drawn by each material, in order of materials.
+///
+/// ranges are clamped to the face count when `num_face_vertices` exceed it.
+pub fn material_faces(model: &Model) -> Vec<Range<usize>> {
+    let mut start = 0;
+    model
+        .materials
+        .iter()
+        .map(|material| {
+            let end = (start + usize::try_from(material.num_face_vertices).unwrap_or(0) / 3)
+                .min(model.faces.len());
+            let range = start..end;
+            start = end;
+            range
+        })
+        .collect()
+}
+
+/// materials drawing each vertex in ascending order.
+///
+/// vertices not referred by any face of materials have no material.
+/// out of range vertex indices in faces are ignored.
+pub fn vertex_materials(model: &Model) -> Vec<Vec<MaterialIndex>> {
+    let mut materials = vec![Vec::new(); model.vertices.len()];
+    for (material, faces) in material_faces(model).into_iter().enumerate() {
+        let material = material as MaterialIndex;
+        for vertex in model.faces[faces].iter().flat_map(|face| face.vertices) {
+            let used = usize::try_from(vertex)
+                .ok()
+                .and_then(|vertex| materials.get_mut(vertex));
+            if let Some(used) = used {
+                // materials are visited in order so duplicates are adjacent.
+                if used.last() != Some(&material) {
+                    used.push(material);
+                }
+            }
+        }
+    }
+    materials
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::{Bone, FrameInner};
+    use crate::usage::{bone_usage, material_faces, vertex_materials, BoneUses};
+
+    #[test]
+    fn bone_uses() {
+        let mut model = crate::test_support::cube();
+        model.bones.push(Bone::builder("arm").parent(0).build());
+        model.bones.push(Bone::builder("arm tip").parent(2).build());
+        model.frames[2].inners.push(FrameInner::Bone(3));
+        let usage = bone_usage(&model);
+        assert!(usage.get(0).contains(
+            BoneUses::SKINNING
+                | BoneUses::RIGID
+                | BoneUses::IK
+                | BoneUses::APPEND_PARENT
+                | BoneUses::FRAME
+        ));
+        assert_eq!(
+            usage.get(1),
+            BoneUses::SKINNING | BoneUses::IK | BoneUses::MORPH | BoneUses::FRAME
+        );
+        assert!(usage.get(2).is_empty());
+        assert_eq!(usage.get(3), BoneUses::FRAME);
+        assert!(usage.is_removable(2));
+        assert!(usage.is_removable(3));
+        assert!(!usage.is_removable(1));
+        assert!(!usage.is_removable(4));
+    }
+
+    #[test]
+    fn materials_of_vertices() {
+        let mut model = crate::test_support::cube();
+        model.materials.truncate(2);
+        model.materials[0].num_face_vertices = 6;
+        model.materials[1].num_face_vertices = 6;
+        model.faces[0].vertices = [0, 1, 2];
+        model.faces[1].vertices = [0, 2, 3];
+        model.faces[2].vertices = [2, 4, 100];
+        model.faces[3].vertices = [4, 5, 6];
+        assert_eq!(material_faces(&model), [0..2, 2..4]);
+        let materials = vertex_materials(&model);
+        assert_eq!(materials.len(), model.vertices.len());
+        assert_eq!(materials[0], [0]);
+        assert_eq!(materials[2], [0, 1]);
+        assert_eq!(materials[4], [1]);
+        assert!(materials[7].is_empty());
+    }
+}
@@ -0,0 +1,2197 @@
+//! Consistency checks beyond what reader guarantees.
+//!
+//! ```rust
+//! use PMXUtil::builder::standard_humanoid_skeleton;
+//! use PMXUtil::validation::check_ik;
+//!
+//! let bones = standard_humanoid_skeleton();
+//! assert!(check_ik(&bones).is_empty());
+//! ```
+use crate::frames::{is_special_frame, EXPRESSION_FRAME_NAME};
+use crate::progress::SectionKind;
+use crate::types::{
+    Bone, ConnectionDisplayMode, ControlPanel, Face, Frame, FrameInner, JointType, Model, Morph,
+    MorphKinds, PrimitiveTopology, Rigid, RigidForm, RotateAndTranslateInherits, SoftBodyForm,
+    Vertex, VertexWeight,
+};
+use crate::usage::{material_faces, vertex_materials};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::f32::consts::PI;
+use std::fmt::{Display, Formatter};
+
+/// problem of IK setup found by [`check_ik`].
+///
+/// `bone` is index of the IK bone and `link` is index in its links.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IkIssue {
+    /// target bone index is out of range.
+    InvalidTarget { bone: usize, target: i32 },
+    /// link bone index is out of range.
+    InvalidLink {
+        bone: usize,
+        link: usize,
+        link_bone: i32,
+    },
+    /// link is not an ancestor of the target or the previous link.
+    LinkNotAncestor {
+        bone: usize,
+        link: usize,
+        link_bone: i32,
+    },
+    /// some component of angle limit has min > max.
+    InvertedAngleLimit { bone: usize, link: usize },
+    /// `ik_iter_count` is 0 or less.
+    NoIteration { bone: usize, iterations: i32 },
+    /// `ik_limit_angle` is 0 or less.
+    NonPositiveLimitAngle { bone: usize, limit_angle: f32 },
+}
+
+impl Display for IkIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IkIssue::InvalidTarget { bone, target } => {
+                write!(f, "IK bone {}: target {} does not exist", bone, target)
+            }
+            IkIssue::InvalidLink {
+                bone,
+                link,
+                link_bone,
+            } => write!(
+                f,
+                "IK bone {}: link {} refers bone {} which does not exist",
+                bone, link, link_bone
+            ),
+            IkIssue::LinkNotAncestor {
+                bone,
+                link,
+                link_bone,
+            } => write!(
+                f,
+                "IK bone {}: link {} (bone {}) is not an ancestor of the previous chain",
+                bone, link, link_bone
+            ),
+            IkIssue::InvertedAngleLimit { bone, link } => {
+                write!(
+                    f,
+                    "IK bone {}: link {} has min > max angle limit",
+                    bone, link
+                )
+            }
+            IkIssue::NoIteration { bone, iterations } => {
+                write!(f, "IK bone {}: iteration count is {}", bone, iterations)
+            }
+            IkIssue::NonPositiveLimitAngle { bone, limit_angle } => {
+                write!(f, "IK bone {}: limit angle is {}", bone, limit_angle)
+            }
+        }
+    }
+}
+
+fn bone_index(bones: &[Bone], index: i32) -> Option<usize> {
+    usize::try_from(index)
+        .ok()
+        .filter(|index| *index < bones.len())
+}
+
+/// true if `ancestor` is a parent of `bone` or its ancestors.
+fn is_ancestor(bones: &[Bone], ancestor: usize, bone: usize) -> bool {
+    let mut current = bone;
+    // step limit guards broken parent loops
+    for _ in 0..bones.len() {
+        match bone_index(bones, bones[current].parent) {
+            Some(parent) if parent == ancestor => return true,
+            Some(parent) => current = parent,
+            None => return false,
+        }
+    }
+    false
+}
+
+/// check every IK bone in `bones`.
+///
+/// links must be listed from the target side and each one must be an ancestor of the previous
+/// link or the target.
+pub fn check_ik(bones: &[Bone]) -> Vec<IkIssue> {
+    let mut issues = vec![];
+    for (bone, ik) in bones
+        .iter()
+        .enumerate()
+        .filter_map(|(index, bone)| bone.ik_info.as_ref().map(|ik| (index, ik)))
+    {
+        if ik.ik_iter_count <= 0 {
+            issues.push(IkIssue::NoIteration {
+                bone,
+                iterations: ik.ik_iter_count,
+            });
+        }
+        if ik.ik_limit_angle.partial_cmp(&0.0) != Some(std::cmp::Ordering::Greater) {
+            issues.push(IkIssue::NonPositiveLimitAngle {
+                bone,
+                limit_angle: ik.ik_limit_angle,
+            });
+        }
+        let mut previous = bone_index(bones, ik.ik_target_bone_index);
+        if previous.is_none() {
+            issues.push(IkIssue::InvalidTarget {
+                bone,
+                target: ik.ik_target_bone_index,
+            });
+        }
+        for (link, ik_link) in ik.ik_links.iter().enumerate() {
+            if let Some((min, max)) = ik_link.angle_limit {
+                if min.iter().zip(max.iter()).any(|(min, max)| min > max) {
+                    issues.push(IkIssue::InvertedAngleLimit { bone, link });
+                }
+            }
+            let link_bone = ik_link.ik_bone_index;
+            let index = match bone_index(bones, link_bone) {
+                Some(index) => index,
+                None => {
+                    issues.push(IkIssue::InvalidLink {
+                        bone,
+                        link,
+                        link_bone,
+                    });
+                    // chain can't be followed anymore
+                    previous = None;
+                    continue;
+                }
+            };
+            if let Some(previous) = previous {
+                if !is_ancestor(bones, index, previous) {
+                    issues.push(IkIssue::LinkNotAncestor {
+                        bone,
+                        link,
+                        link_bone,
+                    });
+                }
+            }
+            previous = Some(index);
+        }
+    }
+    issues
+}
+
+/// fix trivial IK problems and return issues left.
+///
+/// * iteration count is raised to 1.
+/// * limit angle of 0 or less is replaced by π, no limit per iteration.
+/// * min and max of inverted angle limits are swapped.
+/// * links to bones which do not exist are removed.
+///
+/// invalid targets and broken chains need human decision so they are left as is.
+pub fn repair_ik(bones: &mut [Bone]) -> Vec<IkIssue> {
+    let len = bones.len();
+    for ik in bones.iter_mut().filter_map(|bone| bone.ik_info.as_mut()) {
+        ik.ik_iter_count = ik.ik_iter_count.max(1);
+        if ik.ik_limit_angle.partial_cmp(&0.0) != Some(std::cmp::Ordering::Greater) {
+            ik.ik_limit_angle = PI;
+        }
+        ik.ik_links.retain(|link| {
+            usize::try_from(link.ik_bone_index)
+                .map(|index| index < len)
+                .unwrap_or(false)
+        });
+        for (min, max) in ik
+            .ik_links
+            .iter_mut()
+            .filter_map(|link| link.angle_limit.as_mut())
+        {
+            for (min, max) in min.iter_mut().zip(max.iter_mut()) {
+                if *min > *max {
+                    std::mem::swap(min, max);
+                }
+            }
+        }
+    }
+    check_ik(bones)
+}
+
+/// elements sharing a name.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DuplicateName {
+    pub name: String,
+    /// indices of elements in ascending order.
+    pub indices: Vec<usize>,
+}
+
+/// result of [`find_duplicate_names`]. each list is ordered by first appearance.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DuplicateReport {
+    pub bones: Vec<DuplicateName>,
+    pub morphs: Vec<DuplicateName>,
+    pub materials: Vec<DuplicateName>,
+    pub rigid_bodies: Vec<DuplicateName>,
+    pub frames: Vec<DuplicateName>,
+}
+
+impl DuplicateReport {
+    pub fn is_empty(&self) -> bool {
+        self.bones.is_empty()
+            && self.morphs.is_empty()
+            && self.materials.is_empty()
+            && self.rigid_bodies.is_empty()
+            && self.frames.is_empty()
+    }
+}
+
+fn duplicates<'a>(names: impl Iterator<Item = &'a str>) -> Vec<DuplicateName> {
+    let mut found: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, name) in names.enumerate() {
+        found.entry(name).or_default().push(index);
+    }
+    let mut duplicates = found
+        .into_iter()
+        .filter(|(_, indices)| indices.len() > 1)
+        .map(|(name, indices)| DuplicateName {
+            name: name.to_owned(),
+            indices,
+        })
+        .collect::<Vec<_>>();
+    duplicates.sort_by_key(|duplicate| duplicate.indices[0]);
+    duplicates
+}
+
+/// find elements sharing japanese name.
+///
+/// motions refer bones and morphs by name so only the first one of them is moved by `MMD`.
+/// see [`convert::dedupe_names`](crate::convert::dedupe_names) to rename them.
+pub fn find_duplicate_names(model: &Model) -> DuplicateReport {
+    DuplicateReport {
+        bones: duplicates(model.bones.iter().map(|bone| bone.name.as_str())),
+        morphs: duplicates(model.morphs.iter().map(|morph| morph.name.as_str())),
+        materials: duplicates(
+            model
+                .materials
+                .iter()
+                .map(|material| material.name.as_str()),
+        ),
+        rigid_bodies: duplicates(model.rigid_bodies.iter().map(|rigid| rigid.name.as_str())),
+        frames: duplicates(model.frames.iter().map(|frame| frame.name.as_str())),
+    }
+}
+
+/// result of [`find_unframed`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct UnframedReport {
+    /// indices of bones in ascending order.
+    pub bones: Vec<usize>,
+    /// indices of morphs in ascending order.
+    pub morphs: Vec<usize>,
+}
+
+impl UnframedReport {
+    pub fn is_empty(&self) -> bool {
+        self.bones.is_empty() && self.morphs.is_empty()
+    }
+}
+
+/// find bones and morphs in no display frame.
+///
+/// `PMXEditor` and `MMD` show only elements in frames.
+/// see [`frames::add_bone_to`](crate::frames::add_bone_to) to register them.
+pub fn find_unframed(model: &Model) -> UnframedReport {
+    let mut bones = vec![false; model.bones.len()];
+    let mut morphs = vec![false; model.morphs.len()];
+    for inner in model.frames.iter().flat_map(|frame| &frame.inners) {
+        let (framed, index) = match *inner {
+            FrameInner::Bone(index) => (&mut bones, index),
+            FrameInner::Morph(index) => (&mut morphs, index),
+        };
+        if let Some(framed) = usize::try_from(index).ok().and_then(|i| framed.get_mut(i)) {
+            *framed = true;
+        }
+    }
+    let unframed = |framed: Vec<bool>| {
+        framed
+            .into_iter()
+            .enumerate()
+            .filter(|(_, framed)| !framed)
+            .map(|(index, _)| index)
+            .collect()
+    };
+    UnframedReport {
+        bones: unframed(bones),
+        morphs: unframed(morphs),
+    }
+}
+
+/// side of a bone in pairs named with 左 and 右.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// bone of the semi-standard set (準標準ボーン) which motions for `MMD` commonly assume
+/// in addition to the standard ones.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum SemiStandardBone {
+    Groove,
+    Waist,
+    UpperBody2,
+    OperationCenter,
+    ShoulderP(Side),
+    ArmTwist(Side),
+    HandTwist(Side),
+    ThumbZero(Side),
+    WaistCancel(Side),
+    LegIkParent(Side),
+    ToeEx(Side),
+}
+
+/// the semi-standard bones and their names in the order `PMXEditor` adds them.
+pub const SEMISTANDARD_BONES: &[(SemiStandardBone, &str)] = {
+    use SemiStandardBone::*;
+    use Side::*;
+    &[
+        (OperationCenter, "操作中心"),
+        (Groove, "グルーブ"),
+        (Waist, "腰"),
+        (UpperBody2, "上半身2"),
+        (WaistCancel(Left), "腰キャンセル左"),
+        (WaistCancel(Right), "腰キャンセル右"),
+        (ShoulderP(Left), "左肩P"),
+        (ShoulderP(Right), "右肩P"),
+        (ArmTwist(Left), "左腕捩"),
+        (ArmTwist(Right), "右腕捩"),
+        (HandTwist(Left), "左手捩"),
+        (HandTwist(Right), "右手捩"),
+        (ThumbZero(Left), "左親指０"),
+        (ThumbZero(Right), "右親指０"),
+        (LegIkParent(Left), "左足ＩＫ親"),
+        (LegIkParent(Right), "右足ＩＫ親"),
+        (ToeEx(Left), "左足先EX"),
+        (ToeEx(Right), "右足先EX"),
+    ]
+};
+
+impl SemiStandardBone {
+    /// japanese name motions refer the bone by.
+    pub fn name(self) -> &'static str {
+        SEMISTANDARD_BONES
+            .iter()
+            .find(|(bone, _)| *bone == self)
+            .map(|(_, name)| *name)
+            .expect("every bone is listed")
+    }
+}
+
+/// result of [`check_semistandard_bones`]. both lists follow [`SEMISTANDARD_BONES`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct SemiStandardReport {
+    /// bones found and index of the first bone of the name.
+    pub present: Vec<(SemiStandardBone, usize)>,
+    pub missing: Vec<SemiStandardBone>,
+}
+
+impl SemiStandardReport {
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+/// find semi-standard bones by name.
+///
+/// motions keyed on missing bones play incorrectly. see
+/// [`convert::add_missing_semistandard`](crate::convert::add_missing_semistandard)
+/// to add some of them.
+pub fn check_semistandard_bones(bones: &[Bone]) -> SemiStandardReport {
+    let mut report = SemiStandardReport::default();
+    for &(semistandard, name) in SEMISTANDARD_BONES {
+        match bones.iter().position(|bone| bone.name == name) {
+            Some(index) => report.present.push((semistandard, index)),
+            None => report.missing.push(semistandard),
+        }
+    }
+    report
+}
+
+/// morphs in a panel of `MMD` facial panel above which choosing one gets tedious.
+pub const PANEL_SOFT_LIMIT: usize = 64;
+
+/// problem of morph panels found by [`check_morph_panels`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PanelIssue {
+    /// `morph` in the 表情 frame has `ControlPanel::System` so `MMD` shows it in no panel.
+    SystemMorphInExpressionFrame { morph: usize },
+    /// `frame` other than 表情 refers `morph` which has `ControlPanel::System`,
+    /// or any frame refers `morph` which doesn't exist.
+    UnpanelledMorphInFrame { frame: usize, morph: i32 },
+    /// `panel` has `count` morphs, more than [`PANEL_SOFT_LIMIT`].
+    CrowdedPanel { panel: ControlPanel, count: usize },
+}
+
+impl Display for PanelIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PanelIssue::SystemMorphInExpressionFrame { morph } => {
+                write!(f, "morph {}: in 表情 frame without panel", morph)
+            }
+            PanelIssue::UnpanelledMorphInFrame { frame, morph } => {
+                write!(f, "frame {}: morph {} has no panel", frame, morph)
+            }
+            PanelIssue::CrowdedPanel { panel, count } => {
+                write!(f, "{:?} panel has {} morphs", panel, count)
+            }
+        }
+    }
+}
+
+/// check morphs are placed where `MMD` facial panel can show them.
+///
+/// see [`convert::assign_panels`](crate::convert::assign_panels) to fix panels.
+pub fn check_morph_panels(morphs: &[Morph], frames: &[Frame]) -> Vec<PanelIssue> {
+    let mut issues = vec![];
+    for (frame_index, frame) in frames.iter().enumerate() {
+        let expression = is_special_frame(frame, EXPRESSION_FRAME_NAME);
+        for inner in &frame.inners {
+            let index = match *inner {
+                FrameInner::Morph(index) => index,
+                FrameInner::Bone(_) => continue,
+            };
+            let panel = usize::try_from(index)
+                .ok()
+                .and_then(|i| morphs.get(i))
+                .map(|morph| morph.control_panel);
+            match panel {
+                Some(ControlPanel::System) if expression => {
+                    issues.push(PanelIssue::SystemMorphInExpressionFrame {
+                        morph: index as usize,
+                    })
+                }
+                Some(ControlPanel::System) | None => {
+                    issues.push(PanelIssue::UnpanelledMorphInFrame {
+                        frame: frame_index,
+                        morph: index,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    for panel in [
+        ControlPanel::BottomLeft,
+        ControlPanel::TopLeft,
+        ControlPanel::TopRight,
+        ControlPanel::BottomRight,
+    ] {
+        let count = morphs
+            .iter()
+            .filter(|morph| morph.control_panel == panel)
+            .count();
+        if count > PANEL_SOFT_LIMIT {
+            issues.push(PanelIssue::CrowdedPanel { panel, count });
+        }
+    }
+    issues
+}
+
+/// problem of display frame layout found by [`check_frames`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FrameIssue {
+    /// PMX requires exactly two special frames.
+    SpecialFrameCount { count: usize },
+    /// frame 0 or 1 is not special.
+    NotSpecial { frame: usize },
+    /// special frame after the first two.
+    MisplacedSpecial { frame: usize },
+    /// frame 0 doesn't hold exactly one bone and no morph.
+    RootFrameEntries { bones: usize, morphs: usize },
+    /// frame 1 holds a bone at `entry`.
+    BoneInExpressionFrame { entry: usize },
+    /// entry at `entry` refers the same element as the entry at `original`.
+    DuplicateEntry {
+        frame: usize,
+        entry: usize,
+        original: usize,
+    },
+}
+
+impl Display for FrameIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameIssue::SpecialFrameCount { count } => {
+                write!(f, "{} special frames instead of 2", count)
+            }
+            FrameIssue::NotSpecial { frame } => write!(f, "frame {}: is not special", frame),
+            FrameIssue::MisplacedSpecial { frame } => {
+                write!(f, "frame {}: special frame after the first two", frame)
+            }
+            FrameIssue::RootFrameEntries { bones, morphs } => write!(
+                f,
+                "frame 0: holds {} bones and {} morphs instead of one bone",
+                bones, morphs
+            ),
+            FrameIssue::BoneInExpressionFrame { entry } => {
+                write!(f, "frame 1: entry {} is a bone", entry)
+            }
+            FrameIssue::DuplicateEntry {
+                frame,
+                entry,
+                original,
+            } => write!(
+                f,
+                "frame {}: entry {} duplicates entry {}",
+                frame, entry, original
+            ),
+        }
+    }
+}
+
+/// check special frames are the first two and entries are where `MMD` expects them.
+///
+/// some loaders crash on models violating these. see
+/// [`convert::fix_frames`](crate::convert::fix_frames) to repair them.
+pub fn check_frames(frames: &[Frame]) -> Vec<FrameIssue> {
+    let mut issues = vec![];
+    let count = frames.iter().filter(|frame| frame.is_special).count();
+    if count != 2 {
+        issues.push(FrameIssue::SpecialFrameCount { count });
+    }
+    for (frame, data) in frames.iter().enumerate() {
+        match (frame < 2, data.is_special) {
+            (true, false) => issues.push(FrameIssue::NotSpecial { frame }),
+            (false, true) => issues.push(FrameIssue::MisplacedSpecial { frame }),
+            _ => {}
+        }
+    }
+    if let Some(root) = frames.first() {
+        let bones = root
+            .inners
+            .iter()
+            .filter(|inner| matches!(inner, FrameInner::Bone(_)))
+            .count();
+        let morphs = root.inners.len() - bones;
+        if bones != 1 || morphs != 0 {
+            issues.push(FrameIssue::RootFrameEntries { bones, morphs });
+        }
+    }
+    if let Some(expression) = frames.get(1) {
+        issues.extend(
+            expression
+                .inners
+                .iter()
+                .enumerate()
+                .filter(|(_, inner)| matches!(inner, FrameInner::Bone(_)))
+                .map(|(entry, _)| FrameIssue::BoneInExpressionFrame { entry }),
+        );
+    }
+    for (frame, data) in frames.iter().enumerate() {
+        let mut found = HashMap::new();
+        for (entry, inner) in data.inners.iter().enumerate() {
+            let original = *found.entry(*inner).or_insert(entry);
+            if original != entry {
+                issues.push(FrameIssue::DuplicateEntry {
+                    frame,
+                    entry,
+                    original,
+                });
+            }
+        }
+    }
+    issues
+}
+
+/// how serious an issue is. issues configured as [`Severity::Ignore`] are not reported.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Severity {
+    Ignore,
+    Warning,
+    Error,
+}
+
+/// severity of each check of [`check_geometry`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeometryOptions {
+    pub non_finite: Severity,
+    pub zero_normal: Severity,
+    pub degenerate_face: Severity,
+    pub duplicate_face: Severity,
+    pub bad_weights: Severity,
+    pub unused_rigid_size: Severity,
+    pub undeclared_uv_channel: Severity,
+    pub invisible_edge: Severity,
+    pub material_face_count: Severity,
+    /// allowed error of the sum of `BDEF4` and `QDEF` weights.
+    pub weight_tolerance: f32,
+}
+
+impl Default for GeometryOptions {
+    fn default() -> Self {
+        Self {
+            non_finite: Severity::Error,
+            zero_normal: Severity::Warning,
+            degenerate_face: Severity::Warning,
+            duplicate_face: Severity::Warning,
+            bad_weights: Severity::Warning,
+            unused_rigid_size: Severity::Warning,
+            undeclared_uv_channel: Severity::Warning,
+            invisible_edge: Severity::Warning,
+            material_face_count: Severity::Error,
+            weight_tolerance: 1.0e-3,
+        }
+    }
+}
+
+impl GeometryOptions {
+    pub fn severity(&self, issue: &GeometryIssue) -> Severity {
+        match issue {
+            GeometryIssue::NonFinite { .. } => self.non_finite,
+            GeometryIssue::ZeroNormal { .. } => self.zero_normal,
+            GeometryIssue::DegenerateFace { .. } => self.degenerate_face,
+            GeometryIssue::DuplicateFace { .. } => self.duplicate_face,
+            GeometryIssue::BadWeights { .. } => self.bad_weights,
+            GeometryIssue::UnusedRigidSize { .. } => self.unused_rigid_size,
+            GeometryIssue::UndeclaredUvChannel { .. } => self.undeclared_uv_channel,
+            GeometryIssue::InvisibleEdge { .. } => self.invisible_edge,
+            GeometryIssue::MaterialFaceCount { .. } => self.material_face_count,
+        }
+    }
+}
+
+/// problem of geometry found by [`check_geometry`].
+///
+/// indices are the same as shown in `PMXEditor`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum GeometryIssue {
+    /// NaN or infinity in the element. morph offsets are reported as the morph.
+    NonFinite {
+        section: SectionKind,
+        index: usize,
+    },
+    ZeroNormal {
+        vertex: usize,
+    },
+    /// two or three vertex indices are the same.
+    DegenerateFace {
+        face: usize,
+    },
+    /// same vertices in same winding as `original` in the same material.
+    DuplicateFace {
+        face: usize,
+        original: usize,
+    },
+    /// `BDEF2` or `SDEF` weight is out of 0..=1, or `BDEF4` or `QDEF` weights don't sum to 1.
+    BadWeights {
+        vertex: usize,
+    },
+    /// components of size not used by the form are not zero. see [`Rigid::shape`].
+    UnusedRigidSize {
+        rigid: usize,
+    },
+    /// uv morph targets additional uv `channel` (0 based) but the model declares fewer channels.
+    /// see [`MorphKinds::uv_channel`].
+    UndeclaredUvChannel {
+        morph: usize,
+        channel: usize,
+    },
+    /// material has `HAS_EDGE` but alpha of edge color is 0.
+    /// the edge costs a draw call without being seen.
+    InvisibleEdge {
+        material: usize,
+    },
+    /// sum of `num_face_vertices` of materials differs from 3 times of face count.
+    /// see [`Model::assign_remaining_faces`].
+    MaterialFaceCount {
+        material_vertices: i64,
+        face_vertices: i64,
+    },
+}
+
+impl Display for GeometryIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeometryIssue::NonFinite { section, index } => {
+                write!(f, "{:?} {}: contains NaN or infinity", section, index)
+            }
+            GeometryIssue::ZeroNormal { vertex } => {
+                write!(f, "vertex {}: normal has zero length", vertex)
+            }
+            GeometryIssue::DegenerateFace { face } => {
+                write!(f, "face {}: uses the same vertex twice", face)
+            }
+            GeometryIssue::DuplicateFace { face, original } => {
+                write!(f, "face {}: duplicate of face {}", face, original)
+            }
+            GeometryIssue::BadWeights { vertex } => {
+                write!(f, "vertex {}: weights are not normalized", vertex)
+            }
+            GeometryIssue::UnusedRigidSize { rigid } => {
+                write!(
+                    f,
+                    "rigid body {}: size has values unused by its form",
+                    rigid
+                )
+            }
+            GeometryIssue::UndeclaredUvChannel { morph, channel } => write!(
+                f,
+                "morph {}: targets additional uv {} which is not declared",
+                morph,
+                channel + 1
+            ),
+            GeometryIssue::InvisibleEdge { material } => {
+                write!(f, "material {}: edge is enabled but transparent", material)
+            }
+            GeometryIssue::MaterialFaceCount {
+                material_vertices,
+                face_vertices,
+            } => write!(
+                f,
+                "materials draw {} face vertices but faces have {}",
+                material_vertices, face_vertices
+            ),
+        }
+    }
+}
+
+/// `true` if components of size after the ones used by the form are not zero.
+fn rigid_size_unused(rigid: &Rigid) -> bool {
+    let used = match rigid.form {
+        RigidForm::Sphere => 1,
+        RigidForm::Capsule => 2,
+        RigidForm::Box => 3,
+    };
+    rigid.size[used..].iter().any(|&value| value != 0.0)
+}
+
+fn all_finite<'a>(values: impl IntoIterator<Item = &'a f32>) -> bool {
+    values.into_iter().all(|value| value.is_finite())
+}
+
+fn vertex_is_finite(vertex: &Vertex) -> bool {
+    let sdef = match &vertex.weight_type {
+        VertexWeight::SDEF {
+            sdef_c,
+            sdef_r0,
+            sdef_r1,
+            ..
+        } => all_finite(sdef_c.iter().chain(sdef_r0).chain(sdef_r1)),
+        _ => true,
+    };
+    sdef && all_finite(vertex.position.iter().chain(&vertex.norm).chain(&vertex.uv))
+        && all_finite(vertex.add_uv.iter().flatten())
+        && vertex.edge_mag.is_finite()
+        && vertex
+            .weight_type
+            .bone_weights()
+            .iter()
+            .all(|(_, weight)| weight.is_finite())
+}
+
+fn bone_is_finite(bone: &Bone) -> bool {
+    let offset = match &bone.connection_display_mode {
+        ConnectionDisplayMode::Offset(offset) => all_finite(offset),
+        ConnectionDisplayMode::OtherBone(_) => true,
+    };
+    let inherit = match bone.inherits.rotate_and_translate {
+        RotateAndTranslateInherits::Both(_, weight)
+        | RotateAndTranslateInherits::Rotate(_, weight)
+        | RotateAndTranslateInherits::Translate(_, weight) => weight.is_finite(),
+        RotateAndTranslateInherits::None => true,
+    };
+    let ik = bone.ik_info.as_ref().is_none_or(|ik| {
+        ik.ik_limit_angle.is_finite()
+            && ik
+                .ik_links
+                .iter()
+                .filter_map(|link| link.angle_limit.as_ref())
+                .all(|(min, max)| all_finite(min.iter().chain(max)))
+    });
+    offset
+        && inherit
+        && ik
+        && all_finite(&bone.position)
+        && bone.fixed_axis.as_ref().is_none_or(all_finite)
+        && bone
+            .local_axis
+            .as_ref()
+            .is_none_or(|(x, z)| all_finite(x.iter().chain(z)))
+}
+
+fn morph_is_finite(morph: &MorphKinds) -> bool {
+    match morph {
+        MorphKinds::Vertex(offsets) => offsets.iter().all(|offset| all_finite(&offset.offset)),
+        MorphKinds::UV(offsets)
+        | MorphKinds::UV1(offsets)
+        | MorphKinds::UV2(offsets)
+        | MorphKinds::UV3(offsets)
+        | MorphKinds::UV4(offsets) => offsets.iter().all(|offset| all_finite(&offset.offset)),
+        MorphKinds::Bone(offsets) => offsets
+            .iter()
+            .all(|offset| all_finite(offset.translates.iter().chain(&offset.rotates))),
+        MorphKinds::Material(offsets) => offsets.iter().all(|offset| {
+            all_finite(
+                offset
+                    .diffuse
+                    .iter()
+                    .chain(&offset.specular)
+                    .chain(Some(&offset.specular_factor))
+                    .chain(&offset.ambient)
+                    .chain(&offset.edge_color)
+                    .chain(Some(&offset.edge_size))
+                    .chain(&offset.texture_factor)
+                    .chain(&offset.sphere_texture_factor)
+                    .chain(&offset.toon_texture_factor),
+            )
+        }),
+        MorphKinds::Group(offsets) => offsets.iter().all(|offset| offset.morph_factor.is_finite()),
+        MorphKinds::Flip(offsets) => offsets.iter().all(|offset| offset.morph_factor.is_finite()),
+        MorphKinds::Impulse(offsets) => offsets
+            .iter()
+            .all(|offset| all_finite(offset.velocity.iter().chain(&offset.torque))),
+    }
+}
+
+fn joint_is_finite(joint: &JointType) -> bool {
+    match joint {
+        JointType::Spring6DOF {
+            position,
+            rotation,
+            move_limit_down,
+            move_limit_up,
+            rotation_limit_down,
+            rotation_limit_up,
+            spring_const_move,
+            spring_const_rotation,
+            ..
+        } => all_finite(
+            position
+                .iter()
+                .chain(rotation)
+                .chain(move_limit_down)
+                .chain(move_limit_up)
+                .chain(rotation_limit_down)
+                .chain(rotation_limit_up)
+                .chain(spring_const_move)
+                .chain(spring_const_rotation),
+        ),
+        JointType::SixDof {
+            position,
+            rotation,
+            move_limit_down,
+            move_limit_up,
+            rotation_limit_down,
+            rotation_limit_up,
+            ..
+        } => all_finite(
+            position
+                .iter()
+                .chain(rotation)
+                .chain(move_limit_down)
+                .chain(move_limit_up)
+                .chain(rotation_limit_down)
+                .chain(rotation_limit_up),
+        ),
+        JointType::P2P {
+            position, rotation, ..
+        } => all_finite(position.iter().chain(rotation)),
+        JointType::ConeTwist {
+            position,
+            rotation,
+            swing_span1,
+            swing_span2,
+            twist_span,
+            softness,
+            bias_factor,
+            relaxation_factor,
+            damping,
+            fix_thresh,
+            max_motor_impulse,
+            motor_target_in_constraint_space,
+            ..
+        } => all_finite(
+            [
+                swing_span1,
+                swing_span2,
+                twist_span,
+                softness,
+                bias_factor,
+                relaxation_factor,
+                damping,
+                fix_thresh,
+                max_motor_impulse,
+            ]
+            .iter()
+            .copied()
+            .chain(position)
+            .chain(rotation)
+            .chain(motor_target_in_constraint_space),
+        ),
+        JointType::Slider {
+            position,
+            rotation,
+            lower_linear_limit,
+            upper_linear_limit,
+            lower_angle_limit,
+            upper_angle_limit,
+            target_linear_motor_velocity,
+            max_linear_motor_force,
+            target_angler_motor_velocity,
+            max_angler_motor_force,
+            ..
+        } => all_finite(
+            [
+                lower_linear_limit,
+                upper_linear_limit,
+                lower_angle_limit,
+                upper_angle_limit,
+                target_linear_motor_velocity,
+                max_linear_motor_force,
+                target_angler_motor_velocity,
+                max_angler_motor_force,
+            ]
+            .iter()
+            .copied()
+            .chain(position)
+            .chain(rotation),
+        ),
+        JointType::Hinge {
+            position,
+            rotation,
+            low,
+            high,
+            softness,
+            bias_factor,
+            relaxation_factor,
+            target_velocity,
+            max_motor_impulse,
+            ..
+        } => all_finite(
+            [
+                low,
+                high,
+                softness,
+                bias_factor,
+                relaxation_factor,
+                target_velocity,
+                max_motor_impulse,
+            ]
+            .iter()
+            .copied()
+            .chain(position)
+            .chain(rotation),
+        ),
+    }
+}
+
+pub(crate) fn weights_are_normalized(weight: &VertexWeight, tolerance: f32) -> bool {
+    match *weight {
+        VertexWeight::BDEF1(_) => true,
+        VertexWeight::BDEF2 { bone_weight_1, .. } | VertexWeight::SDEF { bone_weight_1, .. } => {
+            (0.0..=1.0).contains(&bone_weight_1)
+        }
+        VertexWeight::BDEF4 { .. } | VertexWeight::QDEF { .. } => {
+            let weights = weight.bone_weights();
+            weights.iter().all(|(_, weight)| *weight >= 0.0)
+                && (weights.iter().map(|(_, weight)| weight).sum::<f32>() - 1.0).abs() <= tolerance
+        }
+    }
+}
+
+/// faces of each material. faces not assigned to any material are the last group.
+fn material_face_groups(model: &Model) -> Vec<std::ops::Range<usize>> {
+    let mut groups = material_faces(model);
+    let start = groups.last().map_or(0, |faces| faces.end);
+    groups.push(start..model.faces.len());
+    groups
+}
+
+/// same triangle in same winding gives same key.
+fn face_key(face: &Face) -> [u32; 3] {
+    let [a, b, c] = face.vertices;
+    let min = a.min(b).min(c);
+    if min == a {
+        [a, b, c]
+    } else if min == b {
+        [b, c, a]
+    } else {
+        [c, a, b]
+    }
+}
+
+/// duplicate face index and its original.
+fn duplicate_faces(model: &Model) -> Vec<(usize, usize)> {
+    let mut duplicates = vec![];
+    for group in material_face_groups(model) {
+        let mut found = HashMap::new();
+        for face in group {
+            let original = *found.entry(face_key(&model.faces[face])).or_insert(face);
+            if original != face {
+                duplicates.push((face, original));
+            }
+        }
+    }
+    duplicates
+}
+
+/// check numbers and meshes which break renderers without error.
+///
+/// issues are returned in order of sections with severity from `options`.
+pub fn check_geometry(model: &Model, options: &GeometryOptions) -> Vec<(Severity, GeometryIssue)> {
+    let mut issues = vec![];
+    let non_finite = |section, finite: Vec<bool>| {
+        finite
+            .into_iter()
+            .enumerate()
+            .filter(|(_, finite)| !finite)
+            .map(move |(index, _)| GeometryIssue::NonFinite { section, index })
+    };
+    for (vertex, data) in model.vertices.iter().enumerate() {
+        if !vertex_is_finite(data) {
+            issues.push(GeometryIssue::NonFinite {
+                section: SectionKind::Vertices,
+                index: vertex,
+            });
+            continue;
+        }
+        let [x, y, z] = data.norm;
+        if x * x + y * y + z * z < 1.0e-12 {
+            issues.push(GeometryIssue::ZeroNormal { vertex });
+        }
+        if !weights_are_normalized(&data.weight_type, options.weight_tolerance) {
+            issues.push(GeometryIssue::BadWeights { vertex });
+        }
+    }
+    for (face, data) in model.faces.iter().enumerate() {
+        let [a, b, c] = data.vertices;
+        if a == b || b == c || c == a {
+            issues.push(GeometryIssue::DegenerateFace { face });
+        }
+    }
+    let material_vertices = model
+        .materials
+        .iter()
+        .map(|material| i64::from(material.num_face_vertices))
+        .sum::<i64>();
+    let face_vertices = model.faces.len() as i64 * 3;
+    if material_vertices != face_vertices {
+        issues.push(GeometryIssue::MaterialFaceCount {
+            material_vertices,
+            face_vertices,
+        });
+    }
+    issues.extend(
+        duplicate_faces(model)
+            .into_iter()
+            .map(|(face, original)| GeometryIssue::DuplicateFace { face, original }),
+    );
+    issues.extend(non_finite(
+        SectionKind::Materials,
+        model
+            .materials
+            .iter()
+            .map(|material| {
+                all_finite(
+                    material
+                        .diffuse
+                        .iter()
+                        .chain(&material.specular)
+                        .chain(Some(&material.specular_factor))
+                        .chain(&material.ambient)
+                        .chain(&material.edge_color)
+                        .chain(Some(&material.edge_size)),
+                )
+            })
+            .collect(),
+    ));
+    issues.extend(
+        model
+            .materials
+            .iter()
+            .enumerate()
+            .filter(|(_, material)| material.has_edge() && material.edge_color[3] == 0.0)
+            .map(|(material, _)| GeometryIssue::InvisibleEdge { material }),
+    );
+    issues.extend(non_finite(
+        SectionKind::Bones,
+        model.bones.iter().map(bone_is_finite).collect(),
+    ));
+    issues.extend(non_finite(
+        SectionKind::Morphs,
+        model
+            .morphs
+            .iter()
+            .map(|morph| morph_is_finite(&morph.morph_data))
+            .collect(),
+    ));
+    issues.extend(
+        model
+            .morphs
+            .iter()
+            .enumerate()
+            .filter_map(|(morph, data)| Some((morph, data.morph_data.uv_channel()?)))
+            .filter(|(_, channel)| *channel >= usize::from(model.additional_uv))
+            .map(|(morph, channel)| GeometryIssue::UndeclaredUvChannel { morph, channel }),
+    );
+    issues.extend(non_finite(
+        SectionKind::RigidBodies,
+        model
+            .rigid_bodies
+            .iter()
+            .map(|rigid| {
+                all_finite(
+                    rigid
+                        .size
+                        .iter()
+                        .chain(&rigid.position)
+                        .chain(&rigid.rotation)
+                        .chain(&[
+                            rigid.mass,
+                            rigid.move_resist,
+                            rigid.rotation_resist,
+                            rigid.repulsion,
+                            rigid.friction,
+                        ]),
+                )
+            })
+            .collect(),
+    ));
+    issues.extend(
+        model
+            .rigid_bodies
+            .iter()
+            .enumerate()
+            .filter(|(_, rigid)| rigid_size_unused(rigid))
+            .map(|(rigid, _)| GeometryIssue::UnusedRigidSize { rigid }),
+    );
+    issues.extend(non_finite(
+        SectionKind::Joints,
+        model
+            .joints
+            .iter()
+            .map(|joint| joint_is_finite(&joint.joint_type))
+            .collect(),
+    ));
+    issues
+        .into_iter()
+        .map(|issue| (options.severity(&issue), issue))
+        .filter(|(severity, _)| *severity != Severity::Ignore)
+        .collect()
+}
+
+/// changes made by [`fix_geometry`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct GeometryFix {
+    /// vertices whose weights are normalized.
+    pub renormalized_vertices: Vec<usize>,
+    /// indices of removed faces before removal.
+    pub removed_faces: Vec<usize>,
+}
+
+fn normalize_weights(weight: &mut VertexWeight) {
+    match weight {
+        VertexWeight::BDEF1(_) => {}
+        VertexWeight::BDEF2 { bone_weight_1, .. } | VertexWeight::SDEF { bone_weight_1, .. } => {
+            *bone_weight_1 = bone_weight_1.clamp(0.0, 1.0);
+        }
+        VertexWeight::BDEF4 {
+            bone_weight_1,
+            bone_weight_2,
+            bone_weight_3,
+            bone_weight_4,
+            ..
+        }
+        | VertexWeight::QDEF {
+            bone_weight_1,
+            bone_weight_2,
+            bone_weight_3,
+            bone_weight_4,
+            ..
+        } => {
+            let mut weights = [bone_weight_1, bone_weight_2, bone_weight_3, bone_weight_4];
+            for weight in weights.iter_mut() {
+                **weight = weight.max(0.0);
+            }
+            let sum = weights.iter().map(|weight| **weight).sum::<f32>();
+            if sum > 0.0 {
+                for weight in weights.iter_mut() {
+                    **weight /= sum;
+                }
+            } else {
+                // nothing to keep, follow the first bone
+                for (i, weight) in weights.iter_mut().enumerate() {
+                    **weight = if i == 0 { 1.0 } else { 0.0 };
+                }
+            }
+        }
+    }
+}
+
+/// normalize weights reported as [`GeometryIssue::BadWeights`] and remove faces reported as
+/// [`GeometryIssue::DuplicateFace`].
+///
+/// face counts of materials are updated. other issues need human decision so they are left.
+pub fn fix_geometry(model: &mut Model, options: &GeometryOptions) -> GeometryFix {
+    let mut fix = GeometryFix::default();
+    for (index, vertex) in model.vertices.iter_mut().enumerate() {
+        if vertex_is_finite(vertex)
+            && !weights_are_normalized(&vertex.weight_type, options.weight_tolerance)
+        {
+            normalize_weights(&mut vertex.weight_type);
+            fix.renormalized_vertices.push(index);
+        }
+    }
+    let removed = duplicate_faces(model)
+        .into_iter()
+        .map(|(face, _)| face)
+        .collect::<HashSet<_>>();
+    let groups = material_face_groups(model);
+    for (material, group) in model.materials.iter_mut().zip(&groups) {
+        let count = group.clone().filter(|face| removed.contains(face)).count();
+        material.num_face_vertices -= i32::try_from(count * 3).unwrap_or(i32::MAX);
+    }
+    let mut index = 0;
+    model.faces.retain(|_| {
+        index += 1;
+        !removed.contains(&(index - 1))
+    });
+    fix.removed_faces = removed.into_iter().collect();
+    fix.removed_faces.sort_unstable();
+    fix
+}
+
+/// severity of each check of [`check_soft_bodies`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SoftBodyOptions {
+    pub invalid_reference: Severity,
+    pub vertex_outside_material: Severity,
+    pub bad_parameters: Severity,
+    pub rope_without_lines: Severity,
+}
+
+impl Default for SoftBodyOptions {
+    fn default() -> Self {
+        Self {
+            invalid_reference: Severity::Error,
+            vertex_outside_material: Severity::Warning,
+            bad_parameters: Severity::Error,
+            rope_without_lines: Severity::Warning,
+        }
+    }
+}
+
+impl SoftBodyOptions {
+    pub fn severity(&self, issue: &SoftBodyIssue) -> Severity {
+        match issue {
+            SoftBodyIssue::InvalidMaterial { .. }
+            | SoftBodyIssue::InvalidAnchorRigid { .. }
+            | SoftBodyIssue::InvalidVertex { .. } => self.invalid_reference,
+            SoftBodyIssue::VertexOutsideMaterial { .. } => self.vertex_outside_material,
+            SoftBodyIssue::NegativeClusters { .. } | SoftBodyIssue::BadIterations { .. } => {
+                self.bad_parameters
+            }
+            SoftBodyIssue::RopeWithoutLines { .. } => self.rope_without_lines,
+        }
+    }
+}
+
+/// problem of a soft body found by [`check_soft_bodies`].
+///
+/// `vertex` is the vertex index in the soft body. `anchor` is index in its anchors
+/// and `None` for pin vertices.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SoftBodyIssue {
+    /// material index is out of range. faces of the material make the soft body.
+    InvalidMaterial {
+        soft_body: usize,
+        material: i32,
+    },
+    InvalidAnchorRigid {
+        soft_body: usize,
+        anchor: usize,
+        rigid: i32,
+    },
+    /// vertex index is out of range.
+    InvalidVertex {
+        soft_body: usize,
+        anchor: Option<usize>,
+        vertex: i32,
+    },
+    /// vertex is not used by faces of the material so it isn't a part of the soft body.
+    VertexOutsideMaterial {
+        soft_body: usize,
+        anchor: Option<usize>,
+        vertex: i32,
+    },
+    NegativeClusters {
+        soft_body: usize,
+        clusters: i32,
+    },
+    /// an iteration count is negative or position iterations `p_it` is 0.
+    BadIterations {
+        soft_body: usize,
+    },
+    /// rope form on a material not drawn as lines. see [`Material::topology`](crate::types::Material::topology).
+    RopeWithoutLines {
+        soft_body: usize,
+        material: usize,
+    },
+}
+
+impl Display for SoftBodyIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let vertex_kind = |anchor: &Option<usize>| match anchor {
+            Some(anchor) => format!("anchor {} vertex", anchor),
+            None => "pin vertex".to_owned(),
+        };
+        match self {
+            SoftBodyIssue::InvalidMaterial {
+                soft_body,
+                material,
+            } => write!(
+                f,
+                "soft body {}: material {} does not exist",
+                soft_body, material
+            ),
+            SoftBodyIssue::InvalidAnchorRigid {
+                soft_body,
+                anchor,
+                rigid,
+            } => write!(
+                f,
+                "soft body {}: rigid body {} of anchor {} does not exist",
+                soft_body, rigid, anchor
+            ),
+            SoftBodyIssue::InvalidVertex {
+                soft_body,
+                anchor,
+                vertex,
+            } => write!(
+                f,
+                "soft body {}: {} {} does not exist",
+                soft_body,
+                vertex_kind(anchor),
+                vertex
+            ),
+            SoftBodyIssue::VertexOutsideMaterial {
+                soft_body,
+                anchor,
+                vertex,
+            } => write!(
+                f,
+                "soft body {}: {} {} is not drawn by its material",
+                soft_body,
+                vertex_kind(anchor),
+                vertex
+            ),
+            SoftBodyIssue::NegativeClusters {
+                soft_body,
+                clusters,
+            } => write!(
+                f,
+                "soft body {}: cluster count {} is negative",
+                soft_body, clusters
+            ),
+            SoftBodyIssue::BadIterations { soft_body } => {
+                write!(f, "soft body {}: iteration counts are invalid", soft_body)
+            }
+            SoftBodyIssue::RopeWithoutLines {
+                soft_body,
+                material,
+            } => write!(
+                f,
+                "soft body {}: rope on material {} not drawn as lines",
+                soft_body, material
+            ),
+        }
+    }
+}
+
+/// check references and parameters of soft bodies of PMX 2.1.
+///
+/// issues are returned in order of soft bodies with severity from `options`.
+pub fn check_soft_bodies(
+    model: &Model,
+    options: &SoftBodyOptions,
+) -> Vec<(Severity, SoftBodyIssue)> {
+    let mut issues = vec![];
+    let vertex_materials = vertex_materials(model);
+    for (soft_body, data) in model.soft_bodies.iter().enumerate() {
+        let material = usize::try_from(data.material_index)
+            .ok()
+            .filter(|&material| material < model.materials.len());
+        if material.is_none() {
+            issues.push(SoftBodyIssue::InvalidMaterial {
+                soft_body,
+                material: data.material_index,
+            });
+        }
+        let mut check_vertex = |anchor: Option<usize>, vertex: i32| {
+            let materials = usize::try_from(vertex)
+                .ok()
+                .and_then(|index| vertex_materials.get(index));
+            match materials {
+                None => issues.push(SoftBodyIssue::InvalidVertex {
+                    soft_body,
+                    anchor,
+                    vertex,
+                }),
+                // vertices can't be checked against a missing material.
+                Some(materials) if material.is_some() => {
+                    if !materials.contains(&data.material_index) {
+                        issues.push(SoftBodyIssue::VertexOutsideMaterial {
+                            soft_body,
+                            anchor,
+                            vertex,
+                        });
+                    }
+                }
+                Some(_) => {}
+            }
+        };
+        for (anchor, anchor_rigid) in data.anchor_rigid.iter().enumerate() {
+            check_vertex(Some(anchor), anchor_rigid.vertex_index);
+        }
+        for &vertex in &data.pin_vertex {
+            check_vertex(None, vertex);
+        }
+        for (anchor, anchor_rigid) in data.anchor_rigid.iter().enumerate() {
+            let rigid = anchor_rigid.rigid_index;
+            if usize::try_from(rigid).map_or(true, |rigid| rigid >= model.rigid_bodies.len()) {
+                issues.push(SoftBodyIssue::InvalidAnchorRigid {
+                    soft_body,
+                    anchor,
+                    rigid,
+                });
+            }
+        }
+        if data.clusters < 0 {
+            issues.push(SoftBodyIssue::NegativeClusters {
+                soft_body,
+                clusters: data.clusters,
+            });
+        }
+        let iterations = [data.v_it, data.p_it, data.d_it, data.c_it];
+        if iterations.iter().any(|&count| count < 0) || data.p_it == 0 {
+            issues.push(SoftBodyIssue::BadIterations { soft_body });
+        }
+        if let Some(material) = material {
+            if data.form == SoftBodyForm::Rope
+                && model.materials[material].topology() != PrimitiveTopology::LineList
+            {
+                issues.push(SoftBodyIssue::RopeWithoutLines {
+                    soft_body,
+                    material,
+                });
+            }
+        }
+    }
+    issues
+        .into_iter()
+        .map(|issue| (options.severity(&issue), issue))
+        .filter(|(severity, _)| *severity != Severity::Ignore)
+        .collect()
+}
+
+/// problem of references between morphs found by [`check_morphs`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ValidationIssue {
+    /// group or flip morphs refer each other. the path starts and ends with the same morph.
+    MorphCycle(Vec<usize>),
+    /// group morph `morph` contains group morph `child`. PMX allows only one level.
+    NestedGroupMorph { morph: usize, child: usize },
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationIssue::MorphCycle(path) => {
+                write!(f, "morph cycle:")?;
+                for morph in path {
+                    write!(f, " {}", morph)?;
+                }
+                Ok(())
+            }
+            ValidationIssue::NestedGroupMorph { morph, child } => {
+                write!(f, "group morph {} contains group morph {}", morph, child)
+            }
+        }
+    }
+}
+
+/// morphs referred by group or flip morph. out of range indices are skipped.
+fn morph_children(morphs: &[Morph], morph: usize) -> Vec<usize> {
+    let indices = match &morphs[morph].morph_data {
+        MorphKinds::Group(offsets) => offsets.iter().map(|offset| offset.index).collect(),
+        MorphKinds::Flip(offsets) => offsets.iter().map(|offset| offset.index).collect(),
+        _ => vec![],
+    };
+    indices
+        .into_iter()
+        .filter_map(|index| usize::try_from(index).ok())
+        .filter(|index| *index < morphs.len())
+        .collect()
+}
+
+/// find morphs which can't be evaluated in finite steps and nested group morphs.
+///
+/// each cycle is reported once. morph graph is walked without recursion so deep chains are safe.
+pub fn check_morphs(morphs: &[Morph]) -> Vec<ValidationIssue> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        New,
+        Visiting,
+        Done,
+    }
+    let children = (0..morphs.len())
+        .map(|morph| morph_children(morphs, morph))
+        .collect::<Vec<_>>();
+    let mut issues = vec![];
+    let mut marks = vec![Mark::New; morphs.len()];
+    for root in 0..morphs.len() {
+        if marks[root] != Mark::New {
+            continue;
+        }
+        marks[root] = Mark::Visiting;
+        // morph and position in its children
+        let mut path = vec![(root, 0)];
+        while let Some((morph, next)) = path.last_mut() {
+            let morph = *morph;
+            match children[morph].get(*next) {
+                Some(&child) => {
+                    *next += 1;
+                    match marks[child] {
+                        Mark::New => {
+                            marks[child] = Mark::Visiting;
+                            path.push((child, 0));
+                        }
+                        Mark::Visiting => {
+                            let start = path
+                                .iter()
+                                .position(|(morph, _)| *morph == child)
+                                .expect("visiting morph is in path");
+                            let mut cycle = path[start..]
+                                .iter()
+                                .map(|(morph, _)| *morph)
+                                .collect::<Vec<_>>();
+                            cycle.push(child);
+                            issues.push(ValidationIssue::MorphCycle(cycle));
+                        }
+                        Mark::Done => {}
+                    }
+                }
+                None => {
+                    marks[morph] = Mark::Done;
+                    path.pop();
+                }
+            }
+        }
+    }
+    for (morph, data) in morphs.iter().enumerate() {
+        if let MorphKinds::Group(_) = data.morph_data {
+            for &child in &children[morph] {
+                if let MorphKinds::Group(_) = morphs[child].morph_data {
+                    issues.push(ValidationIssue::NestedGroupMorph { morph, child });
+                }
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builder::standard_humanoid_skeleton;
+    use crate::progress::SectionKind;
+    use crate::types::{
+        Bone, BoneIKInfo, ControlPanel, Face, FlipMorph, FrameInner, GroupMorph, IKLink,
+        MorphKinds, VertexWeight,
+    };
+    use crate::validation::{
+        check_frames, check_geometry, check_ik, check_morph_panels, check_morphs,
+        check_semistandard_bones, check_soft_bodies, find_duplicate_names, find_unframed,
+        fix_geometry, repair_ik, DuplicateName, FrameIssue, GeometryIssue, GeometryOptions,
+        IkIssue, PanelIssue, SemiStandardBone, Severity, Side, SoftBodyIssue, SoftBodyOptions,
+        ValidationIssue, PANEL_SOFT_LIMIT, SEMISTANDARD_BONES,
+    };
+    use std::f32::consts::PI;
+
+    #[test]
+    fn semistandard_bones() {
+        let bones = standard_humanoid_skeleton();
+        let report = check_semistandard_bones(&bones);
+        assert_eq!(
+            report.present,
+            [
+                (SemiStandardBone::Groove, 2),
+                (SemiStandardBone::Waist, 3),
+                (SemiStandardBone::UpperBody2, 5),
+            ]
+        );
+        assert_eq!(
+            report.present.len() + report.missing.len(),
+            SEMISTANDARD_BONES.len()
+        );
+        assert!(report
+            .missing
+            .contains(&SemiStandardBone::ArmTwist(Side::Left)));
+        assert_eq!(
+            SemiStandardBone::LegIkParent(Side::Right).name(),
+            "右足ＩＫ親"
+        );
+    }
+
+    /// chain 0 <- 1 <- 2 with IK bone 3 targeting 2.
+    fn chain(ik: BoneIKInfo) -> Vec<Bone> {
+        vec![
+            Bone::builder("根").build(),
+            Bone::builder("中").parent(0).build(),
+            Bone::builder("先").parent(1).build(),
+            Bone {
+                ik_info: Some(ik),
+                ..Bone::builder("IK").build()
+            },
+        ]
+    }
+
+    fn link(ik_bone_index: i32, angle_limit: Option<([f32; 3], [f32; 3])>) -> IKLink {
+        IKLink {
+            ik_bone_index,
+            angle_limit,
+        }
+    }
+
+    #[test]
+    fn valid_skeletons() {
+        assert_eq!(check_ik(&standard_humanoid_skeleton()), []);
+        // links may skip bones in the chain
+        let bones = chain(BoneIKInfo {
+            ik_target_bone_index: 2,
+            ik_iter_count: 10,
+            ik_limit_angle: 1.0,
+            ik_links: vec![link(0, None)],
+        });
+        assert_eq!(check_ik(&bones), []);
+    }
+
+    #[test]
+    fn broken_skeleton() {
+        let mut bones = chain(BoneIKInfo {
+            ik_target_bone_index: 1,
+            ik_iter_count: 0,
+            ik_limit_angle: -1.0,
+            ik_links: vec![
+                // descendant of target
+                link(2, None),
+                link(9, None),
+                link(0, Some(([0.0, 1.0, 0.0], [0.0, -1.0, 0.0]))),
+            ],
+        });
+        assert_eq!(
+            check_ik(&bones),
+            [
+                IkIssue::NoIteration {
+                    bone: 3,
+                    iterations: 0
+                },
+                IkIssue::NonPositiveLimitAngle {
+                    bone: 3,
+                    limit_angle: -1.0
+                },
+                IkIssue::LinkNotAncestor {
+                    bone: 3,
+                    link: 0,
+                    link_bone: 2
+                },
+                IkIssue::InvalidLink {
+                    bone: 3,
+                    link: 1,
+                    link_bone: 9
+                },
+                IkIssue::InvertedAngleLimit { bone: 3, link: 2 },
+            ]
+        );
+
+        assert_eq!(
+            repair_ik(&mut bones),
+            [IkIssue::LinkNotAncestor {
+                bone: 3,
+                link: 0,
+                link_bone: 2
+            }]
+        );
+        let ik = bones[3].ik_info.as_ref().unwrap();
+        assert_eq!(ik.ik_iter_count, 1);
+        assert_eq!(ik.ik_limit_angle, PI);
+        assert_eq!(
+            ik.ik_links,
+            [
+                link(2, None),
+                link(0, Some(([0.0, -1.0, 0.0], [0.0, 1.0, 0.0])))
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_target() {
+        let bones = chain(BoneIKInfo {
+            ik_target_bone_index: -1,
+            ik_iter_count: 1,
+            ik_limit_angle: 1.0,
+            ik_links: vec![link(1, None)],
+        });
+        assert_eq!(
+            check_ik(&bones),
+            [IkIssue::InvalidTarget {
+                bone: 3,
+                target: -1
+            }]
+        );
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let mut model = crate::test_support::cube();
+        assert!(find_duplicate_names(&model).is_empty());
+        let bone = model.bones[0].clone();
+        model.bones.push(bone.clone());
+        model.bones.push(bone.clone());
+        model.materials[1].name = model.materials[0].name.clone();
+        let report = find_duplicate_names(&model);
+        assert_eq!(
+            report.bones,
+            [DuplicateName {
+                name: bone.name,
+                indices: vec![0, 2, 3]
+            }]
+        );
+        assert_eq!(report.materials[0].indices, [0, 1]);
+        assert!(report.morphs.is_empty());
+    }
+
+    #[test]
+    fn geometry() {
+        let mut model = crate::test_support::cube();
+        let options = GeometryOptions::default();
+        assert_eq!(check_geometry(&model, &options), []);
+
+        model.vertices[0].norm = [0.0; 3];
+        model.vertices[1].weight_type = VertexWeight::BDEF2 {
+            bone_index_1: 0,
+            bone_index_2: 1,
+            bone_weight_1: 1.5,
+        };
+        model.vertices[2].weight_type = VertexWeight::BDEF4 {
+            bone_index_1: 0,
+            bone_index_2: 1,
+            bone_index_3: 0,
+            bone_index_4: 1,
+            bone_weight_1: 1.0,
+            bone_weight_2: 1.0,
+            bone_weight_3: 1.0,
+            bone_weight_4: 1.0,
+        };
+        model.vertices[3].position[1] = f32::NAN;
+        model.bones[1].position[0] = f32::INFINITY;
+        model.faces[1] = Face {
+            vertices: [4, 4, 5],
+        };
+        // duplicate of face 0 in a rotated order
+        model.faces[2] = Face {
+            vertices: [2, 1, 0],
+        };
+        // faces of the second material start at 6
+        model.faces[6] = model.faces[2];
+        assert_eq!(
+            check_geometry(&model, &options),
+            [
+                (Severity::Warning, GeometryIssue::ZeroNormal { vertex: 0 }),
+                (Severity::Warning, GeometryIssue::BadWeights { vertex: 1 }),
+                (Severity::Warning, GeometryIssue::BadWeights { vertex: 2 }),
+                (
+                    Severity::Error,
+                    GeometryIssue::NonFinite {
+                        section: SectionKind::Vertices,
+                        index: 3
+                    }
+                ),
+                (Severity::Warning, GeometryIssue::DegenerateFace { face: 1 }),
+                (
+                    Severity::Warning,
+                    GeometryIssue::DuplicateFace {
+                        face: 2,
+                        original: 0
+                    }
+                ),
+                (
+                    Severity::Error,
+                    GeometryIssue::NonFinite {
+                        section: SectionKind::Bones,
+                        index: 1
+                    }
+                ),
+            ]
+        );
+
+        let options = GeometryOptions {
+            zero_normal: Severity::Ignore,
+            degenerate_face: Severity::Error,
+            ..options
+        };
+        let fix = fix_geometry(&mut model, &options);
+        assert_eq!(fix.renormalized_vertices, [1, 2]);
+        assert_eq!(fix.removed_faces, [2]);
+        assert_eq!(model.faces.len(), 11);
+        assert_eq!(model.materials[0].num_face_vertices, 15);
+        assert_eq!(model.vertices[2].weight_type.bone_weights()[0], (0, 0.25));
+        assert_eq!(
+            check_geometry(&model, &options),
+            [
+                (
+                    Severity::Error,
+                    GeometryIssue::NonFinite {
+                        section: SectionKind::Vertices,
+                        index: 3
+                    }
+                ),
+                (Severity::Error, GeometryIssue::DegenerateFace { face: 1 }),
+                (
+                    Severity::Error,
+                    GeometryIssue::NonFinite {
+                        section: SectionKind::Bones,
+                        index: 1
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unframed() {
+        let mut model = crate::test_support::cube();
+        assert!(find_unframed(&model).bones.is_empty());
+        for morph in find_unframed(&model).morphs {
+            crate::frames::add_morph_to(&mut model.frames, "表情", morph as i32).unwrap();
+        }
+        let report = find_unframed(&model);
+        assert!(report.is_empty(), "{:?}", report);
+        crate::frames::remove_references_to_bone(&mut model.frames, 1);
+        model.frames[1]
+            .inners
+            .retain(|inner| *inner != FrameInner::Morph(0));
+        // out of range indices are ignored.
+        model.frames[1].inners.push(FrameInner::Morph(100));
+        let report = find_unframed(&model);
+        assert_eq!(report.bones, [1]);
+        assert_eq!(report.morphs, [0]);
+    }
+
+    #[test]
+    fn morph_panels() {
+        let mut model = crate::test_support::cube();
+        assert!(check_morph_panels(&model.morphs, &model.frames).is_empty());
+        // morph 10 is an impulse morph without panel.
+        model.frames[1].inners.push(FrameInner::Morph(10));
+        model.frames[2].inners.push(FrameInner::Morph(10));
+        model.frames[2].inners.push(FrameInner::Morph(-1));
+        assert_eq!(
+            check_morph_panels(&model.morphs, &model.frames),
+            [
+                PanelIssue::SystemMorphInExpressionFrame { morph: 10 },
+                PanelIssue::UnpanelledMorphInFrame {
+                    frame: 2,
+                    morph: 10
+                },
+                PanelIssue::UnpanelledMorphInFrame {
+                    frame: 2,
+                    morph: -1
+                },
+            ]
+        );
+        let morph = model.morphs[0].clone();
+        model.morphs = vec![morph; PANEL_SOFT_LIMIT + 1];
+        model.frames.clear();
+        assert_eq!(
+            check_morph_panels(&model.morphs, &model.frames),
+            [PanelIssue::CrowdedPanel {
+                panel: ControlPanel::BottomLeft,
+                count: PANEL_SOFT_LIMIT + 1
+            }]
+        );
+    }
+
+    #[test]
+    fn undeclared_uv_channel() {
+        let mut model = crate::test_support::cube();
+        assert!(check_geometry(&model, &GeometryOptions::default()).is_empty());
+        model.additional_uv = 2;
+        let morphs = |kind| {
+            model
+                .morphs
+                .iter()
+                .position(|m| m.morph_data.kind_name() == kind)
+        };
+        let (uv3, uv4) = (morphs("UV3").unwrap(), morphs("UV4").unwrap());
+        let issues = check_geometry(&model, &GeometryOptions::default());
+        assert_eq!(
+            issues,
+            [
+                (
+                    Severity::Warning,
+                    GeometryIssue::UndeclaredUvChannel {
+                        morph: uv3,
+                        channel: 2
+                    }
+                ),
+                (
+                    Severity::Warning,
+                    GeometryIssue::UndeclaredUvChannel {
+                        morph: uv4,
+                        channel: 3
+                    }
+                )
+            ]
+        );
+        assert_eq!(
+            issues[0].1.to_string(),
+            format!(
+                "morph {}: targets additional uv 3 which is not declared",
+                uv3
+            )
+        );
+    }
+
+    #[test]
+    fn invisible_edge() {
+        let mut model = crate::test_support::cube();
+        model.materials[1].edge_color[3] = 0.0;
+        assert_eq!(
+            check_geometry(&model, &GeometryOptions::default()),
+            [(
+                Severity::Warning,
+                GeometryIssue::InvisibleEdge { material: 1 }
+            )]
+        );
+        model.materials[1].set_edge(None);
+        assert!(check_geometry(&model, &GeometryOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn material_face_count() {
+        let mut model = crate::test_support::cube();
+        model.faces.pop();
+        assert_eq!(
+            check_geometry(&model, &GeometryOptions::default()),
+            [(
+                Severity::Error,
+                GeometryIssue::MaterialFaceCount {
+                    material_vertices: 36,
+                    face_vertices: 33
+                }
+            )]
+        );
+        assert!(model.assign_remaining_faces());
+        assert!(check_geometry(&model, &GeometryOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn unused_rigid_size() {
+        use crate::types::{RigidForm, RigidShape};
+        let mut model = crate::test_support::cube();
+        let mut rigid = model.rigid_bodies[0].clone();
+        rigid.set_shape(RigidShape::Sphere { radius: 1.0 });
+        model.rigid_bodies = vec![rigid.clone(); 4];
+        model.rigid_bodies[1].size[1] = 0.5;
+        model.rigid_bodies[2].form = RigidForm::Capsule;
+        model.rigid_bodies[2].size = [1.0, 2.0, 0.1];
+        model.rigid_bodies[3].form = RigidForm::Box;
+        model.rigid_bodies[3].size = [1.0, 2.0, 3.0];
+        assert_eq!(
+            check_geometry(&model, &GeometryOptions::default()),
+            [
+                (
+                    Severity::Warning,
+                    GeometryIssue::UnusedRigidSize { rigid: 1 }
+                ),
+                (
+                    Severity::Warning,
+                    GeometryIssue::UnusedRigidSize { rigid: 2 }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn frame_layout() {
+        let cube = crate::test_support::cube();
+        assert!(check_frames(&cube.frames).is_empty());
+        let [root, expression, ik] = [0, 1, 2].map(|i| cube.frames[i].clone());
+        let mut frames = vec![ik, expression, root];
+        frames[0].inners.push(FrameInner::Bone(1));
+        frames[1].inners.insert(0, FrameInner::Bone(1));
+        frames[2].inners.push(FrameInner::Morph(8));
+        assert_eq!(
+            check_frames(&frames),
+            [
+                FrameIssue::NotSpecial { frame: 0 },
+                FrameIssue::MisplacedSpecial { frame: 2 },
+                FrameIssue::RootFrameEntries {
+                    bones: 2,
+                    morphs: 0
+                },
+                FrameIssue::BoneInExpressionFrame { entry: 0 },
+                FrameIssue::DuplicateEntry {
+                    frame: 0,
+                    entry: 1,
+                    original: 0
+                },
+            ]
+        );
+        frames[0].is_special = true;
+        assert_eq!(
+            check_frames(&frames)[..2],
+            [
+                FrameIssue::SpecialFrameCount { count: 3 },
+                FrameIssue::MisplacedSpecial { frame: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn soft_bodies() {
+        use crate::types::{PrimitiveTopology, SoftBodyAnchorRigid, SoftBodyForm};
+        let model = crate::test_support::cube();
+        let options = SoftBodyOptions::default();
+        assert!(check_soft_bodies(&model, &options).is_empty());
+
+        // one violation of each rule per soft body.
+        let valid = model.soft_bodies[0].clone();
+        let mut broken = model.clone();
+        broken.soft_bodies = vec![valid.clone(); 7];
+        broken.soft_bodies[0].material_index = 2;
+        broken.soft_bodies[1]
+            .anchor_rigid
+            .push(SoftBodyAnchorRigid {
+                rigid_index: 1,
+                vertex_index: 5,
+                near_mode: false,
+            });
+        broken.soft_bodies[2].pin_vertex.push(9);
+        // vertex 8 is not drawn by any material.
+        broken.vertices.push(model.vertices[0].clone());
+        broken.soft_bodies[3].anchor_rigid[0].vertex_index = 8;
+        broken.soft_bodies[4].clusters = -1;
+        broken.soft_bodies[5].p_it = 0;
+        broken.soft_bodies[6].form = SoftBodyForm::Rope;
+        assert_eq!(
+            check_soft_bodies(&broken, &options),
+            [
+                (
+                    Severity::Error,
+                    SoftBodyIssue::InvalidMaterial {
+                        soft_body: 0,
+                        material: 2
+                    }
+                ),
+                (
+                    Severity::Error,
+                    SoftBodyIssue::InvalidAnchorRigid {
+                        soft_body: 1,
+                        anchor: 1,
+                        rigid: 1
+                    }
+                ),
+                (
+                    Severity::Error,
+                    SoftBodyIssue::InvalidVertex {
+                        soft_body: 2,
+                        anchor: None,
+                        vertex: 9
+                    }
+                ),
+                (
+                    Severity::Warning,
+                    SoftBodyIssue::VertexOutsideMaterial {
+                        soft_body: 3,
+                        anchor: Some(0),
+                        vertex: 8
+                    }
+                ),
+                (
+                    Severity::Error,
+                    SoftBodyIssue::NegativeClusters {
+                        soft_body: 4,
+                        clusters: -1
+                    }
+                ),
+                (
+                    Severity::Error,
+                    SoftBodyIssue::BadIterations { soft_body: 5 }
+                ),
+                (
+                    Severity::Warning,
+                    SoftBodyIssue::RopeWithoutLines {
+                        soft_body: 6,
+                        material: 1
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            check_soft_bodies(&broken, &options)[3].1.to_string(),
+            "soft body 3: anchor 0 vertex 8 is not drawn by its material"
+        );
+
+        let options = SoftBodyOptions {
+            invalid_reference: Severity::Ignore,
+            rope_without_lines: Severity::Error,
+            ..SoftBodyOptions::default()
+        };
+        assert_eq!(check_soft_bodies(&broken, &options).len(), 4);
+        broken.materials[1].set_topology(PrimitiveTopology::LineList);
+        assert_eq!(check_soft_bodies(&broken, &options).len(), 3);
+    }
+
+    fn group(indices: &[i32]) -> MorphKinds {
+        MorphKinds::Group(
+            indices
+                .iter()
+                .map(|&index| GroupMorph {
+                    index,
+                    morph_factor: 1.0,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn morph_cycles() {
+        let mut morphs = crate::test_support::cube().morphs;
+        assert_eq!(check_morphs(&morphs), []);
+
+        // group 8 contains itself
+        morphs[8].morph_data = group(&[0, 8]);
+        assert_eq!(
+            check_morphs(&morphs),
+            [
+                ValidationIssue::MorphCycle(vec![8, 8]),
+                ValidationIssue::NestedGroupMorph { morph: 8, child: 8 },
+            ]
+        );
+
+        // flip 9 -> group 8 -> flip 9
+        morphs[8].morph_data = group(&[0, 9]);
+        morphs[9].morph_data = MorphKinds::Flip(vec![FlipMorph {
+            index: 8,
+            morph_factor: 1.0,
+        }]);
+        let issues = check_morphs(&morphs);
+        assert_eq!(issues, [ValidationIssue::MorphCycle(vec![8, 9, 8])]);
+        assert_eq!(issues[0].to_string(), "morph cycle: 8 9 8");
+    }
+
+    #[test]
+    fn nested_group_morph() {
+        let mut morphs = crate::test_support::cube().morphs;
+        morphs[10].morph_data = group(&[8, 1]);
+        assert_eq!(
+            check_morphs(&morphs),
+            [ValidationIssue::NestedGroupMorph {
+                morph: 10,
+                child: 8
+            }]
+        );
+    }
+}
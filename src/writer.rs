@@ -1,20 +1,84 @@
 //! PMX writing module.
 use std::convert::TryFrom;
 
-use crate::binary_writer::BinaryWriter;
+use crate::binary_writer::{BinaryWriter, TextFields, WriteElement, DEFAULT_BUFFER_CAPACITY};
+use crate::frames::{
+    expression_frame, is_special_frame, root_frame, EXPRESSION_FRAME_NAME, ROOT_FRAME_NAME,
+};
+use crate::optimize::normalize_edge_data;
+use crate::progress::{Progress, ProgressCallback, Reporter, SectionKind};
 use crate::types::{
-    Bone, Encode, Face, Frame, Header, IndexKinds, Joint, JointType, Material, ModelInfo, Morph,
-    MorphKinds, PMXVersion, Rigid, SoftBody, Vertex, VertexIndexKinds, VertexWeight,
+    Bone, Encode, Face, Frame, Header, IndexKinds, IndexKindsConfig, Joint, JointType, Material,
+    MaterialFlags, MaterialIndex, Model, ModelInfo, Morph, MorphKinds, PMXVersion, Rigid, SoftBody,
+    Vertex, VertexBuffers, VertexIndexKinds, VertexWeight,
 };
+use std::borrow::Cow;
 use std::io::{Error, Write};
-use std::num::TryFromIntError;
+use std::mem::take;
+use std::ops::ControlFlow;
 use std::path::Path;
+#[cfg(feature = "std-fs")]
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// settings of a single [`Writer::write_with`] call overriding the writer's own.
+///
+/// the default writes as [`Writer::write`] does.
+/// set fields by builder methods as more may be added.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct WriteOptions {
+    /// text encoding instead of [`Writer::set_encode`].
+    pub encode: Option<Encode>,
+    /// version instead of [`Writer::set_version`] or deciding by content.
+    pub version: Option<PMXVersion>,
+    /// index kinds instead of the smallest ones fitting each section.
+    ///
+    /// indices which don't fit fail with `WritePMXErrors::IndexOutOfRange`.
+    pub index_kinds: Option<IndexKindsConfig>,
+    /// fail with `WritePMXErrors::AdditionalUvTruncated` if vertices have additional uvs
+    /// after the declared count. `true` by default.
+    ///
+    /// otherwise they are dropped and reported by [`Writer::take_warnings`].
+    pub strict: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            encode: None,
+            version: None,
+            index_kinds: None,
+            strict: true,
+        }
+    }
+}
+
+impl WriteOptions {
+    pub fn encode(mut self, encode: Encode) -> Self {
+        self.encode = Some(encode);
+        self
+    }
+    pub fn version(mut self, version: PMXVersion) -> Self {
+        self.version = Some(version);
+        self
+    }
+    pub fn index_kinds(mut self, index_kinds: IndexKindsConfig) -> Self {
+        self.index_kinds = Some(index_kinds);
+        self
+    }
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+}
 
 /// PMX writer
 ///
 /// This hold all  ingredients e.g. Vertex, Face, Texture Path,
 ///
 /// When write was called all data was wrote and dropped.
+/// [`write_with`](Writer::write_with) keeps the writer to write variants of the same data.
 ///
 /// ```rust
 /// use PMXUtil::types::ModelInfo;
@@ -28,8 +92,11 @@ use std::path::Path;
 /// });
 /// writer.set_additional_uv(4);// vertices contains 4 additional uv
 /// writer.add_vertices(&vertices);
-/// writer.write_to_path("./path/to/pmx/file.pmx");
+/// let buf: Vec<u8> = writer.write_to_vec().unwrap();
 /// ```
+///
+/// `Writer` is `Send` so a model can be built on one thread and written on another.
+/// it is also `Sync` as [`write_with`](Writer::write_with) and its variants only read it.
 pub struct Writer {
     encode_to_utf_16: bool,
     model_info: Option<ModelInfo>,
@@ -44,6 +111,23 @@ pub struct Writer {
     rigid_bodies: Vec<Rigid>,
     joints: Vec<Joint>,
     soft_bodies: Vec<SoftBody>,
+    /// taken while writing so writes can share the writer.
+    progress: Mutex<Option<ProgressCallback>>,
+    version: Option<PMXVersion>,
+    buffer_capacity: usize,
+    parallel: bool,
+    path_separator: PathSeparator,
+    #[cfg(feature = "std-fs")]
+    base_dir: Option<PathBuf>,
+    trailing_data: Vec<u8>,
+    /// header of the file `trailing_data` was read from.
+    trailing_header: Option<Header>,
+
+    text_limit: Option<usize>,
+    truncate_long_text: bool,
+    mmd_compat: bool,
+    stamp_generator: bool,
+    warnings: Mutex<Vec<WriteWarning>>,
 }
 
 impl Writer {
@@ -51,7 +135,7 @@ impl Writer {
     /// # Arguments
     ///
     /// * `encode_to_utf16`: if true text will encoded in UTF-16 Little Endian
-    ///     if you don't have any special reason turn on it to keep MMD compatibility.
+    ///   if you don't have any special reason turn on it to keep MMD compatibility.
     ///
     ///
     /// # Examples
@@ -74,9 +158,31 @@ impl Writer {
             frames: vec![],
             joints: vec![],
             soft_bodies: vec![],
+            progress: Mutex::new(None),
+            version: None,
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+            parallel: false,
+            path_separator: PathSeparator::default(),
+            #[cfg(feature = "std-fs")]
+            base_dir: None,
+            trailing_data: vec![],
+            trailing_header: None,
+
+            text_limit: None,
+            truncate_long_text: false,
+            mmd_compat: true,
+            stamp_generator: false,
+            warnings: Mutex::new(vec![]),
         }
     }
 
+    /// writer filled with all sections of `model`.
+    ///
+    /// version is decided by content as usual so `model.version` is ignored.
+    pub fn from_model(model: &Model) -> Self {
+        Self::from(model.clone())
+    }
+
     pub fn set_model_info(&mut self, model_info: &ModelInfo) {
         self.model_info.replace(model_info.clone());
     }
@@ -91,6 +197,7 @@ impl Writer {
     /// if additional uv count exceed 4.
     ///
     /// the default additional uv count is 0.
+    /// see also [`infer_additional_uv`](Self::infer_additional_uv).
     ///
     pub fn set_additional_uv(&mut self, count: u8) -> Result<(), &str> {
         if count > 4 {
@@ -101,22 +208,354 @@ impl Writer {
         }
     }
 
+    /// set additional uv count to the number of channels used by vertices and morphs added so far.
+    ///
+    /// a channel is used if any vertex has non zero value in it or channels after it,
+    /// or a uv morph targets it or channels after it.
+    pub fn infer_additional_uv(&mut self) {
+        self.additional_uvs = Some(self.used_additional_uv().max(self.morph_additional_uv()));
+    }
+
+    /// number of channels targeted by `UV1`..`UV4` morphs.
+    fn morph_additional_uv(&self) -> u8 {
+        self.morphs
+            .iter()
+            .filter_map(|morph| morph.morph_data.uv_channel())
+            .map(|channel| channel as u8 + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn used_additional_uv(&self) -> u8 {
+        self.vertices
+            .iter()
+            .map(vertex_additional_uv)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// additional uvs in channels after declared count are not written.
+    fn check_additional_uv(&self) -> Result<(), WritePMXErrors> {
+        let declared = self.additional_uvs.unwrap_or(0);
+        let used = self.used_additional_uv();
+        if declared < used {
+            Err(WritePMXErrors::AdditionalUvTruncated { declared, used })
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn add_vertices(&mut self, vertices: &[Vertex]) {
         self.vertices.extend_from_slice(vertices);
     }
 
+    /// [`add_vertices`](Self::add_vertices) from separate arrays.
+    /// # Panics
+    /// * if arrays of `buffers` have different lengths.
+    pub fn add_vertex_buffers(&mut self, buffers: &VertexBuffers) {
+        self.vertices.extend(buffers.to_vertices());
+    }
+
     pub fn add_faces(&mut self, faces: &[Face]) {
         self.faces.extend_from_slice(faces);
     }
 
+    /// [`add_faces`](Self::add_faces) from flat `[v0, v1, v2, v0, v1, v2, ...]` index buffer.
+    ///
+    /// # Errors
+    /// * `WritePMXErrors::IncompleteFace` if length of `indices` is not a multiple of 3.
+    ///
+    /// nothing is added on error.
+    pub fn add_faces_flat(&mut self, indices: &[u32]) -> Result<(), WritePMXErrors> {
+        if !indices.len().is_multiple_of(3) {
+            return Err(WritePMXErrors::IncompleteFace { len: indices.len() });
+        }
+        self.faces.extend(indices.chunks_exact(3).map(|face| Face {
+            vertices: [face[0], face[1], face[2]],
+        }));
+        Ok(())
+    }
+
     pub fn add_textures(&mut self, textures: &[String]) {
         self.textures.extend_from_slice(textures);
     }
 
+    /// add texture at `path` relative to the model and return its index.
+    ///
+    /// separators are unified to the one set by [`set_path_separator`](Self::set_path_separator)
+    /// and `.` components are removed. returns index of the same path if already added.
+    /// if [`set_base_dir`](Self::set_base_dir) was called the file must exist under it.
+    ///
+    /// # Errors
+    /// * `TexturePathError::Absolute` for absolute paths including Windows style ones.
+    /// * `TexturePathError::Empty` if no file name is left.
+    /// * `TexturePathError::NotUnicode` if `path` can't be represented in PMX text.
+    /// * `TexturePathError::ControlCharacter` if `path` contains control characters.
+    /// * `TexturePathError::NotFound` if the file doesn't exist under the base directory.
+    /// * `TexturePathError::TooMany` if texture index overflows.
+    pub fn add_texture_path(&mut self, path: impl AsRef<Path>) -> Result<i32, TexturePathError> {
+        let path = path.as_ref();
+        let text = path.to_str().ok_or(TexturePathError::NotUnicode)?;
+        if text.chars().any(char::is_control) {
+            return Err(TexturePathError::ControlCharacter(text.to_owned()));
+        }
+        if is_absolute_path(text) || path.is_absolute() {
+            return Err(TexturePathError::Absolute(text.to_owned()));
+        }
+        let components = text
+            .split(['/', '\\'])
+            .filter(|component| !component.is_empty() && *component != ".")
+            .collect::<Vec<_>>();
+        if text.ends_with(['/', '\\']) || components.last().is_none_or(|name| *name == "..") {
+            return Err(TexturePathError::Empty);
+        }
+        let separator = match self.path_separator {
+            PathSeparator::Backslash => "\\",
+            PathSeparator::Slash => "/",
+        };
+        let normalized = components.join(separator);
+        #[cfg(feature = "std-fs")]
+        if let Some(base_dir) = &self.base_dir {
+            let file = components
+                .iter()
+                .fold(base_dir.clone(), |path, component| path.join(component));
+            if !file.is_file() {
+                return Err(TexturePathError::NotFound(file));
+            }
+        }
+        if let Some(index) = self.textures.iter().position(|t| *t == normalized) {
+            return Ok(index as i32);
+        }
+        let index = i32::try_from(self.textures.len()).map_err(|_| TexturePathError::TooMany)?;
+        self.textures.push(normalized);
+        Ok(index)
+    }
+
     pub fn add_materials(&mut self, materials: &[Material]) {
         self.materials.extend_from_slice(materials);
     }
 
+    /// append `faces` and `material` drawing them.
+    ///
+    /// `num_face_vertices` of `material` is overwritten with `3 * faces.len()`.
+    /// can be mixed with [`add_faces`](Self::add_faces) and [`add_materials`](Self::add_materials)
+    /// as long as faces added by them are covered by materials before calling this.
+    /// face indices are not offset so `faces` refer vertices by index in the whole vertex list.
+    ///
+    /// # Errors
+    /// * `WritePMXErrors::FaceCountMismatch` if faces already added don't match materials.
+    ///   nothing is added in this case.
+    /// * `WritePMXErrors::TooBig` of materials if `faces` is too long for `material`.
+    pub fn add_mesh(
+        &mut self,
+        mut material: Material,
+        faces: &[Face],
+    ) -> Result<MaterialIndex, WritePMXErrors> {
+        self.check_face_count()?;
+        let index = section_count(SectionKind::Materials, self.materials.len())?;
+        material.num_face_vertices = faces
+            .len()
+            .checked_mul(3)
+            .and_then(|count| i32::try_from(count).ok())
+            .ok_or_else(|| WritePMXErrors::TooBig {
+                section: SectionKind::Materials,
+                element_name: Some(material.name.clone()),
+            })?;
+        self.faces.extend_from_slice(faces);
+        self.materials.push(material);
+        Ok(index)
+    }
+
+    /// make sure materials draw all faces before writing.
+    ///
+    /// if `num_face_vertices` of the last material is 0, e.g. materials were added before
+    /// their faces, it takes the faces not covered by other materials.
+    ///
+    /// # Errors
+    /// * `WritePMXErrors::FaceCountMismatch` if materials still don't cover faces exactly.
+    ///   writing it makes a file `MMD` draws with wrong materials.
+    pub fn finalize_material_counts(&mut self) -> Result<(), WritePMXErrors> {
+        let face_vertices = self.faces.len() as i64 * 3;
+        let material_vertices = self.material_vertices();
+        if let Some(last) = self.materials.last_mut() {
+            if last.num_face_vertices == 0 && material_vertices < face_vertices {
+                last.num_face_vertices =
+                    i32::try_from(face_vertices - material_vertices).map_err(|_| {
+                        WritePMXErrors::TooBig {
+                            section: SectionKind::Materials,
+                            element_name: Some(last.name.clone()),
+                        }
+                    })?;
+            }
+        }
+        self.check_face_count()
+    }
+
+    /// zero fields which are written but never used so equal models give identical bytes.
+    ///
+    /// * unused components of rigid body sizes, see [`Rigid::shape`].
+    /// * `edge_mag` of vertices not drawn with edge, see
+    ///   [`optimize::normalize_edge_data`](crate::optimize::normalize_edge_data).
+    ///
+    /// joint slots not used by their kind are always written as zero.
+    /// call this after all sections and faces of materials are added.
+    pub fn canonicalize(&mut self) {
+        let mut model = Model {
+            vertices: take(&mut self.vertices),
+            faces: take(&mut self.faces),
+            materials: take(&mut self.materials),
+            ..Model::default()
+        };
+        normalize_edge_data(&mut model);
+        self.vertices = model.vertices;
+        self.faces = model.faces;
+        self.materials = model.materials;
+        for rigid in &mut self.rigid_bodies {
+            rigid.set_shape(rigid.shape());
+        }
+    }
+
+    /// sum of `num_face_vertices` of materials.
+    fn material_vertices(&self) -> i64 {
+        self.materials
+            .iter()
+            .map(|material| i64::from(material.num_face_vertices))
+            .sum()
+    }
+
+    fn check_face_count(&self) -> Result<(), WritePMXErrors> {
+        let material_vertices = self.material_vertices();
+        let face_vertices = self.faces.len() as i64 * 3;
+        if material_vertices != face_vertices {
+            return Err(WritePMXErrors::FaceCountMismatch {
+                material_vertices,
+                face_vertices,
+            });
+        }
+        Ok(())
+    }
+
+    /// check that the model to be written is consistent.
+    ///
+    /// problems `MMD` tolerates are returned as warnings.
+    ///
+    /// # Errors
+    /// * `WritePMXErrors::FaceCountMismatch` if sum of `num_face_vertices` of materials is not
+    ///   3 times of face count. `MMD` draws faces with wrong material or fails to load.
+    /// * `WritePMXErrors::AdditionalUvTruncated` if vertices use more additional uvs than declared.
+    /// * `WritePMXErrors::TextTooLong` if a text is over the limit and not truncated.
+    /// * `WritePMXErrors::TooBig` if a list in an element is too long.
+    /// * `WritePMXErrors::RequiresV21` and `WritePMXErrors::MmdIncompatibleEncoding`
+    ///   as [`write`](Self::write).
+    pub fn validate(&self) -> Result<Vec<WriteWarning>, WritePMXErrors> {
+        self.check_additional_uv()?;
+        let text_warnings = self.check_texts()?;
+        self.check_face_count()?;
+        self.check_entry_counts()?;
+        self.resolve_version(&WriteOptions::default())?;
+        let mut warnings = vec![];
+        for (expected, name) in [ROOT_FRAME_NAME, EXPRESSION_FRAME_NAME].iter().enumerate() {
+            match self
+                .frames
+                .iter()
+                .position(|frame| is_special_frame(frame, name))
+            {
+                None => warnings.push(WriteWarning::MissingSpecialFrame(name)),
+                Some(index) if index != expected => {
+                    warnings.push(WriteWarning::MisplacedSpecialFrame {
+                        name,
+                        index,
+                        expected,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        warnings.extend(text_warnings);
+        Ok(warnings)
+    }
+
+    fn encode(&self) -> Encode {
+        if self.encode_to_utf_16 {
+            Encode::Utf16Le
+        } else {
+            Encode::UTF8
+        }
+    }
+
+    /// texts longer than `i32::MAX` bytes can't be written and ones over `text_limit` fail
+    /// unless truncated.
+    fn check_texts(&self) -> Result<Vec<WriteWarning>, WritePMXErrors> {
+        let encode = self.encode();
+        let mut warnings = vec![];
+        let mut check = |field: &dyn Fn() -> String, text: &str| {
+            let over_limit = self
+                .text_limit
+                .and_then(|limit| text.char_indices().nth(limit))
+                .map(|(end, _)| end);
+            let written = match over_limit {
+                Some(end) if self.truncate_long_text => &text[..end],
+                _ => text,
+            };
+            let len = || text.chars().count();
+            if (over_limit.is_some() && !self.truncate_long_text)
+                || i32::try_from(encode.text_len(written)).is_err()
+            {
+                return Err(WritePMXErrors::TextTooLong {
+                    field: field(),
+                    len: len(),
+                });
+            }
+            if over_limit.is_some() {
+                warnings.push(WriteWarning::TextTruncated {
+                    field: field(),
+                    len: len(),
+                });
+            }
+            Ok(())
+        };
+        for (field, text) in self.model_info.iter().flat_map(TextFields::text_fields) {
+            check(&|| format!("model_info.{}", field), text)?;
+        }
+        check_list("textures", &self.textures, &mut check)?;
+        check_list("materials", &self.materials, &mut check)?;
+        check_list("bones", &self.bones, &mut check)?;
+        check_list("morphs", &self.morphs, &mut check)?;
+        check_list("frames", &self.frames, &mut check)?;
+        check_list("rigid_bodies", &self.rigid_bodies, &mut check)?;
+        check_list("joints", &self.joints, &mut check)?;
+        check_list("soft_bodies", &self.soft_bodies, &mut check)?;
+        Ok(warnings)
+    }
+
+    /// lists in elements are written with `i32` count.
+    fn check_entry_counts(&self) -> Result<(), WritePMXErrors> {
+        check_entries(SectionKind::Bones, &self.bones)?;
+        check_entries(SectionKind::Morphs, &self.morphs)?;
+        check_entries(SectionKind::Frames, &self.frames)?;
+        check_entries(SectionKind::SoftBodies, &self.soft_bodies)
+    }
+
+    /// put special "Root" and "表情" frames at the top of frames.
+    ///
+    /// existing ones are moved. missing ones are created by
+    /// [`frames::root_frame`](crate::frames::root_frame) and
+    /// [`frames::expression_frame`](crate::frames::expression_frame) from bones and morphs added
+    /// so far.
+    pub fn ensure_default_frames(&mut self) {
+        let mut take = |name: &str| {
+            self.frames
+                .iter()
+                .position(|frame| is_special_frame(frame, name))
+                .map(|index| self.frames.remove(index))
+        };
+        let root = take(ROOT_FRAME_NAME);
+        let expression = take(EXPRESSION_FRAME_NAME);
+        let root = root.unwrap_or_else(|| root_frame(&self.bones));
+        let expression = expression.unwrap_or_else(|| expression_frame(&self.morphs));
+        self.frames.splice(0..0, vec![root, expression]);
+    }
+
     pub fn add_morphs(&mut self, morphs: &[Morph]) {
         self.morphs.extend_from_slice(morphs);
     }
@@ -141,122 +580,355 @@ impl Writer {
         self.soft_bodies.extend_from_slice(soft_bodies);
     }
 
-    fn calculate_header(&self) -> (Header, bool) {
-        let vertex = self
-            .vertices
-            .iter()
-            .find(|vertex| matches!(vertex.weight_type, VertexWeight::QDEF { .. }));
-        let morph = self.morphs.iter().find(|morph| {
-            matches!(
-                morph.morph_data,
-                MorphKinds::Flip(_) | MorphKinds::Impulse(_)
-            )
-        });
-        let joint = self.joints.iter().find(|joint| {
-            matches!(
-                joint.joint_type,
-                JointType::Slider { .. }
-                    | JointType::SixDof { .. }
-                    | JointType::ConeTwist { .. }
-                    | JointType::Hinge { .. }
-                    | JointType::P2P { .. }
-            )
-        });
-        let ext_2_1 =
-            vertex.is_some() | morph.is_some() | joint.is_some() | !self.soft_bodies.is_empty();
+    /// text encoding. same as `encode_to_utf_16` of [`begin_writer`](Self::begin_writer).
+    pub fn set_encode(&mut self, encode: Encode) {
+        self.encode_to_utf_16 = encode == Encode::Utf16Le;
+    }
+
+    /// write as `version` instead of deciding by content.
+    ///
+    /// PMX 2.0 fails with `WritePMXErrors::RequiresV21` when 2.1 only elements were added.
+    /// materials with vertex color, point or line drawing flags are 2.1 only too.
+    pub fn set_version(&mut self, version: PMXVersion) {
+        self.version = Some(version);
+    }
+
+    /// size of the buffer between serializer and destination. 256 KiB by default.
+    ///
+    /// sections are serialized into the buffer and flushed in chunks of this size.
+    /// small buffers make every field a separate write to the destination, so keep it large
+    /// even if the destination is a `BufWriter`.
+    pub fn set_buffer_capacity(&mut self, capacity: usize) {
+        self.buffer_capacity = capacity;
+    }
+
+    /// encode chunks of each section on the rayon thread pool before writing it.
+    ///
+    /// output is the same as sequential writing but each section is kept in memory once more.
+    /// progress is reported only at section boundaries.
+    #[cfg(feature = "rayon")]
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel;
+    }
+
+    /// separator of paths added by [`add_texture_path`](Self::add_texture_path).
+    /// `\` by default as `MMD` runs on Windows.
+    pub fn set_path_separator(&mut self, separator: PathSeparator) {
+        self.path_separator = separator;
+    }
+
+    /// check that textures added by [`add_texture_path`](Self::add_texture_path) exist under
+    /// `base_dir`, usually the directory the model is written to.
+    #[cfg(feature = "std-fs")]
+    pub fn set_base_dir(&mut self, base_dir: impl Into<PathBuf>) {
+        self.base_dir = Some(base_dir.into());
+    }
+
+    /// limit characters of each text field, e.g. to caps of `MMD` or `PMXEditor`.
+    ///
+    /// longer texts fail to write with `WritePMXErrors::TextTooLong` unless
+    /// [`set_truncate_long_text`](Self::set_truncate_long_text) is enabled.
+    /// `None` by default which allows texts up to `i32::MAX` bytes, the limit of PMX.
+    /// see [`Encode::text_len`] for bytes of texts.
+    pub fn set_text_limit(&mut self, limit: Option<usize>) {
+        self.text_limit = limit;
+    }
+
+    /// cut texts longer than [`set_text_limit`](Self::set_text_limit) on write instead of failing.
+    ///
+    /// truncated texts are reported by [`validate`](Self::validate) as
+    /// `WriteWarning::TextTruncated`.
+    pub fn set_truncate_long_text(&mut self, truncate: bool) {
+        self.truncate_long_text = truncate;
+    }
+
+    /// reject output `MMD` reads incorrectly. enabled by default.
+    ///
+    /// PMX 2.0 allows UTF-8 text but `MMD` expects UTF-16LE in practice, so UTF-8 PMX 2.0
+    /// fails with `WritePMXErrors::MmdIncompatibleEncoding` unless this is disabled.
+    pub fn set_mmd_compat(&mut self, mmd_compat: bool) {
+        self.mmd_compat = mmd_compat;
+    }
+
+    /// append `Exported by pmx_util {version} on {date}` line to
+    /// [`comment_en`](ModelInfo::comment_en) on write. disabled by default.
+    ///
+    /// a line stamped by an earlier write is replaced, so saving repeatedly keeps one line.
+    /// the line ends with `\r\n` if the comment does, as comments written by `PMXEditor` do.
+    /// the comment is stamped with [`set_trailing_data`](Self::set_trailing_data) too, so the
+    /// output is no longer byte identical to the source then.
+    /// the date is omitted on `wasm32-unknown-unknown` which has no clock.
+    /// the writer keeps the comment as given.
+    pub fn set_stamp_generator(&mut self, stamp: bool) {
+        self.stamp_generator = stamp;
+    }
+
+    /// bytes written after the last section as they are.
+    ///
+    /// for byte identical round trip of files with [`TailInfo::raw`](crate::reader::TailInfo::raw).
+    pub fn set_trailing_data(&mut self, data: Vec<u8>) {
+        self.trailing_data = data;
+    }
+
+    /// `callback` is invoked at section boundaries and every
+    /// [`PROGRESS_INTERVAL`](crate::progress::PROGRESS_INTERVAL) elements while writing.
+    ///
+    /// returning `ControlFlow::Break(())` stops writing with `WritePMXErrors::Cancelled`.
+    /// already written part is left in the destination.
+    pub fn set_progress<F>(&mut self, callback: F)
+    where
+        F: FnMut(Progress) -> ControlFlow<()> + Send + 'static,
+    {
+        *lock(&self.progress) = Some(Box::new(callback));
+    }
+
+    /// `true` if written as PMX 2.1.
+    fn resolve_version(&self, options: &WriteOptions) -> Result<bool, WritePMXErrors> {
+        let requires_v21 = self.vertices.iter().any(vertex_requires_v21)
+            || self.materials.iter().any(material_requires_v21)
+            || self.morphs.iter().any(morph_requires_v21)
+            || self.joints.iter().any(joint_requires_v21)
+            || !self.soft_bodies.is_empty();
+        let ext_2_1 = match options.version.or(self.version) {
+            None => requires_v21,
+            Some(PMXVersion::V20) if requires_v21 => return Err(WritePMXErrors::RequiresV21),
+            Some(version) => version == PMXVersion::V21,
+        };
+        let encode = options.encode.unwrap_or_else(|| self.encode());
+        if self.mmd_compat && !ext_2_1 && encode != Encode::Utf16Le {
+            return Err(WritePMXErrors::MmdIncompatibleEncoding {
+                version: PMXVersion::V20,
+                encode,
+            });
+        }
+        Ok(ext_2_1)
+    }
+
+    pub(crate) fn calculate_header(
+        &self,
+        options: &WriteOptions,
+    ) -> Result<(Header, bool), WritePMXErrors> {
+        if options.strict {
+            self.check_additional_uv()?;
+        }
+        self.check_texts()?;
+        self.check_entry_counts()?;
+        let ext_2_1 = self.resolve_version(options)?;
 
         // calculate all parameters and create actual writer.
-        (
-            Header {
-                magic: "PMX ".to_owned(),
-                version: if ext_2_1 {
-                    PMXVersion::V21
-                } else {
-                    PMXVersion::V20
-                },
-                length: 8,
-                encode: if self.encode_to_utf_16 {
-                    Encode::Utf16Le
-                } else {
-                    Encode::UTF8
-                },
-                additional_uv: self.additional_uvs.unwrap_or(0),
-                s_vertex_index: optimal_data_type_vertex(self.vertices.len()),
-                s_texture_index: optimal_data_type(self.textures.len()),
-                s_material_index: optimal_data_type(self.materials.len()),
-                s_bone_index: optimal_data_type(self.bones.len()),
-                s_morph_index: optimal_data_type(self.morphs.len()),
-                s_rigid_body_index: optimal_data_type(self.rigid_bodies.len()),
+        let mut header = Header {
+            version: if ext_2_1 {
+                PMXVersion::V21
+            } else {
+                PMXVersion::V20
             },
-            ext_2_1,
-        )
+            encode: options.encode.unwrap_or_else(|| self.encode()),
+            // uv morphs targeting undeclared channels are rejected by `PMXEditor`.
+            additional_uv: self
+                .additional_uvs
+                .unwrap_or(0)
+                .max(self.morph_additional_uv()),
+            ..Header::for_counts(
+                self.vertices.len(),
+                self.textures.len(),
+                self.materials.len(),
+                self.bones.len(),
+                self.morphs.len(),
+                self.rigid_bodies.len(),
+            )
+        };
+        if let Some(kinds) = options.index_kinds {
+            header.s_vertex_index = kinds.vertex;
+            header.s_texture_index = kinds.texture;
+            header.s_material_index = kinds.material;
+            header.s_bone_index = kinds.bone;
+            header.s_morph_index = kinds.morph;
+            header.s_rigid_body_index = kinds.rigid_body;
+        }
+        Ok((header, ext_2_1))
+    }
+
+    /// indices in data kept by `ParseOptions::preserve_unknown` are never rewritten,
+    /// so they must keep the sizes of the source file.
+    fn check_trailing_index_sizes(&self, header: &Header) -> Result<(), WritePMXErrors> {
+        let source = match &self.trailing_header {
+            Some(source) if !self.trailing_data.is_empty() => source,
+            _ => return Ok(()),
+        };
+        let sizes = |header: &Header| {
+            [
+                u8::from(header.s_vertex_index),
+                u8::from(header.s_texture_index),
+                u8::from(header.s_material_index),
+                u8::from(header.s_bone_index),
+                u8::from(header.s_morph_index),
+                u8::from(header.s_rigid_body_index),
+            ]
+        };
+        if sizes(source) == sizes(header) {
+            Ok(())
+        } else {
+            Err(WritePMXErrors::UnknownDataIndexSizes)
+        }
     }
 
+    /// warnings of writes by [`write_with`](Self::write_with) and its variants so far.
+    ///
+    /// the list is emptied.
+    pub fn take_warnings(&mut self) -> Vec<WriteWarning> {
+        take(&mut *lock(&self.warnings))
+    }
+
+    /// warn additional uvs after `declared` channels which are not written.
+    fn warn_dropped_additional_uv(&self, declared: u8) {
+        let mut warnings = lock(&self.warnings);
+        for (vertex, data) in self.vertices.iter().enumerate() {
+            for channel in declared..vertex_additional_uv(data) {
+                if data.add_uv[usize::from(channel)] != [0.0; 4] {
+                    warnings.push(WriteWarning::AdditionalUvDropped { vertex, channel });
+                }
+            }
+        }
+    }
+
+    /// write sections. texts are truncated in output only.
     fn burn_by_writer<W: Write>(
         &self,
         mut writer: BinaryWriter<W>,
         ext_2_1: bool,
     ) -> Result<(), WritePMXErrors> {
-        let model_info = if let Some(mi) = &self.model_info {
-            mi
+        self.check_trailing_index_sizes(&writer.header)?;
+
+        let declared = writer.header.additional_uv;
+        self.warn_dropped_additional_uv(declared);
+        if self.truncate_long_text {
+            writer.text_limit = self.text_limit;
+        }
+        // a concurrent write on another thread reports no progress.
+        let callback = lock(&self.progress).take();
+        let mut reporter = Reporter::new(callback);
+        let result = self.write_sections(writer, ext_2_1, &mut reporter);
+        if let Some(callback) = reporter.into_callback() {
+            *lock(&self.progress) = Some(callback);
+        }
+        result
+    }
+
+    fn write_sections<W: Write>(
+        &self,
+        mut writer: BinaryWriter<W>,
+        ext_2_1: bool,
+        reporter: &mut Reporter,
+    ) -> Result<(), WritePMXErrors> {
+        let mut model_info = if let Some(mi) = &self.model_info {
+            Cow::Borrowed(mi)
         } else {
             return Err(WritePMXErrors::NoModelInfo);
         };
+        if self.stamp_generator {
+            let line = generator_line(today().as_deref());
+            let stamped = stamp_generator(&model_info.comment_en, &line);
+            model_info.to_mut().comment_en = stamped;
+        }
+        let parallel = self.parallel;
+        let writer = &mut writer;
         writer.write_header();
-        writer.write_text_buf(&model_info.name);
-        writer.write_text_buf(&model_info.name_en);
-        writer.write_text_buf(&model_info.comment);
-        writer.write_text_buf(&model_info.comment_en);
+        write_list(
+            writer,
+            reporter,
+            SectionKind::ModelInfo,
+            std::slice::from_ref(model_info.as_ref()),
+            parallel,
+        )?;
         //wrote model info
 
-        writer.write_i32(i32::try_from(self.vertices.len())?);
-        self.vertices
-            .iter()
-            .for_each(|vertex| writer.write_vertex(vertex));
+        writer.write_i32(section_count(SectionKind::Vertices, self.vertices.len())?);
+        write_list(
+            writer,
+            reporter,
+            SectionKind::Vertices,
+            &self.vertices,
+            parallel,
+        )?;
 
-        writer.write_i32(i32::try_from(3 * self.faces.len())?);
-        self.faces.iter().for_each(|face| writer.write_face(face));
+        writer.write_i32(section_count(SectionKind::Faces, 3 * self.faces.len())?);
+        write_list(writer, reporter, SectionKind::Faces, &self.faces, parallel)?;
 
-        writer.write_i32(i32::try_from(self.textures.len())?);
-        self.textures
-            .iter()
-            .for_each(|name| writer.write_text_buf(name));
+        writer.write_i32(section_count(SectionKind::Textures, self.textures.len())?);
+        write_list(
+            writer,
+            reporter,
+            SectionKind::Textures,
+            &self.textures,
+            parallel,
+        )?;
 
-        writer.write_i32(i32::try_from(self.materials.len())?);
-        self.materials
-            .iter()
-            .for_each(|material| writer.write_material(material));
+        writer.write_i32(section_count(SectionKind::Materials, self.materials.len())?);
+        write_list(
+            writer,
+            reporter,
+            SectionKind::Materials,
+            &self.materials,
+            parallel,
+        )?;
 
-        writer.write_i32(i32::try_from(self.bones.len())?);
-        self.bones.iter().for_each(|bone| writer.write_bone(bone));
+        writer.write_i32(section_count(SectionKind::Bones, self.bones.len())?);
+        write_list(writer, reporter, SectionKind::Bones, &self.bones, parallel)?;
 
-        writer.write_i32(i32::try_from(self.morphs.len())?);
-        self.morphs
-            .iter()
-            .for_each(|morph| writer.write_morph(morph));
+        writer.write_i32(section_count(SectionKind::Morphs, self.morphs.len())?);
+        write_list(
+            writer,
+            reporter,
+            SectionKind::Morphs,
+            &self.morphs,
+            parallel,
+        )?;
 
-        writer.write_i32(i32::try_from(self.frames.len())?);
-        self.frames
-            .iter()
-            .for_each(|frame| writer.write_frame(frame));
+        writer.write_i32(section_count(SectionKind::Frames, self.frames.len())?);
+        write_list(
+            writer,
+            reporter,
+            SectionKind::Frames,
+            &self.frames,
+            parallel,
+        )?;
 
-        writer.write_i32(i32::try_from(self.rigid_bodies.len())?);
-        self.rigid_bodies
-            .iter()
-            .for_each(|rigid| writer.write_rigid(rigid));
+        writer.write_i32(section_count(
+            SectionKind::RigidBodies,
+            self.rigid_bodies.len(),
+        )?);
+        write_list(
+            writer,
+            reporter,
+            SectionKind::RigidBodies,
+            &self.rigid_bodies,
+            parallel,
+        )?;
 
-        writer.write_i32(i32::try_from(self.joints.len())?);
-        self.joints
-            .iter()
-            .for_each(|joint| writer.write_joint(joint));
+        writer.write_i32(section_count(SectionKind::Joints, self.joints.len())?);
+        write_list(
+            writer,
+            reporter,
+            SectionKind::Joints,
+            &self.joints,
+            parallel,
+        )?;
 
         // 2.1 extended section.
         if ext_2_1 {
-            writer.write_i32(i32::try_from(self.soft_bodies.len())?);
-            self.soft_bodies
-                .iter()
-                .for_each(|soft_body| writer.write_soft_body(soft_body));
+            writer.write_i32(section_count(
+                SectionKind::SoftBodies,
+                self.soft_bodies.len(),
+            )?);
+            write_list(
+                writer,
+                reporter,
+                SectionKind::SoftBodies,
+                &self.soft_bodies,
+                parallel,
+            )?;
         }
+        writer.inner.write_all(&self.trailing_data)?;
         writer.inner.flush().map_err(WritePMXErrors::IoError)
     }
 
@@ -265,12 +937,34 @@ impl Writer {
     /// # Panics
     ///
     /// # Errors
-    /// * `WritePMXErrors::TooBig` if any buffer elements exceeds `i32::MAX`
+    /// * `WritePMXErrors::TooBig` if a section or a list in its element exceeds `i32::MAX`
     /// * `WritePMXErrors::NoModelInfo` if model info is not set.
+    /// * `WritePMXErrors::AdditionalUvTruncated` if vertices use more additional uvs than declared.
+    /// * `WritePMXErrors::RequiresV21` if PMX 2.0 was requested for 2.1 only elements.
+    /// * `WritePMXErrors::MmdIncompatibleEncoding` if PMX 2.0 is written in UTF-8.
+    ///   see [`set_mmd_compat`](Self::set_mmd_compat).
+    /// * `WritePMXErrors::TextTooLong` if a text is over the limit. see [`set_text_limit`](Self::set_text_limit).
+    /// * `WritePMXErrors::IndexOutOfRange` if an index doesn't fit the index size,
+    ///   e.g. refers past the end of its section. data before it is already written.
     /// * `WritePMXErrors::IoError` if failed to write pmx.
+    /// * `WritePMXErrors::Cancelled` if progress callback cancelled writing.
+    #[cfg(feature = "std-fs")]
     pub fn write_to_path<P: AsRef<Path>>(self, path: P) -> Result<(), WritePMXErrors> {
-        let (header, ext_2_1) = self.calculate_header();
-        let writer = crate::binary_writer::BinaryWriter::create(path, header)?;
+        self.write_to_path_with(path, &WriteOptions::default())
+    }
+
+    /// [`write_to_path`](Self::write_to_path) with `options` keeping the writer.
+    ///
+    /// # Errors
+    /// same as [`write_to_path`](Self::write_to_path)
+    #[cfg(feature = "std-fs")]
+    pub fn write_to_path_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &WriteOptions,
+    ) -> Result<(), WritePMXErrors> {
+        let (header, ext_2_1) = self.calculate_header(options)?;
+        let writer = BinaryWriter::create(path, header, self.buffer_capacity)?;
         self.burn_by_writer(writer, ext_2_1)
     }
 
@@ -279,16 +973,367 @@ impl Writer {
     /// # Panics
     ///
     /// # Errors
-    /// * `WritePMXErrors::TooBig` if any buffer elements exceeds `i32::MAX`
+    /// * `WritePMXErrors::TooBig` if a section or a list in its element exceeds `i32::MAX`
     /// * `WritePMXErrors::NoModelInfo` if model info is not set.
+    /// * `WritePMXErrors::AdditionalUvTruncated` if vertices use more additional uvs than declared.
+    /// * `WritePMXErrors::RequiresV21` if PMX 2.0 was requested for 2.1 only elements.
+    /// * `WritePMXErrors::MmdIncompatibleEncoding` if PMX 2.0 is written in UTF-8.
+    ///   see [`set_mmd_compat`](Self::set_mmd_compat).
+    /// * `WritePMXErrors::TextTooLong` if a text is over the limit. see [`set_text_limit`](Self::set_text_limit).
+    /// * `WritePMXErrors::IndexOutOfRange` if an index doesn't fit the index size,
+    ///   e.g. refers past the end of its section. data before it is already written.
+    /// * `WritePMXErrors::Cancelled` if progress callback cancelled writing.
     pub fn write<W: Write>(self, writer: W) -> Result<(), WritePMXErrors> {
-        let (header, ext_2_1) = self.calculate_header();
-        let writer = crate::binary_writer::BinaryWriter::from_writer(writer, header);
+        self.write_with(writer, &WriteOptions::default())
+    }
+
+    /// [`write`](Self::write) with `options` keeping the writer.
+    ///
+    /// the writer is only read. texts over the limit are truncated in output only, and the
+    /// progress callback is kept for the next write.
+    ///
+    /// # Errors
+    /// same as [`write`](Self::write)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use PMXUtil::types::{Encode, PMXVersion};
+    /// use PMXUtil::writer::{WriteOptions, Writer};
+    /// let mut writer = Writer::begin_writer(true);
+    /// writer.set_model_info(&Default::default());
+    /// let mut utf16 = vec![];
+    /// writer.write_with(&mut utf16, &WriteOptions::default()).unwrap();
+    /// let options = WriteOptions::default()
+    ///     .encode(Encode::UTF8)
+    ///     .version(PMXVersion::V21);
+    /// let utf8 = writer.write_to_vec_with(&options).unwrap();
+    /// assert_ne!(utf16, utf8);
+    /// ```
+    pub fn write_with<W: Write>(
+        &self,
+        writer: W,
+        options: &WriteOptions,
+    ) -> Result<(), WritePMXErrors> {
+        let (header, ext_2_1) = self.calculate_header(options)?;
+        let writer = BinaryWriter::with_capacity(writer, header, self.buffer_capacity);
         self.burn_by_writer(writer, ext_2_1)
     }
+
+    /// write all data to memory and drop it
+    ///
+    /// # Errors
+    /// same as [`write`](Self::write)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut writer = PMXUtil::writer::Writer::begin_writer(true);
+    /// writer.set_model_info(&Default::default());
+    /// let buf = writer.write_to_vec().unwrap();
+    /// assert_eq!(&buf[..4], b"PMX ");
+    /// ```
+    pub fn write_to_vec(self) -> Result<Vec<u8>, WritePMXErrors> {
+        self.write_to_vec_with(&WriteOptions::default())
+    }
+
+    /// [`write_to_vec`](Self::write_to_vec) with `options` keeping the writer.
+    ///
+    /// # Errors
+    /// same as [`write`](Self::write)
+    pub fn write_to_vec_with(&self, options: &WriteOptions) -> Result<Vec<u8>, WritePMXErrors> {
+        let mut buf = vec![];
+        self.write_with(&mut buf, options)?;
+        Ok(buf)
+    }
+}
+
+impl From<Model> for Writer {
+    /// [`Writer::from_model`] without copying sections.
+    fn from(model: Model) -> Self {
+        let mut writer = Self::begin_writer(model.encode == Encode::Utf16Le);
+        writer.model_info = Some(model.model_info);
+        writer.additional_uvs = Some(model.additional_uv);
+        writer.vertices = model.vertices;
+        writer.faces = model.faces;
+        writer.textures = model.textures.textures;
+        writer.materials = model.materials;
+        writer.bones = model.bones;
+        writer.morphs = model.morphs;
+        writer.frames = model.frames;
+        writer.rigid_bodies = model.rigid_bodies;
+        writer.joints = model.joints;
+        writer.soft_bodies = model.soft_bodies;
+        writer.trailing_data = model.unknown.trailing;
+        writer.trailing_header = model.unknown.header;
+        writer
+    }
+}
+
+/// check text fields of `list` named like `bones[3].name`.
+fn check_list<T: TextFields>(
+    section: &str,
+    list: &[T],
+    check: &mut impl FnMut(&dyn Fn() -> String, &str) -> Result<(), WritePMXErrors>,
+) -> Result<(), WritePMXErrors> {
+    for (index, element) in list.iter().enumerate() {
+        for (field, text) in element.text_fields() {
+            let name = || {
+                if field.is_empty() {
+                    format!("{}[{}]", section, index)
+                } else {
+                    format!("{}[{}].{}", section, index, field)
+                }
+            };
+            check(&name, text)?;
+        }
+    }
+    Ok(())
+}
+
+/// number of additional uv channels up to the last non zero one.
+pub(crate) fn vertex_additional_uv(vertex: &Vertex) -> u8 {
+    vertex
+        .add_uv
+        .iter()
+        .rposition(|uv| uv.iter().any(|x| *x != 0.0))
+        .map_or(0, |channel| channel as u8 + 1)
+}
+
+pub(crate) fn vertex_requires_v21(vertex: &Vertex) -> bool {
+    matches!(vertex.weight_type, VertexWeight::QDEF { .. })
+}
+
+/// vertex color, point and line drawing are 2.1 only flags.
+pub(crate) fn material_requires_v21(material: &Material) -> bool {
+    material.draw_mode.intersects(
+        MaterialFlags::VERTEX_COLOR | MaterialFlags::POINT_DRAW | MaterialFlags::LINE_DRAW,
+    )
+}
+
+pub(crate) fn morph_requires_v21(morph: &Morph) -> bool {
+    matches!(
+        morph.morph_data,
+        MorphKinds::Flip(_) | MorphKinds::Impulse(_)
+    )
+}
+
+pub(crate) fn joint_requires_v21(joint: &Joint) -> bool {
+    matches!(
+        joint.joint_type,
+        JointType::Slider { .. }
+            | JointType::SixDof { .. }
+            | JointType::ConeTwist { .. }
+            | JointType::Hinge { .. }
+            | JointType::P2P { .. }
+    )
+}
+
+/// write elements of `section` reporting progress.
+#[cfg_attr(not(feature = "rayon"), allow(unused_variables))]
+fn write_list<W: Write, T: WriteElement + Sync>(
+    writer: &mut BinaryWriter<W>,
+    reporter: &mut Reporter,
+    section: SectionKind,
+    list: &[T],
+    parallel: bool,
+) -> Result<(), WritePMXErrors> {
+    #[cfg(feature = "tracing")]
+    let _span = crate::trace::write_section(section, list.len());
+    #[cfg(feature = "rayon")]
+    if parallel {
+        return write_list_parallel(writer, reporter, section, list);
+    }
+    let total = list.len();
+    for (done, element) in list.iter().enumerate() {
+        if !reporter.report(section, done, total) {
+            return Err(WritePMXErrors::Cancelled);
+        }
+        element.write_to(writer);
+        writer.check_indices(section, done)?;
+    }
+    if reporter.report(section, total, total) {
+        Ok(())
+    } else {
+        Err(WritePMXErrors::Cancelled)
+    }
+}
+
+/// lock ignoring panics of other writes, which leave nothing half updated.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// start of the line [`Writer::set_stamp_generator`] appends.
+const GENERATOR_PREFIX: &str = "Exported by pmx_util ";
+
+/// `YYYY-MM-DD` of now in UTC. `None` without a clock.
+fn today() -> Option<String> {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    if let Ok(now) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        return Some(iso_date(now.as_secs()));
+    }
+    None
+}
+
+fn generator_line(date: Option<&str>) -> String {
+    let version = env!("CARGO_PKG_VERSION");
+    match date {
+        Some(date) => format!("{}{} on {}", GENERATOR_PREFIX, version, date),
+        None => format!("{}{}", GENERATOR_PREFIX, version),
+    }
+}
+
+/// `comment` with lines stamped before replaced by `line` at the end.
+///
+/// lines are separated by `\r\n` if `comment` has one, otherwise by `\n`.
+fn stamp_generator(comment: &str, line: &str) -> String {
+    let newline = if comment.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut stamped = comment
+        .split(newline)
+        .filter(|l| !l.starts_with(GENERATOR_PREFIX))
+        .collect::<Vec<_>>()
+        .join(newline);
+    if !stamped.is_empty() {
+        stamped.push_str(newline);
+    }
+    stamped.push_str(line);
+    stamped
+}
+
+/// `YYYY-MM-DD` of seconds since unix epoch in UTC.
+fn iso_date(unix_secs: u64) -> String {
+    // days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = unix_secs / 86400 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// elements encoded by a task of [`write_list_parallel`].
+#[cfg(feature = "rayon")]
+const PARALLEL_CHUNK: usize = 4096;
+
+/// encode chunks of `list` in parallel then write them in order.
+#[cfg(feature = "rayon")]
+fn write_list_parallel<W: Write, T: WriteElement + Sync>(
+    writer: &mut BinaryWriter<W>,
+    reporter: &mut Reporter,
+    section: SectionKind,
+    list: &[T],
+) -> Result<(), WritePMXErrors> {
+    use rayon::prelude::*;
+    let total = list.len();
+    if !reporter.report(section, 0, total) {
+        return Err(WritePMXErrors::Cancelled);
+    }
+    let header = writer.header;
+    let text_limit = writer.text_limit;
+    let chunks = list
+        .par_chunks(PARALLEL_CHUNK)
+        .enumerate()
+        .map(|(chunk_index, chunk)| {
+            let mut chunk_writer = BinaryWriter::with_capacity(vec![], header, 8 * 1024);
+            chunk_writer.text_limit = text_limit;
+            for (index, element) in chunk.iter().enumerate() {
+                element.write_to(&mut chunk_writer);
+                chunk_writer.check_indices(section, chunk_index * PARALLEL_CHUNK + index)?;
+            }
+            chunk_writer
+                .inner
+                .into_inner()
+                .map_err(|err| WritePMXErrors::IoError(err.into_error()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    chunks.iter().for_each(|chunk| writer.write_vec(chunk));
+    if reporter.report(section, total, total) {
+        Ok(())
+    } else {
+        Err(WritePMXErrors::Cancelled)
+    }
+}
+
+/// `len` as count of `section` written before its elements.
+pub(crate) fn section_count(section: SectionKind, len: usize) -> Result<i32, WritePMXErrors> {
+    i32::try_from(len).map_err(|_| WritePMXErrors::TooBig {
+        section,
+        element_name: None,
+    })
+}
+
+/// element having lists written with `i32` count.
+pub(crate) trait Entries {
+    fn name(&self) -> &str;
+    /// length of the longest list.
+    fn max_entries(&self) -> usize;
+}
+
+impl Entries for Bone {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    /// IK links.
+    fn max_entries(&self) -> usize {
+        self.ik_info.as_ref().map_or(0, |ik| ik.ik_links.len())
+    }
 }
 
-fn optimal_data_type_vertex(len: usize) -> VertexIndexKinds {
+impl Entries for Morph {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn max_entries(&self) -> usize {
+        self.morph_data.offset_count()
+    }
+}
+
+impl Entries for Frame {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn max_entries(&self) -> usize {
+        self.inners.len()
+    }
+}
+
+impl Entries for SoftBody {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    /// anchor rigid bodies or pinned vertices.
+    fn max_entries(&self) -> usize {
+        self.anchor_rigid.len().max(self.pin_vertex.len())
+    }
+}
+
+/// `WritePMXErrors::TooBig` naming the first element with a list over `i32::MAX`.
+pub(crate) fn check_entries<T: Entries>(
+    section: SectionKind,
+    elements: &[T],
+) -> Result<(), WritePMXErrors> {
+    match elements
+        .iter()
+        .find(|element| i32::try_from(element.max_entries()).is_err())
+    {
+        Some(element) => Err(WritePMXErrors::TooBig {
+            section,
+            element_name: Some(element.name().to_owned()),
+        }),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn optimal_data_type_vertex(len: usize) -> VertexIndexKinds {
     if u8::try_from(len).is_ok() {
         VertexIndexKinds::U8 //8 bit
     } else if u16::try_from(len).is_ok() {
@@ -298,7 +1343,7 @@ fn optimal_data_type_vertex(len: usize) -> VertexIndexKinds {
     }
 }
 
-fn optimal_data_type(len: usize) -> IndexKinds {
+pub(crate) fn optimal_data_type(len: usize) -> IndexKinds {
     if i8::try_from(len).is_ok() {
         IndexKinds::I8 //8 bit
     } else if i16::try_from(len).is_ok() {
@@ -308,11 +1353,226 @@ fn optimal_data_type(len: usize) -> IndexKinds {
     }
 }
 
+/// `C:\`, `C:/`, `\\server` or leading separator.
+fn is_absolute_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    matches!(bytes.first(), Some(b'/') | Some(b'\\'))
+        || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+}
+
+/// path separator written in texture paths.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum PathSeparator {
+    /// `\` used by most models.
+    #[default]
+    Backslash,
+    /// `/`
+    Slash,
+}
+
+/// texture path rejected by [`Writer::add_texture_path`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TexturePathError {
+    /// PMX texture paths are relative to the model.
+    Absolute(String),
+    /// path has no file name.
+    Empty,
+    NotUnicode,
+    ControlCharacter(String),
+    /// file is missing under the base directory.
+    NotFound(std::path::PathBuf),
+    TooMany,
+}
+
+impl std::fmt::Display for TexturePathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TexturePathError::Absolute(path) => {
+                write!(f, "texture path {:?} is not relative to the model", path)
+            }
+            TexturePathError::Empty => write!(f, "texture path has no file name"),
+            TexturePathError::NotUnicode => write!(f, "texture path is not valid unicode"),
+            TexturePathError::ControlCharacter(path) => {
+                write!(f, "texture path {:?} contains control characters", path)
+            }
+            TexturePathError::NotFound(path) => {
+                write!(f, "texture {} does not exist", path.display())
+            }
+            TexturePathError::TooMany => write!(f, "too many textures"),
+        }
+    }
+}
+
+impl std::error::Error for TexturePathError {}
+
+/// Problems found by [`Writer::validate`] `MMD` can still load,
+/// or data dropped on write reported by [`Writer::take_warnings`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum WriteWarning {
+    /// special frame named this is missing. see [`Writer::ensure_default_frames`].
+    MissingSpecialFrame(&'static str),
+    /// special frame `name` is at `index` instead of `expected`.
+    MisplacedSpecialFrame {
+        name: &'static str,
+        index: usize,
+        expected: usize,
+    },
+    /// text of `len` characters is cut to the limit. see [`Writer::set_truncate_long_text`].
+    TextTruncated { field: String, len: usize },
+    /// additional uv `channel` (0 based) of `vertex` is after the declared count and not written.
+    /// see [`WriteOptions::strict`].
+    AdditionalUvDropped { vertex: usize, channel: u8 },
+}
+
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum WritePMXErrors {
     NoModelInfo,
     IoError(std::io::Error),
-    TooBig,
+    /// count of `section`, or of a list in its element named `element_name`,
+    /// doesn't fit in `i32`.
+    TooBig {
+        section: SectionKind,
+        element_name: Option<String>,
+    },
+    /// progress callback returned `ControlFlow::Break`
+    Cancelled,
+    /// vertices have non zero additional uvs in channels after declared count.
+    AdditionalUvTruncated {
+        declared: u8,
+        used: u8,
+    },
+    /// additional uv count is over 4, the most PMX can hold.
+    TooManyAdditionalUv(u8),
+    /// iterator given to [`stream_writer`](crate::stream_writer) yielded other than its length.
+    SectionCountMismatch {
+        section: SectionKind,
+        declared: usize,
+        written: usize,
+    },
+    /// PMX 2.0 was requested by [`Writer::set_version`] but 2.1 only elements were added.
+    RequiresV21,
+    /// sum of `num_face_vertices` of materials differs from 3 times of face count.
+    FaceCountMismatch {
+        material_vertices: i64,
+        face_vertices: i64,
+    },
+    /// length of flat index buffer given to [`Writer::add_faces_flat`] is not a multiple of 3.
+    IncompleteFace {
+        len: usize,
+    },
+    /// index sizes differ from the file data kept by
+    /// [`ParseOptions::preserve_unknown`](crate::reader::ParseOptions::preserve_unknown)
+    /// came from. indices inside the kept data would be read wrong.
+    UnknownDataIndexSizes,
+
+    /// text of `len` characters is over [`Writer::set_text_limit`] or `i32::MAX` bytes.
+    /// `field` is like `model_info.comment`, `textures[2]` or `bones[3].name`.
+    TextTooLong {
+        field: String,
+        len: usize,
+    },
+    /// `index` in `element` of `section` doesn't fit the index size of the header.
+    /// `-1` for none is only valid for signed indices, i.e. other than 8 and 16 bit vertex
+    /// indices. other values need to be in range of the size.
+    IndexOutOfRange {
+        section: SectionKind,
+        element: usize,
+        index: i64,
+    },
+    /// `MMD` mishandles text of `version` in `encode`. see [`Writer::set_mmd_compat`].
+    MmdIncompatibleEncoding {
+        version: PMXVersion,
+        encode: Encode,
+    },
+    /// raw records of `section` were read with other index sizes or additional uv count
+    /// than the header being written.
+    RawSectionMismatch {
+        section: SectionKind,
+    },
+}
+
+impl std::fmt::Display for WritePMXErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WritePMXErrors::NoModelInfo => write!(f, "model info is not set"),
+            WritePMXErrors::IoError(err) => write!(f, "failed to write: {}", err),
+            WritePMXErrors::TooBig {
+                section,
+                element_name: None,
+            } => write!(f, "too many elements in {:?}", section),
+            WritePMXErrors::TooBig {
+                section,
+                element_name: Some(name),
+            } => write!(f, "too many entries in {:?} {:?}", section, name),
+            WritePMXErrors::Cancelled => write!(f, "cancelled by progress callback"),
+            WritePMXErrors::AdditionalUvTruncated { declared, used } => write!(
+                f,
+                "{} additional uvs are declared but vertices use {}",
+                declared, used
+            ),
+            WritePMXErrors::TooManyAdditionalUv(count) => {
+                write!(f, "{} additional uvs are declared but 4 is the most", count)
+            }
+            WritePMXErrors::SectionCountMismatch {
+                section,
+                declared,
+                written,
+            } => write!(
+                f,
+                "{:?} declared {} elements but {} were written",
+                section, declared, written
+            ),
+            WritePMXErrors::RequiresV21 => write!(f, "PMX 2.0 can't hold 2.1 only elements"),
+            WritePMXErrors::FaceCountMismatch {
+                material_vertices,
+                face_vertices,
+            } => write!(
+                f,
+                "materials draw {} face vertices but faces have {}",
+                material_vertices, face_vertices
+            ),
+            WritePMXErrors::IndexOutOfRange {
+                section,
+                element,
+                index,
+            } => write!(
+                f,
+                "index {} in {:?} {} doesn't fit the index size",
+                index, section, element
+            ),
+            WritePMXErrors::IncompleteFace { len } => {
+                write!(f, "{} face indices are not a multiple of 3", len)
+            }
+            WritePMXErrors::UnknownDataIndexSizes => write!(
+                f,
+                "index sizes changed from the file unknown data was kept from"
+            ),
+
+            WritePMXErrors::TextTooLong { field, len } => {
+                write!(f, "{} is too long ({} characters)", field, len)
+            }
+            WritePMXErrors::MmdIncompatibleEncoding { version, encode } => write!(
+                f,
+                "MMD misreads {:?} text in PMX {:?}: use UTF-16LE for PMX 2.0 \
+                 (UTF-16LE works for 2.0 and 2.1, UTF-8 only for 2.1) or disable MMD compatibility",
+                encode, version
+            ),
+            WritePMXErrors::RawSectionMismatch { section } => {
+                write!(f, "raw {:?} were read with another header layout", section)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WritePMXErrors {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WritePMXErrors::IoError(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 impl From<std::io::Error> for WritePMXErrors {
@@ -320,8 +1580,1056 @@ impl From<std::io::Error> for WritePMXErrors {
         Self::IoError(err)
     }
 }
-impl From<TryFromIntError> for WritePMXErrors {
-    fn from(_: TryFromIntError) -> Self {
-        Self::TooBig
+
+#[cfg(test)]
+mod test {
+    use crate::frames::{generate_default, EXPRESSION_FRAME_NAME, ROOT_FRAME_NAME};
+    use crate::progress::SectionKind;
+    use crate::types::{Face, FrameInner, Material, Morph, RigidForm, RigidShape, VertexBuffers};
+    use crate::writer::{
+        check_entries, Entries, PathSeparator, TexturePathError, WriteOptions, WritePMXErrors,
+        WriteWarning, Writer,
+    };
+
+    fn faces(first: u32, count: u32) -> Vec<Face> {
+        (first..first + count)
+            .map(|i| Face {
+                vertices: [i, i + 1, i + 2],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn add_mesh() {
+        let mut writer = Writer::begin_writer(true);
+        writer.set_model_info(&Default::default());
+        writer.add_faces(&faces(0, 1));
+        writer.add_materials(&[Material::builder("raw").num_face_vertices(3).build()]);
+        let body = writer
+            .add_mesh(Material::builder("body").build(), &faces(10, 2))
+            .unwrap();
+        let hair = writer
+            .add_mesh(Material::builder("hair").build(), &faces(20, 3))
+            .unwrap();
+        assert_eq!((body, hair), (1, 2));
+        writer.validate().unwrap();
+
+        let model = crate::test_support::read(&writer.write_to_vec().unwrap());
+        let counts = model
+            .materials
+            .iter()
+            .map(|material| (material.name.as_str(), material.num_face_vertices))
+            .collect::<Vec<_>>();
+        assert_eq!(counts, [("raw", 3), ("body", 6), ("hair", 9)]);
+        let first = model
+            .faces
+            .iter()
+            .map(|face| face.vertices[0])
+            .collect::<Vec<_>>();
+        assert_eq!(first, [0, 10, 11, 20, 21, 22]);
+    }
+
+    #[test]
+    fn add_faces_flat() {
+        let model = crate::test_support::cube();
+        let flat = model
+            .faces
+            .iter()
+            .flat_map(|face| face.vertices.iter().copied())
+            .collect::<Vec<_>>();
+        let mut writer = Writer::from_model(&model);
+        writer.faces.clear();
+        assert!(matches!(
+            writer.add_faces_flat(&flat[..4]),
+            Err(WritePMXErrors::IncompleteFace { len: 4 })
+        ));
+        assert!(writer.faces.is_empty());
+        writer.add_faces_flat(&flat).unwrap();
+        assert_eq!(
+            writer.write_to_vec().unwrap(),
+            crate::test_support::write(&model)
+        );
+    }
+
+    #[test]
+    fn too_many_entries() {
+        /// morph claiming `entries` offsets without allocating them.
+        struct Mocked {
+            morph: Morph,
+            entries: Option<usize>,
+        }
+        impl Entries for Mocked {
+            fn name(&self) -> &str {
+                self.morph.name()
+            }
+            fn max_entries(&self) -> usize {
+                self.entries.unwrap_or_else(|| self.morph.max_entries())
+            }
+        }
+        let model = crate::test_support::cube();
+        let mut morphs = model
+            .morphs
+            .iter()
+            .map(|morph| Mocked {
+                morph: morph.clone(),
+                entries: None,
+            })
+            .collect::<Vec<_>>();
+        check_entries(SectionKind::Morphs, &morphs).unwrap();
+        morphs[3].entries = Some(i32::MAX as usize);
+        check_entries(SectionKind::Morphs, &morphs).unwrap();
+        morphs[3].entries = Some(i32::MAX as usize + 1);
+        let err = check_entries(SectionKind::Morphs, &morphs).unwrap_err();
+        match &err {
+            WritePMXErrors::TooBig {
+                section: SectionKind::Morphs,
+                element_name: Some(name),
+            } => assert_eq!(*name, model.morphs[3].name),
+            _ => panic!("{:?}", err),
+        }
+        assert_eq!(
+            err.to_string(),
+            format!("too many entries in Morphs {:?}", model.morphs[3].name)
+        );
+        assert_eq!(
+            WritePMXErrors::TooBig {
+                section: SectionKind::Vertices,
+                element_name: None
+            }
+            .to_string(),
+            "too many elements in Vertices"
+        );
+    }
+
+    #[test]
+    fn add_vertex_buffers() {
+        let mut model = crate::test_support::cube();
+        model.additional_uv = 1;
+        model.vertices[3].add_uv[0] = [0.5, 0.25, 0.0, 1.0];
+        let buffers = VertexBuffers::from_vertices(&model.vertices, model.additional_uv);
+        let mut writer = Writer::from_model(&model);
+        writer.vertices.clear();
+        writer.add_vertex_buffers(&buffers);
+        assert_eq!(
+            writer.write_to_vec().unwrap(),
+            crate::test_support::write(&model)
+        );
+    }
+
+    #[test]
+    fn texture_path() {
+        let mut writer = Writer::begin_writer(true);
+        assert_eq!(writer.add_texture_path("tex/body.png").unwrap(), 0);
+        assert_eq!(writer.add_texture_path(r"tex\face.png").unwrap(), 1);
+        assert_eq!(writer.add_texture_path(r".\tex//body.png").unwrap(), 0);
+        assert_eq!(writer.add_texture_path("../shared/toon.bmp").unwrap(), 2);
+        writer.set_path_separator(PathSeparator::Slash);
+        assert_eq!(writer.add_texture_path(r"sph\hair.spa").unwrap(), 3);
+        assert_eq!(
+            writer.textures,
+            [
+                r"tex\body.png",
+                r"tex\face.png",
+                r"..\shared\toon.bmp",
+                "sph/hair.spa"
+            ]
+        );
+
+        for absolute in [
+            r"C:\Users\me\tex.png",
+            "d:/models/tex.png",
+            r"\\server\share\tex.png",
+            "/home/me/tex.png",
+        ] {
+            assert!(
+                matches!(
+                    writer.add_texture_path(absolute),
+                    Err(TexturePathError::Absolute(_))
+                ),
+                "{}",
+                absolute
+            );
+        }
+        assert!(matches!(
+            writer.add_texture_path("tex/"),
+            Err(TexturePathError::Empty)
+        ));
+        assert!(matches!(
+            writer.add_texture_path("tex\n.png"),
+            Err(TexturePathError::ControlCharacter(_))
+        ));
+        assert_eq!(writer.textures.len(), 4);
+    }
+
+    #[cfg(feature = "std-fs")]
+    #[test]
+    fn texture_path_base_dir() {
+        let dir = std::env::temp_dir().join(format!("pmxutil-texture-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("tex")).unwrap();
+        std::fs::write(dir.join("tex").join("body.png"), b"").unwrap();
+        let mut writer = Writer::begin_writer(true);
+        writer.set_base_dir(&dir);
+        let exists = writer.add_texture_path(r"tex\body.png");
+        let missing = writer.add_texture_path("tex/face.png");
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(exists.unwrap(), 0);
+        assert!(matches!(missing, Err(TexturePathError::NotFound(_))));
+    }
+
+    #[test]
+    fn add_mesh_after_unassigned_faces() {
+        let mut writer = Writer::begin_writer(true);
+        writer.add_faces(&faces(0, 2));
+        assert!(matches!(
+            writer.validate(),
+            Err(WritePMXErrors::FaceCountMismatch {
+                material_vertices: 0,
+                face_vertices: 6
+            })
+        ));
+        assert!(writer
+            .add_mesh(Material::builder("body").build(), &faces(10, 1))
+            .is_err());
+        assert_eq!(writer.faces.len(), 2);
+        assert!(writer.materials.is_empty());
+    }
+
+    /// problems of other sections don't stop adding meshes.
+    #[test]
+    fn add_mesh_checks_faces_only() {
+        let mut writer = Writer::begin_writer(false);
+        writer.add_vertices(&[crate::types::Vertex {
+            add_uv: [[1.0; 4]; 4],
+            ..crate::test_support::cube().vertices[0].clone()
+        }]);
+        assert!(writer.validate().is_err());
+        assert!(writer
+            .add_mesh(Material::builder("body").build(), &faces(0, 1))
+            .is_ok());
+    }
+
+    #[test]
+    fn finalize_material_counts() {
+        let mut writer = Writer::begin_writer(true);
+        writer.add_materials(&[
+            Material::builder("body").num_face_vertices(3).build(),
+            Material::builder("hair").build(),
+        ]);
+        writer.add_faces(&faces(0, 4));
+        writer.finalize_material_counts().unwrap();
+        assert_eq!(writer.materials[1].num_face_vertices, 9);
+        writer.validate().unwrap();
+
+        writer.add_faces(&faces(0, 1));
+        assert!(matches!(
+            writer.finalize_material_counts(),
+            Err(WritePMXErrors::FaceCountMismatch {
+                material_vertices: 12,
+                face_vertices: 15
+            })
+        ));
+    }
+
+    #[test]
+    fn canonical_output() {
+        let mut stale = crate::test_support::cube();
+        let mut clean = crate::test_support::cube();
+        for model in [&mut stale, &mut clean] {
+            for material in &mut model.materials {
+                material.set_edge(None);
+            }
+        }
+        for vertex in &mut stale.vertices {
+            vertex.edge_mag = 0.7;
+        }
+        stale.rigid_bodies[0].form = RigidForm::Sphere;
+        stale.rigid_bodies[0].size = [0.5, 3.0, 4.0];
+        clean.rigid_bodies[0].set_shape(RigidShape::Sphere { radius: 0.5 });
+        for vertex in &mut clean.vertices {
+            vertex.edge_mag = 0.0;
+        }
+        let write = |model: &crate::types::Model, canonical: bool| {
+            let mut writer = Writer::from_model(model);
+            if canonical {
+                writer.canonicalize();
+            }
+            writer.write_to_vec().unwrap()
+        };
+        assert_ne!(write(&stale, false), write(&clean, false));
+        let bytes = write(&stale, true);
+        assert_eq!(bytes, write(&clean, true));
+        assert_eq!(bytes, write(&clean, false));
+        let read = crate::test_support::read(&bytes);
+        assert_eq!(read.rigid_bodies[0].size, [0.5, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn default_frames() {
+        let model = crate::test_support::cube();
+        let mut writer = Writer::begin_writer(true);
+        writer.add_bones(&model.bones);
+        writer.add_morphs(&model.morphs);
+        assert_eq!(
+            writer.validate().unwrap(),
+            [
+                WriteWarning::MissingSpecialFrame(ROOT_FRAME_NAME),
+                WriteWarning::MissingSpecialFrame(EXPRESSION_FRAME_NAME)
+            ]
+        );
+        writer.ensure_default_frames();
+        assert!(writer.validate().unwrap().is_empty());
+        assert_eq!(
+            writer.frames,
+            generate_default(&model.bones, &model.morphs)[..2]
+        );
+
+        // existing special frames are moved to the top
+        let mut writer = Writer::begin_writer(true);
+        writer.add_frames(&model.frames[1..]);
+        assert_eq!(
+            writer.validate().unwrap(),
+            [
+                WriteWarning::MissingSpecialFrame(ROOT_FRAME_NAME),
+                WriteWarning::MisplacedSpecialFrame {
+                    name: EXPRESSION_FRAME_NAME,
+                    index: 0,
+                    expected: 1
+                }
+            ]
+        );
+        writer.ensure_default_frames();
+        assert!(writer.validate().unwrap().is_empty());
+        assert_eq!(writer.frames[0].inners, []);
+        assert_eq!(writer.frames[1..], model.frames[1..]);
+        assert_eq!(writer.frames[1].inners[1], FrameInner::Morph(8));
+    }
+
+    #[test]
+    fn infer_additional_uv() {
+        let mut vertices = crate::test_support::cube().vertices[..2].to_vec();
+        for vertex in &mut vertices {
+            vertex.add_uv = [[0.0; 4]; 4];
+        }
+        vertices[1].add_uv[2] = [0.0, 0.5, 0.0, 0.0];
+        let mut writer = Writer::begin_writer(false);
+        writer.set_mmd_compat(false);
+        writer.set_model_info(&Default::default());
+        writer.add_vertices(&vertices);
+        assert!(matches!(
+            writer.validate(),
+            Err(WritePMXErrors::AdditionalUvTruncated {
+                declared: 0,
+                used: 3
+            })
+        ));
+        writer.infer_additional_uv();
+        let model = crate::test_support::read(&writer.write_to_vec().unwrap());
+        assert_eq!(model.additional_uv, 3);
+        assert_eq!(model.vertices, vertices);
+
+        let mut writer = Writer::begin_writer(false);
+        writer.set_model_info(&Default::default());
+        writer.set_additional_uv(2).unwrap();
+        writer.add_vertices(&vertices);
+        assert!(matches!(
+            writer.write_to_vec(),
+            Err(WritePMXErrors::AdditionalUvTruncated {
+                declared: 2,
+                used: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn drop_undeclared_additional_uv() {
+        // vertices merged from a model with more additional uvs than declared.
+        let mut model = crate::test_support::cube();
+        model.morphs.truncate(2);
+        model.frames[1].inners.truncate(1);
+        model.additional_uv = 1;
+        let mut merged = model.vertices[..2].to_vec();
+        merged[1].add_uv[1] = [0.5, 0.0, 0.0, 0.0];
+        merged[1].add_uv[3] = [0.0, 0.0, 0.0, 0.25];
+        let mut writer = Writer::from_model(&model);
+        writer.add_vertices(&merged);
+        let lenient = WriteOptions {
+            strict: false,
+            ..WriteOptions::default()
+        };
+        assert!(matches!(
+            writer.write_to_vec_with(&WriteOptions::default()),
+            Err(WritePMXErrors::AdditionalUvTruncated {
+                declared: 1,
+                used: 4
+            })
+        ));
+        assert!(writer.take_warnings().is_empty());
+
+        let buf = writer.write_to_vec_with(&lenient).unwrap();
+        assert_eq!(
+            writer.take_warnings(),
+            [
+                WriteWarning::AdditionalUvDropped {
+                    vertex: 9,
+                    channel: 1
+                },
+                WriteWarning::AdditionalUvDropped {
+                    vertex: 9,
+                    channel: 3
+                },
+            ]
+        );
+        assert!(writer.take_warnings().is_empty());
+        let read = crate::test_support::read(&buf);
+        assert_eq!(read.additional_uv, 1);
+        assert_eq!(read.vertices[9].add_uv[0], merged[1].add_uv[0]);
+        assert_eq!(read.vertices[9].add_uv[1], [0.0; 4]);
+
+        // nothing to warn once declared.
+        writer.set_additional_uv(4).unwrap();
+        writer.write_to_vec_with(&lenient).unwrap();
+        assert!(writer.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn uv_morph_additional_uv() {
+        let cube = crate::test_support::cube();
+        let mut vertices = cube.vertices.clone();
+        for vertex in &mut vertices {
+            vertex.add_uv = [[0.0; 4]; 4];
+        }
+        let uv3 = cube
+            .morphs
+            .iter()
+            .find(|morph| morph.morph_data.uv_channel() == Some(2))
+            .unwrap();
+        let writer = || {
+            let mut writer = Writer::begin_writer(false);
+            writer.set_model_info(&Default::default());
+            writer.add_vertices(&vertices);
+            writer.add_morphs(std::slice::from_ref(uv3));
+            writer
+        };
+        // declared count is raised on write.
+        let model = crate::test_support::read(&writer().write_to_vec().unwrap());
+        assert_eq!(model.additional_uv, 3);
+        assert_eq!(model.morphs[0], *uv3);
+        let mut writer = writer();
+        writer.infer_additional_uv();
+        assert_eq!(writer.additional_uvs, Some(3));
+    }
+
+    #[test]
+    fn error_display() {
+        use std::error::Error;
+        let err = WritePMXErrors::from(std::io::Error::other("disk full"));
+        assert_eq!(err.to_string(), "failed to write: disk full");
+        assert_eq!(err.source().unwrap().to_string(), "disk full");
+        let err = WritePMXErrors::FaceCountMismatch {
+            material_vertices: 6,
+            face_vertices: 3,
+        };
+        assert_eq!(
+            err.to_string(),
+            "materials draw 6 face vertices but faces have 3"
+        );
+        assert!(err.source().is_none());
+        // usable as a boxed error
+        let _: Box<dyn Error> = Box::new(WritePMXErrors::NoModelInfo);
+    }
+
+    #[test]
+    fn buffer_capacity() {
+        let model = crate::test_support::cube();
+        let expected = Writer::from_model(&model).write_to_vec().unwrap();
+        for capacity in [0, 1, 7, 1 << 20] {
+            let mut writer = Writer::from_model(&model);
+            writer.set_buffer_capacity(capacity);
+            let mut buf = vec![];
+            writer.write(&mut buf).unwrap();
+            assert_eq!(buf, expected);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_same_bytes() {
+        let mut model = crate::test_support::cube();
+        // several chunks
+        let vertices = model.vertices.clone();
+        model.vertices = vertices.iter().cycle().take(10_000).cloned().collect();
+        let expected = Writer::from_model(&model).write_to_vec().unwrap();
+        let mut writer = Writer::from_model(&model);
+        writer.set_parallel(true);
+        assert_eq!(writer.write_to_vec().unwrap(), expected);
+
+        // long texts are cut in chunks too.
+        model.bones[0].name = "b".repeat(20);
+        let mut writer = Writer::from_model(&model);
+        writer.set_truncate_long_text(true);
+        writer.set_text_limit(Some(16));
+        let expected = writer.write_to_vec_with(&WriteOptions::default()).unwrap();
+        writer.set_parallel(true);
+        assert_eq!(
+            writer.write_to_vec_with(&WriteOptions::default()).unwrap(),
+            expected
+        );
+    }
+
+    /// bone with only `flag` set and the payload of it.
+    fn bone_with_flag(flag: crate::types::BoneFlags) -> crate::types::Bone {
+        use crate::types::{
+            Bone, BoneFlags, BoneIKInfo, ConnectionDisplayMode, IKLink, RotateAndTranslateInherits,
+        };
+        let mut bone = Bone {
+            name: "subject".to_owned(),
+            connection_display_mode: ConnectionDisplayMode::Offset([0.0; 3]),
+            ..Bone::default()
+        };
+        let inherit = (1, 0.5);
+        match flag {
+            BoneFlags::CONNECT_TO_OTHER_BONE => {
+                bone.connection_display_mode = ConnectionDisplayMode::OtherBone(-1)
+            }
+            BoneFlags::ROTATABLE => bone.rotatable_in_viewer = true,
+            BoneFlags::TRANSLATABLE => bone.translatable_in_viewer = true,
+            BoneFlags::IS_VISIBLE => bone.display_bone_in_viewer = true,
+            BoneFlags::ENABLED => bone.controllable_in_viewer = true,
+            BoneFlags::IK => {
+                bone.ik_info = Some(BoneIKInfo {
+                    ik_target_bone_index: 1,
+                    ik_iter_count: 40,
+                    ik_limit_angle: 2.0,
+                    ik_links: vec![IKLink {
+                        ik_bone_index: 1,
+                        angle_limit: Some(([-1.0, 0.0, 0.0], [0.0, 0.0, 0.0])),
+                    }],
+                })
+            }
+            // local inherit has no payload of its own.
+            BoneFlags::INHERIT_LOCAL => bone.inherits.inherit_local = true,
+            BoneFlags::INHERIT_ROTATION => {
+                bone.inherits.rotate_and_translate =
+                    RotateAndTranslateInherits::Rotate(inherit.0, inherit.1)
+            }
+            BoneFlags::INHERIT_TRANSLATION => {
+                bone.inherits.rotate_and_translate =
+                    RotateAndTranslateInherits::Translate(inherit.0, inherit.1)
+            }
+            BoneFlags::FIXED_AXIS => bone.fixed_axis = Some([0.0, 1.0, 0.0]),
+            BoneFlags::LOCAL_COORDINATE => {
+                bone.local_axis = Some(([1.0, 0.0, 0.0], [0.0, 0.0, 1.0]))
+            }
+            BoneFlags::PHYSICS_AFTER_DEFORM => bone.physics_after_deform = true,
+            BoneFlags::EXTERNAL_PARENT_DEFORM => bone.external_parent = Some(7),
+            _ => unreachable!("{:?}", flag),
+        }
+        bone
+    }
+
+    /// write `bones` followed by a bone named "next" and read them back.
+    fn round_trip_bones(bones: Vec<crate::types::Bone>) -> Vec<crate::types::Bone> {
+        use crate::types::{Bone, Model};
+        let mut model = Model {
+            bones,
+            ..Model::default()
+        };
+        model.bones.push(Bone {
+            name: "next".to_owned(),
+            ..Bone::default()
+        });
+        let read = crate::test_support::read(&crate::test_support::write(&model));
+        assert_eq!(read.bones.last().unwrap().name, "next");
+        read.bones
+    }
+
+    #[test]
+    fn bone_flag_round_trip() {
+        use crate::types::BoneFlags;
+        for bit in 0..16 {
+            let bits = 1u16 << bit;
+            let flag = match BoneFlags::from_bits(bits) {
+                Some(flag) => flag,
+                // 0x0040, 0x4000 and 0x8000 are not defined and kept as they are.
+                None => {
+                    let bone = crate::types::Bone {
+                        reserved_flags: bits,
+                        ..Default::default()
+                    };
+                    let read = round_trip_bones(vec![bone.clone()]);
+                    assert_eq!(read[0], bone);
+                    assert_eq!(read[0].flag_bits() & BoneFlags::RESERVED_BITS, bits);
+                    continue;
+                }
+            };
+            let bone = bone_with_flag(flag);
+            assert_eq!(bone.calculate_bone_flag(), flag);
+            let read = round_trip_bones(vec![bone.clone()]);
+            assert_eq!(read[0], bone, "{:?}", flag);
+            assert_eq!(read[0].calculate_bone_flag(), flag);
+        }
+    }
+
+    #[test]
+    fn bone_flag_combinations() {
+        use crate::types::{BoneFlags, RotateAndTranslateInherits};
+        let mut ik_and_external = bone_with_flag(BoneFlags::IK);
+        ik_and_external.external_parent = Some(3);
+        let mut local_both = bone_with_flag(BoneFlags::INHERIT_LOCAL);
+        local_both.inherits.rotate_and_translate = RotateAndTranslateInherits::Both(1, -1.0);
+        let mut all = bone_with_flag(BoneFlags::IK);
+        all.external_parent = Some(3);
+        all.fixed_axis = Some([1.0, 0.0, 0.0]);
+        all.local_axis = Some(([1.0, 0.0, 0.0], [0.0, 0.0, 1.0]));
+        all.inherits = local_both.inherits;
+        let bones = vec![ik_and_external, local_both, all];
+        assert_eq!(round_trip_bones(bones.clone())[..3], bones[..]);
+        assert_eq!(
+            bones[2].calculate_bone_flag(),
+            BoneFlags::IK
+                | BoneFlags::EXTERNAL_PARENT_DEFORM
+                | BoneFlags::FIXED_AXIS
+                | BoneFlags::LOCAL_COORDINATE
+                | BoneFlags::INHERIT_LOCAL
+                | BoneFlags::INHERIT_ROTATION
+                | BoneFlags::INHERIT_TRANSLATION
+        );
+    }
+
+    #[test]
+    fn undefined_bone_flags_have_no_payload() {
+        use crate::progress::SectionKind;
+        use crate::reader::ModelInfoStage;
+        use crate::types::{BoneFlags, Model};
+        let model = Model {
+            bones: round_trip_bones(vec![bone_with_flag(BoneFlags::FIXED_AXIS)]),
+            ..Model::default()
+        };
+        let mut bytes = crate::test_support::write(&model);
+        let (_, offsets) = ModelInfoStage::from_reader(bytes.as_slice())
+            .unwrap()
+            .read_model_with_offsets();
+        // count, name, english name, position, 1 byte parent index and deform depth.
+        let flags = offsets.get(SectionKind::Bones).unwrap().start as usize
+            + 4
+            + (4 + 2 * "subject".len())
+            + 4
+            + 12
+            + 1
+            + 4;
+        assert_eq!(
+            bytes[flags..flags + 2],
+            BoneFlags::FIXED_AXIS.bits().to_le_bytes()
+        );
+        for undefined in [0x0040u16, 0x4000, 0x8000, 0xC040] {
+            let patched = BoneFlags::FIXED_AXIS.bits() | undefined;
+            bytes[flags..flags + 2].copy_from_slice(&patched.to_le_bytes());
+            // undefined bits are kept and the rest is read as before.
+            let read = crate::test_support::read(&bytes);
+            assert_eq!(read.bones[0].reserved_flags, undefined);
+            let mut bones = read.bones.clone();
+            bones[0].reserved_flags = 0;
+            assert_eq!(bones, model.bones);
+            assert_eq!(crate::test_support::write(&read), bytes);
+        }
+    }
+
+    #[test]
+    fn material_flag_bits_round_trip() {
+        use crate::types::MaterialFlags;
+        // every bit of material flags is named so nothing is dropped on read.
+        let mut model = crate::test_support::cube();
+        for bits in [0xFFu8, 0xC0, 0x01] {
+            model.materials[0].draw_mode = MaterialFlags::from_bits(bits).unwrap();
+            let bytes = crate::test_support::write(&model);
+            let read = crate::test_support::read(&bytes);
+            assert_eq!(read.materials[0].draw_mode.bits(), bits);
+            assert_eq!(crate::test_support::write(&read), bytes);
+        }
+    }
+
+    #[test]
+    fn joint_round_trip_bytes() {
+        use crate::progress::SectionKind;
+        use crate::reader::ModelInfoStage;
+        use crate::types::Model;
+        let cube = crate::test_support::cube();
+        for joint in &cube.joints {
+            let model = Model {
+                joints: vec![joint.clone()],
+                ..cube.clone()
+            };
+            let bytes = crate::test_support::write(&model);
+            let (read, offsets) = ModelInfoStage::from_reader(bytes.as_slice())
+                .unwrap()
+                .read_model_with_offsets();
+            assert_eq!(read.joints[0], *joint);
+            let again = crate::test_support::write(&read);
+            let section = offsets.get(SectionKind::Joints).unwrap();
+            let range = section.start as usize..(section.start + section.len) as usize;
+            assert_eq!(bytes[range.clone()], again[range], "{}", joint);
+            assert_eq!(bytes, again);
+        }
+    }
+
+    #[test]
+    fn long_text() {
+        let model = crate::test_support::cube();
+        let mut writer = Writer::from_model(&model);
+        writer.model_info.as_mut().unwrap().comment = "あ".repeat(3_000_000);
+        writer.set_text_limit(Some(1_000_000));
+        match writer.validate() {
+            Err(WritePMXErrors::TextTooLong { field, len }) => {
+                assert_eq!(field, "model_info.comment");
+                assert_eq!(len, 3_000_000);
+            }
+            other => panic!("{:?}", other),
+        }
+        writer.textures[0] = "t".repeat(20);
+        writer.set_truncate_long_text(true);
+        writer.set_text_limit(Some(16));
+        let warnings = writer.validate().unwrap();
+        let truncated = |field: &str, len| WriteWarning::TextTruncated {
+            field: field.to_owned(),
+            len,
+        };
+        assert!(warnings.contains(&truncated("model_info.comment", 3_000_000)));
+        assert!(warnings.contains(&truncated("textures[0]", 20)));
+        let options = WriteOptions::default();
+        let written = crate::test_support::read(&writer.write_to_vec_with(&options).unwrap());
+        assert_eq!(written.model_info.comment, "あ".repeat(16));
+        assert_eq!(written.textures.textures[0], "t".repeat(16));
+        assert_eq!(written.bones, model.bones);
+        // texts are cut in output only.
+        assert_eq!(writer.textures[0], "t".repeat(20));
+
+        let mut writer = Writer::from_model(&model);
+        writer.bones[1].name = "ボーン".repeat(10);
+        writer.set_text_limit(Some(15));
+        assert!(matches!(
+            writer.write_to_vec(),
+            Err(WritePMXErrors::TextTooLong { field, len: 30 }) if field == "bones[1].name"
+        ));
+        assert_eq!(crate::types::Encode::Utf16Le.text_len("ボーン"), 6);
+        assert_eq!(crate::types::Encode::UTF8.text_len("ボーン"), 9);
+    }
+
+    #[test]
+    fn nil_indices_at_each_size() {
+        use crate::progress::SectionKind;
+        use crate::types::{IndexKinds, ToonMode};
+        let mut model = crate::test_support::cube();
+        model.materials[0].texture_index = -1;
+        model.materials[0].sphere_mode = None;
+        model.materials[0].toon_mode = ToonMode::Separate(-1);
+        let textures = model.textures.textures.clone();
+        for (count, size) in [
+            (0, IndexKinds::I8),
+            (200, IndexKinds::I16),
+            (40000, IndexKinds::I32),
+        ] {
+            model.textures.textures = textures.clone();
+            model
+                .textures
+                .textures
+                .extend((0..count).map(|i| format!("{}.png", i)));
+            let bytes = crate::test_support::write(&model);
+            let header = crate::types::Header::peek(&bytes).unwrap();
+            assert_eq!(u8::from(header.s_texture_index), u8::from(size));
+            assert_eq!(crate::test_support::read(&bytes).materials, model.materials);
+        }
+
+        let mut dangling = model.clone();
+        dangling.materials[1].texture_index = 300;
+        dangling.textures.textures.truncate(2);
+        assert!(matches!(
+            Writer::from_model(&dangling).write_to_vec(),
+            Err(WritePMXErrors::IndexOutOfRange {
+                section: SectionKind::Materials,
+                element: 1,
+                index: 300
+            })
+        ));
+        // vertex indices must fit the vertex index size of the header.
+        let mut far_vertex = dangling;
+        far_vertex.materials[1].texture_index = 0;
+        far_vertex.faces[3].vertices[1] = u32::MAX;
+        assert!(matches!(
+            Writer::from_model(&far_vertex).write_to_vec(),
+            Err(WritePMXErrors::IndexOutOfRange {
+                section: SectionKind::Faces,
+                element: 3,
+                index: 4_294_967_295
+            })
+        ));
+    }
+
+    #[test]
+    fn write_variants() {
+        use crate::types::{Encode, IndexKinds, IndexKindsConfig, PMXVersion, VertexIndexKinds};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        let model = crate::test_support::cube();
+        let mut writer = Writer::from_model(&model);
+        writer.set_mmd_compat(false);
+        let reports = Arc::new(AtomicUsize::new(0));
+        let counter = reports.clone();
+        writer.set_progress(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            std::ops::ControlFlow::Continue(())
+        });
+
+        let wide = IndexKindsConfig {
+            vertex: VertexIndexKinds::I32,
+            texture: IndexKinds::I32,
+            material: IndexKinds::I32,
+            bone: IndexKinds::I32,
+            morph: IndexKinds::I32,
+            rigid_body: IndexKinds::I32,
+        };
+        let utf8 = writer
+            .write_to_vec_with(
+                &WriteOptions::default()
+                    .encode(Encode::UTF8)
+                    .version(PMXVersion::V21)
+                    .index_kinds(wide),
+            )
+            .unwrap();
+        let once = reports.load(Ordering::Relaxed);
+        assert!(once > 0);
+        let default = writer.write_to_vec_with(&WriteOptions::default()).unwrap();
+        assert_eq!(reports.load(Ordering::Relaxed), 2 * once);
+        assert_eq!(default, crate::test_support::write(&model));
+
+        let header = crate::types::Header::peek(&utf8).unwrap();
+        assert_eq!(header.encode, Encode::UTF8);
+        assert_eq!(header.index_kinds(), wide);
+        let read = crate::test_support::read(&utf8);
+        assert_eq!(
+            read,
+            crate::types::Model {
+                encode: Encode::UTF8,
+                ..crate::test_support::read(&default)
+            }
+        );
+
+        // too small index kinds fail without breaking the writer.
+        let narrow = IndexKindsConfig {
+            vertex: VertexIndexKinds::U8,
+            texture: IndexKinds::I8,
+            material: IndexKinds::I8,
+            bone: IndexKinds::I8,
+            morph: IndexKinds::I8,
+            rigid_body: IndexKinds::I8,
+        };
+        writer.add_vertices(&vec![model.vertices[0].clone(); 300]);
+        writer.add_faces(&[Face::from([0, 1, 299])]);
+        assert!(matches!(
+            writer.write_to_vec_with(&WriteOptions {
+                index_kinds: Some(narrow),
+                ..WriteOptions::default()
+            }),
+            Err(WritePMXErrors::IndexOutOfRange {
+                section: SectionKind::Faces,
+                ..
+            })
+        ));
+        assert!(writer.write_to_vec().is_ok());
+    }
+
+    #[test]
+    fn writer_is_send() {
+        fn assert_send<T: Send>() {}
+        fn assert_sync<T: Sync>() {}
+        assert_send::<Writer>();
+        assert_sync::<Writer>();
+        assert_send::<WritePMXErrors>();
+    }
+
+    #[test]
+    fn mmd_incompatible_encoding() {
+        use crate::types::{Encode, PMXVersion};
+        let mut writer = Writer::begin_writer(false);
+        writer.set_model_info(&Default::default());
+        let err = writer.validate().unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("MMD misreads UTF8 text in PMX V20"));
+        assert!(matches!(
+            writer.calculate_header(&WriteOptions::default()),
+            Err(WritePMXErrors::MmdIncompatibleEncoding {
+                version: PMXVersion::V20,
+                encode: Encode::UTF8
+            })
+        ));
+        writer.set_mmd_compat(false);
+        let model = crate::test_support::read(&writer.write_to_vec().unwrap());
+        assert_eq!(model.encode, Encode::UTF8);
+        // writers made from models check it too.
+        assert!(matches!(
+            Writer::from_model(&model).write_to_vec(),
+            Err(WritePMXErrors::MmdIncompatibleEncoding { .. })
+        ));
+
+        // PMX 2.1 and UTF-16LE are fine.
+        let mut model = crate::test_support::cube();
+        model.encode = Encode::UTF8;
+        assert!(Writer::from_model(&model).write_to_vec().is_ok());
+        let mut writer = Writer::begin_writer(true);
+        writer.set_model_info(&Default::default());
+        writer.set_version(PMXVersion::V20);
+        assert!(writer.write_to_vec().is_ok());
+    }
+
+    #[test]
+    fn material_flags_require_v21() {
+        use crate::types::{MaterialFlags, PMXVersion};
+        for flag in [
+            MaterialFlags::POINT_DRAW,
+            MaterialFlags::LINE_DRAW,
+            MaterialFlags::VERTEX_COLOR,
+        ] {
+            let mut writer = Writer::begin_writer(true);
+            writer.set_model_info(&Default::default());
+            writer.add_materials(&[Material {
+                draw_mode: flag,
+                ..Material::builder("points").build()
+            }]);
+            let (header, ext_2_1) = writer.calculate_header(&WriteOptions::default()).unwrap();
+            assert!(ext_2_1);
+            assert_eq!(header.version, PMXVersion::V21);
+            let bytes = writer.write_to_vec_with(&WriteOptions::default()).unwrap();
+            let read = crate::test_support::read(&bytes);
+            assert_eq!(read.version, PMXVersion::V21);
+            assert_eq!(read.materials[0].draw_mode, flag);
+
+            writer.set_version(PMXVersion::V20);
+            assert!(matches!(
+                writer.write_to_vec(),
+                Err(WritePMXErrors::RequiresV21)
+            ));
+        }
+        let mut writer = Writer::begin_writer(true);
+        writer.set_model_info(&Default::default());
+        writer.add_materials(&[Material::builder("faces").build()]);
+        assert!(!writer.calculate_header(&WriteOptions::default()).unwrap().1);
+    }
+
+    #[test]
+    fn header_encode_matches_texts() {
+        use crate::types::Encode;
+        fn encoded(encode: Encode, text: &str) -> Vec<u8> {
+            let mut bytes = (encode.text_len(text) as i32).to_le_bytes().to_vec();
+            match encode {
+                Encode::UTF8 => bytes.extend_from_slice(text.as_bytes()),
+                Encode::Utf16Le => bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes)),
+            }
+            bytes
+        }
+        for (encode, byte) in [(Encode::Utf16Le, 0), (Encode::UTF8, 1)] {
+            let mut model = crate::test_support::cube();
+            model.encode = encode;
+            let buf = Writer::from_model(&model).write_to_vec().unwrap();
+            // magic, version and header length come before the encode byte.
+            assert_eq!(buf[9], byte);
+            let texts = [
+                model.model_info.name.as_str(),
+                &model.model_info.comment_en,
+                &model.textures.textures[0],
+                &model.materials[0].name,
+                &model.bones[1].name,
+                &model.morphs[0].name,
+                &model.frames[1].name,
+                &model.rigid_bodies[0].name,
+                &model.joints[0].name,
+                &model.soft_bodies[0].name,
+            ];
+            for text in texts {
+                let expected = encoded(encode, text);
+                assert!(
+                    buf.windows(expected.len()).any(|window| window == expected),
+                    "{} is not written in {:?}",
+                    text,
+                    encode
+                );
+            }
+            assert_eq!(crate::test_support::read(&buf), model);
+        }
+    }
+
+    #[test]
+    fn stamp_generator_once() {
+        use crate::writer::{generator_line, iso_date, stamp_generator};
+        assert_eq!(iso_date(0), "1970-01-01");
+        assert_eq!(iso_date(951_782_400), "2000-02-29");
+        assert_eq!(iso_date(1_792_195_199), "2026-10-16");
+
+        let version = env!("CARGO_PKG_VERSION");
+        let line = generator_line(Some("2026-10-17"));
+        assert_eq!(
+            line,
+            format!("Exported by pmx_util {} on 2026-10-17", version)
+        );
+        let stamped = stamp_generator(
+            "free to use\nExported by pmx_util 0.1.0 on 2020-01-01",
+            &line,
+        );
+        assert_eq!(stamped, format!("free to use\n{}", line));
+        assert_eq!(stamp_generator(&stamped, &line), stamped);
+        // comments of PMXEditor end lines with CRLF.
+        let crlf = stamp_generator("readme\r\nfree to use", &line);
+        assert_eq!(crlf, format!("readme\r\nfree to use\r\n{}", line));
+        assert_eq!(stamp_generator(&crlf, &line), crlf);
+        assert_eq!(stamp_generator("", &line), line);
+        let trailing = stamp_generator("a\n", "Exported by pmx_util 1");
+        assert_eq!(trailing, "a\n\nExported by pmx_util 1");
+        assert_eq!(
+            stamp_generator(&trailing, "Exported by pmx_util 2"),
+            "a\n\nExported by pmx_util 2"
+        );
+        assert_eq!(
+            generator_line(None),
+            format!("Exported by pmx_util {}", version)
+        );
+
+        let mut model = crate::test_support::cube();
+        model.model_info.comment_en =
+            "free to use\nExported by pmx_util 0.1.0 on 2020-01-01".to_owned();
+        let mut writer = Writer::from_model(&model);
+        writer.set_mmd_compat(false);
+        let plain = writer.write_to_vec_with(&WriteOptions::default()).unwrap();
+        assert_eq!(crate::test_support::read(&plain), model);
+
+        // the date of the written line depends on when the test runs.
+        let stamped_once = |buf: &[u8]| {
+            let comment = crate::test_support::read(buf).model_info.comment_en;
+            let prefix = format!("free to use\nExported by pmx_util {} on ", version);
+            assert!(comment.starts_with(&prefix), "{}", comment);
+            assert_eq!(comment.lines().count(), 2);
+        };
+        writer.set_stamp_generator(true);
+        let first = writer.write_to_vec_with(&WriteOptions::default()).unwrap();
+        stamped_once(&first);
+        // the writer keeps the comment as given.
+        assert_eq!(writer.model_info.as_ref(), Some(&model.model_info));
+        // saving again or writing the read model again does not add lines.
+        stamped_once(&writer.write_to_vec_with(&WriteOptions::default()).unwrap());
+        let mut rewriter = Writer::from_model(&crate::test_support::read(&first));
+        rewriter.set_mmd_compat(false);
+        rewriter.set_stamp_generator(true);
+        stamped_once(&rewriter.write_to_vec().unwrap());
+    }
+
+    #[test]
+    fn stamp_generator_with_trailing_data() {
+        let mut model = crate::test_support::cube();
+        model.unknown.trailing = b"tail".to_vec();
+        let mut writer = Writer::from_model(&model);
+        writer.set_mmd_compat(false);
+        writer.set_stamp_generator(true);
+        let buf = writer.write_to_vec().unwrap();
+        assert!(buf.ends_with(b"tail"));
+        let read = crate::test_support::read(&buf[..buf.len() - 4]);
+        assert!(read.model_info.comment_en.contains("Exported by pmx_util "));
     }
 }
@@ -0,0 +1,67 @@
+//! count heap allocations made by reader.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use PMXUtil::reader::ModelInfoStage;
+use PMXUtil::types::{Bone, Model};
+use PMXUtil::writer::Writer;
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const BONES: usize = 2000;
+
+/// allocations made while reading `buf`.
+fn allocations(buf: &[u8]) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let model = ModelInfoStage::from_reader(buf).unwrap().read_model();
+    let count = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    assert_eq!(model.bones.len(), BONES);
+    count
+}
+
+#[test]
+fn one_allocation_per_name() {
+    // japanese names and empty english names like most models
+    let bones = (0..BONES)
+        .map(|i| Bone::builder(&format!("物理{}", i)).build())
+        .collect();
+    let model = Model {
+        bones,
+        ..Model::default()
+    };
+    for utf16 in [false, true] {
+        let mut writer = Writer::from(model.clone());
+        writer.set_encode(if utf16 {
+            PMXUtil::types::Encode::Utf16Le
+        } else {
+            PMXUtil::types::Encode::UTF8
+        });
+        // the reader is measured, not compatibility with MMD.
+        writer.set_mmd_compat(false);
+        let buf = writer.write_to_vec().unwrap();
+        let count = allocations(&buf);
+        eprintln!("utf16 {}: {} allocations for {} bones", utf16, count, BONES);
+        // a name each and growth of lists
+        assert!(count < BONES + 100, "{} allocations", count);
+    }
+}
@@ -0,0 +1,157 @@
+//! call `ffi` functions through C ABI like C code does.
+use std::ptr;
+use PMXUtil::ffi::{PmxError, PmxMaterial};
+use PMXUtil::types::{Face, Material, MaterialFlags, ModelInfo, ToonMode, Vertex, VertexWeight};
+use PMXUtil::writer::Writer;
+
+/// opaque as declared in C header.
+#[repr(C)]
+struct PmxModel {
+    _private: [u8; 0],
+}
+
+extern "C" {
+    fn pmxutil_load_from_memory(
+        ptr: *const u8,
+        len: usize,
+        out_handle: *mut *mut PmxModel,
+    ) -> PmxError;
+    fn pmxutil_free(handle: *mut PmxModel);
+    fn pmxutil_vertex_count(handle: *const PmxModel) -> usize;
+    fn pmxutil_face_count(handle: *const PmxModel) -> usize;
+    fn pmxutil_material_count(handle: *const PmxModel) -> usize;
+    fn pmxutil_copy_positions(handle: *const PmxModel, out_ptr: *mut f32, cap: usize) -> usize;
+    fn pmxutil_copy_indices(handle: *const PmxModel, out_ptr: *mut u32, cap: usize) -> usize;
+    fn pmxutil_material_get(
+        handle: *const PmxModel,
+        index: usize,
+        out_struct: *mut PmxMaterial,
+    ) -> PmxError;
+    fn pmxutil_model_name(handle: *const PmxModel, out_len: *mut usize) -> *const u8;
+    fn pmxutil_material_name(
+        handle: *const PmxModel,
+        index: usize,
+        out_len: *mut usize,
+    ) -> *const u8;
+}
+
+fn vertex(position: [f32; 3]) -> Vertex {
+    Vertex {
+        position,
+        norm: [0.0, 1.0, 0.0],
+        uv: [0.0, 0.0],
+        add_uv: [[0.0; 4]; 4],
+        weight_type: VertexWeight::BDEF1(-1),
+        edge_mag: 1.0,
+    }
+}
+
+fn triangle() -> Vec<u8> {
+    let mut writer = Writer::begin_writer(true);
+    writer.set_model_info(&ModelInfo {
+        name: "三角形".to_owned(),
+        ..Default::default()
+    });
+    writer.add_vertices(&[
+        vertex([0.0, 0.0, 0.0]),
+        vertex([1.0, 0.0, 0.0]),
+        vertex([0.0, 1.0, 0.0]),
+    ]);
+    writer.add_faces(&[Face {
+        vertices: [0, 1, 2],
+    }]);
+    writer.add_materials(&[Material {
+        name: "material".to_owned(),
+        english_name: String::new(),
+        diffuse: [1.0, 0.5, 0.25, 1.0],
+        specular: [0.0; 3],
+        specular_factor: 5.0,
+        ambient: [0.5; 3],
+        draw_mode: MaterialFlags::empty(),
+        edge_color: [0.0, 0.0, 0.0, 1.0],
+        edge_size: 1.0,
+        texture_index: -1,
+        sphere_mode: None,
+        toon_mode: ToonMode::Common(3),
+        memo: String::new(),
+        num_face_vertices: 3,
+    }]);
+    writer.write_to_vec().unwrap()
+}
+
+unsafe fn string(ptr: *const u8, len: usize) -> String {
+    String::from_utf8(std::slice::from_raw_parts(ptr, len).to_vec()).unwrap()
+}
+
+#[test]
+fn load_inspect_free() {
+    let buf = triangle();
+    unsafe {
+        let mut handle = ptr::null_mut();
+        assert_eq!(
+            pmxutil_load_from_memory(buf.as_ptr(), buf.len(), &mut handle),
+            PmxError::Ok
+        );
+        assert!(!handle.is_null());
+        assert_eq!(pmxutil_vertex_count(handle), 3);
+        assert_eq!(pmxutil_face_count(handle), 1);
+        assert_eq!(pmxutil_material_count(handle), 1);
+
+        let mut positions = [0.0_f32; 6];
+        assert_eq!(pmxutil_copy_positions(handle, positions.as_mut_ptr(), 2), 2);
+        assert_eq!(positions, [0.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        let mut indices = [0_u32; 3];
+        assert_eq!(pmxutil_copy_indices(handle, indices.as_mut_ptr(), 1), 1);
+        assert_eq!(indices, [0, 1, 2]);
+
+        let mut material = PmxMaterial::default();
+        assert_eq!(pmxutil_material_get(handle, 0, &mut material), PmxError::Ok);
+        assert_eq!(material.diffuse, [1.0, 0.5, 0.25, 1.0]);
+        assert_eq!(material.sphere_texture_index, -1);
+        assert_eq!((material.toon_is_common, material.toon_index), (1, 3));
+        assert_eq!(
+            pmxutil_material_get(handle, 1, &mut material),
+            PmxError::OutOfRange
+        );
+
+        let mut len = 0;
+        let name = pmxutil_model_name(handle, &mut len);
+        assert_eq!(string(name, len), "三角形");
+        let name = pmxutil_material_name(handle, 0, &mut len);
+        assert_eq!(string(name, len), "material");
+        assert!(pmxutil_material_name(handle, 1, &mut len).is_null());
+        assert_eq!(len, 0);
+
+        pmxutil_free(handle);
+    }
+}
+
+#[test]
+fn invalid_input() {
+    let buf = triangle();
+    unsafe {
+        let mut handle = ptr::null_mut();
+        assert_eq!(
+            pmxutil_load_from_memory(ptr::null(), 0, &mut handle),
+            PmxError::NullPointer
+        );
+        assert_eq!(
+            pmxutil_load_from_memory(b"Pmd\0".as_ptr(), 4, &mut handle),
+            PmxError::InvalidData
+        );
+        let mut pmd = buf.clone();
+        pmd[..4].copy_from_slice(b"Pmd ");
+        assert_eq!(
+            pmxutil_load_from_memory(pmd.as_ptr(), pmd.len(), &mut handle),
+            PmxError::InvalidHeader
+        );
+        // header and model info only. vertices are missing.
+        assert_eq!(
+            pmxutil_load_from_memory(buf.as_ptr(), 60, &mut handle),
+            PmxError::InvalidData
+        );
+        assert!(handle.is_null());
+        assert_eq!(pmxutil_vertex_count(handle), 0);
+        pmxutil_free(handle);
+    }
+}
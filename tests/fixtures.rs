@@ -0,0 +1,500 @@
+//! generate small PMX files in memory for every combination of header settings and read them back.
+//!
+//! nothing binary is kept in the repository. [`fixture`] builds the model and
+//! [`Writer::write_to_vec_with`] writes each variant of it.
+use PMXUtil::reader::ModelInfoStage;
+use PMXUtil::types::{
+    Bone, BoneMorph, ControlPanel, Encode, Face, FlipMorph, Frame, FrameInner, GroupMorph, Header,
+    ImpulseMorph, IndexKinds, IndexKindsConfig, Joint, JointType, Material, MaterialMorph,
+    MaterialMorphFormula, Model, ModelInfo, Morph, MorphKinds, PMXVersion, Rigid, RigidGroups,
+    SoftBody, SoftBodyAeroModel, SoftBodyAnchorRigid, SoftBodyFlags, SoftBodyForm, SphereModeKind,
+    TextureList, ToonMode, UVMorph, Vertex, VertexIndexKinds, VertexMorph, VertexWeight,
+};
+use PMXUtil::validation::{check_geometry, GeometryOptions, Severity};
+use PMXUtil::writer::{WriteOptions, Writer};
+
+/// vertex index 255 needs unsigned 8 bit index.
+const GRID: usize = 16;
+/// bone index 127 is the largest of signed 8 bit index.
+const BONES: usize = 128;
+
+const VERTEX_KINDS: [VertexIndexKinds; 3] = [
+    VertexIndexKinds::U8,
+    VertexIndexKinds::U16,
+    VertexIndexKinds::I32,
+];
+const KINDS: [IndexKinds; 3] = [IndexKinds::I8, IndexKinds::I16, IndexKinds::I32];
+
+fn vertex(i: usize, additional_uv: u8, v21: bool) -> Vertex {
+    let (x, y) = ((i % GRID) as f32, (i / GRID) as f32);
+    let bone = (i % BONES) as i32;
+    let next = ((i + 1) % BONES) as i32;
+    let weight_type = match i % 5 {
+        0 => VertexWeight::BDEF1(bone),
+        1 => VertexWeight::BDEF2 {
+            bone_index_1: bone,
+            bone_index_2: next,
+            bone_weight_1: 0.25,
+        },
+        2 => VertexWeight::SDEF {
+            bone_index_1: bone,
+            bone_index_2: next,
+            bone_weight_1: 0.5,
+            sdef_c: [x, y, 0.0],
+            sdef_r0: [x, y - 0.5, 0.0],
+            sdef_r1: [x, y + 0.5, 0.0],
+        },
+        3 if v21 => VertexWeight::QDEF {
+            bone_index_1: bone,
+            bone_index_2: next,
+            bone_index_3: 0,
+            bone_index_4: -1,
+            bone_weight_1: 0.5,
+            bone_weight_2: 0.25,
+            bone_weight_3: 0.25,
+            bone_weight_4: 0.0,
+        },
+        _ => VertexWeight::BDEF4 {
+            bone_index_1: bone,
+            bone_index_2: next,
+            bone_index_3: 0,
+            bone_index_4: -1,
+            bone_weight_1: 0.5,
+            bone_weight_2: 0.25,
+            bone_weight_3: 0.25,
+            bone_weight_4: 0.0,
+        },
+    };
+    let mut add_uv = [[0.0; 4]; 4];
+    for (channel, uv) in add_uv.iter_mut().enumerate().take(additional_uv as usize) {
+        *uv = [x, y, channel as f32, 1.0];
+    }
+    Vertex {
+        position: [x, y, 0.0],
+        norm: [0.0, 0.0, -1.0],
+        uv: [x / GRID as f32, y / GRID as f32],
+        add_uv,
+        weight_type,
+        edge_mag: 1.0,
+    }
+}
+
+fn morph(name: &str, morph_data: MorphKinds) -> Morph {
+    Morph {
+        name: name.to_owned(),
+        english_name: name.to_owned(),
+        control_panel: ControlPanel::BottomRight,
+        morph_data,
+    }
+}
+
+/// grid of `GRID` x `GRID` vertices skinned by a chain of `BONES` bones
+/// with every morph and joint kind `version` and `additional_uv` allow.
+///
+/// names contain UTF-16 surrogate pairs.
+fn fixture(version: PMXVersion, additional_uv: u8) -> Model {
+    let v21 = version == PMXVersion::V21;
+    let vertices = (0..GRID * GRID)
+        .map(|i| vertex(i, additional_uv, v21))
+        .collect();
+    let mut faces = vec![];
+    for y in 0..GRID as u32 - 1 {
+        for x in 0..GRID as u32 - 1 {
+            let i = y * GRID as u32 + x;
+            let j = i + GRID as u32;
+            faces.push(Face::from([i, j, i + 1]));
+            faces.push(Face::from([i + 1, j, j + 1]));
+        }
+    }
+    let half = 3 * faces.len() as i32 / 2;
+    let materials = vec![
+        Material::builder("𠮷野家")
+            .texture(0)
+            .sphere(1, SphereModeKind::Mul)
+            .toon(ToonMode::Separate(2))
+            .num_face_vertices(half)
+            .build(),
+        Material::builder("🎀")
+            .texture(-1)
+            .toon(ToonMode::Common(0))
+            .num_face_vertices(half)
+            .build(),
+    ];
+    let bones = (0..BONES as i32)
+        .map(|i| {
+            let bone = Bone::builder(&format!("骨{}", i)).parent(i - 1).position([
+                i as f32 / 8.0,
+                0.0,
+                0.0,
+            ]);
+            match i {
+                // an IK chain at the end of the chain.
+                127 => bone.ik(
+                    126,
+                    40,
+                    1.0,
+                    vec![
+                        (125, Some(([-3.0, 0.0, 0.0], [-0.01, 0.0, 0.0]))),
+                        (124, None),
+                    ],
+                ),
+                _ if i % 2 == 1 => bone.inherit_rotation(i - 1, 0.5),
+                _ => bone,
+            }
+            .build()
+        })
+        .collect();
+    let uv_morph = vec![UVMorph {
+        index: (GRID * GRID - 1) as u32,
+        offset: [0.1, 0.2, 0.0, 0.0],
+    }];
+    let mut morphs = vec![
+        morph(
+            "頂点",
+            MorphKinds::Vertex(vec![VertexMorph {
+                index: (GRID * GRID - 1) as u32,
+                offset: [0.0, 0.5, 0.0],
+            }]),
+        ),
+        morph("UV", MorphKinds::UV(uv_morph.clone())),
+        morph(
+            "ボーン",
+            MorphKinds::Bone(vec![BoneMorph {
+                index: BONES as i32 - 1,
+                translates: [0.0, 1.0, 0.0],
+                rotates: [0.0, 0.0, 0.0, 1.0],
+            }]),
+        ),
+        morph(
+            "材質",
+            MorphKinds::Material(vec![MaterialMorph {
+                index: -1,
+                formula: MaterialMorphFormula::Additive,
+                diffuse: [0.0, 0.0, 0.0, -1.0],
+                specular: [0.0; 3],
+                specular_factor: 0.0,
+                ambient: [0.0; 3],
+                edge_color: [0.0; 4],
+                edge_size: 0.0,
+                texture_factor: [0.0; 4],
+                sphere_texture_factor: [0.0; 4],
+                toon_texture_factor: [0.0; 4],
+            }]),
+        ),
+        morph(
+            "グループ",
+            MorphKinds::Group(vec![GroupMorph {
+                index: 0,
+                morph_factor: 0.5,
+            }]),
+        ),
+    ];
+    let channels = [
+        MorphKinds::UV1 as fn(Vec<UVMorph>) -> MorphKinds,
+        MorphKinds::UV2,
+        MorphKinds::UV3,
+        MorphKinds::UV4,
+    ];
+    for (channel, kind) in channels.iter().enumerate().take(additional_uv as usize) {
+        morphs.push(morph(
+            &format!("追加UV{}", channel + 1),
+            kind(uv_morph.clone()),
+        ));
+    }
+    if v21 {
+        morphs.push(morph(
+            "フリップ",
+            MorphKinds::Flip(vec![FlipMorph {
+                index: 1,
+                morph_factor: 1.0,
+            }]),
+        ));
+        morphs.push(morph(
+            "インパルス",
+            MorphKinds::Impulse(vec![ImpulseMorph {
+                rigid_index: 1,
+                is_local: false,
+                velocity: [0.0, 1.0, 0.0],
+                torque: [0.0; 3],
+            }]),
+        ));
+    }
+    let frames = vec![
+        Frame {
+            name: "Root".to_owned(),
+            name_en: "Root".to_owned(),
+            is_special: true,
+            inners: vec![FrameInner::Bone(0)],
+        },
+        Frame {
+            name: "表情".to_owned(),
+            name_en: "Exp".to_owned(),
+            is_special: true,
+            inners: (0..morphs.len() as i32).map(FrameInner::Morph).collect(),
+        },
+        Frame {
+            name: "骨".to_owned(),
+            name_en: "bones".to_owned(),
+            is_special: false,
+            inners: (1..BONES as i32).map(FrameInner::Bone).collect(),
+        },
+    ];
+    let rigid_bodies = vec![
+        Rigid::builder("根", 0).build(),
+        Rigid::builder("先", BONES as i32 - 1)
+            .position([BONES as f32 / 8.0, 0.0, 0.0])
+            .build(),
+    ];
+    let mut joint_types = vec![JointType::Spring6DOF {
+        a_rigid_index: 0,
+        b_rigid_index: 1,
+        position: [1.0, 0.0, 0.0],
+        rotation: [0.0; 3],
+        move_limit_down: [0.0; 3],
+        move_limit_up: [0.0; 3],
+        rotation_limit_down: [-0.5; 3],
+        rotation_limit_up: [0.5; 3],
+        spring_const_move: [0.0; 3],
+        spring_const_rotation: [10.0; 3],
+    }];
+    if v21 {
+        joint_types.extend(vec![
+            JointType::SixDof {
+                a_rigid_index: 0,
+                b_rigid_index: 1,
+                position: [1.0, 0.0, 0.0],
+                rotation: [0.0; 3],
+                move_limit_down: [0.0; 3],
+                move_limit_up: [0.0; 3],
+                rotation_limit_down: [-0.5; 3],
+                rotation_limit_up: [0.5; 3],
+            },
+            JointType::P2P {
+                a_rigid_index: 0,
+                b_rigid_index: 1,
+                position: [1.0, 0.0, 0.0],
+                rotation: [0.0; 3],
+            },
+            JointType::ConeTwist {
+                a_rigid_index: 0,
+                b_rigid_index: 1,
+                position: [1.0, 0.0, 0.0],
+                rotation: [0.0; 3],
+                swing_span1: 0.1,
+                swing_span2: 0.2,
+                twist_span: 0.3,
+                softness: 1.0,
+                bias_factor: 0.3,
+                relaxation_factor: 1.0,
+                damping: 0.5,
+                fix_thresh: 0.1,
+                enable_motor: false,
+                max_motor_impulse: 0.0,
+                motor_target_in_constraint_space: [0.0; 3],
+            },
+            JointType::Slider {
+                a_rigid_index: 0,
+                b_rigid_index: 1,
+                position: [1.0, 0.0, 0.0],
+                rotation: [0.0; 3],
+                lower_linear_limit: -1.0,
+                upper_linear_limit: 1.0,
+                lower_angle_limit: 0.0,
+                upper_angle_limit: 0.0,
+                power_linear_motor: true,
+                target_linear_motor_velocity: 1.0,
+                max_linear_motor_force: 2.0,
+                power_angler_motor: false,
+                target_angler_motor_velocity: 0.0,
+                max_angler_motor_force: 0.0,
+            },
+            JointType::Hinge {
+                a_rigid_index: 0,
+                b_rigid_index: 1,
+                position: [1.0, 0.0, 0.0],
+                rotation: [0.0; 3],
+                low: -1.0,
+                high: 1.0,
+                softness: 0.9,
+                bias_factor: 0.3,
+                relaxation_factor: 1.0,
+                enable_motor: true,
+                target_velocity: 1.0,
+                max_motor_impulse: 2.0,
+            },
+        ]);
+    }
+    let joints = joint_types
+        .into_iter()
+        .enumerate()
+        .map(|(i, joint_type)| Joint {
+            name: format!("関節{}", i),
+            name_en: format!("joint{}", i),
+            joint_type,
+        })
+        .collect();
+    let soft_bodies = if v21 {
+        vec![SoftBody {
+            name: "布😀".to_owned(),
+            name_en: "cloth".to_owned(),
+            form: SoftBodyForm::TriMesh,
+            material_index: 1,
+            group: 1,
+            un_collision_group_flag: RigidGroups::single(0),
+            bit_flag: SoftBodyFlags::B_LINK_CREATION,
+            b_link_create_distance: 2,
+            clusters: 0,
+            mass: 1.0,
+            collision_margin: 0.05,
+            aero_model: SoftBodyAeroModel::VPoint,
+            vcf: 1.0,
+            dp: 0.0,
+            dg: 0.0,
+            lf: 0.0,
+            pr: 0.0,
+            vc: 0.0,
+            df: 0.2,
+            mt: 0.0,
+            chr: 1.0,
+            khr: 0.1,
+            shr: 1.0,
+            ahr: 0.7,
+            srhr_cl: 0.1,
+            skhr_cl: 1.0,
+            sshr_cl: 0.5,
+            sr_splt_cl: 0.5,
+            sk_splt_cl: 0.5,
+            ss_splt_cl: 0.5,
+            v_it: 0,
+            p_it: 1,
+            d_it: 0,
+            c_it: 4,
+            lst: 1.0,
+            ast: 1.0,
+            vst: 1.0,
+            anchor_rigid: vec![SoftBodyAnchorRigid {
+                rigid_index: 1,
+                vertex_index: (GRID * GRID - 1) as i32,
+                near_mode: true,
+            }],
+            pin_vertex: vec![0, GRID as i32 - 1],
+        }]
+    } else {
+        vec![]
+    };
+    Model {
+        version,
+        encode: Encode::Utf16Le,
+        additional_uv,
+        model_info: ModelInfo {
+            name: "𩸽のモデル".to_owned(),
+            name_en: "fixture 🎀".to_owned(),
+            comment: "テスト用\r\n生成データ".to_owned(),
+            comment_en: "generated at test time".to_owned(),
+        },
+        vertices,
+        faces,
+        textures: TextureList {
+            textures: vec![
+                "tex\\𠮷.png".to_owned(),
+                "sphere.spa".to_owned(),
+                "toon.bmp".to_owned(),
+            ],
+        },
+        materials,
+        bones,
+        morphs,
+        frames,
+        rigid_bodies,
+        joints,
+        soft_bodies,
+        unknown: Default::default(),
+    }
+}
+
+/// every combination of index kinds of the 6 sections.
+fn index_kinds() -> impl Iterator<Item = IndexKindsConfig> {
+    (0..3_usize.pow(6)).map(|mut i| {
+        let mut next = || {
+            let kind = i % 3;
+            i /= 3;
+            kind
+        };
+        IndexKindsConfig {
+            vertex: VERTEX_KINDS[next()],
+            texture: KINDS[next()],
+            material: KINDS[next()],
+            bone: KINDS[next()],
+            morph: KINDS[next()],
+            rigid_body: KINDS[next()],
+        }
+    })
+}
+
+/// every combination of index kinds once, cycling through versions, encodings and
+/// additional uv counts so that each of them is written with about 90 combinations.
+#[test]
+fn header_matrix() {
+    let mut variants = vec![];
+    for &version in &[PMXVersion::V20, PMXVersion::V21] {
+        for &additional_uv in &[0, 4] {
+            let model = fixture(version, additional_uv);
+            let errors = check_geometry(&model, &GeometryOptions::default())
+                .into_iter()
+                .filter(|(severity, _)| *severity == Severity::Error)
+                .collect::<Vec<_>>();
+            assert!(errors.is_empty(), "{:?}", errors);
+            for &encode in &[Encode::Utf16Le, Encode::UTF8] {
+                let mut writer = Writer::from_model(&model);
+                writer.set_mmd_compat(false);
+                let expected = Model {
+                    encode,
+                    ..model.clone()
+                };
+                variants.push((writer, expected));
+            }
+        }
+    }
+    let mut files = vec![0; variants.len()];
+    for (i, kinds) in index_kinds().enumerate() {
+        let variant = i % variants.len();
+        let (writer, expected) = &mut variants[variant];
+        let options = WriteOptions::default()
+            .encode(expected.encode)
+            .version(expected.version)
+            .index_kinds(kinds);
+        let buf = writer.write_to_vec_with(&options).unwrap();
+        let header = Header::peek(&buf).unwrap();
+        assert_eq!(header.version, expected.version);
+        assert_eq!(header.encode, expected.encode);
+        assert_eq!(header.additional_uv, expected.additional_uv);
+        assert_eq!(header.index_kinds(), kinds);
+        let read = ModelInfoStage::from_reader(&buf[..])
+            .unwrap()
+            .try_read_model()
+            .unwrap_or_else(|e| panic!("{:?}: {}", options, e));
+        assert!(read == *expected, "{:?}", options);
+        files[variant] += 1;
+    }
+    assert!(files.iter().all(|&files| files >= 729 / 8), "{:?}", files);
+}
+
+/// indices past the range of the smallest kinds fail to write instead of wrapping.
+#[test]
+fn narrow_index_kinds() {
+    let model = fixture(PMXVersion::V21, 0);
+    let mut writer = Writer::from_model(&model);
+    writer.add_vertices(&[model.vertices[0].clone()]);
+    writer.add_faces(&[Face::from([0, 1, GRID as u32 * GRID as u32])]);
+    let narrow = IndexKindsConfig {
+        vertex: VertexIndexKinds::U8,
+        ..Header::for_counts(0, 0, 0, 0, 0, 0).index_kinds()
+    };
+    assert!(writer
+        .write_to_vec_with(&WriteOptions::default().index_kinds(narrow))
+        .is_err());
+    // the smallest kinds fitting the counts are chosen by default.
+    let header = Header::peek(&writer.write_to_vec().unwrap()).unwrap();
+    assert_eq!(header.index_kinds().vertex, VertexIndexKinds::U16);
+    assert_eq!(header.index_kinds().bone, IndexKinds::I16);
+}